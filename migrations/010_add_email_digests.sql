@@ -0,0 +1,20 @@
+-- Outbound email queue drained by the scheduler
+CREATE TABLE IF NOT EXISTS email_outbox (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    to_address VARCHAR(255) NOT NULL,
+    subject VARCHAR(255) NOT NULL,
+    body_html TEXT NOT NULL,
+    status VARCHAR(50) NOT NULL DEFAULT 'queued', -- queued, sent, failed
+    error TEXT,
+    created_at TIMESTAMPTZ DEFAULT NOW(),
+    sent_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_email_outbox_status ON email_outbox(status);
+
+-- Per-user digest preference
+ALTER TABLE users
+    ADD COLUMN IF NOT EXISTS digest_frequency VARCHAR(20) NOT NULL DEFAULT 'off', -- off, daily, weekly
+    ADD COLUMN IF NOT EXISTS last_digest_sent_at TIMESTAMPTZ;
+
+SELECT 'Email outbox and digest preferences added successfully!' as status;
@@ -0,0 +1,23 @@
+-- Track when a deal last moved stage and when its owner was last told it stalled
+ALTER TABLE deals
+    ADD COLUMN IF NOT EXISTS stage_changed_at TIMESTAMPTZ DEFAULT NOW(),
+    ADD COLUMN IF NOT EXISTS stall_notified_at TIMESTAMPTZ;
+
+UPDATE deals SET stage_changed_at = COALESCE(updated_at, created_at) WHERE stage_changed_at IS NULL;
+
+-- Days without activity or stage change before an open deal counts as stalled
+CREATE TABLE IF NOT EXISTS deal_stage_settings (
+    stage VARCHAR(50) PRIMARY KEY,
+    stale_after_days INTEGER NOT NULL CHECK (stale_after_days > 0),
+    updated_at TIMESTAMPTZ DEFAULT NOW()
+);
+
+CREATE TRIGGER update_deal_stage_settings_updated_at BEFORE UPDATE ON deal_stage_settings
+    FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
+
+INSERT INTO deal_stage_settings (stage, stale_after_days) VALUES
+('prospect', 14),
+('negotiation', 7)
+ON CONFLICT (stage) DO NOTHING;
+
+SELECT 'Deal stagnation tracking added successfully!' as status;
@@ -0,0 +1,36 @@
+-- Outcome codes recorded when an activity is completed (call disposition, meeting held, ...)
+CREATE TABLE IF NOT EXISTS activity_outcomes (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    activity_type VARCHAR(50) NOT NULL,
+    code VARCHAR(50) NOT NULL,
+    label VARCHAR(100) NOT NULL,
+    -- Reporting flags: a connected call / a meeting that actually took place
+    is_connect BOOLEAN NOT NULL DEFAULT false,
+    is_held BOOLEAN NOT NULL DEFAULT false,
+    is_active BOOLEAN NOT NULL DEFAULT true,
+    sort_order INTEGER NOT NULL DEFAULT 0,
+    created_at TIMESTAMPTZ DEFAULT NOW(),
+    updated_at TIMESTAMPTZ DEFAULT NOW(),
+    UNIQUE (activity_type, code)
+);
+
+CREATE TRIGGER update_activity_outcomes_updated_at BEFORE UPDATE ON activity_outcomes
+    FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
+
+ALTER TABLE activities ADD COLUMN IF NOT EXISTS outcome_code VARCHAR(50);
+
+CREATE INDEX IF NOT EXISTS idx_activities_outcome ON activities(activity_type, outcome_code);
+
+INSERT INTO activity_outcomes (activity_type, code, label, is_connect, is_held, sort_order) VALUES
+('call', 'connected', 'Connected', true, false, 1),
+('call', 'left_voicemail', 'Left voicemail', false, false, 2),
+('call', 'no_answer', 'No answer', false, false, 3),
+('call', 'wrong_number', 'Wrong number', false, false, 4),
+('meeting', 'held', 'Held', false, true, 1),
+('meeting', 'no_show', 'No-show', false, false, 2),
+('meeting', 'rescheduled', 'Rescheduled', false, false, 3),
+('email', 'replied', 'Replied', true, false, 1),
+('email', 'no_reply', 'No reply', false, false, 2)
+ON CONFLICT (activity_type, code) DO NOTHING;
+
+SELECT 'Activity outcome codes added successfully!' as status;
@@ -0,0 +1,48 @@
+-- Marketing campaigns and the customers/deals they sourced
+CREATE TABLE IF NOT EXISTS campaigns (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    name VARCHAR(255) NOT NULL,
+    channel VARCHAR(50) NOT NULL,
+    cost DECIMAL(15,2) NOT NULL DEFAULT 0,
+    currency VARCHAR(3) DEFAULT 'USD',
+    start_date DATE,
+    end_date DATE,
+    description TEXT,
+    created_by UUID REFERENCES users(id),
+    created_at TIMESTAMPTZ DEFAULT NOW(),
+    updated_at TIMESTAMPTZ DEFAULT NOW()
+);
+
+CREATE TRIGGER update_campaigns_updated_at BEFORE UPDATE ON campaigns
+    FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
+
+ALTER TABLE customers ADD COLUMN IF NOT EXISTS campaign_id UUID REFERENCES campaigns(id) ON DELETE SET NULL;
+ALTER TABLE deals ADD COLUMN IF NOT EXISTS campaign_id UUID REFERENCES campaigns(id) ON DELETE SET NULL;
+
+CREATE INDEX IF NOT EXISTS idx_customers_campaign_id ON customers(campaign_id);
+CREATE INDEX IF NOT EXISTS idx_deals_campaign_id ON deals(campaign_id);
+
+-- Campaign permissions
+UPDATE roles r
+SET permissions = (
+    SELECT jsonb_agg(DISTINCT elem)
+    FROM (
+        SELECT jsonb_array_elements_text(r.permissions) as elem
+        UNION ALL
+        SELECT unnest(ARRAY['campaigns:read', 'campaigns:write'])
+    ) combined
+)
+WHERE r.name IN ('Super Admin', 'Manager');
+
+UPDATE roles r
+SET permissions = (
+    SELECT jsonb_agg(DISTINCT elem)
+    FROM (
+        SELECT jsonb_array_elements_text(r.permissions) as elem
+        UNION ALL
+        SELECT unnest(ARRAY['campaigns:read'])
+    ) combined
+)
+WHERE r.name IN ('Sales Rep', 'Viewer');
+
+SELECT 'Campaign tracking added successfully!' as status;
@@ -0,0 +1,15 @@
+-- Where a customer came from when it arrived through the public lead form
+ALTER TABLE customers
+    ADD COLUMN IF NOT EXISTS lead_source VARCHAR(50),
+    ADD COLUMN IF NOT EXISTS utm_source VARCHAR(255),
+    ADD COLUMN IF NOT EXISTS utm_medium VARCHAR(255),
+    ADD COLUMN IF NOT EXISTS utm_campaign VARCHAR(255),
+    ADD COLUMN IF NOT EXISTS referrer TEXT;
+
+-- utm_campaign value that links incoming leads to a campaign
+ALTER TABLE campaigns ADD COLUMN IF NOT EXISTS utm_campaign VARCHAR(255);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_campaigns_utm_campaign ON campaigns(LOWER(utm_campaign));
+CREATE INDEX IF NOT EXISTS idx_customers_lead_source ON customers(lead_source);
+
+SELECT 'Lead UTM capture added successfully!' as status;
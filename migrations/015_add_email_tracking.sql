@@ -0,0 +1,35 @@
+-- Link outgoing emails to the contact they were sent to
+ALTER TABLE email_outbox
+    ADD COLUMN IF NOT EXISTS contact_id UUID REFERENCES contacts(id) ON DELETE SET NULL;
+
+CREATE INDEX IF NOT EXISTS idx_email_outbox_contact ON email_outbox(contact_id);
+
+-- Consent flag: contacts who opted out get no pixel or wrapped links
+ALTER TABLE contacts
+    ADD COLUMN IF NOT EXISTS email_tracking_opt_out BOOLEAN NOT NULL DEFAULT false;
+
+-- Wrapped links; the redirect only ever targets a stored URL
+CREATE TABLE IF NOT EXISTS email_links (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    email_id UUID NOT NULL REFERENCES email_outbox(id) ON DELETE CASCADE,
+    url TEXT NOT NULL,
+    created_at TIMESTAMPTZ DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_email_links_email ON email_links(email_id);
+
+CREATE TABLE IF NOT EXISTS email_events (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    email_id UUID NOT NULL REFERENCES email_outbox(id) ON DELETE CASCADE,
+    contact_id UUID REFERENCES contacts(id) ON DELETE SET NULL,
+    event_type VARCHAR(20) NOT NULL, -- open, click
+    url TEXT,
+    ip_address VARCHAR(64),
+    user_agent TEXT,
+    created_at TIMESTAMPTZ DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_email_events_email ON email_events(email_id);
+CREATE INDEX IF NOT EXISTS idx_email_events_contact ON email_events(contact_id);
+
+SELECT 'Email open/click tracking added successfully!' as status;
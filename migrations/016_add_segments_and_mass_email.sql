@@ -0,0 +1,48 @@
+-- Saved customer segments; a NULL filter matches every customer
+CREATE TABLE IF NOT EXISTS customer_segments (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    name VARCHAR(255) NOT NULL,
+    description TEXT,
+    status VARCHAR(50),
+    industry VARCHAR(100),
+    campaign_id UUID REFERENCES campaigns(id) ON DELETE SET NULL,
+    lead_source VARCHAR(50),
+    primary_contacts_only BOOLEAN NOT NULL DEFAULT false,
+    created_by UUID REFERENCES users(id),
+    created_at TIMESTAMPTZ DEFAULT NOW(),
+    updated_at TIMESTAMPTZ DEFAULT NOW()
+);
+
+CREATE TRIGGER update_customer_segments_updated_at BEFORE UPDATE ON customer_segments
+    FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
+
+-- Contacts flagged here are never included in mass emails
+ALTER TABLE contacts
+    ADD COLUMN IF NOT EXISTS do_not_contact BOOLEAN NOT NULL DEFAULT false;
+
+CREATE TABLE IF NOT EXISTS mass_emails (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    segment_id UUID REFERENCES customer_segments(id) ON DELETE SET NULL,
+    subject VARCHAR(255) NOT NULL,
+    body_template TEXT NOT NULL,
+    per_minute_limit INTEGER NOT NULL DEFAULT 60 CHECK (per_minute_limit > 0),
+    status VARCHAR(50) NOT NULL DEFAULT 'sending', -- sending, completed, cancelled
+    created_by UUID REFERENCES users(id),
+    created_at TIMESTAMPTZ DEFAULT NOW(),
+    completed_at TIMESTAMPTZ
+);
+
+-- Recipient snapshot taken when the send is started
+CREATE TABLE IF NOT EXISTS mass_email_recipients (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    mass_email_id UUID NOT NULL REFERENCES mass_emails(id) ON DELETE CASCADE,
+    contact_id UUID REFERENCES contacts(id) ON DELETE SET NULL,
+    email_address VARCHAR(255) NOT NULL,
+    status VARCHAR(50) NOT NULL DEFAULT 'pending', -- pending, queued, suppressed
+    email_id UUID REFERENCES email_outbox(id) ON DELETE SET NULL,
+    queued_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_mass_email_recipients_pending ON mass_email_recipients(mass_email_id, status);
+
+SELECT 'Customer segments and mass email added successfully!' as status;
@@ -0,0 +1,13 @@
+-- Deliverability status set by the email provider's bounce/complaint webhook
+ALTER TABLE contacts
+    ADD COLUMN IF NOT EXISTS email_status VARCHAR(20) NOT NULL DEFAULT 'valid', -- valid, invalid, complained
+    ADD COLUMN IF NOT EXISTS email_status_reason TEXT,
+    ADD COLUMN IF NOT EXISTS email_status_at TIMESTAMPTZ;
+
+-- Provider reason recorded with bounce and complaint events
+ALTER TABLE email_events
+    ADD COLUMN IF NOT EXISTS detail TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_contacts_email_lower ON contacts(LOWER(email));
+
+SELECT 'Email bounce and complaint handling added successfully!' as status;
@@ -0,0 +1,14 @@
+-- Daily values of key metrics, one row per metric (and dimension, e.g. deal stage) per day
+CREATE TABLE IF NOT EXISTS metric_snapshots (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    snapshot_date DATE NOT NULL,
+    metric VARCHAR(100) NOT NULL,
+    dimension VARCHAR(100) NOT NULL DEFAULT '',
+    value NUMERIC(18, 2) NOT NULL DEFAULT 0,
+    created_at TIMESTAMPTZ DEFAULT NOW(),
+    UNIQUE (snapshot_date, metric, dimension)
+);
+
+CREATE INDEX IF NOT EXISTS idx_metric_snapshots_metric_date ON metric_snapshots(metric, dimension, snapshot_date);
+
+SELECT 'Metric snapshots table created successfully!' as status;
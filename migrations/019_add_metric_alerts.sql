@@ -0,0 +1,52 @@
+-- Rules that flag a daily snapshot deviating from its trailing average
+CREATE TABLE IF NOT EXISTS metric_alert_rules (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    metric VARCHAR(100) NOT NULL,
+    direction VARCHAR(10) NOT NULL DEFAULT 'either' CHECK (direction IN ('drop', 'spike', 'either')),
+    threshold_percent NUMERIC(7, 2) NOT NULL CHECK (threshold_percent > 0),
+    trailing_days INTEGER NOT NULL DEFAULT 14 CHECK (trailing_days BETWEEN 3 AND 90),
+    -- Averages below this are too small to judge, e.g. a quiet week of 1-2 activities
+    min_baseline NUMERIC(18, 2) NOT NULL DEFAULT 1,
+    is_active BOOLEAN NOT NULL DEFAULT true,
+    created_by UUID REFERENCES users(id),
+    created_at TIMESTAMPTZ DEFAULT NOW(),
+    updated_at TIMESTAMPTZ DEFAULT NOW()
+);
+
+CREATE TRIGGER update_metric_alert_rules_updated_at BEFORE UPDATE ON metric_alert_rules
+    FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
+
+-- Each time a rule fired, with the figures it compared
+CREATE TABLE IF NOT EXISTS metric_alerts (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    rule_id UUID NOT NULL REFERENCES metric_alert_rules(id) ON DELETE CASCADE,
+    snapshot_date DATE NOT NULL,
+    value NUMERIC(18, 2) NOT NULL,
+    baseline NUMERIC(18, 2) NOT NULL,
+    deviation_percent NUMERIC(9, 2) NOT NULL,
+    created_at TIMESTAMPTZ DEFAULT NOW(),
+    UNIQUE (rule_id, snapshot_date)
+);
+
+CREATE INDEX IF NOT EXISTS idx_metric_alerts_created_at ON metric_alerts(created_at);
+
+INSERT INTO metric_alert_rules (metric, direction, threshold_percent, trailing_days, min_baseline)
+SELECT * FROM (VALUES
+    ('activities_logged', 'drop', 50.00, 14, 3.00),
+    ('expense_amount', 'spike', 100.00, 14, 50.00)
+) AS defaults(metric, direction, threshold_percent, trailing_days, min_baseline)
+WHERE NOT EXISTS (SELECT 1 FROM metric_alert_rules);
+
+-- Alert permissions
+UPDATE roles r
+SET permissions = (
+    SELECT jsonb_agg(DISTINCT elem)
+    FROM (
+        SELECT jsonb_array_elements_text(r.permissions) as elem
+        UNION ALL
+        SELECT unnest(ARRAY['alerts:manage'])
+    ) combined
+)
+WHERE r.name IN ('Super Admin', 'Manager');
+
+SELECT 'Metric alerts added successfully!' as status;
@@ -0,0 +1,9 @@
+-- Which home dashboard variant a role's members see
+ALTER TABLE roles
+    ADD COLUMN IF NOT EXISTS dashboard VARCHAR(20) CHECK (dashboard IN ('sales', 'manager', 'warehouse'));
+
+UPDATE roles SET dashboard = 'manager' WHERE dashboard IS NULL AND name IN ('Super Admin', 'Manager', 'Accountant');
+UPDATE roles SET dashboard = 'sales' WHERE dashboard IS NULL AND name = 'Sales Rep';
+UPDATE roles SET dashboard = 'warehouse' WHERE dashboard IS NULL AND name IN ('Inventory', 'Inventory Manager');
+
+SELECT 'Role dashboards added successfully!' as status;
@@ -0,0 +1,6 @@
+-- Reporting line: each user's direct manager
+ALTER TABLE users ADD COLUMN IF NOT EXISTS manager_id UUID REFERENCES users(id) ON DELETE SET NULL;
+
+CREATE INDEX IF NOT EXISTS idx_users_manager_id ON users(manager_id);
+
+SELECT 'User managers added successfully!' as status;
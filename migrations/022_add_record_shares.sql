@@ -0,0 +1,37 @@
+-- Customers and deals shared by their owner with another user or a manager's team
+CREATE TABLE IF NOT EXISTS record_shares (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    record_type VARCHAR(20) NOT NULL CHECK (record_type IN ('customer', 'deal')),
+    record_id UUID NOT NULL,
+    -- Exactly one of: a single user, or a team lead whose whole reporting tree gets access
+    user_id UUID REFERENCES users(id) ON DELETE CASCADE,
+    team_lead_id UUID REFERENCES users(id) ON DELETE CASCADE,
+    access VARCHAR(10) NOT NULL DEFAULT 'read' CHECK (access IN ('read', 'write')),
+    shared_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ DEFAULT NOW(),
+    CHECK ((user_id IS NULL) <> (team_lead_id IS NULL))
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_record_shares_user
+    ON record_shares(record_type, record_id, user_id) WHERE user_id IS NOT NULL;
+CREATE UNIQUE INDEX IF NOT EXISTS idx_record_shares_team
+    ON record_shares(record_type, record_id, team_lead_id) WHERE team_lead_id IS NOT NULL;
+CREATE INDEX IF NOT EXISTS idx_record_shares_record ON record_shares(record_type, record_id);
+
+-- Customers had no owner recorded at creation; deals already do
+CREATE INDEX IF NOT EXISTS idx_customers_created_by ON customers(created_by);
+
+-- Roles that can see customers today keep seeing every record. Roles without
+-- crm:all_records only see what they or their reports own, plus what is shared.
+UPDATE roles r
+SET permissions = (
+    SELECT jsonb_agg(DISTINCT elem)
+    FROM (
+        SELECT jsonb_array_elements_text(r.permissions) as elem
+        UNION ALL
+        SELECT unnest(ARRAY['crm:all_records'])
+    ) combined
+)
+WHERE r.permissions ? 'customers:read';
+
+SELECT 'Record shares added successfully!' as status;
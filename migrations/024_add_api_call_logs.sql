@@ -0,0 +1,24 @@
+-- One row per request made with an API key, for integrators to debug against.
+-- Payloads are truncated before they are stored.
+CREATE TABLE IF NOT EXISTS api_call_logs (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    api_key_id UUID REFERENCES api_keys(id) ON DELETE CASCADE,
+    method VARCHAR(10) NOT NULL,
+    -- Route pattern, e.g. /api/customers/:id/contacts
+    route VARCHAR(255) NOT NULL,
+    -- Path and query string as requested
+    path TEXT NOT NULL,
+    status INTEGER NOT NULL,
+    latency_ms INTEGER NOT NULL,
+    request_body TEXT,
+    response_body TEXT,
+    ip VARCHAR(64),
+    -- Set when the call was re-run from the log by an admin
+    replay_of UUID REFERENCES api_call_logs(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_api_call_logs_created_at ON api_call_logs(created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_api_call_logs_api_key_id ON api_call_logs(api_key_id, created_at DESC);
+
+SELECT 'API call logs added successfully!' as status;
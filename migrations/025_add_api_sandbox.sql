@@ -0,0 +1,22 @@
+-- Sandbox API keys read and write shadow copies of the API-writable tables in
+-- the sandbox schema, so integrators can test without touching real records.
+-- Columns added to public.customers or public.contacts must be added here too.
+ALTER TABLE api_keys ADD COLUMN IF NOT EXISTS is_sandbox BOOLEAN NOT NULL DEFAULT false;
+
+CREATE SCHEMA IF NOT EXISTS sandbox;
+
+CREATE TABLE IF NOT EXISTS sandbox.customers (
+    LIKE public.customers INCLUDING DEFAULTS INCLUDING CONSTRAINTS INCLUDING INDEXES
+);
+
+CREATE TABLE IF NOT EXISTS sandbox.contacts (
+    LIKE public.contacts INCLUDING DEFAULTS INCLUDING CONSTRAINTS INCLUDING INDEXES,
+    FOREIGN KEY (customer_id) REFERENCES sandbox.customers(id) ON DELETE CASCADE
+);
+
+CREATE TRIGGER update_customers_updated_at BEFORE UPDATE ON sandbox.customers
+    FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
+CREATE TRIGGER update_contacts_updated_at BEFORE UPDATE ON sandbox.contacts
+    FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
+
+SELECT 'API sandbox added successfully!' as status;
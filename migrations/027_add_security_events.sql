@@ -0,0 +1,18 @@
+-- Sign-ins and account changes shown on the user's profile. Logins carry the
+-- device cookie so a sign-in from an unrecognized device can be flagged.
+CREATE TABLE IF NOT EXISTS security_events (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    event_type VARCHAR(30) NOT NULL CHECK (event_type IN ('login', 'password_changed', 'roles_changed')),
+    device_id VARCHAR(64),
+    new_device BOOLEAN NOT NULL DEFAULT false,
+    ip_address VARCHAR(64),
+    user_agent TEXT,
+    detail TEXT,
+    created_at TIMESTAMPTZ DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_security_events_user ON security_events(user_id, created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_security_events_device ON security_events(user_id, device_id) WHERE event_type = 'login';
+
+SELECT 'Security events table created successfully!' as status;
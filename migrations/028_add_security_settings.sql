@@ -0,0 +1,17 @@
+-- Organization-wide security settings, kept as a single row
+CREATE TABLE IF NOT EXISTS security_settings (
+    id BOOLEAN PRIMARY KEY DEFAULT true CHECK (id),
+    ip_allowlist_enabled BOOLEAN NOT NULL DEFAULT false,
+    ip_allowlist CIDR[] NOT NULL DEFAULT '{}',
+    -- Requests authenticated with an API key skip the allowlist; keys have their own
+    exempt_api_keys BOOLEAN NOT NULL DEFAULT true,
+    updated_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    updated_at TIMESTAMPTZ DEFAULT NOW()
+);
+
+INSERT INTO security_settings (id) VALUES (true) ON CONFLICT DO NOTHING;
+
+CREATE TRIGGER update_security_settings_updated_at BEFORE UPDATE ON security_settings
+    FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
+
+SELECT 'Security settings table created successfully!' as status;
@@ -0,0 +1,14 @@
+-- Read-only roles (e.g. external auditors) may view whatever their permissions
+-- allow but can't change anything, whatever else they've been granted
+ALTER TABLE roles ADD COLUMN IF NOT EXISTS is_read_only BOOLEAN NOT NULL DEFAULT false;
+
+INSERT INTO roles (name, description, permissions, is_read_only)
+VALUES (
+    'Auditor',
+    'Read-only access for external auditors',
+    '["customers:read", "crm:all_records", "campaigns:read", "inventory:read", "team:read", "expenses:read", "shipping:read"]'::jsonb,
+    true
+)
+ON CONFLICT (name) DO NOTHING;
+
+SELECT 'Read-only roles added successfully!' as status;
@@ -0,0 +1,7 @@
+-- Deal values, purchase/cost prices and other people's expense amounts now need
+-- finance:read. Existing roles keep seeing them; remove it where it isn't wanted.
+UPDATE roles
+SET permissions = permissions || '["finance:read"]'::jsonb
+WHERE NOT permissions ? 'finance:read';
+
+SELECT 'finance:read permission added successfully!' as status;
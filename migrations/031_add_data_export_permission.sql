@@ -0,0 +1,7 @@
+-- Spreadsheet exports now need data:export. Existing roles keep the ability;
+-- remove it from roles that shouldn't take data out of the system.
+UPDATE roles
+SET permissions = permissions || '["data:export"]'::jsonb
+WHERE NOT permissions ? 'data:export';
+
+SELECT 'data:export permission added successfully!' as status;
@@ -0,0 +1,11 @@
+-- Track activity and revocation on sessions so idle and concurrency limits can apply
+ALTER TABLE sessions ADD COLUMN IF NOT EXISTS last_seen_at TIMESTAMPTZ NOT NULL DEFAULT NOW();
+ALTER TABLE sessions ADD COLUMN IF NOT EXISTS revoked_at TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS idx_sessions_active ON sessions(user_id, created_at) WHERE revoked_at IS NULL;
+
+-- NULL leaves the limit off
+ALTER TABLE security_settings ADD COLUMN IF NOT EXISTS session_idle_minutes INTEGER CHECK (session_idle_minutes > 0);
+ALTER TABLE security_settings ADD COLUMN IF NOT EXISTS max_sessions_per_user INTEGER CHECK (max_sessions_per_user > 0);
+
+SELECT 'Session limits added successfully!' as status;
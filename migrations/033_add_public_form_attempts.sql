@@ -0,0 +1,11 @@
+-- Submissions to the public sign-in, registration and lead forms, for per-IP throttling
+CREATE TABLE IF NOT EXISTS public_form_attempts (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    endpoint VARCHAR(50) NOT NULL,
+    ip_address VARCHAR(45) NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_public_form_attempts_lookup ON public_form_attempts(endpoint, ip_address, created_at);
+
+SELECT 'Public form attempts table created successfully!' as status;
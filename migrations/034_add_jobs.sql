@@ -0,0 +1,43 @@
+-- Queue for one-off background work such as mass email sends and imports
+CREATE TABLE IF NOT EXISTS jobs (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    kind VARCHAR(50) NOT NULL,
+    description VARCHAR(255) NOT NULL,
+    payload JSONB NOT NULL DEFAULT '{}',
+    status VARCHAR(20) NOT NULL DEFAULT 'queued' CHECK (status IN ('queued', 'running', 'succeeded', 'failed', 'cancelled')),
+    progress_done INTEGER NOT NULL DEFAULT 0,
+    progress_total INTEGER,
+    retries INTEGER NOT NULL DEFAULT 0,
+    last_error TEXT,
+    -- Long-running jobs work in steps and are requeued for their next step
+    run_after TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    started_at TIMESTAMPTZ,
+    finished_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ DEFAULT NOW(),
+    updated_at TIMESTAMPTZ DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_jobs_queued ON jobs(run_after) WHERE status = 'queued';
+CREATE INDEX IF NOT EXISTS idx_jobs_status ON jobs(status, created_at);
+
+CREATE TRIGGER update_jobs_updated_at BEFORE UPDATE ON jobs
+    FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
+
+CREATE TABLE IF NOT EXISTS job_logs (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    job_id UUID NOT NULL REFERENCES jobs(id) ON DELETE CASCADE,
+    level VARCHAR(10) NOT NULL DEFAULT 'info' CHECK (level IN ('info', 'warning', 'error')),
+    message TEXT NOT NULL,
+    created_at TIMESTAMPTZ DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_job_logs_job_id ON job_logs(job_id, created_at);
+
+-- Sends already in progress continue as jobs
+INSERT INTO jobs (kind, description, payload, created_by)
+SELECT 'mass_email', 'Mass email: ' || LEFT(subject, 200), jsonb_build_object('mass_email_id', id), created_by
+FROM mass_emails
+WHERE status = 'sending';
+
+SELECT 'Background jobs tables created successfully!' as status;
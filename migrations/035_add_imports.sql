@@ -0,0 +1,28 @@
+-- CSV imports run as background jobs; rejected rows are kept for the error report
+CREATE TABLE IF NOT EXISTS imports (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    import_type VARCHAR(50) NOT NULL CHECK (import_type IN ('customers', 'contacts', 'inventory', 'card_transactions')),
+    file_name VARCHAR(255) NOT NULL,
+    content TEXT NOT NULL,
+    total_rows INTEGER NOT NULL DEFAULT 0,
+    imported_rows INTEGER NOT NULL DEFAULT 0,
+    rejected_rows INTEGER NOT NULL DEFAULT 0,
+    job_id UUID REFERENCES jobs(id) ON DELETE SET NULL,
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_imports_created_by ON imports(created_by, created_at);
+
+CREATE TABLE IF NOT EXISTS import_errors (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    import_id UUID NOT NULL REFERENCES imports(id) ON DELETE CASCADE,
+    -- Line in the uploaded file, counting the header as line 1
+    row_number INTEGER NOT NULL,
+    fields JSONB NOT NULL DEFAULT '[]',
+    reason TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_import_errors_import_id ON import_errors(import_id, row_number);
+
+SELECT 'Imports tables created successfully!' as status;
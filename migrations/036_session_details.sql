@@ -0,0 +1,5 @@
+-- Remember where each session signed in from so users can recognize it on /profile/sessions
+ALTER TABLE sessions ADD COLUMN IF NOT EXISTS ip_address TEXT;
+ALTER TABLE sessions ADD COLUMN IF NOT EXISTS user_agent TEXT;
+
+SELECT 'Session details added successfully!' as status;
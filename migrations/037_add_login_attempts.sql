@@ -0,0 +1,29 @@
+-- Record every sign-in attempt so repeated failures can be slowed down and locked out
+CREATE TABLE IF NOT EXISTS login_attempts (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    email VARCHAR(255) NOT NULL,
+    user_id UUID REFERENCES users(id) ON DELETE CASCADE,
+    ip_address TEXT,
+    succeeded BOOLEAN NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_login_attempts_email ON login_attempts(LOWER(email), created_at);
+CREATE INDEX IF NOT EXISTS idx_login_attempts_ip ON login_attempts(ip_address, created_at);
+CREATE INDEX IF NOT EXISTS idx_login_attempts_created_at ON login_attempts(created_at);
+
+-- Consecutive failures since the last successful sign-in or unlock. Automatic
+-- locks carry an expiry; locks set by an admin (locked_by) do not.
+ALTER TABLE users ADD COLUMN IF NOT EXISTS failed_login_count INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE users ADD COLUMN IF NOT EXISTS locked_until TIMESTAMPTZ;
+
+-- NULL threshold turns automatic locking off; NULL duration keeps accounts
+-- locked until an admin unlocks them
+ALTER TABLE security_settings ADD COLUMN IF NOT EXISTS lockout_threshold INTEGER DEFAULT 10 CHECK (lockout_threshold > 0);
+ALTER TABLE security_settings ADD COLUMN IF NOT EXISTS lockout_minutes INTEGER DEFAULT 30 CHECK (lockout_minutes > 0);
+
+ALTER TABLE security_events DROP CONSTRAINT IF EXISTS security_events_event_type_check;
+ALTER TABLE security_events ADD CONSTRAINT security_events_event_type_check
+    CHECK (event_type IN ('login', 'password_changed', 'roles_changed', 'account_locked'));
+
+SELECT 'Login attempts added successfully!' as status;
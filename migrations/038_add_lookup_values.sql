@@ -0,0 +1,80 @@
+-- Admin-managed options for dropdowns that used to be hardcoded in templates
+CREATE TABLE IF NOT EXISTS lookup_values (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    kind VARCHAR(30) NOT NULL CHECK (kind IN ('industry', 'country', 'activity_type', 'currency')),
+    -- What gets stored on records; the label is only for display
+    value VARCHAR(100) NOT NULL,
+    label VARCHAR(255) NOT NULL,
+    sort_order INTEGER NOT NULL DEFAULT 0,
+    is_active BOOLEAN NOT NULL DEFAULT true,
+    created_at TIMESTAMPTZ DEFAULT NOW(),
+    updated_at TIMESTAMPTZ DEFAULT NOW(),
+    UNIQUE (kind, value)
+);
+
+CREATE INDEX IF NOT EXISTS idx_lookup_values_kind ON lookup_values(kind, sort_order);
+
+CREATE TRIGGER update_lookup_values_updated_at BEFORE UPDATE ON lookup_values
+    FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
+
+-- The options the templates offered until now
+INSERT INTO lookup_values (kind, value, label, sort_order) VALUES
+    ('industry', 'Technology', 'Technology', 1),
+    ('industry', 'Healthcare', 'Healthcare', 2),
+    ('industry', 'Finance', 'Finance', 3),
+    ('industry', 'Manufacturing', 'Manufacturing', 4),
+    ('industry', 'Retail', 'Retail', 5),
+    ('industry', 'Education', 'Education', 6),
+    ('industry', 'Real Estate', 'Real Estate', 7),
+    ('industry', 'Other', 'Other', 8),
+    ('activity_type', 'call', 'Call', 1),
+    ('activity_type', 'meeting', 'Meeting', 2),
+    ('activity_type', 'email', 'Email', 3),
+    ('activity_type', 'note', 'Note', 4),
+    ('activity_type', 'task', 'Task', 5),
+    ('currency', 'USD', 'USD - US Dollar', 1),
+    ('currency', 'CAD', 'CAD - Canadian Dollar', 2),
+    ('currency', 'MXN', 'MXN - Mexican Peso', 3),
+    ('country', 'United States', 'United States', 1),
+    ('country', 'Canada', 'Canada', 2),
+    ('country', 'Mexico', 'Mexico', 3),
+    ('country', 'United Kingdom', 'United Kingdom', 4),
+    ('country', 'Ireland', 'Ireland', 5),
+    ('country', 'Germany', 'Germany', 6),
+    ('country', 'France', 'France', 7),
+    ('country', 'Spain', 'Spain', 8),
+    ('country', 'Italy', 'Italy', 9),
+    ('country', 'Netherlands', 'Netherlands', 10),
+    ('country', 'Australia', 'Australia', 11),
+    ('country', 'New Zealand', 'New Zealand', 12),
+    ('country', 'Japan', 'Japan', 13),
+    ('country', 'China', 'China', 14),
+    ('country', 'India', 'India', 15),
+    ('country', 'Brazil', 'Brazil', 16)
+ON CONFLICT (kind, value) DO NOTHING;
+
+-- Values already on records stay selectable
+INSERT INTO lookup_values (kind, value, label, sort_order)
+SELECT DISTINCT 'country', TRIM(country), TRIM(country), 100
+FROM customers
+WHERE country IS NOT NULL AND TRIM(country) <> ''
+ON CONFLICT (kind, value) DO NOTHING;
+
+INSERT INTO lookup_values (kind, value, label, sort_order)
+SELECT DISTINCT 'industry', TRIM(industry), TRIM(industry), 100
+FROM customers
+WHERE industry IS NOT NULL AND TRIM(industry) <> ''
+ON CONFLICT (kind, value) DO NOTHING;
+
+INSERT INTO lookup_values (kind, value, label, sort_order)
+SELECT DISTINCT 'activity_type', activity_type, INITCAP(activity_type), 100
+FROM activities
+ON CONFLICT (kind, value) DO NOTHING;
+
+INSERT INTO lookup_values (kind, value, label, sort_order)
+SELECT DISTINCT 'currency', currency, currency, 100
+FROM deals
+WHERE currency IS NOT NULL
+ON CONFLICT (kind, value) DO NOTHING;
+
+SELECT 'Lookup values added successfully!' as status;
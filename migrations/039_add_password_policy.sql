@@ -0,0 +1,25 @@
+-- Password rules, enforced on registration, admin user creation and password changes.
+-- Character classes are lowercase, uppercase, digits and symbols; NULL history
+-- turns reuse checks off.
+ALTER TABLE security_settings ADD COLUMN IF NOT EXISTS password_min_length INTEGER NOT NULL DEFAULT 8 CHECK (password_min_length BETWEEN 6 AND 72);
+ALTER TABLE security_settings ADD COLUMN IF NOT EXISTS password_required_classes INTEGER NOT NULL DEFAULT 3 CHECK (password_required_classes BETWEEN 0 AND 4);
+ALTER TABLE security_settings ADD COLUMN IF NOT EXISTS password_block_breached BOOLEAN NOT NULL DEFAULT true;
+ALTER TABLE security_settings ADD COLUMN IF NOT EXISTS password_history_count INTEGER DEFAULT 5 CHECK (password_history_count > 0);
+
+-- Previous password hashes, so a user can't go back to one they used recently
+CREATE TABLE IF NOT EXISTS password_history (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    password_hash VARCHAR(255) NOT NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_password_history_user ON password_history(user_id, created_at DESC);
+
+-- Start everyone's history with the password they have now
+INSERT INTO password_history (user_id, password_hash, created_at)
+SELECT u.id, u.password_hash, COALESCE(u.updated_at, NOW())
+FROM users u
+WHERE NOT EXISTS (SELECT 1 FROM password_history ph WHERE ph.user_id = u.id);
+
+SELECT 'Password policy added successfully!' as status;
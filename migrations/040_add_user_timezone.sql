@@ -0,0 +1,5 @@
+-- IANA time zone each user works in; dates they enter and see are converted
+-- to and from it, while the database keeps storing UTC
+ALTER TABLE users ADD COLUMN IF NOT EXISTS timezone VARCHAR(64) NOT NULL DEFAULT 'UTC';
+
+SELECT 'User time zones added successfully!' as status;
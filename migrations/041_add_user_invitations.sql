@@ -0,0 +1,26 @@
+-- Accounts are created by invitation instead of open registration. The
+-- invitee sets their own password from a one-time link; roles and manager
+-- are chosen by the admin up front.
+CREATE TABLE IF NOT EXISTS user_invitations (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    email VARCHAR(255) NOT NULL,
+    first_name VARCHAR(100) NOT NULL DEFAULT '',
+    last_name VARCHAR(100) NOT NULL DEFAULT '',
+    role_ids UUID[] NOT NULL DEFAULT '{}',
+    manager_id UUID REFERENCES users(id) ON DELETE SET NULL,
+    -- SHA-256 of the link token; the token itself is only ever emailed
+    token_hash VARCHAR(64) NOT NULL UNIQUE,
+    invited_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    expires_at TIMESTAMPTZ NOT NULL,
+    accepted_at TIMESTAMPTZ,
+    user_id UUID REFERENCES users(id) ON DELETE SET NULL,
+    revoked_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- At most one outstanding invitation per address
+CREATE UNIQUE INDEX IF NOT EXISTS idx_user_invitations_open_email
+    ON user_invitations(LOWER(email)) WHERE accepted_at IS NULL AND revoked_at IS NULL;
+CREATE INDEX IF NOT EXISTS idx_user_invitations_created_at ON user_invitations(created_at DESC);
+
+SELECT 'User invitations added successfully!' as status;
@@ -0,0 +1,19 @@
+-- First day of the week for "this week"/"last week" report presets
+ALTER TABLE users ADD COLUMN IF NOT EXISTS week_start VARCHAR(10) NOT NULL DEFAULT 'monday'
+    CHECK (week_start IN ('monday', 'sunday', 'saturday'));
+
+-- Organization-wide reporting settings, kept as a single row. Fiscal quarters
+-- count from the fiscal year's first month.
+CREATE TABLE IF NOT EXISTS reporting_settings (
+    id BOOLEAN PRIMARY KEY DEFAULT true CHECK (id),
+    fiscal_year_start_month INTEGER NOT NULL DEFAULT 1 CHECK (fiscal_year_start_month BETWEEN 1 AND 12),
+    updated_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    updated_at TIMESTAMPTZ DEFAULT NOW()
+);
+
+INSERT INTO reporting_settings (id) VALUES (true) ON CONFLICT DO NOTHING;
+
+CREATE TRIGGER update_reporting_settings_updated_at BEFORE UPDATE ON reporting_settings
+    FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
+
+SELECT 'Reporting periods added successfully!' as status;
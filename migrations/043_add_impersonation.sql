@@ -0,0 +1,15 @@
+-- "Login as": an administrator borrows another user's identity through a
+-- session of its own. The administrator's session is resumed when it ends.
+ALTER TABLE sessions ADD COLUMN IF NOT EXISTS impersonator_id UUID REFERENCES users(id) ON DELETE CASCADE;
+ALTER TABLE sessions ADD COLUMN IF NOT EXISTS return_session_id UUID REFERENCES sessions(id) ON DELETE CASCADE;
+
+-- The administrator actually behind changes made while impersonating
+ALTER TABLE audit_logs ADD COLUMN IF NOT EXISTS impersonator_id UUID REFERENCES users(id) ON DELETE SET NULL;
+
+CREATE INDEX IF NOT EXISTS idx_audit_logs_impersonator_id ON audit_logs(impersonator_id) WHERE impersonator_id IS NOT NULL;
+
+ALTER TABLE security_events DROP CONSTRAINT IF EXISTS security_events_event_type_check;
+ALTER TABLE security_events ADD CONSTRAINT security_events_event_type_check
+    CHECK (event_type IN ('login', 'password_changed', 'roles_changed', 'account_locked', 'impersonated'));
+
+SELECT 'Impersonation added successfully!' as status;
@@ -0,0 +1,8 @@
+-- Users can be imported in bulk, either as invitations or as accounts created
+-- up front. Accounts created up front have an empty password hash until their
+-- invitation is accepted, and user_invitations.user_id points at them.
+ALTER TABLE imports DROP CONSTRAINT IF EXISTS imports_import_type_check;
+ALTER TABLE imports ADD CONSTRAINT imports_import_type_check
+    CHECK (import_type IN ('customers', 'contacts', 'inventory', 'card_transactions', 'user_invitations', 'users'));
+
+SELECT 'User imports added successfully!' as status;
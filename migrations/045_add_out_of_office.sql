@@ -0,0 +1,18 @@
+-- Out-of-office periods, and who covers for each user while they're away.
+-- New deal assignments to someone who is away go to their backup.
+ALTER TABLE users ADD COLUMN IF NOT EXISTS backup_user_id UUID REFERENCES users(id) ON DELETE SET NULL;
+
+CREATE TABLE IF NOT EXISTS out_of_office (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    -- Both days inclusive
+    starts_on DATE NOT NULL,
+    ends_on DATE NOT NULL,
+    note VARCHAR(255),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CHECK (ends_on >= starts_on)
+);
+
+CREATE INDEX IF NOT EXISTS idx_out_of_office_user ON out_of_office(user_id, ends_on);
+
+SELECT 'Out of office added successfully!' as status;
@@ -0,0 +1,10 @@
+-- Each user's email signature (sanitized HTML) and reply-to address, applied
+-- to email Allo sends on their behalf. The outbox keeps the sender's name and
+-- reply-to as they were when the email was queued.
+ALTER TABLE users ADD COLUMN IF NOT EXISTS email_signature TEXT;
+ALTER TABLE users ADD COLUMN IF NOT EXISTS reply_to VARCHAR(255);
+
+ALTER TABLE email_outbox ADD COLUMN IF NOT EXISTS sender_name VARCHAR(255);
+ALTER TABLE email_outbox ADD COLUMN IF NOT EXISTS reply_to VARCHAR(255);
+
+SELECT 'Email signatures added successfully!' as status;
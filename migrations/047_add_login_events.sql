@@ -0,0 +1,30 @@
+-- Every sign-in attempt, successful or not, kept as the user's login history.
+-- login_attempts only covers the last few weeks for throttling. Failures for
+-- addresses that match no account have no user.
+CREATE TABLE IF NOT EXISTS login_events (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    user_id UUID REFERENCES users(id) ON DELETE CASCADE,
+    email VARCHAR(255) NOT NULL,
+    succeeded BOOLEAN NOT NULL,
+    failure_reason VARCHAR(30) CHECK (failure_reason IN ('invalid_credentials', 'locked', 'throttled')),
+    device_id VARCHAR(64),
+    -- A device with no earlier successful sign-in for this user
+    new_device BOOLEAN NOT NULL DEFAULT false,
+    ip_address VARCHAR(64),
+    user_agent TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    CHECK (succeeded = (failure_reason IS NULL))
+);
+
+CREATE INDEX IF NOT EXISTS idx_login_events_user ON login_events(user_id, created_at DESC);
+CREATE INDEX IF NOT EXISTS idx_login_events_device ON login_events(user_id, device_id) WHERE succeeded;
+
+-- Start the history with the sign-ins already recorded as security events
+INSERT INTO login_events (user_id, email, succeeded, device_id, new_device, ip_address, user_agent, created_at)
+SELECT se.user_id, u.email, true, se.device_id, se.new_device, se.ip_address, se.user_agent, COALESCE(se.created_at, NOW())
+FROM security_events se
+JOIN users u ON u.id = se.user_id
+WHERE se.event_type = 'login'
+  AND NOT EXISTS (SELECT 1 FROM login_events);
+
+SELECT 'Login events added successfully!' as status;
@@ -0,0 +1,48 @@
+-- Products and services quoted on a deal, each with an optional discount
+CREATE TABLE IF NOT EXISTS deal_line_items (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    deal_id UUID NOT NULL REFERENCES deals(id) ON DELETE CASCADE,
+    item_id UUID REFERENCES inventory_items(id) ON DELETE SET NULL,
+    description VARCHAR(255) NOT NULL,
+    quantity NUMERIC(12, 2) NOT NULL CHECK (quantity > 0),
+    unit_price NUMERIC(15, 2) NOT NULL CHECK (unit_price >= 0),
+    discount_percent NUMERIC(5, 2) NOT NULL DEFAULT 0 CHECK (discount_percent BETWEEN 0 AND 100),
+    -- 'none' when the discount is within every threshold
+    discount_status VARCHAR(20) NOT NULL DEFAULT 'none'
+        CHECK (discount_status IN ('none', 'pending', 'approved', 'denied')),
+    discount_decided_by UUID REFERENCES users(id),
+    discount_decided_at TIMESTAMPTZ,
+    created_by UUID REFERENCES users(id),
+    created_at TIMESTAMPTZ DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_deal_line_items_deal ON deal_line_items(deal_id);
+CREATE INDEX IF NOT EXISTS idx_deal_line_items_pending ON deal_line_items(created_at) WHERE discount_status = 'pending';
+
+-- Discounts above a threshold need approval. The highest threshold crossed
+-- decides who may give it: the requester's manager, or only holders of
+-- discounts:approve.
+CREATE TABLE IF NOT EXISTS discount_thresholds (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    above_percent NUMERIC(5, 2) NOT NULL UNIQUE CHECK (above_percent >= 0 AND above_percent < 100),
+    approver VARCHAR(20) NOT NULL CHECK (approver IN ('manager', 'approver')),
+    created_at TIMESTAMPTZ DEFAULT NOW()
+);
+
+INSERT INTO discount_thresholds (above_percent, approver)
+SELECT * FROM (VALUES (15.00, 'manager'), (30.00, 'approver')) AS defaults(above_percent, approver)
+WHERE NOT EXISTS (SELECT 1 FROM discount_thresholds);
+
+-- Discount permissions
+UPDATE roles r
+SET permissions = (
+    SELECT jsonb_agg(DISTINCT elem)
+    FROM (
+        SELECT jsonb_array_elements_text(r.permissions) as elem
+        UNION ALL
+        SELECT unnest(ARRAY['discounts:approve'])
+    ) combined
+)
+WHERE r.name IN ('Super Admin', 'Manager');
+
+SELECT 'Deal line items added successfully!' as status;
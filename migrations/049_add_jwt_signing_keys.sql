@@ -0,0 +1,16 @@
+-- Secrets for signing session tokens, identified by the token's kid header.
+-- The newest key without retires_at signs; older ones keep verifying until
+-- they retire so nobody is signed out by a rotation. Until the first rotation
+-- tokens are signed with JWT_SECRET and carry no kid.
+CREATE TABLE IF NOT EXISTS jwt_signing_keys (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    kid VARCHAR(32) NOT NULL UNIQUE,
+    secret VARCHAR(128) NOT NULL,
+    retires_at TIMESTAMPTZ,
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_jwt_signing_keys_active ON jwt_signing_keys(created_at DESC) WHERE retires_at IS NULL;
+
+SELECT 'JWT signing keys added successfully!' as status;
@@ -0,0 +1,27 @@
+-- What each inventory item actually sold for, per customer: the net price of
+-- every line on a won deal, taken when the deal is won
+CREATE TABLE IF NOT EXISTS item_price_history (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    item_id UUID NOT NULL REFERENCES inventory_items(id) ON DELETE CASCADE,
+    customer_id UUID NOT NULL REFERENCES customers(id) ON DELETE CASCADE,
+    deal_id UUID NOT NULL REFERENCES deals(id) ON DELETE CASCADE,
+    line_item_id UUID NOT NULL UNIQUE REFERENCES deal_line_items(id) ON DELETE CASCADE,
+    quantity NUMERIC(12, 2) NOT NULL,
+    -- After the line's discount
+    unit_price NUMERIC(15, 2) NOT NULL,
+    currency VARCHAR(3) NOT NULL,
+    sold_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_item_price_history_item_customer ON item_price_history(item_id, customer_id, sold_at DESC);
+
+INSERT INTO item_price_history (item_id, customer_id, deal_id, line_item_id, quantity, unit_price, currency, sold_at)
+SELECT li.item_id, d.customer_id, d.id, li.id, li.quantity,
+       ROUND(li.unit_price * (100 - li.discount_percent) / 100, 2), d.currency,
+       COALESCE(d.stage_changed_at, d.updated_at)
+FROM deal_line_items li
+JOIN deals d ON d.id = li.deal_id
+WHERE d.stage = 'closed_won' AND li.item_id IS NOT NULL
+ON CONFLICT (line_item_id) DO NOTHING;
+
+SELECT 'Item price history added successfully!' as status;
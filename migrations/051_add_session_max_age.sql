@@ -0,0 +1,6 @@
+-- How long a sign-in lasts at most, however active it is. Sessions and their
+-- tokens expire this long after sign-in and the user has to sign in again.
+ALTER TABLE security_settings ADD COLUMN IF NOT EXISTS session_max_hours INTEGER NOT NULL DEFAULT 24
+    CHECK (session_max_hours BETWEEN 1 AND 2160);
+
+SELECT 'Session max age added successfully!' as status;
@@ -0,0 +1,8 @@
+-- SKUs compare without regard to case and are stored upper-cased; UPC/EAN
+-- codes are stored as digits and identify a single item
+UPDATE inventory_items SET sku = UPPER(TRIM(sku)) WHERE sku <> UPPER(TRIM(sku));
+
+CREATE UNIQUE INDEX IF NOT EXISTS inventory_items_sku_upper_key ON inventory_items (UPPER(sku));
+CREATE UNIQUE INDEX IF NOT EXISTS inventory_items_upc_key ON inventory_items (upc) WHERE upc IS NOT NULL AND upc <> '';
+
+SELECT 'Item code uniqueness added successfully!' as status;
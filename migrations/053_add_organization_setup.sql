@@ -0,0 +1,20 @@
+-- The organization this installation belongs to, kept as a single row. It is
+-- created by the first-run setup, so its absence means setup hasn't happened.
+CREATE TABLE IF NOT EXISTS organization (
+    id BOOLEAN PRIMARY KEY DEFAULT true CHECK (id),
+    name VARCHAR(255) NOT NULL,
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ DEFAULT NOW(),
+    updated_at TIMESTAMPTZ DEFAULT NOW()
+);
+
+CREATE TRIGGER update_organization_updated_at BEFORE UPDATE ON organization
+    FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
+
+-- Installations that already have users were set up by hand before this existed
+INSERT INTO organization (name, created_by)
+SELECT 'Allo', (SELECT id FROM users ORDER BY created_at LIMIT 1)
+WHERE EXISTS (SELECT 1 FROM users)
+ON CONFLICT DO NOTHING;
+
+SELECT 'Organization setup table created successfully!' as status;
@@ -0,0 +1,22 @@
+-- Variants (a size or color of something) are inventory items of their own,
+-- with their own SKU and stock, pointing at the item they're a variant of
+ALTER TABLE inventory_items ADD COLUMN IF NOT EXISTS parent_item_id UUID REFERENCES inventory_items(id) ON DELETE CASCADE;
+-- The variant's value for each of the parent's option sets, in the sets' order:
+-- [{"name": "Size", "value": "M"}, {"name": "Color", "value": "Red"}]
+ALTER TABLE inventory_items ADD COLUMN IF NOT EXISTS variant_options JSONB;
+
+CREATE INDEX IF NOT EXISTS idx_inventory_items_parent ON inventory_items(parent_item_id);
+CREATE UNIQUE INDEX IF NOT EXISTS inventory_items_variant_key
+    ON inventory_items(parent_item_id, variant_options) WHERE parent_item_id IS NOT NULL;
+
+-- The options a parent item's variants are generated from
+CREATE TABLE IF NOT EXISTS item_option_sets (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    item_id UUID NOT NULL REFERENCES inventory_items(id) ON DELETE CASCADE,
+    name VARCHAR(50) NOT NULL,
+    option_values TEXT[] NOT NULL,
+    position INTEGER NOT NULL DEFAULT 0,
+    UNIQUE (item_id, name)
+);
+
+SELECT 'Item variants added successfully!' as status;
@@ -0,0 +1,20 @@
+-- A customer's own numbers for our items, so orders can be taken and documents
+-- printed in their numbering. An item can have several for one customer.
+CREATE TABLE IF NOT EXISTS customer_part_numbers (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    customer_id UUID NOT NULL REFERENCES customers(id) ON DELETE CASCADE,
+    item_id UUID NOT NULL REFERENCES inventory_items(id) ON DELETE CASCADE,
+    part_number VARCHAR(100) NOT NULL,
+    description TEXT,
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ DEFAULT NOW()
+);
+
+-- Matched like SKUs, without regard to case
+CREATE UNIQUE INDEX IF NOT EXISTS customer_part_numbers_key ON customer_part_numbers(customer_id, UPPER(part_number));
+CREATE INDEX IF NOT EXISTS idx_customer_part_numbers_item ON customer_part_numbers(item_id);
+
+-- The customer's number as it was when the line was added, for printing
+ALTER TABLE deal_line_items ADD COLUMN IF NOT EXISTS customer_part_number VARCHAR(100);
+
+SELECT 'Customer part numbers added successfully!' as status;
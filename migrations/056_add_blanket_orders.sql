@@ -0,0 +1,57 @@
+-- Long-running agreements to supply a customer over a period, called off in
+-- scheduled releases
+CREATE TABLE IF NOT EXISTS blanket_orders (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    customer_id UUID NOT NULL REFERENCES customers(id) ON DELETE CASCADE,
+    -- The customer's PO or contract number
+    reference VARCHAR(100) NOT NULL,
+    status VARCHAR(20) NOT NULL DEFAULT 'open' CHECK (status IN ('open', 'closed', 'cancelled')),
+    starts_on DATE NOT NULL,
+    ends_on DATE NOT NULL CHECK (ends_on >= starts_on),
+    -- Stock is reserved for a release this many days before it's due
+    reserve_days_ahead INTEGER NOT NULL DEFAULT 7 CHECK (reserve_days_ahead BETWEEN 0 AND 90),
+    currency VARCHAR(3) NOT NULL DEFAULT 'USD',
+    notes TEXT,
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ DEFAULT NOW(),
+    updated_at TIMESTAMPTZ DEFAULT NOW()
+);
+
+CREATE TRIGGER update_blanket_orders_updated_at BEFORE UPDATE ON blanket_orders
+    FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
+
+CREATE INDEX IF NOT EXISTS idx_blanket_orders_customer ON blanket_orders(customer_id);
+
+-- The total agreed for each item over the life of the order
+CREATE TABLE IF NOT EXISTS blanket_order_lines (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    blanket_order_id UUID NOT NULL REFERENCES blanket_orders(id) ON DELETE CASCADE,
+    item_id UUID NOT NULL REFERENCES inventory_items(id),
+    quantity INTEGER NOT NULL CHECK (quantity > 0),
+    unit_price NUMERIC(12, 2) NOT NULL CHECK (unit_price >= 0),
+    customer_part_number VARCHAR(100),
+    created_at TIMESTAMPTZ DEFAULT NOW(),
+    UNIQUE (blanket_order_id, item_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_blanket_order_lines_item ON blanket_order_lines(item_id);
+
+-- One call-off against a line. Scheduled releases hold no stock; reserved ones
+-- are counted in the warehouse's committed quantity until shipped or cancelled.
+CREATE TABLE IF NOT EXISTS blanket_order_releases (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    line_id UUID NOT NULL REFERENCES blanket_order_lines(id) ON DELETE CASCADE,
+    warehouse_id UUID NOT NULL REFERENCES warehouses(id),
+    quantity INTEGER NOT NULL CHECK (quantity > 0),
+    scheduled_for DATE NOT NULL,
+    status VARCHAR(20) NOT NULL DEFAULT 'scheduled' CHECK (status IN ('scheduled', 'reserved', 'shipped', 'cancelled')),
+    reserved_at TIMESTAMPTZ,
+    shipped_at TIMESTAMPTZ,
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_blanket_order_releases_line ON blanket_order_releases(line_id);
+CREATE INDEX IF NOT EXISTS idx_blanket_order_releases_due ON blanket_order_releases(scheduled_for) WHERE status = 'scheduled';
+
+SELECT 'Blanket orders added successfully!' as status;
@@ -0,0 +1,27 @@
+-- Counters for the numbers printed on documents. Each row is locked while a
+-- number is taken, so concurrent documents never share one.
+CREATE TABLE IF NOT EXISTS number_sequences (
+    document_type VARCHAR(30) PRIMARY KEY,
+    prefix VARCHAR(20) NOT NULL DEFAULT '',
+    padding INTEGER NOT NULL DEFAULT 5 CHECK (padding BETWEEN 1 AND 12),
+    -- When set, the year goes into the number and counting restarts at 1 each January
+    reset_yearly BOOLEAN NOT NULL DEFAULT FALSE,
+    next_number BIGINT NOT NULL DEFAULT 1 CHECK (next_number > 0),
+    -- Year the last number was issued in
+    period_year INTEGER,
+    updated_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    updated_at TIMESTAMPTZ DEFAULT NOW()
+);
+
+INSERT INTO number_sequences (document_type, prefix, reset_yearly) VALUES
+    ('quote', 'Q-', FALSE),
+    ('invoice', 'INV-', TRUE),
+    ('sales_order', 'SO-', FALSE),
+    ('purchase_order', 'PO-', FALSE),
+    ('rma', 'RMA-', FALSE)
+ON CONFLICT (document_type) DO NOTHING;
+
+-- Our own number for a blanket order, alongside the customer's reference
+ALTER TABLE blanket_orders ADD COLUMN IF NOT EXISTS order_number VARCHAR(50) UNIQUE;
+
+SELECT 'Number sequences added successfully!' as status;
@@ -0,0 +1,26 @@
+-- How each kind of generated document is laid out: the heading, where the logo
+-- sits, the text blocks around the lines and which line columns are printed
+CREATE TABLE IF NOT EXISTS document_templates (
+    document_type VARCHAR(30) PRIMARY KEY,
+    title VARCHAR(100) NOT NULL,
+    -- An http(s) URL or a data:image URI
+    logo_url TEXT,
+    logo_position VARCHAR(10) NOT NULL DEFAULT 'left'
+        CHECK (logo_position IN ('left', 'center', 'right', 'none')),
+    header_text TEXT,
+    terms_text TEXT,
+    footer_text TEXT,
+    columns TEXT[] NOT NULL DEFAULT ARRAY['sku', 'description', 'quantity', 'unit_price', 'line_total'],
+    updated_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    updated_at TIMESTAMPTZ DEFAULT NOW()
+);
+
+INSERT INTO document_templates (document_type, title, terms_text) VALUES
+    ('quote', 'Quotation', 'Prices are valid for 30 days from the date above.'),
+    ('invoice', 'Invoice', 'Payment is due within 30 days of the invoice date.'),
+    ('sales_order', 'Sales Order', NULL),
+    ('purchase_order', 'Purchase Order', NULL),
+    ('rma', 'Return Authorization', NULL)
+ON CONFLICT (document_type) DO NOTHING;
+
+SELECT 'Document templates added successfully!' as status;
@@ -0,0 +1,4 @@
+-- Language the customer's documents are printed in; NULL prints them in English
+ALTER TABLE customers ADD COLUMN IF NOT EXISTS preferred_language VARCHAR(10);
+
+SELECT 'Customer language added successfully!' as status;
@@ -0,0 +1,27 @@
+-- Amounts are reported in the organization's base currency
+ALTER TABLE organization ADD COLUMN IF NOT EXISTS base_currency VARCHAR(3) NOT NULL DEFAULT 'USD';
+
+-- What one unit of each other currency is worth in the base currency today
+CREATE TABLE IF NOT EXISTS exchange_rates (
+    currency VARCHAR(3) PRIMARY KEY,
+    rate NUMERIC(18, 8) NOT NULL CHECK (rate > 0),
+    updated_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    updated_at TIMESTAMPTZ DEFAULT NOW()
+);
+
+-- The rate in force when the deal or order was made, so later rate changes
+-- don't move its totals. NULL when no rate was set for its currency then.
+ALTER TABLE deals ADD COLUMN IF NOT EXISTS exchange_rate NUMERIC(18, 8);
+ALTER TABLE blanket_orders ADD COLUMN IF NOT EXISTS exchange_rate NUMERIC(18, 8);
+
+UPDATE deals SET exchange_rate = 1
+WHERE exchange_rate IS NULL AND currency = (SELECT base_currency FROM organization LIMIT 1);
+UPDATE blanket_orders SET exchange_rate = 1
+WHERE exchange_rate IS NULL AND currency = (SELECT base_currency FROM organization LIMIT 1);
+
+-- The deal's value in the base currency, for reports. Without a locked rate
+-- the value is counted as it is.
+ALTER TABLE deals ADD COLUMN IF NOT EXISTS base_value NUMERIC(15, 2)
+    GENERATED ALWAYS AS (value * COALESCE(exchange_rate, 1)) STORED;
+
+SELECT 'Exchange rates added successfully!' as status;
@@ -0,0 +1,24 @@
+-- Departments spend is booked against. The code is what the accounting
+-- system knows the department by and is carried into exports.
+CREATE TABLE IF NOT EXISTS cost_centers (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    code VARCHAR(30) NOT NULL UNIQUE,
+    name VARCHAR(100) NOT NULL,
+    is_active BOOLEAN NOT NULL DEFAULT TRUE,
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+INSERT INTO cost_centers (code, name) VALUES ('GEN', 'General')
+ON CONFLICT (code) DO NOTHING;
+
+-- Every expense belongs to one; existing ones go to General
+ALTER TABLE expenses ADD COLUMN IF NOT EXISTS cost_center_id UUID REFERENCES cost_centers(id);
+UPDATE expenses SET cost_center_id = (SELECT id FROM cost_centers WHERE code = 'GEN')
+WHERE cost_center_id IS NULL;
+ALTER TABLE expenses ALTER COLUMN cost_center_id SET NOT NULL;
+
+CREATE INDEX IF NOT EXISTS idx_expenses_cost_center ON expenses(cost_center_id, expense_date);
+
+SELECT 'Cost centers added successfully!' as status;
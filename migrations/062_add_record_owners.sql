@@ -0,0 +1,20 @@
+-- Customers can be handed to someone other than their creator, like deals.
+-- Customers, deals and activities all belong to the assignee, or else the
+-- creator.
+ALTER TABLE customers ADD COLUMN IF NOT EXISTS assigned_to UUID REFERENCES users(id) ON DELETE SET NULL;
+
+-- The sandbox copy has to keep up, including the language added in 059
+ALTER TABLE sandbox.customers ADD COLUMN IF NOT EXISTS assigned_to UUID;
+ALTER TABLE sandbox.customers ADD COLUMN IF NOT EXISTS preferred_language VARCHAR(10);
+
+CREATE INDEX IF NOT EXISTS idx_customers_owner ON customers((COALESCE(assigned_to, created_by)));
+CREATE INDEX IF NOT EXISTS idx_deals_owner ON deals((COALESCE(assigned_to, created_by)));
+CREATE INDEX IF NOT EXISTS idx_activities_owner ON activities((COALESCE(assigned_to, created_by)));
+
+-- Web leads were saved without an owner; give those linked to a campaign to
+-- whoever runs it
+UPDATE customers c SET assigned_to = cp.created_by
+FROM campaigns cp
+WHERE c.campaign_id = cp.id AND c.created_by IS NULL AND c.assigned_to IS NULL;
+
+SELECT 'Record owners added successfully!' as status;
@@ -0,0 +1,50 @@
+-- Projects collect the time and expenses spent delivering work for a
+-- customer. Revenue comes from the won deal the project delivers.
+CREATE TABLE IF NOT EXISTS projects (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    name VARCHAR(200) NOT NULL,
+    customer_id UUID NOT NULL REFERENCES customers(id) ON DELETE CASCADE,
+    deal_id UUID REFERENCES deals(id) ON DELETE SET NULL,
+    status VARCHAR(20) NOT NULL DEFAULT 'active' CHECK (status IN ('active', 'closed')),
+    description TEXT,
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_projects_customer ON projects(customer_id);
+
+-- What an hour of someone's time costs the business. Each time entry keeps
+-- the rate from when it was logged, so changing it doesn't rewrite history.
+ALTER TABLE users ADD COLUMN IF NOT EXISTS hourly_cost NUMERIC(12, 2) CHECK (hourly_cost >= 0);
+
+CREATE TABLE IF NOT EXISTS time_entries (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    project_id UUID NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+    user_id UUID NOT NULL REFERENCES users(id),
+    work_date DATE NOT NULL,
+    hours NUMERIC(5, 2) NOT NULL CHECK (hours > 0 AND hours <= 24),
+    description TEXT,
+    -- NULL when the user had no rate set
+    hourly_cost NUMERIC(12, 2),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_time_entries_project ON time_entries(project_id, work_date);
+
+ALTER TABLE expenses ADD COLUMN IF NOT EXISTS project_id UUID REFERENCES projects(id) ON DELETE SET NULL;
+CREATE INDEX IF NOT EXISTS idx_expenses_project ON expenses(project_id) WHERE project_id IS NOT NULL;
+
+-- Project permissions
+UPDATE roles r
+SET permissions = (
+    SELECT jsonb_agg(DISTINCT elem)
+    FROM (
+        SELECT jsonb_array_elements_text(r.permissions) as elem
+        UNION ALL
+        SELECT unnest(ARRAY['projects:read', 'projects:write'])
+    ) combined
+)
+WHERE r.name IN ('Super Admin', 'Manager');
+
+SELECT 'Projects added successfully!' as status;
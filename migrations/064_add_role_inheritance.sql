@@ -0,0 +1,10 @@
+-- A role can extend another and gets everything the parent grants, on top
+-- of its own permissions. Parents can have parents of their own.
+ALTER TABLE roles ADD COLUMN IF NOT EXISTS parent_role_id UUID REFERENCES roles(id) ON DELETE SET NULL;
+
+ALTER TABLE roles DROP CONSTRAINT IF EXISTS roles_parent_not_self;
+ALTER TABLE roles ADD CONSTRAINT roles_parent_not_self CHECK (parent_role_id <> id);
+
+CREATE INDEX IF NOT EXISTS idx_roles_parent ON roles(parent_role_id) WHERE parent_role_id IS NOT NULL;
+
+SELECT 'Role inheritance added successfully!' as status;
@@ -0,0 +1,17 @@
+-- Browsing the audit log is its own permission so auditors can see it
+-- without being able to manage roles
+UPDATE roles r
+SET permissions = (
+    SELECT jsonb_agg(DISTINCT elem)
+    FROM (
+        SELECT jsonb_array_elements_text(r.permissions) as elem
+        UNION ALL
+        SELECT 'audit:read'
+    ) combined
+)
+WHERE r.name IN ('Super Admin', 'Auditor');
+
+-- Filtering by record lands on one resource's history
+CREATE INDEX IF NOT EXISTS idx_audit_logs_resource ON audit_logs(resource_type, resource_id);
+
+SELECT 'Audit log permission added successfully!' as status;
@@ -0,0 +1,21 @@
+-- What finishing the project is expected to cost. Cost to date against it
+-- gives how complete the project is, which is how much of the deal's value
+-- has been earned.
+ALTER TABLE projects ADD COLUMN IF NOT EXISTS estimated_cost NUMERIC(14, 2) CHECK (estimated_cost >= 0);
+
+-- Invoices raised for a project in the accounting system, in the base
+-- currency. Time and expenses up to billed_through count as billed.
+CREATE TABLE IF NOT EXISTS project_billings (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    project_id UUID NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+    billed_on DATE NOT NULL,
+    billed_through DATE NOT NULL,
+    amount NUMERIC(14, 2) NOT NULL CHECK (amount >= 0),
+    reference VARCHAR(100),
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_project_billings_project ON project_billings(project_id, billed_on);
+
+SELECT 'Project billings added successfully!' as status;
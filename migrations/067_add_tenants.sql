@@ -0,0 +1,152 @@
+-- Organizations sharing one database. Every business table gets a tenant_id
+-- and a row level security policy keeping it to the tenant the connection
+-- was set to (app.tenant_id, see database.rs). A connection with no tenant
+-- set sees and writes nothing.
+--
+-- The app works through the allo_app role: the connecting user is usually
+-- the table owner or a superuser, and those skip row level security. That
+-- lets migrations and the functions below work across tenants.
+--
+-- To add an organization: SELECT create_tenant('Acme Ltd', 'acme'); then
+-- open acme.<host> to run its first-time setup.
+CREATE TABLE IF NOT EXISTS tenants (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    name VARCHAR(255) NOT NULL,
+    -- The subdomain it's reached on
+    slug VARCHAR(63) NOT NULL UNIQUE CHECK (slug ~ '^[a-z][a-z0-9-]*$'),
+    -- Served when the host names no tenant; the data from before tenants
+    is_primary BOOLEAN NOT NULL DEFAULT false,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_tenants_primary ON tenants(is_primary) WHERE is_primary;
+
+INSERT INTO tenants (name, slug, is_primary)
+SELECT COALESCE((SELECT name FROM organization LIMIT 1), 'Default'), 'default', true
+WHERE NOT EXISTS (SELECT 1 FROM tenants);
+
+CREATE OR REPLACE FUNCTION current_tenant_id() RETURNS UUID
+LANGUAGE sql STABLE AS $$
+    SELECT NULLIF(current_setting('app.tenant_id', true), '')::uuid
+$$;
+
+-- Everything but the tenants themselves and the token signing keys, which
+-- are the server's own
+DO $$
+DECLARE
+    primary_tenant UUID := (SELECT id FROM tenants WHERE is_primary);
+    t RECORD;
+BEGIN
+    FOR t IN
+        SELECT table_schema, table_name FROM information_schema.tables
+        WHERE table_type = 'BASE TABLE'
+          AND (table_schema = 'public' AND table_name NOT IN ('tenants', 'jwt_signing_keys')
+               OR table_schema = 'sandbox')
+    LOOP
+        EXECUTE format('ALTER TABLE %I.%I ADD COLUMN IF NOT EXISTS tenant_id UUID REFERENCES tenants(id) ON DELETE CASCADE',
+            t.table_schema, t.table_name);
+        EXECUTE format('UPDATE %I.%I SET tenant_id = $1 WHERE tenant_id IS NULL', t.table_schema, t.table_name)
+            USING primary_tenant;
+        EXECUTE format('ALTER TABLE %I.%I ALTER COLUMN tenant_id SET NOT NULL, ALTER COLUMN tenant_id SET DEFAULT current_tenant_id()',
+            t.table_schema, t.table_name);
+        EXECUTE format('CREATE INDEX IF NOT EXISTS %I ON %I.%I(tenant_id)',
+            'idx_' || t.table_name || '_tenant', t.table_schema, t.table_name);
+        EXECUTE format('ALTER TABLE %I.%I ENABLE ROW LEVEL SECURITY', t.table_schema, t.table_name);
+        EXECUTE format('DROP POLICY IF EXISTS tenant_isolation ON %I.%I', t.table_schema, t.table_name);
+        EXECUTE format('CREATE POLICY tenant_isolation ON %I.%I USING (tenant_id = current_tenant_id())',
+            t.table_schema, t.table_name);
+    END LOOP;
+END $$;
+
+-- Names and codes only have to be unique within an organization. User
+-- emails stay unique across all of them, as do API key and invitation
+-- tokens.
+ALTER TABLE organization DROP CONSTRAINT IF EXISTS organization_pkey, ADD CONSTRAINT organization_pkey PRIMARY KEY (tenant_id);
+ALTER TABLE security_settings DROP CONSTRAINT IF EXISTS security_settings_pkey, ADD CONSTRAINT security_settings_pkey PRIMARY KEY (tenant_id);
+ALTER TABLE reporting_settings DROP CONSTRAINT IF EXISTS reporting_settings_pkey, ADD CONSTRAINT reporting_settings_pkey PRIMARY KEY (tenant_id);
+ALTER TABLE deal_stage_settings DROP CONSTRAINT IF EXISTS deal_stage_settings_pkey, ADD CONSTRAINT deal_stage_settings_pkey PRIMARY KEY (tenant_id, stage);
+ALTER TABLE number_sequences DROP CONSTRAINT IF EXISTS number_sequences_pkey, ADD CONSTRAINT number_sequences_pkey PRIMARY KEY (tenant_id, document_type);
+ALTER TABLE document_templates DROP CONSTRAINT IF EXISTS document_templates_pkey, ADD CONSTRAINT document_templates_pkey PRIMARY KEY (tenant_id, document_type);
+ALTER TABLE exchange_rates DROP CONSTRAINT IF EXISTS exchange_rates_pkey, ADD CONSTRAINT exchange_rates_pkey PRIMARY KEY (tenant_id, currency);
+
+ALTER TABLE roles DROP CONSTRAINT IF EXISTS roles_name_key, ADD CONSTRAINT roles_name_key UNIQUE (tenant_id, name);
+ALTER TABLE expense_categories DROP CONSTRAINT IF EXISTS expense_categories_name_key, ADD CONSTRAINT expense_categories_name_key UNIQUE (tenant_id, name);
+ALTER TABLE inventory_items DROP CONSTRAINT IF EXISTS inventory_items_sku_key, ADD CONSTRAINT inventory_items_sku_key UNIQUE (tenant_id, sku);
+ALTER TABLE activity_outcomes DROP CONSTRAINT IF EXISTS activity_outcomes_activity_type_code_key, ADD CONSTRAINT activity_outcomes_activity_type_code_key UNIQUE (tenant_id, activity_type, code);
+ALTER TABLE metric_snapshots DROP CONSTRAINT IF EXISTS metric_snapshots_snapshot_date_metric_dimension_key, ADD CONSTRAINT metric_snapshots_snapshot_date_metric_dimension_key UNIQUE (tenant_id, snapshot_date, metric, dimension);
+ALTER TABLE lookup_values DROP CONSTRAINT IF EXISTS lookup_values_kind_value_key, ADD CONSTRAINT lookup_values_kind_value_key UNIQUE (tenant_id, kind, value);
+ALTER TABLE discount_thresholds DROP CONSTRAINT IF EXISTS discount_thresholds_above_percent_key, ADD CONSTRAINT discount_thresholds_above_percent_key UNIQUE (tenant_id, above_percent);
+ALTER TABLE blanket_orders DROP CONSTRAINT IF EXISTS blanket_orders_order_number_key, ADD CONSTRAINT blanket_orders_order_number_key UNIQUE (tenant_id, order_number);
+ALTER TABLE cost_centers DROP CONSTRAINT IF EXISTS cost_centers_code_key, ADD CONSTRAINT cost_centers_code_key UNIQUE (tenant_id, code);
+
+DROP INDEX IF EXISTS inventory_items_sku_upper_key;
+CREATE UNIQUE INDEX inventory_items_sku_upper_key ON inventory_items(tenant_id, UPPER(sku));
+DROP INDEX IF EXISTS inventory_items_upc_key;
+CREATE UNIQUE INDEX inventory_items_upc_key ON inventory_items(tenant_id, upc) WHERE upc IS NOT NULL AND upc <> '';
+DROP INDEX IF EXISTS idx_campaigns_utm_campaign;
+CREATE UNIQUE INDEX idx_campaigns_utm_campaign ON campaigns(tenant_id, LOWER(utm_campaign));
+
+-- Which tenant a session belongs to, asked before any tenant is set
+CREATE OR REPLACE FUNCTION session_tenant(session UUID) RETURNS UUID
+LANGUAGE sql STABLE SECURITY DEFINER SET search_path = public AS $$
+    SELECT tenant_id FROM sessions WHERE id = session
+$$;
+
+-- When the last live session of any tenant ends, for retiring token signing
+-- keys, which every tenant shares
+CREATE OR REPLACE FUNCTION last_session_expiry(created_before TIMESTAMPTZ) RETURNS TIMESTAMPTZ
+LANGUAGE sql STABLE SECURITY DEFINER SET search_path = public AS $$
+    SELECT MAX(expires_at) FROM sessions
+    WHERE revoked_at IS NULL AND (created_before IS NULL OR created_at < created_before)
+$$;
+
+-- A new organization gets the settings rows the app expects and the primary
+-- organization's reference lists to start from. Its roles and first admin
+-- come from the setup page.
+CREATE OR REPLACE FUNCTION create_tenant(tenant_name TEXT, tenant_slug TEXT) RETURNS UUID
+LANGUAGE plpgsql AS $$
+DECLARE
+    template UUID := (SELECT id FROM tenants WHERE is_primary);
+    tenant UUID;
+BEGIN
+    INSERT INTO tenants (name, slug) VALUES (tenant_name, LOWER(tenant_slug)) RETURNING id INTO tenant;
+
+    INSERT INTO security_settings (tenant_id) VALUES (tenant);
+    INSERT INTO reporting_settings (tenant_id) VALUES (tenant);
+    INSERT INTO cost_centers (tenant_id, code, name) VALUES (tenant, 'GEN', 'General');
+
+    INSERT INTO deal_stage_settings (tenant_id, stage, stale_after_days)
+    SELECT tenant, stage, stale_after_days FROM deal_stage_settings WHERE tenant_id = template;
+    INSERT INTO activity_outcomes (tenant_id, activity_type, code, label, is_connect, is_held, sort_order)
+    SELECT tenant, activity_type, code, label, is_connect, is_held, sort_order
+    FROM activity_outcomes WHERE tenant_id = template AND is_active;
+    INSERT INTO lookup_values (tenant_id, kind, value, label, sort_order)
+    SELECT tenant, kind, value, label, sort_order FROM lookup_values WHERE tenant_id = template AND is_active;
+    INSERT INTO expense_categories (tenant_id, name, description)
+    SELECT tenant, name, description FROM expense_categories WHERE tenant_id = template AND is_active;
+    INSERT INTO discount_thresholds (tenant_id, above_percent, approver)
+    SELECT tenant, above_percent, approver FROM discount_thresholds WHERE tenant_id = template;
+    INSERT INTO number_sequences (tenant_id, document_type, prefix, padding, reset_yearly)
+    SELECT tenant, document_type, prefix, padding, reset_yearly FROM number_sequences WHERE tenant_id = template;
+    INSERT INTO document_templates (tenant_id, document_type, title, terms_text)
+    SELECT tenant, document_type, title, terms_text FROM document_templates WHERE tenant_id = template;
+
+    RETURN tenant;
+END $$;
+
+DO $$
+BEGIN
+    IF NOT EXISTS (SELECT 1 FROM pg_roles WHERE rolname = 'allo_app') THEN
+        CREATE ROLE allo_app NOLOGIN;
+    END IF;
+END $$;
+
+GRANT allo_app TO CURRENT_USER;
+GRANT USAGE ON SCHEMA public, sandbox TO allo_app;
+GRANT SELECT, INSERT, UPDATE, DELETE ON ALL TABLES IN SCHEMA public, sandbox TO allo_app;
+GRANT USAGE, SELECT, UPDATE ON ALL SEQUENCES IN SCHEMA public TO allo_app;
+-- Tables added by later migrations
+ALTER DEFAULT PRIVILEGES IN SCHEMA public GRANT SELECT, INSERT, UPDATE, DELETE ON TABLES TO allo_app;
+ALTER DEFAULT PRIVILEGES IN SCHEMA public GRANT USAGE, SELECT, UPDATE ON SEQUENCES TO allo_app;
+
+SELECT 'Tenants added successfully!' as status;
@@ -0,0 +1,19 @@
+-- People watching a customer or deal, who get a notification whenever
+-- someone else changes it or logs activity against it
+CREATE TABLE IF NOT EXISTS watchers (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    record_type VARCHAR(20) NOT NULL CHECK (record_type IN ('customer', 'deal')),
+    record_id UUID NOT NULL,
+    user_id UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    tenant_id UUID NOT NULL DEFAULT current_tenant_id() REFERENCES tenants(id) ON DELETE CASCADE,
+    UNIQUE (record_type, record_id, user_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_watchers_tenant ON watchers(tenant_id);
+
+ALTER TABLE watchers ENABLE ROW LEVEL SECURITY;
+DROP POLICY IF EXISTS tenant_isolation ON watchers;
+CREATE POLICY tenant_isolation ON watchers USING (tenant_id = current_tenant_id());
+
+SELECT 'Watchers added successfully!' as status;
@@ -0,0 +1,23 @@
+-- Dashboards users compose from the widgets in services/dashboard.rs. One
+-- with a share token can be opened read-only by anyone holding its link,
+-- who sees the widgets their own permissions allow.
+CREATE TABLE IF NOT EXISTS saved_dashboards (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    name VARCHAR(100) NOT NULL,
+    widgets TEXT[] NOT NULL DEFAULT '{}',
+    share_token VARCHAR(64) UNIQUE,
+    shared_at TIMESTAMPTZ,
+    created_by UUID NOT NULL REFERENCES users(id) ON DELETE CASCADE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    tenant_id UUID NOT NULL DEFAULT current_tenant_id() REFERENCES tenants(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_saved_dashboards_created_by ON saved_dashboards(created_by);
+CREATE INDEX IF NOT EXISTS idx_saved_dashboards_tenant ON saved_dashboards(tenant_id);
+
+ALTER TABLE saved_dashboards ENABLE ROW LEVEL SECURITY;
+DROP POLICY IF EXISTS tenant_isolation ON saved_dashboards;
+CREATE POLICY tenant_isolation ON saved_dashboards USING (tenant_id = current_tenant_id());
+
+SELECT 'Saved dashboards added successfully!' as status;
@@ -0,0 +1,77 @@
+-- Precomputed totals for the summary reports, so they stay fast however many
+-- deals, expenses and stock rows an organization has. The scheduler refreshes
+-- them every few minutes through refresh_reporting_views().
+--
+-- Materialized views ignore row level security, so each keeps every tenant's
+-- rows and the app only gets the reporting_* views over them, which filter to
+-- the current tenant.
+
+CREATE MATERIALIZED VIEW IF NOT EXISTS mv_pipeline_by_stage AS
+SELECT d.tenant_id,
+       d.stage,
+       COUNT(*) as deal_count,
+       COALESCE(SUM(d.base_value), 0) as total_value,
+       NOW() as refreshed_at
+FROM deals d
+GROUP BY d.tenant_id, d.stage;
+
+-- Denied expenses are left out, as in the team roll-up
+CREATE MATERIALIZED VIEW IF NOT EXISTS mv_expenses_by_category_month AS
+SELECT e.tenant_id,
+       e.category_id,
+       ec.name as category_name,
+       DATE_TRUNC('month', e.expense_date)::date as month,
+       COUNT(*) as expense_count,
+       SUM(e.amount) as total_amount,
+       NOW() as refreshed_at
+FROM expenses e
+JOIN expense_categories ec ON ec.id = e.category_id
+WHERE e.status <> 'denied'
+GROUP BY e.tenant_id, e.category_id, ec.name, DATE_TRUNC('month', e.expense_date);
+
+-- On-hand stock valued at average cost, falling back to the cost and
+-- purchase prices, as in the stock_value metric
+CREATE MATERIALIZED VIEW IF NOT EXISTS mv_stock_valuation AS
+SELECT sl.tenant_id,
+       sl.warehouse_id,
+       w.name as warehouse_name,
+       COUNT(DISTINCT sl.item_id) FILTER (WHERE sl.quantity_on_hand > 0) as item_count,
+       COALESCE(SUM(sl.quantity_on_hand), 0) as quantity_on_hand,
+       COALESCE(SUM(sl.quantity_on_hand * COALESCE(i.average_cost, i.cost_price, i.purchase_price, 0)), 0) as total_value,
+       NOW() as refreshed_at
+FROM stock_levels sl
+JOIN warehouses w ON w.id = sl.warehouse_id
+JOIN inventory_items i ON i.id = sl.item_id
+GROUP BY sl.tenant_id, sl.warehouse_id, w.name;
+
+-- Unique indexes let them refresh concurrently, without blocking readers
+CREATE UNIQUE INDEX IF NOT EXISTS idx_mv_pipeline_by_stage ON mv_pipeline_by_stage(tenant_id, stage);
+CREATE UNIQUE INDEX IF NOT EXISTS idx_mv_expenses_by_category_month ON mv_expenses_by_category_month(tenant_id, category_id, month);
+CREATE UNIQUE INDEX IF NOT EXISTS idx_mv_stock_valuation ON mv_stock_valuation(tenant_id, warehouse_id);
+
+CREATE OR REPLACE VIEW reporting_pipeline_by_stage AS
+SELECT * FROM mv_pipeline_by_stage WHERE tenant_id = current_tenant_id();
+
+CREATE OR REPLACE VIEW reporting_expenses_by_category_month AS
+SELECT * FROM mv_expenses_by_category_month WHERE tenant_id = current_tenant_id();
+
+CREATE OR REPLACE VIEW reporting_stock_valuation AS
+SELECT * FROM mv_stock_valuation WHERE tenant_id = current_tenant_id();
+
+REVOKE ALL ON mv_pipeline_by_stage, mv_expenses_by_category_month, mv_stock_valuation FROM allo_app;
+REVOKE ALL ON reporting_pipeline_by_stage, reporting_expenses_by_category_month, reporting_stock_valuation FROM allo_app;
+GRANT SELECT ON reporting_pipeline_by_stage, reporting_expenses_by_category_month, reporting_stock_valuation TO allo_app;
+
+-- Refreshing takes ownership of the views, which the app's role doesn't have
+CREATE OR REPLACE FUNCTION refresh_reporting_views() RETURNS void
+LANGUAGE plpgsql SECURITY DEFINER SET search_path = public AS $$
+BEGIN
+    REFRESH MATERIALIZED VIEW CONCURRENTLY mv_pipeline_by_stage;
+    REFRESH MATERIALIZED VIEW CONCURRENTLY mv_expenses_by_category_month;
+    REFRESH MATERIALIZED VIEW CONCURRENTLY mv_stock_valuation;
+END $$;
+
+REVOKE ALL ON FUNCTION refresh_reporting_views() FROM PUBLIC;
+GRANT EXECUTE ON FUNCTION refresh_reporting_views() TO allo_app;
+
+SELECT 'Reporting views added successfully!' as status;
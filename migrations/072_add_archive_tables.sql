@@ -0,0 +1,61 @@
+-- Old activities and audit entries move to archive tables, keeping the live
+-- tables small for the day-to-day screens. The scheduler moves them (see
+-- services/archive.rs); reports over a long enough range read the
+-- *_with_archive views, which add the archived rows back in.
+--
+-- The archives copy the live tables column for column, so a column added to
+-- activities or audit_logs has to be added to its archive as well.
+
+CREATE TABLE IF NOT EXISTS activities_archive (
+    LIKE activities INCLUDING DEFAULTS INCLUDING CONSTRAINTS,
+    PRIMARY KEY (id),
+    FOREIGN KEY (customer_id) REFERENCES customers(id) ON DELETE CASCADE,
+    FOREIGN KEY (contact_id) REFERENCES contacts(id) ON DELETE SET NULL,
+    FOREIGN KEY (deal_id) REFERENCES deals(id) ON DELETE SET NULL,
+    FOREIGN KEY (assigned_to) REFERENCES users(id),
+    FOREIGN KEY (created_by) REFERENCES users(id),
+    FOREIGN KEY (tenant_id) REFERENCES tenants(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_activities_archive_tenant ON activities_archive(tenant_id);
+CREATE INDEX IF NOT EXISTS idx_activities_archive_activity_date ON activities_archive(activity_date);
+CREATE INDEX IF NOT EXISTS idx_activities_archive_customer_id ON activities_archive(customer_id);
+CREATE INDEX IF NOT EXISTS idx_activities_archive_owner ON activities_archive((COALESCE(assigned_to, created_by)));
+
+CREATE TABLE IF NOT EXISTS audit_logs_archive (
+    LIKE audit_logs INCLUDING DEFAULTS INCLUDING CONSTRAINTS,
+    PRIMARY KEY (id),
+    FOREIGN KEY (user_id) REFERENCES users(id),
+    FOREIGN KEY (impersonator_id) REFERENCES users(id) ON DELETE SET NULL,
+    FOREIGN KEY (tenant_id) REFERENCES tenants(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_audit_logs_archive_tenant ON audit_logs_archive(tenant_id);
+CREATE INDEX IF NOT EXISTS idx_audit_logs_archive_created_at ON audit_logs_archive(created_at);
+CREATE INDEX IF NOT EXISTS idx_audit_logs_archive_resource ON audit_logs_archive(resource_type, resource_id);
+CREATE INDEX IF NOT EXISTS idx_audit_logs_archive_user_id ON audit_logs_archive(user_id);
+
+ALTER TABLE activities_archive ENABLE ROW LEVEL SECURITY;
+DROP POLICY IF EXISTS tenant_isolation ON activities_archive;
+CREATE POLICY tenant_isolation ON activities_archive USING (tenant_id = current_tenant_id());
+
+ALTER TABLE audit_logs_archive ENABLE ROW LEVEL SECURITY;
+DROP POLICY IF EXISTS tenant_isolation ON audit_logs_archive;
+CREATE POLICY tenant_isolation ON audit_logs_archive USING (tenant_id = current_tenant_id());
+
+-- security_invoker keeps row level security on both sides applying to the
+-- app's role, rather than to the views' owner
+CREATE OR REPLACE VIEW activities_with_archive WITH (security_invoker = true) AS
+SELECT * FROM activities
+UNION ALL
+SELECT * FROM activities_archive;
+
+CREATE OR REPLACE VIEW audit_logs_with_archive WITH (security_invoker = true) AS
+SELECT * FROM audit_logs
+UNION ALL
+SELECT * FROM audit_logs_archive;
+
+GRANT SELECT, INSERT, UPDATE, DELETE ON activities_archive, audit_logs_archive TO allo_app;
+GRANT SELECT ON activities_with_archive, audit_logs_with_archive TO allo_app;
+
+SELECT 'Archive tables added successfully!' as status;
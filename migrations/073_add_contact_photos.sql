@@ -0,0 +1,9 @@
+-- A photo for each contact, and the business card it was scanned from.
+-- Both hold the storage URL of an uploaded image (see services/storage.rs).
+ALTER TABLE contacts ADD COLUMN IF NOT EXISTS photo_url TEXT;
+ALTER TABLE contacts ADD COLUMN IF NOT EXISTS business_card_url TEXT;
+
+ALTER TABLE sandbox.contacts ADD COLUMN IF NOT EXISTS photo_url TEXT;
+ALTER TABLE sandbox.contacts ADD COLUMN IF NOT EXISTS business_card_url TEXT;
+
+SELECT 'Contact photos added successfully!' as status;
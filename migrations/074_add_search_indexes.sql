@@ -0,0 +1,32 @@
+-- Full-text search over CRM records and inventory items (see
+-- services/search.rs). The indexes are on expressions rather than stored
+-- columns, so the sandbox and archive copies of these tables don't need to
+-- change; queries must use the same search_document(...) call to hit them.
+CREATE OR REPLACE FUNCTION search_document(VARIADIC parts TEXT[])
+RETURNS tsvector
+LANGUAGE sql IMMUTABLE PARALLEL SAFE
+AS $$
+    SELECT to_tsvector('english'::regconfig, array_to_string(parts, ' '))
+$$;
+
+CREATE INDEX IF NOT EXISTS idx_customers_search ON customers USING GIN (
+    search_document(company_name, industry, email, phone, website, city, country, notes)
+);
+
+CREATE INDEX IF NOT EXISTS idx_contacts_search ON contacts USING GIN (
+    search_document(first_name, last_name, title, email, phone, mobile, notes)
+);
+
+CREATE INDEX IF NOT EXISTS idx_deals_search ON deals USING GIN (
+    search_document(title, description)
+);
+
+CREATE INDEX IF NOT EXISTS idx_activities_search ON activities USING GIN (
+    search_document(subject, description)
+);
+
+CREATE INDEX IF NOT EXISTS idx_inventory_items_search ON inventory_items USING GIN (
+    search_document(item_name, sku, upc, brand, model, category, short_description, description)
+);
+
+SELECT 'Search indexes added successfully!' as status;
@@ -0,0 +1,13 @@
+-- Customer imports can map the file's columns to fields and decide what
+-- happens to rows matching an existing customer. A staged import has been
+-- uploaded but is waiting for its mapping to be confirmed; it has no job yet.
+ALTER TABLE imports ADD COLUMN IF NOT EXISTS staged BOOLEAN NOT NULL DEFAULT false;
+-- The field each column goes to, by position; '' leaves a column out.
+-- NULL matches columns to fields by their header names.
+ALTER TABLE imports ADD COLUMN IF NOT EXISTS mapping JSONB;
+ALTER TABLE imports ADD COLUMN IF NOT EXISTS duplicates VARCHAR(10) NOT NULL DEFAULT 'create'
+    CHECK (duplicates IN ('create', 'skip', 'merge'));
+ALTER TABLE imports ADD COLUMN IF NOT EXISTS merged_rows INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE imports ADD COLUMN IF NOT EXISTS skipped_rows INTEGER NOT NULL DEFAULT 0;
+
+SELECT 'Import mapping added successfully!' as status;
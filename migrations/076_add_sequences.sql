@@ -0,0 +1,88 @@
+-- Follow-up sequences: a short series of emails and tasks spread over days,
+-- applied to a contact or a deal. The scheduler runs each enrollment's steps
+-- as they come due (see services/sequences.rs) and records them in
+-- sequence_step_runs, so steps added or removed later only affect what
+-- hasn't run yet. A reply from the contact or a change of the deal's stage
+-- stops the enrollment.
+CREATE TABLE IF NOT EXISTS sequences (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    name VARCHAR(100) NOT NULL,
+    description TEXT,
+    is_active BOOLEAN NOT NULL DEFAULT true,
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    tenant_id UUID NOT NULL DEFAULT current_tenant_id() REFERENCES tenants(id) ON DELETE CASCADE,
+    CONSTRAINT sequences_name_key UNIQUE (tenant_id, name)
+);
+
+-- Day 1 is the day of enrollment
+CREATE TABLE IF NOT EXISTS sequence_steps (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    sequence_id UUID NOT NULL REFERENCES sequences(id) ON DELETE CASCADE,
+    day INTEGER NOT NULL CHECK (day BETWEEN 1 AND 365),
+    step_type VARCHAR(10) NOT NULL CHECK (step_type IN ('email', 'task')),
+    -- The activity type of a task, e.g. 'call'
+    activity_type VARCHAR(50),
+    subject VARCHAR(255) NOT NULL,
+    body TEXT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    tenant_id UUID NOT NULL DEFAULT current_tenant_id() REFERENCES tenants(id) ON DELETE CASCADE
+);
+
+CREATE TABLE IF NOT EXISTS sequence_enrollments (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    sequence_id UUID NOT NULL REFERENCES sequences(id) ON DELETE CASCADE,
+    customer_id UUID NOT NULL REFERENCES customers(id) ON DELETE CASCADE,
+    contact_id UUID REFERENCES contacts(id) ON DELETE CASCADE,
+    deal_id UUID REFERENCES deals(id) ON DELETE CASCADE,
+    status VARCHAR(20) NOT NULL DEFAULT 'active' CHECK (status IN ('active', 'completed', 'stopped')),
+    stop_reason TEXT,
+    -- Emails go out as, and tasks are assigned to, whoever enrolled
+    enrolled_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    started_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    finished_at TIMESTAMPTZ,
+    tenant_id UUID NOT NULL DEFAULT current_tenant_id() REFERENCES tenants(id) ON DELETE CASCADE,
+    CHECK (contact_id IS NOT NULL OR deal_id IS NOT NULL)
+);
+
+CREATE TABLE IF NOT EXISTS sequence_step_runs (
+    enrollment_id UUID NOT NULL REFERENCES sequence_enrollments(id) ON DELETE CASCADE,
+    step_id UUID NOT NULL REFERENCES sequence_steps(id) ON DELETE CASCADE,
+    activity_id UUID REFERENCES activities(id) ON DELETE SET NULL,
+    email_id UUID REFERENCES email_outbox(id) ON DELETE SET NULL,
+    ran_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    tenant_id UUID NOT NULL DEFAULT current_tenant_id() REFERENCES tenants(id) ON DELETE CASCADE,
+    PRIMARY KEY (enrollment_id, step_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_sequence_steps_sequence ON sequence_steps(sequence_id, day);
+CREATE INDEX IF NOT EXISTS idx_sequence_enrollments_contact ON sequence_enrollments(contact_id);
+CREATE INDEX IF NOT EXISTS idx_sequence_enrollments_deal ON sequence_enrollments(deal_id);
+CREATE INDEX IF NOT EXISTS idx_sequence_enrollments_active ON sequence_enrollments(started_at) WHERE status = 'active';
+-- A record can only be in a sequence once at a time
+CREATE UNIQUE INDEX IF NOT EXISTS idx_sequence_enrollments_unique_active
+    ON sequence_enrollments(sequence_id, COALESCE(deal_id, contact_id)) WHERE status = 'active';
+
+CREATE INDEX IF NOT EXISTS idx_sequences_tenant ON sequences(tenant_id);
+CREATE INDEX IF NOT EXISTS idx_sequence_steps_tenant ON sequence_steps(tenant_id);
+CREATE INDEX IF NOT EXISTS idx_sequence_enrollments_tenant ON sequence_enrollments(tenant_id);
+CREATE INDEX IF NOT EXISTS idx_sequence_step_runs_tenant ON sequence_step_runs(tenant_id);
+
+ALTER TABLE sequences ENABLE ROW LEVEL SECURITY;
+DROP POLICY IF EXISTS tenant_isolation ON sequences;
+CREATE POLICY tenant_isolation ON sequences USING (tenant_id = current_tenant_id());
+
+ALTER TABLE sequence_steps ENABLE ROW LEVEL SECURITY;
+DROP POLICY IF EXISTS tenant_isolation ON sequence_steps;
+CREATE POLICY tenant_isolation ON sequence_steps USING (tenant_id = current_tenant_id());
+
+ALTER TABLE sequence_enrollments ENABLE ROW LEVEL SECURITY;
+DROP POLICY IF EXISTS tenant_isolation ON sequence_enrollments;
+CREATE POLICY tenant_isolation ON sequence_enrollments USING (tenant_id = current_tenant_id());
+
+ALTER TABLE sequence_step_runs ENABLE ROW LEVEL SECURITY;
+DROP POLICY IF EXISTS tenant_isolation ON sequence_step_runs;
+CREATE POLICY tenant_isolation ON sequence_step_runs USING (tenant_id = current_tenant_id());
+
+SELECT 'Sequences added successfully!' as status;
@@ -0,0 +1,33 @@
+-- Prices negotiated with a customer for particular items: either a fixed unit
+-- price or a discount off the item's selling price, each for a period. Deal
+-- lines and blanket order lines pick them up when no price is entered (see
+-- services/pricing_agreements.rs), and deals warn when one is running out.
+CREATE TABLE IF NOT EXISTS pricing_agreements (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    customer_id UUID NOT NULL REFERENCES customers(id) ON DELETE CASCADE,
+    item_id UUID NOT NULL REFERENCES inventory_items(id) ON DELETE CASCADE,
+    fixed_price NUMERIC(15,2) CHECK (fixed_price >= 0),
+    discount_percent NUMERIC(5,2) CHECK (discount_percent > 0 AND discount_percent <= 100),
+    valid_from DATE NOT NULL DEFAULT CURRENT_DATE,
+    -- Open-ended when NULL
+    valid_until DATE,
+    notes TEXT,
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    tenant_id UUID NOT NULL DEFAULT current_tenant_id() REFERENCES tenants(id) ON DELETE CASCADE,
+    CHECK ((fixed_price IS NULL) <> (discount_percent IS NULL)),
+    CHECK (valid_until IS NULL OR valid_until >= valid_from)
+);
+
+CREATE INDEX IF NOT EXISTS idx_pricing_agreements_customer_item ON pricing_agreements(customer_id, item_id);
+CREATE INDEX IF NOT EXISTS idx_pricing_agreements_tenant ON pricing_agreements(tenant_id);
+
+ALTER TABLE pricing_agreements ENABLE ROW LEVEL SECURITY;
+DROP POLICY IF EXISTS tenant_isolation ON pricing_agreements;
+CREATE POLICY tenant_isolation ON pricing_agreements USING (tenant_id = current_tenant_id());
+
+-- The agreement a deal line's price came from, if any
+ALTER TABLE deal_line_items ADD COLUMN IF NOT EXISTS pricing_agreement_id UUID
+    REFERENCES pricing_agreements(id) ON DELETE SET NULL;
+
+SELECT 'Pricing agreements added successfully!' as status;
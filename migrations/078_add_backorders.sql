@@ -0,0 +1,27 @@
+-- The part of a blanket order release the warehouse couldn't cover when it
+-- was reserved, for items that allow backorders. Stock received or
+-- transferred into the warehouse is allocated to open backorders oldest
+-- first (see services/backorders.rs); the release ships once it's filled.
+CREATE TABLE IF NOT EXISTS backorders (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    release_id UUID NOT NULL UNIQUE REFERENCES blanket_order_releases(id) ON DELETE CASCADE,
+    item_id UUID NOT NULL REFERENCES inventory_items(id) ON DELETE CASCADE,
+    warehouse_id UUID NOT NULL REFERENCES warehouses(id) ON DELETE CASCADE,
+    -- Short when the release was reserved
+    quantity INTEGER NOT NULL CHECK (quantity > 0),
+    allocated INTEGER NOT NULL DEFAULT 0,
+    status VARCHAR(20) NOT NULL DEFAULT 'open' CHECK (status IN ('open', 'filled', 'cancelled')),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    filled_at TIMESTAMPTZ,
+    tenant_id UUID NOT NULL DEFAULT current_tenant_id() REFERENCES tenants(id) ON DELETE CASCADE,
+    CHECK (allocated BETWEEN 0 AND quantity)
+);
+
+CREATE INDEX IF NOT EXISTS idx_backorders_open ON backorders(item_id, warehouse_id, created_at) WHERE status = 'open';
+CREATE INDEX IF NOT EXISTS idx_backorders_tenant ON backorders(tenant_id);
+
+ALTER TABLE backorders ENABLE ROW LEVEL SECURITY;
+DROP POLICY IF EXISTS tenant_isolation ON backorders;
+CREATE POLICY tenant_isolation ON backorders USING (tenant_id = current_tenant_id());
+
+SELECT 'Backorders added successfully!' as status;
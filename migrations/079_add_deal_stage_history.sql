@@ -0,0 +1,27 @@
+-- Every stage a deal has been in, recorded as it moves (see
+-- services/stage_history.rs). The first row of a deal has no old stage.
+CREATE TABLE IF NOT EXISTS deal_stage_history (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    deal_id UUID NOT NULL REFERENCES deals(id) ON DELETE CASCADE,
+    old_stage VARCHAR(50),
+    new_stage VARCHAR(50) NOT NULL,
+    changed_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    changed_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    tenant_id UUID NOT NULL DEFAULT current_tenant_id() REFERENCES tenants(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_deal_stage_history_deal ON deal_stage_history(deal_id, changed_at);
+CREATE INDEX IF NOT EXISTS idx_deal_stage_history_tenant ON deal_stage_history(tenant_id);
+
+ALTER TABLE deal_stage_history ENABLE ROW LEVEL SECURITY;
+DROP POLICY IF EXISTS tenant_isolation ON deal_stage_history;
+CREATE POLICY tenant_isolation ON deal_stage_history USING (tenant_id = current_tenant_id());
+
+-- Earlier moves weren't kept, so existing deals start from their current
+-- stage as of when they last moved
+INSERT INTO deal_stage_history (deal_id, new_stage, changed_at, tenant_id)
+SELECT d.id, d.stage, COALESCE(d.stage_changed_at, d.created_at, NOW()), d.tenant_id
+FROM deals d
+WHERE NOT EXISTS (SELECT 1 FROM deal_stage_history h WHERE h.deal_id = d.id);
+
+SELECT 'Deal stage history added successfully!' as status;
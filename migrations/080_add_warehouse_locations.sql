@@ -0,0 +1,42 @@
+-- Bin locations managed per warehouse, replacing the free-text aisle and bin
+-- on stock levels. Walk order is the order a picker passes them in; capacity
+-- is a rough number of units the location holds, used when suggesting where
+-- to put received stock away (see services/locations.rs).
+CREATE TABLE IF NOT EXISTS warehouse_locations (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    warehouse_id UUID NOT NULL REFERENCES warehouses(id) ON DELETE CASCADE,
+    code VARCHAR(50) NOT NULL,
+    aisle VARCHAR(50),
+    bin VARCHAR(50),
+    walk_order INTEGER NOT NULL DEFAULT 0,
+    capacity INTEGER CHECK (capacity > 0),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    tenant_id UUID NOT NULL DEFAULT current_tenant_id() REFERENCES tenants(id) ON DELETE CASCADE,
+    UNIQUE (warehouse_id, code)
+);
+
+CREATE INDEX IF NOT EXISTS idx_warehouse_locations_tenant ON warehouse_locations(tenant_id);
+
+ALTER TABLE warehouse_locations ENABLE ROW LEVEL SECURITY;
+DROP POLICY IF EXISTS tenant_isolation ON warehouse_locations;
+CREATE POLICY tenant_isolation ON warehouse_locations USING (tenant_id = current_tenant_id());
+
+ALTER TABLE stock_levels ADD COLUMN IF NOT EXISTS location_id UUID
+    REFERENCES warehouse_locations(id) ON DELETE SET NULL;
+
+-- Every aisle and bin already in use becomes a location
+INSERT INTO warehouse_locations (warehouse_id, code, aisle, bin, tenant_id)
+SELECT DISTINCT sl.warehouse_id, concat_ws('-', NULLIF(sl.aisle, ''), NULLIF(sl.bin, '')),
+       NULLIF(sl.aisle, ''), NULLIF(sl.bin, ''), w.tenant_id
+FROM stock_levels sl
+JOIN warehouses w ON w.id = sl.warehouse_id
+WHERE COALESCE(sl.aisle, '') <> '' OR COALESCE(sl.bin, '') <> ''
+ON CONFLICT (warehouse_id, code) DO NOTHING;
+
+UPDATE stock_levels sl SET location_id = l.id
+FROM warehouse_locations l
+WHERE l.warehouse_id = sl.warehouse_id
+  AND l.code = concat_ws('-', NULLIF(sl.aisle, ''), NULLIF(sl.bin, ''))
+  AND sl.location_id IS NULL;
+
+SELECT 'Warehouse locations added successfully!' as status;
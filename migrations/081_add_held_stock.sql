@@ -0,0 +1,81 @@
+-- Stock kept in our warehouses that we don't own: a supplier's consignment,
+-- which becomes ours once taken into stock, or goods a customer owns. It's
+-- kept apart from stock_levels so it's never counted as available or valued.
+CREATE TABLE IF NOT EXISTS held_stock (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    item_id UUID NOT NULL REFERENCES inventory_items(id) ON DELETE CASCADE,
+    warehouse_id UUID NOT NULL REFERENCES warehouses(id) ON DELETE CASCADE,
+    ownership VARCHAR(20) NOT NULL CHECK (ownership IN ('consignment', 'customer_owned')),
+    -- The customer who owns it, or the supplier who consigned it
+    customer_id UUID REFERENCES customers(id) ON DELETE CASCADE,
+    consignor VARCHAR(255),
+    quantity_on_hand INTEGER NOT NULL DEFAULT 0 CHECK (quantity_on_hand >= 0),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    tenant_id UUID NOT NULL DEFAULT current_tenant_id() REFERENCES tenants(id) ON DELETE CASCADE,
+    CHECK (
+        (ownership = 'consignment' AND consignor IS NOT NULL AND customer_id IS NULL)
+        OR (ownership = 'customer_owned' AND customer_id IS NOT NULL AND consignor IS NULL)
+    ),
+    UNIQUE NULLS NOT DISTINCT (item_id, warehouse_id, ownership, customer_id, consignor)
+);
+
+CREATE INDEX IF NOT EXISTS idx_held_stock_item ON held_stock(item_id);
+CREATE INDEX IF NOT EXISTS idx_held_stock_customer ON held_stock(customer_id);
+CREATE INDEX IF NOT EXISTS idx_held_stock_tenant ON held_stock(tenant_id);
+
+ALTER TABLE held_stock ENABLE ROW LEVEL SECURITY;
+DROP POLICY IF EXISTS tenant_isolation ON held_stock;
+CREATE POLICY tenant_isolation ON held_stock
+    USING (tenant_id = current_tenant_id())
+    WITH CHECK (tenant_id = current_tenant_id());
+
+CREATE TRIGGER update_held_stock_updated_at BEFORE UPDATE ON held_stock
+    FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
+
+-- Whose stock a movement moved
+ALTER TABLE stock_movements ADD COLUMN IF NOT EXISTS ownership VARCHAR(20) NOT NULL DEFAULT 'owned'
+    CHECK (ownership IN ('owned', 'consignment', 'customer_owned'));
+
+-- Stock valuation counts held stock alongside, never in, what's valued
+DROP VIEW IF EXISTS reporting_stock_valuation;
+DROP MATERIALIZED VIEW IF EXISTS mv_stock_valuation;
+
+CREATE MATERIALIZED VIEW mv_stock_valuation AS
+SELECT w.tenant_id,
+       w.id as warehouse_id,
+       w.name as warehouse_name,
+       COALESCE(owned.item_count, 0) as item_count,
+       COALESCE(owned.quantity_on_hand, 0) as quantity_on_hand,
+       COALESCE(owned.total_value, 0) as total_value,
+       COALESCE(held.consigned, 0) as consigned_on_hand,
+       COALESCE(held.customer_owned, 0) as customer_owned_on_hand,
+       NOW() as refreshed_at
+FROM warehouses w
+LEFT JOIN (
+    SELECT sl.warehouse_id,
+           COUNT(DISTINCT sl.item_id) FILTER (WHERE sl.quantity_on_hand > 0) as item_count,
+           SUM(sl.quantity_on_hand) as quantity_on_hand,
+           SUM(sl.quantity_on_hand * COALESCE(i.average_cost, i.cost_price, i.purchase_price, 0)) as total_value
+    FROM stock_levels sl
+    JOIN inventory_items i ON i.id = sl.item_id
+    GROUP BY sl.warehouse_id
+) owned ON owned.warehouse_id = w.id
+LEFT JOIN (
+    SELECT h.warehouse_id,
+           SUM(h.quantity_on_hand) FILTER (WHERE h.ownership = 'consignment') as consigned,
+           SUM(h.quantity_on_hand) FILTER (WHERE h.ownership = 'customer_owned') as customer_owned
+    FROM held_stock h
+    GROUP BY h.warehouse_id
+) held ON held.warehouse_id = w.id
+WHERE owned.warehouse_id IS NOT NULL OR held.warehouse_id IS NOT NULL;
+
+CREATE UNIQUE INDEX IF NOT EXISTS idx_mv_stock_valuation ON mv_stock_valuation(tenant_id, warehouse_id);
+
+CREATE OR REPLACE VIEW reporting_stock_valuation AS
+SELECT * FROM mv_stock_valuation WHERE tenant_id = current_tenant_id();
+
+REVOKE ALL ON mv_stock_valuation FROM allo_app;
+REVOKE ALL ON reporting_stock_valuation FROM allo_app;
+GRANT SELECT ON reporting_stock_valuation TO allo_app;
+
+SELECT 'Held stock added successfully!' as status;
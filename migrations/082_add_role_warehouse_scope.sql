@@ -0,0 +1,8 @@
+-- A role can be limited to one warehouse, so a location manager sees and
+-- moves only that warehouse's stock. Roles it inherits from are limited too,
+-- and the warehouse can't be deleted while a role is limited to it.
+ALTER TABLE roles ADD COLUMN IF NOT EXISTS warehouse_id UUID REFERENCES warehouses(id);
+
+CREATE INDEX IF NOT EXISTS idx_roles_warehouse ON roles(warehouse_id) WHERE warehouse_id IS NOT NULL;
+
+SELECT 'Role warehouse scope added successfully!' as status;
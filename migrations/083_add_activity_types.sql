@@ -0,0 +1,89 @@
+-- Activity types move out of the generic dropdown lookups into their own
+-- table, so each can carry an icon, a default duration and whether an
+-- outcome has to be recorded when it's completed
+CREATE TABLE IF NOT EXISTS activity_types (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    -- What activities.activity_type stores; the name is only for display
+    code VARCHAR(50) NOT NULL,
+    name VARCHAR(100) NOT NULL,
+    icon VARCHAR(16) NOT NULL DEFAULT '📝',
+    -- Prefilled on new activities of the type
+    default_duration_minutes INTEGER CHECK (default_duration_minutes > 0),
+    -- Completing one without an outcome code is refused
+    requires_outcome BOOLEAN NOT NULL DEFAULT false,
+    sort_order INTEGER NOT NULL DEFAULT 0,
+    is_active BOOLEAN NOT NULL DEFAULT true,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    tenant_id UUID NOT NULL DEFAULT current_tenant_id() REFERENCES tenants(id) ON DELETE CASCADE,
+    UNIQUE (tenant_id, code)
+);
+
+CREATE INDEX IF NOT EXISTS idx_activity_types_tenant ON activity_types(tenant_id);
+
+ALTER TABLE activity_types ENABLE ROW LEVEL SECURITY;
+DROP POLICY IF EXISTS tenant_isolation ON activity_types;
+CREATE POLICY tenant_isolation ON activity_types
+    USING (tenant_id = current_tenant_id())
+    WITH CHECK (tenant_id = current_tenant_id());
+
+CREATE TRIGGER update_activity_types_updated_at BEFORE UPDATE ON activity_types
+    FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
+
+-- The icons the lists used to hardcode
+INSERT INTO activity_types (tenant_id, code, name, icon, default_duration_minutes, sort_order, is_active)
+SELECT tenant_id, value, label,
+       CASE value WHEN 'call' THEN '📞' WHEN 'meeting' THEN '🤝' WHEN 'email' THEN '✉️' ELSE '📝' END,
+       CASE value WHEN 'call' THEN 15 WHEN 'meeting' THEN 60 END,
+       sort_order, is_active
+FROM lookup_values
+WHERE kind = 'activity_type'
+ON CONFLICT (tenant_id, code) DO NOTHING;
+
+-- Types already on activities stay selectable
+INSERT INTO activity_types (tenant_id, code, name, sort_order)
+SELECT DISTINCT tenant_id, activity_type, INITCAP(activity_type), 100
+FROM activities
+ON CONFLICT (tenant_id, code) DO NOTHING;
+
+DELETE FROM lookup_values WHERE kind = 'activity_type';
+ALTER TABLE lookup_values DROP CONSTRAINT IF EXISTS lookup_values_kind_check;
+ALTER TABLE lookup_values ADD CONSTRAINT lookup_values_kind_check
+    CHECK (kind IN ('industry', 'country', 'currency'));
+
+-- As in 067, with new organizations also getting the activity types
+CREATE OR REPLACE FUNCTION create_tenant(tenant_name TEXT, tenant_slug TEXT) RETURNS UUID
+LANGUAGE plpgsql AS $$
+DECLARE
+    template UUID := (SELECT id FROM tenants WHERE is_primary);
+    tenant UUID;
+BEGIN
+    INSERT INTO tenants (name, slug) VALUES (tenant_name, LOWER(tenant_slug)) RETURNING id INTO tenant;
+
+    INSERT INTO security_settings (tenant_id) VALUES (tenant);
+    INSERT INTO reporting_settings (tenant_id) VALUES (tenant);
+    INSERT INTO cost_centers (tenant_id, code, name) VALUES (tenant, 'GEN', 'General');
+
+    INSERT INTO deal_stage_settings (tenant_id, stage, stale_after_days)
+    SELECT tenant, stage, stale_after_days FROM deal_stage_settings WHERE tenant_id = template;
+    INSERT INTO activity_types (tenant_id, code, name, icon, default_duration_minutes, requires_outcome, sort_order)
+    SELECT tenant, code, name, icon, default_duration_minutes, requires_outcome, sort_order
+    FROM activity_types WHERE tenant_id = template AND is_active;
+    INSERT INTO activity_outcomes (tenant_id, activity_type, code, label, is_connect, is_held, sort_order)
+    SELECT tenant, activity_type, code, label, is_connect, is_held, sort_order
+    FROM activity_outcomes WHERE tenant_id = template AND is_active;
+    INSERT INTO lookup_values (tenant_id, kind, value, label, sort_order)
+    SELECT tenant, kind, value, label, sort_order FROM lookup_values WHERE tenant_id = template AND is_active;
+    INSERT INTO expense_categories (tenant_id, name, description)
+    SELECT tenant, name, description FROM expense_categories WHERE tenant_id = template AND is_active;
+    INSERT INTO discount_thresholds (tenant_id, above_percent, approver)
+    SELECT tenant, above_percent, approver FROM discount_thresholds WHERE tenant_id = template;
+    INSERT INTO number_sequences (tenant_id, document_type, prefix, padding, reset_yearly)
+    SELECT tenant, document_type, prefix, padding, reset_yearly FROM number_sequences WHERE tenant_id = template;
+    INSERT INTO document_templates (tenant_id, document_type, title, terms_text)
+    SELECT tenant, document_type, title, terms_text FROM document_templates WHERE tenant_id = template;
+
+    RETURN tenant;
+END $$;
+
+SELECT 'Activity types added successfully!' as status;
@@ -0,0 +1,44 @@
+-- Prospects not yet qualified enough to be a customer. Converting one creates
+-- the customer, its primary contact and optionally a deal; the lead stays
+-- behind, marked converted and pointing at what it became.
+CREATE TABLE IF NOT EXISTS leads (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    first_name VARCHAR(100) NOT NULL,
+    last_name VARCHAR(100) NOT NULL,
+    company_name VARCHAR(255),
+    title VARCHAR(100),
+    email VARCHAR(255),
+    phone VARCHAR(50),
+    -- Carried over to the customer and deal on conversion
+    lead_source VARCHAR(50),
+    campaign_id UUID REFERENCES campaigns(id) ON DELETE SET NULL,
+    status VARCHAR(20) NOT NULL DEFAULT 'new'
+        CHECK (status IN ('new', 'contacted', 'qualified', 'disqualified', 'converted')),
+    notes TEXT,
+    assigned_to UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_by UUID REFERENCES users(id),
+    converted_customer_id UUID REFERENCES customers(id) ON DELETE SET NULL,
+    converted_deal_id UUID REFERENCES deals(id) ON DELETE SET NULL,
+    converted_at TIMESTAMPTZ,
+    converted_by UUID REFERENCES users(id),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    tenant_id UUID NOT NULL DEFAULT current_tenant_id() REFERENCES tenants(id) ON DELETE CASCADE,
+    CHECK ((status = 'converted') = (converted_at IS NOT NULL))
+);
+
+CREATE INDEX IF NOT EXISTS idx_leads_status ON leads(status);
+CREATE INDEX IF NOT EXISTS idx_leads_owner ON leads(COALESCE(assigned_to, created_by));
+CREATE INDEX IF NOT EXISTS idx_leads_converted_customer ON leads(converted_customer_id);
+CREATE INDEX IF NOT EXISTS idx_leads_tenant ON leads(tenant_id);
+
+ALTER TABLE leads ENABLE ROW LEVEL SECURITY;
+DROP POLICY IF EXISTS tenant_isolation ON leads;
+CREATE POLICY tenant_isolation ON leads
+    USING (tenant_id = current_tenant_id())
+    WITH CHECK (tenant_id = current_tenant_id());
+
+CREATE TRIGGER update_leads_updated_at BEFORE UPDATE ON leads
+    FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
+
+SELECT 'Leads added successfully!' as status;
@@ -0,0 +1,68 @@
+-- Backs the public /status page. Each server probes the web app and the API
+-- once a minute; a minute counts as up if any server got an answer. The
+-- checks are the server's own, like the token signing keys, so they have no
+-- tenant. Incidents and maintenance windows are entered by each
+-- organization's admins.
+CREATE TABLE IF NOT EXISTS status_checks (
+    component VARCHAR(20) NOT NULL CHECK (component IN ('app', 'api')),
+    minute TIMESTAMPTZ NOT NULL,
+    ok BOOLEAN NOT NULL,
+    response_ms INTEGER,
+    PRIMARY KEY (component, minute)
+);
+
+CREATE TABLE IF NOT EXISTS status_incidents (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    title VARCHAR(255) NOT NULL,
+    message TEXT,
+    -- 'all' for both the web app and the API
+    component VARCHAR(20) NOT NULL CHECK (component IN ('app', 'api', 'all')),
+    impact VARCHAR(20) NOT NULL CHECK (impact IN ('degraded', 'partial_outage', 'major_outage')),
+    started_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    resolved_at TIMESTAMPTZ,
+    created_by UUID REFERENCES users(id),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    tenant_id UUID NOT NULL DEFAULT current_tenant_id() REFERENCES tenants(id) ON DELETE CASCADE,
+    CHECK (resolved_at IS NULL OR resolved_at >= started_at)
+);
+
+CREATE INDEX IF NOT EXISTS idx_status_incidents_started ON status_incidents(started_at);
+CREATE INDEX IF NOT EXISTS idx_status_incidents_tenant ON status_incidents(tenant_id);
+
+CREATE TABLE IF NOT EXISTS status_maintenance (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    title VARCHAR(255) NOT NULL,
+    description TEXT,
+    component VARCHAR(20) NOT NULL CHECK (component IN ('app', 'api', 'all')),
+    starts_at TIMESTAMPTZ NOT NULL,
+    ends_at TIMESTAMPTZ NOT NULL,
+    created_by UUID REFERENCES users(id),
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    tenant_id UUID NOT NULL DEFAULT current_tenant_id() REFERENCES tenants(id) ON DELETE CASCADE,
+    CHECK (ends_at > starts_at)
+);
+
+CREATE INDEX IF NOT EXISTS idx_status_maintenance_ends ON status_maintenance(ends_at);
+CREATE INDEX IF NOT EXISTS idx_status_maintenance_tenant ON status_maintenance(tenant_id);
+
+ALTER TABLE status_incidents ENABLE ROW LEVEL SECURITY;
+DROP POLICY IF EXISTS tenant_isolation ON status_incidents;
+CREATE POLICY tenant_isolation ON status_incidents
+    USING (tenant_id = current_tenant_id())
+    WITH CHECK (tenant_id = current_tenant_id());
+
+ALTER TABLE status_maintenance ENABLE ROW LEVEL SECURITY;
+DROP POLICY IF EXISTS tenant_isolation ON status_maintenance;
+CREATE POLICY tenant_isolation ON status_maintenance
+    USING (tenant_id = current_tenant_id())
+    WITH CHECK (tenant_id = current_tenant_id());
+
+CREATE TRIGGER update_status_incidents_updated_at BEFORE UPDATE ON status_incidents
+    FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
+
+CREATE TRIGGER update_status_maintenance_updated_at BEFORE UPDATE ON status_maintenance
+    FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
+
+SELECT 'Status page added successfully!' as status;
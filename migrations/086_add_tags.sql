@@ -0,0 +1,65 @@
+-- Free-form labels for segmenting customers, contacts and deals beyond
+-- their status. Tags are shared across the three; taggings say what's
+-- tagged with what.
+CREATE TABLE IF NOT EXISTS tags (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    name VARCHAR(50) NOT NULL,
+    -- One of the palette in models::tag
+    color VARCHAR(20) NOT NULL DEFAULT 'gray',
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    tenant_id UUID NOT NULL DEFAULT current_tenant_id() REFERENCES tenants(id) ON DELETE CASCADE
+);
+
+-- "VIP" and "vip" are the same tag
+CREATE UNIQUE INDEX IF NOT EXISTS idx_tags_name ON tags(tenant_id, LOWER(name));
+
+CREATE TABLE IF NOT EXISTS taggings (
+    tag_id UUID NOT NULL REFERENCES tags(id) ON DELETE CASCADE,
+    record_type VARCHAR(20) NOT NULL CHECK (record_type IN ('customer', 'contact', 'deal')),
+    record_id UUID NOT NULL,
+    created_by UUID REFERENCES users(id) ON DELETE SET NULL,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    tenant_id UUID NOT NULL DEFAULT current_tenant_id() REFERENCES tenants(id) ON DELETE CASCADE,
+    PRIMARY KEY (tag_id, record_type, record_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_taggings_record ON taggings(record_type, record_id);
+CREATE INDEX IF NOT EXISTS idx_taggings_tenant ON taggings(tenant_id);
+
+ALTER TABLE tags ENABLE ROW LEVEL SECURITY;
+DROP POLICY IF EXISTS tenant_isolation ON tags;
+CREATE POLICY tenant_isolation ON tags
+    USING (tenant_id = current_tenant_id())
+    WITH CHECK (tenant_id = current_tenant_id());
+
+ALTER TABLE taggings ENABLE ROW LEVEL SECURITY;
+DROP POLICY IF EXISTS tenant_isolation ON taggings;
+CREATE POLICY tenant_isolation ON taggings
+    USING (tenant_id = current_tenant_id())
+    WITH CHECK (tenant_id = current_tenant_id());
+
+CREATE TRIGGER update_tags_updated_at BEFORE UPDATE ON tags
+    FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
+
+-- record_id can't reference three tables, so taggings go with the record
+-- here instead of by foreign key
+CREATE OR REPLACE FUNCTION delete_taggings() RETURNS TRIGGER
+LANGUAGE plpgsql AS $$
+BEGIN
+    DELETE FROM taggings WHERE record_type = TG_ARGV[0] AND record_id = OLD.id;
+    RETURN OLD;
+END $$;
+
+DROP TRIGGER IF EXISTS delete_customer_taggings ON customers;
+CREATE TRIGGER delete_customer_taggings AFTER DELETE ON customers
+    FOR EACH ROW EXECUTE FUNCTION delete_taggings('customer');
+DROP TRIGGER IF EXISTS delete_contact_taggings ON contacts;
+CREATE TRIGGER delete_contact_taggings AFTER DELETE ON contacts
+    FOR EACH ROW EXECUTE FUNCTION delete_taggings('contact');
+DROP TRIGGER IF EXISTS delete_deal_taggings ON deals;
+CREATE TRIGGER delete_deal_taggings AFTER DELETE ON deals
+    FOR EACH ROW EXECUTE FUNCTION delete_taggings('deal');
+
+SELECT 'Tags added successfully!' as status;
@@ -0,0 +1,104 @@
+-- Which migrations have been run against this database, so admins can see
+-- from /team/diagnostics whether the schema matches the deployed build.
+-- Migrations are still applied by hand; each one from here on records itself
+-- at the end with an INSERT like the one below. The table belongs to the
+-- server, not to a tenant.
+CREATE TABLE IF NOT EXISTS schema_migrations (
+    -- The file name without .sql, e.g. 088_add_schema_migrations
+    version VARCHAR(255) PRIMARY KEY,
+    applied_at TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+-- Everything up to this one has been run if this one is being run
+INSERT INTO schema_migrations (version) VALUES
+    ('001_create_tables'),
+    ('002_add_rbac_fixed'),
+    ('003_add_sessions'),
+    ('004_add_deal_activity_permissions'),
+    ('005_add_expenses'),
+    ('006_seed_expense_categories'),
+    ('007_add_inventory_tables'),
+    ('008_seed_inventory_roles_and_permissions'),
+    ('009_expand_items_table'),
+    ('010_add_email_digests'),
+    ('011_add_deal_stagnation'),
+    ('012_add_activity_outcomes'),
+    ('013_add_campaigns'),
+    ('014_add_lead_utm_capture'),
+    ('015_add_email_tracking'),
+    ('016_add_segments_and_mass_email'),
+    ('017_add_email_bounce_handling'),
+    ('018_add_metric_snapshots'),
+    ('019_add_metric_alerts'),
+    ('020_add_role_dashboards'),
+    ('021_add_user_managers'),
+    ('022_add_record_shares'),
+    ('023_add_api_keys'),
+    ('024_add_api_call_logs'),
+    ('025_add_api_sandbox'),
+    ('026_add_webhooks'),
+    ('027_add_security_events'),
+    ('028_add_security_settings'),
+    ('029_add_read_only_roles'),
+    ('030_add_finance_read_permission'),
+    ('031_add_data_export_permission'),
+    ('032_add_session_limits'),
+    ('033_add_public_form_attempts'),
+    ('034_add_jobs'),
+    ('035_add_imports'),
+    ('036_session_details'),
+    ('037_add_login_attempts'),
+    ('038_add_lookup_values'),
+    ('039_add_password_policy'),
+    ('040_add_user_timezone'),
+    ('041_add_user_invitations'),
+    ('042_add_reporting_periods'),
+    ('043_add_impersonation'),
+    ('044_add_user_imports'),
+    ('045_add_out_of_office'),
+    ('046_add_email_signatures'),
+    ('047_add_login_events'),
+    ('048_add_deal_line_items'),
+    ('049_add_jwt_signing_keys'),
+    ('050_add_item_price_history'),
+    ('051_add_session_max_age'),
+    ('052_add_item_code_uniqueness'),
+    ('053_add_organization_setup'),
+    ('054_add_item_variants'),
+    ('055_add_customer_part_numbers'),
+    ('056_add_blanket_orders'),
+    ('057_add_number_sequences'),
+    ('058_add_document_templates'),
+    ('059_add_customer_language'),
+    ('060_add_exchange_rates'),
+    ('061_add_cost_centers'),
+    ('062_add_record_owners'),
+    ('063_add_projects'),
+    ('064_add_role_inheritance'),
+    ('065_add_audit_read_permission'),
+    ('066_add_project_billings'),
+    ('067_add_tenants'),
+    ('068_add_watchers'),
+    ('069_add_teams'),
+    ('070_add_saved_dashboards'),
+    ('071_add_reporting_views'),
+    ('072_add_archive_tables'),
+    ('073_add_contact_photos'),
+    ('074_add_search_indexes'),
+    ('075_add_import_mapping'),
+    ('076_add_sequences'),
+    ('077_add_pricing_agreements'),
+    ('078_add_backorders'),
+    ('079_add_deal_stage_history'),
+    ('080_add_warehouse_locations'),
+    ('081_add_held_stock'),
+    ('082_add_role_warehouse_scope'),
+    ('083_add_activity_types'),
+    ('084_add_leads'),
+    ('085_add_status_page'),
+    ('086_add_tags'),
+    ('087_add_custom_fields'),
+    ('088_add_schema_migrations')
+ON CONFLICT (version) DO NOTHING;
+
+SELECT 'Schema migrations added successfully!' as status;
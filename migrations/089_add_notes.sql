@@ -0,0 +1,45 @@
+-- Threads of markdown notes on customers and deals, one row per note, so a
+-- record's history isn't all in its single notes column
+CREATE TABLE IF NOT EXISTS notes (
+    id UUID PRIMARY KEY DEFAULT gen_random_uuid(),
+    record_type VARCHAR(20) NOT NULL CHECK (record_type IN ('customer', 'deal')),
+    record_id UUID NOT NULL,
+    body TEXT NOT NULL,
+    author_id UUID REFERENCES users(id) ON DELETE SET NULL,
+    -- Set when the body is changed after posting
+    edited_at TIMESTAMPTZ,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    tenant_id UUID NOT NULL DEFAULT current_tenant_id() REFERENCES tenants(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_notes_record ON notes(record_type, record_id, created_at);
+CREATE INDEX IF NOT EXISTS idx_notes_tenant ON notes(tenant_id);
+
+ALTER TABLE notes ENABLE ROW LEVEL SECURITY;
+DROP POLICY IF EXISTS tenant_isolation ON notes;
+CREATE POLICY tenant_isolation ON notes
+    USING (tenant_id = current_tenant_id())
+    WITH CHECK (tenant_id = current_tenant_id());
+
+CREATE TRIGGER update_notes_updated_at BEFORE UPDATE ON notes
+    FOR EACH ROW EXECUTE FUNCTION update_updated_at_column();
+
+-- As with taggings, record_id can't reference both tables
+CREATE OR REPLACE FUNCTION delete_notes() RETURNS TRIGGER
+LANGUAGE plpgsql AS $$
+BEGIN
+    DELETE FROM notes WHERE record_type = TG_ARGV[0] AND record_id = OLD.id;
+    RETURN OLD;
+END $$;
+
+DROP TRIGGER IF EXISTS delete_customer_notes ON customers;
+CREATE TRIGGER delete_customer_notes AFTER DELETE ON customers
+    FOR EACH ROW EXECUTE FUNCTION delete_notes('customer');
+DROP TRIGGER IF EXISTS delete_deal_notes ON deals;
+CREATE TRIGGER delete_deal_notes AFTER DELETE ON deals
+    FOR EACH ROW EXECUTE FUNCTION delete_notes('deal');
+
+INSERT INTO schema_migrations (version) VALUES ('089_add_notes') ON CONFLICT (version) DO NOTHING;
+
+SELECT 'Notes added successfully!' as status;
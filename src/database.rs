@@ -1,15 +1,50 @@
-use sqlx::{PgPool, Pool, Postgres};
+use sqlx::{postgres::PgPoolOptions, Executor, PgConnection, Pool, Postgres};
+
+use crate::services::tenancy;
 
 pub type Database = Pool<Postgres>;
 
+// Every connection works as allo_app, which row level security applies to
+// even when DATABASE_URL names a superuser or the tables' owner
+async fn set_role(conn: &mut PgConnection) -> Result<(), sqlx::Error> {
+    conn.execute("SET ROLE allo_app").await?;
+    Ok(())
+}
+
+// Point the connection at the tenant of the request or job taking it, or at
+// none, in which case the business tables look empty
+async fn set_tenant(conn: &mut PgConnection) -> Result<(), sqlx::Error> {
+    let tenant = tenancy::current_id().map(|id| id.to_string()).unwrap_or_default();
+    sqlx::query("SELECT set_config('app.tenant_id', $1, false)")
+        .bind(tenant)
+        .execute(conn)
+        .await?;
+    Ok(())
+}
+
 pub async fn create_database_pool(database_url: &str) -> Result<Database, sqlx::Error> {
-    let pool = PgPool::connect(database_url).await?;
-    
+    // Both hooks run in the task acquiring the connection, so they see its tenant
+    let pool = PgPoolOptions::new()
+        .after_connect(|conn, _| {
+            Box::pin(async move {
+                set_role(conn).await?;
+                set_tenant(conn).await
+            })
+        })
+        .before_acquire(|conn, _| {
+            Box::pin(async move {
+                set_tenant(conn).await?;
+                Ok(true)
+            })
+        })
+        .connect(database_url)
+        .await?;
+
     // Test the connection
     sqlx::query("SELECT 1")
         .fetch_one(&pool)
         .await?;
-    
-    println!("Connected to database successfully!");
+
+    tracing::info!("Connected to database successfully!");
     Ok(pool)
-}
\ No newline at end of file
+}
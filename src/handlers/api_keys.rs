@@ -0,0 +1,407 @@
+use axum::{
+    extract::{Form, Path, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Redirect, Response},
+};
+use askama::Template;
+use chrono::{DateTime, NaiveDate, Utc};
+use uuid::Uuid;
+
+use crate::{
+    database::Database,
+    filters,
+    middleware::{permission::held_roles_cte, AuthUser, CurrentUser},
+    models::{get_all_permissions, ApiKey, Permission, User, API_KEY_SELECT},
+    services::sandbox,
+    utils::{api_key::generate_api_key, request::parse_cidr_list},
+};
+
+#[derive(Template)]
+#[template(path = "team/api_keys.html")]
+struct ApiKeysTemplate {
+    keys: Vec<ApiKey>,
+    owners: Vec<User>,
+    permissions: Vec<Permission>,
+    categories: Vec<String>,
+    new_token: Option<String>,
+    error: Option<String>,
+    current_user: CurrentUser,
+}
+
+#[derive(Template)]
+#[template(path = "team/api_key_form.html")]
+struct ApiKeyFormTemplate {
+    key: ApiKey,
+    permissions: Vec<Permission>,
+    categories: Vec<String>,
+    expires_on: String,
+    allowed_ips: String,
+    error: Option<String>,
+}
+
+// Fields shared by the create and edit forms. Checkbox groups repeat the
+// `permissions` field, so the body is read as raw pairs.
+struct KeySettings {
+    name: String,
+    permissions: Vec<String>,
+    expires_at: Option<DateTime<Utc>>,
+    allowed_ips: Vec<String>,
+    is_sandbox: bool,
+}
+
+fn require_api_admin(current_user: &CurrentUser) -> Result<(), StatusCode> {
+    if current_user.permissions.contains(&"api:admin".to_string()) {
+        Ok(())
+    } else {
+        Err(StatusCode::FORBIDDEN)
+    }
+}
+
+fn field<'a>(form: &'a [(String, String)], name: &str) -> &'a str {
+    form.iter()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.trim())
+        .unwrap_or_default()
+}
+
+fn permission_categories(permissions: &[Permission]) -> Vec<String> {
+    let mut categories: Vec<String> = Vec::new();
+    for permission in permissions {
+        if !categories.contains(&permission.category) {
+            categories.push(permission.category.clone());
+        }
+    }
+    categories
+}
+
+// Unknown permission keys can only come from a tampered form, so they are a
+// 400; everything else is reported back on the page.
+fn parse_settings(form: &[(String, String)]) -> Result<Result<KeySettings, String>, StatusCode> {
+    let catalog = get_all_permissions();
+    let mut permissions = Vec::new();
+    for (_, key) in form.iter().filter(|(name, _)| name == "permissions") {
+        if !catalog.iter().any(|p| &p.key == key) {
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        if !permissions.contains(key) {
+            permissions.push(key.clone());
+        }
+    }
+
+    let name = field(form, "name");
+    if name.is_empty() || name.len() > 100 {
+        return Ok(Err("Give the key a name of at most 100 characters.".to_string()));
+    }
+    if permissions.is_empty() {
+        return Ok(Err("Select at least one permission for the key.".to_string()));
+    }
+
+    let expires_at = match field(form, "expires_on") {
+        "" => None,
+        value => {
+            let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") else {
+                return Ok(Err("Expiry must be a date.".to_string()));
+            };
+            // Keys stay valid through the whole expiry day
+            let at = date.and_hms_opt(23, 59, 59).unwrap().and_utc();
+            if at <= Utc::now() {
+                return Ok(Err("Expiry must be in the future.".to_string()));
+            }
+            Some(at)
+        }
+    };
+
+    let allowed_ips = match parse_cidr_list(field(form, "allowed_ips")) {
+        Ok(allowed_ips) => allowed_ips,
+        Err(message) => return Ok(Err(message)),
+    };
+
+    Ok(Ok(KeySettings {
+        name: name.to_string(),
+        permissions,
+        expires_at,
+        allowed_ips,
+        is_sandbox: field(form, "is_sandbox") == "true",
+    }))
+}
+
+async fn owner_has_api_access(db: &Database, user_id: Uuid) -> bool {
+    crate::middleware::permission::get_user_permissions(db, user_id)
+        .await
+        .contains(&"api:access".to_string())
+}
+
+async fn load_key(db: &Database, key_id: Uuid) -> Result<ApiKey, StatusCode> {
+    sqlx::query_as::<_, ApiKey>(&format!("{} WHERE k.id = $1", API_KEY_SELECT))
+        .bind(key_id)
+        .fetch_optional(db)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error loading API key: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+async fn render_list(
+    db: &Database,
+    current_user: CurrentUser,
+    new_token: Option<String>,
+    error: Option<String>,
+) -> Result<Html<String>, StatusCode> {
+    let keys = sqlx::query_as::<_, ApiKey>(&format!(
+        "{} ORDER BY k.revoked_at IS NOT NULL, k.created_at DESC",
+        API_KEY_SELECT
+    ))
+    .fetch_all(db)
+    .await
+    .map_err(|e| {
+        tracing::error!("Error loading API keys: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    // Only users whose roles allow API access can own a key
+    let owners = sqlx::query_as::<_, User>(&format!(
+        r#"
+        {}
+        SELECT DISTINCT u.*
+        FROM users u
+        JOIN held_roles h ON h.user_id = u.id
+        JOIN roles r ON r.id = h.role_id
+        WHERE u.is_active = true AND u.is_locked = false AND r.permissions ? 'api:access'
+        ORDER BY u.first_name, u.last_name
+        "#,
+        held_roles_cte(None)
+    ))
+    .fetch_all(db)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let permissions = get_all_permissions();
+    let template = ApiKeysTemplate {
+        keys,
+        owners,
+        categories: permission_categories(&permissions),
+        permissions,
+        new_token,
+        error,
+        current_user,
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+async fn log_key_change(
+    db: &Database,
+    actor: &CurrentUser,
+    action: &str,
+    key_id: Uuid,
+    new_values: Option<serde_json::Value>,
+) {
+    let result = sqlx::query(
+        r#"
+        INSERT INTO audit_logs (user_id, impersonator_id, action, resource_type, resource_id, new_values, ip_address, user_agent)
+        VALUES ($1, $2, $3, 'api_key', $4, $5, $6, $7)
+        "#,
+    )
+    .bind(actor.id)
+    .bind(actor.impersonator_id())
+    .bind(action)
+    .bind(key_id)
+    .bind(new_values)
+    .bind(&actor.client.ip_address)
+    .bind(&actor.client.user_agent)
+    .execute(db)
+    .await;
+
+    if let Err(e) = result {
+        tracing::error!("Failed to write API key audit log: {}", e);
+    }
+}
+
+pub async fn api_keys_list(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+) -> Result<Html<String>, StatusCode> {
+    require_api_admin(&current_user)?;
+
+    render_list(&db, current_user, None, None).await
+}
+
+pub async fn create_api_key(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Form(form): Form<Vec<(String, String)>>,
+) -> Result<Html<String>, StatusCode> {
+    require_api_admin(&current_user)?;
+
+    let settings = match parse_settings(&form)? {
+        Ok(settings) => settings,
+        Err(error) => return render_list(&db, current_user, None, Some(error)).await,
+    };
+
+    let owner_id = Uuid::parse_str(field(&form, "user_id")).map_err(|_| StatusCode::BAD_REQUEST)?;
+    if !owner_has_api_access(&db, owner_id).await {
+        let error = "The selected owner does not have the API Access permission.".to_string();
+        return render_list(&db, current_user, None, Some(error)).await;
+    }
+
+    let generated = generate_api_key();
+    let key_id = sqlx::query_scalar::<_, Uuid>(
+        r#"
+        INSERT INTO api_keys (name, key_prefix, key_hash, user_id, permissions, allowed_ips, expires_at, is_sandbox, created_by)
+        VALUES ($1, $2, $3, $4, $5, $6::cidr[], $7, $8, $9)
+        RETURNING id
+        "#,
+    )
+    .bind(&settings.name)
+    .bind(&generated.prefix)
+    .bind(&generated.hash)
+    .bind(owner_id)
+    .bind(sqlx::types::Json(&settings.permissions))
+    .bind(&settings.allowed_ips)
+    .bind(settings.expires_at)
+    .bind(settings.is_sandbox)
+    .bind(current_user.id)
+    .fetch_one(&db)
+    .await
+    .map_err(|e| {
+        tracing::error!("Error creating API key: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    log_key_change(
+        &db,
+        &current_user,
+        "create",
+        key_id,
+        Some(serde_json::json!({
+            "name": settings.name,
+            "user_id": owner_id,
+            "permissions": settings.permissions,
+            "allowed_ips": settings.allowed_ips,
+            "expires_at": settings.expires_at,
+            "is_sandbox": settings.is_sandbox,
+        })),
+    )
+    .await;
+
+    // The plain token is never stored, so this is the only time it is shown
+    render_list(&db, current_user, Some(generated.token), None).await
+}
+
+pub async fn api_key_edit_form(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(key_id): Path<Uuid>,
+) -> Result<Html<String>, StatusCode> {
+    require_api_admin(&current_user)?;
+
+    let key = load_key(&db, key_id).await?;
+    render_form(key, None)
+}
+
+fn render_form(key: ApiKey, error: Option<String>) -> Result<Html<String>, StatusCode> {
+    let permissions = get_all_permissions();
+    let template = ApiKeyFormTemplate {
+        expires_on: key.expires_at.map(|at| at.format("%Y-%m-%d").to_string()).unwrap_or_default(),
+        allowed_ips: key.allowed_ips.join("\n"),
+        key,
+        categories: permission_categories(&permissions),
+        permissions,
+        error,
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+pub async fn update_api_key(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(key_id): Path<Uuid>,
+    Form(form): Form<Vec<(String, String)>>,
+) -> Result<Response, StatusCode> {
+    require_api_admin(&current_user)?;
+
+    let key = load_key(&db, key_id).await?;
+    if key.revoked_at.is_some() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let settings = match parse_settings(&form)? {
+        Ok(settings) => settings,
+        Err(error) => return render_form(key, Some(error)).map(IntoResponse::into_response),
+    };
+
+    sqlx::query(
+        r#"
+        UPDATE api_keys
+        SET name = $2, permissions = $3, allowed_ips = $4::cidr[], expires_at = $5, is_sandbox = $6
+        WHERE id = $1
+        "#,
+    )
+    .bind(key_id)
+    .bind(&settings.name)
+    .bind(sqlx::types::Json(&settings.permissions))
+    .bind(&settings.allowed_ips)
+    .bind(settings.expires_at)
+    .bind(settings.is_sandbox)
+    .execute(&db)
+    .await
+    .map_err(|e| {
+        tracing::error!("Error updating API key: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    log_key_change(
+        &db,
+        &current_user,
+        "update",
+        key_id,
+        Some(serde_json::json!({
+            "name": settings.name,
+            "permissions": settings.permissions,
+            "allowed_ips": settings.allowed_ips,
+            "expires_at": settings.expires_at,
+            "is_sandbox": settings.is_sandbox,
+        })),
+    )
+    .await;
+
+    Ok(Redirect::to("/team/api-keys").into_response())
+}
+
+pub async fn revoke_api_key(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(key_id): Path<Uuid>,
+) -> Result<Redirect, StatusCode> {
+    require_api_admin(&current_user)?;
+
+    let result = sqlx::query("UPDATE api_keys SET revoked_at = NOW() WHERE id = $1 AND revoked_at IS NULL")
+        .bind(key_id)
+        .execute(&db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    if result.rows_affected() == 0 {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    log_key_change(&db, &current_user, "revoke", key_id, None).await;
+
+    Ok(Redirect::to("/team/api-keys"))
+}
+
+// Clears the shadow tables used by sandbox keys; real records are untouched
+pub async fn reset_sandbox(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+) -> Result<Redirect, StatusCode> {
+    require_api_admin(&current_user)?;
+
+    sandbox::reset(&db).await.map_err(|e| {
+        tracing::error!("Error resetting API sandbox: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    Ok(Redirect::to("/team/api-keys"))
+}
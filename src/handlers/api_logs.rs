@@ -0,0 +1,156 @@
+use axum::{
+    body::Body,
+    extract::{Path, Query, State},
+    http::{Request, StatusCode},
+    response::{Html, Redirect},
+};
+use askama::Template;
+use serde::Deserialize;
+use tower::Service;
+use uuid::Uuid;
+
+use crate::{
+    database::Database,
+    middleware::{
+        api_auth::{ApiCallLogged, ApiReplay},
+        AuthUser, CurrentUser,
+    },
+    models::{ApiCallLog, ApiKey, API_KEY_SELECT},
+    services::api_log::{self, API_CALL_LOG_SELECT},
+};
+
+const PAGE_SIZE: i64 = 100;
+
+#[derive(Template)]
+#[template(path = "team/api_logs.html")]
+struct ApiLogsTemplate {
+    logs: Vec<ApiCallLog>,
+    keys: Vec<ApiKey>,
+    selected_key: String,
+    selected_status: String,
+    page_size: i64,
+}
+
+#[derive(Template)]
+#[template(path = "team/api_log_detail.html")]
+struct ApiLogDetailTemplate {
+    log: ApiCallLog,
+}
+
+#[derive(Deserialize)]
+pub struct ApiLogFilters {
+    key_id: Option<String>,
+    // "success" for 2xx/3xx, "error" for 4xx/5xx
+    status: Option<String>,
+}
+
+fn require_api_admin(current_user: &CurrentUser) -> Result<(), StatusCode> {
+    if current_user.permissions.contains(&"api:admin".to_string()) {
+        Ok(())
+    } else {
+        Err(StatusCode::FORBIDDEN)
+    }
+}
+
+pub async fn api_logs_list(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Query(filters): Query<ApiLogFilters>,
+) -> Result<Html<String>, StatusCode> {
+    require_api_admin(&current_user)?;
+
+    let selected_key = filters.key_id.unwrap_or_default();
+    let key_id = match selected_key.as_str() {
+        "" => None,
+        value => Some(Uuid::parse_str(value).map_err(|_| StatusCode::BAD_REQUEST)?),
+    };
+
+    let selected_status = filters.status.unwrap_or_default();
+    let status_condition = match selected_status.as_str() {
+        "" => "TRUE",
+        "success" => "l.status < 400",
+        "error" => "l.status >= 400",
+        _ => return Err(StatusCode::BAD_REQUEST),
+    };
+
+    let logs = sqlx::query_as::<_, ApiCallLog>(&format!(
+        "{} WHERE ($1::uuid IS NULL OR l.api_key_id = $1) AND {} ORDER BY l.created_at DESC LIMIT $2",
+        API_CALL_LOG_SELECT, status_condition
+    ))
+    .bind(key_id)
+    .bind(PAGE_SIZE)
+    .fetch_all(&db)
+    .await
+    .map_err(|e| {
+        tracing::error!("Error loading API call logs: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let keys = sqlx::query_as::<_, ApiKey>(&format!("{} ORDER BY k.name", API_KEY_SELECT))
+        .fetch_all(&db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let template = ApiLogsTemplate {
+        logs,
+        keys,
+        selected_key,
+        selected_status,
+        page_size: PAGE_SIZE,
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+pub async fn api_log_detail(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(log_id): Path<Uuid>,
+) -> Result<Html<String>, StatusCode> {
+    require_api_admin(&current_user)?;
+
+    let log = api_log::find(&db, log_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let template = ApiLogDetailTemplate { log };
+    Ok(Html(template.render().unwrap()))
+}
+
+// Re-runs a logged GET with the same key through the API routes, so the key's
+// current permissions, expiry and revocation all apply. The replay is logged
+// as its own entry, which is where the admin lands afterwards.
+pub async fn replay_api_call(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(log_id): Path<Uuid>,
+) -> Result<Redirect, StatusCode> {
+    require_api_admin(&current_user)?;
+
+    let log = api_log::find(&db, log_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    if !log.can_replay() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let key_id = log.api_key_id.ok_or(StatusCode::BAD_REQUEST)?;
+
+    let request = Request::get(&log.path)
+        .extension(ApiReplay { key_id, log_id })
+        .body(Body::empty())
+        .map_err(|_| StatusCode::BAD_REQUEST)?;
+
+    // Router is always ready, so it can be called without polling first
+    let response = crate::api_router(db.clone())
+        .with_state(db)
+        .call(request)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    match response.extensions().get::<ApiCallLogged>() {
+        Some(ApiCallLogged(replay_id)) => Ok(Redirect::to(&format!("/team/api-logs/{}", replay_id))),
+        None => Err(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
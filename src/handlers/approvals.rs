@@ -0,0 +1,83 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{Html, Redirect},
+};
+use askama::Template;
+use uuid::Uuid;
+
+use crate::{
+    database::Database,
+    handlers::team::create_audit_log,
+    middleware::{AuthUser, CurrentUser},
+    services::approvals::{self, Approval, APPROVAL_KINDS},
+};
+
+#[derive(Template)]
+#[template(path = "approvals/inbox.html")]
+struct ApprovalsTemplate {
+    current_user: CurrentUser,
+    approvals: Vec<Approval>,
+}
+
+pub async fn approvals_page(
+    AuthUser(current_user): AuthUser,
+    State(db): State<Database>,
+) -> Result<Html<String>, StatusCode> {
+    let approvals = approvals::pending(&db, &current_user).await.map_err(|e| {
+        tracing::error!("Error loading approvals: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let template = ApprovalsTemplate {
+        current_user,
+        approvals,
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+async fn decide(db: &Database, current_user: &CurrentUser, kind: &str, id: Uuid, approve: bool) -> Result<Redirect, StatusCode> {
+    if !APPROVAL_KINDS.iter().any(|(k, _, _)| *k == kind) {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    if !approvals::can_decide(current_user, kind) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let decided = approvals::decide(db, current_user, kind, id, approve).await.map_err(|e| {
+        tracing::error!("Error deciding {} {}: {}", kind, id, e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    // Someone else may have got there first; the inbox simply no longer shows it
+    if decided {
+        let status = if approve { "approved" } else { "denied" };
+        let _ = create_audit_log(
+            db,
+            current_user,
+            if approve { "approve" } else { "deny" }.to_string(),
+            kind.to_string(),
+            Some(id),
+            Some(serde_json::json!({ "status": "pending" })),
+            Some(serde_json::json!({ "status": status })),
+        ).await;
+    }
+
+    Ok(Redirect::to("/approvals"))
+}
+
+pub async fn approve(
+    AuthUser(current_user): AuthUser,
+    State(db): State<Database>,
+    Path((kind, id)): Path<(String, Uuid)>,
+) -> Result<Redirect, StatusCode> {
+    decide(&db, &current_user, &kind, id, true).await
+}
+
+pub async fn deny(
+    AuthUser(current_user): AuthUser,
+    State(db): State<Database>,
+    Path((kind, id)): Path<(String, Uuid)>,
+) -> Result<Redirect, StatusCode> {
+    decide(&db, &current_user, &kind, id, false).await
+}
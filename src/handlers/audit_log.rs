@@ -0,0 +1,441 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::{Html, IntoResponse, Response},
+};
+use askama::Template;
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    database::Database,
+    middleware::{AuthUser, CurrentUser},
+    models::AuditLogDisplay,
+    handlers::crm::require_access,
+    services::{
+        audit_log::{self, AuditFilter, Audited},
+        sharing::{Access, RecordKind},
+        teams,
+    },
+    utils::{
+        json_diff::FieldChange,
+        timezone::{format_local, from_local},
+    },
+};
+
+const PAGE_SIZE: i64 = 50;
+
+const TIME_FORMAT: &str = "%Y-%m-%d %H:%M:%S";
+
+#[derive(Template)]
+#[template(path = "team/audit.html")]
+struct AuditLogTemplate {
+    entries: Vec<AuditLogDisplay>,
+    users: Vec<(Uuid, String)>,
+    actions: Vec<String>,
+    resource_types: Vec<String>,
+    selected_user: String,
+    selected_action: String,
+    selected_resource_type: String,
+    selected_resource_id: String,
+    date_from: String,
+    date_to: String,
+    page: i64,
+    has_next: bool,
+    // The filters as a query string, for the paging links
+    filter_query: String,
+    current_user: CurrentUser,
+}
+
+impl AuditLogTemplate {
+    fn when(&self, entry: &AuditLogDisplay) -> String {
+        format_local(entry.created_at, self.current_user.timezone, TIME_FORMAT)
+    }
+
+    fn is_user(&self, id: &Uuid) -> bool {
+        self.selected_user == id.to_string()
+    }
+}
+
+#[derive(Template)]
+#[template(path = "team/audit_detail.html")]
+struct AuditLogDetailTemplate {
+    entry: AuditLogDisplay,
+    changes: Vec<FieldChange>,
+    current_user: CurrentUser,
+}
+
+impl AuditLogDetailTemplate {
+    fn when(&self) -> String {
+        format_local(self.entry.created_at, self.current_user.timezone, TIME_FORMAT)
+    }
+}
+
+#[derive(Template)]
+#[template(path = "team/audit_history.html")]
+struct RecordHistoryTemplate {
+    record: HistoryOf,
+    record_name: String,
+    entries: Vec<HistoryEntry>,
+    exported_at: String,
+    current_user: CurrentUser,
+}
+
+struct HistoryEntry {
+    when: String,
+    entry: AuditLogDisplay,
+    // Only the fields the entry changed
+    changes: Vec<FieldChange>,
+}
+
+// The records whose history can be downloaded from their own pages
+#[derive(Clone, Copy)]
+enum HistoryOf {
+    Customer,
+    Deal,
+    Expense,
+}
+
+impl HistoryOf {
+    fn audited(self) -> Audited {
+        match self {
+            Self::Customer => Audited::Customer,
+            Self::Deal => Audited::Deal,
+            Self::Expense => Audited::Expense,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::Customer => "customer",
+            Self::Deal => "deal",
+            Self::Expense => "expense",
+        }
+    }
+
+    // An expense is named by its amount only for those who may see it
+    fn name_query(self, finance: bool) -> &'static str {
+        match self {
+            Self::Customer => "SELECT company_name FROM customers WHERE id = $1",
+            Self::Deal => "SELECT title FROM deals WHERE id = $1",
+            Self::Expense if finance => {
+                "SELECT CONCAT(amount, ' on ', expense_date, COALESCE(' - ' || description, '')) FROM expenses WHERE id = $1"
+            }
+            Self::Expense => "SELECT CONCAT('Expense on ', expense_date, COALESCE(' - ' || description, '')) FROM expenses WHERE id = $1",
+        }
+    }
+
+    async fn require_access(self, db: &Database, current_user: &CurrentUser, id: Uuid) -> Result<(), StatusCode> {
+        match self {
+            Self::Customer => require_access(db, current_user, RecordKind::Customer, id, Access::Read).await.map(|_| ()),
+            Self::Deal => require_access(db, current_user, RecordKind::Deal, id, Access::Read).await.map(|_| ()),
+            Self::Expense => require_expense_access(db, current_user, id).await,
+        }
+    }
+}
+
+// Expenses aren't shared like customers and deals; their history is for
+// whoever submitted one and whoever may approve it
+async fn require_expense_access(db: &Database, current_user: &CurrentUser, id: Uuid) -> Result<(), StatusCode> {
+    let allowed = sqlx::query_scalar::<_, bool>(&format!(
+        "SELECT e.user_id = $2 OR {} FROM expenses e WHERE e.id = $1",
+        teams::expense_approval_condition(&current_user.permissions, "e", "$2")
+    ))
+    .bind(id)
+    .bind(current_user.id)
+    .fetch_optional(db)
+    .await
+    .map_err(|e| {
+        tracing::error!("Error checking expense access: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    match allowed {
+        None => Err(StatusCode::NOT_FOUND),
+        Some(false) => Err(StatusCode::FORBIDDEN),
+        Some(true) => Ok(()),
+    }
+}
+
+#[derive(Deserialize)]
+pub struct HistoryQuery {
+    // "csv" downloads it; otherwise it's a page to print or save as PDF
+    format: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct AuditLogQuery {
+    user_id: Option<String>,
+    action: Option<String>,
+    resource_type: Option<String>,
+    resource_id: Option<String>,
+    // Days in the viewer's timezone, both included
+    date_from: Option<String>,
+    date_to: Option<String>,
+    page: Option<i64>,
+}
+
+fn non_empty(value: Option<String>) -> Option<String> {
+    value.map(|value| value.trim().to_string()).filter(|value| !value.is_empty())
+}
+
+fn parse_uuid(value: &Option<String>) -> Result<Option<Uuid>, StatusCode> {
+    value
+        .as_deref()
+        .map(|value| Uuid::parse_str(value).map_err(|_| StatusCode::BAD_REQUEST))
+        .transpose()
+}
+
+// Start of the given day in the viewer's timezone, `days_after` it
+fn day_start(value: &Option<String>, days_after: i64, current_user: &CurrentUser) -> Result<Option<DateTime<Utc>>, StatusCode> {
+    let Some(value) = value else {
+        return Ok(None);
+    };
+    let day = NaiveDate::parse_from_str(value, "%Y-%m-%d").map_err(|_| StatusCode::BAD_REQUEST)?;
+    let midnight = (day + Duration::days(days_after)).and_hms_opt(0, 0, 0).ok_or(StatusCode::BAD_REQUEST)?;
+    Ok(Some(from_local(midnight, current_user.timezone)))
+}
+
+pub async fn audit_log_list(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Query(query): Query<AuditLogQuery>,
+) -> Result<Html<String>, StatusCode> {
+    current_user.require("audit:read")?;
+
+    let user_id = non_empty(query.user_id);
+    let action = non_empty(query.action);
+    let resource_type = non_empty(query.resource_type);
+    let resource_id = non_empty(query.resource_id);
+    let date_from = non_empty(query.date_from);
+    let date_to = non_empty(query.date_to);
+    let page = query.page.unwrap_or(1).max(1);
+
+    let filter = AuditFilter {
+        user_id: parse_uuid(&user_id)?,
+        action: action.clone(),
+        resource_type: resource_type.clone(),
+        resource_id: parse_uuid(&resource_id)?,
+        from: day_start(&date_from, 0, &current_user)?,
+        to: day_start(&date_to, 1, &current_user)?,
+    };
+
+    // One extra row tells whether there's another page
+    let mut entries = audit_log::search(&db, &filter, PAGE_SIZE + 1, (page - 1) * PAGE_SIZE)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error loading audit log: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    let has_next = entries.len() as i64 > PAGE_SIZE;
+    entries.truncate(PAGE_SIZE as usize);
+
+    let users = audit_log::actors(&db).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let actions = audit_log::actions(&db).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let resource_types = audit_log::resource_types(&db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let filter_query = [
+        ("user_id", &user_id),
+        ("action", &action),
+        ("resource_type", &resource_type),
+        ("resource_id", &resource_id),
+        ("date_from", &date_from),
+        ("date_to", &date_to),
+    ]
+    .iter()
+    .filter_map(|(name, value)| value.as_ref().map(|value| format!("{}={}&", name, urlencoding::encode(value))))
+    .collect();
+
+    let template = AuditLogTemplate {
+        entries,
+        users,
+        actions,
+        resource_types,
+        selected_user: user_id.unwrap_or_default(),
+        selected_action: action.unwrap_or_default(),
+        selected_resource_type: resource_type.unwrap_or_default(),
+        selected_resource_id: resource_id.unwrap_or_default(),
+        date_from: date_from.unwrap_or_default(),
+        date_to: date_to.unwrap_or_default(),
+        page,
+        has_next,
+        filter_query,
+        current_user,
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+// Everything recorded about a record, for disputes and compliance reviews.
+// Both formats are exports, so this needs audit:read and data:export on top
+// of access to the record. Financial fields are left out without finance:read.
+async fn record_history(
+    db: &Database,
+    current_user: CurrentUser,
+    record: HistoryOf,
+    id: Uuid,
+    query: HistoryQuery,
+) -> Result<Response, StatusCode> {
+    current_user.require("audit:read")?;
+    current_user.require("data:export")?;
+    record.require_access(db, &current_user, id).await?;
+
+    let access = current_user.field_access();
+    let record_name = sqlx::query_scalar::<_, String>(record.name_query(access.finance))
+        .bind(id)
+        .fetch_optional(db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let entries = audit_log::history(db, record.audited(), id).await.map_err(|e| {
+        tracing::error!("Error loading {} history: {}", record.label(), e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    let entries: Vec<HistoryEntry> = entries
+        .into_iter()
+        .map(|entry| HistoryEntry {
+            when: format_local(entry.created_at, current_user.timezone, TIME_FORMAT),
+            changes: audit_log::visible_changes(&entry, &access)
+                .into_iter()
+                .filter(FieldChange::changed)
+                .collect(),
+            entry,
+        })
+        .collect();
+
+    if query.format.as_deref() == Some("csv") {
+        let filter = format!("{} = {} ({})", record.label(), record_name, id);
+        let bytes = history_csv(&entries, &current_user, &filter).map_err(|e| {
+            tracing::error!("Error writing {} history: {}", record.label(), e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+        return Ok((
+            [
+                (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+                (
+                    header::CONTENT_DISPOSITION,
+                    format!("attachment; filename=\"{}-{}-history.csv\"", record.label(), id),
+                ),
+            ],
+            bytes,
+        )
+            .into_response());
+    }
+
+    let template = RecordHistoryTemplate {
+        record,
+        record_name,
+        entries,
+        exported_at: format_local(Utc::now(), current_user.timezone, TIME_FORMAT),
+        current_user,
+    };
+    Ok(Html(template.render().unwrap()).into_response())
+}
+
+// A row per changed field, or a single row for entries that changed none
+// (logins, shares and the like). Like the xlsx exports, it starts with who
+// exported it, when and for what, so a copy can be traced back.
+fn history_csv(entries: &[HistoryEntry], current_user: &CurrentUser, filter: &str) -> Result<Vec<u8>, csv::Error> {
+    let mut writer = csv::WriterBuilder::new().flexible(true).from_writer(Vec::new());
+    writer.write_record([format!(
+        "Exported by {} {} on {} UTC",
+        current_user.first_name,
+        current_user.last_name,
+        Utc::now().format("%Y-%m-%d %H:%M")
+    )])?;
+    writer.write_record([format!("Filters: {}", filter)])?;
+    writer.write_record([""])?;
+    writer.write_record([
+        format!("time ({})", current_user.timezone).as_str(),
+        "user",
+        "impersonated_by",
+        "action",
+        "field",
+        "old_value",
+        "new_value",
+        "ip_address",
+    ])?;
+
+    for HistoryEntry { when, entry, changes } in entries {
+        let row = |field: &str, old: &str, new: &str| {
+            [
+                when.as_str(),
+                entry.user_name.as_deref().unwrap_or("System"),
+                entry.impersonator_name.as_deref().unwrap_or(""),
+                entry.action.as_str(),
+                field,
+                old,
+                new,
+                entry.ip_address.as_deref().unwrap_or(""),
+            ]
+            .map(str::to_string)
+        };
+        if changes.is_empty() {
+            writer.write_record(row("", "", ""))?;
+        }
+        for change in changes {
+            writer.write_record(row(
+                &change.field,
+                change.old.as_deref().unwrap_or(""),
+                change.new.as_deref().unwrap_or(""),
+            ))?;
+        }
+    }
+
+    writer.into_inner().map_err(|e| csv::Error::from(e.into_error()))
+}
+
+pub async fn customer_history(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+    Query(query): Query<HistoryQuery>,
+) -> Result<Response, StatusCode> {
+    record_history(&db, current_user, HistoryOf::Customer, id, query).await
+}
+
+pub async fn deal_history(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+    Query(query): Query<HistoryQuery>,
+) -> Result<Response, StatusCode> {
+    record_history(&db, current_user, HistoryOf::Deal, id, query).await
+}
+
+pub async fn expense_history(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+    Query(query): Query<HistoryQuery>,
+) -> Result<Response, StatusCode> {
+    record_history(&db, current_user, HistoryOf::Expense, id, query).await
+}
+
+pub async fn audit_log_detail(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<Html<String>, StatusCode> {
+    current_user.require("audit:read")?;
+
+    let entry = audit_log::find(&db, id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error loading audit entry: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let changes = audit_log::visible_changes(&entry, &current_user.field_access());
+
+    let template = AuditLogDetailTemplate {
+        entry,
+        changes,
+        current_user,
+    };
+    Ok(Html(template.render().unwrap()))
+}
@@ -6,51 +6,55 @@ use axum::{
 use askama::Template;
 use serde::Deserialize;
 use tower_cookies::{Cookies, Cookie};
-use chrono::{Utc, Duration};
 use uuid::Uuid;
 
 use crate::{
     database::Database,
-    models::{CreateUser, User},
-    utils::{create_token, hash_password, verify_password},
+    middleware::ClientInfo,
+    models::User,
+    services::{
+        captcha::{self, CaptchaResponse},
+        login_attempts,
+        login_events::{self, Failure},
+        security::{self, LoginContext, DEVICE_COOKIE},
+        sessions,
+    },
+    utils::{create_token, verify_password},
 };
 
 #[derive(Template)]
 #[template(path = "login.html")]
 struct LoginTemplate {
     error: String,
+    captcha: Option<captcha::Widget>,
 }
 
-#[derive(Template)]
-#[template(path = "register.html")]
-struct RegisterTemplate {
-    error: String,
+enum LoginError {
+    Invalid,
+    Locked,
+    // Seconds until another attempt will be considered
+    Throttled(i64),
+    Database(sqlx::Error),
 }
 
-#[derive(Deserialize)]
-pub struct LoginForm {
-    email: String,
-    password: String,
+impl From<sqlx::Error> for LoginError {
+    fn from(e: sqlx::Error) -> Self {
+        LoginError::Database(e)
+    }
 }
 
 #[derive(Deserialize)]
-pub struct RegisterForm {
+pub struct LoginForm {
     email: String,
     password: String,
-    first_name: String,
-    last_name: String,
+    #[serde(flatten)]
+    captcha: CaptchaResponse,
 }
 
 pub async fn login_page() -> Html<String> {
     let template = LoginTemplate { 
-        error: String::new() 
-    };
-    Html(template.render().unwrap())
-}
-
-pub async fn register_page() -> Html<String> {
-    let template = RegisterTemplate { 
-        error: String::new() 
+        error: String::new(),
+        captcha: captcha::widget(),
     };
     Html(template.render().unwrap())
 }
@@ -58,31 +62,75 @@ pub async fn register_page() -> Html<String> {
 pub async fn login(
     State(db): State<Database>,
     cookies: Cookies,
+    client: ClientInfo,
     Form(form): Form<LoginForm>,
 ) -> Result<impl IntoResponse, (StatusCode, Html<String>)> {
-    match authenticate_user(&db, &form.email, &form.password).await {
+    if !captcha::verify(&form.captcha, client.ip_address.clone()).await {
+        let template = LoginTemplate {
+            error: "Please complete the CAPTCHA check".to_string(),
+            captcha: captcha::widget(),
+        };
+        return Err((StatusCode::BAD_REQUEST, Html(template.render().unwrap())));
+    }
+
+    // The browser's device cookie, so sign-ins from elsewhere stand out
+    let device_id = cookies
+        .get(DEVICE_COOKIE)
+        .map(|cookie| cookie.value().to_string())
+        .filter(|value| Uuid::parse_str(value).is_ok())
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+
+    let context = LoginContext {
+        device_id,
+        ip_address: client.ip_address.clone(),
+        user_agent: client.user_agent.clone(),
+    };
+
+    let result = authenticate_user(&db, &form.email, &form.password, context.ip_address.as_deref()).await;
+    let failure = match &result {
+        Ok(_) => None,
+        Err(LoginError::Invalid) => Some(Failure::InvalidCredentials),
+        Err(LoginError::Locked) => Some(Failure::Locked),
+        Err(LoginError::Throttled(_)) => Some(Failure::Throttled),
+        Err(LoginError::Database(_)) => None,
+    };
+    if result.is_ok() || failure.is_some() {
+        if let Err(e) = login_events::record(&db, &form.email, failure, &context).await {
+            tracing::error!("Error recording login event: {}", e);
+        }
+    }
+
+    match result {
         Ok(user) => {
-            // Create JWT token
-            let token = create_token(user.id, user.email.clone())
-                .map_err(|_| {
-                    let template = LoginTemplate {
-                        error: "Authentication failed".to_string(),
-                    };
-                    (StatusCode::INTERNAL_SERVER_ERROR, Html(template.render().unwrap()))
-                })?;
-            
-            // Create session record in database for additional tracking
-            let session_id = Uuid::new_v4();
-            let expires_at = Utc::now() + Duration::hours(24);
-            
-            let _ = sqlx::query!(
-                "INSERT INTO sessions (id, user_id, expires_at) VALUES ($1, $2, $3)",
-                session_id,
+            let failed = || {
+                let template = LoginTemplate {
+                    error: "Authentication failed".to_string(),
+                    captcha: captcha::widget(),
+                };
+                (StatusCode::INTERNAL_SERVER_ERROR, Html(template.render().unwrap()))
+            };
+
+            // Create the session first; the token is only valid while it stays active
+            let expires_at = sessions::expiry(&db).await.map_err(|e| {
+                tracing::error!("Error loading session lifetime: {}", e);
+                failed()
+            })?;
+            let session_id = sessions::start(
+                &db,
                 user.id,
-                expires_at
+                expires_at,
+                context.ip_address.as_deref(),
+                context.user_agent.as_deref(),
             )
-            .execute(&db)
-            .await;
+                .await
+                .map_err(|e| {
+                    tracing::error!("Error creating session for {}: {}", user.id, e);
+                    failed()
+                })?;
+
+            // Create JWT token
+            let token = create_token(user.id, user.email.clone(), session_id, expires_at)
+                .map_err(|_| failed())?;
             
             // Update last login
             let _ = sqlx::query!(
@@ -91,99 +139,123 @@ pub async fn login(
             )
             .execute(&db)
             .await;
+
+            if let Err(e) = security::record_login(&db, user.id, &context).await {
+                tracing::error!("Error recording login for {}: {}", user.id, e);
+            }
+            if let Err(e) = security::audit_session(&db, "login", user.id, None, session_id, &client).await {
+                tracing::error!("Error auditing login for {}: {}", user.id, e);
+            }
+
+            // Remember this browser
+            cookies.add(
+                Cookie::build((DEVICE_COOKIE, context.device_id))
+                    .path("/")
+                    .http_only(true)
+                    .max_age(time::Duration::days(365))
+                    .build(),
+            );
             
-            // Set secure HTTP-only cookie with JWT token
-            let cookie = Cookie::build(("auth_token", token))
-                .path("/")
-                .http_only(true)
-                .max_age(time::Duration::hours(24))
-                .build();
-            
-            cookies.add(cookie);
+            // Set secure HTTP-only cookie with JWT token; the idle timeout
+            // applies from the first request
+            sessions::set_cookie(&cookies, token, expires_at, None);
             
             Ok(Redirect::to("/dashboard"))
         }
-        Err(_) => {
+        Err(e) => {
+            let (status, error) = match e {
+                LoginError::Invalid => (StatusCode::UNAUTHORIZED, "Invalid email or password".to_string()),
+                LoginError::Locked => (
+                    StatusCode::FORBIDDEN,
+                    "This account is locked. Try again later or ask an administrator to unlock it.".to_string(),
+                ),
+                LoginError::Throttled(seconds) => {
+                    let wait = match seconds {
+                        1 => "1 second".to_string(),
+                        2..=59 => format!("{} seconds", seconds),
+                        _ => format!("{} minutes", (seconds + 59) / 60),
+                    };
+                    (
+                        StatusCode::TOO_MANY_REQUESTS,
+                        format!("Too many failed sign-in attempts. Try again in {}.", wait),
+                    )
+                }
+                LoginError::Database(e) => {
+                    tracing::error!("Error during sign-in: {}", e);
+                    (StatusCode::INTERNAL_SERVER_ERROR, "Authentication failed".to_string())
+                }
+            };
             let template = LoginTemplate {
-                error: "Invalid email or password".to_string(),
+                error,
+                captcha: captcha::widget(),
             };
-            Err((StatusCode::UNAUTHORIZED, Html(template.render().unwrap())))
+            Err((status, Html(template.render().unwrap())))
         }
     }
 }
 
-pub async fn logout(cookies: Cookies) -> impl IntoResponse {
-    cookies.remove(Cookie::from("auth_token"));
-    Redirect::to("/login")
-}
-
-pub async fn register(
-    State(db): State<Database>,
-    Form(form): Form<RegisterForm>,
-) -> Result<Redirect, (StatusCode, Html<String>)> {
-    let password_hash = hash_password(&form.password)
-        .map_err(|_| {
-            let template = RegisterTemplate {
-                error: "Failed to process password".to_string(),
-            };
-            (StatusCode::INTERNAL_SERVER_ERROR, Html(template.render().unwrap()))
-        })?;
-
-    let create_user = CreateUser {
-        email: form.email,
-        password: form.password,
-        first_name: form.first_name,
-        last_name: form.last_name,
-    };
-
-    match create_user_in_db(&db, &create_user, &password_hash).await {
-        Ok(_) => Ok(Redirect::to("/login")),
-        Err(_) => {
-            let template = RegisterTemplate {
-                error: "Email already exists or registration failed".to_string(),
-            };
-            Err((StatusCode::BAD_REQUEST, Html(template.render().unwrap())))
+pub async fn logout(State(db): State<Database>, cookies: Cookies, client: ClientInfo) -> impl IntoResponse {
+    // Remove the session row so the token stops working even if it was copied
+    if let Some(session_id) = sessions::from_cookies(&cookies) {
+        match sessions::remove(&db, session_id).await {
+            Ok(Some((user_id, impersonator_id))) => {
+                if let Err(e) = security::audit_session(&db, "logout", user_id, impersonator_id, session_id, &client).await {
+                    tracing::error!("Error auditing logout for {}: {}", user_id, e);
+                }
+            }
+            Ok(None) => {}
+            Err(e) => tracing::error!("Error ending session {}: {}", session_id, e),
         }
     }
+
+    cookies.remove(Cookie::from("auth_token"));
+    Redirect::to("/login")
 }
 
 async fn authenticate_user(
     db: &Database,
     email: &str,
     password: &str,
-) -> Result<User, sqlx::Error> {
+    ip_address: Option<&str>,
+) -> Result<User, LoginError> {
+    // Back off before looking at the password at all
+    if let Some(seconds) = login_attempts::retry_after(db, email, ip_address).await? {
+        return Err(LoginError::Throttled(seconds));
+    }
+
     let user = sqlx::query_as::<_, User>(
-        "SELECT * FROM users WHERE email = $1 AND is_active = true AND is_locked = false"
+        "SELECT * FROM users WHERE email = $1 AND is_active = true"
     )
     .bind(email)
-    .fetch_one(db)
+    .fetch_optional(db)
     .await?;
 
-    if verify_password(password, &user.password_hash).unwrap_or(false) {
-        Ok(user)
-    } else {
-        Err(sqlx::Error::RowNotFound)
+    let Some(mut user) = user else {
+        login_attempts::record(db, email, None, ip_address, false).await?;
+        return Err(LoginError::Invalid);
+    };
+
+    if user.is_locked && login_attempts::release_expired_lock(db, user.id).await? {
+        user.is_locked = false;
     }
-}
 
-async fn create_user_in_db(
-    db: &Database,
-    user_data: &CreateUser,
-    password_hash: &str,
-) -> Result<User, sqlx::Error> {
-    let user = sqlx::query_as::<_, User>(
-        r#"
-        INSERT INTO users (email, password_hash, first_name, last_name)
-        VALUES ($1, $2, $3, $4)
-        RETURNING *
-        "#,
-    )
-    .bind(&user_data.email)
-    .bind(password_hash)
-    .bind(&user_data.first_name)
-    .bind(&user_data.last_name)
-    .fetch_one(db)
-    .await?;
+    let valid = verify_password(password, &user.password_hash).unwrap_or(false);
+    login_attempts::record(db, email, Some(user.id), ip_address, valid && !user.is_locked).await?;
+
+    if !valid {
+        if let Some(failures) = login_attempts::count_failure(db, user.id).await? {
+            if let Err(e) = security::record_lockout(db, user.id, failures, ip_address).await {
+                tracing::error!("Error recording lockout for {}: {}", user.id, e);
+            }
+        }
+        return Err(LoginError::Invalid);
+    }
+
+    // Only someone who knows the password learns the account is locked
+    if user.is_locked {
+        return Err(LoginError::Locked);
+    }
 
+    login_attempts::reset(db, user.id).await?;
     Ok(user)
-}
\ No newline at end of file
+}
@@ -0,0 +1,606 @@
+use axum::{
+    extract::{Form, Path, Query, State},
+    http::StatusCode,
+    response::{Html, Redirect},
+};
+use askama::Template;
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    database::Database,
+    handlers::{
+        crm::{parse_optional_decimal, parse_optional_id, require_access},
+        team::create_audit_log,
+    },
+    middleware::{AuthUser, CurrentUser},
+    models::{BlanketOrder, BlanketOrderLine, BlanketOrderRelease, Customer, InventoryItem, Warehouse, BLANKET_ORDER_SELECT, BLANKET_ORDER_STATUSES},
+    services::{
+        blanket_orders::{self, NewRelease},
+        documents::{self, Document, DocumentLine},
+        exchange_rates,
+        numbering,
+        part_numbers,
+        pricing_agreements,
+        sharing::{self, Access, RecordKind},
+    },
+    utils::locale,
+};
+
+#[derive(Template)]
+#[template(path = "crm/blanket_orders.html")]
+struct BlanketOrdersTemplate {
+    orders: Vec<BlanketOrder>,
+}
+
+#[derive(Template)]
+#[template(path = "crm/blanket_order_form.html")]
+struct BlanketOrderFormTemplate {
+    customers: Vec<Customer>,
+    customer_id: Option<Uuid>,
+    today: NaiveDate,
+}
+
+#[derive(Template)]
+#[template(path = "crm/blanket_order_detail.html")]
+struct BlanketOrderDetailTemplate {
+    order: BlanketOrder,
+    lines: Vec<BlanketOrderLine>,
+    releases: Vec<BlanketOrderRelease>,
+    // Offered when adding a line or scheduling a release; empty when the viewer can't
+    items: Vec<InventoryItem>,
+    warehouses: Vec<Warehouse>,
+    can_edit: bool,
+    show_prices: bool,
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct NewBlanketOrderQuery {
+    customer_id: Option<Uuid>,
+}
+
+#[derive(Deserialize)]
+pub struct BlanketOrderQuery {
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct BlanketOrderForm {
+    customer_id: Uuid,
+    reference: String,
+    starts_on: NaiveDate,
+    ends_on: NaiveDate,
+    reserve_days_ahead: i32,
+    currency: Option<String>,
+    notes: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct BlanketOrderLineForm {
+    item_id: Option<String>,
+    // Our SKU or UPC, or the customer's own part number; used when no item is picked
+    item_code: Option<String>,
+    quantity: i32,
+    // Defaults to the item's selling price
+    unit_price: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ReleaseForm {
+    line_id: Uuid,
+    warehouse_id: Uuid,
+    quantity: i32,
+    scheduled_for: NaiveDate,
+}
+
+#[derive(Deserialize)]
+pub struct FinishForm {
+    status: String,
+}
+
+// The order, after checking the user may see (or change) its customer
+async fn load_order(db: &Database, user: &CurrentUser, id: Uuid, needed: Access) -> Result<(BlanketOrder, Access), StatusCode> {
+    let order = blanket_orders::find(db, id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error loading blanket order: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let access = require_access(db, user, RecordKind::Customer, order.customer_id, needed).await?;
+    Ok((order, access))
+}
+
+fn back_to(id: Uuid, error: Option<&str>) -> Redirect {
+    match error {
+        Some(error) => Redirect::to(&format!("/crm/blanket-orders/{}?error={}", id, urlencoding::encode(error))),
+        None => Redirect::to(&format!("/crm/blanket-orders/{}", id)),
+    }
+}
+
+pub async fn blanket_orders_list(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+) -> Result<Html<String>, StatusCode> {
+    current_user.require("inventory:read")?;
+
+    let scope = if sharing::is_scoped(&current_user) {
+        format!("WHERE {}", sharing::visibility_condition(RecordKind::Customer, "c", 1))
+    } else {
+        String::new()
+    };
+
+    let orders = sqlx::query_as::<_, BlanketOrder>(&format!(
+        "{} {} ORDER BY o.status = 'open' DESC, o.ends_on, c.company_name",
+        BLANKET_ORDER_SELECT, scope
+    ))
+    .bind(current_user.id)
+    .fetch_all(&db)
+    .await
+    .map_err(|e| {
+        tracing::error!("Error loading blanket orders: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let template = BlanketOrdersTemplate { orders };
+    Ok(Html(template.render().unwrap()))
+}
+
+pub async fn blanket_order_form(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Query(query): Query<NewBlanketOrderQuery>,
+) -> Result<Html<String>, StatusCode> {
+    current_user.require("inventory:write")?;
+
+    let scope = if sharing::is_scoped(&current_user) {
+        format!("WHERE {}", sharing::visibility_condition(RecordKind::Customer, "c", 1))
+    } else {
+        String::new()
+    };
+    let customers = sqlx::query_as::<_, Customer>(&format!(
+        "SELECT c.* FROM customers c {} ORDER BY c.company_name",
+        scope
+    ))
+    .bind(current_user.id)
+    .fetch_all(&db)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let template = BlanketOrderFormTemplate {
+        customers,
+        customer_id: query.customer_id,
+        today: chrono::Utc::now().date_naive(),
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+pub async fn create_blanket_order(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Form(form): Form<BlanketOrderForm>,
+) -> Result<Redirect, StatusCode> {
+    current_user.require("inventory:write")?;
+    require_access(&db, &current_user, RecordKind::Customer, form.customer_id, Access::Write).await?;
+
+    let reference = form.reference.trim();
+    let currency = form
+        .currency
+        .as_deref()
+        .map(|c| c.trim().to_uppercase())
+        .filter(|c| !c.is_empty())
+        .unwrap_or_else(|| "USD".to_string());
+    if reference.is_empty()
+        || reference.len() > 100
+        || form.ends_on < form.starts_on
+        || !(0..=90).contains(&form.reserve_days_ahead)
+        || currency.len() != 3
+    {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let notes = form.notes.as_deref().map(str::trim).filter(|n| !n.is_empty());
+
+    let create = async {
+        let exchange_rate = exchange_rates::rate_for(&db, &currency).await?;
+        let mut tx = db.begin().await?;
+        let order_number = numbering::next(&mut tx, "sales_order").await?;
+        let id = sqlx::query_scalar::<_, Uuid>(
+            r#"
+            INSERT INTO blanket_orders (
+                customer_id, order_number, reference, starts_on, ends_on, reserve_days_ahead, currency, notes, created_by,
+                exchange_rate
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+            RETURNING id
+            "#,
+        )
+        .bind(form.customer_id)
+        .bind(&order_number)
+        .bind(reference)
+        .bind(form.starts_on)
+        .bind(form.ends_on)
+        .bind(form.reserve_days_ahead)
+        .bind(&currency)
+        .bind(notes)
+        .bind(current_user.id)
+        .bind(exchange_rate)
+        .fetch_one(&mut *tx)
+        .await?;
+        tx.commit().await?;
+        Ok::<_, sqlx::Error>((id, order_number))
+    };
+    let (id, order_number) = create.await.map_err(|e| {
+        tracing::error!("Error creating blanket order: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let _ = create_audit_log(
+        &db,
+        &current_user,
+        "create".to_string(),
+        "blanket_order".to_string(),
+        Some(id),
+        None,
+        Some(serde_json::json!({
+            "customer_id": form.customer_id,
+            "order_number": order_number,
+            "reference": reference,
+            "starts_on": form.starts_on,
+            "ends_on": form.ends_on,
+        })),
+    )
+    .await;
+
+    Ok(back_to(id, None))
+}
+
+pub async fn blanket_order_detail(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+    Query(query): Query<BlanketOrderQuery>,
+) -> Result<Html<String>, StatusCode> {
+    current_user.require("inventory:read")?;
+    let (order, access) = load_order(&db, &current_user, id, Access::Read).await?;
+
+    let lines = blanket_orders::lines(&db, id).await.map_err(|e| {
+        tracing::error!("Error loading blanket order lines: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    let releases = blanket_orders::releases(&db, id).await.map_err(|e| {
+        tracing::error!("Error loading blanket order releases: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let can_edit = order.is_open()
+        && access >= Access::Write
+        && current_user.permissions.iter().any(|p| p == "inventory:write");
+    let (items, warehouses) = if can_edit {
+        let items = sqlx::query_as::<_, InventoryItem>(
+            r#"
+            SELECT * FROM inventory_items i
+            WHERE i.is_active = true
+              AND NOT EXISTS (SELECT 1 FROM inventory_items v WHERE v.parent_item_id = i.id)
+            ORDER BY i.item_name
+            "#,
+        )
+        .fetch_all(&db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        let warehouses = sqlx::query_as::<_, Warehouse>("SELECT * FROM warehouses WHERE is_active = true ORDER BY name")
+            .fetch_all(&db)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        (items, warehouses)
+    } else {
+        (Vec::new(), Vec::new())
+    };
+
+    let template = BlanketOrderDetailTemplate {
+        order,
+        lines,
+        releases,
+        items,
+        warehouses,
+        can_edit,
+        show_prices: current_user.has_finance_read,
+        error: query.error,
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+pub async fn add_blanket_order_line(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+    Form(form): Form<BlanketOrderLineForm>,
+) -> Result<Redirect, StatusCode> {
+    current_user.require("inventory:write")?;
+    let (order, _) = load_order(&db, &current_user, id, Access::Write).await?;
+    if !order.is_open() {
+        return Ok(back_to(id, Some("Lines can only be added to an open order")));
+    }
+
+    // The item can be picked, or typed as any code the customer knows it by
+    let (item_id, customer_part_number) = match parse_optional_id(&form.item_id)? {
+        Some(item_id) => {
+            let part_number = part_numbers::for_item(&db, order.customer_id, item_id)
+                .await
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            (item_id, part_number)
+        }
+        None => {
+            let code = form.item_code.as_deref().unwrap_or_default();
+            let resolved = part_numbers::resolve(&db, order.customer_id, code)
+                .await
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            match resolved {
+                Some(resolved) => (resolved.item_id, resolved.customer_part_number),
+                None => return Ok(back_to(id, Some("No item matches that code"))),
+            }
+        }
+    };
+
+    let selling_price = sqlx::query_scalar::<_, Option<Decimal>>("SELECT selling_price FROM inventory_items WHERE id = $1")
+        .bind(item_id)
+        .fetch_optional(&db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::BAD_REQUEST)?;
+    // Only those who can see prices may set one
+    let entered = if current_user.has_finance_read { parse_optional_decimal(&form.unit_price)? } else { None };
+    // Otherwise the customer's agreed price, then the list price
+    let agreed = match entered {
+        Some(_) => None,
+        None => pricing_agreements::in_force(&db, order.customer_id, item_id, chrono::Utc::now().date_naive())
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .and_then(|agreement| agreement.unit_price()),
+    };
+    let Some(unit_price) = entered.or(agreed).or(selling_price) else {
+        return Ok(back_to(id, Some("That item has no selling price; enter a unit price")));
+    };
+    if form.quantity <= 0 || unit_price < Decimal::ZERO {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let result = sqlx::query_scalar::<_, Uuid>(
+        r#"
+        INSERT INTO blanket_order_lines (blanket_order_id, item_id, quantity, unit_price, customer_part_number)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING id
+        "#,
+    )
+    .bind(id)
+    .bind(item_id)
+    .bind(form.quantity)
+    .bind(unit_price)
+    .bind(&customer_part_number)
+    .fetch_one(&db)
+    .await;
+    let line_id = match result {
+        Ok(line_id) => line_id,
+        Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+            return Ok(back_to(id, Some("That item is already on this order")));
+        }
+        Err(e) => {
+            tracing::error!("Error adding blanket order line: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    let _ = create_audit_log(
+        &db,
+        &current_user,
+        "add_line".to_string(),
+        "blanket_order".to_string(),
+        Some(id),
+        None,
+        Some(serde_json::json!({ "line_id": line_id, "item_id": item_id, "quantity": form.quantity })),
+    )
+    .await;
+
+    Ok(back_to(id, None))
+}
+
+pub async fn schedule_blanket_order_release(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+    Form(form): Form<ReleaseForm>,
+) -> Result<Redirect, StatusCode> {
+    current_user.require("inventory:write")?;
+    let (order, _) = load_order(&db, &current_user, id, Access::Write).await?;
+
+    let release = NewRelease {
+        line_id: form.line_id,
+        warehouse_id: form.warehouse_id,
+        quantity: form.quantity,
+        scheduled_for: form.scheduled_for,
+    };
+    let scheduled = blanket_orders::schedule_release(&db, &order, release, current_user.id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error scheduling release: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    let release_id = match scheduled {
+        Ok(release_id) => release_id,
+        Err(error) => return Ok(back_to(id, Some(&error))),
+    };
+
+    let _ = create_audit_log(
+        &db,
+        &current_user,
+        "schedule_release".to_string(),
+        "blanket_order".to_string(),
+        Some(id),
+        None,
+        Some(serde_json::json!({
+            "release_id": release_id,
+            "line_id": form.line_id,
+            "quantity": form.quantity,
+            "scheduled_for": form.scheduled_for,
+        })),
+    )
+    .await;
+
+    Ok(back_to(id, None))
+}
+
+pub async fn ship_blanket_order_release(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path((id, release_id)): Path<(Uuid, Uuid)>,
+) -> Result<Redirect, StatusCode> {
+    current_user.require("inventory:write")?;
+    let (order, _) = load_order(&db, &current_user, id, Access::Write).await?;
+
+    let shipped = blanket_orders::ship_release(&db, &order, release_id, current_user.id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error shipping release: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    if let Err(reason) = shipped {
+        return Ok(back_to(id, Some(&reason)));
+    }
+
+    let _ = create_audit_log(
+        &db,
+        &current_user,
+        "ship_release".to_string(),
+        "blanket_order".to_string(),
+        Some(id),
+        None,
+        Some(serde_json::json!({ "release_id": release_id })),
+    )
+    .await;
+
+    Ok(back_to(id, None))
+}
+
+pub async fn cancel_blanket_order_release(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path((id, release_id)): Path<(Uuid, Uuid)>,
+) -> Result<Redirect, StatusCode> {
+    current_user.require("inventory:write")?;
+    load_order(&db, &current_user, id, Access::Write).await?;
+
+    let cancelled = blanket_orders::cancel_release(&db, id, release_id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error cancelling release: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    if cancelled {
+        let _ = create_audit_log(
+            &db,
+            &current_user,
+            "cancel_release".to_string(),
+            "blanket_order".to_string(),
+            Some(id),
+            None,
+            Some(serde_json::json!({ "release_id": release_id })),
+        )
+        .await;
+    }
+
+    Ok(back_to(id, None))
+}
+
+// The order printed with the sales order layout, in the customer's language
+pub async fn blanket_order_document(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<Html<String>, StatusCode> {
+    current_user.require("inventory:read")?;
+    let (order, _) = load_order(&db, &current_user, id, Access::Read).await?;
+
+    let lines = blanket_orders::lines(&db, id).await.map_err(|e| {
+        tracing::error!("Error loading blanket order lines: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    let layout = documents::find(&db, "sales_order")
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let organization = documents::organization_name(&db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let base_currency = exchange_rates::base_currency(&db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let locale = locale::locale(order.preferred_language.as_deref());
+
+    let document = Document {
+        number: order.order_number.clone().unwrap_or_else(|| order.reference.clone()),
+        issued_on: order.created_at.map_or(order.starts_on, |created| created.date_naive()),
+        party_heading: "customer",
+        party_name: order.company_name.clone(),
+        reference: Some(order.reference.clone()),
+        currency: order.currency.clone(),
+        exchange_rate: order.exchange_rate,
+        base_currency,
+        lines: lines
+            .into_iter()
+            .map(|line| DocumentLine {
+                sku: line.sku,
+                customer_part_number: line.customer_part_number,
+                description: line.item_name,
+                quantity: i64::from(line.quantity),
+                unit_price: current_user.has_finance_read.then_some(line.unit_price),
+            })
+            .collect(),
+        notes: Some(
+            locale
+                .text("delivery_window")
+                .replace("{from}", &locale.date(order.starts_on))
+                .replace("{to}", &locale.date(order.ends_on)),
+        ),
+        language: order.preferred_language.clone(),
+    };
+    let html = documents::render(&layout, &organization, &document).map_err(|e| {
+        tracing::error!("Error rendering blanket order document: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    Ok(Html(html))
+}
+
+// Close (fulfilled as far as it goes) or cancel an open order
+pub async fn finish_blanket_order(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+    Form(form): Form<FinishForm>,
+) -> Result<Redirect, StatusCode> {
+    current_user.require("inventory:write")?;
+    if form.status == "open" || !BLANKET_ORDER_STATUSES.contains(&form.status.as_str()) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let (order, _) = load_order(&db, &current_user, id, Access::Write).await?;
+
+    let finished = blanket_orders::finish(&db, id, &form.status).await.map_err(|e| {
+        tracing::error!("Error finishing blanket order: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    if finished {
+        let _ = create_audit_log(
+            &db,
+            &current_user,
+            "update".to_string(),
+            "blanket_order".to_string(),
+            Some(id),
+            Some(serde_json::json!({ "status": order.status })),
+            Some(serde_json::json!({ "status": form.status })),
+        )
+        .await;
+    }
+
+    Ok(back_to(id, None))
+}
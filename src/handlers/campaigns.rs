@@ -0,0 +1,340 @@
+use axum::{
+    extract::{Form, Path, State},
+    http::StatusCode,
+    response::{Html, Redirect, Response},
+};
+use askama::Template;
+use chrono::NaiveDate;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    database::Database,
+    middleware::AuthUser,
+    models::{Campaign, CampaignDisplay, CampaignRoi, UtmLeadSummary, CAMPAIGN_CHANNELS},
+    utils::xlsx::{ColumnType, XlsxExport},
+};
+
+#[derive(Template)]
+#[template(path = "crm/campaigns.html")]
+struct CampaignsTemplate {
+    campaigns: Vec<CampaignDisplay>,
+    can_write: bool,
+}
+
+#[derive(Template)]
+#[template(path = "crm/campaign_form.html")]
+struct CampaignFormTemplate {
+    campaign: Option<CampaignDisplay>,
+    channels: Vec<String>,
+}
+
+#[derive(Template)]
+#[template(path = "crm/campaign_roi.html")]
+struct CampaignRoiTemplate {
+    rows: Vec<CampaignRoi>,
+    total_cost: String,
+    total_pipeline: String,
+    total_revenue: String,
+    utm_leads: Vec<UtmLeadSummary>,
+    can_export: bool,
+}
+
+#[derive(Deserialize)]
+pub struct CampaignForm {
+    name: String,
+    channel: String,
+    cost: rust_decimal::Decimal,
+    currency: String,
+    start_date: Option<String>,
+    end_date: Option<String>,
+    description: Option<String>,
+    utm_campaign: Option<String>,
+}
+
+fn parse_date(value: &Option<String>) -> Result<Option<NaiveDate>, StatusCode> {
+    match value {
+        Some(date) if !date.trim().is_empty() => NaiveDate::parse_from_str(date, "%Y-%m-%d")
+            .map(Some)
+            .map_err(|_| StatusCode::BAD_REQUEST),
+        _ => Ok(None),
+    }
+}
+
+fn validate(form: &CampaignForm) -> Result<(Option<NaiveDate>, Option<NaiveDate>), StatusCode> {
+    if form.name.trim().is_empty() || !CAMPAIGN_CHANNELS.contains(&form.channel.as_str()) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    if form.cost.is_sign_negative() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let start_date = parse_date(&form.start_date)?;
+    let end_date = parse_date(&form.end_date)?;
+    if let (Some(start), Some(end)) = (start_date, end_date) {
+        if end < start {
+            return Err(StatusCode::BAD_REQUEST);
+        }
+    }
+
+    Ok((start_date, end_date))
+}
+
+fn utm_campaign(form: &CampaignForm) -> Option<String> {
+    form.utm_campaign
+        .as_deref()
+        .map(str::trim)
+        .filter(|value| !value.is_empty())
+        .map(str::to_string)
+}
+
+pub async fn campaigns_list(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+) -> Result<Html<String>, StatusCode> {
+    current_user.require("campaigns:read")?;
+
+    let campaigns = sqlx::query_as::<_, Campaign>(
+        "SELECT * FROM campaigns ORDER BY start_date DESC NULLS LAST, name"
+    )
+    .fetch_all(&db)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    .into_iter()
+    .map(CampaignDisplay::from)
+    .collect();
+
+    let can_write = current_user.permissions.contains(&"campaigns:write".to_string());
+
+    let template = CampaignsTemplate { campaigns, can_write };
+    Ok(Html(template.render().unwrap()))
+}
+
+pub async fn campaign_form(
+    AuthUser(current_user): AuthUser,
+) -> Result<Html<String>, StatusCode> {
+    current_user.require("campaigns:write")?;
+
+    let template = CampaignFormTemplate {
+        campaign: None,
+        channels: CAMPAIGN_CHANNELS.iter().map(|c| c.to_string()).collect(),
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+pub async fn campaign_edit_form(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<Html<String>, StatusCode> {
+    current_user.require("campaigns:write")?;
+
+    let campaign = sqlx::query_as::<_, Campaign>("SELECT * FROM campaigns WHERE id = $1")
+        .bind(id)
+        .fetch_one(&db)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+
+    let template = CampaignFormTemplate {
+        campaign: Some(campaign.into()),
+        channels: CAMPAIGN_CHANNELS.iter().map(|c| c.to_string()).collect(),
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+pub async fn create_campaign(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Form(form): Form<CampaignForm>,
+) -> Result<Redirect, StatusCode> {
+    current_user.require("campaigns:write")?;
+
+    let (start_date, end_date) = validate(&form)?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO campaigns (name, channel, cost, currency, start_date, end_date, description, created_by, utm_campaign)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+        "#,
+    )
+    .bind(form.name.trim())
+    .bind(&form.channel)
+    .bind(form.cost)
+    .bind(&form.currency)
+    .bind(start_date)
+    .bind(end_date)
+    .bind(&form.description)
+    .bind(current_user.id)
+    .bind(utm_campaign(&form))
+    .execute(&db)
+    .await
+    .map_err(|e| {
+        tracing::error!("Error creating campaign: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    Ok(Redirect::to("/crm/campaigns"))
+}
+
+pub async fn update_campaign(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+    Form(form): Form<CampaignForm>,
+) -> Result<Redirect, StatusCode> {
+    current_user.require("campaigns:write")?;
+
+    let (start_date, end_date) = validate(&form)?;
+
+    sqlx::query(
+        r#"
+        UPDATE campaigns SET
+            name = $2, channel = $3, cost = $4, currency = $5,
+            start_date = $6, end_date = $7, description = $8, utm_campaign = $9
+        WHERE id = $1
+        "#,
+    )
+    .bind(id)
+    .bind(form.name.trim())
+    .bind(&form.channel)
+    .bind(form.cost)
+    .bind(&form.currency)
+    .bind(start_date)
+    .bind(end_date)
+    .bind(&form.description)
+    .bind(utm_campaign(&form))
+    .execute(&db)
+    .await
+    .map_err(|e| {
+        tracing::error!("Error updating campaign: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    Ok(Redirect::to("/crm/campaigns"))
+}
+
+// A deal is attributed to its own campaign, falling back to its customer's campaign
+async fn load_roi_rows(db: &Database) -> Result<Vec<CampaignRoi>, StatusCode> {
+    sqlx::query_as::<_, CampaignRoi>(
+        r#"
+        WITH attributed AS (
+            SELECT d.id, d.stage, COALESCE(d.base_value, 0) as value,
+                   COALESCE(d.campaign_id, c.campaign_id) as campaign_id
+            FROM deals d
+            JOIN customers c ON c.id = d.customer_id
+        )
+        SELECT
+            cp.id,
+            cp.name,
+            cp.channel,
+            cp.cost,
+            (SELECT COUNT(*) FROM customers WHERE campaign_id = cp.id) as customers,
+            COUNT(a.id) as deals,
+            COALESCE(SUM(a.value) FILTER (WHERE a.stage NOT IN ('closed_won', 'closed_lost')), 0) as pipeline_value,
+            COUNT(a.id) FILTER (WHERE a.stage = 'closed_won') as won_deals,
+            COALESCE(SUM(a.value) FILTER (WHERE a.stage = 'closed_won'), 0) as won_revenue
+        FROM campaigns cp
+        LEFT JOIN attributed a ON a.campaign_id = cp.id
+        GROUP BY cp.id
+        ORDER BY cp.start_date DESC NULLS LAST, cp.name
+        "#,
+    )
+    .fetch_all(db)
+    .await
+    .map_err(|e| {
+        tracing::error!("Error loading campaign ROI: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
+
+// ROI report
+pub async fn campaign_roi_report(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+) -> Result<Html<String>, StatusCode> {
+    current_user.require("campaigns:read")?;
+
+    let rows = load_roi_rows(&db).await?;
+
+    let utm_leads = sqlx::query_as::<_, UtmLeadSummary>(
+        r#"
+        SELECT
+            COALESCE(c.utm_source, '') as utm_source,
+            COALESCE(c.utm_medium, '') as utm_medium,
+            COALESCE(c.utm_campaign, '') as utm_campaign,
+            cp.name as campaign_name,
+            COUNT(DISTINCT c.id) as leads,
+            COUNT(d.id) as deals,
+            COALESCE(SUM(d.base_value) FILTER (WHERE d.stage = 'closed_won'), 0) as won_revenue
+        FROM customers c
+        LEFT JOIN campaigns cp ON cp.id = c.campaign_id
+        LEFT JOIN deals d ON d.customer_id = c.id
+        WHERE c.lead_source = 'web_form'
+        GROUP BY c.utm_source, c.utm_medium, c.utm_campaign, cp.name
+        ORDER BY leads DESC
+        "#,
+    )
+    .fetch_all(&db)
+    .await
+    .map_err(|e| {
+        tracing::error!("Error loading UTM lead summary: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let total_cost: rust_decimal::Decimal = rows.iter().map(|r| r.cost).sum();
+    let total_pipeline: rust_decimal::Decimal = rows.iter().map(|r| r.pipeline_value).sum();
+    let total_revenue: rust_decimal::Decimal = rows.iter().map(|r| r.won_revenue).sum();
+
+    let template = CampaignRoiTemplate {
+        rows,
+        total_cost: format!("{:.2}", total_cost),
+        total_pipeline: format!("{:.2}", total_pipeline),
+        total_revenue: format!("{:.2}", total_revenue),
+        utm_leads,
+        can_export: current_user.has_data_export,
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+pub async fn campaign_roi_export(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+) -> Result<Response, StatusCode> {
+    if !current_user.has_data_export {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    current_user.require("campaigns:read")?;
+
+    let rows = load_roi_rows(&db).await?;
+
+    let mut export = XlsxExport::new("Campaign ROI", &[
+        ("Campaign", ColumnType::Text),
+        ("Channel", ColumnType::Text),
+        ("Cost", ColumnType::Currency),
+        ("Customers", ColumnType::Integer),
+        ("Deals", ColumnType::Integer),
+        ("Open Pipeline", ColumnType::Currency),
+        ("Won Deals", ColumnType::Integer),
+        ("Won Revenue", ColumnType::Currency),
+        ("ROI", ColumnType::Percent),
+    ]);
+
+    for row in rows {
+        let roi = row.roi_percent().map(|percent| percent as f64 / 100.0);
+        export.row(vec![
+            row.name.into(),
+            row.channel.replace('_', " ").into(),
+            row.cost.into(),
+            row.customers.into(),
+            row.deals.into(),
+            row.pipeline_value.into(),
+            row.won_deals.into(),
+            row.won_revenue.into(),
+            roi.into(),
+        ]);
+    }
+
+    let generated_by = format!("{} {} <{}>", current_user.first_name, current_user.last_name, current_user.email);
+    Ok(export.into_response("campaign-roi.xlsx", &generated_by))
+}
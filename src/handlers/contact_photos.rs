@@ -0,0 +1,169 @@
+use axum::{
+    body::Bytes,
+    extract::{Path, State},
+    http::StatusCode,
+    response::{IntoResponse, Json, Redirect, Response},
+};
+use axum_extra::extract::Multipart;
+use serde::Serialize;
+use uuid::Uuid;
+
+use crate::{
+    database::Database,
+    handlers::crm::require_access,
+    middleware::{AuthUser, CurrentUser},
+    services::{
+        audit_log::{audited_execute, Audited},
+        ocr,
+        sharing::{Access, RecordKind},
+        storage,
+    },
+    utils::business_card::{self, CardDetails},
+};
+
+// Storage folders for the two kinds of image
+pub const PHOTOS: &str = "contact_photos";
+pub const BUSINESS_CARDS: &str = "business_cards";
+
+// The named file from an upload form, if one was chosen
+async fn uploaded_file(mut multipart: Multipart, name: &str) -> Result<Option<(String, Bytes)>, StatusCode> {
+    while let Some(field) = multipart.next_field().await.map_err(|_| StatusCode::BAD_REQUEST)? {
+        if field.name() != Some(name) {
+            continue;
+        }
+        let filename = field.file_name().unwrap_or_default().to_string();
+        let data = field.bytes().await.map_err(|_| StatusCode::BAD_REQUEST)?;
+        if !filename.is_empty() && !data.is_empty() {
+            return Ok(Some((filename, data)));
+        }
+    }
+    Ok(None)
+}
+
+async fn contact_photo_url(db: &Database, customer_id: Uuid, contact_id: Uuid) -> Result<Option<String>, StatusCode> {
+    sqlx::query_scalar::<_, Option<String>>("SELECT photo_url FROM contacts WHERE id = $1 AND customer_id = $2")
+        .bind(contact_id)
+        .bind(customer_id)
+        .fetch_optional(db)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error loading contact photo: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+// Points the contact at `photo_url`, removing the photo it replaces
+async fn set_photo(
+    db: &Database,
+    current_user: &CurrentUser,
+    customer_id: Uuid,
+    contact_id: Uuid,
+    photo_url: Option<String>,
+) -> Result<(), StatusCode> {
+    let previous = contact_photo_url(db, customer_id, contact_id).await?;
+    let update = sqlx::query(
+        "UPDATE contacts SET photo_url = $1, updated_at = NOW() WHERE id = $2 AND customer_id = $3",
+    )
+    .bind(&photo_url)
+    .bind(contact_id)
+    .bind(customer_id);
+    audited_execute(db, current_user, "update", Audited::Contact, contact_id, update)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error saving contact photo: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    if let Some(previous) = previous.filter(|previous| Some(previous) != photo_url.as_ref()) {
+        storage::remove(PHOTOS, &previous).await;
+    }
+    Ok(())
+}
+
+pub async fn upload_contact_photo(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path((customer_id, contact_id)): Path<(Uuid, Uuid)>,
+    multipart: Multipart,
+) -> Result<Redirect, StatusCode> {
+    current_user.require("customers:write")?;
+    require_access(&db, &current_user, RecordKind::Customer, customer_id, Access::Write).await?;
+    let edit_url = format!("/crm/customers/{}/contacts/{}/edit", customer_id, contact_id);
+
+    let Some((filename, data)) = uploaded_file(multipart, "photo").await? else {
+        return Ok(Redirect::to(&format!(
+            "{}?error={}",
+            edit_url,
+            urlencoding::encode("Choose a photo to upload.")
+        )));
+    };
+    let stored = storage::save_image(PHOTOS, &filename, &data).await.map_err(|e| {
+        tracing::error!("Error storing contact photo: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    let Some(photo_url) = stored else {
+        return Ok(Redirect::to(&format!(
+            "{}?error={}",
+            edit_url,
+            urlencoding::encode("Photos must be PNG or JPEG images.")
+        )));
+    };
+
+    set_photo(&db, &current_user, customer_id, contact_id, Some(photo_url)).await?;
+    Ok(Redirect::to(&edit_url))
+}
+
+pub async fn remove_contact_photo(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path((customer_id, contact_id)): Path<(Uuid, Uuid)>,
+) -> Result<Redirect, StatusCode> {
+    current_user.require("customers:write")?;
+    require_access(&db, &current_user, RecordKind::Customer, customer_id, Access::Write).await?;
+
+    set_photo(&db, &current_user, customer_id, contact_id, None).await?;
+    Ok(Redirect::to(&format!("/crm/customers/{}/contacts/{}/edit", customer_id, contact_id)))
+}
+
+// What the add contact form gets back from a scanned card. The card is kept
+// even when it can't be read, so it still goes on the contact.
+#[derive(Serialize)]
+pub struct CardScan {
+    business_card_url: String,
+    details: Option<CardDetails>,
+    error: Option<&'static str>,
+}
+
+// Stores a business card image and reads the contact's details off it, for
+// the add contact form to fill itself in with
+pub async fn scan_business_card(
+    AuthUser(current_user): AuthUser,
+    multipart: Multipart,
+) -> Result<Response, StatusCode> {
+    current_user.require("customers:write")?;
+
+    let rejected = |message: &'static str| {
+        (StatusCode::BAD_REQUEST, Json(serde_json::json!({ "error": message }))).into_response()
+    };
+    let Some((filename, data)) = uploaded_file(multipart, "card").await? else {
+        return Ok(rejected("Choose a photo of the card."));
+    };
+    let stored = storage::save_image(BUSINESS_CARDS, &filename, &data).await.map_err(|e| {
+        tracing::error!("Error storing business card: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    let Some(business_card_url) = stored else {
+        return Ok(rejected("Cards must be PNG or JPEG images."));
+    };
+
+    let scan = match ocr::read_text(&data).await {
+        Ok(text) => CardScan { business_card_url, details: Some(business_card::parse(&text)), error: None },
+        Err(e) => {
+            if let ocr::OcrError::Failed(reason) = &e {
+                tracing::error!("Error reading business card: {}", reason);
+            }
+            CardScan { business_card_url, details: None, error: Some(e.message()) }
+        }
+    };
+    Ok(Json(scan).into_response())
+}
@@ -0,0 +1,233 @@
+use axum::{
+    extract::{Form, Path, Query, State},
+    http::StatusCode,
+    response::{Html, Redirect, Response},
+};
+use askama::Template;
+use chrono::{Datelike, NaiveDate, Utc};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    database::Database,
+    handlers::team::create_audit_log,
+    middleware::{AuthUser, CurrentUser},
+    models::CostCenter,
+    services::{
+        cost_centers::{self, CostCenterSpend},
+        periods::{self, PeriodContext, PeriodPicker},
+    },
+    utils::xlsx::{ColumnType, XlsxExport},
+};
+
+#[derive(Template)]
+#[template(path = "expenses/cost_centers.html")]
+struct CostCentersTemplate {
+    // Empty unless the viewer can see amounts
+    spend: Vec<CostCenterSpend>,
+    total_approved: Decimal,
+    total_pending: Decimal,
+    period: PeriodPicker,
+    // Empty unless the viewer can manage them
+    cost_centers: Vec<CostCenter>,
+    can_see_amounts: bool,
+    can_export: bool,
+    can_manage: bool,
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct CostCenterQuery {
+    period: Option<String>,
+    date_from: Option<String>,
+    date_to: Option<String>,
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct CostCenterForm {
+    code: String,
+    name: String,
+    // Checkbox on the edit form: only sent when ticked
+    is_active: Option<String>,
+}
+
+// The picked period, the current month when none is given
+async fn period_range(db: &Database, current_user: &CurrentUser, query: &CostCenterQuery) -> Result<(NaiveDate, NaiveDate), StatusCode> {
+    let ctx = PeriodContext::for_user(db, current_user).await.map_err(|e| {
+        tracing::error!("Error loading reporting settings: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    if let Some(range) = periods::resolve(query.period.as_deref(), &ctx).map_err(|_| StatusCode::BAD_REQUEST)? {
+        return Ok(range);
+    }
+
+    let parse_date = |value: &Option<String>| {
+        value.as_deref()
+            .filter(|d| !d.trim().is_empty())
+            .map(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d"))
+            .transpose()
+            .map_err(|_| StatusCode::BAD_REQUEST)
+    };
+    let date_to = parse_date(&query.date_to)?.unwrap_or_else(|| Utc::now().date_naive());
+    let date_from = parse_date(&query.date_from)?.unwrap_or_else(|| date_to.with_day(1).unwrap_or(date_to));
+    if date_from > date_to {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    Ok((date_from, date_to))
+}
+
+// Spend per department for a period, and the list of cost centers for admins
+pub async fn cost_centers_page(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Query(query): Query<CostCenterQuery>,
+) -> Result<Html<String>, StatusCode> {
+    current_user.require("expenses:read")?;
+    let (date_from, date_to) = period_range(&db, &current_user, &query).await?;
+
+    let spend = if current_user.has_finance_read {
+        cost_centers::spend(&db, date_from, date_to).await.map_err(|e| {
+            tracing::error!("Error loading cost center spend: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+    } else {
+        Vec::new()
+    };
+    let cost_centers = if current_user.has_manage_roles {
+        cost_centers::list(&db, true).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    } else {
+        Vec::new()
+    };
+
+    let template = CostCentersTemplate {
+        total_approved: spend.iter().map(|row| row.approved).sum(),
+        total_pending: spend.iter().map(|row| row.pending).sum(),
+        spend,
+        period: PeriodPicker::new(query.period.as_deref(), Some(date_from), Some(date_to)),
+        cost_centers,
+        can_see_amounts: current_user.has_finance_read,
+        can_export: current_user.has_finance_read && current_user.has_data_export,
+        can_manage: current_user.has_manage_roles,
+        error: query.error,
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+// Expense lines with their cost center code, for the accounting system
+pub async fn cost_centers_export(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Query(query): Query<CostCenterQuery>,
+) -> Result<Response, StatusCode> {
+    current_user.require("expenses:read")?;
+    if !current_user.has_finance_read || !current_user.has_data_export {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let (date_from, date_to) = period_range(&db, &current_user, &query).await?;
+
+    let lines = cost_centers::export_lines(&db, date_from, date_to).await.map_err(|e| {
+        tracing::error!("Error loading expenses for export: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let mut export = XlsxExport::new("Expenses by Cost Center", &[
+        ("Date", ColumnType::Date),
+        ("Cost Center Code", ColumnType::Text),
+        ("Cost Center", ColumnType::Text),
+        ("Category", ColumnType::Text),
+        ("Submitted By", ColumnType::Text),
+        ("Customer", ColumnType::Text),
+        ("Description", ColumnType::Text),
+        ("Amount", ColumnType::Currency),
+        ("Status", ColumnType::Text),
+        ("Approved", ColumnType::DateTime),
+    ]);
+    export.filter("From", date_from.to_string());
+    export.filter("To", date_to.to_string());
+
+    for line in lines {
+        export.row(vec![
+            line.expense_date.into(),
+            line.code.into(),
+            line.cost_center.into(),
+            line.category.into(),
+            line.user_name.into(),
+            line.customer.into(),
+            line.description.into(),
+            line.amount.into(),
+            line.status.into(),
+            line.approved_at.into(),
+        ]);
+    }
+
+    let generated_by = format!("{} {} <{}>", current_user.first_name, current_user.last_name, current_user.email);
+    Ok(export.into_response(&format!("cost-centers-{}-{}.xlsx", date_from, date_to), &generated_by))
+}
+
+pub async fn create_cost_center(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Form(form): Form<CostCenterForm>,
+) -> Result<Redirect, StatusCode> {
+    if !current_user.has_manage_roles {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let (code, name) = (form.code.trim(), form.name.trim());
+
+    let created = cost_centers::create(&db, code, name, current_user.id).await.map_err(|e| {
+        tracing::error!("Error creating cost center: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    let id = match created {
+        Ok(id) => id,
+        Err(error) => return Ok(Redirect::to(&format!("/expenses/cost-centers?error={}", urlencoding::encode(&error)))),
+    };
+
+    let _ = create_audit_log(
+        &db,
+        &current_user,
+        "create".to_string(),
+        "cost_center".to_string(),
+        Some(id),
+        None,
+        Some(serde_json::json!({ "code": code, "name": name })),
+    )
+    .await;
+
+    Ok(Redirect::to("/expenses/cost-centers"))
+}
+
+pub async fn update_cost_center(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+    Form(form): Form<CostCenterForm>,
+) -> Result<Redirect, StatusCode> {
+    if !current_user.has_manage_roles {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let (code, name, is_active) = (form.code.trim(), form.name.trim(), form.is_active.is_some());
+
+    let updated = cost_centers::update(&db, id, code, name, is_active).await.map_err(|e| {
+        tracing::error!("Error updating cost center: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    if let Err(error) = updated {
+        return Ok(Redirect::to(&format!("/expenses/cost-centers?error={}", urlencoding::encode(&error))));
+    }
+
+    let _ = create_audit_log(
+        &db,
+        &current_user,
+        "update".to_string(),
+        "cost_center".to_string(),
+        Some(id),
+        None,
+        Some(serde_json::json!({ "code": code, "name": name, "is_active": is_active })),
+    )
+    .await;
+
+    Ok(Redirect::to("/expenses/cost-centers"))
+}
@@ -1,18 +1,23 @@
 use axum::{
-    extract::{Form, Path, Query, State},
+    extract::{Extension, Form, Path, Query, State},
     http::StatusCode,
-    response::{Html, Redirect},
+    response::{Html, Redirect, Response},
 };
 use askama::Template;
+use std::collections::HashMap;
 use serde::{Deserialize, Serialize};
 use tower_cookies::Cookies;
 use uuid::Uuid;
-use chrono::{Utc, NaiveDate, NaiveDateTime};
+use chrono::{DateTime, Datelike, Duration, Utc, NaiveDate};
+use chrono_tz::Tz;
 
 use crate::{
     database::Database,
-    models::{Customer, CustomerTemplate, Contact, Deal, Activity, CustomerDisplay, ContactDisplay, DealDisplay, ActivityDisplay},
-    middleware::{get_current_user, CurrentUser},
+    handlers::{contact_photos, team::create_audit_log},
+    models::{CUSTOMER_STATUSES, DEAL_STAGES, BlanketOrder, Customer, CustomField, CustomerPartNumber, CustomerTemplate, Contact, Deal, DealLineItem, DiscountThreshold, InventoryItem, PricingAgreement, Activity, CustomerDisplay, ContactDisplay, DealDisplay, ActivityDisplay, DealStageChange, DealStageSetting, ActivityOutcome, ActivityType, Campaign, EmailEvent, Note, RecordShare, Sequence, SequenceEnrollment, Tag, TrackedEmail, User},
+    middleware::{api_auth::ApiPrincipal, AuthUser, get_current_user, CurrentUser},
+    services::{activity_types, audit_log::{self, audited_execute, Audited}, blanket_orders, custom_fields::{self, Extended, FieldInput, FieldValue}, events::{self, Event}, deal_health, discounts, exchange_rates, lookups::{self, LookupOptions, Lookups}, mailer, metrics, notes, out_of_office::{self, AssigneePicker}, part_numbers, periods::{PeriodContext, PeriodPicker, Preset}, price_history::{self, PriceCheck}, pricing_agreements::{self, NewAgreement}, sandbox, stage_history, sequences, sharing::{self, Access, RecordKind}, storage, tags::{self, TagPicker, Tagged}, teams::TeamFilter, watchers},
+    utils::{empty_state::EmptyState, paging::{PageRequest, Pagination, SortColumn, PER_PAGE_OPTIONS}, locale::{self, LANGUAGES}, timezone::{format_local, parse_local_input, to_local_input}, xlsx::{ColumnType, XlsxExport}},
     filters,
 };
 
@@ -34,40 +39,124 @@ struct CrmDashboardTemplate {
     closed_lost_value: String,
     // Performance metrics
     win_rate: i32,
-    activities_this_month: i64,
+    // Activities in the picked period, compared with the period before
+    activity_count: i64,
+    activity_period: PeriodPicker,
     customer_change: i32,
     deals_change: i32,
     win_rate_change: i32,
     activities_change: i32,
+    // Daily snapshot series for the trend chart, as JSON
+    trend_json: String,
+    trend_points: usize,
+    can_add_customers: bool,
 }
 
 #[derive(Template)]
 #[template(path = "crm/customers.html")]
 struct CustomersTemplate {
     customers: Vec<CustomerDisplay>,
+    empty: Option<EmptyState>,
+    pagination: Pagination,
+    statuses: &'static [&'static str],
+    industries: LookupOptions,
+    selected_status: String,
+    selected_industry: String,
+    tags: Vec<Tag>,
+    selected_tag: Option<Uuid>,
+    // The tags on each customer on the page
+    customer_tags: HashMap<Uuid, Vec<Tag>>,
+    per_page_options: &'static [i64],
+    can_export: bool,
+    can_write: bool,
+}
+
+impl CustomersTemplate {
+    fn is_status(&self, status: &str) -> bool {
+        self.selected_status == status
+    }
+
+    fn is_tag(&self, tag: &Tag) -> bool {
+        self.selected_tag == Some(tag.id)
+    }
+
+    fn tags_for(&self, customer_id: &Uuid) -> &[Tag] {
+        self.customer_tags.get(customer_id).map(Vec::as_slice).unwrap_or(&[])
+    }
 }
 
 #[derive(Template)]
 #[template(path = "crm/customer_form.html")]
 struct CustomerFormTemplate {
     customer: Option<CustomerTemplate>,
+    campaigns: Vec<Campaign>,
+    lookups: Lookups,
+    languages: Vec<(&'static str, &'static str)>,
+    can_delete: bool,
+    assignee_picker: AssigneePicker,
+    tag_picker: TagPicker,
+    custom_fields: Vec<FieldInput>,
+    error: Option<String>,
 }
 
 #[derive(Template)]
 #[template(path = "crm/customer_detail.html")]
 struct CustomerDetailTemplate {
     customer: CustomerDisplay,
+    campaign_name: Option<String>,
     contacts: Vec<ContactDisplay>,
     deals: Vec<DealDisplay>,
     activities: Vec<ActivityDisplay>,
     current_user: CurrentUser,
+    sharing: SharingPanel,
+    watch: WatchButton,
+    notes: NotesPanel,
+    tags: Vec<Tag>,
+    custom_values: Vec<FieldValue>,
+    // Both the role and the record's sharing allow editing
+    can_write: bool,
+    // The add contact form's custom fields, and why the last add was refused
+    custom_fields: Vec<FieldInput>,
+    contact_error: Option<String>,
+    // Empty unless the viewer can see inventory
+    part_numbers: Vec<CustomerPartNumber>,
+    // Items offered when mapping a part number or agreeing a price; empty
+    // when the viewer can't
+    part_number_items: Vec<InventoryItem>,
+    part_number_taken: Option<String>,
+    // Empty unless the viewer can see both inventory and prices
+    pricing_agreements: Vec<PricingAgreement>,
+    shows_pricing: bool,
+    pricing_error: Option<String>,
+    // Empty unless the viewer can see inventory
+    blanket_orders: Vec<BlanketOrder>,
 }
 
 #[derive(Template)]
 #[template(path = "crm/deals.html")]
 struct DealsTemplate {
     deals: Vec<DealDisplay>,
+    empty: Option<EmptyState>,
     current_user: CurrentUser,
+    team_filter: TeamFilter,
+    // Owners the list can be narrowed to, with the chosen one selected
+    owners: AssigneePicker,
+    // Showing only the viewer's own deals
+    mine: bool,
+    tags: Vec<Tag>,
+    selected_tag: Option<Uuid>,
+    // The tags on each listed deal
+    deal_tags: HashMap<Uuid, Vec<Tag>>,
+}
+
+impl DealsTemplate {
+    fn is_tag(&self, tag: &Tag) -> bool {
+        self.selected_tag == Some(tag.id)
+    }
+
+    fn tags_for(&self, deal_id: &Uuid) -> &[Tag] {
+        self.deal_tags.get(deal_id).map(Vec::as_slice).unwrap_or(&[])
+    }
 }
 
 #[derive(Template)]
@@ -77,14 +166,152 @@ struct DealFormTemplate {
     customers: Vec<Customer>,
     contacts: Vec<Contact>,
     customer_id: Option<Uuid>,
+    campaigns: Vec<Campaign>,
+    show_value: bool,
+    lookups: Lookups,
+    assignee_picker: AssigneePicker,
+    tag_picker: TagPicker,
+    custom_fields: Vec<FieldInput>,
+    error: Option<String>,
 }
 
 #[derive(Template)]
 #[template(path = "crm/deal_detail.html")]
 struct DealDetailTemplate {
     deal: DealDisplay,
+    base_currency: String,
     customer: Customer,
     contact: Option<Contact>,
+    sharing: SharingPanel,
+    watch: WatchButton,
+    notes: NotesPanel,
+    tags: Vec<Tag>,
+    custom_values: Vec<FieldValue>,
+    line_items: Vec<DealLineItem>,
+    line_items_total: rust_decimal::Decimal,
+    // Discounts still waiting or denied; the deal can't be won until resolved
+    unapproved_discounts: i64,
+    // Items offered when adding a line; empty when the viewer can't add one
+    inventory_items: Vec<InventoryItem>,
+    thresholds: Vec<DiscountThreshold>,
+    // Margin and past-price warnings; only loaded for those who see prices
+    price_checks: Vec<PriceCheck>,
+    // Agreed prices on the deal's items that are about to run out
+    expiring_agreements: Vec<PricingAgreement>,
+    // The customer's own numbers, shown next to our items when adding a line
+    customer_part_numbers: Vec<CustomerPartNumber>,
+    // Whether the viewer can download the deal's audit history
+    show_history: bool,
+    stage_history: Vec<DealStageChange>,
+    sequence_panel: SequencePanel,
+}
+
+impl DealDetailTemplate {
+    fn part_number_for(&self, item_id: &Uuid) -> Option<&str> {
+        self.customer_part_numbers
+            .iter()
+            .find(|p| p.item_id == *item_id)
+            .map(|p| p.part_number.as_str())
+    }
+
+    fn price_check(&self, line_id: &Uuid) -> Option<&PriceCheck> {
+        self.price_checks.iter().find(|check| check.line_id == *line_id)
+    }
+}
+
+// Who a record is shared with, and the share form when the viewer may change it
+pub struct SharingPanel {
+    action_url: String,
+    can_edit: bool,
+    can_share: bool,
+    shares: Vec<RecordShare>,
+    users: Vec<User>,
+    team_leads: Vec<User>,
+}
+
+// Follow-up sequences a contact or deal is in, and the form to start another
+pub struct SequencePanel {
+    action_url: String,
+    can_enroll: bool,
+    // Why the last enrollment was refused, passed back in the query string
+    error: Option<String>,
+    enrollments: Vec<SequenceEnrollment>,
+    sequences: Vec<Sequence>,
+}
+
+// Whether the viewer watches the record, and how many do
+pub struct WatchButton {
+    action_url: String,
+    watching: bool,
+    watchers: i64,
+}
+
+// A record's notes, oldest first, and the form to add one
+pub struct NotesPanel {
+    action_url: String,
+    notes: Vec<Note>,
+    rights: NoteRights,
+    timezone: Tz,
+    // Why the last note was refused, passed back in the query string
+    error: Option<String>,
+}
+
+impl NotesPanel {
+    fn posted_at(&self, note: &Note) -> String {
+        format_local(note.created_at, self.timezone, "%Y-%m-%d %H:%M")
+    }
+
+    fn edited_at(&self, note: &Note) -> Option<String> {
+        note.edited_at.map(|at| format_local(at, self.timezone, "%Y-%m-%d %H:%M"))
+    }
+}
+
+// What the viewer may do in a record's notes
+pub struct NoteRights {
+    viewer_id: Uuid,
+    // Both the role and the record's sharing allow adding notes
+    can_write: bool,
+    // The record's owner and admins may delete anyone's note
+    can_moderate: bool,
+}
+
+impl NoteRights {
+    fn new(current_user: &CurrentUser, access: Access) -> Self {
+        NoteRights {
+            viewer_id: current_user.id,
+            can_write: current_user.can("customers:write") && access >= Access::Write,
+            can_moderate: access == Access::Manage,
+        }
+    }
+
+    // Only the author can reword a note
+    fn can_edit(&self, note: &Note) -> bool {
+        self.can_write && note.author_id == Some(self.viewer_id)
+    }
+
+    fn can_delete(&self, note: &Note) -> bool {
+        self.can_edit(note) || self.can_moderate
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ShareForm {
+    // "user:<id>" or "team:<lead id>"
+    grantee: String,
+    access: String,
+}
+
+#[derive(Deserialize)]
+pub struct NoteForm {
+    body: String,
+}
+
+#[derive(Template)]
+#[template(path = "crm/deal_stage_settings.html")]
+struct DealStageSettingsTemplate {
+    stages: Vec<DealStageSetting>,
+    current_user: CurrentUser,
+    success_message: Option<String>,
 }
 
 #[derive(Template)]
@@ -92,6 +319,9 @@ struct DealDetailTemplate {
 struct ActivitiesTemplate {
     activities: Vec<ActivityDisplay>,
     current_user: CurrentUser,
+    team_filter: TeamFilter,
+    activity_types: Vec<ActivityType>,
+    selected_type: String,
 }
 
 #[derive(Template)]
@@ -103,6 +333,30 @@ struct ActivityFormTemplate {
     deals: Vec<Deal>,
     customer_id: Option<Uuid>,
     deal_id: Option<Uuid>,
+    outcomes: Vec<ActivityOutcome>,
+    // Retired types are only offered to the activity already using one
+    activity_types: Vec<ActivityType>,
+    selected_type: String,
+    // datetime-local value in the user's time zone
+    activity_date: String,
+    timezone: Tz,
+    assignee_picker: AssigneePicker,
+}
+
+#[derive(Template)]
+#[template(path = "crm/activity_outcomes.html")]
+struct ActivityOutcomesTemplate {
+    outcomes: Vec<ActivityOutcome>,
+    current_user: CurrentUser,
+    activity_types: Vec<ActivityType>,
+}
+
+#[derive(Template)]
+#[template(path = "crm/activity_types.html")]
+struct ActivityTypesTemplate {
+    activity_types: Vec<ActivityType>,
+    current_user: CurrentUser,
+    error: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -120,6 +374,16 @@ pub struct CustomerForm {
     country: Option<String>,
     status: String,
     notes: Option<String>,
+    campaign_id: Option<String>,
+    preferred_language: Option<String>,
+    assigned_to: Option<String>,
+    // Set when the user chose an assignee who is away and wants them anyway
+    keep_assignee: Option<String>,
+    // Comma-separated tag names from the tag picker
+    tags: Option<String>,
+    // The cf_<key> inputs of the custom fields
+    #[serde(flatten)]
+    custom: HashMap<String, String>,
 }
 
 #[derive(Deserialize)]
@@ -133,11 +397,20 @@ pub struct ContactForm {
     mobile: Option<String>,
     is_primary: Option<String>,
     notes: Option<String>,
+    email_tracking_opt_out: Option<String>,
+    do_not_contact: Option<String>,
+    // From a scanned card on the add form; see handlers::contact_photos
+    business_card_url: Option<String>,
+    // Only on the edit form; adding a contact leaves it untagged
+    tags: Option<String>,
+    #[serde(flatten)]
+    custom: HashMap<String, String>,
 }
 
 #[derive(Deserialize)]
 pub struct DealQuery {
     customer_id: Option<Uuid>,
+    error: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -150,6 +423,94 @@ pub struct DealForm {
     currency: String,
     stage: String,
     expected_close_date: Option<NaiveDate>,
+    campaign_id: Option<String>,
+    assigned_to: Option<String>,
+    // Set when the user chose an assignee who is away and wants them anyway
+    keep_assignee: Option<String>,
+    // Tell a newly assigned owner the deal is theirs
+    notify_assignee: Option<String>,
+    tags: Option<String>,
+    #[serde(flatten)]
+    custom: HashMap<String, String>,
+}
+
+// Inline edits of a single field from the list pages
+#[derive(Deserialize)]
+pub struct CustomerStatusPatch {
+    status: String,
+}
+
+#[derive(Deserialize)]
+pub struct DealStagePatch {
+    stage: String,
+}
+
+#[derive(Deserialize)]
+pub struct ActivityCompletedPatch {
+    completed: bool,
+}
+
+#[derive(Deserialize)]
+pub struct DealStageSettingsQuery {
+    success: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct LineItemForm {
+    item_id: Option<String>,
+    // Our SKU or UPC, or the customer's own part number; used when no item is picked
+    item_code: Option<String>,
+    // Both default to the inventory item's when one is picked
+    description: Option<String>,
+    unit_price: Option<String>,
+    quantity: rust_decimal::Decimal,
+    discount_percent: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct PartNumberForm {
+    item_id: Uuid,
+    part_number: String,
+    description: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct CustomerDetailQuery {
+    part_number_taken: Option<String>,
+    pricing_error: Option<String>,
+    contact_error: Option<String>,
+    note_error: Option<String>,
+}
+
+// Why the last save of an edit form was refused
+#[derive(Deserialize)]
+pub struct FormErrorQuery {
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct PricingAgreementForm {
+    item_id: Uuid,
+    // One or the other
+    fixed_price: Option<String>,
+    discount_percent: Option<String>,
+    // Blank starts it today
+    valid_from: Option<String>,
+    valid_until: Option<String>,
+    notes: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct DiscountThresholdForm {
+    above_percent: rust_decimal::Decimal,
+    approver: String,
+}
+
+#[derive(Template)]
+#[template(path = "crm/discount_settings.html")]
+struct DiscountSettingsTemplate {
+    thresholds: Vec<DiscountThreshold>,
+    current_user: CurrentUser,
 }
 
 #[derive(Deserialize)]
@@ -169,10 +530,59 @@ pub struct ActivityForm {
     activity_date: String,
     duration_minutes: Option<i32>,
     completed: Option<String>,
+    outcome_code: Option<String>,
+    assigned_to: Option<String>,
+    // Set when the user chose an assignee who is away and wants them anyway
+    keep_assignee: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ActivityOutcomeForm {
+    activity_type: String,
+    code: String,
+    label: String,
+    is_connect: Option<String>,
+    is_held: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ActivityTypeForm {
+    code: String,
+    name: String,
+    icon: String,
+    default_duration_minutes: Option<String>,
+    requires_outcome: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ActivityTypesQuery {
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct DashboardQuery {
+    period: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ListQuery {
+    // "team" limits the list to what the viewer's teams own
+    show: Option<String>,
+    // Deals only: "me", or a user's id, limits it to what they own
+    owner: Option<String>,
+    // Activities only: an activity type code
+    #[serde(rename = "type")]
+    activity_type: Option<String>,
+    // Deals only: a tag's id
+    tag: Option<String>,
 }
 
 // CRM Dashboard - FIXED VERSION WITH CORRECT PERFORMANCE METRICS
-pub async fn crm_dashboard(State(db): State<Database>) -> Result<Html<String>, StatusCode> {
+pub async fn crm_dashboard(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Query(query): Query<DashboardQuery>,
+) -> Result<Html<String>, StatusCode> {
     let customer_count = sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM customers")
         .fetch_one(&db)
         .await
@@ -187,13 +597,14 @@ pub async fn crm_dashboard(State(db): State<Database>) -> Result<Html<String>, S
     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     let total_deal_value_raw = sqlx::query_scalar::<_, Option<rust_decimal::Decimal>>(
-        "SELECT SUM(value) FROM deals WHERE stage NOT IN ('closed_lost')"
+        "SELECT SUM(base_value) FROM deals WHERE stage NOT IN ('closed_lost')"
     )
     .fetch_one(&db)
     .await
     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     let total_deal_value = match total_deal_value_raw {
+        _ if !current_user.has_finance_read => "Hidden".to_string(),
         Some(value) => format!("${}", value),
         None => "$0".to_string(),
     };
@@ -219,50 +630,89 @@ pub async fn crm_dashboard(State(db): State<Database>) -> Result<Html<String>, S
         0
     };
 
-    // Calculate activities this month
-    let activities_this_month = sqlx::query_scalar::<_, i64>(
-        "SELECT COUNT(*) FROM activities WHERE activity_date >= DATE_TRUNC('month', CURRENT_DATE)"
-    )
-    .fetch_one(&db)
-    .await
-    .unwrap_or(0);
-
-    // Calculate last month metrics for comparison
-    let customers_last_month = sqlx::query_scalar::<_, i64>(
-        "SELECT COUNT(*) FROM customers WHERE created_at < DATE_TRUNC('month', CURRENT_DATE)"
-    )
-    .fetch_one(&db)
-    .await
-    .unwrap_or(0);
+    // Activities in the picked period (this month unless chosen otherwise)
+    let preset = match query.period.as_deref() {
+        None | Some("") => Preset::find("this_month"),
+        Some(key) => Preset::find(key),
+    }
+    .ok_or(StatusCode::BAD_REQUEST)?;
+    let period_ctx = PeriodContext::for_user(&db, &current_user).await.map_err(|e| {
+        tracing::error!("Error loading reporting settings: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    let count_activities = |(date_from, date_to): (NaiveDate, NaiveDate)| {
+        let db = db.clone();
+        async move {
+            sqlx::query_scalar::<_, i64>(
+                "SELECT COUNT(*) FROM activities WHERE DATE(activity_date) BETWEEN $1 AND $2"
+            )
+            .bind(date_from)
+            .bind(date_to)
+            .fetch_one(&db)
+            .await
+            .unwrap_or(0)
+        }
+    };
+    let (period_from, period_to) = preset.range(&period_ctx);
+    let activity_count = count_activities((period_from, period_to)).await;
+    let activities_previous = count_activities(preset.previous(&period_ctx)).await;
+
+    // Month-start figures come from the last snapshot taken before this month.
+    // Until snapshots exist they are approximated from creation dates.
+    let month_start = Utc::now().date_naive().with_day(1).unwrap_or_else(|| Utc::now().date_naive());
+    let snapshot = |metric: &'static str, dimensions: &'static [&'static str]| {
+        let db = db.clone();
+        async move {
+            metrics::value_before(&db, metric, dimensions, month_start)
+                .await
+                .unwrap_or_else(|e| {
+                    tracing::error!("Error loading {} snapshot: {}", metric, e);
+                    None
+                })
+                .map(|value| value as i64)
+        }
+    };
 
-    let open_deals_last_month = sqlx::query_scalar::<_, i64>(
-        "SELECT COUNT(*) FROM deals WHERE created_at < DATE_TRUNC('month', CURRENT_DATE) AND stage IN ('prospect', 'negotiation')"
-    )
-    .fetch_one(&db)
-    .await
-    .unwrap_or(0);
+    let customers_last_month = match snapshot("customers", &[]).await {
+        Some(count) => count,
+        None => sqlx::query_scalar::<_, i64>(
+            "SELECT COUNT(*) FROM customers WHERE created_at < DATE_TRUNC('month', CURRENT_DATE)"
+        )
+        .fetch_one(&db)
+        .await
+        .unwrap_or(0),
+    };
 
-    let activities_last_month = sqlx::query_scalar::<_, i64>(
-        "SELECT COUNT(*) FROM activities WHERE activity_date >= DATE_TRUNC('month', CURRENT_DATE - INTERVAL '1 month') AND activity_date < DATE_TRUNC('month', CURRENT_DATE)"
-    )
-    .fetch_one(&db)
-    .await
-    .unwrap_or(0);
+    let open_deals_last_month = match snapshot("pipeline_deals", &["prospect", "negotiation"]).await {
+        Some(count) => count,
+        None => sqlx::query_scalar::<_, i64>(
+            "SELECT COUNT(*) FROM deals WHERE created_at < DATE_TRUNC('month', CURRENT_DATE) AND stage IN ('prospect', 'negotiation')"
+        )
+        .fetch_one(&db)
+        .await
+        .unwrap_or(0),
+    };
 
     // Calculate last month win rate
-    let total_closed_deals_last_month = sqlx::query_scalar::<_, i64>(
-        "SELECT COUNT(*) FROM deals WHERE created_at < DATE_TRUNC('month', CURRENT_DATE) AND stage IN ('closed_won', 'closed_lost')"
-    )
-    .fetch_one(&db)
-    .await
-    .unwrap_or(0);
+    let total_closed_deals_last_month = match snapshot("pipeline_deals", &["closed_won", "closed_lost"]).await {
+        Some(count) => count,
+        None => sqlx::query_scalar::<_, i64>(
+            "SELECT COUNT(*) FROM deals WHERE created_at < DATE_TRUNC('month', CURRENT_DATE) AND stage IN ('closed_won', 'closed_lost')"
+        )
+        .fetch_one(&db)
+        .await
+        .unwrap_or(0),
+    };
 
-    let won_deals_last_month = sqlx::query_scalar::<_, i64>(
-        "SELECT COUNT(*) FROM deals WHERE created_at < DATE_TRUNC('month', CURRENT_DATE) AND stage = 'closed_won'"
-    )
-    .fetch_one(&db)
-    .await
-    .unwrap_or(0);
+    let won_deals_last_month = match snapshot("pipeline_deals", &["closed_won"]).await {
+        Some(count) => count,
+        None => sqlx::query_scalar::<_, i64>(
+            "SELECT COUNT(*) FROM deals WHERE created_at < DATE_TRUNC('month', CURRENT_DATE) AND stage = 'closed_won'"
+        )
+        .fetch_one(&db)
+        .await
+        .unwrap_or(0),
+    };
 
     let win_rate_last_month = if total_closed_deals_last_month > 0 {
         ((won_deals_last_month as f64 / total_closed_deals_last_month as f64) * 100.0).round() as i32
@@ -289,9 +739,9 @@ pub async fn crm_dashboard(State(db): State<Database>) -> Result<Html<String>, S
 
     let win_rate_change = win_rate - win_rate_last_month;
 
-    let activities_change = if activities_last_month > 0 {
-        (((activities_this_month - activities_last_month) as f64 / activities_last_month as f64) * 100.0).round() as i32
-    } else if activities_this_month > 0 {
+    let activities_change = if activities_previous > 0 {
+        (((activity_count - activities_previous) as f64 / activities_previous as f64) * 100.0).round() as i32
+    } else if activity_count > 0 {
         100
     } else {
         0
@@ -306,7 +756,7 @@ pub async fn crm_dashboard(State(db): State<Database>) -> Result<Html<String>, S
     .unwrap_or(0);
 
     let prospect_value_raw = sqlx::query_scalar::<_, Option<rust_decimal::Decimal>>(
-        "SELECT SUM(value) FROM deals WHERE stage = 'prospect'"
+        "SELECT SUM(base_value) FROM deals WHERE stage = 'prospect'"
     )
     .fetch_one(&db)
     .await
@@ -325,7 +775,7 @@ pub async fn crm_dashboard(State(db): State<Database>) -> Result<Html<String>, S
     .unwrap_or(0);
 
     let negotiation_value_raw = sqlx::query_scalar::<_, Option<rust_decimal::Decimal>>(
-        "SELECT SUM(value) FROM deals WHERE stage = 'negotiation'"
+        "SELECT SUM(base_value) FROM deals WHERE stage = 'negotiation'"
     )
     .fetch_one(&db)
     .await
@@ -344,7 +794,7 @@ pub async fn crm_dashboard(State(db): State<Database>) -> Result<Html<String>, S
     .unwrap_or(0);
 
     let closed_won_value_raw = sqlx::query_scalar::<_, Option<rust_decimal::Decimal>>(
-        "SELECT SUM(value) FROM deals WHERE stage = 'closed_won'"
+        "SELECT SUM(base_value) FROM deals WHERE stage = 'closed_won'"
     )
     .fetch_one(&db)
     .await
@@ -363,7 +813,7 @@ pub async fn crm_dashboard(State(db): State<Database>) -> Result<Html<String>, S
     .unwrap_or(0);
 
     let closed_lost_value_raw = sqlx::query_scalar::<_, Option<rust_decimal::Decimal>>(
-        "SELECT SUM(value) FROM deals WHERE stage = 'closed_lost'"
+        "SELECT SUM(base_value) FROM deals WHERE stage = 'closed_lost'"
     )
     .fetch_one(&db)
     .await
@@ -374,14 +824,47 @@ pub async fn crm_dashboard(State(db): State<Database>) -> Result<Html<String>, S
         None => "$0".to_string(),
     };
 
-    let recent_activities = sqlx::query_as::<_, Activity>(
-        "SELECT * FROM activities ORDER BY activity_date DESC LIMIT 5"
-    )
+    // Open pipeline value and customer count over the last 90 days of snapshots
+    let since = Utc::now().date_naive() - Duration::days(90);
+    let pipeline_trend = metrics::series(&db, "pipeline_value", &["prospect", "negotiation"], since)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error loading pipeline trend: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    let customer_trend: HashMap<NaiveDate, f64> = metrics::series(&db, "customers", &[], since)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error loading customer trend: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .into_iter()
+        .collect();
+
+    let trend_json = serde_json::json!({
+        "labels": pipeline_trend.iter().map(|(date, _)| date.format("%Y-%m-%d").to_string()).collect::<Vec<_>>(),
+        "pipeline": pipeline_trend.iter().map(|(_, value)| value).collect::<Vec<_>>(),
+        "customers": pipeline_trend.iter().map(|(date, _)| customer_trend.get(date)).collect::<Vec<_>>(),
+    })
+    .to_string()
+    .replace('<', "\\u003c");
+
+    let activity_scope = if sharing::is_scoped(&current_user) {
+        format!("WHERE {}", sharing::visibility_condition(RecordKind::Activity, "a", 1))
+    } else {
+        String::new()
+    };
+    let activity_types = load_activity_types(&db, true).await?;
+    let recent_activities = sqlx::query_as::<_, Activity>(&format!(
+        "SELECT a.* FROM activities a {} ORDER BY a.activity_date DESC LIMIT 5",
+        activity_scope
+    ))
+    .bind(current_user.id)
     .fetch_all(&db)
     .await
     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
     .into_iter()
-    .map(ActivityDisplay::from)
+    .map(|activity| ActivityDisplay::in_timezone(activity, current_user.timezone).with_type(&activity_types))
     .collect();
 
     let template = CrmDashboardTemplate {
@@ -399,45 +882,370 @@ pub async fn crm_dashboard(State(db): State<Database>) -> Result<Html<String>, S
         closed_lost_value,
         // Add new fields for performance metrics
         win_rate,
-        activities_this_month,
+        activity_count,
+        activity_period: PeriodPicker::new(Some(preset.key), Some(period_from), Some(period_to)),
         customer_change,
         deals_change,
         win_rate_change,
         activities_change,
+        trend_points: pipeline_trend.len(),
+        trend_json,
+        can_add_customers: current_user.can("customers:write"),
     };
 
     Ok(Html(template.render().unwrap()))
 }
 
+static CUSTOMER_SORTS: &[SortColumn] = &[
+    SortColumn { key: "created_at", column: "c.created_at", descending: true },
+    SortColumn { key: "name", column: "LOWER(c.company_name)", descending: false },
+    SortColumn { key: "status", column: "c.status", descending: false },
+];
+
+#[derive(Deserialize)]
+pub struct CustomerListQuery {
+    page: Option<i64>,
+    per_page: Option<i64>,
+    sort: Option<String>,
+    dir: Option<String>,
+    status: Option<String>,
+    industry: Option<String>,
+    // A tag's id
+    tag: Option<String>,
+}
+
 // Customers List
-pub async fn customers_list(State(db): State<Database>) -> Result<Html<String>, StatusCode> {
-    let customers = sqlx::query_as::<_, Customer>(
-        "SELECT * FROM customers ORDER BY created_at DESC"
-    )
+pub async fn customers_list(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Query(query): Query<CustomerListQuery>,
+) -> Result<Html<String>, StatusCode> {
+    current_user.require("customers:read")?;
+
+    let status = query.status.filter(|status| CUSTOMER_STATUSES.contains(&status.as_str()));
+    let industry = query.industry.filter(|industry| !industry.is_empty());
+    let tag = parse_tag_filter(&query.tag)?;
+
+    // Unset filters are bound as NULL, so the placeholders stay put
+    let mut conditions = vec![
+        "($2::text IS NULL OR c.status = $2)".to_string(),
+        "($3::text IS NULL OR c.industry = $3)".to_string(),
+        format!("($4::uuid IS NULL OR {})", tags::filter_condition(Tagged::Customer, "c", 4)),
+    ];
+    if sharing::is_scoped(&current_user) {
+        conditions.push(sharing::visibility_condition(RecordKind::Customer, "c", 1));
+    }
+    let filter = format!("WHERE {}", conditions.join(" AND "));
+
+    let total = sqlx::query_scalar::<_, i64>(&format!("SELECT COUNT(*) FROM customers c {}", filter))
+        .bind(current_user.id)
+        .bind(&status)
+        .bind(&industry)
+        .bind(tag)
+        .fetch_one(&db)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error counting customers: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    let list = PageRequest::new(query.page, query.per_page, query.sort.as_deref(), query.dir.as_deref(), CUSTOMER_SORTS)
+        .within(total);
+
+    let customers: Vec<CustomerDisplay> = sqlx::query_as::<_, Customer>(&format!(
+        "SELECT c.* FROM customers c {} {} LIMIT $5 OFFSET $6",
+        filter,
+        list.order_by("c.id")
+    ))
+    .bind(current_user.id)
+    .bind(&status)
+    .bind(&industry)
+    .bind(tag)
+    .bind(list.limit())
+    .bind(list.offset())
     .fetch_all(&db)
     .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    .map_err(|e| {
+        tracing::error!("Error loading customers: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?
     .into_iter()
     .map(CustomerDisplay::from)
     .collect();
 
-    let template = CustomersTemplate { customers };
+    let customer_ids: Vec<Uuid> = customers.iter().map(|customer| customer.id).collect();
+    let customer_tags = tags::for_records(&db, Tagged::Customer, &customer_ids).await.map_err(|e| {
+        tracing::error!("Error loading customer tags: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let tag_param = tag.map(|tag| tag.to_string());
+    let filter_query = [("status", &status), ("industry", &industry), ("tag", &tag_param)]
+        .iter()
+        .filter_map(|(name, value)| value.as_ref().map(|value| format!("{}={}&", name, urlencoding::encode(value))))
+        .collect();
+
+    let can_write = current_user.can("customers:write");
+    // With filters set, an empty page means nothing matched rather than
+    // nothing there yet
+    let filtered = status.is_some() || industry.is_some() || tag.is_some();
+    let empty = (total == 0 && !filtered).then(|| customers_empty_state(&current_user, can_write));
+
+    let template = CustomersTemplate {
+        customers,
+        empty,
+        pagination: list.pagination(total, filter_query),
+        statuses: CUSTOMER_STATUSES,
+        industries: load_lookup(&db, "industry").await?,
+        selected_status: status.unwrap_or_default(),
+        selected_industry: industry.unwrap_or_default(),
+        tags: load_tags(&db).await?,
+        selected_tag: tag,
+        customer_tags,
+        per_page_options: PER_PAGE_OPTIONS,
+        can_export: current_user.has_data_export,
+        can_write,
+    };
     Ok(Html(template.render().unwrap()))
 }
 
+// The list pages' tag filter; anything that isn't a tag id matches nothing
+fn parse_tag_filter(tag: &Option<String>) -> Result<Option<Uuid>, StatusCode> {
+    match tag.as_deref().map(str::trim) {
+        None | Some("") => Ok(None),
+        Some(id) => Uuid::parse_str(id).map(Some).map_err(|_| StatusCode::BAD_REQUEST),
+    }
+}
+
+async fn load_tags(db: &Database) -> Result<Vec<Tag>, StatusCode> {
+    tags::list(db).await.map_err(|e| {
+        tracing::error!("Error loading tags: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
+
+async fn load_record_tags(db: &Database, kind: Tagged, record_id: Uuid) -> Result<Vec<Tag>, StatusCode> {
+    tags::for_record(db, kind, record_id).await.map_err(|e| {
+        tracing::error!("Error loading tags: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
+
+async fn load_tag_picker(db: &Database, kind: Tagged, record_id: Option<Uuid>) -> Result<TagPicker, StatusCode> {
+    tags::picker(db, kind, record_id).await.map_err(|e| {
+        tracing::error!("Error loading tags: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
+
+// Applies the tag picker's value to a record and audits any change. Forms
+// without the picker leave the record's tags alone.
+async fn save_tags(
+    db: &Database,
+    current_user: &CurrentUser,
+    kind: Tagged,
+    record_id: Uuid,
+    value: &Option<String>,
+) -> Result<(), StatusCode> {
+    let Some(value) = value else {
+        return Ok(());
+    };
+    let (before, after) = tags::set_for_record(db, kind, record_id, value, current_user.id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error tagging {} {}: {}", kind.key(), record_id, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    if before != after {
+        let _ = create_audit_log(
+            db,
+            current_user,
+            "tag".to_string(),
+            kind.key().to_string(),
+            Some(record_id),
+            Some(serde_json::json!({ "tags": before })),
+            Some(serde_json::json!({ "tags": after })),
+        )
+        .await;
+    }
+    Ok(())
+}
+
+async fn load_custom_fields(db: &Database, kind: Extended) -> Result<Vec<CustomField>, StatusCode> {
+    custom_fields::for_record_type(db, kind).await.map_err(|e| {
+        tracing::error!("Error loading custom fields: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
+
+// Checks a form's custom inputs and merges them into the record's stored
+// values. An inner error is the message to send the user back with.
+async fn custom_field_values(
+    db: &Database,
+    kind: Extended,
+    record_id: Option<Uuid>,
+    submitted: &HashMap<String, String>,
+) -> Result<Result<serde_json::Value, String>, StatusCode> {
+    let fields = load_custom_fields(db, kind).await?;
+    let existing = match record_id {
+        Some(id) => custom_fields::values(db, kind, id)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .ok_or(StatusCode::NOT_FOUND)?,
+        None => serde_json::json!({}),
+    };
+    Ok(custom_fields::collect(&fields, &custom_fields::form_values(submitted), &existing))
+}
+
+fn customers_empty_state(current_user: &CurrentUser, can_write: bool) -> EmptyState {
+    let empty = if sharing::is_scoped(current_user) {
+        EmptyState::new(
+            "🏢",
+            "No customers of yours yet",
+            "You see the customers assigned to you or shared with you. Ones you add are yours.",
+        )
+    } else if can_write {
+        EmptyState::new("🏢", "No customers yet", "Get started by adding your first customer, or bring them over from a spreadsheet.")
+    } else {
+        EmptyState::new("🏢", "No customers yet", "Customers will be listed here once someone adds them.")
+    };
+    empty
+        .action_if(can_write, "Add Customer", "/crm/customers/new")
+        .action_if(can_write, "Import CSV", "/crm/customers/import")
+}
+
+#[derive(sqlx::FromRow)]
+struct CustomerExportRow {
+    company_name: String,
+    industry: Option<String>,
+    status: String,
+    email: Option<String>,
+    phone: Option<String>,
+    city: Option<String>,
+    country: Option<String>,
+    lead_source: Option<String>,
+    campaign_name: Option<String>,
+    contact_count: i64,
+    open_pipeline: rust_decimal::Decimal,
+    created_at: DateTime<Utc>,
+    custom_fields: serde_json::Value,
+}
+
+pub async fn customers_export(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+) -> Result<Response, StatusCode> {
+    current_user.require("customers:read")?;
+    if !current_user.has_data_export {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let scope = if sharing::is_scoped(&current_user) {
+        format!("WHERE {}", sharing::visibility_condition(RecordKind::Customer, "c", 1))
+    } else {
+        String::new()
+    };
+
+    let customers = sqlx::query_as::<_, CustomerExportRow>(&format!(
+        r#"
+        SELECT c.company_name, c.industry, c.status, c.email, c.phone, c.city, c.country,
+               c.lead_source, cp.name as campaign_name,
+               (SELECT COUNT(*) FROM contacts ct WHERE ct.customer_id = c.id) as contact_count,
+               (SELECT COALESCE(SUM(d.base_value), 0) FROM deals d
+                WHERE d.customer_id = c.id AND d.stage NOT IN ('closed_won', 'closed_lost')) as open_pipeline,
+               c.created_at, c.custom_fields
+        FROM customers c
+        LEFT JOIN campaigns cp ON cp.id = c.campaign_id
+        {}
+        ORDER BY c.company_name
+        "#,
+        scope
+    ))
+    .bind(current_user.id)
+    .fetch_all(&db)
+    .await
+    .map_err(|e| {
+        tracing::error!("Error exporting customers: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let fields = load_custom_fields(&db, Extended::Customer).await?;
+    let custom_columns = custom_fields::export_columns(&fields);
+    let mut columns = vec![
+        ("Company", ColumnType::Text),
+        ("Industry", ColumnType::Text),
+        ("Status", ColumnType::Text),
+        ("Email", ColumnType::Text),
+        ("Phone", ColumnType::Text),
+        ("City", ColumnType::Text),
+        ("Country", ColumnType::Text),
+        ("Lead Source", ColumnType::Text),
+        ("Campaign", ColumnType::Text),
+        ("Contacts", ColumnType::Integer),
+        ("Open Pipeline", ColumnType::Currency),
+        ("Created", ColumnType::DateTime),
+    ];
+    columns.extend(custom_columns.iter().map(|(label, kind)| (label.as_str(), *kind)));
+    let mut export = XlsxExport::new("Customers", &columns);
+
+    export.filter("Scope", if sharing::is_scoped(&current_user) { "Records visible to you" } else { "" });
+
+    for customer in customers {
+        let mut cells = vec![
+            customer.company_name.into(),
+            customer.industry.into(),
+            customer.status.into(),
+            customer.email.into(),
+            customer.phone.into(),
+            customer.city.into(),
+            customer.country.into(),
+            customer.lead_source.into(),
+            customer.campaign_name.into(),
+            customer.contact_count.into(),
+            customer.open_pipeline.into(),
+            customer.created_at.into(),
+        ];
+        cells.extend(custom_fields::export_cells(&fields, &customer.custom_fields));
+        export.row(cells);
+    }
+
+    let generated_by = format!("{} {} <{}>", current_user.first_name, current_user.last_name, current_user.email);
+    Ok(export.into_response("customers.xlsx", &generated_by))
+}
+
 // Customer Form (New)
-pub async fn customer_form() -> Html<String> {
+pub async fn customer_form(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Query(query): Query<FormErrorQuery>,
+) -> Result<Html<String>, StatusCode> {
+    current_user.require("customers:write")?;
+
+    let campaigns = load_campaigns(&db).await?;
+
     let template = CustomerFormTemplate {
         customer: None,
+        campaigns,
+        lookups: load_lookups(&db).await?,
+        languages: LANGUAGES.to_vec(),
+        can_delete: false,
+        assignee_picker: load_assignee_picker(&db, None).await?,
+        tag_picker: load_tag_picker(&db, Tagged::Customer, None).await?,
+        custom_fields: custom_fields::inputs(load_custom_fields(&db, Extended::Customer).await?, &serde_json::json!({})),
+        error: query.error,
     };
-    Html(template.render().unwrap())
+    Ok(Html(template.render().unwrap()))
 }
 
 // Customer Form (Edit)
 pub async fn customer_edit_form(
     State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
     Path(id): Path<Uuid>,
+    Query(query): Query<FormErrorQuery>,
 ) -> Result<Html<String>, StatusCode> {
+    current_user.require("customers:write")?;
+    let access = require_access(&db, &current_user, RecordKind::Customer, id, Access::Write).await?;
+
     let customer = sqlx::query_as::<_, Customer>(
         "SELECT * FROM customers WHERE id = $1"
     )
@@ -446,8 +1254,20 @@ pub async fn customer_edit_form(
     .await
     .map_err(|_| StatusCode::NOT_FOUND)?;
 
+    let campaigns = load_campaigns(&db).await?;
+    let assignee_picker = load_assignee_picker(&db, customer.assigned_to).await?;
+    let custom_fields = custom_fields::inputs(load_custom_fields(&db, Extended::Customer).await?, &customer.custom_fields);
+
     let template = CustomerFormTemplate {
         customer: Some(customer.into()),
+        campaigns,
+        lookups: load_lookups(&db).await?,
+        languages: LANGUAGES.to_vec(),
+        can_delete: current_user.can("customers:delete") && access == Access::Manage,
+        assignee_picker,
+        tag_picker: load_tag_picker(&db, Tagged::Customer, Some(id)).await?,
+        custom_fields,
+        error: query.error,
     };
     Ok(Html(template.render().unwrap()))
 }
@@ -455,16 +1275,31 @@ pub async fn customer_edit_form(
 // Create Customer
 pub async fn create_customer(
     State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
     Form(form): Form<CustomerForm>,
 ) -> Result<Redirect, StatusCode> {
+    current_user.require("customers:write")?;
+    check_customer_status(&form.status)?;
+
+    let campaign_id = parse_optional_id(&form.campaign_id)?;
+    let preferred_language = form.preferred_language.as_deref().filter(|code| locale::is_supported(code));
+    let assigned_to = route_assignee(&db, parse_optional_id(&form.assigned_to)?, form.keep_assignee.is_some()).await?;
+    let custom_values = match custom_field_values(&db, Extended::Customer, None, &form.custom).await? {
+        Ok(values) => values,
+        Err(error) => {
+            return Ok(Redirect::to(&format!("/crm/customers/new?error={}", urlencoding::encode(&error))));
+        }
+    };
+
     let customer = sqlx::query_as::<_, Customer>(
         r#"
         INSERT INTO customers (
             company_name, industry, website, phone, email,
             address_line1, address_line2, city, state, postal_code,
-            country, status, notes
+            country, status, notes, campaign_id, created_by, preferred_language, assigned_to,
+            custom_fields
         )
-        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18)
         RETURNING *
         "#,
     )
@@ -481,25 +1316,70 @@ pub async fn create_customer(
     .bind(&form.country)
     .bind(&form.status)
     .bind(&form.notes)
+    .bind(campaign_id)
+    .bind(current_user.id)
+    .bind(preferred_language)
+    .bind(assigned_to)
+    .bind(&custom_values)
     .fetch_one(&db)
     .await
     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
+    audit_log::record(&db, &current_user, "create", Audited::Customer, customer.id, None).await;
+    save_tags(&db, &current_user, Tagged::Customer, customer.id, &form.tags).await?;
+    events::publish(&db, &current_user, Event::CustomerCreated(&customer)).await;
+
     Ok(Redirect::to(&format!("/crm/customers/{}", customer.id)))
 }
 
 // Update Customer
 pub async fn update_customer(
     State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
     Path(id): Path<Uuid>,
     Form(form): Form<CustomerForm>,
 ) -> Result<Redirect, StatusCode> {
+    current_user.require("customers:write")?;
+    let access = require_access(&db, &current_user, RecordKind::Customer, id, Access::Write).await?;
+    check_customer_status(&form.status)?;
+
+    let campaign_id = parse_optional_id(&form.campaign_id)?;
+    let preferred_language = form.preferred_language.as_deref().filter(|code| locale::is_supported(code));
+
+    let previous_assignee = sqlx::query_scalar::<_, Option<Uuid>>("SELECT assigned_to FROM customers WHERE id = $1")
+        .bind(id)
+        .fetch_one(&db)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+
+    // As with deals, handing the customer to someone else takes the access
+    // needed to share it
+    let chosen = parse_optional_id(&form.assigned_to)?;
+    let assigned_to = if chosen == previous_assignee {
+        previous_assignee
+    } else if access < Access::Manage {
+        return Err(StatusCode::FORBIDDEN);
+    } else {
+        route_assignee(&db, chosen, form.keep_assignee.is_some()).await?
+    };
+    let custom_values = match custom_field_values(&db, Extended::Customer, Some(id), &form.custom).await? {
+        Ok(values) => values,
+        Err(error) => {
+            return Ok(Redirect::to(&format!("/crm/customers/{}/edit?error={}", id, urlencoding::encode(&error))));
+        }
+    };
+
+    let before = audit_log::snapshot(&db, Audited::Customer, id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
     let customer = sqlx::query_as::<_, Customer>(
         r#"
         UPDATE customers SET
             company_name = $2, industry = $3, website = $4, phone = $5, email = $6,
             address_line1 = $7, address_line2 = $8, city = $9, state = $10, postal_code = $11,
-            country = $12, status = $13, notes = $14, updated_at = NOW()
+            country = $12, status = $13, notes = $14, campaign_id = $15, preferred_language = $16,
+            assigned_to = $17, custom_fields = $18, updated_at = NOW()
         WHERE id = $1
         RETURNING *
         "#,
@@ -518,22 +1398,139 @@ pub async fn update_customer(
     .bind(&form.country)
     .bind(&form.status)
     .bind(&form.notes)
+    .bind(campaign_id)
+    .bind(preferred_language)
+    .bind(assigned_to)
+    .bind(&custom_values)
     .fetch_one(&db)
     .await
     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
+    audit_log::record(&db, &current_user, "update", Audited::Customer, id, before).await;
+    save_tags(&db, &current_user, Tagged::Customer, id, &form.tags).await?;
+    events::publish(&db, &current_user, Event::CustomerUpdated(&customer)).await;
+
     Ok(Redirect::to(&format!("/crm/customers/{}", customer.id)))
 }
 
+fn check_customer_status(status: &str) -> Result<(), StatusCode> {
+    if CUSTOMER_STATUSES.contains(&status) {
+        Ok(())
+    } else {
+        Err(StatusCode::BAD_REQUEST)
+    }
+}
+
+// Change just the customer's status, from the list page
+pub async fn patch_customer_status(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+    axum::Json(patch): axum::Json<CustomerStatusPatch>,
+) -> Result<axum::Json<serde_json::Value>, StatusCode> {
+    current_user.require("customers:write")?;
+    require_access(&db, &current_user, RecordKind::Customer, id, Access::Write).await?;
+    check_customer_status(&patch.status)?;
+
+    let before = audit_log::snapshot(&db, Audited::Customer, id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let customer = sqlx::query_as::<_, Customer>(
+        "UPDATE customers SET status = $2, updated_at = NOW() WHERE id = $1 RETURNING *",
+    )
+    .bind(id)
+    .bind(&patch.status)
+    .fetch_one(&db)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    audit_log::record(&db, &current_user, "update", Audited::Customer, id, before).await;
+    events::publish(&db, &current_user, Event::CustomerUpdated(&customer)).await;
+
+    Ok(axum::Json(serde_json::json!({ "id": customer.id, "status": customer.status })))
+}
+
+pub(crate) async fn load_campaigns(db: &Database) -> Result<Vec<Campaign>, StatusCode> {
+    sqlx::query_as::<_, Campaign>("SELECT * FROM campaigns ORDER BY start_date DESC NULLS LAST, name")
+        .fetch_all(db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+async fn load_lookups(db: &Database) -> Result<Lookups, StatusCode> {
+    lookups::load(db).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+async fn load_lookup(db: &Database, kind: &str) -> Result<LookupOptions, StatusCode> {
+    lookups::options(db, kind).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+// New records only take options that are currently offered. Edits aren't
+// checked, so records using a retired option can still be saved.
+async fn require_lookup(db: &Database, kind: &str, value: &str) -> Result<(), StatusCode> {
+    match lookups::is_allowed(db, kind, value).await {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(StatusCode::BAD_REQUEST),
+        Err(_) => Err(StatusCode::INTERNAL_SERVER_ERROR),
+    }
+}
+
+pub(crate) fn parse_optional_id(id: &Option<String>) -> Result<Option<Uuid>, StatusCode> {
+    match id {
+        Some(id) if !id.trim().is_empty() => Ok(Some(Uuid::parse_str(id).map_err(|_| StatusCode::BAD_REQUEST)?)),
+        _ => Ok(None),
+    }
+}
+
+// Customers offered when picking one for a deal or activity
+pub(crate) async fn load_visible_customers(db: &Database, user: &CurrentUser) -> Result<Vec<Customer>, StatusCode> {
+    let scope = if sharing::is_scoped(user) {
+        format!("WHERE {}", sharing::visibility_condition(RecordKind::Customer, "c", 1))
+    } else {
+        String::new()
+    };
+
+    sqlx::query_as::<_, Customer>(&format!("SELECT c.* FROM customers c {} ORDER BY c.company_name", scope))
+        .bind(user.id)
+        .fetch_all(db)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error loading customers: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+}
+
+pub(crate) async fn load_assignee_picker(db: &Database, selected: Option<Uuid>) -> Result<AssigneePicker, StatusCode> {
+    AssigneePicker::load(db, selected).await.map_err(|e| {
+        tracing::error!("Error loading assignees: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
+
+// A newly chosen assignee who is out of office hands over to their backup,
+// unless the form asked to keep them
+pub(crate) async fn route_assignee(db: &Database, chosen: Option<Uuid>, keep: bool) -> Result<Option<Uuid>, StatusCode> {
+    let Some(assigned_to) = chosen else {
+        return Ok(None);
+    };
+    if keep {
+        return Ok(Some(assigned_to));
+    }
+    out_of_office::route(db, assigned_to).await.map(Some).map_err(|e| {
+        tracing::error!("Error routing assignment: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
+
 // Customer Detail
 pub async fn customer_detail(
     State(db): State<Database>,
-    cookies: Cookies,
+    AuthUser(current_user): AuthUser,
     Path(id): Path<Uuid>,
+    Query(query): Query<CustomerDetailQuery>,
 ) -> Result<Html<String>, StatusCode> {
-    let current_user = get_current_user(cookies, &db)
-        .await
-        .ok_or(StatusCode::UNAUTHORIZED)?;
+    current_user.require("customers:read")?;
+    let access = require_access(&db, &current_user, RecordKind::Customer, id, Access::Read).await?;
 
     let customer = sqlx::query_as::<_, Customer>(
         "SELECT * FROM customers WHERE id = $1"
@@ -554,7 +1551,8 @@ pub async fn customer_detail(
     .map(ContactDisplay::from)
     .collect();
 
-    let deals = sqlx::query_as::<_, Deal>(
+    let fields = current_user.field_access();
+    let mut deals: Vec<DealDisplay> = sqlx::query_as::<_, Deal>(
         "SELECT * FROM deals WHERE customer_id = $1 ORDER BY created_at DESC"
     )
     .bind(id)
@@ -562,9 +1560,11 @@ pub async fn customer_detail(
     .await
     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
     .into_iter()
-    .map(DealDisplay::from)
+    .map(|deal| DealDisplay::new(deal, &fields))
     .collect();
+    deal_health::mark_stalled(&db, &mut deals).await;
 
+    let activity_types = load_activity_types(&db, true).await?;
     let activities = sqlx::query_as::<_, Activity>(
         "SELECT * FROM activities WHERE customer_id = $1 ORDER BY activity_date DESC LIMIT 10"
     )
@@ -573,15 +1573,86 @@ pub async fn customer_detail(
     .await
     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
     .into_iter()
-    .map(ActivityDisplay::from)
+    .map(|activity| ActivityDisplay::in_timezone(activity, current_user.timezone).with_type(&activity_types))
     .collect();
 
+    let campaign_name = match customer.campaign_id {
+        Some(campaign_id) => sqlx::query_scalar::<_, String>("SELECT name FROM campaigns WHERE id = $1")
+            .bind(campaign_id)
+            .fetch_optional(&db)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?,
+        None => None,
+    };
+
+    let sees_inventory = current_user.permissions.iter().any(|p| p == "inventory:read");
+    let part_numbers = if sees_inventory {
+        part_numbers::for_customer(&db, id).await.map_err(|e| {
+            tracing::error!("Error loading part numbers: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+    } else {
+        Vec::new()
+    };
+    let shows_pricing = sees_inventory && current_user.has_finance_read;
+    let pricing_agreements = if shows_pricing {
+        pricing_agreements::for_customer(&db, id).await.map_err(|e| {
+            tracing::error!("Error loading pricing agreements: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+    } else {
+        Vec::new()
+    };
+    let blanket_orders = if sees_inventory {
+        blanket_orders::for_customer(&db, id).await.map_err(|e| {
+            tracing::error!("Error loading blanket orders: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+    } else {
+        Vec::new()
+    };
+    let can_write = current_user.can("customers:write") && access >= Access::Write;
+    let part_number_items = if sees_inventory && can_write {
+        sqlx::query_as::<_, InventoryItem>(
+            r#"
+            SELECT * FROM inventory_items i
+            WHERE i.is_active = true
+              AND NOT EXISTS (SELECT 1 FROM inventory_items v WHERE v.parent_item_id = i.id)
+            ORDER BY i.item_name
+            "#
+        )
+        .fetch_all(&db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    } else {
+        Vec::new()
+    };
+
+    let watch = load_watch_button(&db, RecordKind::Customer, id, current_user.id).await?;
+    let notes = load_notes_panel(&db, &current_user, RecordKind::Customer, id, access, query.note_error).await?;
+    let custom_values = custom_fields::display(&load_custom_fields(&db, Extended::Customer).await?, &customer.custom_fields);
     let template = CustomerDetailTemplate {
         customer: CustomerDisplay::from(customer),
+        campaign_name,
         contacts,
         deals,
         activities,
         current_user,
+        sharing: load_sharing_panel(&db, RecordKind::Customer, id, access).await?,
+        watch,
+        notes,
+        tags: load_record_tags(&db, Tagged::Customer, id).await?,
+        custom_values,
+        can_write,
+        custom_fields: custom_fields::inputs(load_custom_fields(&db, Extended::Contact).await?, &serde_json::json!({})),
+        contact_error: query.contact_error,
+        part_numbers,
+        part_number_items,
+        part_number_taken: query.part_number_taken,
+        pricing_agreements,
+        shows_pricing,
+        pricing_error: query.pricing_error,
+        blanket_orders,
     };
     
     Ok(Html(template.render().unwrap()))
@@ -590,8 +1661,23 @@ pub async fn customer_detail(
 // Create Contact
 pub async fn create_contact(
     State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
     Form(form): Form<ContactForm>,
 ) -> Result<Redirect, StatusCode> {
+    current_user.require("customers:write")?;
+    require_access(&db, &current_user, RecordKind::Customer, form.customer_id, Access::Write).await?;
+
+    let custom_values = match custom_field_values(&db, Extended::Contact, None, &form.custom).await? {
+        Ok(values) => values,
+        Err(error) => {
+            return Ok(Redirect::to(&format!(
+                "/crm/customers/{}?contact_error={}",
+                form.customer_id,
+                urlencoding::encode(&error)
+            )));
+        }
+    };
+
     let is_primary = form.is_primary.is_some();
     
     if is_primary {
@@ -602,12 +1688,19 @@ pub async fn create_contact(
             .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
     }
 
-    sqlx::query(
+    let business_card_url = form
+        .business_card_url
+        .as_deref()
+        .filter(|url| storage::is_stored(contact_photos::BUSINESS_CARDS, url));
+
+    let contact_id = sqlx::query_scalar::<_, Uuid>(
         r#"
         INSERT INTO contacts (
-            customer_id, first_name, last_name, title, email, phone, mobile, is_primary, notes
+            customer_id, first_name, last_name, title, email, phone, mobile, is_primary, notes, business_card_url,
+            custom_fields
         )
-        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+        RETURNING id
         "#,
     )
     .bind(form.customer_id)
@@ -619,18 +1712,26 @@ pub async fn create_contact(
     .bind(&form.mobile)
     .bind(is_primary)
     .bind(&form.notes)
-    .execute(&db)
+    .bind(business_card_url)
+    .bind(&custom_values)
+    .fetch_one(&db)
     .await
     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
+    audit_log::record(&db, &current_user, "create", Audited::Contact, contact_id, None).await;
+
     Ok(Redirect::to(&format!("/crm/customers/{}", form.customer_id)))
 }
 
 // Delete Customer
 pub async fn delete_customer(
     State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
     Path(id): Path<Uuid>,
 ) -> Result<Redirect, StatusCode> {
+    current_user.require("customers:delete")?;
+    require_access(&db, &current_user, RecordKind::Customer, id, Access::Manage).await?;
+
     // First delete related contacts
     sqlx::query("DELETE FROM contacts WHERE customer_id = $1")
         .bind(id)
@@ -653,9 +1754,8 @@ pub async fn delete_customer(
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
     // Finally delete the customer
-    sqlx::query("DELETE FROM customers WHERE id = $1")
-        .bind(id)
-        .execute(&db)
+    let delete = sqlx::query("DELETE FROM customers WHERE id = $1").bind(id);
+    audited_execute(&db, &current_user, "delete", Audited::Customer, id, delete)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
@@ -665,51 +1765,460 @@ pub async fn delete_customer(
 // Delete Contact
 pub async fn delete_contact(
     State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
     Path((customer_id, contact_id)): Path<(Uuid, Uuid)>,
 ) -> Result<Redirect, StatusCode> {
-    sqlx::query("DELETE FROM contacts WHERE id = $1 AND customer_id = $2")
+    current_user.require("customers:write")?;
+    require_access(&db, &current_user, RecordKind::Customer, customer_id, Access::Write).await?;
+
+    let images = sqlx::query_as::<_, (Option<String>, Option<String>)>(
+        "SELECT photo_url, business_card_url FROM contacts WHERE id = $1 AND customer_id = $2",
+    )
+    .bind(contact_id)
+    .bind(customer_id)
+    .fetch_optional(&db)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let delete = sqlx::query("DELETE FROM contacts WHERE id = $1 AND customer_id = $2")
         .bind(contact_id)
-        .bind(customer_id)
-        .execute(&db)
+        .bind(customer_id);
+    audited_execute(&db, &current_user, "delete", Audited::Contact, contact_id, delete)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
+    if let Some((photo_url, business_card_url)) = images {
+        if let Some(url) = photo_url {
+            storage::remove(contact_photos::PHOTOS, &url).await;
+        }
+        if let Some(url) = business_card_url {
+            storage::remove(contact_photos::BUSINESS_CARDS, &url).await;
+        }
+    }
+
     Ok(Redirect::to(&format!("/crm/customers/{}", customer_id)))
 }
 
+pub async fn add_part_number(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+    Form(form): Form<PartNumberForm>,
+) -> Result<Redirect, StatusCode> {
+    current_user.require("customers:write")?;
+    require_access(&db, &current_user, RecordKind::Customer, id, Access::Write).await?;
+    current_user.require("inventory:read")?;
+
+    let part_number = form.part_number.trim();
+    if part_number.is_empty() || part_number.len() > 100 {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let description = form.description.as_deref().map(str::trim).filter(|d| !d.is_empty());
+
+    let added = part_numbers::add(&db, id, form.item_id, part_number, description, current_user.id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error adding part number: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    let Some(part_number_id) = added else {
+        return Ok(Redirect::to(&format!(
+            "/crm/customers/{}?part_number_taken={}#part-numbers",
+            id,
+            urlencoding::encode(part_number)
+        )));
+    };
+
+    let _ = create_audit_log(
+        &db,
+        &current_user,
+        "add_part_number".to_string(),
+        "customer".to_string(),
+        Some(id),
+        None,
+        Some(serde_json::json!({ "id": part_number_id, "item_id": form.item_id, "part_number": part_number })),
+    ).await;
+
+    Ok(Redirect::to(&format!("/crm/customers/{}#part-numbers", id)))
+}
+
+pub async fn delete_part_number(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path((id, part_number_id)): Path<(Uuid, Uuid)>,
+) -> Result<Redirect, StatusCode> {
+    current_user.require("customers:write")?;
+    require_access(&db, &current_user, RecordKind::Customer, id, Access::Write).await?;
+    current_user.require("inventory:read")?;
+
+    let removed = part_numbers::remove(&db, id, part_number_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if removed {
+        let _ = create_audit_log(
+            &db,
+            &current_user,
+            "delete_part_number".to_string(),
+            "customer".to_string(),
+            Some(id),
+            Some(serde_json::json!({ "id": part_number_id })),
+            None,
+        ).await;
+    }
+
+    Ok(Redirect::to(&format!("/crm/customers/{}#part-numbers", id)))
+}
+
+pub async fn add_pricing_agreement(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+    Form(form): Form<PricingAgreementForm>,
+) -> Result<Redirect, StatusCode> {
+    current_user.require("customers:write")?;
+    require_access(&db, &current_user, RecordKind::Customer, id, Access::Write).await?;
+    current_user.require("inventory:read")?;
+    if !current_user.has_finance_read {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let refused = |reason: &str| {
+        Ok(Redirect::to(&format!("/crm/customers/{}?pricing_error={}#pricing", id, urlencoding::encode(reason))))
+    };
+
+    let agreement = NewAgreement {
+        item_id: form.item_id,
+        fixed_price: parse_optional_decimal(&form.fixed_price)?,
+        discount_percent: parse_optional_decimal(&form.discount_percent)?,
+        valid_from: parse_optional_date(&form.valid_from)?.unwrap_or_else(|| chrono::Utc::now().date_naive()),
+        valid_until: parse_optional_date(&form.valid_until)?,
+        notes: form.notes.as_deref().map(str::trim).filter(|n| !n.is_empty()).map(str::to_string),
+    };
+
+    // An agreed price is a standing discount, so it needs the same approval
+    // a discount that size would on a single deal
+    let selling_price = sqlx::query_scalar::<_, Option<rust_decimal::Decimal>>("SELECT selling_price FROM inventory_items WHERE id = $1")
+        .bind(form.item_id)
+        .fetch_optional(&db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::BAD_REQUEST)?;
+    let hundred = rust_decimal::Decimal::from(100);
+    let percent_off = match (agreement.discount_percent, agreement.fixed_price, selling_price) {
+        (Some(percent), _, _) => percent,
+        (None, Some(price), Some(list)) if list > rust_decimal::Decimal::ZERO && price < list => (list - price) / list * hundred,
+        _ => rust_decimal::Decimal::ZERO,
+    };
+    let status = discounts::initial_status(&db, &current_user, percent_off)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if status == "pending" {
+        return refused(&format!(
+            "An agreement {}% below the selling price needs someone who can approve discounts that size",
+            percent_off.round_dp(1)
+        ));
+    }
+
+    let added = pricing_agreements::add(&db, id, &agreement, current_user.id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error adding pricing agreement: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    let agreement_id = match added {
+        Ok(agreement_id) => agreement_id,
+        Err(reason) => return refused(&reason),
+    };
+
+    let _ = create_audit_log(
+        &db,
+        &current_user,
+        "add_pricing_agreement".to_string(),
+        "customer".to_string(),
+        Some(id),
+        None,
+        Some(serde_json::json!({
+            "id": agreement_id,
+            "item_id": agreement.item_id,
+            "fixed_price": agreement.fixed_price,
+            "discount_percent": agreement.discount_percent,
+            "valid_from": agreement.valid_from,
+            "valid_until": agreement.valid_until,
+        })),
+    ).await;
+
+    Ok(Redirect::to(&format!("/crm/customers/{}#pricing", id)))
+}
+
+pub async fn delete_pricing_agreement(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path((id, agreement_id)): Path<(Uuid, Uuid)>,
+) -> Result<Redirect, StatusCode> {
+    current_user.require("customers:write")?;
+    require_access(&db, &current_user, RecordKind::Customer, id, Access::Write).await?;
+    if !current_user.has_finance_read {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let removed = pricing_agreements::remove(&db, id, agreement_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if removed {
+        let _ = create_audit_log(
+            &db,
+            &current_user,
+            "delete_pricing_agreement".to_string(),
+            "customer".to_string(),
+            Some(id),
+            Some(serde_json::json!({ "id": agreement_id })),
+            None,
+        ).await;
+    }
+
+    Ok(Redirect::to(&format!("/crm/customers/{}#pricing", id)))
+}
+
+async fn load_team_filter(db: &Database, current_user: &CurrentUser, path: &'static str, query: &ListQuery) -> Result<TeamFilter, StatusCode> {
+    TeamFilter::load(db, current_user.id, path, query.show.as_deref()).await.map_err(|e| {
+        tracing::error!("Error loading teams: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
+
+// WHERE clause for a list of `kind` aliased as `alias`, with the viewer bound at $1
+fn list_scope(current_user: &CurrentUser, kind: RecordKind, alias: &str, team_filter: &TeamFilter) -> String {
+    let mut conditions = Vec::new();
+    if sharing::is_scoped(current_user) {
+        conditions.push(sharing::visibility_condition(kind, alias, 1));
+    }
+    conditions.extend(team_filter.condition(&format!("COALESCE({0}.assigned_to, {0}.created_by)", alias), "$1"));
+    if conditions.is_empty() {
+        String::new()
+    } else {
+        format!("WHERE {}", conditions.join(" AND "))
+    }
+}
+
 // Deals functions
 pub async fn deals_list(
     State(db): State<Database>,
-    cookies: Cookies,
+    AuthUser(current_user): AuthUser,
+    Query(query): Query<ListQuery>,
 ) -> Result<Html<String>, StatusCode> {
-    let current_user = get_current_user(cookies, &db)
+    let team_filter = load_team_filter(&db, &current_user, "/crm/deals", &query).await?;
+    let mut scope = list_scope(&current_user, RecordKind::Deal, "d", &team_filter);
+    let owner = match query.owner.as_deref().map(str::trim) {
+        None | Some("") => None,
+        Some("me") => Some(current_user.id),
+        Some(id) => Some(Uuid::parse_str(id).map_err(|_| StatusCode::BAD_REQUEST)?),
+    };
+    if owner.is_some() {
+        scope.push_str(if scope.is_empty() { "WHERE " } else { " AND " });
+        scope.push_str("COALESCE(d.assigned_to, d.created_by) = $2");
+    }
+    let tag = parse_tag_filter(&query.tag)?;
+    if tag.is_some() {
+        scope.push_str(if scope.is_empty() { "WHERE " } else { " AND " });
+        scope.push_str(&tags::filter_condition(Tagged::Deal, "d", if owner.is_some() { 3 } else { 2 }));
+    }
+
+    let fields = current_user.field_access();
+    let sql = format!("SELECT d.* FROM deals d {} ORDER BY d.created_at DESC", scope);
+    let mut deals_query = sqlx::query_as::<_, Deal>(&sql).bind(current_user.id);
+    if let Some(owner) = owner {
+        deals_query = deals_query.bind(owner);
+    }
+    if let Some(tag) = tag {
+        deals_query = deals_query.bind(tag);
+    }
+    let mut deals: Vec<DealDisplay> = deals_query
+        .fetch_all(&db)
         .await
-        .ok_or(StatusCode::UNAUTHORIZED)?;
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .into_iter()
+        .map(|deal| DealDisplay::new(deal, &fields))
+        .collect();
+    deal_health::mark_stalled(&db, &mut deals).await;
+    let owners = load_assignee_picker(&db, owner).await?;
+    let mine = owner == Some(current_user.id);
+    let deal_ids: Vec<Uuid> = deals.iter().map(|deal| deal.id).collect();
+    let deal_tags = tags::for_records(&db, Tagged::Deal, &deal_ids).await.map_err(|e| {
+        tracing::error!("Error loading deal tags: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
 
-    let deals = sqlx::query_as::<_, Deal>(
-        "SELECT * FROM deals ORDER BY created_at DESC"
-    )
+    let empty = if !deals.is_empty() {
+        None
+    } else if tag.is_some() {
+        Some(
+            EmptyState::new("🏷️", "No deals with this tag", "None of the deals you can see are tagged with it.")
+                .action("Show All Deals", "/crm/deals"),
+        )
+    } else if mine {
+        Some(
+            EmptyState::new("🔍", "No deals of yours", "Nothing here is assigned to you or was created by you.")
+                .action("Show Everyone's", "/crm/deals"),
+        )
+    } else if owner.is_some() {
+        Some(
+            EmptyState::new("🔍", "No deals for this owner", "They don't own any deals you can see.")
+                .action("Show Everyone's", "/crm/deals"),
+        )
+    } else if team_filter.team_only {
+        Some(
+            EmptyState::new("🔍", "No deals for your team", "Nobody on your teams owns a deal yet.")
+                .action("Show Everyone's", "/crm/deals"),
+        )
+    } else {
+        Some(deals_empty_state(&db, &current_user).await?)
+    };
+
+    let template = DealsTemplate {
+        deals,
+        empty,
+        current_user,
+        team_filter,
+        owners,
+        mine,
+        tags: load_tags(&db).await?,
+        selected_tag: tag,
+        deal_tags,
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+// A deal hangs off a customer, so with none to pick the way in is adding one
+async fn deals_empty_state(db: &Database, current_user: &CurrentUser) -> Result<EmptyState, StatusCode> {
+    if load_visible_customers(db, current_user).await?.is_empty() {
+        let can_add_customers = current_user.can("customers:write");
+        let message = if can_add_customers {
+            "Deals are tracked against a customer. Add one to start your pipeline."
+        } else {
+            "Deals are tracked against a customer, and there are none you can see yet."
+        };
+        return Ok(EmptyState::new("💼", "No deals yet", message)
+            .action_if(can_add_customers, "Add Customer", "/crm/customers/new"));
+    }
+
+    let message = if sharing::is_scoped(current_user) {
+        "You see the deals assigned to you or shared with you. Start tracking your own pipeline."
+    } else {
+        "Start tracking your sales pipeline."
+    };
+    Ok(EmptyState::new("💼", "No deals yet", message).action("Create First Deal", "/crm/deals/new"))
+}
+
+#[derive(sqlx::FromRow)]
+struct DealExportRow {
+    id: Uuid,
+    title: String,
+    company_name: String,
+    stage: String,
+    value: Option<rust_decimal::Decimal>,
+    currency: String,
+    probability: i32,
+    expected_close_date: Option<NaiveDate>,
+    actual_close_date: Option<NaiveDate>,
+    owner_name: Option<String>,
+    campaign_name: Option<String>,
+    created_at: DateTime<Utc>,
+    custom_fields: serde_json::Value,
+}
+
+pub async fn deals_export(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+) -> Result<Response, StatusCode> {
+    if !current_user.has_data_export {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let scope = if sharing::is_scoped(&current_user) {
+        format!("WHERE {}", sharing::visibility_condition(RecordKind::Deal, "d", 1))
+    } else {
+        String::new()
+    };
+
+    let deals = sqlx::query_as::<_, DealExportRow>(&format!(
+        r#"
+        SELECT d.id, d.title, c.company_name, d.stage, d.value, d.currency, d.probability,
+               d.expected_close_date, d.actual_close_date,
+               NULLIF(CONCAT(u.first_name, ' ', u.last_name), ' ') as owner_name,
+               cp.name as campaign_name,
+               d.created_at, d.custom_fields
+        FROM deals d
+        JOIN customers c ON c.id = d.customer_id
+        LEFT JOIN users u ON u.id = COALESCE(d.assigned_to, d.created_by)
+        LEFT JOIN campaigns cp ON cp.id = COALESCE(d.campaign_id, c.campaign_id)
+        {}
+        ORDER BY d.created_at DESC
+        "#,
+        scope
+    ))
+    .bind(current_user.id)
     .fetch_all(&db)
     .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-    .into_iter()
-    .map(DealDisplay::from)
-    .collect();
+    .map_err(|e| {
+        tracing::error!("Error exporting deals: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
 
-    let template = DealsTemplate { deals, current_user };
-    Ok(Html(template.render().unwrap()))
+    let deal_ids: Vec<Uuid> = deals.iter().map(|deal| deal.id).collect();
+    let stalled = deal_health::stalled_deal_ids(&db, &deal_ids).await.unwrap_or_default();
+
+    let fields = load_custom_fields(&db, Extended::Deal).await?;
+    let custom_columns = custom_fields::export_columns(&fields);
+    let mut columns = vec![
+        ("Deal", ColumnType::Text),
+        ("Customer", ColumnType::Text),
+        ("Stage", ColumnType::Text),
+        ("Value", ColumnType::Currency),
+        ("Currency", ColumnType::Text),
+        ("Probability", ColumnType::Percent),
+        ("Expected Close", ColumnType::Date),
+        ("Actual Close", ColumnType::Date),
+        ("Owner", ColumnType::Text),
+        ("Campaign", ColumnType::Text),
+        ("Stalled", ColumnType::Text),
+        ("Created", ColumnType::DateTime),
+    ];
+    columns.extend(custom_columns.iter().map(|(label, kind)| (label.as_str(), *kind)));
+    let mut export = XlsxExport::new("Deals", &columns);
+
+    export.filter("Scope", if sharing::is_scoped(&current_user) { "Records visible to you" } else { "" });
+
+    for deal in deals {
+        let mut cells = vec![
+            deal.title.into(),
+            deal.company_name.into(),
+            deal.stage.replace('_', " ").into(),
+            deal.value.into(),
+            deal.currency.into(),
+            (f64::from(deal.probability) / 100.0).into(),
+            deal.expected_close_date.into(),
+            deal.actual_close_date.into(),
+            deal.owner_name.into(),
+            deal.campaign_name.into(),
+            if stalled.contains(&deal.id) { "Yes" } else { "" }.into(),
+            deal.created_at.into(),
+        ];
+        cells.extend(custom_fields::export_cells(&fields, &deal.custom_fields));
+        export.row(cells);
+    }
+
+    let generated_by = format!("{} {} <{}>", current_user.first_name, current_user.last_name, current_user.email);
+    Ok(export.into_response("deals.xlsx", &generated_by))
 }
 
 pub async fn deal_form(
     State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
     Query(query): Query<DealQuery>,
 ) -> Result<Html<String>, StatusCode> {
-    let customers = sqlx::query_as::<_, Customer>(
-        "SELECT * FROM customers ORDER BY company_name"
-    )
-    .fetch_all(&db)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if let Some(customer_id) = query.customer_id {
+        require_access(&db, &current_user, RecordKind::Customer, customer_id, Access::Read).await?;
+    }
+    let customers = load_visible_customers(&db, &current_user).await?;
 
     let contacts = if let Some(customer_id) = query.customer_id {
         sqlx::query_as::<_, Contact>(
@@ -723,19 +2232,38 @@ pub async fn deal_form(
         Vec::new()
     };
 
+    let campaigns = load_campaigns(&db).await?;
+
     let template = DealFormTemplate {
         deal: None,
         customers,
         contacts,
         customer_id: query.customer_id,
+        campaigns,
+        show_value: current_user.has_finance_read,
+        lookups: load_lookups(&db).await?,
+        assignee_picker: load_assignee_picker(&db, None).await?,
+        tag_picker: load_tag_picker(&db, Tagged::Deal, None).await?,
+        custom_fields: custom_fields::inputs(load_custom_fields(&db, Extended::Deal).await?, &serde_json::json!({})),
+        error: query.error,
     };
     Ok(Html(template.render().unwrap()))
 }
 
+#[derive(Deserialize)]
+pub struct DealDetailQuery {
+    sequence_error: Option<String>,
+    note_error: Option<String>,
+}
+
 pub async fn deal_detail(
     State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
     Path(id): Path<Uuid>,
+    Query(query): Query<DealDetailQuery>,
 ) -> Result<Html<String>, StatusCode> {
+    let access = require_access(&db, &current_user, RecordKind::Deal, id, Access::Read).await?;
+
     let deal = sqlx::query_as::<_, Deal>(
         "SELECT * FROM deals WHERE id = $1"
     )
@@ -764,35 +2292,131 @@ pub async fn deal_detail(
         None
     };
 
-    let template = DealDetailTemplate {
-        deal: DealDisplay::from(deal),
-        customer,
-        contact,
-    };
-    
-    Ok(Html(template.render().unwrap()))
-}
+    let custom_values = custom_fields::display(&load_custom_fields(&db, Extended::Deal).await?, &deal.custom_fields);
+    let mut deal = DealDisplay::new(deal, &current_user.field_access());
+    deal_health::mark_stalled(&db, std::slice::from_mut(&mut deal)).await;
 
-pub async fn deal_edit_form(
-    State(db): State<Database>,
-    Path(id): Path<Uuid>,
-) -> Result<Html<String>, StatusCode> {
-    let deal = sqlx::query_as::<_, Deal>(
-        "SELECT * FROM deals WHERE id = $1"
+    let line_items = sqlx::query_as::<_, DealLineItem>(
+        "SELECT * FROM deal_line_items WHERE deal_id = $1 ORDER BY created_at"
     )
     .bind(id)
-    .fetch_one(&db)
-    .await
-    .map_err(|_| StatusCode::NOT_FOUND)?;
-
-    let customers = sqlx::query_as::<_, Customer>(
-        "SELECT * FROM customers ORDER BY company_name"
-    )
     .fetch_all(&db)
     .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
-    let contacts = sqlx::query_as::<_, Contact>(
+    .map_err(|e| {
+        tracing::error!("Error loading deal line items: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    let line_items_total = line_items.iter().map(DealLineItem::total).sum();
+    let unapproved_discounts = line_items
+        .iter()
+        .filter(|line| line.discount_status == "pending" || line.discount_status == "denied")
+        .count() as i64;
+
+    let price_checks = if current_user.has_finance_read {
+        price_history::check_deal(&db, id).await.map_err(|e| {
+            tracing::error!("Error checking deal prices: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+    } else {
+        Vec::new()
+    };
+    let expiring_agreements = if current_user.has_finance_read {
+        pricing_agreements::expiring_for_deal(&db, id).await.map_err(|e| {
+            tracing::error!("Error loading expiring pricing agreements: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+    } else {
+        Vec::new()
+    };
+
+    // Prices are financial fields, so only those who see them can add lines
+    let inventory_items = if access >= Access::Write && current_user.has_finance_read {
+        // An item with variants is sold as one of them
+        sqlx::query_as::<_, InventoryItem>(
+            r#"
+            SELECT * FROM inventory_items i
+            WHERE i.is_active = true
+              AND NOT EXISTS (SELECT 1 FROM inventory_items v WHERE v.parent_item_id = i.id)
+            ORDER BY i.item_name
+            "#
+        )
+        .fetch_all(&db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    } else {
+        Vec::new()
+    };
+
+    let customer_part_numbers = if inventory_items.is_empty() {
+        Vec::new()
+    } else {
+        part_numbers::for_customer(&db, customer.id).await.map_err(|e| {
+            tracing::error!("Error loading part numbers: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+    };
+
+    let template = DealDetailTemplate {
+        deal,
+        base_currency: exchange_rates::base_currency(&db).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?,
+        customer,
+        contact,
+        sharing: load_sharing_panel(&db, RecordKind::Deal, id, access).await?,
+        watch: load_watch_button(&db, RecordKind::Deal, id, current_user.id).await?,
+        notes: load_notes_panel(&db, &current_user, RecordKind::Deal, id, access, query.note_error).await?,
+        tags: load_record_tags(&db, Tagged::Deal, id).await?,
+        custom_values,
+        line_items,
+        line_items_total,
+        unapproved_discounts,
+        inventory_items,
+        thresholds: discounts::thresholds(&db).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?,
+        price_checks,
+        expiring_agreements,
+        customer_part_numbers,
+        show_history: current_user.can("audit:read") && current_user.has_data_export,
+        stage_history: stage_history::for_deal(&db, id).await.map_err(|e| {
+            tracing::error!("Error loading stage history: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?,
+        sequence_panel: load_sequence_panel(
+            &db,
+            format!("/crm/deals/{}/sequences", id),
+            sequences::for_deal(&db, id).await.map_err(|e| {
+                tracing::error!("Error loading sequence enrollments: {}", e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?,
+            current_user.can("customers:write") && access >= Access::Write,
+            query.sequence_error,
+        )
+        .await?,
+    };
+    
+    Ok(Html(template.render().unwrap()))
+}
+
+pub async fn deal_edit_form(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+    Query(query): Query<FormErrorQuery>,
+) -> Result<Html<String>, StatusCode> {
+    require_access(&db, &current_user, RecordKind::Deal, id, Access::Write).await?;
+
+    let mut deal = sqlx::query_as::<_, Deal>(
+        "SELECT * FROM deals WHERE id = $1"
+    )
+    .bind(id)
+    .fetch_one(&db)
+    .await
+    .map_err(|_| StatusCode::NOT_FOUND)?;
+    if !current_user.has_finance_read {
+        deal.value = None;
+    }
+
+    let customers = load_visible_customers(&db, &current_user).await?;
+
+    let contacts = sqlx::query_as::<_, Contact>(
         "SELECT * FROM contacts WHERE customer_id = $1 ORDER BY first_name"
         )
    .bind(deal.customer_id)
@@ -800,44 +2424,65 @@ pub async fn deal_edit_form(
    .await
    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
+   let campaigns = load_campaigns(&db).await?;
+
+   let assignee_picker = load_assignee_picker(&db, deal.assigned_to).await?;
+   let custom_fields = custom_fields::inputs(load_custom_fields(&db, Extended::Deal).await?, &deal.custom_fields);
    let template = DealFormTemplate {
        deal: Some(deal),
        customers,
        contacts,
        customer_id: None,
+       campaigns,
+       show_value: current_user.has_finance_read,
+       lookups: load_lookups(&db).await?,
+       assignee_picker,
+       tag_picker: load_tag_picker(&db, Tagged::Deal, Some(id)).await?,
+       custom_fields,
+       error: query.error,
    };
    Ok(Html(template.render().unwrap()))
 }
 
 pub async fn create_deal(
     State(db): State<Database>,
-    cookies: Cookies,
+    AuthUser(user): AuthUser,
     Form(form): Form<DealForm>,
 ) -> Result<Redirect, StatusCode> {
-    let user = get_current_user(cookies, &db).await.ok_or(StatusCode::UNAUTHORIZED)?;
+    require_lookup(&db, "currency", &form.currency).await?;
 
     let customer_id = Uuid::parse_str(&form.customer_id).map_err(|_| StatusCode::BAD_REQUEST)?;
+    require_access(&db, &user, RecordKind::Customer, customer_id, Access::Write).await?;
 
     let contact_id = if let Some(contact_str) = form.contact_id {
         if contact_str.trim().is_empty() { None }
         else { Some(Uuid::parse_str(&contact_str).map_err(|_| StatusCode::BAD_REQUEST)?) }
     } else { None };
 
-    let probability: i32 = match form.stage.as_str() {
-        "prospect" => 25,
-        "negotiation" => 75,
-        "closed_won" => 100,
-        "closed_lost" => 0,
-        _ => 50,
+    let campaign_id = parse_optional_id(&form.campaign_id)?;
+    let assigned_to = route_assignee(&db, parse_optional_id(&form.assigned_to)?, form.keep_assignee.is_some()).await?;
+    let exchange_rate = locked_rate(&db, &form.currency).await?;
+    check_stage(&form.stage)?;
+    let probability = stage_probability(&form.stage);
+    let custom_values = match custom_field_values(&db, Extended::Deal, None, &form.custom).await? {
+        Ok(values) => values,
+        Err(error) => {
+            return Ok(Redirect::to(&format!(
+                "/crm/deals/new?customer_id={}&error={}",
+                customer_id,
+                urlencoding::encode(&error)
+            )));
+        }
     };
 
     let deal = sqlx::query_as::<_, Deal>(
         r#"
         INSERT INTO deals (
             customer_id, contact_id, title, description, value,
-            currency, stage, probability, expected_close_date, created_by
+            currency, stage, probability, expected_close_date, created_by, campaign_id, assigned_to, exchange_rate,
+            custom_fields
         )
-        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14)
         RETURNING *
         "#,
     )
@@ -845,27 +2490,44 @@ pub async fn create_deal(
     .bind(contact_id)
     .bind(&form.title)
     .bind(form.description)
-    .bind(form.value)
+    .bind(form.value.filter(|_| user.has_finance_read))
     .bind(&form.currency)
     .bind(&form.stage)
     .bind(probability)
     .bind(form.expected_close_date)
     .bind(user.id)
+    .bind(campaign_id)
+    .bind(assigned_to)
+    .bind(exchange_rate)
+    .bind(&custom_values)
     .fetch_one(&db)
     .await
     .map_err(|e| {
-        eprintln!("Error creating deal: {}", e);
+        tracing::error!("Error creating deal: {}", e);
         StatusCode::INTERNAL_SERVER_ERROR
     })?;
 
+    audit_log::record(&db, &user, "create", Audited::Deal, deal.id, None).await;
+    save_tags(&db, &user, Tagged::Deal, deal.id, &form.tags).await?;
+    if let Err(e) = stage_history::record(&db, deal.id, None, &deal.stage, user.id).await {
+        tracing::error!("Error recording stage history for deal {}: {}", deal.id, e);
+    }
+    events::publish(&db, &user, Event::DealCreated(&deal)).await;
+    if form.notify_assignee.is_some() {
+        notify_deal_assignee(&db, &user, &deal).await;
+    }
+
     Ok(Redirect::to(&format!("/crm/deals/{}", deal.id)))
 }
 
 pub async fn update_deal(
     State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
     Path(id): Path<Uuid>,
     Form(form): Form<DealForm>,
  ) -> Result<Redirect, StatusCode> {
+    let access = require_access(&db, &current_user, RecordKind::Deal, id, Access::Write).await?;
+
     let customer_id = Uuid::parse_str(&form.customer_id).map_err(|_| StatusCode::BAD_REQUEST)?;
  
     let contact_id = if let Some(contact_str) = form.contact_id {
@@ -873,20 +2535,53 @@ pub async fn update_deal(
         else { Some(Uuid::parse_str(&contact_str).map_err(|_| StatusCode::BAD_REQUEST)?) }
     } else { None };
  
-    let probability = match form.stage.as_str() {
-        "prospect" => 25,
-        "negotiation" => 75,
-        "closed_won" => 100,
-        "closed_lost" => 0,
-        _ => 50,
+    let campaign_id = parse_optional_id(&form.campaign_id)?;
+    let probability = stage_probability(&form.stage);
+
+    let (previous_stage, previous_assignee) = sqlx::query_as::<_, (String, Option<Uuid>)>(
+        "SELECT stage, assigned_to FROM deals WHERE id = $1"
+    )
+    .bind(id)
+    .fetch_one(&db)
+    .await
+    .map_err(|_| StatusCode::NOT_FOUND)?;
+
+    check_stage_change(&db, id, &previous_stage, &form.stage).await?;
+
+    // Handing a deal to someone else changes who owns it, so it takes the
+    // same access as sharing it. Only a new assignee is routed around absences.
+    let chosen = parse_optional_id(&form.assigned_to)?;
+    let assigned_to = if chosen == previous_assignee {
+        previous_assignee
+    } else if access < Access::Manage {
+        return Err(StatusCode::FORBIDDEN);
+    } else {
+        route_assignee(&db, chosen, form.keep_assignee.is_some()).await?
     };
+    // Only used when the currency changes; otherwise the deal keeps its rate
+    let exchange_rate = locked_rate(&db, &form.currency).await?;
+    let custom_values = match custom_field_values(&db, Extended::Deal, Some(id), &form.custom).await? {
+        Ok(values) => values,
+        Err(error) => {
+            return Ok(Redirect::to(&format!("/crm/deals/{}/edit?error={}", id, urlencoding::encode(&error))));
+        }
+    };
+    let before = audit_log::snapshot(&db, Audited::Deal, id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
  
-    sqlx::query(
+    let deal = sqlx::query_as::<_, Deal>(
         r#"
         UPDATE deals SET
-            customer_id = $2, contact_id = $3, title = $4, description = $5, value = $6,
-            currency = $7, stage = $8, probability = $9, expected_close_date = $10, updated_at = NOW()
+            customer_id = $2, contact_id = $3, title = $4, description = $5,
+            value = CASE WHEN $12 THEN $6 ELSE value END,
+            currency = $7, stage = $8, probability = $9, expected_close_date = $10, campaign_id = $11, assigned_to = $13, updated_at = NOW(),
+            exchange_rate = CASE WHEN currency IS DISTINCT FROM $7 THEN $14 ELSE exchange_rate END,
+            stage_changed_at = CASE WHEN stage <> $8 THEN NOW() ELSE stage_changed_at END,
+            stall_notified_at = CASE WHEN stage <> $8 THEN NULL ELSE stall_notified_at END,
+            custom_fields = $15
         WHERE id = $1
+        RETURNING *
         "#,
     )
     .bind(id)
@@ -899,229 +2594,956 @@ pub async fn update_deal(
     .bind(&form.stage)
     .bind(&probability)
     .bind(&form.expected_close_date)
-    .execute(&db)
+    .bind(campaign_id)
+    // Without finance:read the form has no value field, so keep what's stored
+    .bind(current_user.has_finance_read)
+    .bind(assigned_to)
+    .bind(exchange_rate)
+    .bind(&custom_values)
+    .fetch_one(&db)
     .await
     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    audit_log::record(&db, &current_user, "update", Audited::Deal, id, before).await;
+    save_tags(&db, &current_user, Tagged::Deal, id, &form.tags).await?;
+    if deal.assigned_to != previous_assignee {
+        let _ = create_audit_log(
+            &db,
+            &current_user,
+            "assign_deal".to_string(),
+            "deal".to_string(),
+            Some(id),
+            Some(serde_json::json!({ "assigned_to": previous_assignee })),
+            Some(serde_json::json!({ "assigned_to": deal.assigned_to })),
+        ).await;
+        if form.notify_assignee.is_some() {
+            notify_deal_assignee(&db, &current_user, &deal).await;
+        }
+    }
+    if deal.stage != previous_stage {
+        stage_changed(&db, &current_user, &deal, previous_stage).await;
+    } else {
+        events::publish(&db, &current_user, Event::DealUpdated(&deal)).await;
+    }
  
     Ok(Redirect::to(&format!("/crm/deals/{}", id)))
  }
- 
 
-// Activities functions
-pub async fn activities_list(
+// Let whoever the deal was just assigned to know it's theirs, unless they
+// assigned it to themselves
+async fn notify_deal_assignee(db: &Database, actor: &CurrentUser, deal: &Deal) {
+    let Some(assignee) = deal.assigned_to.filter(|&id| id != actor.id) else {
+        return;
+    };
+    let notified = sqlx::query("INSERT INTO notifications (user_id, message, link_url) VALUES ($1, $2, $3)")
+        .bind(assignee)
+        .bind(format!("{} {} assigned you the deal \"{}\"", actor.first_name, actor.last_name, deal.title))
+        .bind(format!("/crm/deals/{}", deal.id))
+        .execute(db)
+        .await;
+    if let Err(e) = notified {
+        tracing::error!("Error notifying the new owner of deal {}: {}", deal.id, e);
+    }
+}
+
+// Move just the deal to another stage, from the list page
+pub async fn patch_deal_stage(
     State(db): State<Database>,
-    cookies: Cookies,
-) -> Result<Html<String>, StatusCode> {
-    let current_user = get_current_user(cookies, &db)
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+    axum::Json(patch): axum::Json<DealStagePatch>,
+) -> Result<axum::Json<serde_json::Value>, StatusCode> {
+    require_access(&db, &current_user, RecordKind::Deal, id, Access::Write).await?;
+
+    let before = audit_log::snapshot(&db, Audited::Deal, id)
         .await
-        .ok_or(StatusCode::UNAUTHORIZED)?;
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let previous_stage = before["stage"].as_str().unwrap_or_default().to_string();
+    check_stage_change(&db, id, &previous_stage, &patch.stage).await?;
 
-    let activities = sqlx::query_as::<_, Activity>(
-       "SELECT * FROM activities ORDER BY activity_date DESC"
+    let deal = sqlx::query_as::<_, Deal>(
+        r#"
+        UPDATE deals SET
+            stage = $2, probability = $3, updated_at = NOW(),
+            stage_changed_at = CASE WHEN stage <> $2 THEN NOW() ELSE stage_changed_at END,
+            stall_notified_at = CASE WHEN stage <> $2 THEN NULL ELSE stall_notified_at END
+        WHERE id = $1
+        RETURNING *
+        "#,
     )
-    .fetch_all(&db)
+    .bind(id)
+    .bind(&patch.stage)
+    .bind(stage_probability(&patch.stage))
+    .fetch_one(&db)
     .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-    .into_iter()
-    .map(ActivityDisplay::from)
-    .collect();
-
-    let template = ActivitiesTemplate { activities, current_user };
-    Ok(Html(template.render().unwrap()))
-}
-
-pub async fn activity_form(
-   State(db): State<Database>,
-   Query(query): Query<ActivityQuery>,
-) -> Result<Html<String>, StatusCode> {
-   let customers = sqlx::query_as::<_, Customer>(
-       "SELECT * FROM customers ORDER BY company_name"
-   )
-   .fetch_all(&db)
-   .await
-   .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
-   let contacts = if let Some(customer_id) = query.customer_id {
-       sqlx::query_as::<_, Contact>(
-           "SELECT * FROM contacts WHERE customer_id = $1 ORDER BY first_name"
-       )
-       .bind(customer_id)
-       .fetch_all(&db)
-       .await
-       .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-   } else {
-       Vec::new()
-   };
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-   let deals = if let Some(customer_id) = query.customer_id {
-       sqlx::query_as::<_, Deal>(
-           "SELECT * FROM deals WHERE customer_id = $1 ORDER BY title"
-       )
-       .bind(customer_id)
-       .fetch_all(&db)
-       .await
-       .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-   } else {
-       Vec::new()
-   };
+    audit_log::record(&db, &current_user, "update", Audited::Deal, id, Some(before)).await;
+    if deal.stage != previous_stage {
+        stage_changed(&db, &current_user, &deal, previous_stage).await;
+    }
 
-   let template = ActivityFormTemplate {
-       activity: None,
-       customers,
-       contacts,
-       deals,
-       customer_id: query.customer_id,
-       deal_id: query.deal_id,
-   };
-   Ok(Html(template.render().unwrap()))
+    Ok(axum::Json(serde_json::json!({ "id": deal.id, "stage": deal.stage, "probability": deal.probability })))
 }
 
-pub async fn create_activity(
-   State(db): State<Database>,
-   cookies: Cookies,
-   Form(form): Form<ActivityForm>,
-) -> Result<Redirect, StatusCode> {
-   let user = get_current_user(cookies, &db).await
-       .ok_or(StatusCode::UNAUTHORIZED)?;
+fn check_stage(stage: &str) -> Result<(), StatusCode> {
+    if DEAL_STAGES.contains(&stage) {
+        Ok(())
+    } else {
+        Err(StatusCode::BAD_REQUEST)
+    }
+}
 
-   // Parse customer_id
-   let customer_id = Uuid::parse_str(&form.customer_id)
-       .map_err(|_| StatusCode::BAD_REQUEST)?;
+// Discounts have to be settled before the deal is won at those prices
+async fn check_stage_change(db: &Database, deal_id: Uuid, previous_stage: &str, stage: &str) -> Result<(), StatusCode> {
+    check_stage(stage)?;
+    if stage == "closed_won" && previous_stage != "closed_won" {
+        let unapproved = discounts::unapproved(db, deal_id).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        if unapproved > 0 {
+            return Err(StatusCode::CONFLICT);
+        }
+    }
+    Ok(())
+}
 
-   // Parse contact_id if provided and not empty
-   let contact_id = if let Some(contact_str) = form.contact_id {
-       if contact_str.trim().is_empty() {
-           None
-       } else {
-           Some(Uuid::parse_str(&contact_str).map_err(|_| StatusCode::BAD_REQUEST)?)
-       }
-   } else {
-       None
-   };
+pub(crate) fn stage_probability(stage: &str) -> i32 {
+    match stage {
+        "prospect" => 25,
+        "negotiation" => 75,
+        "closed_won" => 100,
+        "closed_lost" => 0,
+        _ => 50,
+    }
+}
 
-   // Parse deal_id if provided and not empty
-   let deal_id = if let Some(deal_str) = form.deal_id {
-       if deal_str.trim().is_empty() {
-           None
-       } else {
-           Some(Uuid::parse_str(&deal_str).map_err(|_| StatusCode::BAD_REQUEST)?)
-       }
-   } else {
-       None
-   };
+// Winning a deal records its prices; reopening it takes them back out
+async fn stage_changed(db: &Database, actor: &CurrentUser, deal: &Deal, previous_stage: String) {
+    if let Err(e) = stage_history::record(db, deal.id, Some(&previous_stage), &deal.stage, actor.id).await {
+        tracing::error!("Error recording stage history for deal {}: {}", deal.id, e);
+    }
 
-   // Parse activity_date
-   let activity_date = if form.activity_date.is_empty() {
-       Utc::now()
-   } else {
-       // Parse datetime-local format (YYYY-MM-DDTHH:MM)
-       NaiveDateTime::parse_from_str(&form.activity_date, "%Y-%m-%dT%H:%M")
-           .map_err(|_| StatusCode::BAD_REQUEST)?
-           .and_utc()
-   };
+    let recorded = if deal.stage == "closed_won" {
+        price_history::record_sale(db, deal.id).await
+    } else if previous_stage == "closed_won" {
+        price_history::forget_sale(db, deal.id).await
+    } else {
+        Ok(())
+    };
+    if let Err(e) = recorded {
+        tracing::error!("Error updating price history for deal {}: {}", deal.id, e);
+    }
 
-   let completed = form.completed.is_some();
+    let reason = format!("The deal moved to {}", deal.stage.replace('_', " "));
+    if let Err(e) = sequences::stop_for_deal(db, deal.id, &reason).await {
+        tracing::error!("Error stopping sequences for deal {}: {}", deal.id, e);
+    }
 
-   sqlx::query(
-       r#"
-       INSERT INTO activities (
-           customer_id, contact_id, deal_id, activity_type, subject,
-           description, activity_date, duration_minutes, completed, created_by
-       )
-       VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
-       "#,
-   )
-   .bind(&customer_id)
-   .bind(&contact_id)
-   .bind(&deal_id)
-   .bind(&form.activity_type)
-   .bind(&form.subject)
-   .bind(&form.description)
-   .bind(&activity_date)
-   .bind(&form.duration_minutes)
-   .bind(completed)
-   .bind(&user.id)
-   .execute(&db)
-   .await
-   .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    events::publish(db, actor, Event::DealStageChanged { deal, previous_stage: &previous_stage }).await;
+}
 
-   Ok(Redirect::to("/crm/activities"))
+// Today's rate for a deal's currency, to lock onto it
+pub(crate) async fn locked_rate(db: &Database, currency: &str) -> Result<Option<rust_decimal::Decimal>, StatusCode> {
+    exchange_rates::rate_for(db, currency).await.map_err(|e| {
+        tracing::error!("Error loading exchange rate: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
 }
 
-// API function
-#[derive(Serialize)]
-pub struct ContactResponse {
-   pub id: Uuid,
-   pub first_name: String,
-   pub last_name: String,
+// Optional dates in a form (YYYY-MM-DD); blank counts as not given
+pub(crate) fn parse_optional_date(value: &Option<String>) -> Result<Option<NaiveDate>, StatusCode> {
+    match value.as_deref().map(str::trim) {
+        None | Some("") => Ok(None),
+        Some(value) => NaiveDate::parse_from_str(value, "%Y-%m-%d").map(Some).map_err(|_| StatusCode::BAD_REQUEST),
+    }
 }
 
-impl From<Contact> for ContactResponse {
-   fn from(contact: Contact) -> Self {
-       Self {
-           id: contact.id,
-           first_name: contact.first_name,
-           last_name: contact.last_name,
-       }
-   }
+// Optional numbers in a form; blank counts as not given
+pub(crate) fn parse_optional_decimal(value: &Option<String>) -> Result<Option<rust_decimal::Decimal>, StatusCode> {
+    match value.as_deref().map(str::trim) {
+        None | Some("") => Ok(None),
+        Some(value) => value.parse().map(Some).map_err(|_| StatusCode::BAD_REQUEST),
+    }
 }
 
-pub async fn get_customer_contacts(
-   State(db): State<Database>,
-   Path(customer_id): Path<Uuid>,
-) -> Result<axum::Json<Vec<ContactResponse>>, StatusCode> {
-   let contacts = sqlx::query_as::<_, Contact>(
-       "SELECT * FROM contacts WHERE customer_id = $1 ORDER BY first_name"
-   )
-   .bind(customer_id)
-   .fetch_all(&db)
-   .await
-   .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-   .into_iter()
-   .map(ContactResponse::from)
-   .collect();
+pub async fn add_deal_line_item(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+    Form(form): Form<LineItemForm>,
+) -> Result<Redirect, StatusCode> {
+    require_access(&db, &current_user, RecordKind::Deal, id, Access::Write).await?;
+    if !current_user.has_finance_read {
+        return Err(StatusCode::FORBIDDEN);
+    }
 
-   Ok(axum::Json(contacts))
+    let (stage, customer_id) = sqlx::query_as::<_, (String, Uuid)>("SELECT stage, customer_id FROM deals WHERE id = $1")
+        .bind(id)
+        .fetch_one(&db)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+
+    // The item can be picked, or typed as any code the customer knows it by
+    let (item_id, customer_part_number) = match parse_optional_id(&form.item_id)? {
+        Some(item_id) => {
+            let part_number = part_numbers::for_item(&db, customer_id, item_id)
+                .await
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+            (Some(item_id), part_number)
+        }
+        None => match form.item_code.as_deref().map(str::trim).filter(|code| !code.is_empty()) {
+            Some(code) => {
+                let resolved = part_numbers::resolve(&db, customer_id, code)
+                    .await
+                    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+                    .ok_or(StatusCode::BAD_REQUEST)?;
+                (Some(resolved.item_id), resolved.customer_part_number)
+            }
+            None => (None, None),
+        },
+    };
+
+    let item = match item_id {
+        Some(item_id) => Some(
+            sqlx::query_as::<_, InventoryItem>("SELECT * FROM inventory_items WHERE id = $1")
+                .bind(item_id)
+                .fetch_optional(&db)
+                .await
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+                .ok_or(StatusCode::BAD_REQUEST)?,
+        ),
+        None => None,
+    };
+
+    let description = form
+        .description
+        .map(|d| d.trim().to_string())
+        .filter(|d| !d.is_empty())
+        .or_else(|| item.as_ref().map(|item| item.item_name.clone()))
+        .ok_or(StatusCode::BAD_REQUEST)?;
+    let entered_price = parse_optional_decimal(&form.unit_price)?;
+    let entered_discount = parse_optional_decimal(&form.discount_percent)?;
+
+    // Left unpriced, an item the customer has agreed a price for gets it
+    let agreement = match (&item, entered_price, entered_discount) {
+        (Some(item), None, None) => pricing_agreements::in_force(&db, customer_id, item.id, chrono::Utc::now().date_naive())
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?,
+        _ => None,
+    };
+    let unit_price = entered_price
+        .or_else(|| agreement.as_ref().and_then(|agreement| agreement.fixed_price))
+        .or_else(|| item.as_ref().and_then(|item| item.selling_price))
+        .ok_or(StatusCode::BAD_REQUEST)?;
+    let discount_percent = entered_discount
+        .or_else(|| agreement.as_ref().and_then(|agreement| agreement.discount_percent))
+        .unwrap_or_default();
+
+    let hundred = rust_decimal::Decimal::from(100);
+    if form.quantity <= rust_decimal::Decimal::ZERO
+        || unit_price < rust_decimal::Decimal::ZERO
+        || discount_percent < rust_decimal::Decimal::ZERO
+        || discount_percent > hundred
+    {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let mut status = discounts::initial_status(&db, &current_user, discount_percent)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    // The discount was approved when the agreement was made
+    if agreement.is_some() && status == "pending" {
+        status = "approved";
+    }
+
+    // A won deal can't take on a discount nobody has approved yet
+    if status == "pending" && stage == "closed_won" {
+        return Err(StatusCode::CONFLICT);
+    }
+
+    sqlx::query(
+        r#"
+        INSERT INTO deal_line_items (
+            deal_id, item_id, description, quantity, unit_price, discount_percent,
+            discount_status, discount_decided_by, discount_decided_at, created_by, customer_part_number,
+            pricing_agreement_id
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7,
+                CASE WHEN $7 = 'approved' THEN $8 END, CASE WHEN $7 = 'approved' THEN NOW() END, $8, $9, $10)
+        "#,
+    )
+    .bind(id)
+    .bind(item.map(|item| item.id))
+    .bind(description)
+    .bind(form.quantity)
+    .bind(unit_price)
+    .bind(discount_percent)
+    .bind(status)
+    .bind(current_user.id)
+    .bind(customer_part_number)
+    .bind(agreement.map(|agreement| agreement.id))
+    .execute(&db)
+    .await
+    .map_err(|e| {
+        tracing::error!("Error adding deal line item: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    // Already sold, so the line goes straight into the price history
+    if stage == "closed_won" {
+        if let Err(e) = price_history::record_sale(&db, id).await {
+            tracing::error!("Error updating price history for deal {}: {}", id, e);
+        }
+    }
+
+    Ok(Redirect::to(&format!("/crm/deals/{}", id)))
 }
 
-pub async fn delete_deal(
+pub async fn delete_deal_line_item(
     State(db): State<Database>,
-    cookies: Cookies,
-    Path(deal_id): Path<Uuid>,
+    AuthUser(current_user): AuthUser,
+    Path((id, line_id)): Path<(Uuid, Uuid)>,
 ) -> Result<Redirect, StatusCode> {
-    let current_user = get_current_user(cookies, &db)
-        .await
-        .ok_or(StatusCode::UNAUTHORIZED)?;
-
-    if !current_user.permissions.contains(&"team:manage_roles".to_string()) {
+    require_access(&db, &current_user, RecordKind::Deal, id, Access::Write).await?;
+    if !current_user.has_finance_read {
         return Err(StatusCode::FORBIDDEN);
     }
 
-    sqlx::query("DELETE FROM deals WHERE id = $1")
-        .bind(deal_id)
+    sqlx::query("DELETE FROM deal_line_items WHERE id = $1 AND deal_id = $2")
+        .bind(line_id)
+        .bind(id)
         .execute(&db)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-    Ok(Redirect::to("/crm/deals"))
+    Ok(Redirect::to(&format!("/crm/deals/{}", id)))
 }
 
-pub async fn delete_activity(
+// Records the user can't see at all are reported as missing rather than forbidden
+pub(crate) async fn require_access(
+    db: &Database,
+    user: &CurrentUser,
+    kind: RecordKind,
+    id: Uuid,
+    needed: Access,
+) -> Result<Access, StatusCode> {
+    let access = sharing::access_level(db, user, kind, id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error checking record access: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    if access == Access::None {
+        Err(StatusCode::NOT_FOUND)
+    } else if access < needed {
+        Err(StatusCode::FORBIDDEN)
+    } else {
+        Ok(access)
+    }
+}
+
+async fn load_sharing_panel(
+    db: &Database,
+    kind: RecordKind,
+    id: Uuid,
+    access: Access,
+) -> Result<SharingPanel, StatusCode> {
+    let shares = sharing::list_shares(db, kind, id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let can_share = access == Access::Manage;
+    let (users, team_leads) = if can_share {
+        let users = sqlx::query_as::<_, User>(
+            "SELECT * FROM users WHERE is_active = true ORDER BY first_name, last_name"
+        )
+        .fetch_all(db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        // Anyone with reports can also be picked as a whole team
+        let team_leads = sqlx::query_as::<_, User>(
+            r#"
+            SELECT * FROM users l
+            WHERE l.is_active = true AND EXISTS (SELECT 1 FROM users r WHERE r.manager_id = l.id)
+            ORDER BY first_name, last_name
+            "#,
+        )
+        .fetch_all(db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        (users, team_leads)
+    } else {
+        (Vec::new(), Vec::new())
+    };
+
+    Ok(SharingPanel {
+        action_url: format!("{}/shares", kind.url(id)),
+        can_edit: access >= Access::Write,
+        can_share,
+        shares,
+        users,
+        team_leads,
+    })
+}
+
+// Deal stage settings - how long a deal may sit in each stage before it is flagged as stalled
+pub async fn deal_stage_settings(
     State(db): State<Database>,
-    cookies: Cookies,
-    Path(activity_id): Path<Uuid>,
+    AuthUser(current_user): AuthUser,
+    Query(query): Query<DealStageSettingsQuery>,
+) -> Result<Html<String>, StatusCode> {
+    if !current_user.has_manage_roles {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let stages = sqlx::query_as::<_, DealStageSetting>(
+        "SELECT * FROM deal_stage_settings ORDER BY stale_after_days DESC, stage"
+    )
+    .fetch_all(&db)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let success_message = query.success.map(|_| "Stage settings saved".to_string());
+
+    let template = DealStageSettingsTemplate { stages, current_user, success_message };
+    Ok(Html(template.render().unwrap()))
+}
+
+pub async fn update_deal_stage_settings(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Form(form): Form<HashMap<String, String>>,
 ) -> Result<Redirect, StatusCode> {
-    let current_user = get_current_user(cookies, &db)
+    if !current_user.has_manage_roles {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    for (key, value) in form {
+        let stage = match key.strip_prefix("stale_after_days_") {
+            Some(stage) => stage,
+            None => continue,
+        };
+
+        let days: i32 = value.trim().parse().map_err(|_| StatusCode::BAD_REQUEST)?;
+        if days <= 0 {
+            return Err(StatusCode::BAD_REQUEST);
+        }
+
+        sqlx::query("UPDATE deal_stage_settings SET stale_after_days = $1 WHERE stage = $2")
+            .bind(days)
+            .bind(stage)
+            .execute(&db)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    }
+
+    Ok(Redirect::to("/crm/deals/stages?success=1"))
+}
+ 
+// Discount thresholds - how large a discount may be before someone has to approve it
+pub async fn discount_settings(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+) -> Result<Html<String>, StatusCode> {
+    if !current_user.has_manage_roles {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let thresholds = discounts::thresholds(&db)
         .await
-        .ok_or(StatusCode::UNAUTHORIZED)?;
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-    if !current_user.permissions.contains(&"team:manage_roles".to_string()) {
+    let template = DiscountSettingsTemplate { thresholds, current_user };
+    Ok(Html(template.render().unwrap()))
+}
+
+pub async fn add_discount_threshold(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Form(form): Form<DiscountThresholdForm>,
+) -> Result<Redirect, StatusCode> {
+    if !current_user.has_manage_roles {
         return Err(StatusCode::FORBIDDEN);
     }
+    if form.above_percent < rust_decimal::Decimal::ZERO
+        || form.above_percent >= rust_decimal::Decimal::from(100)
+        || !["manager", "approver"].contains(&form.approver.as_str())
+    {
+        return Err(StatusCode::BAD_REQUEST);
+    }
 
-    sqlx::query("DELETE FROM activities WHERE id = $1")
-        .bind(activity_id)
-        .execute(&db)
+    // Re-adding a percentage changes who approves above it
+    let threshold = sqlx::query_as::<_, DiscountThreshold>(
+        r#"
+        INSERT INTO discount_thresholds (above_percent, approver) VALUES ($1, $2)
+        ON CONFLICT (tenant_id, above_percent) DO UPDATE SET approver = EXCLUDED.approver
+        RETURNING *
+        "#,
+    )
+    .bind(form.above_percent)
+    .bind(&form.approver)
+    .fetch_one(&db)
+    .await
+    .map_err(|e| {
+        tracing::error!("Error saving discount threshold: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let _ = create_audit_log(
+        &db,
+        &current_user,
+        "save_discount_threshold".to_string(),
+        "discount_threshold".to_string(),
+        Some(threshold.id),
+        None,
+        Some(serde_json::json!({ "above_percent": threshold.above_percent, "approver": threshold.approver })),
+    ).await;
+
+    Ok(Redirect::to("/crm/deals/discounts"))
+}
+
+pub async fn delete_discount_threshold(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<Redirect, StatusCode> {
+    if !current_user.has_manage_roles {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let threshold = sqlx::query_as::<_, DiscountThreshold>(
+        "DELETE FROM discount_thresholds WHERE id = $1 RETURNING *"
+    )
+    .bind(id)
+    .fetch_optional(&db)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    .ok_or(StatusCode::NOT_FOUND)?;
+
+    let _ = create_audit_log(
+        &db,
+        &current_user,
+        "delete_discount_threshold".to_string(),
+        "discount_threshold".to_string(),
+        Some(threshold.id),
+        Some(serde_json::json!({ "above_percent": threshold.above_percent, "approver": threshold.approver })),
+        None,
+    ).await;
+
+    Ok(Redirect::to("/crm/deals/discounts"))
+}
+
+// Activities functions
+pub async fn activities_list(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Query(query): Query<ListQuery>,
+) -> Result<Html<String>, StatusCode> {
+    let outcomes = load_outcomes(&db, false).await?;
+    let activity_types = load_activity_types(&db, true).await?;
+
+    let team_filter = load_team_filter(&db, &current_user, "/crm/activities", &query).await?;
+    let mut scope = list_scope(&current_user, RecordKind::Activity, "a", &team_filter);
+    let selected_type = query.activity_type.as_deref().map(str::trim).unwrap_or_default().to_string();
+    if !selected_type.is_empty() {
+        scope.push_str(if scope.is_empty() { "WHERE " } else { " AND " });
+        scope.push_str("a.activity_type = $2");
+    }
+
+    let activities = sqlx::query_as::<_, Activity>(&format!(
+       "SELECT a.* FROM activities a {} ORDER BY a.activity_date DESC",
+       scope
+    ))
+    .bind(current_user.id)
+    .bind(&selected_type)
+    .fetch_all(&db)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    .into_iter()
+    .map(|activity| {
+        let label = outcomes
+            .iter()
+            .find(|o| o.activity_type == activity.activity_type && Some(&o.code) == activity.outcome_code.as_ref())
+            .map(|o| o.label.clone());
+        let mut display = ActivityDisplay::in_timezone(activity, current_user.timezone).with_type(&activity_types);
+        if let Some(label) = label {
+            display.outcome = label;
+        }
+        display
+    })
+    .collect();
+
+    let template = ActivitiesTemplate { activities, current_user, team_filter, activity_types, selected_type };
+    Ok(Html(template.render().unwrap()))
+}
+
+pub async fn activity_form(
+   State(db): State<Database>,
+   AuthUser(current_user): AuthUser,
+   Query(query): Query<ActivityQuery>,
+) -> Result<Html<String>, StatusCode> {
+   if let Some(customer_id) = query.customer_id {
+       require_access(&db, &current_user, RecordKind::Customer, customer_id, Access::Read).await?;
+   }
+   let customers = load_visible_customers(&db, &current_user).await?;
+
+   let contacts = if let Some(customer_id) = query.customer_id {
+       sqlx::query_as::<_, Contact>(
+           "SELECT * FROM contacts WHERE customer_id = $1 ORDER BY first_name"
+       )
+       .bind(customer_id)
+       .fetch_all(&db)
+       .await
+       .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+   } else {
+       Vec::new()
+   };
+
+   let deals = if let Some(customer_id) = query.customer_id {
+       sqlx::query_as::<_, Deal>(
+           "SELECT * FROM deals WHERE customer_id = $1 ORDER BY title"
+       )
+       .bind(customer_id)
+       .fetch_all(&db)
+       .await
+       .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+   } else {
+       Vec::new()
+   };
+
+   let outcomes = load_outcomes(&db, true).await?;
+
+   let template = ActivityFormTemplate {
+       activity: None,
+       customers,
+       contacts,
+       deals,
+       customer_id: query.customer_id,
+       deal_id: query.deal_id,
+       outcomes,
+       activity_types: load_activity_types(&db, true).await?,
+       selected_type: String::new(),
+       activity_date: to_local_input(Utc::now(), current_user.timezone),
+       timezone: current_user.timezone,
+       assignee_picker: load_assignee_picker(&db, None).await?,
+   };
+   Ok(Html(template.render().unwrap()))
+}
+
+pub async fn create_activity(
+   State(db): State<Database>,
+   AuthUser(user): AuthUser,
+   Form(form): Form<ActivityForm>,
+) -> Result<Redirect, StatusCode> {
+   match activity_types::is_allowed(&db, &form.activity_type).await {
+       Ok(true) => {}
+       Ok(false) => return Err(StatusCode::BAD_REQUEST),
+       Err(_) => return Err(StatusCode::INTERNAL_SERVER_ERROR),
+   }
+
+   // Parse customer_id
+   let customer_id = Uuid::parse_str(&form.customer_id)
+       .map_err(|_| StatusCode::BAD_REQUEST)?;
+   require_access(&db, &user, RecordKind::Customer, customer_id, Access::Write).await?;
+
+   // Parse contact_id if provided and not empty
+   let contact_id = if let Some(contact_str) = form.contact_id {
+       if contact_str.trim().is_empty() {
+           None
+       } else {
+           Some(Uuid::parse_str(&contact_str).map_err(|_| StatusCode::BAD_REQUEST)?)
+       }
+   } else {
+       None
+   };
+
+   // Parse deal_id if provided and not empty
+   let deal_id = if let Some(deal_str) = form.deal_id {
+       if deal_str.trim().is_empty() {
+           None
+       } else {
+           Some(Uuid::parse_str(&deal_str).map_err(|_| StatusCode::BAD_REQUEST)?)
+       }
+   } else {
+       None
+   };
+
+   // Parse activity_date, entered in the user's own time zone
+   let activity_date = if form.activity_date.is_empty() {
+       Utc::now()
+   } else {
+       parse_local_input(&form.activity_date, user.timezone).ok_or(StatusCode::BAD_REQUEST)?
+   };
+
+   let completed = form.completed.is_some();
+   let outcome_code = resolve_outcome(&db, &form.activity_type, form.outcome_code, completed).await?;
+   let assigned_to = route_assignee(&db, parse_optional_id(&form.assigned_to)?, form.keep_assignee.is_some()).await?;
+
+   let activity = sqlx::query_as::<_, Activity>(
+       r#"
+       INSERT INTO activities (
+           customer_id, contact_id, deal_id, activity_type, subject,
+           description, activity_date, duration_minutes, completed, created_by, outcome_code, assigned_to
+       )
+       VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+       RETURNING *
+       "#,
+   )
+   .bind(&customer_id)
+   .bind(&contact_id)
+   .bind(&deal_id)
+   .bind(&form.activity_type)
+   .bind(&form.subject)
+   .bind(&form.description)
+   .bind(&activity_date)
+   .bind(&form.duration_minutes)
+   .bind(completed)
+   .bind(&user.id)
+   .bind(&outcome_code)
+   .bind(assigned_to)
+   .fetch_one(&db)
+   .await
+   .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+   audit_log::record(&db, &user, "create", Audited::Activity, activity.id, None).await;
+   events::publish(&db, &user, Event::ActivityCreated(&activity)).await;
+
+   Ok(Redirect::to("/crm/activities"))
+}
+
+// API function
+#[derive(Serialize)]
+pub struct ContactResponse {
+   pub id: Uuid,
+   pub first_name: String,
+   pub last_name: String,
+   pub custom_fields: serde_json::Value,
+}
+
+impl From<Contact> for ContactResponse {
+   fn from(contact: Contact) -> Self {
+       Self {
+           id: contact.id,
+           first_name: contact.first_name,
+           last_name: contact.last_name,
+           custom_fields: contact.custom_fields,
+       }
+   }
+}
+
+pub async fn get_customer_contacts(
+   State(db): State<Database>,
+   cookies: Cookies,
+   Path(customer_id): Path<Uuid>,
+   principal: Option<Extension<ApiPrincipal>>,
+) -> Result<axum::Json<Vec<ContactResponse>>, StatusCode> {
+   // Callers only see customers they could open in the UI, whether they come
+   // with a key or a session. Sandbox data is shared by all sandbox keys, so
+   // it is not scoped.
+   let sandbox = principal.as_ref().is_some_and(|Extension(p)| p.sandbox);
+   let user = match principal {
+       Some(Extension(principal)) => principal.user,
+       None => get_current_user(cookies, &db).await.ok_or(StatusCode::UNAUTHORIZED)?,
+   };
+   user.require("customers:read")?;
+   if !sandbox {
+       require_access(&db, &user, RecordKind::Customer, customer_id, Access::Read).await?;
+   }
+
+   let mut tx = sandbox::begin(&db, sandbox)
+       .await
+       .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+   let contacts = sqlx::query_as::<_, Contact>(
+       "SELECT * FROM contacts WHERE customer_id = $1 ORDER BY first_name"
+   )
+   .bind(customer_id)
+   .fetch_all(&mut *tx)
+   .await
+   .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+   .into_iter()
+   .map(ContactResponse::from)
+   .collect();
+
+   Ok(axum::Json(contacts))
+}
+
+#[derive(Serialize)]
+pub struct ApiItemResponse {
+    id: Uuid,
+    sku: String,
+    item_name: String,
+    customer_part_number: Option<String>,
+    // Which identifier the code matched: customer_part_number, sku or upc
+    matched_by: &'static str,
+}
+
+// Look up an item by the customer's own part number or by our SKU or UPC
+pub async fn api_customer_item(
+    State(db): State<Database>,
+    cookies: Cookies,
+    Path((customer_id, code)): Path<(Uuid, String)>,
+    principal: Option<Extension<ApiPrincipal>>,
+) -> Result<axum::Json<ApiItemResponse>, StatusCode> {
+    // Part numbers and items aren't sandboxed, so sandbox customers only match SKUs
+    let sandbox = principal.as_ref().is_some_and(|Extension(p)| p.sandbox);
+    let user = match principal {
+        Some(Extension(principal)) => principal.user,
+        None => get_current_user(cookies, &db).await.ok_or(StatusCode::UNAUTHORIZED)?,
+    };
+    user.require("inventory:read")?;
+    if !sandbox {
+        require_access(&db, &user, RecordKind::Customer, customer_id, Access::Read).await?;
+    }
+
+    let resolved = part_numbers::resolve(&db, customer_id, &code)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let (sku, item_name) = sqlx::query_as::<_, (String, String)>("SELECT sku, item_name FROM inventory_items WHERE id = $1")
+        .bind(resolved.item_id)
+        .fetch_one(&db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(axum::Json(ApiItemResponse {
+        id: resolved.item_id,
+        sku,
+        item_name,
+        customer_part_number: resolved.customer_part_number,
+        matched_by: resolved.matched_by,
+    }))
+}
+
+#[derive(Deserialize)]
+pub struct ApiCustomerRequest {
+    company_name: String,
+    industry: Option<String>,
+    website: Option<String>,
+    phone: Option<String>,
+    email: Option<String>,
+    city: Option<String>,
+    country: Option<String>,
+    status: Option<String>,
+    notes: Option<String>,
+    // Keyed by custom field key, e.g. {"region": "EMEA"}
+    custom_fields: Option<serde_json::Map<String, serde_json::Value>>,
+}
+
+#[derive(Serialize)]
+pub struct ApiCustomerResponse {
+    id: Uuid,
+    company_name: String,
+    status: String,
+    custom_fields: serde_json::Value,
+    sandbox: bool,
+}
+
+pub async fn api_create_customer(
+    State(db): State<Database>,
+    cookies: Cookies,
+    principal: Option<Extension<ApiPrincipal>>,
+    axum::Json(body): axum::Json<ApiCustomerRequest>,
+) -> Result<(StatusCode, axum::Json<ApiCustomerResponse>), StatusCode> {
+    let (user, sandbox) = match principal {
+        Some(Extension(principal)) => (principal.user, principal.sandbox),
+        None => (
+            get_current_user(cookies, &db).await.ok_or(StatusCode::UNAUTHORIZED)?,
+            false,
+        ),
+    };
+    user.require("customers:write")?;
+
+    let company_name = body.company_name.trim();
+    let status = body.status.as_deref().unwrap_or("prospect");
+    if company_name.is_empty() || company_name.len() > 255 || !CUSTOMER_STATUSES.contains(&status) {
+        return Err(StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    let fields = load_custom_fields(&db, Extended::Customer).await?;
+    let submitted = custom_fields::json_values(&fields, &body.custom_fields.unwrap_or_default())
+        .map_err(|_| StatusCode::UNPROCESSABLE_ENTITY)?;
+    let custom_values = custom_fields::collect(&fields, &submitted, &serde_json::json!({}))
+        .map_err(|_| StatusCode::UNPROCESSABLE_ENTITY)?;
+
+    // Sandbox keys write into the shadow customers table
+    let mut tx = sandbox::begin(&db, sandbox)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let customer = sqlx::query_as::<_, Customer>(
+        r#"
+        INSERT INTO customers (company_name, industry, website, phone, email, city, country, status, notes, lead_source, created_by, custom_fields)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, 'api', $10, $11)
+        RETURNING *
+        "#,
+    )
+    .bind(company_name)
+    .bind(&body.industry)
+    .bind(&body.website)
+    .bind(&body.phone)
+    .bind(&body.email)
+    .bind(&body.city)
+    .bind(&body.country)
+    .bind(status)
+    .bind(&body.notes)
+    .bind(user.id)
+    .bind(&custom_values)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| {
+        tracing::error!("Error creating customer via API: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    // Sandbox records are invisible outside the API, so they raise no events
+    // and aren't audited
+    if !sandbox {
+        audit_log::record(&db, &user, "create", Audited::Customer, customer.id, None).await;
+        events::publish(&db, &user, Event::CustomerCreated(&customer)).await;
+    }
+
+    Ok((
+        StatusCode::CREATED,
+        axum::Json(ApiCustomerResponse {
+            id: customer.id,
+            company_name: customer.company_name,
+            status: customer.status,
+            custom_fields: customer.custom_fields,
+            sandbox,
+        }),
+    ))
+}
+
+pub async fn delete_deal(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(deal_id): Path<Uuid>,
+) -> Result<Redirect, StatusCode> {
+    current_user.require("team:manage_roles")?;
+
+    let delete = sqlx::query("DELETE FROM deals WHERE id = $1").bind(deal_id);
+    audited_execute(&db, &current_user, "delete", Audited::Deal, deal_id, delete)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Redirect::to("/crm/deals"))
+}
+
+pub async fn delete_activity(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(activity_id): Path<Uuid>,
+) -> Result<Redirect, StatusCode> {
+    current_user.require("team:manage_roles")?;
+
+    let delete = sqlx::query("DELETE FROM activities WHERE id = $1").bind(activity_id);
+    audited_execute(&db, &current_user, "delete", Audited::Activity, activity_id, delete)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
@@ -1130,18 +3552,18 @@ pub async fn delete_activity(
 
 pub async fn activity_edit_form(
     State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
     Path(activity_id): Path<Uuid>,
 ) -> Result<Html<String>, StatusCode> {
+    require_access(&db, &current_user, RecordKind::Activity, activity_id, Access::Write).await?;
+
     let activity = sqlx::query_as::<_, Activity>("SELECT * FROM activities WHERE id = $1")
         .bind(activity_id)
         .fetch_one(&db)
         .await
         .map_err(|_| StatusCode::NOT_FOUND)?;
 
-    let customers = sqlx::query_as::<_, Customer>("SELECT * FROM customers ORDER BY company_name")
-        .fetch_all(&db)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let customers = load_visible_customers(&db, &current_user).await?;
 
     let contacts = sqlx::query_as::<_, Contact>("SELECT * FROM contacts WHERE customer_id = $1 ORDER BY first_name")
         .bind(activity.customer_id)
@@ -1155,13 +3577,22 @@ pub async fn activity_edit_form(
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
+    let outcomes = load_outcomes(&db, true).await?;
+    let assignee_picker = load_assignee_picker(&db, activity.assigned_to).await?;
+
     let template = ActivityFormTemplate {
+        assignee_picker,
+        activity_date: to_local_input(activity.activity_date, current_user.timezone),
+        timezone: current_user.timezone,
+        activity_types: load_activity_types(&db, true).await?,
+        selected_type: activity.activity_type.clone(),
         activity: Some(activity),
         customers,
         contacts,
         deals,
         customer_id: None,
         deal_id: None,
+        outcomes,
     };
 
     Ok(Html(template.render().unwrap()))
@@ -1169,9 +3600,12 @@ pub async fn activity_edit_form(
 
 pub async fn update_activity(
     State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
     Path(activity_id): Path<Uuid>,
     Form(form): Form<ActivityForm>,
 ) -> Result<Redirect, StatusCode> {
+    let access = require_access(&db, &current_user, RecordKind::Activity, activity_id, Access::Write).await?;
+
     let customer_id = Uuid::parse_str(&form.customer_id).map_err(|_| StatusCode::BAD_REQUEST)?;
 
     let contact_id = if let Some(contact_str) = form.contact_id {
@@ -1187,18 +3621,41 @@ pub async fn update_activity(
     let activity_date = if form.activity_date.is_empty() {
         Utc::now()
     } else {
-        NaiveDateTime::parse_from_str(&form.activity_date, "%Y-%m-%dT%H:%M")
-            .map_err(|_| StatusCode::BAD_REQUEST)?
-            .and_utc()
+        parse_local_input(&form.activity_date, current_user.timezone).ok_or(StatusCode::BAD_REQUEST)?
     };
 
     let completed = form.completed.is_some();
+    let outcome_code = resolve_outcome(&db, &form.activity_type, form.outcome_code, completed).await?;
 
-    sqlx::query(
+    let (previous_customer, previous_assignee) = sqlx::query_as::<_, (Uuid, Option<Uuid>)>(
+        "SELECT customer_id, assigned_to FROM activities WHERE id = $1"
+    )
+    .bind(activity_id)
+    .fetch_one(&db)
+    .await
+    .map_err(|_| StatusCode::NOT_FOUND)?;
+
+    // Moving it to another customer takes the same access as logging it there
+    if customer_id != previous_customer {
+        require_access(&db, &current_user, RecordKind::Customer, customer_id, Access::Write).await?;
+    }
+
+    // Only the owner, their managers or an admin can hand it to someone else
+    let chosen = parse_optional_id(&form.assigned_to)?;
+    let assigned_to = if chosen == previous_assignee {
+        previous_assignee
+    } else if access < Access::Manage {
+        return Err(StatusCode::FORBIDDEN);
+    } else {
+        route_assignee(&db, chosen, form.keep_assignee.is_some()).await?
+    };
+
+    let update = sqlx::query(
         r#"
         UPDATE activities SET
             customer_id = $2, contact_id = $3, deal_id = $4, activity_type = $5, subject = $6,
-            description = $7, activity_date = $8, duration_minutes = $9, completed = $10
+            description = $7, activity_date = $8, duration_minutes = $9, completed = $10,
+            outcome_code = $11, assigned_to = $12
         WHERE id = $1
         "#,
     )
@@ -1212,11 +3669,257 @@ pub async fn update_activity(
     .bind(&activity_date)
     .bind(&form.duration_minutes)
     .bind(completed)
-    .execute(&db)
+    .bind(&outcome_code)
+    .bind(assigned_to);
+    audited_execute(&db, &current_user, "update", Audited::Activity, activity_id, update)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Redirect::to("/crm/activities"))
+}
+
+// Tick an activity off, or reopen it, from the list page. Reopening drops
+// the outcome, as the full form does.
+pub async fn patch_activity_completed(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(activity_id): Path<Uuid>,
+    axum::Json(patch): axum::Json<ActivityCompletedPatch>,
+) -> Result<axum::Json<serde_json::Value>, StatusCode> {
+    require_access(&db, &current_user, RecordKind::Activity, activity_id, Access::Write).await?;
+
+    let (activity_type, outcome_code) = sqlx::query_as::<_, (String, Option<String>)>(
+        "SELECT activity_type, outcome_code FROM activities WHERE id = $1"
+    )
+    .bind(activity_id)
+    .fetch_optional(&db)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    .ok_or(StatusCode::NOT_FOUND)?;
+    let outcome_code = resolve_outcome(&db, &activity_type, outcome_code, patch.completed).await?;
+
+    let update = sqlx::query("UPDATE activities SET completed = $2, outcome_code = $3 WHERE id = $1")
+        .bind(activity_id)
+        .bind(patch.completed)
+        .bind(&outcome_code);
+    audited_execute(&db, &current_user, "update", Audited::Activity, activity_id, update)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(axum::Json(serde_json::json!({ "id": activity_id, "completed": patch.completed, "outcome_code": outcome_code })))
+}
+
+async fn load_activity_types(db: &Database, include_inactive: bool) -> Result<Vec<ActivityType>, StatusCode> {
+    activity_types::list(db, include_inactive).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+async fn load_outcomes(db: &Database, active_only: bool) -> Result<Vec<ActivityOutcome>, StatusCode> {
+    sqlx::query_as::<_, ActivityOutcome>(
+        "SELECT * FROM activity_outcomes WHERE is_active OR NOT $1 ORDER BY activity_type, sort_order, label"
+    )
+    .bind(active_only)
+    .fetch_all(db)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+// Outcomes are only recorded on completed activities and must belong to the
+// activity's type. Types that require one can't be completed without it.
+async fn resolve_outcome(
+    db: &Database,
+    activity_type: &str,
+    outcome_code: Option<String>,
+    completed: bool,
+) -> Result<Option<String>, StatusCode> {
+    let code = match outcome_code {
+        Some(code) if completed && !code.trim().is_empty() => code,
+        _ if completed => {
+            let requires_outcome = activity_types::get(db, activity_type)
+                .await
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+                .is_some_and(|t| t.requires_outcome);
+            if requires_outcome {
+                return Err(StatusCode::BAD_REQUEST);
+            }
+            return Ok(None);
+        }
+        _ => return Ok(None),
+    };
+
+    let exists = sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS(SELECT 1 FROM activity_outcomes WHERE activity_type = $1 AND code = $2)"
+    )
+    .bind(activity_type)
+    .bind(&code)
+    .fetch_one(db)
     .await
     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-    Ok(Redirect::to("/crm/activities"))
+    if !exists {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    Ok(Some(code))
+}
+
+// Activity outcome codes - configurable per activity type
+pub async fn activity_outcomes(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+) -> Result<Html<String>, StatusCode> {
+    if !current_user.has_manage_roles {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let outcomes = load_outcomes(&db, false).await?;
+
+    let template = ActivityOutcomesTemplate {
+        outcomes,
+        current_user,
+        activity_types: load_activity_types(&db, false).await?,
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+pub async fn create_activity_outcome(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Form(form): Form<ActivityOutcomeForm>,
+) -> Result<Redirect, StatusCode> {
+    if !current_user.has_manage_roles {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let code = form.code.trim().to_lowercase().replace(' ', "_");
+    if code.is_empty() || form.label.trim().is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    sqlx::query(
+        r#"
+        INSERT INTO activity_outcomes (activity_type, code, label, is_connect, is_held, sort_order)
+        VALUES ($1, $2, $3, $4, $5,
+            (SELECT COALESCE(MAX(sort_order), 0) + 1 FROM activity_outcomes WHERE activity_type = $1))
+        ON CONFLICT (tenant_id, activity_type, code) DO UPDATE SET
+            label = EXCLUDED.label, is_connect = EXCLUDED.is_connect,
+            is_held = EXCLUDED.is_held, is_active = true
+        "#,
+    )
+    .bind(&form.activity_type)
+    .bind(&code)
+    .bind(form.label.trim())
+    .bind(form.is_connect.is_some())
+    .bind(form.is_held.is_some())
+    .execute(&db)
+    .await
+    .map_err(|e| {
+        tracing::error!("Error saving activity outcome: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    Ok(Redirect::to("/crm/activities/outcomes"))
+}
+
+// Outcomes are retired rather than deleted so historical activities keep their label
+pub async fn toggle_activity_outcome(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(outcome_id): Path<Uuid>,
+) -> Result<Redirect, StatusCode> {
+    if !current_user.has_manage_roles {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    sqlx::query("UPDATE activity_outcomes SET is_active = NOT is_active WHERE id = $1")
+        .bind(outcome_id)
+        .execute(&db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Redirect::to("/crm/activities/outcomes"))
+}
+
+// Activity types - the icon, default duration and outcome rule for each kind of activity
+pub async fn activity_types_page(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Query(query): Query<ActivityTypesQuery>,
+) -> Result<Html<String>, StatusCode> {
+    if !current_user.has_manage_roles {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let template = ActivityTypesTemplate {
+        activity_types: load_activity_types(&db, true).await?,
+        current_user,
+        error: query.error,
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+pub async fn save_activity_type(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Form(form): Form<ActivityTypeForm>,
+) -> Result<Redirect, StatusCode> {
+    if !current_user.has_manage_roles {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let default_duration_minutes = match form.default_duration_minutes.as_deref().map(str::trim) {
+        None | Some("") => None,
+        Some(minutes) => Some(minutes.parse::<i32>().map_err(|_| StatusCode::BAD_REQUEST)?),
+    };
+    let input = activity_types::TypeInput {
+        code: &form.code,
+        name: &form.name,
+        icon: &form.icon,
+        default_duration_minutes,
+        requires_outcome: form.requires_outcome.is_some(),
+    };
+
+    let saved = activity_types::save(&db, &input).await.map_err(|e| {
+        tracing::error!("Error saving activity type: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    if let Err(error) = saved {
+        return Ok(Redirect::to(&format!("/crm/activities/types?error={}", urlencoding::encode(&error))));
+    }
+
+    let _ = create_audit_log(
+        &db,
+        &current_user,
+        "save".to_string(),
+        "activity_type".to_string(),
+        None,
+        None,
+        Some(serde_json::json!({
+            "code": form.code.trim(),
+            "name": form.name.trim(),
+            "icon": form.icon.trim(),
+            "default_duration_minutes": default_duration_minutes,
+            "requires_outcome": input.requires_outcome,
+        })),
+    )
+    .await;
+
+    Ok(Redirect::to("/crm/activities/types"))
+}
+
+pub async fn toggle_activity_type(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(type_id): Path<Uuid>,
+) -> Result<Redirect, StatusCode> {
+    if !current_user.has_manage_roles {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    activity_types::toggle(&db, type_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Redirect::to("/crm/activities/types"))
 }
 
 #[derive(Template)]
@@ -1224,12 +3927,25 @@ pub async fn update_activity(
 struct ContactEditTemplate {
     customer: Customer,
     contact: ContactDisplay,
+    tag_picker: TagPicker,
+    custom_fields: Vec<FieldInput>,
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ContactEditQuery {
+    error: Option<String>,
 }
 
 pub async fn contact_edit_form(
     State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
     Path((customer_id, contact_id)): Path<(Uuid, Uuid)>,
+    Query(query): Query<ContactEditQuery>,
 ) -> Result<Html<String>, StatusCode> {
+    current_user.require("customers:write")?;
+    require_access(&db, &current_user, RecordKind::Customer, customer_id, Access::Write).await?;
+
     let customer = sqlx::query_as::<_, Customer>("SELECT * FROM customers WHERE id = $1")
         .bind(customer_id)
         .fetch_one(&db)
@@ -1243,17 +3959,40 @@ pub async fn contact_edit_form(
         .await
         .map_err(|_| StatusCode::NOT_FOUND)?;
 
+    let custom_fields = custom_fields::inputs(load_custom_fields(&db, Extended::Contact).await?, &contact_db.custom_fields);
     let contact: ContactDisplay = contact_db.into();
 
-    let template = ContactEditTemplate { customer, contact };
+    let template = ContactEditTemplate {
+        customer,
+        contact,
+        tag_picker: load_tag_picker(&db, Tagged::Contact, Some(contact_id)).await?,
+        custom_fields,
+        error: query.error,
+    };
     Ok(Html(template.render().unwrap()))
 }
 
 pub async fn update_contact(
     State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
     Path((customer_id, contact_id)): Path<(Uuid, Uuid)>,
     Form(form): Form<ContactForm>,
 ) -> Result<Redirect, StatusCode> {
+    current_user.require("customers:write")?;
+    require_access(&db, &current_user, RecordKind::Customer, customer_id, Access::Write).await?;
+
+    let custom_values = match custom_field_values(&db, Extended::Contact, Some(contact_id), &form.custom).await? {
+        Ok(values) => values,
+        Err(error) => {
+            return Ok(Redirect::to(&format!(
+                "/crm/customers/{}/contacts/{}/edit?error={}",
+                customer_id,
+                contact_id,
+                urlencoding::encode(&error)
+            )));
+        }
+    };
+
     let is_primary = form.is_primary.is_some();
 
     if is_primary {
@@ -1265,11 +4004,14 @@ pub async fn update_contact(
             .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
     }
 
-    sqlx::query(
+    let update = sqlx::query(
         r#"
         UPDATE contacts SET
             first_name = $1, last_name = $2, title = $3, email = $4, phone = $5,
-            mobile = $6, is_primary = $7, notes = $8, updated_at = NOW()
+            mobile = $6, is_primary = $7, notes = $8, email_tracking_opt_out = $11,
+            do_not_contact = $12, custom_fields = $13, updated_at = NOW(),
+            email_status = CASE WHEN LOWER(COALESCE(email, '')) = LOWER(COALESCE($4, '')) THEN email_status ELSE 'valid' END,
+            email_status_reason = CASE WHEN LOWER(COALESCE(email, '')) = LOWER(COALESCE($4, '')) THEN email_status_reason END
         WHERE id = $9 AND customer_id = $10
         "#,
     )
@@ -1283,9 +4025,591 @@ pub async fn update_contact(
     .bind(&form.notes)
     .bind(contact_id)
     .bind(customer_id)
+    .bind(form.email_tracking_opt_out.is_some())
+    .bind(form.do_not_contact.is_some())
+    .bind(&custom_values);
+    audited_execute(&db, &current_user, "update", Audited::Contact, contact_id, update)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    save_tags(&db, &current_user, Tagged::Contact, contact_id, &form.tags).await?;
+
+    Ok(Redirect::to(&format!("/crm/customers/{}", customer_id)))
+}
+
+// One row on the contact timeline: an activity or an email open/click
+struct TimelineEntry {
+    kind: String,
+    title: String,
+    detail: String,
+    occurred_at: DateTime<Utc>,
+}
+
+#[derive(Template)]
+#[template(path = "crm/contact_detail.html")]
+struct ContactDetailTemplate {
+    current_user: CurrentUser,
+    can_edit: bool,
+    customer: Customer,
+    contact: ContactDisplay,
+    tags: Vec<Tag>,
+    custom_values: Vec<FieldValue>,
+    emails: Vec<TrackedEmail>,
+    timeline: Vec<TimelineEntry>,
+    sequence_panel: SequencePanel,
+    success_message: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ContactDetailQuery {
+    sent: Option<String>,
+    sequence_error: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ContactEmailForm {
+    subject: String,
+    body: String,
+}
+
+pub async fn contact_detail(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path((customer_id, contact_id)): Path<(Uuid, Uuid)>,
+    Query(query): Query<ContactDetailQuery>,
+) -> Result<Html<String>, StatusCode> {
+    current_user.require("customers:read")?;
+    let access = require_access(&db, &current_user, RecordKind::Customer, customer_id, Access::Read).await?;
+
+    let customer = sqlx::query_as::<_, Customer>("SELECT * FROM customers WHERE id = $1")
+        .bind(customer_id)
+        .fetch_one(&db)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+
+    let contact: ContactDisplay = sqlx::query_as::<_, Contact>("SELECT * FROM contacts WHERE id = $1 AND customer_id = $2")
+        .bind(contact_id)
+        .bind(customer_id)
+        .fetch_one(&db)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?
+        .into();
+
+    let emails = sqlx::query_as::<_, TrackedEmail>(
+        r#"
+        SELECT e.id, e.subject, e.status, COALESCE(e.created_at, NOW()) as created_at,
+               COUNT(ev.id) FILTER (WHERE ev.event_type = 'open') as open_count,
+               COUNT(ev.id) FILTER (WHERE ev.event_type = 'click') as click_count
+        FROM email_outbox e
+        LEFT JOIN email_events ev ON ev.email_id = e.id
+        WHERE e.contact_id = $1
+        GROUP BY e.id
+        ORDER BY e.created_at DESC
+        LIMIT 50
+        "#,
+    )
+    .bind(contact_id)
+    .fetch_all(&db)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let activities = sqlx::query_as::<_, Activity>(
+        "SELECT * FROM activities WHERE contact_id = $1 ORDER BY activity_date DESC LIMIT 50"
+    )
+    .bind(contact_id)
+    .fetch_all(&db)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let events = sqlx::query_as::<_, EmailEvent>(
+        r#"
+        SELECT ev.id, ev.email_id, e.subject, ev.event_type, COALESCE(ev.url, ev.detail) as detail,
+               COALESCE(ev.created_at, NOW()) as created_at
+        FROM email_events ev
+        JOIN email_outbox e ON e.id = ev.email_id
+        WHERE ev.contact_id = $1
+        ORDER BY ev.created_at DESC
+        LIMIT 50
+        "#,
+    )
+    .bind(contact_id)
+    .fetch_all(&db)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let mut timeline: Vec<TimelineEntry> = activities
+        .into_iter()
+        .map(|activity| TimelineEntry {
+            kind: activity.activity_type,
+            title: activity.subject,
+            detail: activity.description.unwrap_or_default(),
+            occurred_at: activity.activity_date,
+        })
+        .chain(events.into_iter().map(|event| TimelineEntry {
+            title: match event.event_type.as_str() {
+                "click" => format!("Clicked a link in \"{}\"", event.subject),
+                "bounce" => format!("\"{}\" bounced", event.subject),
+                "complaint" => format!("Marked \"{}\" as spam", event.subject),
+                "reply" => format!("Replied to \"{}\"", event.subject),
+                _ => format!("Opened \"{}\"", event.subject),
+            },
+            kind: event.event_type,
+            detail: event.detail.unwrap_or_default(),
+            occurred_at: event.created_at,
+        }))
+        .collect();
+    timeline.sort_by_key(|entry| std::cmp::Reverse(entry.occurred_at));
+
+    let can_edit = current_user.can("customers:write") && access >= Access::Write;
+    let sequence_panel = load_sequence_panel(
+        &db,
+        format!("/crm/customers/{}/contacts/{}/sequences", customer_id, contact_id),
+        sequences::for_contact(&db, contact_id).await.map_err(|e| {
+            tracing::error!("Error loading sequence enrollments: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?,
+        can_edit,
+        query.sequence_error,
+    )
+    .await?;
+    let custom_values = custom_fields::display(&load_custom_fields(&db, Extended::Contact).await?, &contact.custom_fields);
+
+    let template = ContactDetailTemplate {
+        can_edit,
+        current_user,
+        customer,
+        contact,
+        tags: load_record_tags(&db, Tagged::Contact, contact_id).await?,
+        custom_values,
+        emails,
+        timeline,
+        sequence_panel,
+        success_message: query.sent.map(|_| "Email queued for delivery.".to_string()),
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+// Queue a tracked email to the contact and log it as a completed email activity
+pub async fn send_contact_email(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path((customer_id, contact_id)): Path<(Uuid, Uuid)>,
+    Form(form): Form<ContactEmailForm>,
+) -> Result<Redirect, StatusCode> {
+    current_user.require("customers:read")?;
+    require_access(&db, &current_user, RecordKind::Customer, customer_id, Access::Read).await?;
+
+    let contact = sqlx::query_as::<_, Contact>("SELECT * FROM contacts WHERE id = $1 AND customer_id = $2")
+        .bind(contact_id)
+        .bind(customer_id)
+        .fetch_one(&db)
+        .await
+        .map_err(|_| StatusCode::NOT_FOUND)?;
+
+    let subject = form.subject.trim();
+    if subject.is_empty() || form.body.trim().is_empty() || contact.email.as_deref().unwrap_or("").is_empty()
+        || contact.email_status == "invalid"
+    {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    mailer::queue_contact_email(&db, contact_id, Some(current_user.id), subject, &mailer::text_to_html(&form.body))
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to queue contact email: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO activities (
+            customer_id, contact_id, activity_type, subject, description,
+            activity_date, completed, created_by
+        )
+        VALUES ($1, $2, 'email', $3, $4, NOW(), true, $5)
+        "#,
+    )
+    .bind(customer_id)
+    .bind(contact_id)
+    .bind(subject)
+    .bind(&form.body)
+    .bind(current_user.id)
     .execute(&db)
     .await
     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-    Ok(Redirect::to(&format!("/crm/customers/{}", customer_id)))
-}
\ No newline at end of file
+    Ok(Redirect::to(&format!("/crm/customers/{}/contacts/{}?sent=1", customer_id, contact_id)))
+}
+async fn save_share(
+    db: &Database,
+    current_user: CurrentUser,
+    kind: RecordKind,
+    id: Uuid,
+    form: ShareForm,
+) -> Result<Redirect, StatusCode> {
+    require_access(db, &current_user, kind, id, Access::Manage).await?;
+
+    if form.access != "read" && form.access != "write" {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let (target, grantee) = form.grantee.split_once(':').ok_or(StatusCode::BAD_REQUEST)?;
+    let grantee = Uuid::parse_str(grantee).map_err(|_| StatusCode::BAD_REQUEST)?;
+    let (user_id, team_lead_id) = match target {
+        "user" => (Some(grantee), None),
+        "team" => (None, Some(grantee)),
+        _ => return Err(StatusCode::BAD_REQUEST),
+    };
+
+    sharing::share(db, kind, id, user_id, team_lead_id, &form.access, current_user.id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error sharing record: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    Ok(Redirect::to(&kind.url(id)))
+}
+
+async fn remove_share(
+    db: &Database,
+    current_user: CurrentUser,
+    kind: RecordKind,
+    id: Uuid,
+    share_id: Uuid,
+) -> Result<Redirect, StatusCode> {
+    require_access(db, &current_user, kind, id, Access::Manage).await?;
+
+    let removed = sharing::unshare(db, kind, id, share_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if !removed {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    Ok(Redirect::to(&kind.url(id)))
+}
+
+async fn load_sequence_panel(
+    db: &Database,
+    action_url: String,
+    enrollments: Vec<SequenceEnrollment>,
+    can_enroll: bool,
+    error: Option<String>,
+) -> Result<SequencePanel, StatusCode> {
+    let sequences = if can_enroll {
+        sequences::available(db).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    } else {
+        Vec::new()
+    };
+    Ok(SequencePanel {
+        action_url,
+        can_enroll,
+        error,
+        enrollments,
+        sequences,
+    })
+}
+
+async fn load_watch_button(db: &Database, kind: RecordKind, id: Uuid, user_id: Uuid) -> Result<WatchButton, StatusCode> {
+    let (watching, watchers) = watchers::status(db, kind, id, user_id).await.map_err(|e| {
+        tracing::error!("Error loading watchers: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    Ok(WatchButton {
+        action_url: kind.url(id),
+        watching,
+        watchers,
+    })
+}
+
+// Anyone who can see a record may watch it
+async fn set_watching(
+    db: &Database,
+    current_user: CurrentUser,
+    kind: RecordKind,
+    id: Uuid,
+    watching: bool,
+) -> Result<Redirect, StatusCode> {
+    require_access(db, &current_user, kind, id, Access::Read).await?;
+
+    let result = if watching {
+        watchers::watch(db, kind, id, current_user.id).await
+    } else {
+        watchers::unwatch(db, kind, id, current_user.id).await
+    };
+    result.map_err(|e| {
+        tracing::error!("Error updating watchers: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    Ok(Redirect::to(&kind.url(id)))
+}
+
+pub async fn watch_customer(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<Redirect, StatusCode> {
+    set_watching(&db, current_user, RecordKind::Customer, id, true).await
+}
+
+pub async fn unwatch_customer(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<Redirect, StatusCode> {
+    set_watching(&db, current_user, RecordKind::Customer, id, false).await
+}
+
+pub async fn watch_deal(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<Redirect, StatusCode> {
+    set_watching(&db, current_user, RecordKind::Deal, id, true).await
+}
+
+pub async fn unwatch_deal(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<Redirect, StatusCode> {
+    set_watching(&db, current_user, RecordKind::Deal, id, false).await
+}
+
+pub async fn share_customer(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+    Form(form): Form<ShareForm>,
+) -> Result<Redirect, StatusCode> {
+    save_share(&db, current_user, RecordKind::Customer, id, form).await
+}
+
+pub async fn unshare_customer(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path((id, share_id)): Path<(Uuid, Uuid)>,
+) -> Result<Redirect, StatusCode> {
+    remove_share(&db, current_user, RecordKind::Customer, id, share_id).await
+}
+
+pub async fn share_deal(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+    Form(form): Form<ShareForm>,
+) -> Result<Redirect, StatusCode> {
+    save_share(&db, current_user, RecordKind::Deal, id, form).await
+}
+
+pub async fn unshare_deal(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path((id, share_id)): Path<(Uuid, Uuid)>,
+) -> Result<Redirect, StatusCode> {
+    remove_share(&db, current_user, RecordKind::Deal, id, share_id).await
+}
+
+async fn load_notes_panel(
+    db: &Database,
+    current_user: &CurrentUser,
+    kind: RecordKind,
+    id: Uuid,
+    access: Access,
+    error: Option<String>,
+) -> Result<NotesPanel, StatusCode> {
+    let notes = notes::for_record(db, kind, id).await.map_err(|e| {
+        tracing::error!("Error loading notes: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    Ok(NotesPanel {
+        action_url: format!("{}/notes", kind.url(id)),
+        notes,
+        rights: NoteRights::new(current_user, access),
+        timezone: current_user.timezone,
+        error,
+    })
+}
+
+fn note_redirect(kind: RecordKind, id: Uuid, error: Option<String>) -> Redirect {
+    match error {
+        Some(error) => Redirect::to(&format!("{}?note_error={}#notes", kind.url(id), urlencoding::encode(&error))),
+        None => Redirect::to(&format!("{}#notes", kind.url(id))),
+    }
+}
+
+// A note in the record's thread, if the viewer may edit it or, when
+// `deleting`, delete it
+async fn load_note_for_change(
+    db: &Database,
+    current_user: &CurrentUser,
+    kind: RecordKind,
+    id: Uuid,
+    note_id: Uuid,
+    deleting: bool,
+) -> Result<Note, StatusCode> {
+    let access = require_access(db, current_user, kind, id, Access::Read).await?;
+    let note = notes::get(db, kind, id, note_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let rights = NoteRights::new(current_user, access);
+    let allowed = if deleting { rights.can_delete(&note) } else { rights.can_edit(&note) };
+    if allowed {
+        Ok(note)
+    } else {
+        Err(StatusCode::FORBIDDEN)
+    }
+}
+
+async fn add_note(
+    db: &Database,
+    current_user: CurrentUser,
+    kind: RecordKind,
+    id: Uuid,
+    form: NoteForm,
+) -> Result<Redirect, StatusCode> {
+    current_user.require("customers:write")?;
+    require_access(db, &current_user, kind, id, Access::Write).await?;
+
+    let note = match notes::create(db, kind, id, current_user.id, &form.body).await {
+        Ok(Ok(note)) => note,
+        Ok(Err(e)) => return Ok(note_redirect(kind, id, Some(e))),
+        Err(e) => {
+            tracing::error!("Error adding note: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    let _ = create_audit_log(
+        db,
+        &current_user,
+        "add_note".to_string(),
+        kind.key().to_string(),
+        Some(id),
+        None,
+        Some(serde_json::json!({ "note_id": note.id, "body": note.body })),
+    )
+    .await;
+    let record_name = notes::record_name(db, kind, id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .unwrap_or_default();
+    events::publish(db, &current_user, Event::NoteCreated { note: &note, kind, record_name: &record_name }).await;
+
+    Ok(note_redirect(kind, id, None))
+}
+
+async fn edit_note(
+    db: &Database,
+    current_user: CurrentUser,
+    kind: RecordKind,
+    id: Uuid,
+    note_id: Uuid,
+    form: NoteForm,
+) -> Result<Redirect, StatusCode> {
+    let note = load_note_for_change(db, &current_user, kind, id, note_id, false).await?;
+
+    match notes::update(db, note_id, &form.body).await {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => return Ok(note_redirect(kind, id, Some(e))),
+        Err(e) => {
+            tracing::error!("Error updating note: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    }
+
+    let _ = create_audit_log(
+        db,
+        &current_user,
+        "update_note".to_string(),
+        kind.key().to_string(),
+        Some(id),
+        Some(serde_json::json!({ "note_id": note_id, "body": note.body })),
+        Some(serde_json::json!({ "note_id": note_id, "body": form.body.trim() })),
+    )
+    .await;
+
+    Ok(note_redirect(kind, id, None))
+}
+
+async fn remove_note(
+    db: &Database,
+    current_user: CurrentUser,
+    kind: RecordKind,
+    id: Uuid,
+    note_id: Uuid,
+) -> Result<Redirect, StatusCode> {
+    let note = load_note_for_change(db, &current_user, kind, id, note_id, true).await?;
+
+    notes::delete(db, note_id).await.map_err(|e| {
+        tracing::error!("Error deleting note: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    let _ = create_audit_log(
+        db,
+        &current_user,
+        "delete_note".to_string(),
+        kind.key().to_string(),
+        Some(id),
+        Some(serde_json::json!({ "note_id": note_id, "author_id": note.author_id, "body": note.body })),
+        None,
+    )
+    .await;
+
+    Ok(note_redirect(kind, id, None))
+}
+
+pub async fn add_customer_note(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+    Form(form): Form<NoteForm>,
+) -> Result<Redirect, StatusCode> {
+    add_note(&db, current_user, RecordKind::Customer, id, form).await
+}
+
+pub async fn update_customer_note(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path((id, note_id)): Path<(Uuid, Uuid)>,
+    Form(form): Form<NoteForm>,
+) -> Result<Redirect, StatusCode> {
+    edit_note(&db, current_user, RecordKind::Customer, id, note_id, form).await
+}
+
+pub async fn delete_customer_note(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path((id, note_id)): Path<(Uuid, Uuid)>,
+) -> Result<Redirect, StatusCode> {
+    remove_note(&db, current_user, RecordKind::Customer, id, note_id).await
+}
+
+pub async fn add_deal_note(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+    Form(form): Form<NoteForm>,
+) -> Result<Redirect, StatusCode> {
+    add_note(&db, current_user, RecordKind::Deal, id, form).await
+}
+
+pub async fn update_deal_note(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path((id, note_id)): Path<(Uuid, Uuid)>,
+    Form(form): Form<NoteForm>,
+) -> Result<Redirect, StatusCode> {
+    edit_note(&db, current_user, RecordKind::Deal, id, note_id, form).await
+}
+
+pub async fn delete_deal_note(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path((id, note_id)): Path<(Uuid, Uuid)>,
+) -> Result<Redirect, StatusCode> {
+    remove_note(&db, current_user, RecordKind::Deal, id, note_id).await
+}
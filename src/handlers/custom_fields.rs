@@ -0,0 +1,132 @@
+use axum::{
+    extract::{Form, Path, Query, State},
+    http::StatusCode,
+    response::{Html, Redirect},
+};
+use askama::Template;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    database::Database,
+    handlers::team::create_audit_log,
+    middleware::{AuthUser, CurrentUser},
+    models::{CustomField, CUSTOM_FIELD_RECORDS, CUSTOM_FIELD_TYPES},
+    services::custom_fields::{self, FieldDefinition},
+};
+
+#[derive(Template)]
+#[template(path = "crm/custom_fields.html")]
+struct CustomFieldsTemplate {
+    current_user: CurrentUser,
+    fields: Vec<CustomField>,
+    records: &'static [(&'static str, &'static str)],
+    types: &'static [(&'static str, &'static str)],
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct CustomFieldsQuery {
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct CustomFieldForm {
+    record_type: String,
+    key: String,
+    label: String,
+    field_type: String,
+    options: Option<String>,
+    is_required: Option<String>,
+}
+
+fn require_admin(current_user: &CurrentUser) -> Result<(), StatusCode> {
+    if current_user.has_manage_roles {
+        Ok(())
+    } else {
+        Err(StatusCode::FORBIDDEN)
+    }
+}
+
+// The extra fields shown on customer, contact and deal forms
+pub async fn custom_fields_page(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Query(query): Query<CustomFieldsQuery>,
+) -> Result<Html<String>, StatusCode> {
+    require_admin(&current_user)?;
+
+    let fields = custom_fields::list(&db).await.map_err(|e| {
+        tracing::error!("Error loading custom fields: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let template = CustomFieldsTemplate {
+        current_user,
+        fields,
+        records: CUSTOM_FIELD_RECORDS,
+        types: CUSTOM_FIELD_TYPES,
+        error: query.error,
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+pub async fn save_custom_field(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Form(form): Form<CustomFieldForm>,
+) -> Result<Redirect, StatusCode> {
+    require_admin(&current_user)?;
+
+    let input = FieldDefinition {
+        record_type: &form.record_type,
+        key: &form.key,
+        label: &form.label,
+        field_type: &form.field_type,
+        options: form.options.as_deref().unwrap_or(""),
+        is_required: form.is_required.is_some(),
+    };
+
+    let saved = custom_fields::save(&db, &input).await.map_err(|e| {
+        tracing::error!("Error saving custom field: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    if let Err(error) = saved {
+        return Ok(Redirect::to(&format!("/crm/custom-fields?error={}", urlencoding::encode(&error))));
+    }
+
+    let _ = create_audit_log(
+        &db,
+        &current_user,
+        "save".to_string(),
+        "custom_field".to_string(),
+        None,
+        None,
+        Some(serde_json::json!({
+            "record_type": form.record_type,
+            "key": form.key.trim(),
+            "label": form.label.trim(),
+            "field_type": form.field_type,
+            "options": input.options,
+            "is_required": input.is_required,
+        })),
+    )
+    .await;
+
+    Ok(Redirect::to("/crm/custom-fields"))
+}
+
+// Retired fields drop off the forms; records keep their values
+pub async fn toggle_custom_field(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<Redirect, StatusCode> {
+    require_admin(&current_user)?;
+
+    custom_fields::toggle(&db, id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Redirect::to("/crm/custom-fields"))
+}
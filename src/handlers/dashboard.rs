@@ -1,13 +1,17 @@
 use axum::{
     extract::State,
-    response::{Html, Redirect},
+    response::Html,
 };
 use askama::Template;
-use tower_cookies::Cookies;
 
 use crate::{
     database::Database,
-    middleware::get_current_user,
+    middleware::AuthUser,
+    services::{
+        approvals::{self, APPROVAL_KINDS},
+        dashboard::{self as widgets, Widget, DASHBOARD_VARIANTS},
+        teams,
+    },
 };
 
 #[derive(Template)]
@@ -19,19 +23,20 @@ struct DashboardTemplate {
     has_inventory_access: bool,
     has_team_access: bool,
     has_expenses_access: bool,
+    has_projects_access: bool,
     has_shipping_access: bool,
     has_api_access: bool,
+    // How many requests wait on this user; None when they can't approve anything
+    approvals_waiting: Option<usize>,
+    // Role-based widgets, e.g. "Sales" with my pipeline and my tasks
+    variant_label: String,
+    widgets: Vec<Widget>,
 }
 
 pub async fn dashboard(
-    cookies: Cookies,
+    AuthUser(current_user): AuthUser,
     State(db): State<Database>,
-) -> Result<Html<String>, Redirect> {
-    let current_user = match get_current_user(cookies, &db).await {
-        Some(user) => user,
-        None => return Err(Redirect::to("/login")),
-    };
-
+) -> Html<String> {
     // Get active customer count (prospect + active status)
     let customer_count = sqlx::query_scalar::<_, i64>(
         "SELECT COUNT(*) FROM customers WHERE status IN ('prospect', 'active')"
@@ -48,6 +53,40 @@ pub async fn dashboard(
     .await
     .unwrap_or(0);
 
+    let variants = widgets::user_variants(&db, current_user.id)
+        .await
+        .unwrap_or_else(|e| {
+            tracing::error!("Error loading dashboard variants: {}", e);
+            Vec::new()
+        });
+    let variant_label = DASHBOARD_VARIANTS
+        .iter()
+        .filter(|(key, _, _)| variants.iter().any(|v| v == key))
+        .map(|(_, label, _)| *label)
+        .collect::<Vec<_>>()
+        .join(" & ");
+
+    let mut loaded = Vec::new();
+    for def in widgets::widgets_for(&variants, &current_user.permissions) {
+        match widgets::load_widget(&db, def, &current_user).await {
+            Ok(widget) => loaded.push(widget),
+            Err(e) => tracing::error!("Error loading dashboard widget {}: {}", def.key, e),
+        }
+    }
+
+    // Approvers always get the inbox link; managers only while one of their
+    // reports' discounts is waiting on them
+    let approver = teams::approves_expenses(&current_user.permissions)
+        || APPROVAL_KINDS
+            .iter()
+            .any(|(_, _, permission)| current_user.permissions.iter().any(|p| p == permission));
+    let approvals_waiting = approvals::pending(&db, &current_user)
+        .await
+        .map(|pending| pending.len())
+        .map_err(|e| tracing::error!("Error counting approvals: {}", e))
+        .ok()
+        .filter(|waiting| approver || *waiting > 0);
+
     let template = DashboardTemplate {
         user_name: format!("{} {}", current_user.first_name, current_user.last_name),
         customer_count,
@@ -55,9 +94,13 @@ pub async fn dashboard(
         has_inventory_access: current_user.permissions.contains(&"inventory:read".to_string()),
         has_team_access: current_user.has_team_read,
         has_expenses_access: current_user.permissions.contains(&"expenses:read".to_string()),
+        has_projects_access: current_user.permissions.contains(&"projects:read".to_string()),
         has_shipping_access: current_user.permissions.contains(&"shipping:read".to_string()),
         has_api_access: current_user.permissions.contains(&"api:access".to_string()),
+        approvals_waiting,
+        variant_label,
+        widgets: loaded,
     };
 
-    Ok(Html(template.render().unwrap()))
+    Html(template.render().unwrap())
 }
@@ -5,14 +5,13 @@ use axum::{
 };
 use askama::Template;
 use serde::Deserialize;
-use tower_cookies::Cookies;
 use uuid::Uuid;
 use chrono::NaiveDate;
 
 use crate::{
     database::Database,
     models::{Deal, DealDisplay, Customer, Contact},
-    middleware::get_current_user,
+    middleware::AuthUser,
 };
 
 #[derive(Template)]
@@ -183,12 +182,9 @@ pub async fn deal_edit_form(
 
 pub async fn create_deal(
     State(db): State<Database>,
-    cookies: Cookies,
+    AuthUser(user): AuthUser,
     Form(form): Form<DealForm>,
 ) -> Result<Redirect, StatusCode> {
-    let user = get_current_user(cookies, &db).await
-        .ok_or(StatusCode::UNAUTHORIZED)?;
-
     // Parse customer_id
     let customer_id = Uuid::parse_str(&form.customer_id)
         .map_err(|_| StatusCode::BAD_REQUEST)?;
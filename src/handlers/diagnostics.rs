@@ -0,0 +1,87 @@
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::Html,
+};
+use askama::Template;
+use chrono::{DateTime, Utc};
+
+use crate::{
+    database::Database,
+    middleware::{AuthUser, CurrentUser},
+    scheduler::{self, JobRun},
+    services::diagnostics::{self, CacheRate, MigrationStatus, PoolStats, QueueDepth},
+};
+
+#[derive(Template)]
+#[template(path = "team/diagnostics.html")]
+struct DiagnosticsTemplate {
+    current_user: CurrentUser,
+    version: &'static str,
+    build: Option<&'static str>,
+    started_at: DateTime<Utc>,
+    uptime: String,
+    migrations: MigrationStatus,
+    pool: PoolStats,
+    queues: Vec<QueueDepth>,
+    caches: Vec<CacheRate>,
+    job_runs: Vec<JobRun>,
+}
+
+fn require_admin(current_user: &CurrentUser) -> Result<(), StatusCode> {
+    if current_user.has_manage_roles {
+        Ok(())
+    } else {
+        Err(StatusCode::FORBIDDEN)
+    }
+}
+
+fn format_uptime(started_at: DateTime<Utc>) -> String {
+    let minutes = (Utc::now() - started_at).num_minutes();
+    match (minutes / (24 * 60), minutes / 60 % 24, minutes % 60) {
+        (0, 0, m) => format!("{}m", m),
+        (0, h, m) => format!("{}h {}m", h, m),
+        (d, h, _) => format!("{}d {}h", d, h),
+    }
+}
+
+// What support needs to know about this server without a shell on it. The
+// queues are the current organization's; everything else is the server's.
+pub async fn diagnostics_page(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+) -> Result<Html<String>, StatusCode> {
+    require_admin(&current_user)?;
+
+    let migrations = diagnostics::migration_status(&db).await.map_err(|e| {
+        tracing::error!("Error loading migration status: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    let pool = diagnostics::pool_stats(&db).await.map_err(|e| {
+        tracing::error!("Error loading pool stats: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    let queues = diagnostics::queue_depths(&db).await.map_err(|e| {
+        tracing::error!("Error loading queue depths: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    let caches = diagnostics::cache_rates(&db).await.map_err(|e| {
+        tracing::error!("Error loading cache stats: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let started_at = diagnostics::started_at();
+    let template = DiagnosticsTemplate {
+        current_user,
+        version: diagnostics::VERSION,
+        build: diagnostics::BUILD,
+        started_at,
+        uptime: format_uptime(started_at),
+        migrations,
+        pool,
+        queues,
+        caches,
+        job_runs: scheduler::last_runs(),
+    };
+    Ok(Html(template.render().unwrap()))
+}
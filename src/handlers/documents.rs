@@ -0,0 +1,161 @@
+use axum::{
+    extract::{Form, Path, Query, State},
+    http::StatusCode,
+    response::{Html, Redirect},
+};
+use askama::Template;
+use serde::Deserialize;
+
+use crate::{
+    database::Database,
+    handlers::team::create_audit_log,
+    middleware::AuthUser,
+    models::{DocumentTemplate, DOCUMENT_COLUMNS, LOGO_POSITIONS},
+    services::documents::{self, TemplateChanges},
+    utils::locale::LANGUAGES,
+};
+
+#[derive(Template)]
+#[template(path = "team/documents.html")]
+struct DocumentsTemplate {
+    templates: Vec<DocumentTemplate>,
+    columns: Vec<(&'static str, &'static str)>,
+    logo_positions: Vec<(&'static str, &'static str)>,
+    languages: Vec<(&'static str, &'static str)>,
+    saved: Option<String>,
+    error: Option<String>,
+}
+
+impl DocumentsTemplate {
+    fn was_saved(&self, document_type: &str) -> bool {
+        self.saved.as_deref() == Some(document_type)
+    }
+}
+
+#[derive(Deserialize)]
+pub struct DocumentsQuery {
+    saved: Option<String>,
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct PreviewQuery {
+    language: Option<String>,
+}
+
+fn field<'a>(form: &'a [(String, String)], name: &str) -> Option<&'a str> {
+    form.iter()
+        .find(|(key, _)| key == name)
+        .map(|(_, value)| value.trim())
+        .filter(|value| !value.is_empty())
+}
+
+pub async fn documents_page(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Query(query): Query<DocumentsQuery>,
+) -> Result<Html<String>, StatusCode> {
+    if !current_user.has_manage_roles {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let templates = documents::list(&db).await.map_err(|e| {
+        tracing::error!("Error loading document templates: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let template = DocumentsTemplate {
+        templates,
+        columns: DOCUMENT_COLUMNS.to_vec(),
+        logo_positions: LOGO_POSITIONS.to_vec(),
+        languages: LANGUAGES.to_vec(),
+        saved: query.saved,
+        error: query.error,
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+pub async fn update_document_template(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(document_type): Path<String>,
+    Form(form): Form<Vec<(String, String)>>,
+) -> Result<Redirect, StatusCode> {
+    if !current_user.has_manage_roles {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let changes = TemplateChanges {
+        title: field(&form, "title").unwrap_or_default(),
+        logo_url: field(&form, "logo_url"),
+        logo_position: field(&form, "logo_position").unwrap_or("left"),
+        header_text: field(&form, "header_text"),
+        terms_text: field(&form, "terms_text"),
+        footer_text: field(&form, "footer_text"),
+        columns: form
+            .iter()
+            .filter(|(name, _)| name == "columns")
+            .map(|(_, column)| column.clone())
+            .collect(),
+    };
+    // The logo can be a whole embedded image, so only whether there is one is logged
+    let new_values = serde_json::json!({
+        "title": changes.title,
+        "has_logo": changes.logo_url.is_some(),
+        "logo_position": changes.logo_position,
+        "header_text": changes.header_text,
+        "terms_text": changes.terms_text,
+        "footer_text": changes.footer_text,
+        "columns": changes.columns,
+    });
+
+    let updated = documents::update(&db, &document_type, changes, current_user.id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error updating document template: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    if let Err(error) = updated {
+        return Ok(Redirect::to(&format!("/team/documents?error={}", urlencoding::encode(&error))));
+    }
+
+    let _ = create_audit_log(
+        &db,
+        &current_user,
+        "update".to_string(),
+        "document_template".to_string(),
+        None,
+        Some(serde_json::json!({ "document_type": document_type })),
+        Some(new_values),
+    )
+    .await;
+
+    Ok(Redirect::to(&format!("/team/documents?saved={}#{}", document_type, document_type)))
+}
+
+// The saved layout filled with sample lines, in any supported language
+pub async fn preview_document(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(document_type): Path<String>,
+    Query(query): Query<PreviewQuery>,
+) -> Result<Html<String>, StatusCode> {
+    if !current_user.has_manage_roles {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let layout = documents::find(&db, &document_type)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let organization = documents::organization_name(&db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let document = documents::sample(&layout, query.language, chrono::Utc::now().date_naive());
+    let html = documents::render(&layout, &organization, &document).map_err(|e| {
+        tracing::error!("Error rendering document preview: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    Ok(Html(html))
+}
@@ -0,0 +1,83 @@
+use axum::{
+    extract::{Path, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Redirect, Response},
+};
+use uuid::Uuid;
+
+use crate::{database::Database, middleware::client_info::ClientInfo};
+
+// 1x1 transparent GIF served as the open pixel
+const TRACKING_PIXEL: [u8; 43] = [
+    0x47, 0x49, 0x46, 0x38, 0x39, 0x61, 0x01, 0x00, 0x01, 0x00, 0x80, 0x00, 0x00, 0x00, 0x00, 0x00,
+    0xff, 0xff, 0xff, 0x21, 0xf9, 0x04, 0x01, 0x00, 0x00, 0x00, 0x00, 0x2c, 0x00, 0x00, 0x00, 0x00,
+    0x01, 0x00, 0x01, 0x00, 0x00, 0x02, 0x02, 0x44, 0x01, 0x00, 0x3b,
+];
+
+// Record an event against the email, skipping contacts who have opted out since it was sent
+async fn record_event(
+    db: &Database,
+    email_id: Uuid,
+    event_type: &str,
+    url: Option<&str>,
+    client: &ClientInfo,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO email_events (email_id, contact_id, event_type, url, ip_address, user_agent)
+        SELECT e.id, e.contact_id, $2, $3, $4, $5
+        FROM email_outbox e
+        LEFT JOIN contacts c ON c.id = e.contact_id
+        WHERE e.id = $1 AND COALESCE(c.email_tracking_opt_out, false) = false
+        "#,
+    )
+    .bind(email_id)
+    .bind(event_type)
+    .bind(url)
+    .bind(&client.ip_address)
+    .bind(&client.user_agent)
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+pub async fn track_open(
+    State(db): State<Database>,
+    Path(email_id): Path<Uuid>,
+    client: ClientInfo,
+) -> Response {
+    if let Err(e) = record_event(&db, email_id, "open", None, &client).await {
+        tracing::error!("Failed to record email open for {}: {}", email_id, e);
+    }
+
+    (
+        [
+            (header::CONTENT_TYPE, "image/gif"),
+            (header::CACHE_CONTROL, "no-store, no-cache, must-revalidate"),
+        ],
+        TRACKING_PIXEL,
+    )
+        .into_response()
+}
+
+pub async fn track_click(
+    State(db): State<Database>,
+    Path(link_id): Path<Uuid>,
+    client: ClientInfo,
+) -> Result<Redirect, StatusCode> {
+    let (email_id, url) = sqlx::query_as::<_, (Uuid, String)>(
+        "SELECT email_id, url FROM email_links WHERE id = $1"
+    )
+    .bind(link_id)
+    .fetch_optional(&db)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    .ok_or(StatusCode::NOT_FOUND)?;
+
+    if let Err(e) = record_event(&db, email_id, "click", Some(&url), &client).await {
+        tracing::error!("Failed to record email click for {}: {}", email_id, e);
+    }
+
+    Ok(Redirect::to(&url))
+}
@@ -0,0 +1,174 @@
+use axum::{
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+    Json,
+};
+use serde::Deserialize;
+use serde_json::{json, Value};
+use std::env;
+use uuid::Uuid;
+
+use crate::{database::Database, services::sequences};
+
+// Notification posted by the email provider, singly or as a batch:
+// {"type": "bounce", "email": "jane@example.com", "bounce_type": "hard", "reason": "550 mailbox unavailable"}
+// {"type": "complaint", "email": "jane@example.com", "email_id": "<outbox id>"}
+// {"type": "reply", "email": "jane@example.com"}
+#[derive(Deserialize)]
+pub struct EmailNotice {
+    #[serde(alias = "event")]
+    r#type: String,
+    #[serde(alias = "recipient")]
+    email: String,
+    bounce_type: Option<String>,
+    reason: Option<String>,
+    email_id: Option<Uuid>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum EmailNoticePayload {
+    Batch(Vec<EmailNotice>),
+    Single(EmailNotice),
+}
+
+#[derive(Deserialize)]
+pub struct WebhookQuery {
+    token: Option<String>,
+}
+
+// Compare without short-circuiting so the token can't be guessed byte by byte
+fn token_matches(given: &str, expected: &str) -> bool {
+    given.len() == expected.len()
+        && given
+            .bytes()
+            .zip(expected.bytes())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+// Requests must carry EMAIL_WEBHOOK_SECRET in X-Webhook-Token or ?token=.
+// Without the secret configured the endpoint rejects everything.
+fn authorize(headers: &HeaderMap, query: &WebhookQuery) -> Result<(), StatusCode> {
+    let expected = env::var("EMAIL_WEBHOOK_SECRET").map_err(|_| StatusCode::SERVICE_UNAVAILABLE)?;
+    let given = headers
+        .get("x-webhook-token")
+        .and_then(|value| value.to_str().ok())
+        .or(query.token.as_deref())
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+
+    if expected.is_empty() || !token_matches(given, &expected) {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+    Ok(())
+}
+
+async fn apply_notice(db: &Database, notice: &EmailNotice) -> Result<bool, sqlx::Error> {
+    let address = notice.email.trim().to_lowercase();
+    if address.is_empty() {
+        return Ok(false);
+    }
+
+    let (event_type, contact_status) = match notice.r#type.to_lowercase().as_str() {
+        "bounce" | "bounced" => {
+            let soft = notice.bounce_type.as_deref().is_some_and(|t| t.eq_ignore_ascii_case("soft"));
+            ("bounce", if soft { None } else { Some("invalid") })
+        }
+        "complaint" | "spamreport" => ("complaint", Some("complained")),
+        "reply" | "inbound" => ("reply", None),
+        _ => return Ok(false),
+    };
+
+    if event_type == "reply" {
+        sequences::stop_for_reply(db, &address).await?;
+    }
+
+    let reason = notice
+        .reason
+        .as_deref()
+        .map(|r| r.chars().take(1000).collect::<String>());
+
+    // Soft bounces are recorded against the email but leave the address usable
+    if let Some(status) = contact_status {
+        sqlx::query(
+            r#"
+            UPDATE contacts SET
+                email_status = $2,
+                email_status_reason = $3,
+                email_status_at = NOW(),
+                do_not_contact = do_not_contact OR $2 = 'complained',
+                updated_at = NOW()
+            WHERE LOWER(email) = $1
+            "#,
+        )
+        .bind(&address)
+        .bind(status)
+        .bind(&reason)
+        .execute(db)
+        .await?;
+    }
+
+    // The notice refers to the given outbox email, or else the latest one sent to the address
+    let email = sqlx::query_as::<_, (Uuid, Option<Uuid>)>(
+        r#"
+        SELECT id, contact_id FROM email_outbox
+        WHERE LOWER(to_address) = $1 AND ($2::uuid IS NULL OR id = $2)
+        ORDER BY COALESCE(sent_at, created_at) DESC
+        LIMIT 1
+        "#,
+    )
+    .bind(&address)
+    .bind(notice.email_id)
+    .fetch_optional(db)
+    .await?;
+
+    if let Some((email_id, contact_id)) = email {
+        if event_type == "bounce" {
+            sqlx::query("UPDATE email_outbox SET status = 'bounced', error = COALESCE($2, error) WHERE id = $1")
+                .bind(email_id)
+                .bind(&reason)
+                .execute(db)
+                .await?;
+        }
+
+        sqlx::query(
+            "INSERT INTO email_events (email_id, contact_id, event_type, detail) VALUES ($1, $2, $3, $4)"
+        )
+        .bind(email_id)
+        .bind(contact_id)
+        .bind(event_type)
+        .bind(&reason)
+        .execute(db)
+        .await?;
+    }
+
+    Ok(true)
+}
+
+pub async fn email_provider_webhook(
+    State(db): State<Database>,
+    headers: HeaderMap,
+    Query(query): Query<WebhookQuery>,
+    Json(payload): Json<EmailNoticePayload>,
+) -> Result<Json<Value>, StatusCode> {
+    authorize(&headers, &query)?;
+
+    let notices = match payload {
+        EmailNoticePayload::Batch(notices) => notices,
+        EmailNoticePayload::Single(notice) => vec![notice],
+    };
+
+    let mut processed = 0;
+    for notice in &notices {
+        match apply_notice(&db, notice).await {
+            Ok(true) => processed += 1,
+            Ok(false) => {}
+            Err(e) => {
+                tracing::error!("Failed to apply email notice for {}: {}", notice.email, e);
+                return Err(StatusCode::INTERNAL_SERVER_ERROR);
+            }
+        }
+    }
+
+    Ok(Json(json!({ "received": notices.len(), "processed": processed })))
+}
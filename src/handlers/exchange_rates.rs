@@ -0,0 +1,111 @@
+use axum::{
+    extract::{Form, Path, Query, State},
+    http::StatusCode,
+    response::{Html, Redirect},
+};
+use askama::Template;
+use serde::Deserialize;
+
+use crate::{
+    database::Database,
+    handlers::team::create_audit_log,
+    middleware::AuthUser,
+    models::ExchangeRate,
+    services::exchange_rates,
+};
+
+#[derive(Template)]
+#[template(path = "team/exchange_rates.html")]
+struct ExchangeRatesTemplate {
+    base_currency: String,
+    rates: Vec<ExchangeRate>,
+    saved: Option<String>,
+    error: Option<String>,
+}
+
+impl ExchangeRatesTemplate {
+    fn was_saved(&self, currency: &str) -> bool {
+        self.saved.as_deref() == Some(currency)
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ExchangeRatesQuery {
+    saved: Option<String>,
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct RateForm {
+    // Blank clears the rate
+    rate: String,
+}
+
+pub async fn exchange_rates_page(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Query(query): Query<ExchangeRatesQuery>,
+) -> Result<Html<String>, StatusCode> {
+    if !current_user.has_manage_roles {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let load = async { Ok::<_, sqlx::Error>((exchange_rates::base_currency(&db).await?, exchange_rates::list(&db).await?)) };
+    let (base_currency, rates) = load.await.map_err(|e| {
+        tracing::error!("Error loading exchange rates: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let template = ExchangeRatesTemplate {
+        base_currency,
+        rates,
+        saved: query.saved,
+        error: query.error,
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+pub async fn update_exchange_rate(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(currency): Path<String>,
+    Form(form): Form<RateForm>,
+) -> Result<Redirect, StatusCode> {
+    if !current_user.has_manage_roles {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let rate = match form.rate.trim() {
+        "" => None,
+        rate => match rate.parse::<rust_decimal::Decimal>() {
+            Ok(rate) => Some(rate),
+            Err(_) => {
+                let error = format!("{} is not a number", rate);
+                return Ok(Redirect::to(&format!("/team/exchange-rates?error={}", urlencoding::encode(&error))));
+            }
+        },
+    };
+
+    let updated = exchange_rates::update(&db, &currency, rate, current_user.id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error updating exchange rate: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    if let Err(error) = updated {
+        return Ok(Redirect::to(&format!("/team/exchange-rates?error={}", urlencoding::encode(&error))));
+    }
+
+    let _ = create_audit_log(
+        &db,
+        &current_user,
+        "update".to_string(),
+        "exchange_rate".to_string(),
+        None,
+        Some(serde_json::json!({ "currency": currency })),
+        Some(serde_json::json!({ "rate": rate })),
+    )
+    .await;
+
+    Ok(Redirect::to(&format!("/team/exchange-rates?saved={}", currency)))
+}
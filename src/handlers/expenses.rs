@@ -6,16 +6,22 @@ use axum::{
 use axum_extra::extract::Multipart;
 use askama::Template;
 use serde::Deserialize;
-use tower_cookies::Cookies;
 use uuid::Uuid;
 use chrono::{NaiveDate, Utc};
-use std::path::PathBuf;
-use tokio::fs;
 
 use crate::{
     database::Database,
-    models::{Expense, ExpenseCategory, ExpenseDisplay, Customer, User},
-    middleware::{get_current_user, CurrentUser},
+    models::{CostCenter, Expense, ExpenseCategory, ExpenseDisplay, Customer, Project, User},
+    middleware::{AuthUser, CurrentUser},
+    services::{
+        audit_log::{self, audited_execute, Audited},
+        cost_centers,
+        periods::{self, PeriodContext, PeriodPicker},
+        projects, storage,
+        teams::{self, TeamFilter},
+    },
+    utils::empty_state::EmptyState,
+    filters,
 };
 
 // MODIFIED: This struct now accepts dates as optional strings.
@@ -29,23 +35,31 @@ pub struct ExpenseFilters {
     category_id: String,
     #[serde(default)]
     customer_id: String,
+    #[serde(default)]
+    cost_center_id: String,
+    period: Option<String>,
     date_from: Option<String>,
     date_to: Option<String>,
+    // "team" limits the list to the viewer's teams
+    show: Option<String>,
 }
 
 #[derive(Template)]
 #[template(path = "expenses/expenses.html")]
 struct ExpensesTemplate {
     expenses: Vec<ExpenseDisplay>,
+    empty: Option<EmptyState>,
     current_user: CurrentUser,
     users: Vec<User>,
     categories: Vec<ExpenseCategory>,
     customers: Vec<Customer>,
+    cost_centers: Vec<CostCenter>,
     selected_user: Option<Uuid>,
     selected_category: Option<Uuid>,
     selected_customer: Option<Uuid>,
-    selected_date_from: String,
-    selected_date_to: String,
+    selected_cost_center: Option<Uuid>,
+    period: PeriodPicker,
+    team_filter: TeamFilter,
 }
 
 #[derive(Template)]
@@ -54,19 +68,26 @@ struct ExpenseFormTemplate {
     expense: Option<Expense>,
     categories: Vec<ExpenseCategory>,
     customers: Vec<Customer>,
+    cost_centers: Vec<CostCenter>,
+    projects: Vec<Project>,
+}
+
+impl ExpenseFormTemplate {
+    fn is_project(&self, id: &Uuid) -> bool {
+        self.expense.as_ref().and_then(|expense| expense.project_id.as_ref()) == Some(id)
+    }
 }
 
 // MODIFIED: The logic inside this function is updated to handle the string-to-date parsing.
 pub async fn expenses_list(
     State(db): State<Database>,
-    cookies: Cookies,
+    AuthUser(current_user): AuthUser,
     Query(filters): Query<ExpenseFilters>,
 ) -> Result<Html<String>, StatusCode> {
-    let current_user = get_current_user(cookies, &db).await.ok_or(StatusCode::UNAUTHORIZED)?;
-
     let user_id = Uuid::parse_str(&filters.user_id).ok();
     let category_id = Uuid::parse_str(&filters.category_id).ok();
     let customer_id = Uuid::parse_str(&filters.customer_id).ok();
+    let cost_center_id = Uuid::parse_str(&filters.cost_center_id).ok();
 
     // Manually parse the date strings into Option<NaiveDate>.
     // This checks if the string is empty before attempting to parse it.
@@ -75,30 +96,53 @@ pub async fn expenses_list(
     let date_to = filters.date_to.as_deref()
         .and_then(|s| if s.is_empty() { None } else { NaiveDate::parse_from_str(s, "%Y-%m-%d").ok() });
 
+    // A period preset replaces whatever dates were typed
+    let ctx = PeriodContext::for_user(&db, &current_user).await.map_err(|e| {
+        tracing::error!("Error loading reporting settings: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    let (date_from, date_to) = match periods::resolve(filters.period.as_deref(), &ctx) {
+        Ok(Some((from, to))) => (Some(from), Some(to)),
+        _ => (date_from, date_to),
+    };
+
 
     let users = sqlx::query_as("SELECT * FROM users ORDER BY first_name, last_name").fetch_all(&db).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
     let categories = sqlx::query_as("SELECT * FROM expense_categories ORDER BY name").fetch_all(&db).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
     let customers = sqlx::query_as("SELECT * FROM customers ORDER BY company_name").fetch_all(&db).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let cost_centers = cost_centers::list(&db, true).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let team_filter = TeamFilter::load(&db, current_user.id, "/expenses", filters.show.as_deref())
+        .await
+        .map_err(|e| {
+            tracing::error!("Error loading teams: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    let viewer = format!("'{}'::uuid", current_user.id);
 
-    let mut query_builder = sqlx::QueryBuilder::new(
+    let mut query_builder = sqlx::QueryBuilder::new(format!(
         r#"
         SELECT
             e.id,
+            e.user_id,
             CONCAT(u.first_name, ' ', u.last_name) as user_name,
             ec.name as category_name,
+            cc.name as cost_center_name,
             c.company_name as customer_name,
             e.amount::text,
             COALESCE(e.description, '') as description,
             e.receipt_url,
             e.status,
             e.expense_date::text,
-            e.created_at
+            e.created_at,
+            {} as can_decide
         FROM expenses e
         JOIN users u ON e.user_id = u.id
         JOIN expense_categories ec ON e.category_id = ec.id
+        JOIN cost_centers cc ON e.cost_center_id = cc.id
         LEFT JOIN customers c ON e.customer_id = c.id
         "#,
-    );
+        teams::expense_approval_condition(&current_user.permissions, "e", &viewer)
+    ));
 
     let mut conditions = Vec::new();
 
@@ -111,6 +155,9 @@ pub async fn expenses_list(
     if let Some(id) = customer_id {
         conditions.push(format!("e.customer_id = '{}'", id));
     }
+    if let Some(id) = cost_center_id {
+        conditions.push(format!("e.cost_center_id = '{}'", id));
+    }
     // Now we use the parsed date_from and date_to variables
     if let Some(date) = date_from {
         conditions.push(format!("e.expense_date >= '{}'", date));
@@ -118,6 +165,7 @@ pub async fn expenses_list(
     if let Some(date) = date_to {
         conditions.push(format!("e.expense_date <= '{}'", date));
     }
+    conditions.extend(team_filter.condition("e.user_id", &viewer));
 
     if !conditions.is_empty() {
         query_builder.push(" WHERE ");
@@ -126,72 +174,114 @@ pub async fn expenses_list(
 
     query_builder.push(" ORDER BY e.expense_date DESC");
 
-    let expenses = query_builder.build_query_as::<ExpenseDisplay>()
+    let fields = current_user.field_access();
+    let filtered = !conditions.is_empty();
+    let expenses: Vec<ExpenseDisplay> = query_builder.build_query_as::<ExpenseDisplay>()
         .fetch_all(&db)
         .await
         .map_err(|e| {
-            eprintln!("Failed to fetch expenses: {}", e);
+            tracing::error!("Failed to fetch expenses: {}", e);
             StatusCode::INTERNAL_SERVER_ERROR
-        })?;
+        })?
+        .into_iter()
+        .map(|expense| expense.with_access(&fields))
+        .collect();
+
+    let empty = expenses
+        .is_empty()
+        .then(|| expenses_empty_state(&current_user, filtered, &cost_centers));
 
     let template = ExpensesTemplate {
         expenses,
+        empty,
         current_user,
         users,
         categories,
         customers,
+        cost_centers,
         selected_user: user_id,
         selected_category: category_id,
         selected_customer: customer_id,
-        // Pass the original string values back to the template.
-        // This ensures the form fields show what the user last entered.
-        selected_date_from: filters.date_from.unwrap_or_default(),
-        selected_date_to: filters.date_to.unwrap_or_default(),
+        selected_cost_center: cost_center_id,
+        period: PeriodPicker::new(filters.period.as_deref(), date_from, date_to),
+        team_filter,
     };
 
     Ok(Html(template.render().unwrap()))
 }
 
+fn expenses_empty_state(current_user: &CurrentUser, filtered: bool, cost_centers: &[CostCenter]) -> EmptyState {
+    if filtered {
+        return EmptyState::new("🔍", "No expenses match these filters", "Try a wider date range or fewer filters.")
+            .action("Clear Filters", "/expenses");
+    }
+
+    // Every expense is charged to a cost center, so there must be one to pick
+    if !cost_centers.iter().any(|cost_center| cost_center.is_active) {
+        let message = if current_user.has_manage_roles {
+            "Expenses are charged to a cost center. Set one up before the first expense comes in."
+        } else {
+            "Expenses are charged to a cost center, and none are open yet. Ask an administrator to set one up."
+        };
+        return EmptyState::new("🧾", "No expenses yet", message)
+            .action_if(current_user.has_manage_roles, "Set Up Cost Centers", "/expenses/cost-centers");
+    }
+
+    EmptyState::new("🧾", "No expenses yet", "Submit an expense with its receipt, or import your card transactions.")
+        .action("Add Expense", "/expenses/new")
+        .action_if(current_user.can("expenses:write"), "Import Card Transactions", "/imports?type=card_transactions")
+}
+
 pub async fn approve_expense(
     State(db): State<Database>,
-    cookies: Cookies,
+    AuthUser(current_user): AuthUser,
     Path(expense_id): Path<Uuid>,
 ) -> Result<Redirect, StatusCode> {
-    let current_user = get_current_user(cookies, &db).await.ok_or(StatusCode::UNAUTHORIZED)?;
-    if !current_user.has_expense_approval {
+    if !teams::approves_expenses(&current_user.permissions) {
         return Err(StatusCode::FORBIDDEN);
     }
 
-    sqlx::query(
-        "UPDATE expenses SET status = 'approved', approved_by = $1, approved_at = NOW() WHERE id = $2"
-    )
-    .bind(current_user.id)
-    .bind(expense_id)
-    .execute(&db)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    // Team managers only get as far as the people on their teams
+    let sql = format!(
+        "UPDATE expenses e SET status = 'approved', approved_by = $1, approved_at = NOW() WHERE e.id = $2 AND {}",
+        teams::expense_approval_condition(&current_user.permissions, "e", "$1")
+    );
+    let update = sqlx::query(&sql)
+        .bind(current_user.id)
+        .bind(expense_id);
+    let result = audited_execute(&db, &current_user, "approve", Audited::Expense, expense_id, update)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if result.rows_affected() == 0 {
+        return Err(StatusCode::FORBIDDEN);
+    }
 
     Ok(Redirect::to("/expenses"))
 }
 
 pub async fn deny_expense(
     State(db): State<Database>,
-    cookies: Cookies,
+    AuthUser(current_user): AuthUser,
     Path(expense_id): Path<Uuid>,
 ) -> Result<Redirect, StatusCode> {
-    let current_user = get_current_user(cookies, &db).await.ok_or(StatusCode::UNAUTHORIZED)?;
-    if !current_user.has_expense_approval {
+    if !teams::approves_expenses(&current_user.permissions) {
         return Err(StatusCode::FORBIDDEN);
     }
 
-    sqlx::query(
-        "UPDATE expenses SET status = 'denied', approved_by = $1, approved_at = NOW() WHERE id = $2"
-    )
-    .bind(current_user.id)
-    .bind(expense_id)
-    .execute(&db)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    // Team managers only get as far as the people on their teams
+    let sql = format!(
+        "UPDATE expenses e SET status = 'denied', approved_by = $1, approved_at = NOW() WHERE e.id = $2 AND {}",
+        teams::expense_approval_condition(&current_user.permissions, "e", "$1")
+    );
+    let update = sqlx::query(&sql)
+        .bind(current_user.id)
+        .bind(expense_id);
+    let result = audited_execute(&db, &current_user, "deny", Audited::Expense, expense_id, update)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if result.rows_affected() == 0 {
+        return Err(StatusCode::FORBIDDEN);
+    }
 
     Ok(Redirect::to("/expenses"))
 }
@@ -209,10 +299,20 @@ pub async fn expense_form(
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
+    let cost_centers = cost_centers::options(&db, None)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let projects = projects::options(&db, None)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
     let template = ExpenseFormTemplate {
         expense: None,
         categories,
         customers,
+        cost_centers,
+        projects,
     };
     Ok(Html(template.render().unwrap()))
 }
@@ -221,7 +321,7 @@ pub async fn expense_edit_form(
     State(db): State<Database>,
     Path(expense_id): Path<Uuid>,
 ) -> Result<Html<String>, StatusCode> {
-    let expense = sqlx::query_as("SELECT * FROM expenses WHERE id = $1")
+    let expense: Expense = sqlx::query_as("SELECT * FROM expenses WHERE id = $1")
         .bind(expense_id)
         .fetch_optional(&db)
         .await
@@ -238,10 +338,20 @@ pub async fn expense_edit_form(
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
+    let cost_centers = cost_centers::options(&db, Some(expense.cost_center_id))
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let projects = projects::options(&db, expense.project_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
     let template = ExpenseFormTemplate {
         expense: Some(expense),
         categories,
         customers,
+        cost_centers,
+        projects,
     };
 
     Ok(Html(template.render().unwrap()))
@@ -249,61 +359,92 @@ pub async fn expense_edit_form(
 
 pub async fn create_expense(
     State(db): State<Database>,
-    cookies: Cookies,
+    AuthUser(user): AuthUser,
     multipart: Multipart,
 ) -> Result<Redirect, StatusCode> {
-    let user = get_current_user(cookies, &db).await.ok_or(StatusCode::UNAUTHORIZED)?;
     let (form_data, receipt_data) = parse_expense_multipart(multipart).await?;
 
-    let (category_id, amount, expense_date) = match (
+    let (category_id, cost_center_id, amount, expense_date) = match (
         form_data.category_id,
+        form_data.cost_center_id,
         form_data.amount,
         form_data.expense_date,
     ) {
-        (Some(c), Some(a), Some(d)) => (c, a, d),
+        (Some(c), Some(cc), Some(a), Some(d)) => (c, cc, a, d),
         _ => return Err(StatusCode::BAD_REQUEST),
     };
+    if !cost_centers::is_usable(&db, cost_center_id, None).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)? {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    if let Some(project_id) = form_data.project_id {
+        if !projects::is_usable(&db, project_id, None).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)? {
+            return Err(StatusCode::BAD_REQUEST);
+        }
+    }
     
     let receipt_url = save_receipt(receipt_data).await?;
 
-    sqlx::query(
-        "INSERT INTO expenses (user_id, category_id, customer_id, amount, description, expense_date, receipt_url) VALUES ($1, $2, $3, $4, $5, $6, $7)"
+    let expense_id = sqlx::query_scalar::<_, Uuid>(
+        "INSERT INTO expenses (user_id, category_id, cost_center_id, customer_id, amount, description, expense_date, receipt_url, project_id) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9) RETURNING id"
     )
     .bind(user.id)
     .bind(category_id)
+    .bind(cost_center_id)
     .bind(form_data.customer_id)
     .bind(amount)
     .bind(form_data.description)
     .bind(expense_date)
     .bind(receipt_url)
-    .execute(&db)
+    .bind(form_data.project_id)
+    .fetch_one(&db)
     .await
     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
+    audit_log::record(&db, &user, "create", Audited::Expense, expense_id, None).await;
+
     Ok(Redirect::to("/expenses"))
 }
 
 pub async fn update_expense(
     State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
     Path(expense_id): Path<Uuid>,
     multipart: Multipart,
 ) -> Result<Redirect, StatusCode> {
     let (form_data, receipt_data) = parse_expense_multipart(multipart).await?;
     
-    let (category_id, amount, expense_date) = match (
+    let (category_id, cost_center_id, amount, expense_date) = match (
         form_data.category_id,
+        form_data.cost_center_id,
         form_data.amount,
         form_data.expense_date,
     ) {
-        (Some(c), Some(a), Some(d)) => (c, a, d),
+        (Some(c), Some(cc), Some(a), Some(d)) => (c, cc, a, d),
         _ => return Err(StatusCode::BAD_REQUEST),
     };
+
+    // An expense can stay on a cost center that has since been retired, or
+    // a project that has since closed
+    let (current_cost_center, current_project) = sqlx::query_as::<_, (Uuid, Option<Uuid>)>("SELECT cost_center_id, project_id FROM expenses WHERE id = $1")
+        .bind(expense_id)
+        .fetch_optional(&db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    if !cost_centers::is_usable(&db, cost_center_id, Some(current_cost_center)).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)? {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    if let Some(project_id) = form_data.project_id {
+        if !projects::is_usable(&db, project_id, current_project).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)? {
+            return Err(StatusCode::BAD_REQUEST);
+        }
+    }
     
     let receipt_url = save_receipt(receipt_data).await?;
 
-    if receipt_url.is_some() {
+    let update = if receipt_url.is_some() {
         sqlx::query(
-            "UPDATE expenses SET category_id = $1, customer_id = $2, amount = $3, description = $4, expense_date = $5, receipt_url = $6, updated_at = NOW() WHERE id = $7"
+            "UPDATE expenses SET category_id = $1, customer_id = $2, amount = $3, description = $4, expense_date = $5, receipt_url = $6, cost_center_id = $7, project_id = $8, updated_at = NOW() WHERE id = $9"
         )
         .bind(category_id)
         .bind(form_data.customer_id)
@@ -311,35 +452,36 @@ pub async fn update_expense(
         .bind(form_data.description)
         .bind(expense_date)
         .bind(receipt_url)
+        .bind(cost_center_id)
+        .bind(form_data.project_id)
         .bind(expense_id)
-        .execute(&db)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
     } else {
         sqlx::query(
-            "UPDATE expenses SET category_id = $1, customer_id = $2, amount = $3, description = $4, expense_date = $5, updated_at = NOW() WHERE id = $6"
+            "UPDATE expenses SET category_id = $1, customer_id = $2, amount = $3, description = $4, expense_date = $5, cost_center_id = $6, project_id = $7, updated_at = NOW() WHERE id = $8"
         )
         .bind(category_id)
         .bind(form_data.customer_id)
         .bind(amount)
         .bind(form_data.description)
         .bind(expense_date)
+        .bind(cost_center_id)
+        .bind(form_data.project_id)
         .bind(expense_id)
-        .execute(&db)
+    };
+    audited_execute(&db, &current_user, "update", Audited::Expense, expense_id, update)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-    }
 
     Ok(Redirect::to("/expenses"))
 }
 
 pub async fn delete_expense(
     State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
     Path(expense_id): Path<Uuid>,
 ) -> Result<Redirect, StatusCode> {
-    sqlx::query("DELETE FROM expenses WHERE id = $1")
-        .bind(expense_id)
-        .execute(&db)
+    let delete = sqlx::query("DELETE FROM expenses WHERE id = $1").bind(expense_id);
+    audited_execute(&db, &current_user, "delete", Audited::Expense, expense_id, delete)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
@@ -348,7 +490,9 @@ pub async fn delete_expense(
 
 struct ExpenseFormData {
     category_id: Option<Uuid>,
+    cost_center_id: Option<Uuid>,
     customer_id: Option<Uuid>,
+    project_id: Option<Uuid>,
     amount: Option<rust_decimal::Decimal>,
     description: Option<String>,
     expense_date: Option<NaiveDate>,
@@ -362,7 +506,9 @@ struct ReceiptData {
 async fn parse_expense_multipart(mut multipart: Multipart) -> Result<(ExpenseFormData, Option<ReceiptData>), StatusCode> {
     let mut form_data = ExpenseFormData {
         category_id: None,
+        cost_center_id: None,
         customer_id: None,
+        project_id: None,
         amount: None,
         description: None,
         expense_date: None,
@@ -388,7 +534,9 @@ async fn parse_expense_multipart(mut multipart: Multipart) -> Result<(ExpenseFor
             if !text_value.is_empty() {
                 match name.as_str() {
                     "category_id" => form_data.category_id = Uuid::parse_str(&text_value).ok(),
+                    "cost_center_id" => form_data.cost_center_id = Uuid::parse_str(&text_value).ok(),
                     "customer_id" => form_data.customer_id = Uuid::parse_str(&text_value).ok(),
+                    "project_id" => form_data.project_id = Uuid::parse_str(&text_value).ok(),
                     "amount" => form_data.amount = rust_decimal::Decimal::from_str_radix(&text_value, 10).ok(),
                     "description" => form_data.description = Some(text_value),
                     "expense_date" => form_data.expense_date = NaiveDate::parse_from_str(&text_value, "%Y-%m-%d").ok(),
@@ -403,17 +551,9 @@ async fn parse_expense_multipart(mut multipart: Multipart) -> Result<(ExpenseFor
 async fn save_receipt(receipt_data: Option<ReceiptData>) -> Result<Option<String>, StatusCode> {
     if let Some(receipt) = receipt_data {
         if let Some(fname) = receipt.filename {
-            let receipts_dir = PathBuf::from("static/receipts");
-            if !receipts_dir.exists() {
-                fs::create_dir_all(&receipts_dir).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-            }
-            let extension = PathBuf::from(&fname).extension().and_then(|s| s.to_str()).unwrap_or("").to_lowercase();
-            if ["png", "jpg", "jpeg"].contains(&extension.as_str()) {
-                let new_file_name = format!("{}.{}", Uuid::new_v4(), extension);
-                let file_path = receipts_dir.join(&new_file_name);
-                fs::write(&file_path, &receipt.data).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-                return Ok(Some(format!("/static/receipts/{}", new_file_name)));
-            }
+            return storage::save_image("receipts", &fname, &receipt.data)
+                .await
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR);
         }
     }
     Ok(None)
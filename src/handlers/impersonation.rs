@@ -0,0 +1,147 @@
+use axum::{
+    extract::{Path, State},
+    http::StatusCode,
+    response::{Html, Redirect},
+};
+use askama::Template;
+use chrono::{Duration, Utc};
+use tower_cookies::{Cookie, Cookies};
+use uuid::Uuid;
+
+use crate::{
+    database::Database,
+    handlers::team::create_audit_log,
+    middleware::{permission::get_user_by_id, AuthUser, CurrentUser},
+    services::{security, sessions},
+    utils::create_token,
+};
+
+// Impersonation sessions end on their own after this long
+const IMPERSONATION_MINUTES: i64 = 60;
+
+#[derive(Template)]
+#[template(path = "impersonation_banner.html")]
+struct BannerTemplate {
+    current_user: CurrentUser,
+}
+
+// The session id ties the start and end entries of one impersonation together
+async fn log_impersonation(db: &Database, actor: &CurrentUser, action: &str, user_id: Uuid, session_id: Uuid) {
+    if let Err(e) = create_audit_log(
+        db,
+        actor,
+        action.to_string(),
+        "user".to_string(),
+        Some(user_id),
+        None,
+        Some(serde_json::json!({ "session_id": session_id })),
+    )
+    .await
+    {
+        tracing::error!("Error auditing {} of {}: {}", action, user_id, e);
+    }
+}
+
+// Sign in as another user to see what they see. The administrator's own
+// session stays open and is resumed by end_impersonation.
+pub async fn impersonate(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    cookies: Cookies,
+    Path(user_id): Path<Uuid>,
+) -> Result<Redirect, StatusCode> {
+    if !current_user.has_manage_roles || current_user.impersonator.is_some() {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    if user_id == current_user.id {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    // Inactive and locked users can't be signed in as
+    let target = get_user_by_id(&db, user_id).await.ok_or(StatusCode::NOT_FOUND)?;
+    // Nor can other administrators, so this can't be used to borrow their rights
+    if target.has_manage_roles {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let return_session_id = sessions::from_cookies(&cookies).ok_or(StatusCode::UNAUTHORIZED)?;
+    let client = &current_user.client;
+
+    let expires_at = Utc::now() + Duration::minutes(IMPERSONATION_MINUTES);
+    let session_id = sessions::start_impersonation(
+        &db,
+        target.id,
+        current_user.id,
+        return_session_id,
+        expires_at,
+        client.ip_address.as_deref(),
+        client.user_agent.as_deref(),
+    )
+    .await
+    .map_err(|e| {
+        tracing::error!("Error starting impersonation of {}: {}", target.id, e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let token = create_token(target.id, target.email.clone(), session_id, expires_at)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    log_impersonation(&db, &current_user, "impersonate", target.id, session_id).await;
+    if let Err(e) = security::record_change(&db, target.id, "impersonated", "Signed in by an administrator", current_user.id).await {
+        tracing::error!("Error recording impersonation of {}: {}", target.id, e);
+    }
+
+    sessions::set_cookie(&cookies, token, expires_at, None);
+    Ok(Redirect::to("/dashboard"))
+}
+
+// Close the impersonation session and go back to the administrator's own
+pub async fn end_impersonation(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    cookies: Cookies,
+) -> Result<Redirect, StatusCode> {
+    let session_id = sessions::from_cookies(&cookies).ok_or(StatusCode::UNAUTHORIZED)?;
+    let owner = sessions::touch(&db, session_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+
+    let (Some(admin_id), Some(return_session_id)) = (owner.impersonator_id, owner.return_session_id) else {
+        return Err(StatusCode::BAD_REQUEST);
+    };
+
+    if let Err(e) = sessions::remove(&db, session_id).await {
+        tracing::error!("Error ending impersonation session {}: {}", session_id, e);
+    }
+    // Logged as the impersonated user, with the administrator as impersonator
+    log_impersonation(&db, &current_user, "end_impersonation", owner.user_id, session_id).await;
+
+    // The administrator's session may have lapsed in the meantime
+    let resumed = match sessions::touch(&db, return_session_id).await {
+        Ok(Some(session)) if session.user_id == admin_id => {
+            get_user_by_id(&db, admin_id).await.map(|admin| (admin, session))
+        }
+        _ => None,
+    };
+    let Some((admin, session)) = resumed else {
+        cookies.remove(Cookie::from("auth_token"));
+        return Ok(Redirect::to("/login"));
+    };
+
+    let token = create_token(admin.id, admin.email.clone(), return_session_id, session.expires_at)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    sessions::set_cookie(&cookies, token, session.expires_at, session.idle_minutes);
+
+    Ok(Redirect::to(&format!("/team/users/{}/edit", owner.user_id)))
+}
+
+// Loaded into every page by base.html; empty unless impersonating
+pub async fn banner(user: Option<AuthUser>) -> Html<String> {
+    match user {
+        Some(AuthUser(current_user)) if current_user.impersonator.is_some() => {
+            Html(BannerTemplate { current_user }.render().unwrap())
+        }
+        _ => Html(String::new()),
+    }
+}
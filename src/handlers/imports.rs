@@ -0,0 +1,425 @@
+use axum::{
+    extract::{Form, Path, Query, State},
+    http::{header, StatusCode},
+    response::{Html, IntoResponse, Redirect, Response},
+};
+use axum_extra::extract::Multipart;
+use askama::Template;
+use serde::Deserialize;
+use std::collections::HashMap;
+use uuid::Uuid;
+
+use crate::{
+    database::Database,
+    middleware::{AuthUser, CurrentUser},
+    models::{Import, IMPORT_SELECT},
+    services::imports::{self, Duplicates, ImportType, PreviewRow, StagedImport},
+};
+
+// Uploads are read into memory and kept until the import has run
+const MAX_FILE_BYTES: usize = 5 * 1024 * 1024;
+
+#[derive(Template)]
+#[template(path = "imports/list.html")]
+struct ImportsTemplate {
+    imports: Vec<Import>,
+    types: Vec<ImportType>,
+    selected_type: String,
+    error: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "imports/detail.html")]
+struct ImportDetailTemplate {
+    import: Import,
+    type_label: String,
+    can_view_job: bool,
+}
+
+#[derive(Template)]
+#[template(path = "team/user_import.html")]
+struct UserImportTemplate {
+    types: Vec<ImportType>,
+    role_names: Vec<String>,
+    selected_type: String,
+    error: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "crm/customer_import.html")]
+struct CustomerImportTemplate {
+    error: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "crm/customer_import_map.html")]
+struct CustomerMappingTemplate {
+    staged: StagedImport,
+    fields: Vec<(&'static str, String)>,
+    required: &'static [&'static str],
+    duplicate_modes: [Duplicates; 3],
+    preview_columns: Vec<String>,
+    preview: Vec<PreviewRow>,
+    error: Option<String>,
+}
+
+impl CustomerMappingTemplate {
+    fn is_mapped(&self, column: &usize, field: &str) -> bool {
+        self.staged.mapping.get(*column).is_some_and(|mapped| mapped == field)
+    }
+}
+
+#[derive(Deserialize)]
+pub struct MappingQuery {
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ImportQuery {
+    #[serde(rename = "type")]
+    import_type: Option<String>,
+}
+
+fn allowed_types(current_user: &CurrentUser) -> Vec<ImportType> {
+    ImportType::ALL
+        .into_iter()
+        .filter(|kind| current_user.permissions.iter().any(|p| p == kind.permission()))
+        .collect()
+}
+
+fn type_label(import: &Import) -> String {
+    ImportType::parse(&import.import_type)
+        .map(|kind| kind.label().to_string())
+        .unwrap_or_else(|| import.import_type.clone())
+}
+
+async fn render_list(
+    db: &Database,
+    current_user: &CurrentUser,
+    selected_type: String,
+    error: Option<String>,
+) -> Result<Html<String>, StatusCode> {
+    // Admins see every import, everyone else their own
+    let imports = sqlx::query_as::<_, Import>(&format!(
+        "{} WHERE ($1 OR i.created_by = $2) ORDER BY i.created_at DESC LIMIT 50",
+        IMPORT_SELECT
+    ))
+    .bind(current_user.has_manage_roles)
+    .bind(current_user.id)
+    .fetch_all(db)
+    .await
+    .map_err(|e| {
+        tracing::error!("Error loading imports: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let template = ImportsTemplate {
+        imports,
+        types: allowed_types(current_user),
+        selected_type,
+        error,
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+async fn load_import(db: &Database, current_user: &CurrentUser, id: Uuid) -> Result<Import, StatusCode> {
+    let import = sqlx::query_as::<_, Import>(&format!("{} WHERE i.id = $1", IMPORT_SELECT))
+        .bind(id)
+        .fetch_optional(db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    if import.created_by != Some(current_user.id) && !current_user.has_manage_roles {
+        return Err(StatusCode::NOT_FOUND);
+    }
+    Ok(import)
+}
+
+pub async fn imports_list(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Query(query): Query<ImportQuery>,
+) -> Result<Html<String>, StatusCode> {
+    render_list(&db, &current_user, query.import_type.unwrap_or_default(), None).await
+}
+
+// Read an upload form and queue its file, or stage it to have its columns
+// mapped first. The outcome is the import, or a message for the form when the
+// file itself can't be used.
+async fn start_upload(
+    db: &Database,
+    current_user: &CurrentUser,
+    mut multipart: Multipart,
+    stage: bool,
+) -> Result<(String, Result<Uuid, String>), StatusCode> {
+    let mut import_type = String::new();
+    let mut file: Option<(String, Vec<u8>)> = None;
+
+    while let Some(field) = multipart.next_field().await.map_err(|_| StatusCode::BAD_REQUEST)? {
+        match field.name() {
+            Some("import_type") => {
+                import_type = field.text().await.map_err(|_| StatusCode::BAD_REQUEST)?;
+            }
+            Some("file") => {
+                let file_name = field.file_name().unwrap_or("upload.csv").to_string();
+                let data = field.bytes().await.map_err(|_| StatusCode::BAD_REQUEST)?;
+                if !data.is_empty() {
+                    file = Some((file_name, data.to_vec()));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let kind = ImportType::parse(&import_type).ok_or(StatusCode::BAD_REQUEST)?;
+    if !current_user.permissions.iter().any(|p| p == kind.permission()) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let checked = match file {
+        None => Err("Choose a CSV file to import.".to_string()),
+        Some((_, data)) if data.len() > MAX_FILE_BYTES => {
+            Err(format!("The file is too large. Split it into files under {} MB.", MAX_FILE_BYTES / 1024 / 1024))
+        }
+        Some((file_name, data)) => String::from_utf8(data)
+            .map(|content| (file_name, content.trim_start_matches('\u{feff}').to_string()))
+            .map_err(|_| "The file must be UTF-8 encoded CSV.".to_string()),
+    };
+
+    let started = match checked {
+        Ok((file_name, content)) => {
+            let started = if stage {
+                imports::stage(db, kind, &file_name, content, current_user.id).await
+            } else {
+                imports::start(db, kind, &file_name, content, current_user.id).await
+            };
+            started.map_err(|e| {
+                tracing::error!("Error starting import: {}", e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?
+        }
+        Err(message) => Err(message),
+    };
+
+    Ok((import_type, started))
+}
+
+pub async fn create_import(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    multipart: Multipart,
+) -> Result<Response, StatusCode> {
+    match start_upload(&db, &current_user, multipart, false).await? {
+        (_, Ok(id)) => Ok(Redirect::to(&format!("/imports/{}", id)).into_response()),
+        (import_type, Err(message)) => Ok(render_list(&db, &current_user, import_type, Some(message)).await?.into_response()),
+    }
+}
+
+async fn render_user_import(db: &Database, selected_type: String, error: Option<String>) -> Result<Html<String>, StatusCode> {
+    let role_names = sqlx::query_scalar::<_, String>("SELECT name FROM roles WHERE is_active = true ORDER BY name")
+        .fetch_all(db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let template = UserImportTemplate {
+        types: vec![ImportType::UserInvitations, ImportType::Users],
+        role_names,
+        selected_type,
+        error,
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+// Rolling out to a whole team at once: the same background import, with the
+// choice between inviting people and creating their accounts up front
+pub async fn user_import_page(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+) -> Result<Html<String>, StatusCode> {
+    if !current_user.has_team_write {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    render_user_import(&db, ImportType::UserInvitations.key().to_string(), None).await
+}
+
+pub async fn create_user_import(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    multipart: Multipart,
+) -> Result<Response, StatusCode> {
+    if !current_user.has_team_write {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    match start_upload(&db, &current_user, multipart, false).await? {
+        (_, Ok(id)) => Ok(Redirect::to(&format!("/imports/{}", id)).into_response()),
+        (import_type, Err(message)) => Ok(render_user_import(&db, import_type, Some(message)).await?.into_response()),
+    }
+}
+
+pub async fn import_detail(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<Response, StatusCode> {
+    let import = load_import(&db, &current_user, id).await?;
+    if import.is_staged() {
+        return Ok(Redirect::to(&format!("/crm/customers/import/{}", id)).into_response());
+    }
+
+    let template = ImportDetailTemplate {
+        type_label: type_label(&import),
+        import,
+        can_view_job: current_user.has_manage_roles,
+    };
+    Ok(Html(template.render().unwrap()).into_response())
+}
+
+// "Company name" for company_name
+fn field_label(field: &str) -> String {
+    let label = field.replace('_', " ");
+    let mut chars = label.chars();
+    chars
+        .next()
+        .map(|first| first.to_uppercase().chain(chars).collect())
+        .unwrap_or_default()
+}
+
+// Customer imports from another CRM or a spreadsheet: the file is staged,
+// its columns mapped to fields and a preview checked before anything is
+// written
+pub async fn customer_import_page(AuthUser(current_user): AuthUser) -> Result<Html<String>, StatusCode> {
+    current_user.require(ImportType::Customers.permission())?;
+
+    let template = CustomerImportTemplate { error: None };
+    Ok(Html(template.render().unwrap()))
+}
+
+pub async fn upload_customer_import(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    multipart: Multipart,
+) -> Result<Response, StatusCode> {
+    current_user.require(ImportType::Customers.permission())?;
+
+    match start_upload(&db, &current_user, multipart, true).await? {
+        (_, Ok(id)) => Ok(Redirect::to(&format!("/crm/customers/import/{}", id)).into_response()),
+        (_, Err(message)) => {
+            let template = CustomerImportTemplate { error: Some(message) };
+            Ok(Html(template.render().unwrap()).into_response())
+        }
+    }
+}
+
+// The staged customer import behind a mapping page. Once it has started, the
+// mapping page gives way to the import's own page.
+async fn load_customer_staged(db: &Database, current_user: &CurrentUser, id: Uuid) -> Result<Result<StagedImport, Redirect>, StatusCode> {
+    current_user.require(ImportType::Customers.permission())?;
+    let import = load_import(db, current_user, id).await?;
+    let staged = imports::load_staged(db, import.id).await.map_err(|e| {
+        tracing::error!("Error loading staged import: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    Ok(match staged {
+        Some(staged) if staged.kind == ImportType::Customers => Ok(staged),
+        _ => Err(Redirect::to(&format!("/imports/{}", id))),
+    })
+}
+
+pub async fn customer_import_mapping(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+    Query(query): Query<MappingQuery>,
+) -> Result<Response, StatusCode> {
+    let staged = match load_customer_staged(&db, &current_user, id).await? {
+        Ok(staged) => staged,
+        Err(redirect) => return Ok(redirect.into_response()),
+    };
+
+    let preview = imports::preview(&db, &current_user, &staged).await.map_err(|e| {
+        tracing::error!("Error previewing import: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    let preview_columns = staged
+        .mapping
+        .iter()
+        .filter(|field| !field.is_empty())
+        .map(|field| field_label(field))
+        .collect();
+
+    let template = CustomerMappingTemplate {
+        fields: ImportType::Customers.fields().map(|field| (field, field_label(field))).collect(),
+        required: ImportType::Customers.required_columns(),
+        duplicate_modes: Duplicates::ALL,
+        preview_columns,
+        preview,
+        staged,
+        error: query.error,
+    };
+    Ok(Html(template.render().unwrap()).into_response())
+}
+
+// Saves the mapping, then either shows its preview again or, for "import",
+// queues the file
+pub async fn save_customer_import(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+    Form(form): Form<HashMap<String, String>>,
+) -> Result<Redirect, StatusCode> {
+    let mut staged = match load_customer_staged(&db, &current_user, id).await? {
+        Ok(staged) => staged,
+        Err(redirect) => return Ok(redirect),
+    };
+    let mapping_url = format!("/crm/customers/import/{}", id);
+    let failed = |message: String| Redirect::to(&format!("{}?error={}", mapping_url, urlencoding::encode(&message)));
+
+    let mapping = (0..staged.headers.len())
+        .map(|column| form.get(&format!("column_{}", column)).cloned().unwrap_or_default())
+        .collect();
+    let duplicates = form
+        .get("duplicates")
+        .and_then(|mode| Duplicates::parse(mode))
+        .ok_or(StatusCode::BAD_REQUEST)?;
+    let db_error = |e: sqlx::Error| {
+        tracing::error!("Error saving import mapping: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    };
+
+    if let Err(message) = imports::save_mapping(&db, &mut staged, mapping, duplicates).await.map_err(db_error)? {
+        return Ok(failed(message));
+    }
+    if form.get("action").map(String::as_str) != Some("import") {
+        return Ok(Redirect::to(&mapping_url));
+    }
+    match imports::confirm(&db, &staged, current_user.id).await.map_err(db_error)? {
+        Ok(()) => Ok(Redirect::to(&format!("/imports/{}", id))),
+        Err(message) => Ok(failed(message)),
+    }
+}
+
+pub async fn import_errors_csv(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<Response, StatusCode> {
+    let import = load_import(&db, &current_user, id).await?;
+
+    let bytes = imports::error_report(&db, import.id).await.map_err(|e| {
+        tracing::error!("Error building import error report: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let stem = import.file_name.trim_end_matches(".csv").replace(['"', '\\'], "");
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+            (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}-errors.csv\"", stem)),
+        ],
+        bytes,
+    )
+        .into_response())
+}
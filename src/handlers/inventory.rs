@@ -1,10 +1,9 @@
 use axum::{
-    extract::{Form, State},
+    extract::{Form, Path, Query, State},
     http::StatusCode,
-    response::{Html, Redirect},
+    response::{Html, IntoResponse, Redirect, Response},
 };
 use askama::Template;
-use tower_cookies::Cookies;
 use uuid::Uuid;
 use serde::Deserialize;
 use rust_decimal::Decimal;
@@ -13,23 +12,138 @@ use std::str::FromStr;
 
 use crate::{
     database::Database,
-    models::{InventoryItem},
-    middleware::{get_current_user, CurrentUser},
+    handlers::{crm::{parse_optional_id, require_access}, team::create_audit_log},
+    models::{Backorder, Customer, HeldStock, InventoryItem, ItemCommitment, ItemStock, Warehouse, WarehouseStock},
+    middleware::{AuthUser, CurrentUser},
+    services::{
+        audit_log::{self, Audited},
+        backorders,
+        blanket_orders,
+        held_stock::{self, Owner},
+        locations,
+        sharing::{self, Access, RecordKind},
+        stock,
+        variants::{self, StockMatrix},
+    },
+    utils::{
+        barcode::{normalize_gtin, normalize_sku},
+        empty_state::EmptyState,
+    },
     filters,
 };
 
 #[derive(Template)]
 #[template(path = "inventory/items.html")]
 struct ItemsTemplate<'a> {
-    items: Vec<InventoryItem>,
+    items: Vec<ItemRow>,
+    empty: Option<EmptyState>,
     current_user: &'a CurrentUser,
 }
 
+// An item on the list; variants are counted on their parent's row rather than listed
+struct ItemRow {
+    item: InventoryItem,
+    variant_count: i64,
+    stock: Option<ItemStock>,
+}
+
+#[derive(Template)]
+#[template(path = "inventory/item_detail.html")]
+struct ItemDetailTemplate<'a> {
+    item: InventoryItem,
+    parent: Option<InventoryItem>,
+    stock: Vec<WarehouseStock>,
+    variants: Vec<InventoryItem>,
+    variant_stock: Vec<ItemStock>,
+    matrix: Option<StockMatrix>,
+    // Still owed to customers on open blanket orders
+    commitments: Vec<ItemCommitment>,
+    // Consigned and customer-owned stock, kept out of the totals above
+    held_stock: Vec<HeldStock>,
+    // Where stock can be received or transferred; empty when the viewer can't
+    warehouses: Vec<Warehouse>,
+    // Where received stock can be put away, suggested locations first
+    location_options: Vec<LocationOption>,
+    // Who customer-owned goods can be received for
+    customers: Vec<Customer>,
+    option_text: String,
+    notice: Option<String>,
+    error: Option<String>,
+    stock_error: Option<String>,
+    current_user: &'a CurrentUser,
+}
+
+struct LocationOption {
+    id: Uuid,
+    label: String,
+}
+
+impl ItemDetailTemplate<'_> {
+    fn variant_stock(&self, variant_id: &Uuid) -> Option<&ItemStock> {
+        self.variant_stock.iter().find(|s| s.item_id == *variant_id)
+    }
+}
+
+#[derive(Deserialize)]
+pub struct VariantsForm {
+    option_sets: String,
+}
+
+#[derive(Deserialize)]
+pub struct ItemDetailQuery {
+    created: Option<usize>,
+    // Set after stock is received or transferred: how much went to backorders
+    allocated: Option<i32>,
+    // and where received stock was put away
+    putaway: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ReceiveStockForm {
+    warehouse_id: Uuid,
+    quantity: i32,
+    // The purchase order being received
+    reference: Option<String>,
+    // Blank takes the first suggestion
+    location_id: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct HeldStockForm {
+    // consignment or customer_owned
+    ownership: String,
+    warehouse_id: Uuid,
+    quantity: i32,
+    consignor: Option<String>,
+    customer_id: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ReleaseHeldStockForm {
+    quantity: i32,
+    // return, or take for a consignment we're buying
+    action: String,
+}
+
+#[derive(Deserialize)]
+pub struct TransferStockForm {
+    from_warehouse_id: Uuid,
+    to_warehouse_id: Uuid,
+    quantity: i32,
+}
+
+#[derive(Template)]
+#[template(path = "inventory/backorders.html")]
+struct BackordersTemplate {
+    backorders: Vec<Backorder>,
+}
+
 #[derive(Template)]
 #[template(path = "inventory/item_form.html")]
 struct ItemFormTemplate<'a> {
     item: Option<InventoryItem>,
     current_user: &'a CurrentUser,
+    error: Option<String>,
 }
 
 // This struct now includes all the fields from your form
@@ -58,66 +172,179 @@ pub struct ItemForm {
 // Handler to display the list of inventory items
 pub async fn items_list(
     State(db): State<Database>,
-    cookies: Cookies,
+    AuthUser(current_user): AuthUser,
 ) -> Result<Html<String>, StatusCode> {
-    let current_user = get_current_user(cookies, &db).await
-        .ok_or(StatusCode::UNAUTHORIZED)?;
+    current_user.require("inventory:read")?;
 
-    if !current_user.permissions.contains(&"inventory:read".to_string()) {
-        return Err(StatusCode::FORBIDDEN);
-    }
+    let fields = current_user.field_access();
+    let items: Vec<InventoryItem> = sqlx::query_as::<_, InventoryItem>(
+        "SELECT * FROM inventory_items WHERE parent_item_id IS NULL ORDER BY item_name",
+    )
+    .fetch_all(&db)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    .into_iter()
+    .map(|item| item.with_access(&fields))
+    .collect();
 
-    let items = sqlx::query_as::<_, InventoryItem>("SELECT * FROM inventory_items ORDER BY item_name")
-        .fetch_all(&db)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let ids: Vec<Uuid> = items.iter().map(|item| item.id).collect();
+    let mut stock = variants::stock(&db, &current_user, &ids).await.map_err(|e| {
+        tracing::error!("Error loading stock: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    let variant_counts = sqlx::query_as::<_, (Uuid, i64)>(
+        "SELECT parent_item_id, COUNT(*) FROM inventory_items WHERE parent_item_id IS NOT NULL GROUP BY parent_item_id",
+    )
+    .fetch_all(&db)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
-    let template = ItemsTemplate { items, current_user: &current_user };
+    let items: Vec<ItemRow> = items
+        .into_iter()
+        .map(|item| {
+            let variant_count = variant_counts.iter().find(|(id, _)| *id == item.id).map_or(0, |(_, n)| *n);
+            let stock = stock.iter().position(|s| s.item_id == item.id).map(|i| stock.swap_remove(i));
+            ItemRow { item, variant_count, stock }
+        })
+        .collect();
+
+    let can_write = current_user.can("inventory:write");
+    let empty = items.is_empty().then(|| {
+        let message = if can_write {
+            "Start by adding your first inventory item, or import your catalog from a spreadsheet."
+        } else {
+            "Items will be listed here once someone adds them to the catalog."
+        };
+        EmptyState::new("📦", "No items yet", message)
+            .action_if(can_write, "Add First Item", "/inventory/items/new")
+            .action_if(can_write, "Import CSV", "/imports?type=inventory")
+    });
+
+    let template = ItemsTemplate { items, empty, current_user: &current_user };
     Ok(Html(template.render().unwrap()))
 }
 
 // Handler to show the form for creating a new item
 pub async fn item_form(
-    cookies: Cookies,
-    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
 ) -> Result<Html<String>, StatusCode> {
-    let current_user = get_current_user(cookies, &db).await
-        .ok_or(StatusCode::UNAUTHORIZED)?;
+    current_user.require("inventory:write")?;
+
+    let template = ItemFormTemplate { item: None, current_user: &current_user, error: None };
+    Ok(Html(template.render().unwrap()))
+}
 
-    if !current_user.permissions.contains(&"inventory:write".to_string()) {
-        return Err(StatusCode::FORBIDDEN);
+// Helper to parse string to Option<Decimal>
+fn parse_decimal(s: &Option<String>) -> Option<Decimal> {
+    s.as_deref().and_then(|val| Decimal::from_str(val).ok())
+}
+
+// Helper to parse string to Option<i32>
+fn parse_i32(s: &Option<String>) -> Option<i32> {
+    s.as_deref().and_then(|val| val.parse::<i32>().ok())
+}
+
+impl ItemForm {
+    // What was submitted, shaped like a saved item so the form can show it again
+    fn draft(&self, current_user: &CurrentUser) -> InventoryItem {
+        let now = chrono::Utc::now();
+        InventoryItem {
+            id: Uuid::nil(),
+            item_name: self.item_name.clone(),
+            sku: self.sku.clone(),
+            upc: self.upc.clone(),
+            item_type: self.item_type.clone(),
+            category: self.category.clone(),
+            brand: self.brand.clone(),
+            model: self.model.clone(),
+            description: self.description.clone(),
+            short_description: self.short_description.clone(),
+            image_url: None,
+            reorder_point: parse_i32(&self.reorder_point).unwrap_or(0),
+            preferred_stock_level: parse_i32(&self.preferred_stock_level).unwrap_or(0),
+            lead_time: parse_i32(&self.lead_time),
+            backorder_allowed: self.backorder_allowed.is_some(),
+            preferred_supplier_id: None,
+            purchase_price: parse_decimal(&self.purchase_price),
+            selling_price: parse_decimal(&self.selling_price),
+            tax_category: None,
+            cost_price: None,
+            landed_cost: None,
+            average_cost: None,
+            gross_margin: None,
+            currency: String::new(),
+            country_of_origin: self.country_of_origin.clone(),
+            hs_code: self.hs_code.clone(),
+            lifecycle_stage: String::new(),
+            is_active: true,
+            created_at: now,
+            updated_at: now,
+            created_by: Some(current_user.id),
+            parent_item_id: None,
+            variant_options: None,
+        }
     }
+}
 
-    let template = ItemFormTemplate { item: None, current_user: &current_user };
-    Ok(Html(template.render().unwrap()))
+// Normalize the SKU and UPC and make sure no other item has them. The unique
+// indexes still catch a race, this just explains the problem.
+async fn check_codes(db: &Database, form: &mut ItemForm) -> Result<Result<(), String>, StatusCode> {
+    form.sku = normalize_sku(&form.sku);
+    if form.sku.is_empty() {
+        return Ok(Err("SKU is required".to_string()));
+    }
+    form.upc = match form.upc.as_deref().map(str::trim).filter(|upc| !upc.is_empty()) {
+        Some(upc) => match normalize_gtin(upc) {
+            Ok(upc) => Some(upc),
+            Err(message) => return Ok(Err(message)),
+        },
+        None => None,
+    };
+
+    let taken = sqlx::query_scalar::<_, String>(
+        r#"
+        SELECT CASE WHEN UPPER(sku) = $1 THEN 'SKU ' || sku ELSE 'UPC/EAN ' || upc END || ' is already used by ' || item_name
+        FROM inventory_items
+        WHERE UPPER(sku) = $1 OR ($2::text IS NOT NULL AND upc = $2)
+        LIMIT 1
+        "#,
+    )
+    .bind(&form.sku)
+    .bind(&form.upc)
+    .fetch_optional(db)
+    .await
+    .map_err(|e| {
+        tracing::error!("Error checking item codes: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    Ok(taken.map_or(Ok(()), Err))
 }
 
 // Handler to create a new inventory item
 pub async fn create_item(
     State(db): State<Database>,
-    cookies: Cookies,
-    Form(form): Form<ItemForm>,
-) -> Result<Redirect, StatusCode> {
-    let current_user = get_current_user(cookies, &db).await
-        .ok_or(StatusCode::UNAUTHORIZED)?;
-
-    if !current_user.permissions.contains(&"inventory:write".to_string()) {
-        return Err(StatusCode::FORBIDDEN);
-    }
+    AuthUser(current_user): AuthUser,
+    Form(mut form): Form<ItemForm>,
+) -> Result<Response, StatusCode> {
+    current_user.require("inventory:write")?;
 
-    let backorder_allowed = form.backorder_allowed.is_some();
-    
-    // Helper closure to parse string to Option<Decimal>
-    let parse_decimal = |s: Option<String>| -> Option<Decimal> {
-        s.and_then(|val| Decimal::from_str(&val).ok())
+    let show_error = |form: &ItemForm, error: String| {
+        let template = ItemFormTemplate {
+            item: Some(form.draft(&current_user)),
+            current_user: &current_user,
+            error: Some(error),
+        };
+        Ok((StatusCode::UNPROCESSABLE_ENTITY, Html(template.render().unwrap())).into_response())
     };
 
-    // Helper closure to parse string to Option<i32>
-    let parse_i32 = |s: Option<String>| -> Option<i32> {
-        s.and_then(|val| val.parse::<i32>().ok())
-    };
+    if let Err(error) = check_codes(&db, &mut form).await? {
+        return show_error(&form, error);
+    }
+
+    let backorder_allowed = form.backorder_allowed.is_some();
 
-    sqlx::query(
+    let result = sqlx::query_scalar::<_, Uuid>(
         r#"
         INSERT INTO inventory_items (
             item_name, sku, upc, item_type, category, brand, model, description, short_description,
@@ -125,6 +352,7 @@ pub async fn create_item(
             selling_price, country_of_origin, hs_code, created_by
         )
         VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15, $16, $17, $18)
+        RETURNING id
         "#,
     )
     .bind(&form.item_name)
@@ -136,21 +364,521 @@ pub async fn create_item(
     .bind(&form.model)
     .bind(&form.description)
     .bind(&form.short_description)
-    .bind(parse_i32(form.reorder_point).unwrap_or(0))
-    .bind(parse_i32(form.preferred_stock_level).unwrap_or(0))
-    .bind(parse_i32(form.lead_time))
+    .bind(parse_i32(&form.reorder_point).unwrap_or(0))
+    .bind(parse_i32(&form.preferred_stock_level).unwrap_or(0))
+    .bind(parse_i32(&form.lead_time))
     .bind(backorder_allowed)
-    .bind(parse_decimal(form.purchase_price))
-    .bind(parse_decimal(form.selling_price))
+    .bind(parse_decimal(&form.purchase_price).filter(|_| current_user.has_finance_read))
+    .bind(parse_decimal(&form.selling_price))
     .bind(&form.country_of_origin)
     .bind(&form.hs_code)
     .bind(current_user.id)
-    .execute(&db)
+    .fetch_one(&db)
+    .await;
+
+    match result {
+        Ok(item_id) => {
+            audit_log::record(&db, &current_user, "create", Audited::InventoryItem, item_id, None).await;
+            Ok(Redirect::to("/inventory/items").into_response())
+        }
+        Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+            show_error(&form, "Another item was just saved with this SKU or UPC/EAN".to_string())
+        }
+        Err(e) => {
+            tracing::error!("Failed to create item: {}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+async fn render_item_detail(
+    db: &Database,
+    current_user: &CurrentUser,
+    item_id: Uuid,
+    option_text: Option<String>,
+    notice: Option<String>,
+    error: Option<String>,
+    stock_error: Option<String>,
+) -> Result<Html<String>, StatusCode> {
+    let fields = current_user.field_access();
+    let item = sqlx::query_as::<_, InventoryItem>("SELECT * FROM inventory_items WHERE id = $1")
+        .bind(item_id)
+        .fetch_optional(db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?
+        .with_access(&fields);
+
+    let parent = match item.parent_item_id {
+        Some(parent_id) => sqlx::query_as::<_, InventoryItem>("SELECT * FROM inventory_items WHERE id = $1")
+            .bind(parent_id)
+            .fetch_optional(db)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .map(|parent| parent.with_access(&fields)),
+        None => None,
+    };
+
+    // A parent's stock is its variants' stock, so show it per warehouse across them
+    let stock = sqlx::query_as::<_, WarehouseStock>(&format!(
+        r#"
+        SELECT w.name as warehouse_name,
+               SUM(sl.quantity_on_hand)::int as quantity_on_hand,
+               SUM(sl.quantity_committed)::int as quantity_committed,
+               SUM(sl.quantity_available)::int as quantity_available,
+               MIN(loc.code) as location
+        FROM stock_levels sl
+        JOIN warehouses w ON w.id = sl.warehouse_id
+        LEFT JOIN warehouse_locations loc ON loc.id = sl.location_id
+        JOIN inventory_items i ON i.id = sl.item_id
+        WHERE (i.id = $1 OR i.parent_item_id = $1) AND {}
+        GROUP BY w.id, w.name
+        ORDER BY w.name
+        "#,
+        locations::warehouse_condition("sl.warehouse_id", 2)
+    ))
+    .bind(item_id)
+    .bind(current_user.warehouse_ids.as_deref())
+    .fetch_all(db)
     .await
     .map_err(|e| {
-        eprintln!("Failed to create item: {}", e);
+        tracing::error!("Error loading stock levels: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let load = async {
+        let option_sets = variants::option_sets(db, item_id).await?;
+        let item_variants = variants::variants(db, item_id).await?;
+        let ids: Vec<Uuid> = item_variants.iter().map(|v| v.id).collect();
+        let variant_stock = variants::stock(db, current_user, &ids).await?;
+        Ok::<_, sqlx::Error>((option_sets, item_variants, variant_stock))
+    };
+    let (option_sets, item_variants, variant_stock) = load.await.map_err(|e| {
+        tracing::error!("Error loading variants: {}", e);
         StatusCode::INTERNAL_SERVER_ERROR
     })?;
+    let item_variants: Vec<InventoryItem> = item_variants.into_iter().map(|v| v.with_access(&fields)).collect();
 
-    Ok(Redirect::to("/inventory/items"))
-}
\ No newline at end of file
+    let matrix = variants::stock_matrix(&option_sets, &item_variants, &variant_stock);
+    let visible_to = sharing::is_scoped(current_user).then_some(current_user.id);
+    let commitments = blanket_orders::commitments(db, item_id, visible_to).await.map_err(|e| {
+        tracing::error!("Error loading blanket order commitments: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    let held_stock = held_stock::for_item(db, current_user, item_id).await.map_err(|e| {
+        tracing::error!("Error loading held stock: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    // Stock is kept against variants, so a parent with them has none of its own
+    let warehouses = if current_user.can("inventory:write") && item_variants.is_empty() {
+        locations::active_warehouses(db, current_user)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    } else {
+        Vec::new()
+    };
+    let customers = if warehouses.is_empty() {
+        Vec::new()
+    } else {
+        let scope = match visible_to {
+            Some(_) => format!("WHERE {}", sharing::visibility_condition(RecordKind::Customer, "c", 1)),
+            None => String::new(),
+        };
+        sqlx::query_as::<_, Customer>(&format!("SELECT c.* FROM customers c {} ORDER BY c.company_name", scope))
+            .bind(visible_to)
+            .fetch_all(db)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    };
+    let mut location_options = Vec::new();
+    for warehouse in &warehouses {
+        let load = async {
+            let suggested = locations::suggest_putaway(db, item_id, warehouse.id, None).await?;
+            let mut all = locations::for_warehouse(db, warehouse.id).await?;
+            let suggested = suggested.first().map(|location| location.id);
+            all.sort_by_key(|location| Some(location.id) != suggested);
+            Ok::<_, sqlx::Error>((suggested, all))
+        };
+        let (suggested, all) = load.await.map_err(|e| {
+            tracing::error!("Error loading warehouse locations: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+        for location in all {
+            let room = match location.free_capacity() {
+                Some(free) => format!(", room for {}", free),
+                None => String::new(),
+            };
+            let hint = if Some(location.id) == suggested { " (suggested)" } else { "" };
+            location_options.push(LocationOption {
+                id: location.id,
+                label: format!("{}: {}{}{}", warehouse.name, location.code, room, hint),
+            });
+        }
+    }
+    let option_text = option_text.unwrap_or_else(|| {
+        option_sets
+            .iter()
+            .map(|set| format!("{}: {}", set.name, set.option_values.join(", ")))
+            .collect::<Vec<_>>()
+            .join("\n")
+    });
+
+    let template = ItemDetailTemplate {
+        item,
+        parent,
+        stock,
+        variants: item_variants,
+        variant_stock,
+        matrix,
+        commitments,
+        held_stock,
+        warehouses,
+        location_options,
+        customers,
+        option_text,
+        notice,
+        error,
+        stock_error,
+        current_user,
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+// Handler for an item's page: its stock by warehouse and, for parents, its variants
+pub async fn item_detail(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(item_id): Path<Uuid>,
+    Query(query): Query<ItemDetailQuery>,
+) -> Result<Html<String>, StatusCode> {
+    current_user.require("inventory:read")?;
+
+    let notice = query.created.map(|count| match count {
+        0 => "Options saved. Every combination already had a variant.".to_string(),
+        1 => "Created 1 variant.".to_string(),
+        n => format!("Created {} variants.", n),
+    });
+    let notice = notice.or_else(|| {
+        query.allocated.map(|allocated| {
+            let mut notice = match allocated {
+                0 => "Stock recorded.".to_string(),
+                n => format!("Stock recorded. {} went straight to backorders.", n),
+            };
+            if let Some(location) = &query.putaway {
+                notice.push_str(&format!(" Put it away at {}.", location));
+            }
+            notice
+        })
+    });
+    render_item_detail(&db, &current_user, item_id, None, notice, None, None).await
+}
+
+// Handler to generate variants from option sets, one per combination of values
+pub async fn generate_variants(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(item_id): Path<Uuid>,
+    Form(form): Form<VariantsForm>,
+) -> Result<Response, StatusCode> {
+    current_user.require("inventory:write")?;
+
+    let parent = sqlx::query_as::<_, InventoryItem>("SELECT * FROM inventory_items WHERE id = $1")
+        .bind(item_id)
+        .fetch_optional(&db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let generated = match variants::parse_option_sets(&form.option_sets) {
+        Ok(sets) => variants::generate(&db, &parent, &sets, current_user.id).await.map_err(|e| {
+            tracing::error!("Error generating variants: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?,
+        Err(error) => Err(error),
+    };
+
+    match generated {
+        Ok(count) => {
+            let _ = create_audit_log(
+                &db,
+                &current_user,
+                "generate_variants".to_string(),
+                "inventory_item".to_string(),
+                Some(item_id),
+                None,
+                Some(serde_json::json!({ "option_sets": form.option_sets, "created": count })),
+            ).await;
+            Ok(Redirect::to(&format!("/inventory/items/{}?created={}", item_id, count)).into_response())
+        }
+        Err(error) => {
+            let page = render_item_detail(&db, &current_user, item_id, Some(form.option_sets), None, Some(error), None).await?;
+            Ok((StatusCode::UNPROCESSABLE_ENTITY, page).into_response())
+        }
+    }
+}
+
+// Stock is kept against an item without variants; a parent's is its variants'
+async fn require_stocked_item(db: &Database, item_id: Uuid) -> Result<(), StatusCode> {
+    let has_variants = sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS(SELECT 1 FROM inventory_items WHERE parent_item_id = i.id) FROM inventory_items i WHERE i.id = $1",
+    )
+    .bind(item_id)
+    .fetch_optional(db)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    .ok_or(StatusCode::NOT_FOUND)?;
+    if has_variants {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    Ok(())
+}
+
+// Handler to book in stock from a purchase order, filling backorders first
+pub async fn receive_stock(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(item_id): Path<Uuid>,
+    Form(form): Form<ReceiveStockForm>,
+) -> Result<Response, StatusCode> {
+    current_user.require("inventory:write")?;
+    current_user.require_warehouse(form.warehouse_id)?;
+    require_stocked_item(&db, item_id).await?;
+
+    let reference = form.reference.as_deref().map(str::trim).filter(|r| !r.is_empty());
+    let putaway = locations::suggest_putaway(&db, item_id, form.warehouse_id, Some(form.quantity))
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let location = match parse_optional_id(&form.location_id)? {
+        Some(location_id) => {
+            let in_warehouse = locations::for_warehouse(&db, form.warehouse_id)
+                .await
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+                .into_iter()
+                .find(|location| location.id == location_id);
+            match in_warehouse {
+                Some(location) => Some(location),
+                None => {
+                    let error = "That location isn't in the warehouse you're receiving into".to_string();
+                    let page = render_item_detail(&db, &current_user, item_id, None, None, None, Some(error)).await?;
+                    return Ok((StatusCode::UNPROCESSABLE_ENTITY, page).into_response());
+                }
+            }
+        }
+        None => putaway.into_iter().next(),
+    };
+
+    let received = stock::receive(
+        &db,
+        item_id,
+        form.warehouse_id,
+        form.quantity,
+        reference,
+        location.as_ref().map(|location| location.id),
+        current_user.id,
+    )
+        .await
+        .map_err(|e| {
+            tracing::error!("Error receiving stock: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    match received {
+        Ok(allocated) => {
+            let _ = create_audit_log(
+                &db,
+                &current_user,
+                "receive_stock".to_string(),
+                "inventory_item".to_string(),
+                Some(item_id),
+                None,
+                Some(serde_json::json!({
+                    "warehouse_id": form.warehouse_id,
+                    "quantity": form.quantity,
+                    "reference": reference,
+                    "location": location.as_ref().map(|location| &location.code),
+                    "allocated_to_backorders": allocated,
+                })),
+            ).await;
+            let putaway = match &location {
+                Some(location) => format!("&putaway={}", urlencoding::encode(&location.code)),
+                None => String::new(),
+            };
+            Ok(Redirect::to(&format!("/inventory/items/{}?allocated={}{}", item_id, allocated, putaway)).into_response())
+        }
+        Err(error) => {
+            let page = render_item_detail(&db, &current_user, item_id, None, None, None, Some(error)).await?;
+            Ok((StatusCode::UNPROCESSABLE_ENTITY, page).into_response())
+        }
+    }
+}
+
+// Handler to move free stock between warehouses, filling backorders at the
+// destination first
+pub async fn transfer_stock(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(item_id): Path<Uuid>,
+    Form(form): Form<TransferStockForm>,
+) -> Result<Response, StatusCode> {
+    current_user.require("inventory:write")?;
+    // Both ends move stock, so both have to be the user's
+    current_user.require_warehouse(form.from_warehouse_id)?;
+    current_user.require_warehouse(form.to_warehouse_id)?;
+    require_stocked_item(&db, item_id).await?;
+
+    let transferred = stock::transfer(&db, item_id, form.from_warehouse_id, form.to_warehouse_id, form.quantity, current_user.id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error transferring stock: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    match transferred {
+        Ok(allocated) => {
+            let _ = create_audit_log(
+                &db,
+                &current_user,
+                "transfer_stock".to_string(),
+                "inventory_item".to_string(),
+                Some(item_id),
+                None,
+                Some(serde_json::json!({
+                    "from_warehouse_id": form.from_warehouse_id,
+                    "to_warehouse_id": form.to_warehouse_id,
+                    "quantity": form.quantity,
+                    "allocated_to_backorders": allocated,
+                })),
+            ).await;
+            Ok(Redirect::to(&format!("/inventory/items/{}?allocated={}", item_id, allocated)).into_response())
+        }
+        Err(error) => {
+            let page = render_item_detail(&db, &current_user, item_id, None, None, None, Some(error)).await?;
+            Ok((StatusCode::UNPROCESSABLE_ENTITY, page).into_response())
+        }
+    }
+}
+
+// Handler to book in stock we hold but don't own: a supplier's consignment or
+// a customer's goods
+pub async fn receive_held_stock(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(item_id): Path<Uuid>,
+    Form(form): Form<HeldStockForm>,
+) -> Result<Response, StatusCode> {
+    current_user.require("inventory:write")?;
+    current_user.require_warehouse(form.warehouse_id)?;
+    require_stocked_item(&db, item_id).await?;
+
+    let owner = match form.ownership.as_str() {
+        "consignment" => Owner::Consignor(form.consignor.clone().unwrap_or_default()),
+        "customer_owned" => {
+            let Some(customer_id) = parse_optional_id(&form.customer_id)? else {
+                let error = "Choose the customer who owns the stock".to_string();
+                let page = render_item_detail(&db, &current_user, item_id, None, None, None, Some(error)).await?;
+                return Ok((StatusCode::UNPROCESSABLE_ENTITY, page).into_response());
+            };
+            require_access(&db, &current_user, RecordKind::Customer, customer_id, Access::Read).await?;
+            Owner::Customer(customer_id)
+        }
+        _ => return Err(StatusCode::BAD_REQUEST),
+    };
+
+    let received = held_stock::receive(&db, item_id, form.warehouse_id, &owner, form.quantity, current_user.id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error receiving held stock: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    match received {
+        Ok(()) => {
+            let _ = create_audit_log(
+                &db,
+                &current_user,
+                "receive_held_stock".to_string(),
+                "inventory_item".to_string(),
+                Some(item_id),
+                None,
+                Some(serde_json::json!({
+                    "warehouse_id": form.warehouse_id,
+                    "ownership": form.ownership,
+                    "consignor": form.consignor,
+                    "customer_id": form.customer_id,
+                    "quantity": form.quantity,
+                })),
+            ).await;
+            Ok(Redirect::to(&format!("/inventory/items/{}?allocated=0", item_id)).into_response())
+        }
+        Err(error) => {
+            let page = render_item_detail(&db, &current_user, item_id, None, None, None, Some(error)).await?;
+            Ok((StatusCode::UNPROCESSABLE_ENTITY, page).into_response())
+        }
+    }
+}
+
+// Handler to return held stock to its owner, or take consigned stock into our own
+pub async fn release_held_stock(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path((item_id, held_id)): Path<(Uuid, Uuid)>,
+    Form(form): Form<ReleaseHeldStockForm>,
+) -> Result<Response, StatusCode> {
+    current_user.require("inventory:write")?;
+    let warehouse_id = held_stock::warehouse_of(&db, held_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    current_user.require_warehouse(warehouse_id)?;
+
+    let into_stock = match form.action.as_str() {
+        "return" => false,
+        "take" => true,
+        _ => return Err(StatusCode::BAD_REQUEST),
+    };
+    let released = held_stock::release(&db, held_id, item_id, form.quantity, into_stock, current_user.id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error releasing held stock: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    match released {
+        Ok(allocated) => {
+            let _ = create_audit_log(
+                &db,
+                &current_user,
+                if into_stock { "take_consigned_stock" } else { "return_held_stock" }.to_string(),
+                "inventory_item".to_string(),
+                Some(item_id),
+                Some(serde_json::json!({ "held_stock_id": held_id })),
+                Some(serde_json::json!({
+                    "quantity": form.quantity,
+                    "allocated_to_backorders": allocated,
+                })),
+            ).await;
+            Ok(Redirect::to(&format!("/inventory/items/{}?allocated={}", item_id, allocated)).into_response())
+        }
+        Err(error) => {
+            let page = render_item_detail(&db, &current_user, item_id, None, None, None, Some(error)).await?;
+            Ok((StatusCode::UNPROCESSABLE_ENTITY, page).into_response())
+        }
+    }
+}
+
+// Handler for the backorder queue: what customers are waiting on, in the
+// order incoming stock will fill it
+pub async fn backorders_page(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+) -> Result<Html<String>, StatusCode> {
+    current_user.require("inventory:read")?;
+
+    let backorders = backorders::queue(&db, &current_user).await.map_err(|e| {
+        tracing::error!("Error loading backorders: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let template = BackordersTemplate { backorders };
+    Ok(Html(template.render().unwrap()))
+}
@@ -0,0 +1,289 @@
+use axum::{
+    extract::{Form, Path, Query, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Redirect, Response},
+};
+use askama::Template;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    database::Database,
+    handlers::team::{create_audit_log, get_form_values, manager_options, parse_form_data, parse_manager_id},
+    middleware::{AuthUser, ClientInfo},
+    models::{Invitation, Role, RoleDisplay, User},
+    services::{
+        invitations::{self, NewInvitation},
+        password_policy::{self, PasswordPolicy},
+    },
+    utils::hash_password,
+};
+
+#[derive(Template)]
+#[template(path = "team/invite.html")]
+struct InviteTemplate {
+    roles: Vec<RoleDisplay>,
+    managers: Vec<User>,
+    invitations: Vec<Invitation>,
+    sent: bool,
+    error: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "invite_accept.html")]
+struct AcceptInviteTemplate {
+    invitation: Option<Invitation>,
+    token: String,
+    error: String,
+    password_policy: PasswordPolicy,
+}
+
+#[derive(Deserialize)]
+pub struct SentQuery {
+    sent: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct AcceptInviteForm {
+    token: String,
+    first_name: String,
+    last_name: String,
+    password: String,
+}
+
+async fn render_invite_page(
+    db: &Database,
+    sent: bool,
+    error: Option<String>,
+) -> Result<Html<String>, StatusCode> {
+    let roles = sqlx::query_as::<_, Role>("SELECT * FROM roles WHERE is_active = true ORDER BY name")
+        .fetch_all(db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .into_iter()
+        .map(RoleDisplay::from)
+        .collect();
+
+    let invitations = invitations::list_recent(db).await.map_err(|e| {
+        tracing::error!("Error loading invitations: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let template = InviteTemplate {
+        roles,
+        managers: manager_options(db, None).await?,
+        invitations,
+        sent,
+        error,
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+async fn render_accept_page(
+    db: &Database,
+    invitation: Option<Invitation>,
+    token: String,
+    error: String,
+) -> Html<String> {
+    let password_policy = PasswordPolicy::load(db).await.unwrap_or_else(|e| {
+        tracing::error!("Error loading password policy: {}", e);
+        PasswordPolicy::default()
+    });
+    let template = AcceptInviteTemplate {
+        invitation,
+        token,
+        error,
+        password_policy,
+    };
+    Html(template.render().unwrap())
+}
+
+pub async fn invite_page(
+    AuthUser(current_user): AuthUser,
+    State(db): State<Database>,
+    Query(query): Query<SentQuery>,
+) -> Result<Html<String>, StatusCode> {
+    if !current_user.has_team_write {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    render_invite_page(&db, query.sent.is_some(), None).await
+}
+
+// Form data is parsed by hand so every checked role comes through
+pub async fn send_invite(
+    AuthUser(current_user): AuthUser,
+    State(db): State<Database>,
+    body: String,
+) -> Result<Response, StatusCode> {
+    if !current_user.has_team_write {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let form_data = parse_form_data(&body);
+    let field = |key: &str| form_data.get(key).map(|value| value.trim().to_string()).unwrap_or_default();
+    let email = field("email");
+    let first_name = field("first_name");
+    let last_name = field("last_name");
+    let manager_id = parse_manager_id(&form_data)?;
+    let role_ids: Vec<Uuid> = get_form_values(&body, "role_ids")
+        .iter()
+        .filter_map(|id| Uuid::parse_str(id).ok())
+        .collect();
+
+    if !email.contains('@') || email.len() > 255 {
+        return Ok(render_invite_page(&db, false, Some("Enter a valid email address.".to_string()))
+            .await?
+            .into_response());
+    }
+
+    // Accounts created by an import that were never activated can be invited again
+    let existing = sqlx::query_as::<_, (Uuid, bool)>(
+        "SELECT id, password_hash = '' FROM users WHERE LOWER(email) = LOWER($1)",
+    )
+    .bind(&email)
+    .fetch_optional(&db)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let account_id = match existing {
+        Some((id, true)) => Some(id),
+        Some(_) => {
+            let message = format!("{} already has an account.", email);
+            return Ok(render_invite_page(&db, false, Some(message)).await?.into_response());
+        }
+        None => None,
+    };
+
+    let invite = NewInvitation {
+        email: &email,
+        first_name: &first_name,
+        last_name: &last_name,
+        role_ids: &role_ids,
+        manager_id,
+    };
+    let inviter_name = format!("{} {}", current_user.first_name, current_user.last_name);
+    let invitation_id = invitations::send(&db, invite, account_id, current_user.id, &inviter_name)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error sending invitation: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    let _ = create_audit_log(
+        &db,
+        &current_user,
+        "invite".to_string(),
+        "user_invitation".to_string(),
+        Some(invitation_id),
+        None,
+        Some(serde_json::json!({ "email": email, "role_ids": role_ids, "manager_id": manager_id })),
+    )
+    .await;
+
+    Ok(Redirect::to("/team/users/invite?sent=1").into_response())
+}
+
+pub async fn revoke_invite(
+    AuthUser(current_user): AuthUser,
+    State(db): State<Database>,
+    Path(invitation_id): Path<Uuid>,
+) -> Result<Redirect, StatusCode> {
+    if !current_user.has_team_write {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let revoked = invitations::revoke(&db, invitation_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    if revoked {
+        let _ = create_audit_log(
+            &db,
+            &current_user,
+            "revoke".to_string(),
+            "user_invitation".to_string(),
+            Some(invitation_id),
+            None,
+            None,
+        )
+        .await;
+    }
+
+    Ok(Redirect::to("/team/users/invite"))
+}
+
+// Public page the invitation email links to
+pub async fn accept_page(
+    State(db): State<Database>,
+    Path(token): Path<String>,
+) -> Result<Html<String>, StatusCode> {
+    let invitation = invitations::find_open(&db, &token)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(render_accept_page(&db, invitation, token, String::new()).await)
+}
+
+pub async fn accept_invite(
+    State(db): State<Database>,
+    client: ClientInfo,
+    Form(form): Form<AcceptInviteForm>,
+) -> Result<Response, StatusCode> {
+    let invitation = invitations::find_open(&db, &form.token)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let Some(invitation) = invitation else {
+        return Ok(render_accept_page(&db, None, form.token, String::new()).await.into_response());
+    };
+
+    let first_name = form.first_name.trim();
+    let last_name = form.last_name.trim();
+    if first_name.is_empty() || last_name.is_empty() {
+        let error = "Enter your first and last name.".to_string();
+        return Ok(render_accept_page(&db, Some(invitation), form.token, error).await.into_response());
+    }
+
+    let checked = password_policy::validate(&db, None, &form.password).await.map_err(|e| {
+        tracing::error!("Error checking password policy: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    if let Err(error) = checked {
+        return Ok(render_accept_page(&db, Some(invitation), form.token, error).await.into_response());
+    }
+
+    let password_hash = hash_password(&form.password).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let user = match invitations::accept(&db, &invitation, first_name, last_name, &password_hash).await {
+        Ok(Some(user)) => user,
+        // Used or withdrawn while the form was open
+        Ok(None) => return Ok(render_accept_page(&db, None, form.token, String::new()).await.into_response()),
+        Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+            let error = "An account with this email already exists. Sign in instead.".to_string();
+            return Ok(render_accept_page(&db, Some(invitation), form.token, error).await.into_response());
+        }
+        Err(e) => {
+            tracing::error!("Error accepting invitation {}: {}", invitation.id, e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    if let Err(e) = password_policy::remember(&db, user.id, &password_hash).await {
+        tracing::error!("Error recording password history: {}", e);
+    }
+
+    let _ = sqlx::query(
+        r#"
+        INSERT INTO audit_logs (user_id, action, resource_type, resource_id, new_values, ip_address, user_agent)
+        VALUES ($1, 'accept', 'user_invitation', $2, $3, $4, $5)
+        "#,
+    )
+    .bind(user.id)
+    .bind(invitation.id)
+    .bind(serde_json::json!({ "email": user.email, "roles": invitation.role_names }))
+    .bind(&client.ip_address)
+    .bind(&client.user_agent)
+    .execute(&db)
+    .await;
+
+    Ok(Redirect::to("/login").into_response())
+}
@@ -0,0 +1,170 @@
+use axum::{
+    extract::{Path, Query, State},
+    http::StatusCode,
+    response::{Html, Redirect},
+};
+use askama::Template;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    database::Database,
+    middleware::{AuthUser, CurrentUser},
+    models::{Job, JobLog, JOB_STATUSES},
+    services::jobs,
+};
+
+const PAGE_SIZE: i64 = 100;
+
+const JOB_SELECT: &str = r#"
+    SELECT j.*, NULLIF(CONCAT(u.first_name, ' ', u.last_name), ' ') as created_by_name
+    FROM jobs j
+    LEFT JOIN users u ON u.id = j.created_by
+"#;
+
+struct StatusCount {
+    status: String,
+    count: i64,
+}
+
+#[derive(Template)]
+#[template(path = "team/jobs.html")]
+struct JobsTemplate {
+    jobs: Vec<Job>,
+    counts: Vec<StatusCount>,
+    statuses: Vec<String>,
+    selected_status: String,
+    page_size: i64,
+}
+
+#[derive(Template)]
+#[template(path = "team/job_detail.html")]
+struct JobDetailTemplate {
+    job: Job,
+    logs: Vec<JobLog>,
+}
+
+#[derive(Deserialize)]
+pub struct JobFilters {
+    status: Option<String>,
+}
+
+fn require_admin(current_user: &CurrentUser) -> Result<(), StatusCode> {
+    if current_user.has_manage_roles {
+        Ok(())
+    } else {
+        Err(StatusCode::FORBIDDEN)
+    }
+}
+
+pub async fn jobs_dashboard(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Query(filters): Query<JobFilters>,
+) -> Result<Html<String>, StatusCode> {
+    require_admin(&current_user)?;
+
+    let selected_status = filters.status.unwrap_or_default();
+    if !selected_status.is_empty() && !JOB_STATUSES.contains(&selected_status.as_str()) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    // Queued and running jobs first, then everything else newest first
+    let jobs = sqlx::query_as::<_, Job>(&format!(
+        r#"
+        {}
+        WHERE ($1 = '' OR j.status = $1)
+        ORDER BY j.status IN ('queued', 'running') DESC, j.created_at DESC
+        LIMIT $2
+        "#,
+        JOB_SELECT
+    ))
+    .bind(&selected_status)
+    .bind(PAGE_SIZE)
+    .fetch_all(&db)
+    .await
+    .map_err(|e| {
+        tracing::error!("Error loading jobs: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let found = sqlx::query_as::<_, (String, i64)>("SELECT status, COUNT(*) FROM jobs GROUP BY status")
+        .fetch_all(&db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let counts = JOB_STATUSES
+        .iter()
+        .map(|status| {
+            let count = found.iter().find(|(s, _)| s == status).map(|(_, n)| *n).unwrap_or(0);
+            StatusCount {
+                status: status.to_string(),
+                count,
+            }
+        })
+        .collect();
+
+    let template = JobsTemplate {
+        jobs,
+        counts,
+        statuses: JOB_STATUSES.iter().map(|s| s.to_string()).collect(),
+        selected_status,
+        page_size: PAGE_SIZE,
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+pub async fn job_detail(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<Html<String>, StatusCode> {
+    require_admin(&current_user)?;
+
+    let job = sqlx::query_as::<_, Job>(&format!("{} WHERE j.id = $1", JOB_SELECT))
+        .bind(id)
+        .fetch_optional(&db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let logs = sqlx::query_as::<_, JobLog>(
+        "SELECT * FROM job_logs WHERE job_id = $1 ORDER BY created_at DESC LIMIT 500",
+    )
+    .bind(id)
+    .fetch_all(&db)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let template = JobDetailTemplate { job, logs };
+    Ok(Html(template.render().unwrap()))
+}
+
+pub async fn retry_job(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<Redirect, StatusCode> {
+    require_admin(&current_user)?;
+
+    jobs::retry(&db, id, current_user.id).await.map_err(|e| {
+        tracing::error!("Error retrying job {}: {}", id, e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    Ok(Redirect::to(&format!("/team/jobs/{}", id)))
+}
+
+pub async fn cancel_job(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<Redirect, StatusCode> {
+    require_admin(&current_user)?;
+
+    jobs::cancel(&db, id, current_user.id).await.map_err(|e| {
+        tracing::error!("Error cancelling job {}: {}", id, e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    Ok(Redirect::to(&format!("/team/jobs/{}", id)))
+}
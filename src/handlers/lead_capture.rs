@@ -0,0 +1,202 @@
+use axum::{
+    extract::{ConnectInfo, Form, Query, State},
+    http::{header, HeaderMap, StatusCode},
+    response::Html,
+};
+use askama::Template;
+use serde::Deserialize;
+use std::net::SocketAddr;
+use uuid::Uuid;
+
+use crate::{
+    database::Database,
+    services::captcha::{self, CaptchaResponse},
+    utils::request::client_ip,
+};
+
+#[derive(Template)]
+#[template(path = "public/lead_form.html")]
+struct LeadFormTemplate {
+    utm_source: String,
+    utm_medium: String,
+    utm_campaign: String,
+    referrer: String,
+    error: String,
+    captcha: Option<captcha::Widget>,
+}
+
+#[derive(Template)]
+#[template(path = "public/lead_thanks.html")]
+struct LeadThanksTemplate {
+    first_name: String,
+}
+
+#[derive(Deserialize)]
+pub struct UtmQuery {
+    utm_source: Option<String>,
+    utm_medium: Option<String>,
+    utm_campaign: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct LeadForm {
+    first_name: String,
+    last_name: String,
+    email: String,
+    phone: Option<String>,
+    company_name: Option<String>,
+    message: Option<String>,
+    utm_source: Option<String>,
+    utm_medium: Option<String>,
+    utm_campaign: Option<String>,
+    referrer: Option<String>,
+    #[serde(flatten)]
+    captcha: CaptchaResponse,
+}
+
+// Trim, drop empty values and cap the length to fit the column
+fn clean(value: Option<&str>, max_len: usize) -> Option<String> {
+    value
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(|v| v.chars().take(max_len).collect())
+}
+
+fn referer_header(headers: &HeaderMap) -> Option<&str> {
+    headers.get(header::REFERER).and_then(|value| value.to_str().ok())
+}
+
+// Public lead form. UTM parameters from the landing URL and the referring page are carried in hidden fields.
+pub async fn lead_form(
+    Query(query): Query<UtmQuery>,
+    headers: HeaderMap,
+) -> Html<String> {
+    let template = LeadFormTemplate {
+        utm_source: clean(query.utm_source.as_deref(), 255).unwrap_or_default(),
+        utm_medium: clean(query.utm_medium.as_deref(), 255).unwrap_or_default(),
+        utm_campaign: clean(query.utm_campaign.as_deref(), 255).unwrap_or_default(),
+        referrer: clean(referer_header(&headers), 2000).unwrap_or_default(),
+        error: String::new(),
+        captcha: captcha::widget(),
+    };
+    Html(template.render().unwrap())
+}
+
+// Creates a prospect customer and primary contact. Also accepts posts from forms embedded on other sites,
+// which need to include the CAPTCHA widget themselves once CAPTCHA is turned on.
+pub async fn submit_lead(
+    State(db): State<Database>,
+    headers: HeaderMap,
+    peer: Option<ConnectInfo<SocketAddr>>,
+    Form(form): Form<LeadForm>,
+) -> Result<Html<String>, StatusCode> {
+    let utm_source = clean(form.utm_source.as_deref(), 255);
+    let utm_medium = clean(form.utm_medium.as_deref(), 255);
+    let utm_campaign = clean(form.utm_campaign.as_deref(), 255);
+    let referrer = clean(form.referrer.as_deref(), 2000)
+        .or_else(|| clean(referer_header(&headers), 2000));
+
+    let first_name = form.first_name.trim();
+    let last_name = form.last_name.trim();
+    let email = form.email.trim();
+
+    let remote_ip = client_ip(&headers, peer.map(|ConnectInfo(addr)| addr)).map(|ip| ip.to_string());
+    let error = if first_name.is_empty() || last_name.is_empty() || !email.contains('@') {
+        Some("Please enter your name and a valid email address.")
+    } else if !captcha::verify(&form.captcha, remote_ip).await {
+        Some("Please complete the CAPTCHA check.")
+    } else {
+        None
+    };
+
+    if let Some(error) = error {
+        let template = LeadFormTemplate {
+            utm_source: utm_source.unwrap_or_default(),
+            utm_medium: utm_medium.unwrap_or_default(),
+            utm_campaign: utm_campaign.unwrap_or_default(),
+            referrer: referrer.unwrap_or_default(),
+            error: error.to_string(),
+            captcha: captcha::widget(),
+        };
+        return Ok(Html(template.render().unwrap()));
+    }
+
+    // Link to the campaign whose UTM value (or name, when no UTM value is set)
+    // matches. The lead goes to whoever runs that campaign; leads from no
+    // campaign wait for someone who sees every record to assign them.
+    let campaign = match &utm_campaign {
+        Some(utm_campaign) => sqlx::query_as::<_, (Uuid, Option<Uuid>)>(
+            r#"
+            SELECT id, created_by FROM campaigns
+            WHERE LOWER(utm_campaign) = LOWER($1)
+               OR (utm_campaign IS NULL AND LOWER(name) = LOWER($1))
+            ORDER BY utm_campaign NULLS LAST
+            LIMIT 1
+            "#,
+        )
+        .bind(utm_campaign)
+        .fetch_optional(&db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?,
+        None => None,
+    };
+    let (campaign_id, assigned_to) = match campaign {
+        Some((id, owner)) => (Some(id), owner),
+        None => (None, None),
+    };
+
+    let company_name = clean(form.company_name.as_deref(), 255)
+        .unwrap_or_else(|| format!("{} {}", first_name, last_name));
+    let phone = clean(form.phone.as_deref(), 50);
+
+    let mut tx = db.begin().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let customer_id = sqlx::query_scalar::<_, Uuid>(
+        r#"
+        INSERT INTO customers (
+            company_name, email, phone, status, notes, campaign_id,
+            lead_source, utm_source, utm_medium, utm_campaign, referrer, assigned_to
+        )
+        VALUES ($1, $2, $3, 'prospect', $4, $5, 'web_form', $6, $7, $8, $9, $10)
+        RETURNING id
+        "#,
+    )
+    .bind(&company_name)
+    .bind(email)
+    .bind(&phone)
+    .bind(clean(form.message.as_deref(), 5000))
+    .bind(campaign_id)
+    .bind(&utm_source)
+    .bind(&utm_medium)
+    .bind(&utm_campaign)
+    .bind(&referrer)
+    .bind(assigned_to)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(|e| {
+        tracing::error!("Error creating web lead: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO contacts (customer_id, first_name, last_name, email, phone, is_primary)
+        VALUES ($1, $2, $3, $4, $5, true)
+        "#,
+    )
+    .bind(customer_id)
+    .bind(first_name)
+    .bind(last_name)
+    .bind(email)
+    .bind(&phone)
+    .execute(&mut *tx)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    tx.commit().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let template = LeadThanksTemplate {
+        first_name: first_name.to_string(),
+    };
+    Ok(Html(template.render().unwrap()))
+}
@@ -0,0 +1,374 @@
+use axum::{
+    extract::{Form, Path, Query, State},
+    http::StatusCode,
+    response::{Html, Redirect},
+};
+use askama::Template;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    database::Database,
+    handlers::{
+        crm::{
+            load_assignee_picker, load_campaigns, locked_rate, parse_optional_decimal, parse_optional_id,
+            require_access, route_assignee, stage_probability,
+        },
+        team::create_audit_log,
+    },
+    middleware::{AuthUser, CurrentUser},
+    models::{Campaign, Lead, LEAD_SOURCES, LEAD_STATUSES},
+    services::{
+        audit_log::{self, audited_execute, Audited},
+        events::{self, Event},
+        leads::{self, LeadInput, NewDeal},
+        lookups::{self, LookupOptions},
+        out_of_office::AssigneePicker,
+        sharing::{Access, RecordKind},
+        stage_history,
+    },
+};
+
+#[derive(Template)]
+#[template(path = "crm/leads.html")]
+struct LeadsTemplate {
+    current_user: CurrentUser,
+    leads: Vec<Lead>,
+    // The status filtered on; empty for open leads
+    status: String,
+    can_write: bool,
+}
+
+#[derive(Template)]
+#[template(path = "crm/lead_form.html")]
+struct LeadFormTemplate {
+    lead: Option<Lead>,
+    campaigns: Vec<Campaign>,
+    sources: &'static [(&'static str, &'static str)],
+    statuses: &'static [&'static str],
+    assignee_picker: AssigneePicker,
+    error: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "crm/lead_detail.html")]
+struct LeadDetailTemplate {
+    current_user: CurrentUser,
+    lead: Lead,
+    currencies: LookupOptions,
+    can_write: bool,
+    can_see_values: bool,
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct LeadsQuery {
+    status: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct LeadErrorQuery {
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct LeadForm {
+    first_name: String,
+    last_name: String,
+    company_name: Option<String>,
+    title: Option<String>,
+    email: Option<String>,
+    phone: Option<String>,
+    lead_source: Option<String>,
+    campaign_id: Option<String>,
+    status: Option<String>,
+    notes: Option<String>,
+    assigned_to: Option<String>,
+    keep_assignee: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ConvertForm {
+    company_name: String,
+    // Checkbox: open a deal as well
+    create_deal: Option<String>,
+    deal_title: Option<String>,
+    deal_value: Option<String>,
+    currency: Option<String>,
+}
+
+fn trimmed(value: &Option<String>) -> Option<&str> {
+    value.as_deref().map(str::trim).filter(|v| !v.is_empty())
+}
+
+fn lead_input<'a>(form: &'a LeadForm, campaign_id: Option<Uuid>, assigned_to: Option<Uuid>) -> LeadInput<'a> {
+    LeadInput {
+        first_name: form.first_name.trim(),
+        last_name: form.last_name.trim(),
+        company_name: trimmed(&form.company_name),
+        title: trimmed(&form.title),
+        email: trimmed(&form.email),
+        phone: trimmed(&form.phone),
+        lead_source: trimmed(&form.lead_source),
+        campaign_id,
+        status: trimmed(&form.status).unwrap_or("new"),
+        notes: trimmed(&form.notes),
+        assigned_to,
+    }
+}
+
+async fn load_lead(db: &Database, id: Uuid) -> Result<Lead, StatusCode> {
+    leads::find(db, id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error loading lead: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+pub async fn leads_list(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Query(query): Query<LeadsQuery>,
+) -> Result<Html<String>, StatusCode> {
+    current_user.require("customers:read")?;
+    let status = trimmed(&query.status);
+    if status.is_some_and(|status| status != "converted" && !LEAD_STATUSES.contains(&status)) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let leads = leads::list(&db, &current_user, status).await.map_err(|e| {
+        tracing::error!("Error loading leads: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let template = LeadsTemplate {
+        status: status.unwrap_or_default().to_string(),
+        can_write: current_user.can("customers:write"),
+        current_user,
+        leads,
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+pub async fn lead_form(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Query(query): Query<LeadErrorQuery>,
+) -> Result<Html<String>, StatusCode> {
+    current_user.require("customers:write")?;
+
+    let template = LeadFormTemplate {
+        lead: None,
+        campaigns: load_campaigns(&db).await?,
+        sources: LEAD_SOURCES,
+        statuses: LEAD_STATUSES,
+        assignee_picker: load_assignee_picker(&db, None).await?,
+        error: query.error,
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+pub async fn create_lead(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Form(form): Form<LeadForm>,
+) -> Result<Redirect, StatusCode> {
+    current_user.require("customers:write")?;
+    let campaign_id = parse_optional_id(&form.campaign_id)?;
+    let assigned_to = route_assignee(&db, parse_optional_id(&form.assigned_to)?, form.keep_assignee.is_some()).await?;
+
+    let created = leads::create(&db, &lead_input(&form, campaign_id, assigned_to), current_user.id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error creating lead: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    let id = match created {
+        Ok(id) => id,
+        Err(error) => return Ok(Redirect::to(&format!("/crm/leads/new?error={}", urlencoding::encode(&error)))),
+    };
+
+    audit_log::record(&db, &current_user, "create", Audited::Lead, id, None).await;
+
+    Ok(Redirect::to(&format!("/crm/leads/{}", id)))
+}
+
+pub async fn lead_detail(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+    Query(query): Query<LeadErrorQuery>,
+) -> Result<Html<String>, StatusCode> {
+    current_user.require("customers:read")?;
+    let access = require_access(&db, &current_user, RecordKind::Lead, id, Access::Read).await?;
+    let lead = load_lead(&db, id).await?;
+
+    let template = LeadDetailTemplate {
+        lead,
+        currencies: lookups::options(&db, "currency")
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?,
+        can_write: access >= Access::Write && current_user.can("customers:write"),
+        can_see_values: current_user.has_finance_read,
+        current_user,
+        error: query.error,
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+pub async fn lead_edit_form(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+    Query(query): Query<LeadErrorQuery>,
+) -> Result<Html<String>, StatusCode> {
+    current_user.require("customers:write")?;
+    require_access(&db, &current_user, RecordKind::Lead, id, Access::Write).await?;
+    let lead = load_lead(&db, id).await?;
+    if lead.is_converted() {
+        return Err(StatusCode::CONFLICT);
+    }
+
+    let template = LeadFormTemplate {
+        assignee_picker: load_assignee_picker(&db, lead.assigned_to).await?,
+        lead: Some(lead),
+        campaigns: load_campaigns(&db).await?,
+        sources: LEAD_SOURCES,
+        statuses: LEAD_STATUSES,
+        error: query.error,
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+pub async fn update_lead(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+    Form(form): Form<LeadForm>,
+) -> Result<Redirect, StatusCode> {
+    current_user.require("customers:write")?;
+    let access = require_access(&db, &current_user, RecordKind::Lead, id, Access::Write).await?;
+    let lead = load_lead(&db, id).await?;
+
+    let campaign_id = parse_optional_id(&form.campaign_id)?;
+    // As with deals, handing a lead to someone else takes the access needed
+    // to share it, and only a new assignee is routed around absences
+    let chosen = parse_optional_id(&form.assigned_to)?;
+    let assigned_to = if chosen == lead.assigned_to {
+        lead.assigned_to
+    } else if access < Access::Manage {
+        return Err(StatusCode::FORBIDDEN);
+    } else {
+        route_assignee(&db, chosen, form.keep_assignee.is_some()).await?
+    };
+
+    let before = audit_log::snapshot(&db, Audited::Lead, id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let updated = leads::update(&db, id, &lead_input(&form, campaign_id, assigned_to))
+        .await
+        .map_err(|e| {
+            tracing::error!("Error updating lead: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    if let Err(error) = updated {
+        return Ok(Redirect::to(&format!("/crm/leads/{}/edit?error={}", id, urlencoding::encode(&error))));
+    }
+
+    audit_log::record(&db, &current_user, "update", Audited::Lead, id, before).await;
+
+    Ok(Redirect::to(&format!("/crm/leads/{}", id)))
+}
+
+pub async fn delete_lead(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<Redirect, StatusCode> {
+    current_user.require("customers:delete")?;
+    require_access(&db, &current_user, RecordKind::Lead, id, Access::Write).await?;
+
+    let delete = sqlx::query("DELETE FROM leads WHERE id = $1").bind(id);
+    audited_execute(&db, &current_user, "delete", Audited::Lead, id, delete)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error deleting lead: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    Ok(Redirect::to("/crm/leads"))
+}
+
+// Creates the customer, its primary contact and optionally a deal from the
+// lead, then opens the new customer
+pub async fn convert_lead(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+    Form(form): Form<ConvertForm>,
+) -> Result<Redirect, StatusCode> {
+    current_user.require("customers:write")?;
+    require_access(&db, &current_user, RecordKind::Lead, id, Access::Write).await?;
+
+    let deal = if form.create_deal.is_some() {
+        let currency = trimmed(&form.currency).unwrap_or("USD");
+        if !lookups::is_allowed(&db, "currency", currency)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        {
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        Some(NewDeal {
+            title: trimmed(&form.deal_title).unwrap_or_default(),
+            value: parse_optional_decimal(&form.deal_value)?.filter(|_| current_user.has_finance_read),
+            currency,
+            stage: "prospect",
+            probability: stage_probability("prospect"),
+            exchange_rate: locked_rate(&db, currency).await?,
+        })
+    } else {
+        None
+    };
+
+    let converted = leads::convert(&db, id, form.company_name.trim(), deal, current_user.id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error converting lead {}: {}", id, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    let converted = match converted {
+        Ok(converted) => converted,
+        Err(error) => return Ok(Redirect::to(&format!("/crm/leads/{}?error={}", id, urlencoding::encode(&error)))),
+    };
+
+    let customer = &converted.customer;
+    audit_log::record(&db, &current_user, "create", Audited::Customer, customer.id, None).await;
+    audit_log::record(&db, &current_user, "create", Audited::Contact, converted.contact_id, None).await;
+    events::publish(&db, &current_user, Event::CustomerCreated(customer)).await;
+    if let Some(deal) = &converted.deal {
+        audit_log::record(&db, &current_user, "create", Audited::Deal, deal.id, None).await;
+        if let Err(e) = stage_history::record(&db, deal.id, None, &deal.stage, current_user.id).await {
+            tracing::error!("Error recording stage history for deal {}: {}", deal.id, e);
+        }
+        events::publish(&db, &current_user, Event::DealCreated(deal)).await;
+    }
+
+    let _ = create_audit_log(
+        &db,
+        &current_user,
+        "convert".to_string(),
+        "lead".to_string(),
+        Some(id),
+        None,
+        Some(serde_json::json!({
+            "customer_id": customer.id,
+            "contact_id": converted.contact_id,
+            "deal_id": converted.deal.as_ref().map(|deal| deal.id),
+        })),
+    )
+    .await;
+
+    Ok(Redirect::to(&format!("/crm/customers/{}", customer.id)))
+}
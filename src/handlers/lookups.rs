@@ -0,0 +1,210 @@
+use axum::{
+    extract::{Extension, Form, Path, Query, State},
+    http::StatusCode,
+    response::{Html, Json, Redirect},
+};
+use askama::Template;
+use serde::{Deserialize, Serialize};
+use tower_cookies::Cookies;
+use uuid::Uuid;
+
+use crate::{
+    database::Database,
+    handlers::team::create_audit_log,
+    middleware::{api_auth::ApiPrincipal, AuthUser, get_current_user, CurrentUser},
+    models::{LookupValue, LOOKUP_KINDS},
+    services::{activity_types, lookups},
+};
+
+struct LookupKind {
+    key: String,
+    label: String,
+}
+
+#[derive(Template)]
+#[template(path = "team/lookups.html")]
+struct LookupsTemplate {
+    kinds: Vec<LookupKind>,
+    selected_kind: String,
+    selected_label: String,
+    values: Vec<LookupValue>,
+}
+
+#[derive(Deserialize)]
+pub struct LookupsQuery {
+    kind: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct LookupValueForm {
+    kind: String,
+    value: String,
+    label: String,
+    sort_order: Option<String>,
+}
+
+#[derive(Serialize)]
+pub struct LookupResponse {
+    value: String,
+    label: String,
+}
+
+fn require_admin(current_user: &CurrentUser) -> Result<(), StatusCode> {
+    if current_user.has_manage_roles {
+        Ok(())
+    } else {
+        Err(StatusCode::FORBIDDEN)
+    }
+}
+
+fn kind_label(kind: &str) -> Option<&'static str> {
+    LOOKUP_KINDS.iter().find(|(key, _)| *key == kind).map(|(_, label)| *label)
+}
+
+pub async fn lookups_page(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Query(query): Query<LookupsQuery>,
+) -> Result<Html<String>, StatusCode> {
+    require_admin(&current_user)?;
+
+    let selected_kind = query.kind.unwrap_or_else(|| LOOKUP_KINDS[0].0.to_string());
+    let selected_label = kind_label(&selected_kind).ok_or(StatusCode::NOT_FOUND)?.to_string();
+
+    let values = lookups::list(&db, &selected_kind, true).await.map_err(|e| {
+        tracing::error!("Error loading lookup values: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let template = LookupsTemplate {
+        kinds: LOOKUP_KINDS
+            .iter()
+            .map(|(key, label)| LookupKind {
+                key: key.to_string(),
+                label: label.to_string(),
+            })
+            .collect(),
+        selected_kind,
+        selected_label,
+        values,
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+// Adding a value that already exists updates its label and order and restores it
+pub async fn save_lookup_value(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Form(form): Form<LookupValueForm>,
+) -> Result<Redirect, StatusCode> {
+    require_admin(&current_user)?;
+
+    kind_label(&form.kind).ok_or(StatusCode::BAD_REQUEST)?;
+    let value = form.value.trim();
+    let label = match form.label.trim() {
+        "" => value,
+        label => label,
+    };
+    if value.is_empty() || value.len() > 100 {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let sort_order = match form.sort_order.as_deref().map(str::trim) {
+        None | Some("") => None,
+        Some(order) => Some(order.parse::<i32>().map_err(|_| StatusCode::BAD_REQUEST)?),
+    };
+
+    sqlx::query(
+        r#"
+        INSERT INTO lookup_values (kind, value, label, sort_order)
+        VALUES ($1, $2, $3,
+            COALESCE($4, (SELECT COALESCE(MAX(sort_order), 0) + 1 FROM lookup_values WHERE kind = $1)))
+        ON CONFLICT (tenant_id, kind, value) DO UPDATE SET
+            label = EXCLUDED.label,
+            sort_order = COALESCE($4, lookup_values.sort_order),
+            is_active = true
+        "#,
+    )
+    .bind(&form.kind)
+    .bind(value)
+    .bind(label)
+    .bind(sort_order)
+    .execute(&db)
+    .await
+    .map_err(|e| {
+        tracing::error!("Error saving lookup value: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let _ = create_audit_log(
+        &db,
+        &current_user,
+        "update".to_string(),
+        "lookup_value".to_string(),
+        None,
+        None,
+        Some(serde_json::json!({ "kind": form.kind, "value": value, "label": label, "sort_order": sort_order })),
+    )
+    .await;
+
+    Ok(Redirect::to(&format!("/team/lookups?kind={}", form.kind)))
+}
+
+// Values are retired rather than deleted so records using them keep displaying
+pub async fn toggle_lookup_value(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<Redirect, StatusCode> {
+    require_admin(&current_user)?;
+
+    let kind = sqlx::query_scalar::<_, String>(
+        "UPDATE lookup_values SET is_active = NOT is_active WHERE id = $1 RETURNING kind",
+    )
+    .bind(id)
+    .fetch_optional(&db)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+    .ok_or(StatusCode::NOT_FOUND)?;
+
+    Ok(Redirect::to(&format!("/team/lookups?kind={}", kind)))
+}
+
+// Active options for a dropdown, for scripts and API clients
+pub async fn api_lookups(
+    State(db): State<Database>,
+    cookies: Cookies,
+    Path(kind): Path<String>,
+    principal: Option<Extension<ApiPrincipal>>,
+) -> Result<Json<Vec<LookupResponse>>, StatusCode> {
+    if principal.is_none() {
+        get_current_user(cookies, &db)
+            .await
+            .ok_or(StatusCode::UNAUTHORIZED)?;
+    }
+
+    // Activity types have their own table now; clients still ask for them here
+    if kind == "activity_type" {
+        let values = activity_types::list(&db, false)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .into_iter()
+            .map(|activity_type| LookupResponse {
+                value: activity_type.code,
+                label: activity_type.name,
+            })
+            .collect();
+        return Ok(Json(values));
+    }
+    kind_label(&kind).ok_or(StatusCode::NOT_FOUND)?;
+
+    let values = lookups::list(&db, &kind, false)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .into_iter()
+        .map(|value| LookupResponse {
+            value: value.value,
+            label: value.label,
+        })
+        .collect();
+    Ok(Json(values))
+}
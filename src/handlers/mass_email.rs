@@ -0,0 +1,345 @@
+use axum::{
+    extract::{Form, Path, State},
+    http::StatusCode,
+    response::{Html, Redirect},
+};
+use askama::Template;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    database::Database,
+    middleware::{AuthUser, CurrentUser},
+    models::{Campaign, CustomerSegment, MassEmailRecipient, MassEmailReport, SegmentDisplay, LEAD_SOURCES, MERGE_FIELDS},
+    services::{lookups::{self, LookupOptions}, mass_email},
+};
+
+// Per-send counts; delivery status comes from the linked outbox row
+const MASS_EMAIL_REPORT_SQL: &str = r#"
+    SELECT
+        m.id, m.subject, m.status, s.name as segment_name, m.per_minute_limit,
+        COALESCE(m.created_at, NOW()) as created_at, m.completed_at,
+        COUNT(r.id) as total,
+        COUNT(r.id) FILTER (WHERE r.status = 'suppressed') as suppressed,
+        COUNT(r.id) FILTER (WHERE r.status = 'pending') as pending,
+        COUNT(r.id) FILTER (WHERE o.status = 'queued') as queued,
+        COUNT(r.id) FILTER (WHERE o.status = 'sent') as delivered,
+        COUNT(r.id) FILTER (WHERE o.status = 'failed') as failed,
+        COUNT(r.id) FILTER (WHERE o.status = 'bounced') as bounced,
+        COUNT(r.id) FILTER (WHERE EXISTS (
+            SELECT 1 FROM email_events ev WHERE ev.email_id = r.email_id AND ev.event_type = 'open'
+        )) as opened,
+        COUNT(r.id) FILTER (WHERE EXISTS (
+            SELECT 1 FROM email_events ev WHERE ev.email_id = r.email_id AND ev.event_type = 'click'
+        )) as clicked
+    FROM mass_emails m
+    LEFT JOIN customer_segments s ON s.id = m.segment_id
+    LEFT JOIN mass_email_recipients r ON r.mass_email_id = m.id
+    LEFT JOIN email_outbox o ON o.id = r.email_id
+"#;
+
+#[derive(Template)]
+#[template(path = "crm/segments.html")]
+struct SegmentsTemplate {
+    segments: Vec<SegmentDisplay>,
+    campaigns: Vec<Campaign>,
+    industries: LookupOptions,
+    lead_sources: &'static [(&'static str, &'static str)],
+    can_write: bool,
+}
+
+#[derive(Template)]
+#[template(path = "crm/mass_emails.html")]
+struct MassEmailsTemplate {
+    sends: Vec<MassEmailReport>,
+    can_write: bool,
+}
+
+#[derive(Template)]
+#[template(path = "crm/mass_email_form.html")]
+struct MassEmailFormTemplate {
+    segments: Vec<SegmentDisplay>,
+    merge_fields: Vec<String>,
+}
+
+#[derive(Template)]
+#[template(path = "crm/mass_email_report.html")]
+struct MassEmailReportTemplate {
+    send: MassEmailReport,
+    recipients: Vec<MassEmailRecipient>,
+    can_write: bool,
+}
+
+#[derive(Deserialize)]
+pub struct SegmentForm {
+    name: String,
+    description: Option<String>,
+    status: Option<String>,
+    industry: Option<String>,
+    campaign_id: Option<String>,
+    lead_source: Option<String>,
+    primary_contacts_only: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct MassEmailForm {
+    segment_id: Uuid,
+    subject: String,
+    body: String,
+    per_minute_limit: i32,
+}
+
+fn require(current_user: &CurrentUser, permission: &str) -> Result<(), StatusCode> {
+    if current_user.permissions.contains(&permission.to_string()) {
+        Ok(())
+    } else {
+        Err(StatusCode::FORBIDDEN)
+    }
+}
+
+fn non_empty(value: &Option<String>) -> Option<String> {
+    value
+        .as_deref()
+        .map(str::trim)
+        .filter(|v| !v.is_empty())
+        .map(str::to_string)
+}
+
+async fn load_segments(db: &Database) -> Result<Vec<SegmentDisplay>, StatusCode> {
+    let segments = sqlx::query_as::<_, CustomerSegment>("SELECT * FROM customer_segments ORDER BY name")
+        .fetch_all(db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let mut displays = Vec::with_capacity(segments.len());
+    for segment in segments {
+        let recipient_count = mass_email::count_segment_recipients(db, &segment)
+            .await
+            .map_err(|e| {
+                tracing::error!("Error counting segment recipients: {}", e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?;
+
+        displays.push(SegmentDisplay {
+            id: segment.id,
+            criteria: segment.criteria(),
+            name: segment.name,
+            description: segment.description.unwrap_or_default(),
+            recipient_count,
+        });
+    }
+
+    Ok(displays)
+}
+
+pub async fn segments_list(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+) -> Result<Html<String>, StatusCode> {
+    require(&current_user, "campaigns:read")?;
+
+    let campaigns = sqlx::query_as::<_, Campaign>("SELECT * FROM campaigns ORDER BY name")
+        .fetch_all(&db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let template = SegmentsTemplate {
+        segments: load_segments(&db).await?,
+        campaigns,
+        industries: lookups::options(&db, "industry")
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?,
+        lead_sources: LEAD_SOURCES,
+        can_write: current_user.permissions.contains(&"campaigns:write".to_string()),
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+pub async fn create_segment(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Form(form): Form<SegmentForm>,
+) -> Result<Redirect, StatusCode> {
+    require(&current_user, "campaigns:write")?;
+
+    if form.name.trim().is_empty() {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let campaign_id = non_empty(&form.campaign_id)
+        .map(|id| Uuid::parse_str(&id).map_err(|_| StatusCode::BAD_REQUEST))
+        .transpose()?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO customer_segments (
+            name, description, status, industry, campaign_id, lead_source, primary_contacts_only, created_by
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        "#,
+    )
+    .bind(form.name.trim())
+    .bind(non_empty(&form.description))
+    .bind(non_empty(&form.status))
+    .bind(non_empty(&form.industry))
+    .bind(campaign_id)
+    .bind(non_empty(&form.lead_source))
+    .bind(form.primary_contacts_only.is_some())
+    .bind(current_user.id)
+    .execute(&db)
+    .await
+    .map_err(|e| {
+        tracing::error!("Error creating segment: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    Ok(Redirect::to("/crm/segments"))
+}
+
+pub async fn delete_segment(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<Redirect, StatusCode> {
+    require(&current_user, "campaigns:write")?;
+
+    sqlx::query("DELETE FROM customer_segments WHERE id = $1")
+        .bind(id)
+        .execute(&db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Redirect::to("/crm/segments"))
+}
+
+pub async fn mass_emails_list(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+) -> Result<Html<String>, StatusCode> {
+    require(&current_user, "campaigns:read")?;
+
+    let query = format!("{} GROUP BY m.id, s.name ORDER BY m.created_at DESC", MASS_EMAIL_REPORT_SQL);
+    let sends = sqlx::query_as::<_, MassEmailReport>(&query)
+        .fetch_all(&db)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error loading mass emails: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    let template = MassEmailsTemplate {
+        sends,
+        can_write: current_user.permissions.contains(&"campaigns:write".to_string()),
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+pub async fn mass_email_form(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+) -> Result<Html<String>, StatusCode> {
+    require(&current_user, "campaigns:write")?;
+
+    let template = MassEmailFormTemplate {
+        segments: load_segments(&db).await?,
+        merge_fields: MERGE_FIELDS.iter().map(|f| f.to_string()).collect(),
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+pub async fn create_mass_email(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Form(form): Form<MassEmailForm>,
+) -> Result<Redirect, StatusCode> {
+    require(&current_user, "campaigns:write")?;
+
+    if form.subject.trim().is_empty() || form.body.trim().is_empty() || !(1..=1000).contains(&form.per_minute_limit) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let segment = sqlx::query_as::<_, CustomerSegment>("SELECT * FROM customer_segments WHERE id = $1")
+        .bind(form.segment_id)
+        .fetch_optional(&db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::BAD_REQUEST)?;
+
+    let id = mass_email::start_send(
+        &db,
+        &segment,
+        form.subject.trim(),
+        &form.body,
+        form.per_minute_limit,
+        current_user.id,
+    )
+    .await
+    .map_err(|e| {
+        tracing::error!("Error starting mass email: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    Ok(Redirect::to(&format!("/crm/mass-emails/{}", id)))
+}
+
+pub async fn mass_email_report(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<Html<String>, StatusCode> {
+    require(&current_user, "campaigns:read")?;
+
+    let query = format!("{} WHERE m.id = $1 GROUP BY m.id, s.name", MASS_EMAIL_REPORT_SQL);
+    let send = sqlx::query_as::<_, MassEmailReport>(&query)
+        .bind(id)
+        .fetch_optional(&db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let recipients = sqlx::query_as::<_, MassEmailRecipient>(
+        r#"
+        SELECT r.email_address,
+               CONCAT(ct.first_name, ' ', ct.last_name) as contact_name,
+               c.company_name,
+               r.status,
+               o.status as delivery_status,
+               r.queued_at
+        FROM mass_email_recipients r
+        LEFT JOIN contacts ct ON ct.id = r.contact_id
+        LEFT JOIN customers c ON c.id = ct.customer_id
+        LEFT JOIN email_outbox o ON o.id = r.email_id
+        WHERE r.mass_email_id = $1
+        ORDER BY r.email_address
+        LIMIT 500
+        "#,
+    )
+    .bind(id)
+    .fetch_all(&db)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let template = MassEmailReportTemplate {
+        send,
+        recipients,
+        can_write: current_user.permissions.contains(&"campaigns:write".to_string()),
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+// Stop a send; recipients not yet queued stay pending and are never sent
+pub async fn cancel_mass_email(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<Redirect, StatusCode> {
+    require(&current_user, "campaigns:write")?;
+
+    sqlx::query("UPDATE mass_emails SET status = 'cancelled', completed_at = NOW() WHERE id = $1 AND status = 'sending'")
+        .bind(id)
+        .execute(&db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Redirect::to(&format!("/crm/mass-emails/{}", id)))
+}
@@ -0,0 +1,192 @@
+use axum::{
+    extract::{Form, Path, State},
+    http::StatusCode,
+    response::{Html, Redirect},
+};
+use askama::Template;
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    database::Database,
+    middleware::{AuthUser, CurrentUser},
+    models::{metric_label, MetricAlert, MetricAlertRule, ALERT_DIRECTIONS, ALERT_METRICS},
+};
+
+pub struct AlertRuleRow {
+    pub rule: MetricAlertRule,
+    pub label: String,
+}
+
+pub struct AlertRow {
+    pub alert: MetricAlert,
+    pub label: String,
+}
+
+#[derive(Template)]
+#[template(path = "crm/alerts.html")]
+struct AlertsTemplate {
+    rules: Vec<AlertRuleRow>,
+    alerts: Vec<AlertRow>,
+    metrics: Vec<(String, String)>,
+    directions: Vec<String>,
+}
+
+#[derive(Deserialize)]
+pub struct AlertRuleForm {
+    metric: Option<String>,
+    direction: String,
+    threshold_percent: Decimal,
+    trailing_days: i32,
+    min_baseline: Decimal,
+    is_active: Option<String>,
+}
+
+fn require_manage(current_user: &CurrentUser) -> Result<(), StatusCode> {
+    if current_user.permissions.contains(&"alerts:manage".to_string()) {
+        Ok(())
+    } else {
+        Err(StatusCode::FORBIDDEN)
+    }
+}
+
+fn validate(form: &AlertRuleForm) -> Result<(), StatusCode> {
+    if !ALERT_DIRECTIONS.contains(&form.direction.as_str())
+        || form.threshold_percent <= Decimal::ZERO
+        || !(3..=90).contains(&form.trailing_days)
+        || form.min_baseline < Decimal::ZERO
+    {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    Ok(())
+}
+
+pub async fn alerts_page(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+) -> Result<Html<String>, StatusCode> {
+    require_manage(&current_user)?;
+
+    let rules = sqlx::query_as::<_, MetricAlertRule>("SELECT * FROM metric_alert_rules ORDER BY created_at")
+        .fetch_all(&db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .into_iter()
+        .map(|rule| AlertRuleRow { label: metric_label(&rule.metric), rule })
+        .collect();
+
+    let alerts = sqlx::query_as::<_, MetricAlert>(
+        r#"
+        SELECT a.id, r.metric, a.snapshot_date, a.value, a.baseline, a.deviation_percent
+        FROM metric_alerts a
+        JOIN metric_alert_rules r ON r.id = a.rule_id
+        ORDER BY a.snapshot_date DESC, a.created_at DESC
+        LIMIT 50
+        "#,
+    )
+    .fetch_all(&db)
+    .await
+    .map_err(|e| {
+        tracing::error!("Error loading metric alerts: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?
+    .into_iter()
+    .map(|alert| AlertRow { label: metric_label(&alert.metric), alert })
+    .collect();
+
+    let template = AlertsTemplate {
+        rules,
+        alerts,
+        metrics: ALERT_METRICS.iter().map(|(key, label)| (key.to_string(), label.to_string())).collect(),
+        directions: ALERT_DIRECTIONS.iter().map(|d| d.to_string()).collect(),
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+pub async fn create_alert_rule(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Form(form): Form<AlertRuleForm>,
+) -> Result<Redirect, StatusCode> {
+    require_manage(&current_user)?;
+    validate(&form)?;
+
+    let metric = form.metric.as_deref().unwrap_or_default();
+    if !ALERT_METRICS.iter().any(|(key, _)| *key == metric) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    sqlx::query(
+        r#"
+        INSERT INTO metric_alert_rules (metric, direction, threshold_percent, trailing_days, min_baseline, created_by)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        "#,
+    )
+    .bind(metric)
+    .bind(&form.direction)
+    .bind(form.threshold_percent)
+    .bind(form.trailing_days)
+    .bind(form.min_baseline)
+    .bind(current_user.id)
+    .execute(&db)
+    .await
+    .map_err(|e| {
+        tracing::error!("Error creating alert rule: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    Ok(Redirect::to("/crm/alerts"))
+}
+
+pub async fn update_alert_rule(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+    Form(form): Form<AlertRuleForm>,
+) -> Result<Redirect, StatusCode> {
+    require_manage(&current_user)?;
+    validate(&form)?;
+
+    let result = sqlx::query(
+        r#"
+        UPDATE metric_alert_rules
+        SET direction = $2, threshold_percent = $3, trailing_days = $4, min_baseline = $5, is_active = $6
+        WHERE id = $1
+        "#,
+    )
+    .bind(id)
+    .bind(&form.direction)
+    .bind(form.threshold_percent)
+    .bind(form.trailing_days)
+    .bind(form.min_baseline)
+    .bind(form.is_active.is_some())
+    .execute(&db)
+    .await
+    .map_err(|e| {
+        tracing::error!("Error updating alert rule: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    if result.rows_affected() == 0 {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    Ok(Redirect::to("/crm/alerts"))
+}
+
+pub async fn delete_alert_rule(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<Redirect, StatusCode> {
+    require_manage(&current_user)?;
+
+    sqlx::query("DELETE FROM metric_alert_rules WHERE id = $1")
+        .bind(id)
+        .execute(&db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Redirect::to("/crm/alerts"))
+}
@@ -5,6 +5,44 @@ pub mod team;
 pub mod expenses;
 pub mod dashboard; 
 pub mod inventory;
+pub mod profile;
+pub mod notifications;
+pub mod campaigns;
+pub mod lead_capture;
+pub mod email_tracking;
+pub mod email_webhooks;
+pub mod mass_email;
+pub mod metric_alerts;
+pub mod api_keys;
+pub mod api_logs;
+pub mod audit_log;
+pub mod webhooks;
+pub mod security;
+pub mod jobs;
+pub mod imports;
+pub mod lookups;
+pub mod invitations;
+pub mod impersonation;
+pub mod offboarding;
+pub mod approvals;
+pub mod setup;
+pub mod blanket_orders;
+pub mod numbering;
+pub mod documents;
+pub mod exchange_rates;
+pub mod cost_centers;
+pub mod projects;
+pub mod teams;
+pub mod saved_dashboards;
+pub mod contact_photos;
+pub mod search;
+pub mod sequences;
+pub mod warehouses;
+pub mod leads;
+pub mod status;
+pub mod tags;
+pub mod custom_fields;
+pub mod diagnostics;
 
 use axum::{
     extract::State,
@@ -12,11 +50,11 @@ use axum::{
     response::Html,
 };
 use askama::Template;
-use tower_cookies::Cookies;
 
 use crate::{
     database::Database,
-    middleware::get_current_user,
+    middleware::AuthUser,
+    services::dashboard::Widget,
 };
 
 #[derive(Template)]
@@ -28,17 +66,18 @@ struct DashboardTemplate {
     has_team_access: bool,
     has_inventory_access: bool,
     has_expenses_access: bool,
+    has_projects_access: bool,
     has_shipping_access: bool,
     has_api_access: bool,
+    approvals_waiting: Option<usize>,
+    variant_label: String,
+    widgets: Vec<Widget>,
 }
 
 pub async fn dashboard(
-    cookies: Cookies,
+    AuthUser(user): AuthUser,
     State(db): State<Database>,
 ) -> Result<Html<String>, StatusCode> {
-    let user = get_current_user(cookies, &db).await
-        .ok_or(StatusCode::UNAUTHORIZED)?;
-    
     // Get active customer count (prospect + active status)
     let customer_count = sqlx::query_scalar::<_, i64>(
         "SELECT COUNT(*) FROM customers WHERE status IN ('prospect', 'active')"
@@ -62,8 +101,12 @@ pub async fn dashboard(
         has_team_access: user.permissions.contains(&"team:read".to_string()),
         has_inventory_access: user.permissions.contains(&"inventory:read".to_string()),
         has_expenses_access: user.permissions.contains(&"expenses:read".to_string()),
+        has_projects_access: user.permissions.contains(&"projects:read".to_string()),
         has_shipping_access: user.permissions.contains(&"shipping:read".to_string()),
         has_api_access: user.permissions.contains(&"api:access".to_string()),
+        approvals_waiting: None,
+        variant_label: String::new(),
+        widgets: Vec::new(),
     };
     
     Ok(Html(template.render().unwrap()))
@@ -0,0 +1,57 @@
+use axum::{
+    extract::State,
+    http::StatusCode,
+    response::{Html, Redirect},
+};
+use askama::Template;
+
+use crate::{
+    database::Database,
+    middleware::{AuthUser, CurrentUser},
+    models::Notification,
+};
+
+#[derive(Template)]
+#[template(path = "notifications/notifications.html")]
+struct NotificationsTemplate {
+    current_user: CurrentUser,
+    notifications: Vec<Notification>,
+}
+
+pub async fn notifications_list(
+    AuthUser(current_user): AuthUser,
+    State(db): State<Database>,
+) -> Result<Html<String>, StatusCode> {
+    let notifications = sqlx::query_as::<_, Notification>(
+        r#"
+        SELECT id, user_id, message, link_url, COALESCE(is_read, false) as is_read, COALESCE(created_at, NOW()) as created_at
+        FROM notifications
+        WHERE user_id = $1
+        ORDER BY created_at DESC
+        LIMIT 100
+        "#,
+    )
+    .bind(current_user.id)
+    .fetch_all(&db)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let template = NotificationsTemplate {
+        current_user,
+        notifications,
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+pub async fn mark_all_read(
+    AuthUser(current_user): AuthUser,
+    State(db): State<Database>,
+) -> Result<Redirect, StatusCode> {
+    sqlx::query("UPDATE notifications SET is_read = true WHERE user_id = $1 AND is_read = false")
+        .bind(current_user.id)
+        .execute(&db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Redirect::to("/notifications"))
+}
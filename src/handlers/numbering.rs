@@ -0,0 +1,124 @@
+use axum::{
+    extract::{Form, Path, Query, State},
+    http::StatusCode,
+    response::{Html, Redirect},
+};
+use askama::Template;
+use chrono::Datelike;
+use serde::Deserialize;
+
+use crate::{
+    database::Database,
+    handlers::team::create_audit_log,
+    middleware::AuthUser,
+    models::NumberSequence,
+    services::numbering::{self, SequenceChanges},
+};
+
+#[derive(Template)]
+#[template(path = "team/numbering.html")]
+struct NumberingTemplate {
+    sequences: Vec<NumberSequence>,
+    year: i32,
+    saved: Option<String>,
+    error: Option<String>,
+}
+
+impl NumberingTemplate {
+    fn was_saved(&self, document_type: &str) -> bool {
+        self.saved.as_deref() == Some(document_type)
+    }
+
+    fn upcoming(&self, sequence: &NumberSequence) -> String {
+        sequence.upcoming(self.year)
+    }
+}
+
+#[derive(Deserialize)]
+pub struct NumberingQuery {
+    saved: Option<String>,
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct SequenceForm {
+    prefix: String,
+    padding: i32,
+    // Checkbox: only sent when ticked
+    reset_yearly: Option<String>,
+    next_number: Option<String>,
+}
+
+pub async fn numbering_page(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Query(query): Query<NumberingQuery>,
+) -> Result<Html<String>, StatusCode> {
+    if !current_user.has_manage_roles {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let sequences = numbering::list(&db).await.map_err(|e| {
+        tracing::error!("Error loading number sequences: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let template = NumberingTemplate {
+        sequences,
+        year: chrono::Utc::now().year(),
+        saved: query.saved,
+        error: query.error,
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+pub async fn update_number_sequence(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(document_type): Path<String>,
+    Form(form): Form<SequenceForm>,
+) -> Result<Redirect, StatusCode> {
+    if !current_user.has_manage_roles {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let next_number = match form.next_number.as_deref().map(str::trim) {
+        None | Some("") => None,
+        Some(next) => Some(next.parse::<i64>().map_err(|_| StatusCode::BAD_REQUEST)?),
+    };
+    let changes = SequenceChanges {
+        prefix: form.prefix.trim(),
+        padding: form.padding,
+        reset_yearly: form.reset_yearly.is_some(),
+        next_number,
+    };
+    let new_values = serde_json::json!({
+        "prefix": changes.prefix,
+        "padding": changes.padding,
+        "reset_yearly": changes.reset_yearly,
+        "next_number": changes.next_number,
+    });
+
+    let updated = numbering::update(&db, &document_type, changes, current_user.id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error updating number sequence: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    if let Err(error) = updated {
+        return Ok(Redirect::to(&format!("/team/numbering?error={}", urlencoding::encode(&error))));
+    }
+
+    let _ = create_audit_log(
+        &db,
+        &current_user,
+        "update".to_string(),
+        "number_sequence".to_string(),
+        None,
+        Some(serde_json::json!({ "document_type": document_type })),
+        Some(new_values),
+    )
+    .await;
+
+    Ok(Redirect::to(&format!("/team/numbering?saved={}", document_type)))
+}
@@ -0,0 +1,198 @@
+use axum::{
+    extract::{Form, Path, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Redirect, Response},
+};
+use askama::Template;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    database::Database,
+    handlers::team::{create_audit_log, manager_options},
+    middleware::{AuthUser, CurrentUser},
+    models::User,
+    services::offboarding::{self, Holdings},
+};
+
+#[derive(Template)]
+#[template(path = "team/offboard.html")]
+struct OffboardTemplate {
+    user: User,
+    holdings: Holdings,
+    successors: Vec<User>,
+    // Deleting rather than deactivating
+    delete: bool,
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct OffboardForm {
+    successor_id: Option<String>,
+}
+
+async fn load_user(db: &Database, current_user: &CurrentUser, user_id: Uuid) -> Result<User, StatusCode> {
+    if current_user.id == user_id {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    sqlx::query_as::<_, User>("SELECT * FROM users WHERE id = $1")
+        .bind(user_id)
+        .fetch_optional(db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+async fn render_offboard(db: &Database, user: User, delete: bool, error: Option<String>) -> Result<Html<String>, StatusCode> {
+    let holdings = offboarding::holdings(db, user.id).await.map_err(|e| {
+        tracing::error!("Error counting records owned by {}: {}", user.id, e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let template = OffboardTemplate {
+        successors: manager_options(db, Some(user.id)).await?,
+        user,
+        holdings,
+        delete,
+        error,
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+// Whoever takes over, once they've been checked. A successor is required
+// whenever the user still owns something.
+async fn pick_successor(
+    db: &Database,
+    user_id: Uuid,
+    form: &OffboardForm,
+) -> Result<Result<Option<Uuid>, String>, StatusCode> {
+    let successor_id = match form.successor_id.as_deref().map(str::trim) {
+        None | Some("") => None,
+        Some(value) => Some(Uuid::parse_str(value).map_err(|_| StatusCode::BAD_REQUEST)?),
+    };
+
+    let Some(successor_id) = successor_id else {
+        let holdings = offboarding::holdings(db, user_id)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        if holdings.is_empty() {
+            return Ok(Ok(None));
+        }
+        return Ok(Err("Choose who takes over this user's records.".to_string()));
+    };
+
+    let checked = offboarding::check_successor(db, user_id, successor_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    Ok(checked.map(|_| Some(successor_id)))
+}
+
+pub async fn deactivate_page(
+    AuthUser(current_user): AuthUser,
+    State(db): State<Database>,
+    Path(user_id): Path<Uuid>,
+) -> Result<Html<String>, StatusCode> {
+    if !current_user.has_team_write {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let user = load_user(&db, &current_user, user_id).await?;
+    render_offboard(&db, user, false, None).await
+}
+
+pub async fn deactivate_user(
+    AuthUser(current_user): AuthUser,
+    State(db): State<Database>,
+    Path(user_id): Path<Uuid>,
+    Form(form): Form<OffboardForm>,
+) -> Result<Response, StatusCode> {
+    if !current_user.has_team_write {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let user = load_user(&db, &current_user, user_id).await?;
+    let successor_id = match pick_successor(&db, user_id, &form).await? {
+        Ok(successor_id) => successor_id,
+        Err(message) => return Ok(render_offboard(&db, user, false, Some(message)).await?.into_response()),
+    };
+
+    let moved = offboarding::deactivate(&db, user_id, successor_id).await.map_err(|e| {
+        tracing::error!("Error deactivating user {}: {}", user_id, e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let _ = create_audit_log(
+        &db,
+        &current_user,
+        "deactivate".to_string(),
+        "user".to_string(),
+        Some(user_id),
+        Some(serde_json::json!({ "is_active": user.is_active })),
+        Some(serde_json::json!({ "is_active": false, "successor_id": successor_id, "reassigned": moved })),
+    ).await;
+
+    Ok(Redirect::to("/team/users").into_response())
+}
+
+pub async fn delete_page(
+    AuthUser(current_user): AuthUser,
+    State(db): State<Database>,
+    Path(user_id): Path<Uuid>,
+) -> Result<Html<String>, StatusCode> {
+    if !current_user.has_team_delete {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let user = load_user(&db, &current_user, user_id).await?;
+    render_offboard(&db, user, true, None).await
+}
+
+pub async fn delete_user(
+    AuthUser(current_user): AuthUser,
+    State(db): State<Database>,
+    Path(user_id): Path<Uuid>,
+    Form(form): Form<OffboardForm>,
+) -> Result<Response, StatusCode> {
+    if !current_user.has_team_delete {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let user = load_user(&db, &current_user, user_id).await?;
+    let successor_id = match pick_successor(&db, user_id, &form).await? {
+        Ok(successor_id) => successor_id,
+        Err(message) => return Ok(render_offboard(&db, user, true, Some(message)).await?.into_response()),
+    };
+
+    // With nothing to hand over, the records that name them as creator come to the admin
+    let moved = match offboarding::delete(&db, user_id, successor_id.unwrap_or(current_user.id)).await {
+        Ok(moved) => moved,
+        Err(sqlx::Error::Database(e)) if e.is_foreign_key_violation() => {
+            let message = format!(
+                "{} {} has expenses, stock movements or other history that must keep their name. Deactivate them instead.",
+                user.first_name, user.last_name
+            );
+            return Ok(render_offboard(&db, user, true, Some(message)).await?.into_response());
+        }
+        Err(e) => {
+            tracing::error!("Error deleting user {}: {}", user_id, e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    let _ = create_audit_log(
+        &db,
+        &current_user,
+        "delete".to_string(),
+        "user".to_string(),
+        Some(user_id),
+        Some(serde_json::json!({
+            "email": user.email,
+            "first_name": user.first_name,
+            "last_name": user.last_name
+        })),
+        Some(serde_json::json!({ "successor_id": successor_id, "reassigned": moved })),
+    ).await;
+
+    Ok(Redirect::to("/team/users").into_response())
+}
@@ -0,0 +1,502 @@
+use axum::{
+    extract::{Form, Path, Query, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Redirect, Response},
+};
+use askama::Template;
+use chrono::NaiveDate;
+use chrono_tz::{Tz, TZ_VARIANTS};
+use serde::Deserialize;
+use tower_cookies::{Cookie, Cookies};
+use uuid::Uuid;
+
+use crate::{
+    database::Database,
+    handlers::team::create_audit_log,
+    middleware::{AuthUser, CurrentUser},
+    models::{LoginEvent, SecurityEvent, Session, DIGEST_FREQUENCIES},
+    services::{
+        login_events,
+        out_of_office::{self, Absence, Assignee},
+        password_policy::{self, PasswordPolicy},
+        periods::WEEK_STARTS,
+        security, sessions,
+    },
+    utils::{hash_password, html::sanitize_html, verify_password},
+};
+
+// Room for a logo as a small embedded image
+const MAX_SIGNATURE_BYTES: usize = 64 * 1024;
+
+#[derive(Template)]
+#[template(path = "profile/profile.html")]
+struct ProfileTemplate {
+    current_user: CurrentUser,
+    timezones: Vec<String>,
+    week_starts: Vec<(String, String)>,
+    week_start: String,
+    digest_frequency: String,
+    security_events: Vec<SecurityEvent>,
+    reply_to: String,
+    // As stored, i.e. already sanitized
+    email_signature: String,
+    signature_preview: String,
+    absences: Vec<Absence>,
+    // Active users who can cover while this one is away
+    backups: Vec<Assignee>,
+    backup_user_id: Option<Uuid>,
+}
+
+#[derive(Template)]
+#[template(path = "profile/password.html")]
+struct PasswordTemplate {
+    current_user: CurrentUser,
+    password_policy: PasswordPolicy,
+    error: Option<String>,
+    changed: bool,
+}
+
+#[derive(Template)]
+#[template(path = "profile/security.html")]
+struct SecurityTemplate {
+    current_user: CurrentUser,
+    login_events: Vec<LoginEvent>,
+}
+
+#[derive(Template)]
+#[template(path = "profile/sessions.html")]
+struct SessionsTemplate {
+    current_user: CurrentUser,
+    sessions: Vec<Session>,
+    current_session: Option<Uuid>,
+}
+
+impl SessionsTemplate {
+    fn is_current(&self, session: &Session) -> bool {
+        self.current_session == Some(session.id)
+    }
+}
+
+#[derive(Deserialize)]
+pub struct DigestForm {
+    digest_frequency: String,
+}
+
+#[derive(Deserialize)]
+pub struct TimezoneForm {
+    timezone: String,
+    week_start: String,
+}
+
+#[derive(Deserialize)]
+pub struct PasswordQuery {
+    changed: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct PasswordForm {
+    current_password: String,
+    new_password: String,
+    confirm_password: String,
+}
+
+#[derive(Deserialize)]
+pub struct SignatureForm {
+    reply_to: Option<String>,
+    email_signature: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct BackupForm {
+    backup_user_id: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct OutOfOfficeForm {
+    starts_on: NaiveDate,
+    ends_on: NaiveDate,
+    note: Option<String>,
+}
+
+pub async fn profile_page(
+    AuthUser(current_user): AuthUser,
+    State(db): State<Database>,
+) -> Result<Html<String>, StatusCode> {
+    let (digest_frequency, week_start, backup_user_id, reply_to, email_signature) =
+        sqlx::query_as::<_, (String, String, Option<Uuid>, Option<String>, Option<String>)>(
+            "SELECT digest_frequency, week_start, backup_user_id, reply_to, email_signature FROM users WHERE id = $1"
+        )
+    .bind(current_user.id)
+    .fetch_one(&db)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let security_events = security::recent_events(&db, current_user.id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error loading security events: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    let absences = out_of_office::list(&db, current_user.id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error loading out of office periods: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    let backups = out_of_office::assignees(&db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let template = ProfileTemplate {
+        current_user,
+        timezones: TZ_VARIANTS.iter().map(|tz| tz.name().to_string()).collect(),
+        week_starts: WEEK_STARTS.iter().map(|(key, label)| (key.to_string(), label.to_string())).collect(),
+        week_start,
+        digest_frequency,
+        security_events,
+        reply_to: reply_to.unwrap_or_default(),
+        signature_preview: signature_preview(email_signature.as_deref().unwrap_or("")),
+        email_signature: email_signature.unwrap_or_default(),
+        absences,
+        backups,
+        backup_user_id,
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+pub async fn update_digest_preference(
+    AuthUser(current_user): AuthUser,
+    State(db): State<Database>,
+    Form(form): Form<DigestForm>,
+) -> Result<Redirect, StatusCode> {
+    if !DIGEST_FREQUENCIES.contains(&form.digest_frequency.as_str()) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    sqlx::query("UPDATE users SET digest_frequency = $1, updated_at = NOW() WHERE id = $2")
+        .bind(&form.digest_frequency)
+        .bind(current_user.id)
+        .execute(&db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Redirect::to("/profile"))
+}
+
+pub async fn update_timezone(
+    AuthUser(current_user): AuthUser,
+    State(db): State<Database>,
+    Form(form): Form<TimezoneForm>,
+) -> Result<Redirect, StatusCode> {
+    let timezone: Tz = form.timezone.trim().parse().map_err(|_| StatusCode::BAD_REQUEST)?;
+    if !WEEK_STARTS.iter().any(|(key, _)| *key == form.week_start) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    sqlx::query("UPDATE users SET timezone = $1, week_start = $2, updated_at = NOW() WHERE id = $3")
+        .bind(timezone.name())
+        .bind(&form.week_start)
+        .bind(current_user.id)
+        .execute(&db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Redirect::to("/profile"))
+}
+
+// Signatures are HTML, but one typed as plain text keeps its line breaks
+fn clean_signature(input: &str) -> String {
+    let input = input.trim();
+    if input.contains('<') {
+        sanitize_html(input)
+    } else {
+        sanitize_html(&input.replace("\r\n", "\n").replace('\n', "<br>"))
+    }
+}
+
+fn signature_preview(signature: &str) -> String {
+    if signature.is_empty() {
+        r#"<span class="text-gray-400">No signature</span>"#.to_string()
+    } else {
+        format!("<p>--</p>{}", signature)
+    }
+}
+
+pub async fn update_signature(
+    AuthUser(current_user): AuthUser,
+    State(db): State<Database>,
+    Form(form): Form<SignatureForm>,
+) -> Result<Redirect, StatusCode> {
+    let reply_to = form.reply_to.as_deref().map(str::trim).filter(|reply_to| !reply_to.is_empty());
+    if let Some(reply_to) = reply_to {
+        if reply_to.len() > 255 || reply_to.parse::<lettre::Address>().is_err() {
+            return Err(StatusCode::BAD_REQUEST);
+        }
+    }
+
+    let signature = clean_signature(form.email_signature.as_deref().unwrap_or(""));
+    if signature.len() > MAX_SIGNATURE_BYTES {
+        return Err(StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    sqlx::query("UPDATE users SET reply_to = $1, email_signature = NULLIF($2, ''), updated_at = NOW() WHERE id = $3")
+        .bind(reply_to)
+        .bind(&signature)
+        .bind(current_user.id)
+        .execute(&db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Redirect::to("/profile#signature"))
+}
+
+// What the signature will look like once saved, for the live preview
+pub async fn preview_signature(
+    _: AuthUser,
+    Form(form): Form<SignatureForm>,
+) -> Html<String> {
+    Html(signature_preview(&clean_signature(form.email_signature.as_deref().unwrap_or(""))))
+}
+
+pub async fn update_backup(
+    AuthUser(current_user): AuthUser,
+    State(db): State<Database>,
+    Form(form): Form<BackupForm>,
+) -> Result<Redirect, StatusCode> {
+    let backup_user_id = match form.backup_user_id.as_deref().map(str::trim) {
+        None | Some("") => None,
+        Some(value) => Some(Uuid::parse_str(value).map_err(|_| StatusCode::BAD_REQUEST)?),
+    };
+
+    if let Some(backup_user_id) = backup_user_id {
+        let active = sqlx::query_scalar::<_, bool>("SELECT is_active FROM users WHERE id = $1")
+            .bind(backup_user_id)
+            .fetch_optional(&db)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        if backup_user_id == current_user.id || active != Some(true) {
+            return Err(StatusCode::BAD_REQUEST);
+        }
+    }
+
+    sqlx::query("UPDATE users SET backup_user_id = $1, updated_at = NOW() WHERE id = $2")
+        .bind(backup_user_id)
+        .bind(current_user.id)
+        .execute(&db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Redirect::to("/profile#out-of-office"))
+}
+
+pub async fn add_out_of_office(
+    AuthUser(current_user): AuthUser,
+    State(db): State<Database>,
+    Form(form): Form<OutOfOfficeForm>,
+) -> Result<Redirect, StatusCode> {
+    if form.ends_on < form.starts_on {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    let note = form.note.as_deref().map(str::trim).filter(|note| !note.is_empty());
+
+    out_of_office::add(&db, current_user.id, form.starts_on, form.ends_on, note)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error adding out of office period: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    Ok(Redirect::to("/profile#out-of-office"))
+}
+
+pub async fn remove_out_of_office(
+    AuthUser(current_user): AuthUser,
+    State(db): State<Database>,
+    Path(id): Path<Uuid>,
+) -> Result<Redirect, StatusCode> {
+    let removed = out_of_office::remove(&db, current_user.id, id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if !removed {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    Ok(Redirect::to("/profile#out-of-office"))
+}
+
+async fn render_password(current_user: CurrentUser, db: &Database, error: Option<String>, changed: bool) -> Result<Html<String>, StatusCode> {
+    let password_policy = PasswordPolicy::load(db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let template = PasswordTemplate {
+        current_user,
+        password_policy,
+        error,
+        changed,
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+pub async fn password_page(
+    AuthUser(current_user): AuthUser,
+    State(db): State<Database>,
+    Query(query): Query<PasswordQuery>,
+) -> Result<Html<String>, StatusCode> {
+    render_password(current_user, &db, None, query.changed.is_some()).await
+}
+
+pub async fn change_password(
+    AuthUser(current_user): AuthUser,
+    cookies: Cookies,
+    State(db): State<Database>,
+    Form(form): Form<PasswordForm>,
+) -> Result<Response, StatusCode> {
+    let current_session = sessions::from_cookies(&cookies);
+
+    // Someone signed in as this user can't change their password for them
+    if current_user.impersonator.is_some() {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let password_hash = sqlx::query_scalar::<_, String>("SELECT password_hash FROM users WHERE id = $1")
+        .bind(current_user.id)
+        .fetch_one(&db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let error = if !verify_password(&form.current_password, &password_hash).unwrap_or(false) {
+        Some("Your current password is incorrect.".to_string())
+    } else if form.new_password != form.confirm_password {
+        Some("The new passwords don't match.".to_string())
+    } else {
+        password_policy::validate(&db, Some(current_user.id), &form.new_password)
+            .await
+            .map_err(|e| {
+                tracing::error!("Error checking password policy: {}", e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?
+            .err()
+    };
+    if let Some(message) = error {
+        return Ok(render_password(current_user, &db, Some(message), false).await?.into_response());
+    }
+
+    let password_hash = hash_password(&form.new_password)
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    sqlx::query("UPDATE users SET password_hash = $1, updated_at = NOW() WHERE id = $2")
+        .bind(&password_hash)
+        .bind(current_user.id)
+        .execute(&db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    if let Err(e) = password_policy::remember(&db, current_user.id, &password_hash).await {
+        tracing::error!("Error recording password history: {}", e);
+    }
+
+    // Anyone else holding a session (including whoever may have learned the
+    // old password) is signed out; this one stays
+    let sessions_ended = sessions::end_others(&db, current_user.id, current_session)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error ending sessions for {}: {}", current_user.id, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    if let Err(e) = security::record_change(&db, current_user.id, "password_changed", "Changed from your profile", current_user.id).await {
+        tracing::error!("Error recording password change: {}", e);
+    }
+
+    let _ = create_audit_log(
+        &db,
+        &current_user,
+        "change_password".to_string(),
+        "user".to_string(),
+        Some(current_user.id),
+        None,
+        Some(serde_json::json!({ "sessions_ended": sessions_ended })),
+    ).await;
+
+    Ok(Redirect::to("/profile/password?changed=1").into_response())
+}
+
+pub async fn security_page(
+    AuthUser(current_user): AuthUser,
+    State(db): State<Database>,
+) -> Result<Html<String>, StatusCode> {
+    let login_events = login_events::recent(&db, current_user.id, 100)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error loading login history: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    let template = SecurityTemplate {
+        current_user,
+        login_events,
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+pub async fn sessions_page(
+    AuthUser(current_user): AuthUser,
+    cookies: Cookies,
+    State(db): State<Database>,
+) -> Result<Html<String>, StatusCode> {
+    let current_session = sessions::from_cookies(&cookies);
+
+    let sessions = sessions::list_active(&db, current_user.id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error loading sessions: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    let template = SessionsTemplate {
+        current_user,
+        sessions,
+        current_session,
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+pub async fn revoke_session(
+    AuthUser(current_user): AuthUser,
+    cookies: Cookies,
+    State(db): State<Database>,
+    Path(id): Path<Uuid>,
+) -> Result<Redirect, StatusCode> {
+    let current_session = sessions::from_cookies(&cookies);
+    let ended = sessions::end(&db, current_user.id, id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if !ended {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    // Ending the session in use is the same as logging out
+    if current_session == Some(id) {
+        cookies.remove(Cookie::from("auth_token"));
+        return Ok(Redirect::to("/login"));
+    }
+    Ok(Redirect::to("/profile/sessions"))
+}
+
+pub async fn revoke_all_sessions(
+    AuthUser(current_user): AuthUser,
+    cookies: Cookies,
+    State(db): State<Database>,
+) -> Result<Redirect, StatusCode> {
+    sessions::end_all(&db, current_user.id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error ending sessions for {}: {}", current_user.id, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    cookies.remove(Cookie::from("auth_token"));
+    Ok(Redirect::to("/login"))
+}
@@ -0,0 +1,780 @@
+use axum::{
+    extract::{Form, Path, Query, State},
+    http::StatusCode,
+    response::{Html, Redirect, Response},
+};
+use askama::Template;
+use chrono::{NaiveDate, Utc};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    database::Database,
+    handlers::{
+        crm::{load_visible_customers, parse_optional_id, require_access},
+        team::create_audit_log,
+    },
+    middleware::{AuthUser, CurrentUser},
+    models::{
+        CostRate, Customer, ProjectBilling, ProjectSummary, TimeEntryDisplay, WipLine, WipTotals, PROJECT_STATUSES,
+    },
+    services::{
+        exchange_rates,
+        projects::{self, ProjectExpense, ProjectFields},
+        sharing::{self, Access, RecordKind},
+    },
+    utils::xlsx::{ColumnType, XlsxExport},
+};
+
+#[derive(Template)]
+#[template(path = "projects/projects.html")]
+struct ProjectsTemplate {
+    projects: Vec<ProjectSummary>,
+    status: String,
+    base_currency: String,
+    can_see_amounts: bool,
+    can_write: bool,
+    can_set_rates: bool,
+}
+
+// A deal offered on the project form
+#[derive(sqlx::FromRow)]
+struct DealOption {
+    id: Uuid,
+    title: String,
+    customer_name: String,
+    stage: String,
+}
+
+#[derive(Template)]
+#[template(path = "projects/project_form.html")]
+struct ProjectFormTemplate {
+    // None when creating
+    project_id: Option<Uuid>,
+    name: String,
+    customer_id: Option<Uuid>,
+    deal_id: Option<Uuid>,
+    status: String,
+    description: String,
+    estimated_cost: String,
+    customers: Vec<Customer>,
+    deals: Vec<DealOption>,
+    statuses: &'static [&'static str],
+    error: Option<String>,
+}
+
+impl ProjectFormTemplate {
+    fn is_customer(&self, id: &Uuid) -> bool {
+        self.customer_id.as_ref() == Some(id)
+    }
+
+    fn is_deal(&self, id: &Uuid) -> bool {
+        self.deal_id.as_ref() == Some(id)
+    }
+
+    fn is_status(&self, status: &str) -> bool {
+        self.status == status
+    }
+}
+
+#[derive(Template)]
+#[template(path = "projects/project_detail.html")]
+struct ProjectDetailTemplate {
+    project: ProjectSummary,
+    time_entries: Vec<TimeEntryDisplay>,
+    expenses: Vec<ProjectExpense>,
+    // Empty unless the viewer can see amounts
+    billings: Vec<ProjectBilling>,
+    base_currency: String,
+    today: NaiveDate,
+    current_user_id: Uuid,
+    can_see_amounts: bool,
+    can_write: bool,
+    can_remove_any: bool,
+    error: Option<String>,
+}
+
+impl ProjectDetailTemplate {
+    fn can_remove(&self, entry: &TimeEntryDisplay) -> bool {
+        self.can_write && (self.can_remove_any || entry.user_id == self.current_user_id)
+    }
+}
+
+#[derive(Template)]
+#[template(path = "projects/wip.html")]
+struct WipTemplate {
+    lines: Vec<WipLine>,
+    totals: WipTotals,
+    as_of: NaiveDate,
+    include_closed: bool,
+    base_currency: String,
+    can_export: bool,
+}
+
+#[derive(Template)]
+#[template(path = "projects/cost_rates.html")]
+struct CostRatesTemplate {
+    rates: Vec<CostRate>,
+    base_currency: String,
+    saved: Option<String>,
+    error: Option<String>,
+}
+
+impl CostRatesTemplate {
+    fn was_saved(&self, user_id: &Uuid) -> bool {
+        self.saved.as_deref() == Some(user_id.to_string().as_str())
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ProjectsQuery {
+    // "all" shows closed projects too
+    status: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ProjectFormQuery {
+    customer_id: Option<Uuid>,
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ErrorQuery {
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ProjectForm {
+    name: String,
+    customer_id: Uuid,
+    deal_id: Option<String>,
+    // Not on the create form; new projects are active
+    status: Option<String>,
+    description: Option<String>,
+    estimated_cost: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct TimeEntryForm {
+    work_date: NaiveDate,
+    hours: String,
+    description: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct BillingForm {
+    billed_on: NaiveDate,
+    billed_through: NaiveDate,
+    amount: String,
+    reference: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct WipQuery {
+    // Defaults to today
+    as_of: Option<NaiveDate>,
+    // "all" takes in closed projects
+    status: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct CostRatesQuery {
+    saved: Option<String>,
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct CostRateForm {
+    // Blank clears the rate
+    hourly_cost: String,
+}
+
+fn trimmed(value: &Option<String>) -> Option<&str> {
+    value.as_deref().map(str::trim).filter(|v| !v.is_empty())
+}
+
+// Blank is no amount at all
+fn parse_amount(value: &Option<String>) -> Result<Option<Decimal>, String> {
+    trimmed(value)
+        .map(|amount| amount.parse::<Decimal>().map_err(|_| "Amounts have to be numbers".to_string()))
+        .transpose()
+}
+
+async fn load_base_currency(db: &Database) -> Result<String, StatusCode> {
+    exchange_rates::base_currency(db).await.map_err(|e| {
+        tracing::error!("Error loading base currency: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
+
+// Deals on the customers the user can see
+async fn load_deal_options(db: &Database, user: &CurrentUser) -> Result<Vec<DealOption>, StatusCode> {
+    let scope = if sharing::is_scoped(user) {
+        format!("WHERE {}", sharing::visibility_condition(RecordKind::Deal, "d", 1))
+    } else {
+        String::new()
+    };
+
+    sqlx::query_as::<_, DealOption>(&format!(
+        r#"
+        SELECT d.id, d.title, c.company_name as customer_name, d.stage
+        FROM deals d
+        JOIN customers c ON c.id = d.customer_id
+        {}
+        ORDER BY c.company_name, d.title
+        "#,
+        scope
+    ))
+    .bind(user.id)
+    .fetch_all(db)
+    .await
+    .map_err(|e| {
+        tracing::error!("Error loading deals: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
+
+async fn load_summary(db: &Database, id: Uuid) -> Result<ProjectSummary, StatusCode> {
+    projects::summary(db, id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error loading project: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+pub async fn projects_list(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Query(query): Query<ProjectsQuery>,
+) -> Result<Html<String>, StatusCode> {
+    current_user.require("projects:read")?;
+
+    let status = match query.status.as_deref() {
+        Some("all") => "all",
+        Some("closed") => "closed",
+        _ => "active",
+    };
+    let projects = projects::list(&db, (status != "all").then_some(status)).await.map_err(|e| {
+        tracing::error!("Error loading projects: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let template = ProjectsTemplate {
+        projects,
+        status: status.to_string(),
+        base_currency: load_base_currency(&db).await?,
+        can_see_amounts: current_user.has_finance_read,
+        can_write: current_user.can("projects:write"),
+        can_set_rates: current_user.has_manage_roles && current_user.has_finance_read,
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+pub async fn project_form(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Query(query): Query<ProjectFormQuery>,
+) -> Result<Html<String>, StatusCode> {
+    current_user.require("projects:write")?;
+    if let Some(customer_id) = query.customer_id {
+        require_access(&db, &current_user, RecordKind::Customer, customer_id, Access::Read).await?;
+    }
+
+    let template = ProjectFormTemplate {
+        project_id: None,
+        name: String::new(),
+        customer_id: query.customer_id,
+        deal_id: None,
+        status: "active".to_string(),
+        description: String::new(),
+        estimated_cost: String::new(),
+        customers: load_visible_customers(&db, &current_user).await?,
+        deals: load_deal_options(&db, &current_user).await?,
+        statuses: PROJECT_STATUSES,
+        error: query.error,
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+pub async fn project_edit_form(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+    Query(query): Query<ErrorQuery>,
+) -> Result<Html<String>, StatusCode> {
+    current_user.require("projects:write")?;
+    let project = projects::get(&db, id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let template = ProjectFormTemplate {
+        project_id: Some(project.id),
+        name: project.name,
+        customer_id: Some(project.customer_id),
+        deal_id: project.deal_id,
+        status: project.status,
+        description: project.description.unwrap_or_default(),
+        estimated_cost: project.estimated_cost.map(|cost| cost.normalize().to_string()).unwrap_or_default(),
+        customers: load_visible_customers(&db, &current_user).await?,
+        deals: load_deal_options(&db, &current_user).await?,
+        statuses: PROJECT_STATUSES,
+        error: query.error,
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+pub async fn create_project(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Form(form): Form<ProjectForm>,
+) -> Result<Redirect, StatusCode> {
+    current_user.require("projects:write")?;
+    require_access(&db, &current_user, RecordKind::Customer, form.customer_id, Access::Read).await?;
+    let deal_id = parse_optional_id(&form.deal_id)?;
+    let (name, description) = (form.name.trim(), trimmed(&form.description));
+
+    let created = match parse_amount(&form.estimated_cost) {
+        Ok(estimated_cost) => {
+            let fields = ProjectFields { name, customer_id: form.customer_id, deal_id, description, estimated_cost };
+            projects::create(&db, &fields, current_user.id).await.map_err(|e| {
+                tracing::error!("Error creating project: {}", e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?
+        }
+        Err(error) => Err(error),
+    };
+    let id = match created {
+        Ok(id) => id,
+        Err(error) => {
+            return Ok(Redirect::to(&format!(
+                "/projects/new?customer_id={}&error={}",
+                form.customer_id,
+                urlencoding::encode(&error)
+            )))
+        }
+    };
+
+    let _ = create_audit_log(
+        &db,
+        &current_user,
+        "create".to_string(),
+        "project".to_string(),
+        Some(id),
+        None,
+        Some(serde_json::json!({ "name": name, "customer_id": form.customer_id, "deal_id": deal_id, "estimated_cost": form.estimated_cost })),
+    )
+    .await;
+
+    Ok(Redirect::to(&format!("/projects/{}", id)))
+}
+
+pub async fn update_project(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+    Form(form): Form<ProjectForm>,
+) -> Result<Redirect, StatusCode> {
+    current_user.require("projects:write")?;
+    let old = projects::get(&db, id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    if form.customer_id != old.customer_id {
+        require_access(&db, &current_user, RecordKind::Customer, form.customer_id, Access::Read).await?;
+    }
+    let deal_id = parse_optional_id(&form.deal_id)?;
+    let (name, description) = (form.name.trim(), trimmed(&form.description));
+    let status = form.status.as_deref().unwrap_or(&old.status);
+    let estimated_cost = match parse_amount(&form.estimated_cost) {
+        Ok(estimated_cost) => estimated_cost,
+        Err(error) => return Ok(Redirect::to(&format!("/projects/{}/edit?error={}", id, urlencoding::encode(&error)))),
+    };
+
+    let fields = ProjectFields { name, customer_id: form.customer_id, deal_id, description, estimated_cost };
+
+    let updated = projects::update(&db, id, &fields, status).await.map_err(|e| {
+        tracing::error!("Error updating project: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    if let Err(error) = updated {
+        return Ok(Redirect::to(&format!("/projects/{}/edit?error={}", id, urlencoding::encode(&error))));
+    }
+
+    let _ = create_audit_log(
+        &db,
+        &current_user,
+        "update".to_string(),
+        "project".to_string(),
+        Some(id),
+        Some(serde_json::json!({ "name": old.name, "customer_id": old.customer_id, "deal_id": old.deal_id, "status": old.status, "estimated_cost": old.estimated_cost })),
+        Some(serde_json::json!({ "name": name, "customer_id": form.customer_id, "deal_id": deal_id, "status": status, "estimated_cost": estimated_cost })),
+    )
+    .await;
+
+    Ok(Redirect::to(&format!("/projects/{}", id)))
+}
+
+// Time, expenses and, for finance, what the project made or lost
+pub async fn project_detail(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+    Query(query): Query<ErrorQuery>,
+) -> Result<Html<String>, StatusCode> {
+    current_user.require("projects:read")?;
+    let project = load_summary(&db, id).await?;
+
+    let load = async { Ok::<_, sqlx::Error>((projects::time_entries(&db, id).await?, projects::expenses(&db, id).await?)) };
+    let (time_entries, expenses) = load.await.map_err(|e| {
+        tracing::error!("Error loading project costs: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    let billings = if current_user.has_finance_read {
+        projects::billings(&db, id).await.map_err(|e| {
+            tracing::error!("Error loading project billings: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+    } else {
+        Vec::new()
+    };
+
+    let template = ProjectDetailTemplate {
+        project,
+        time_entries,
+        expenses,
+        billings,
+        base_currency: load_base_currency(&db).await?,
+        today: Utc::now().date_naive(),
+        current_user_id: current_user.id,
+        can_see_amounts: current_user.has_finance_read,
+        can_write: current_user.can("projects:write"),
+        can_remove_any: current_user.has_manage_roles,
+        error: query.error,
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+// Logged for the current user at their current cost rate
+pub async fn log_time(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+    Form(form): Form<TimeEntryForm>,
+) -> Result<Redirect, StatusCode> {
+    current_user.require("projects:write")?;
+
+    let Ok(hours) = form.hours.trim().parse::<Decimal>() else {
+        return Ok(Redirect::to(&format!("/projects/{}?error={}", id, urlencoding::encode("Hours have to be a number"))));
+    };
+    let description = trimmed(&form.description);
+
+    let logged = projects::log_time(&db, id, current_user.id, form.work_date, hours, description)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error logging time: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    let entry_id = match logged {
+        Ok(entry_id) => entry_id,
+        Err(error) => return Ok(Redirect::to(&format!("/projects/{}?error={}", id, urlencoding::encode(&error)))),
+    };
+
+    let _ = create_audit_log(
+        &db,
+        &current_user,
+        "create".to_string(),
+        "time_entry".to_string(),
+        Some(entry_id),
+        None,
+        Some(serde_json::json!({ "project_id": id, "work_date": form.work_date, "hours": hours })),
+    )
+    .await;
+
+    Ok(Redirect::to(&format!("/projects/{}", id)))
+}
+
+// People remove their own entries; admins can remove anyone's
+pub async fn delete_time_entry(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path((id, entry_id)): Path<(Uuid, Uuid)>,
+) -> Result<Redirect, StatusCode> {
+    current_user.require("projects:write")?;
+    let owner = projects::time_entry_owner(&db, id, entry_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    if owner != current_user.id && !current_user.has_manage_roles {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    projects::delete_time(&db, entry_id).await.map_err(|e| {
+        tracing::error!("Error deleting time entry: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let _ = create_audit_log(
+        &db,
+        &current_user,
+        "delete".to_string(),
+        "time_entry".to_string(),
+        Some(entry_id),
+        Some(serde_json::json!({ "project_id": id, "user_id": owner })),
+        None,
+    )
+    .await;
+
+    Ok(Redirect::to(&format!("/projects/{}", id)))
+}
+
+// Invoices come from the accounting system; this records what they covered
+pub async fn add_billing(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+    Form(form): Form<BillingForm>,
+) -> Result<Redirect, StatusCode> {
+    current_user.require("projects:write")?;
+    if !current_user.has_finance_read {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let reference = trimmed(&form.reference);
+    let added = match parse_amount(&Some(form.amount)) {
+        Ok(Some(amount)) => projects::add_billing(&db, id, form.billed_on, form.billed_through, amount, reference, current_user.id)
+            .await
+            .map_err(|e| {
+                tracing::error!("Error recording billing: {}", e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?
+            .map(|billing_id| (billing_id, amount)),
+        Ok(None) => Err("The amount is required".to_string()),
+        Err(error) => Err(error),
+    };
+    let (billing_id, amount) = match added {
+        Ok(added) => added,
+        Err(error) => return Ok(Redirect::to(&format!("/projects/{}?error={}", id, urlencoding::encode(&error)))),
+    };
+
+    let _ = create_audit_log(
+        &db,
+        &current_user,
+        "create".to_string(),
+        "project_billing".to_string(),
+        Some(billing_id),
+        None,
+        Some(serde_json::json!({
+            "project_id": id,
+            "billed_on": form.billed_on,
+            "billed_through": form.billed_through,
+            "amount": amount,
+            "reference": reference,
+        })),
+    )
+    .await;
+
+    Ok(Redirect::to(&format!("/projects/{}", id)))
+}
+
+pub async fn delete_billing(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path((id, billing_id)): Path<(Uuid, Uuid)>,
+) -> Result<Redirect, StatusCode> {
+    current_user.require("projects:write")?;
+    if !current_user.has_finance_read {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let deleted = projects::delete_billing(&db, id, billing_id).await.map_err(|e| {
+        tracing::error!("Error deleting billing: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    if !deleted {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let _ = create_audit_log(
+        &db,
+        &current_user,
+        "delete".to_string(),
+        "project_billing".to_string(),
+        Some(billing_id),
+        Some(serde_json::json!({ "project_id": id })),
+        None,
+    )
+    .await;
+
+    Ok(Redirect::to(&format!("/projects/{}", id)))
+}
+
+async fn load_wip(db: &Database, query: &WipQuery) -> Result<(Vec<WipLine>, NaiveDate, bool), StatusCode> {
+    let as_of = query.as_of.unwrap_or_else(|| Utc::now().date_naive());
+    let include_closed = query.status.as_deref() == Some("all");
+    let lines = projects::wip(db, as_of, include_closed).await.map_err(|e| {
+        tracing::error!("Error loading work in progress: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    Ok((lines, as_of, include_closed))
+}
+
+// Unbilled costs and earned against billed revenue per project, for closing
+// the month
+pub async fn wip_report(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Query(query): Query<WipQuery>,
+) -> Result<Html<String>, StatusCode> {
+    current_user.require("projects:read")?;
+    if !current_user.has_finance_read {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let (lines, as_of, include_closed) = load_wip(&db, &query).await?;
+
+    let template = WipTemplate {
+        totals: WipTotals::of(&lines),
+        lines,
+        as_of,
+        include_closed,
+        base_currency: load_base_currency(&db).await?,
+        can_export: current_user.has_data_export,
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+pub async fn wip_export(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Query(query): Query<WipQuery>,
+) -> Result<Response, StatusCode> {
+    current_user.require("projects:read")?;
+    if !current_user.has_finance_read || !current_user.has_data_export {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let (lines, as_of, include_closed) = load_wip(&db, &query).await?;
+    let base_currency = load_base_currency(&db).await?;
+
+    let mut export = XlsxExport::new("Work in Progress", &[
+        ("Project", ColumnType::Text),
+        ("Customer", ColumnType::Text),
+        ("Status", ColumnType::Text),
+        ("Contract Value", ColumnType::Currency),
+        ("Estimated Cost", ColumnType::Currency),
+        ("Hours", ColumnType::Currency),
+        ("Time Cost", ColumnType::Currency),
+        ("Expenses", ColumnType::Currency),
+        ("Cost to Date", ColumnType::Currency),
+        ("Complete", ColumnType::Percent),
+        ("Recognized Revenue", ColumnType::Currency),
+        ("Billed", ColumnType::Currency),
+        ("Billed Through", ColumnType::Date),
+        ("Unbilled Revenue", ColumnType::Currency),
+        ("Deferred Revenue", ColumnType::Currency),
+        ("Unbilled Hours", ColumnType::Currency),
+        ("Unbilled Time Cost", ColumnType::Currency),
+        ("Unbilled Expenses", ColumnType::Currency),
+        ("Margin", ColumnType::Currency),
+    ]);
+    export.filter("As Of", as_of.to_string());
+    export.filter("Projects", if include_closed { "Active and closed" } else { "Active" });
+    export.filter("Currency", base_currency);
+
+    for line in &lines {
+        export.row(vec![
+            line.name.as_str().into(),
+            line.customer_name.as_str().into(),
+            line.status.as_str().into(),
+            line.contract_value.into(),
+            line.estimated_cost.into(),
+            line.hours.into(),
+            line.time_cost.into(),
+            line.expense_cost.into(),
+            line.cost_to_date().into(),
+            line.completion().into(),
+            line.recognized_revenue().into(),
+            line.billed.into(),
+            line.billed_through.into(),
+            line.unbilled_revenue().into(),
+            line.deferred_revenue().into(),
+            line.unbilled_hours.into(),
+            line.unbilled_time_cost.into(),
+            line.unbilled_expenses.into(),
+            line.margin().into(),
+        ]);
+    }
+
+    let generated_by = format!("{} {} <{}>", current_user.first_name, current_user.last_name, current_user.email);
+    Ok(export.into_response(&format!("wip-{}.xlsx", as_of), &generated_by))
+}
+
+pub async fn cost_rates_page(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Query(query): Query<CostRatesQuery>,
+) -> Result<Html<String>, StatusCode> {
+    if !current_user.has_manage_roles || !current_user.has_finance_read {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let rates = projects::cost_rates(&db).await.map_err(|e| {
+        tracing::error!("Error loading cost rates: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let template = CostRatesTemplate {
+        rates,
+        base_currency: load_base_currency(&db).await?,
+        saved: query.saved,
+        error: query.error,
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+pub async fn update_cost_rate(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(user_id): Path<Uuid>,
+    Form(form): Form<CostRateForm>,
+) -> Result<Redirect, StatusCode> {
+    if !current_user.has_manage_roles || !current_user.has_finance_read {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let hourly_cost = match form.hourly_cost.trim() {
+        "" => None,
+        rate => match rate.parse::<Decimal>() {
+            Ok(rate) if rate >= Decimal::ZERO && rate.normalize().scale() <= 2 && rate < Decimal::from(10_000_000_000u64) => Some(rate),
+            _ => {
+                return Ok(Redirect::to(&format!(
+                    "/projects/cost-rates?error={}",
+                    urlencoding::encode("The hourly cost has to be a positive amount with at most two decimals")
+                )))
+            }
+        },
+    };
+
+    let updated = projects::set_cost_rate(&db, user_id, hourly_cost).await.map_err(|e| {
+        tracing::error!("Error updating cost rate: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    if !updated {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let _ = create_audit_log(
+        &db,
+        &current_user,
+        "update".to_string(),
+        "cost_rate".to_string(),
+        Some(user_id),
+        None,
+        Some(serde_json::json!({ "hourly_cost": hourly_cost })),
+    )
+    .await;
+
+    Ok(Redirect::to(&format!("/projects/cost-rates?saved={}", user_id)))
+}
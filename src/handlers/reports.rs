@@ -1,35 +1,121 @@
 use axum::{
-    extract::{Query, State},
+    extract::{Form, Query, State},
     http::StatusCode,
-    response::Html,
+    response::{Html, IntoResponse, Redirect, Response},
 };
 use askama::Template;
 use serde::Deserialize;
-use chrono::{DateTime, Utc, NaiveDate};
+use chrono::{DateTime, Datelike, Months, Utc, NaiveDate};
+use rust_decimal::{prelude::ToPrimitive, Decimal};
+use serde_json::json;
 use uuid::Uuid;
-use sqlx::Row;
+use sqlx::{postgres::PgArguments, query::Query as SqlQuery, PgConnection, Postgres, Row, Transaction};
+
 
 use crate::{
     database::Database,
-    models::{Customer, User},
+    handlers::team::create_audit_log,
+    middleware::{request_id, AuthUser, CurrentUser},
+    models::{ActivityType, Customer, User},
+    services::{
+        activity_types, archive, hierarchy,
+        periods::{self, PeriodContext, PeriodPicker},
+        report_limits::{self, Refusal, Slot},
+        reporting_views::{self, StageTotal, WarehouseValuation},
+    },
+    utils::{
+        pivot::Pivot,
+        timezone::format_local,
+        xlsx::{Cell, ColumnType, XlsxExport},
+    },
 };
 
+// Exports skip the on-screen 100 row cap but stay bounded
+const EXPORT_ROW_LIMIT: i64 = 50_000;
+
 #[derive(Template)]
 #[template(path = "crm/reports.html")]
 struct ReportsTemplate {
     reports: Vec<ReportEntry>,
     customers: Vec<Customer>,
     users: Vec<User>,
+    activity_types: Vec<ActivityType>,
     selected_customer: Option<Uuid>,
     selected_user: Option<Uuid>,
-    selected_date_from: String,
-    selected_date_to: String,
+    selected_type: String,
+    period: PeriodPicker,
+    my_team: bool,
+    show_team_filter: bool,
+    outcome_metrics: OutcomeMetrics,
+    can_export: bool,
+}
+
+#[derive(Template)]
+#[template(path = "crm/report_limit.html")]
+struct ReportLimitTemplate {
+    message: &'static str,
+    request_id: Option<String>,
+}
+
+// Why a report page or export wasn't produced
+pub enum ReportError {
+    Status(StatusCode),
+    Refused(Refusal),
+}
+
+impl From<StatusCode> for ReportError {
+    fn from(status: StatusCode) -> Self {
+        Self::Status(status)
+    }
+}
+
+impl IntoResponse for ReportError {
+    fn into_response(self) -> Response {
+        match self {
+            Self::Status(status) => status.into_response(),
+            Self::Refused(refusal) => {
+                let status = match refusal {
+                    Refusal::Busy => StatusCode::TOO_MANY_REQUESTS,
+                    Refusal::TooSlow => StatusCode::SERVICE_UNAVAILABLE,
+                };
+                let template = ReportLimitTemplate { message: refusal.message(), request_id: request_id::current() };
+                (status, Html(template.render().unwrap())).into_response()
+            }
+        }
+    }
+}
+
+// Timed out queries ask for narrower filters; anything else is logged
+fn query_failed(report: &'static str) -> impl Fn(sqlx::Error) -> ReportError {
+    move |e| {
+        if report_limits::timed_out(&e) {
+            ReportError::Refused(Refusal::TooSlow)
+        } else {
+            tracing::error!("Error loading {}: {}", report, e);
+            ReportError::Status(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+// Takes one of the user's report slots and a transaction whose queries are
+// cancelled if they run too long. The heavy queries go through the
+// transaction; lookups for the filters stay on the pool.
+async fn start_report(
+    db: &Database,
+    user_id: Option<Uuid>,
+) -> Result<(Option<Slot>, Transaction<'static, Postgres>), ReportError> {
+    let slot = user_id.map(report_limits::claim).transpose().map_err(ReportError::Refused)?;
+    let tx = report_limits::begin(db).await.map_err(query_failed("report"))?;
+    Ok((slot, tx))
 }
 
 #[derive(Deserialize)]
 pub struct ReportFilters {
     customer_id: Option<String>,
     user_id: Option<String>,
+    activity_type: Option<String>,
+    team: Option<String>,
+    period: Option<String>,
     date_from: Option<String>,
     date_to: Option<String>,
 }
@@ -43,74 +129,188 @@ pub struct ReportEntry {
     pub user_name: String,
     pub customer_name: String,
     pub activity_date: DateTime<Utc>,
+    pub type_name: String,
+    pub icon: String,
+}
+
+#[derive(Debug)]
+pub struct OutcomeCount {
     pub activity_type: String,
+    pub label: String,
+    pub count: i64,
 }
 
-pub async fn reports_list(
-    query: Query<ReportFilters>,
-    State(db): State<Database>,
-) -> Result<Html<String>, StatusCode> {
-    // Parse customer_id if provided and not empty
-    let customer_id = if let Some(customer_str) = &query.customer_id {
-        if customer_str.trim().is_empty() {
-            None
-        } else {
-            Some(Uuid::parse_str(customer_str).map_err(|_| StatusCode::BAD_REQUEST)?)
-        }
-    } else {
-        None
-    };
+// Connect rate and meetings held, from completed activities with a recorded outcome
+#[derive(Debug, Default)]
+pub struct OutcomeMetrics {
+    pub calls_logged: i64,
+    pub calls_connected: i64,
+    pub connect_rate: i32,
+    pub meetings_logged: i64,
+    pub meetings_held: i64,
+    pub held_rate: i32,
+    pub breakdown: Vec<OutcomeCount>,
+}
 
-    // Parse user_id if provided and not empty
-    let user_id = if let Some(user_str) = &query.user_id {
-        if user_str.trim().is_empty() {
-            None
-        } else {
-            Some(Uuid::parse_str(user_str).map_err(|_| StatusCode::BAD_REQUEST)?)
+fn percent(part: i64, whole: i64) -> i32 {
+    if whole == 0 { 0 } else { ((part as f64 / whole as f64) * 100.0).round() as i32 }
+}
+
+// Bind the filter values in the same order the conditions were built
+fn bind_filters<'q>(
+    mut query: SqlQuery<'q, Postgres, PgArguments>,
+    customer_id: Option<Uuid>,
+    user_id: Option<Uuid>,
+    activity_type: Option<String>,
+    team_ids: Option<Vec<Uuid>>,
+    date_from: Option<NaiveDate>,
+    date_to: Option<NaiveDate>,
+) -> SqlQuery<'q, Postgres, PgArguments> {
+    if let Some(cid) = customer_id {
+        query = query.bind(cid);
+    }
+    if let Some(uid) = user_id {
+        query = query.bind(uid);
+    }
+    if let Some(activity_type) = activity_type {
+        query = query.bind(activity_type);
+    }
+    if let Some(ids) = team_ids {
+        query = query.bind(ids);
+    }
+    if let Some(date_from) = date_from {
+        query = query.bind(date_from);
+    }
+    if let Some(date_to) = date_to {
+        query = query.bind(date_to);
+    }
+    query
+}
+
+// "My team" narrows a report to the current user and everyone reporting up to them
+async fn resolve_team(
+    db: &Database,
+    team: Option<&str>,
+    current_user_id: Option<Uuid>,
+) -> Result<Option<Vec<Uuid>>, StatusCode> {
+    match team.map(str::trim) {
+        None | Some("") => Ok(None),
+        Some("mine") => {
+            let user_id = current_user_id.ok_or(StatusCode::UNAUTHORIZED)?;
+            hierarchy::team_member_ids(db, user_id)
+                .await
+                .map(Some)
+                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
         }
-    } else {
-        None
-    };
+        Some(_) => Err(StatusCode::BAD_REQUEST),
+    }
+}
 
-    // Get all customers for filter dropdown
-    let customers = sqlx::query_as::<_, Customer>(
-        "SELECT * FROM customers ORDER BY company_name"
-    )
-    .fetch_all(&db)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+// Dates for the period preset picked in a filter form, if any
+async fn period_range(
+    db: &Database,
+    current_user: Option<&CurrentUser>,
+    period: Option<&str>,
+) -> Result<Option<(NaiveDate, NaiveDate)>, StatusCode> {
+    if period.is_none_or(|key| key.trim().is_empty()) {
+        return Ok(None);
+    }
+    let current_user = current_user.ok_or(StatusCode::UNAUTHORIZED)?;
+    let ctx = PeriodContext::for_user(db, current_user).await.map_err(|e| {
+        tracing::error!("Error loading reporting settings: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    periods::resolve(period, &ctx).map_err(|_| StatusCode::BAD_REQUEST)
+}
 
-    // Get all users for filter dropdown
-    let users = sqlx::query_as::<_, User>(
-        "SELECT * FROM users ORDER BY first_name, last_name"
-    )
-    .fetch_all(&db)
-    .await
-    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+// Filters parsed once and shared by the report page and its export
+struct ParsedFilters {
+    customer_id: Option<Uuid>,
+    user_id: Option<Uuid>,
+    activity_type: Option<String>,
+    team_ids: Option<Vec<Uuid>>,
+    date_from: Option<NaiveDate>,
+    date_to: Option<NaiveDate>,
+}
 
-    // Build dynamic query based on filters
-    let mut conditions = Vec::new();
-    let mut bind_count = 1;
+impl ParsedFilters {
+    fn parse(query: &ReportFilters) -> Result<Self, StatusCode> {
+        let parse_uuid = |value: &Option<String>| {
+            value.as_deref()
+                .filter(|v| !v.trim().is_empty())
+                .map(Uuid::parse_str)
+                .transpose()
+                .map_err(|_| StatusCode::BAD_REQUEST)
+        };
+        let parse_date = |value: &Option<String>| {
+            value.as_deref()
+                .filter(|d| !d.trim().is_empty())
+                .map(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d"))
+                .transpose()
+                .map_err(|_| StatusCode::BAD_REQUEST)
+        };
 
-    if customer_id.is_some() {
-        conditions.push(format!("a.customer_id = ${}", bind_count));
-        bind_count += 1;
+        Ok(Self {
+            customer_id: parse_uuid(&query.customer_id)?,
+            user_id: parse_uuid(&query.user_id)?,
+            activity_type: query.activity_type.as_deref()
+                .map(str::trim)
+                .filter(|t| !t.is_empty())
+                .map(str::to_string),
+            team_ids: None,
+            date_from: parse_date(&query.date_from)?,
+            date_to: parse_date(&query.date_to)?,
+        })
     }
 
-    if user_id.is_some() {
-        conditions.push(format!("a.created_by = ${}", bind_count));
-        bind_count += 1;
-    }
+    // Build dynamic conditions; bind_filters binds the values in the same order
+    fn conditions(&self) -> Vec<String> {
+        let mut conditions = Vec::new();
+        let mut bind_count = 1;
+
+        if self.customer_id.is_some() {
+            conditions.push(format!("a.customer_id = ${}", bind_count));
+            bind_count += 1;
+        }
+
+        if self.user_id.is_some() {
+            conditions.push(format!("a.created_by = ${}", bind_count));
+            bind_count += 1;
+        }
+
+        if self.activity_type.is_some() {
+            conditions.push(format!("a.activity_type = ${}", bind_count));
+            bind_count += 1;
+        }
+
+        if self.team_ids.is_some() {
+            conditions.push(format!("a.created_by = ANY(${})", bind_count));
+            bind_count += 1;
+        }
+
+        if self.date_from.is_some() {
+            conditions.push(format!("DATE(a.activity_date) >= ${}", bind_count));
+            bind_count += 1;
+        }
 
-    if query.date_from.is_some() && !query.date_from.as_ref().unwrap().trim().is_empty() {
-        conditions.push(format!("DATE(a.activity_date) >= ${}", bind_count));
-        bind_count += 1;
+        if self.date_to.is_some() {
+            conditions.push(format!("DATE(a.activity_date) <= ${}", bind_count));
+        }
+
+        conditions
     }
 
-    if query.date_to.is_some() && !query.date_to.as_ref().unwrap().trim().is_empty() {
-        conditions.push(format!("DATE(a.activity_date) <= ${}", bind_count));
+    fn bind<'q>(&self, query: SqlQuery<'q, Postgres, PgArguments>) -> SqlQuery<'q, Postgres, PgArguments> {
+        bind_filters(query, self.customer_id, self.user_id, self.activity_type.clone(), self.team_ids.clone(), self.date_from, self.date_to)
     }
+}
 
+async fn load_report_entries(
+    conn: &mut PgConnection,
+    filters: &ParsedFilters,
+    limit: i64,
+) -> Result<Vec<ReportEntry>, ReportError> {
+    let conditions = filters.conditions();
     let where_clause = if conditions.is_empty() {
         String::new()
     } else {
@@ -126,48 +326,24 @@ pub async fn reports_list(
             COALESCE(CONCAT(u.first_name, ' ', u.last_name), 'Unknown User') as user_name,
             COALESCE(c.company_name, 'Unknown Customer') as customer_name,
             a.activity_date,
-            a.activity_type
-        FROM activities a
+            a.activity_type,
+            COALESCE(t.name, a.activity_type) as type_name,
+            COALESCE(t.icon, '📝') as icon
+        FROM {} a
         LEFT JOIN users u ON a.created_by = u.id
         LEFT JOIN customers c ON a.customer_id = c.id
+        LEFT JOIN activity_types t ON t.code = a.activity_type
         {}
         ORDER BY a.activity_date DESC
-        LIMIT 100
+        LIMIT {}
         "#,
-        where_clause
+        archive::activities_from(filters.date_from), where_clause, limit
     );
 
-    // Build query with parameters
-    let mut sqlx_query = sqlx::query(&query_sql);
-
-    if let Some(cid) = customer_id {
-        sqlx_query = sqlx_query.bind(cid);
-    }
-
-    if let Some(uid) = user_id {
-        sqlx_query = sqlx_query.bind(uid);
-    }
-
-    if let Some(date_from) = &query.date_from {
-        if !date_from.trim().is_empty() {
-            if let Ok(parsed_date) = NaiveDate::parse_from_str(date_from, "%Y-%m-%d") {
-                sqlx_query = sqlx_query.bind(parsed_date);
-            }
-        }
-    }
-
-    if let Some(date_to) = &query.date_to {
-        if !date_to.trim().is_empty() {
-            if let Ok(parsed_date) = NaiveDate::parse_from_str(date_to, "%Y-%m-%d") {
-                sqlx_query = sqlx_query.bind(parsed_date);
-            }
-        }
-    }
-
-    let rows = sqlx_query
-        .fetch_all(&db)
+    let rows = filters.bind(sqlx::query(&query_sql))
+        .fetch_all(conn)
         .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        .map_err(query_failed("activity report"))?;
 
     let mut reports = Vec::new();
     for row in rows {
@@ -178,28 +354,756 @@ pub async fn reports_list(
         let customer_name: String = row.try_get("customer_name").unwrap_or_else(|_| "Unknown Customer".to_string());
         let activity_date: DateTime<Utc> = row.try_get("activity_date").unwrap_or_else(|_| Utc::now());
         let activity_type: String = row.try_get("activity_type").unwrap_or_default();
+        let type_name: String = row.try_get("type_name").unwrap_or_else(|_| activity_type.clone());
+        let icon: String = row.try_get("icon").unwrap_or_default();
 
         reports.push(ReportEntry {
             id,
-            action: format!("{} - {}", activity_type.to_uppercase(), subject),
+            action: format!("{} - {}", type_name.to_uppercase(), subject),
             subject,
             description,
             user_name,
             customer_name,
             activity_date,
+            type_name,
+            icon,
+        });
+    }
+
+    Ok(reports)
+}
+
+pub async fn reports_list(
+    query: Query<ReportFilters>,
+    user: Option<AuthUser>,
+    State(db): State<Database>,
+) -> Result<Html<String>, ReportError> {
+    let current_user = user.map(|AuthUser(user)| user);
+    let current_user_id = current_user.as_ref().map(|u| u.id);
+    let (_slot, mut tx) = start_report(&db, current_user_id).await?;
+
+    let mut filters = ParsedFilters::parse(&query)?;
+    if let Some((date_from, date_to)) = period_range(&db, current_user.as_ref(), query.period.as_deref()).await? {
+        filters.date_from = Some(date_from);
+        filters.date_to = Some(date_to);
+    }
+    filters.team_ids = resolve_team(&db, query.team.as_deref(), current_user_id).await?;
+    let my_team = filters.team_ids.is_some();
+    let customer_id = filters.customer_id;
+    let user_id = filters.user_id;
+    let selected_type = filters.activity_type.clone().unwrap_or_default();
+
+    // Get all customers for filter dropdown
+    let customers = sqlx::query_as::<_, Customer>(
+        "SELECT * FROM customers ORDER BY company_name"
+    )
+    .fetch_all(&db)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    // Get all users for filter dropdown
+    let users = sqlx::query_as::<_, User>(
+        "SELECT * FROM users ORDER BY first_name, last_name"
+    )
+    .fetch_all(&db)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let activity_types = activity_types::list(&db, true)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    // Only managers get the "my team" option
+    let show_team_filter = match current_user_id {
+        Some(id) => my_team || hierarchy::has_direct_reports(&db, id)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?,
+        None => false,
+    };
+
+    let reports = load_report_entries(&mut tx, &filters, 100).await?;
+    let conditions = filters.conditions();
+
+    let mut outcome_conditions = conditions;
+    outcome_conditions.push("a.completed = true".to_string());
+    outcome_conditions.push("a.outcome_code IS NOT NULL".to_string());
+
+    let outcome_sql = format!(
+        r#"
+        SELECT
+            a.activity_type,
+            COALESCE(o.label, a.outcome_code) as label,
+            COALESCE(o.is_connect, false) as is_connect,
+            COALESCE(o.is_held, false) as is_held,
+            COUNT(*) as count
+        FROM {} a
+        LEFT JOIN activity_outcomes o ON o.activity_type = a.activity_type AND o.code = a.outcome_code
+        WHERE {}
+        GROUP BY a.activity_type, o.label, a.outcome_code, o.is_connect, o.is_held
+        ORDER BY a.activity_type, count DESC
+        "#,
+        archive::activities_from(filters.date_from),
+        outcome_conditions.join(" AND ")
+    );
+
+    let outcome_rows = filters.bind(sqlx::query(&outcome_sql))
+        .fetch_all(&mut *tx)
+        .await
+        .map_err(query_failed("activity outcomes"))?;
+
+    let mut outcome_metrics = OutcomeMetrics::default();
+    for row in outcome_rows {
+        let activity_type: String = row.try_get("activity_type").unwrap_or_default();
+        let count: i64 = row.try_get("count").unwrap_or(0);
+        let is_connect: bool = row.try_get("is_connect").unwrap_or(false);
+        let is_held: bool = row.try_get("is_held").unwrap_or(false);
+
+        match activity_type.as_str() {
+            "call" => {
+                outcome_metrics.calls_logged += count;
+                if is_connect {
+                    outcome_metrics.calls_connected += count;
+                }
+            }
+            "meeting" => {
+                outcome_metrics.meetings_logged += count;
+                if is_held {
+                    outcome_metrics.meetings_held += count;
+                }
+            }
+            _ => {}
+        }
+
+        outcome_metrics.breakdown.push(OutcomeCount {
+            label: row.try_get("label").unwrap_or_default(),
             activity_type,
+            count,
         });
     }
+    outcome_metrics.connect_rate = percent(outcome_metrics.calls_connected, outcome_metrics.calls_logged);
+    outcome_metrics.held_rate = percent(outcome_metrics.meetings_held, outcome_metrics.meetings_logged);
 
     let template = ReportsTemplate {
         reports,
         customers,
         users,
+        activity_types,
         selected_customer: customer_id,
         selected_user: user_id,
-        selected_date_from: query.date_from.clone().unwrap_or_default(),
-        selected_date_to: query.date_to.clone().unwrap_or_default(),
+        selected_type,
+        period: PeriodPicker::new(query.period.as_deref(), filters.date_from, filters.date_to),
+        my_team,
+        show_team_filter,
+        outcome_metrics,
+        can_export: current_user.as_ref().is_some_and(|u| u.has_data_export),
     };
 
     Ok(Html(template.render().unwrap()))
-}
\ No newline at end of file
+}
+pub async fn reports_export(
+    query: Query<ReportFilters>,
+    AuthUser(current_user): AuthUser,
+    State(db): State<Database>,
+) -> Result<Response, ReportError> {
+    if !current_user.has_data_export {
+        return Err(StatusCode::FORBIDDEN.into());
+    }
+    let (_slot, mut tx) = start_report(&db, Some(current_user.id)).await?;
+
+    let mut filters = ParsedFilters::parse(&query)?;
+    if let Some((date_from, date_to)) = period_range(&db, Some(&current_user), query.period.as_deref()).await? {
+        filters.date_from = Some(date_from);
+        filters.date_to = Some(date_to);
+    }
+    filters.team_ids = resolve_team(&db, query.team.as_deref(), Some(current_user.id)).await?;
+    let reports = load_report_entries(&mut tx, &filters, EXPORT_ROW_LIMIT).await?;
+
+    let customer_name = match filters.customer_id {
+        Some(id) => sqlx::query_scalar::<_, String>("SELECT company_name FROM customers WHERE id = $1")
+            .bind(id)
+            .fetch_optional(&db)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?,
+        None => None,
+    };
+    let user_name = match filters.user_id {
+        Some(id) => sqlx::query_scalar::<_, String>("SELECT CONCAT(first_name, ' ', last_name) FROM users WHERE id = $1")
+            .bind(id)
+            .fetch_optional(&db)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?,
+        None => None,
+    };
+
+    let type_name = match &filters.activity_type {
+        Some(code) => activity_types::get(&db, code)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+            .map(|t| t.name)
+            .or_else(|| Some(code.clone())),
+        None => None,
+    };
+
+    let mut export = XlsxExport::new("Activity Report", &[
+        ("Date", ColumnType::DateTime),
+        ("Type", ColumnType::Text),
+        ("Subject", ColumnType::Text),
+        ("Description", ColumnType::Text),
+        ("Customer", ColumnType::Text),
+        ("User", ColumnType::Text),
+    ]);
+    export
+        .filter("Customer", customer_name.unwrap_or_default())
+        .filter("User", user_name.unwrap_or_default())
+        .filter("Activity type", type_name.unwrap_or_default())
+        .filter("Team", if filters.team_ids.is_some() { "My team" } else { "" })
+        .filter("Period", PeriodPicker::new(query.period.as_deref(), None, None).label())
+        .filter("Date from", filters.date_from.map(|d| d.to_string()).unwrap_or_default())
+        .filter("Date to", filters.date_to.map(|d| d.to_string()).unwrap_or_default());
+
+    for entry in reports {
+        export.row(vec![
+            entry.activity_date.into(),
+            entry.type_name.into(),
+            entry.subject.into(),
+            entry.description.into(),
+            entry.customer_name.into(),
+            entry.user_name.into(),
+        ]);
+    }
+
+    let generated_by = format!("{} {} <{}>", current_user.first_name, current_user.last_name, current_user.email);
+    Ok(export.into_response("activity-report.xlsx", &generated_by))
+}
+
+// Longest month range a cross-tab covers
+const PIVOT_MAX_MONTHS: usize = 36;
+
+#[derive(Deserialize)]
+pub struct PivotFilters {
+    measure: Option<String>,
+    team: Option<String>,
+    period: Option<String>,
+    date_from: Option<String>,
+    date_to: Option<String>,
+}
+
+pub struct PivotRow {
+    pub label: String,
+    pub values: Vec<String>,
+    pub total: String,
+}
+
+#[derive(Template)]
+#[template(path = "crm/pivot_report.html")]
+struct PivotReportTemplate {
+    measure: String,
+    measure_label: String,
+    period: PeriodPicker,
+    my_team: bool,
+    show_team_filter: bool,
+    show_revenue: bool,
+    can_export: bool,
+    months: Vec<String>,
+    rows: Vec<PivotRow>,
+    column_totals: Vec<String>,
+    grand_total: String,
+    chart_json: String,
+}
+
+// Which value the cross-tab sums: closed-won revenue or logged activities
+#[derive(Clone, Copy, PartialEq)]
+enum PivotMeasure {
+    Revenue,
+    Activities,
+}
+
+impl PivotMeasure {
+    // Revenue needs finance:read; without it the report defaults to activities
+    fn parse(value: Option<&str>, finance: bool) -> Result<Self, StatusCode> {
+        match value.unwrap_or(if finance { "revenue" } else { "activities" }) {
+            "revenue" if finance => Ok(Self::Revenue),
+            "revenue" => Err(StatusCode::FORBIDDEN),
+            "activities" => Ok(Self::Activities),
+            _ => Err(StatusCode::BAD_REQUEST),
+        }
+    }
+
+    fn key(self) -> &'static str {
+        match self {
+            Self::Revenue => "revenue",
+            Self::Activities => "activities",
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Self::Revenue => "Closed Revenue",
+            Self::Activities => "Activity Count",
+        }
+    }
+
+    fn format(self, value: Decimal) -> String {
+        match self {
+            Self::Revenue => format!("{:.2}", value),
+            Self::Activities => format!("{:.0}", value),
+        }
+    }
+}
+
+fn first_of_month(date: NaiveDate) -> NaiveDate {
+    date.with_day(1).unwrap_or(date)
+}
+
+fn next_month(date: NaiveDate) -> NaiveDate {
+    date.checked_add_months(Months::new(1)).unwrap_or(date)
+}
+
+struct PivotParams {
+    measure: PivotMeasure,
+    date_from: NaiveDate,
+    date_to: NaiveDate,
+    months: Vec<String>,
+    team_ids: Option<Vec<Uuid>>,
+}
+
+// Defaults to the trailing 12 months, including the current one. A period
+// preset's dates take the place of date_from/date_to.
+fn parse_pivot_filters(
+    query: &PivotFilters,
+    finance: bool,
+    period: Option<(NaiveDate, NaiveDate)>,
+) -> Result<PivotParams, StatusCode> {
+    let measure = PivotMeasure::parse(query.measure.as_deref(), finance)?;
+    let parse_date = |value: &Option<String>| {
+        value.as_deref()
+            .filter(|d| !d.trim().is_empty())
+            .map(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d"))
+            .transpose()
+            .map_err(|_| StatusCode::BAD_REQUEST)
+    };
+
+    let today = Utc::now().date_naive();
+    let date_to = match period {
+        Some((_, date_to)) => date_to,
+        None => parse_date(&query.date_to)?.unwrap_or(today),
+    };
+    let date_from = match period {
+        Some((date_from, _)) => date_from,
+        None => parse_date(&query.date_from)?.unwrap_or_else(|| {
+            first_of_month(date_to)
+                .checked_sub_months(Months::new(11))
+                .unwrap_or(date_to)
+        }),
+    };
+
+    if date_from > date_to {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    let mut months = Vec::new();
+    let mut month = first_of_month(date_from);
+    while month <= date_to {
+        months.push(month.format("%Y-%m").to_string());
+        if months.len() > PIVOT_MAX_MONTHS {
+            return Err(StatusCode::BAD_REQUEST);
+        }
+        month = next_month(month);
+    }
+
+    Ok(PivotParams { measure, date_from, date_to, months, team_ids: None })
+}
+
+async fn load_pivot(conn: &mut PgConnection, params: &PivotParams) -> Result<Pivot, ReportError> {
+    let sql = match params.measure {
+        PivotMeasure::Revenue => r#"
+            SELECT u.id as owner_id,
+                   COALESCE(NULLIF(CONCAT(u.first_name, ' ', u.last_name), ' '), 'Unassigned') as owner,
+                   TO_CHAR(COALESCE(d.actual_close_date, d.updated_at::date), 'YYYY-MM') as month,
+                   COALESCE(SUM(d.base_value), 0)::numeric as value
+            FROM deals d
+            LEFT JOIN users u ON u.id = COALESCE(d.assigned_to, d.created_by)
+            WHERE d.stage = 'closed_won'
+              AND COALESCE(d.actual_close_date, d.updated_at::date) BETWEEN $1 AND $2
+              AND ($3::uuid[] IS NULL OR COALESCE(d.assigned_to, d.created_by) = ANY($3))
+            GROUP BY 1, 2, 3
+        "#.to_string(),
+        PivotMeasure::Activities => format!(r#"
+            SELECT u.id as owner_id,
+                   COALESCE(NULLIF(CONCAT(u.first_name, ' ', u.last_name), ' '), 'Unassigned') as owner,
+                   TO_CHAR(a.activity_date, 'YYYY-MM') as month,
+                   COUNT(*)::numeric as value
+            FROM {} a
+            LEFT JOIN users u ON u.id = COALESCE(a.assigned_to, a.created_by)
+            WHERE DATE(a.activity_date) BETWEEN $1 AND $2
+              AND ($3::uuid[] IS NULL OR COALESCE(a.assigned_to, a.created_by) = ANY($3))
+            GROUP BY 1, 2, 3
+        "#, archive::activities_from(Some(params.date_from))),
+    };
+
+    // Keyed by owner so namesakes stay apart; None collects unassigned records
+    let records = sqlx::query_as::<_, (Option<Uuid>, String, String, Decimal)>(&sql)
+        .bind(params.date_from)
+        .bind(params.date_to)
+        .bind(&params.team_ids)
+        .fetch_all(conn)
+        .await
+        .map_err(query_failed("pivot report"))?;
+
+    Ok(Pivot::build(records, params.months.clone()))
+}
+
+pub async fn pivot_report(
+    query: Query<PivotFilters>,
+    AuthUser(current_user): AuthUser,
+    State(db): State<Database>,
+) -> Result<Html<String>, ReportError> {
+    let (_slot, mut tx) = start_report(&db, Some(current_user.id)).await?;
+    let period = period_range(&db, Some(&current_user), query.period.as_deref()).await?;
+    let mut params = parse_pivot_filters(&query, current_user.has_finance_read, period)?;
+    params.team_ids = resolve_team(&db, query.team.as_deref(), Some(current_user.id)).await?;
+    let my_team = params.team_ids.is_some();
+    let show_team_filter = my_team || hierarchy::has_direct_reports(&db, current_user.id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let pivot = load_pivot(&mut tx, &params).await?;
+    let measure = params.measure;
+
+    // One dataset per owner for the stacked chart
+    let chart = json!({
+        "labels": pivot.column_labels,
+        "datasets": pivot.row_labels.iter().zip(&pivot.cells)
+            .map(|(label, values)| json!({
+                "label": label,
+                "data": values.iter().map(|v| v.to_f64().unwrap_or_default()).collect::<Vec<_>>(),
+            }))
+            .collect::<Vec<_>>(),
+    });
+
+    let template = PivotReportTemplate {
+        measure: measure.key().to_string(),
+        measure_label: measure.label().to_string(),
+        period: PeriodPicker::new(query.period.as_deref(), Some(params.date_from), Some(params.date_to)),
+        my_team,
+        show_team_filter,
+        show_revenue: current_user.has_finance_read,
+        can_export: current_user.has_data_export,
+        rows: pivot.row_labels.iter().zip(&pivot.cells).zip(&pivot.row_totals)
+            .map(|((label, values), total)| PivotRow {
+                label: label.clone(),
+                values: values.iter().map(|v| measure.format(*v)).collect(),
+                total: measure.format(*total),
+            })
+            .collect(),
+        column_totals: pivot.column_totals.iter().map(|v| measure.format(*v)).collect(),
+        grand_total: measure.format(pivot.grand_total),
+        // Keep the JSON safe to embed in a <script> block
+        chart_json: chart.to_string().replace('<', "\\u003c"),
+        months: pivot.column_labels,
+    };
+
+    Ok(Html(template.render().unwrap()))
+}
+
+pub async fn pivot_export(
+    query: Query<PivotFilters>,
+    AuthUser(current_user): AuthUser,
+    State(db): State<Database>,
+) -> Result<Response, ReportError> {
+    if !current_user.has_data_export {
+        return Err(StatusCode::FORBIDDEN.into());
+    }
+    let (_slot, mut tx) = start_report(&db, Some(current_user.id)).await?;
+
+    let period = period_range(&db, Some(&current_user), query.period.as_deref()).await?;
+    let mut params = parse_pivot_filters(&query, current_user.has_finance_read, period)?;
+    params.team_ids = resolve_team(&db, query.team.as_deref(), Some(current_user.id)).await?;
+    let pivot = load_pivot(&mut tx, &params).await?;
+
+    let value_type = match params.measure {
+        PivotMeasure::Revenue => ColumnType::Currency,
+        PivotMeasure::Activities => ColumnType::Integer,
+    };
+    let mut columns = vec![("Owner", ColumnType::Text)];
+    columns.extend(pivot.column_labels.iter().map(|month| (month.as_str(), value_type)));
+    columns.push(("Total", value_type));
+
+    let mut export = XlsxExport::new(params.measure.label(), &columns);
+    export
+        .filter("Row field", "Owner")
+        .filter("Column field", "Month")
+        .filter("Values", params.measure.label())
+        .filter("Team", if params.team_ids.is_some() { "My team" } else { "" })
+        .filter("Period", PeriodPicker::new(query.period.as_deref(), None, None).label())
+        .filter("Date from", params.date_from.to_string())
+        .filter("Date to", params.date_to.to_string());
+
+    for ((label, values), total) in pivot.row_labels.iter().zip(&pivot.cells).zip(&pivot.row_totals) {
+        let mut cells: Vec<Cell> = vec![label.as_str().into()];
+        cells.extend(values.iter().map(|v| Cell::from(*v)));
+        cells.push((*total).into());
+        export.row(cells);
+    }
+
+    let mut totals: Vec<Cell> = vec!["Total".into()];
+    totals.extend(pivot.column_totals.iter().map(|v| Cell::from(*v)));
+    totals.push(pivot.grand_total.into());
+    export.row(totals);
+
+    let generated_by = format!("{} {} <{}>", current_user.first_name, current_user.last_name, current_user.email);
+    Ok(export.into_response(&format!("pivot-{}.xlsx", params.measure.key()), &generated_by))
+}
+
+#[derive(Deserialize)]
+pub struct TeamRollupFilters {
+    team: Option<String>,
+    period: Option<String>,
+    date_from: Option<String>,
+    date_to: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "crm/team_rollup.html")]
+struct TeamRollupTemplate {
+    rows: Vec<hierarchy::TeamRollup>,
+    period: PeriodPicker,
+    my_team: bool,
+    show_team_filter: bool,
+    show_expenses: bool,
+}
+
+// Pipeline, activity and expense totals per team lead, each covering the
+// lead's whole reporting tree. Defaults to the current month.
+pub async fn team_rollup(
+    query: Query<TeamRollupFilters>,
+    AuthUser(current_user): AuthUser,
+    State(db): State<Database>,
+) -> Result<Html<String>, ReportError> {
+    let parse_date = |value: &Option<String>| {
+        value.as_deref()
+            .filter(|d| !d.trim().is_empty())
+            .map(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d"))
+            .transpose()
+            .map_err(|_| StatusCode::BAD_REQUEST)
+    };
+    let today = Utc::now().date_naive();
+    let (date_from, date_to) = match period_range(&db, Some(&current_user), query.period.as_deref()).await? {
+        Some(range) => range,
+        None => {
+            let date_to = parse_date(&query.date_to)?.unwrap_or(today);
+            (parse_date(&query.date_from)?.unwrap_or_else(|| first_of_month(date_to)), date_to)
+        }
+    };
+    if date_from > date_to {
+        return Err(StatusCode::BAD_REQUEST.into());
+    }
+    let (_slot, mut tx) = start_report(&db, Some(current_user.id)).await?;
+
+    let team_ids = resolve_team(&db, query.team.as_deref(), Some(current_user.id)).await?;
+    let my_team = team_ids.is_some();
+    let show_team_filter = my_team || hierarchy::has_direct_reports(&db, current_user.id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let rows = hierarchy::team_rollups(&mut tx, team_ids.as_deref(), date_from, date_to)
+        .await
+        .map_err(query_failed("team roll-up"))?;
+
+    let template = TeamRollupTemplate {
+        rows,
+        period: PeriodPicker::new(query.period.as_deref(), Some(date_from), Some(date_to)),
+        my_team,
+        show_team_filter,
+        show_expenses: current_user.permissions.contains(&"expenses:read".to_string()),
+    };
+
+    Ok(Html(template.render().unwrap()))
+}
+
+// Months of expenses on the summary report, including the current one
+const SUMMARY_MONTHS: u32 = 12;
+
+#[derive(Template)]
+#[template(path = "crm/report_summary.html")]
+struct SummaryReportTemplate {
+    stages: Vec<StageTotal>,
+    // Empty unless the viewer can see expenses
+    expense_months: Vec<String>,
+    expense_rows: Vec<PivotRow>,
+    expense_totals: Vec<String>,
+    expense_grand_total: String,
+    // Empty unless the viewer can see inventory
+    warehouses: Vec<WarehouseValuation>,
+    show_expenses: bool,
+    show_stock: bool,
+    show_values: bool,
+    refreshed_at: Option<String>,
+    refresh_minutes: u64,
+}
+
+// Pipeline, expense and stock totals read from the reporting views rather
+// than the live tables, so they stay quick for large organizations at the
+// cost of lagging by up to one refresh
+pub async fn summary_report(
+    AuthUser(current_user): AuthUser,
+    State(db): State<Database>,
+) -> Result<Html<String>, ReportError> {
+    let (_slot, mut tx) = start_report(&db, Some(current_user.id)).await?;
+    let show_expenses = current_user.can("expenses:read");
+    let show_stock = current_user.can("inventory:read");
+
+    let stages = reporting_views::pipeline_by_stage(&mut tx)
+        .await
+        .map_err(query_failed("pipeline summary"))?;
+
+    let this_month = first_of_month(Utc::now().date_naive());
+    let first_month = this_month
+        .checked_sub_months(Months::new(SUMMARY_MONTHS - 1))
+        .unwrap_or(this_month);
+    let expenses = if show_expenses {
+        let months: Vec<String> = (0..SUMMARY_MONTHS)
+            .filter_map(|n| first_month.checked_add_months(Months::new(n)))
+            .map(|month| month.format("%Y-%m").to_string())
+            .collect();
+        let records = reporting_views::expenses_by_category_month(&mut tx, first_month, this_month)
+            .await
+            .map_err(query_failed("expense summary"))?;
+        let records = records.into_iter().map(|(category, month, amount)| (category.clone(), category, month, amount));
+        Pivot::build(records, months)
+    } else {
+        Pivot::build(Vec::<(String, String, String, Decimal)>::new(), Vec::new())
+    };
+
+    let warehouses = if show_stock {
+        reporting_views::stock_valuation(&mut tx, current_user.warehouse_ids.as_deref())
+            .await
+            .map_err(query_failed("stock valuation"))?
+    } else {
+        Vec::new()
+    };
+
+    let refreshed_at = reporting_views::refreshed_at(&mut tx)
+        .await
+        .map_err(query_failed("report refresh time"))?
+        .map(|at| format_local(at, current_user.timezone, "%Y-%m-%d %H:%M"));
+
+    let money = |value: &Decimal| format!("{:.2}", value);
+    let template = SummaryReportTemplate {
+        stages,
+        expense_rows: expenses.row_labels.iter().zip(&expenses.cells).zip(&expenses.row_totals)
+            .map(|((label, values), total)| PivotRow {
+                label: label.clone(),
+                values: values.iter().map(money).collect(),
+                total: money(total),
+            })
+            .collect(),
+        expense_totals: expenses.column_totals.iter().map(money).collect(),
+        expense_grand_total: money(&expenses.grand_total),
+        expense_months: expenses.column_labels,
+        warehouses,
+        show_expenses,
+        show_stock,
+        show_values: current_user.has_finance_read,
+        refreshed_at,
+        refresh_minutes: reporting_views::REFRESH_MINUTES,
+    };
+
+    Ok(Html(template.render().unwrap()))
+}
+
+const MONTH_NAMES: [&str; 12] = [
+    "January", "February", "March", "April", "May", "June",
+    "July", "August", "September", "October", "November", "December",
+];
+
+#[derive(Template)]
+#[template(path = "team/reporting.html")]
+struct ReportingSettingsTemplate {
+    months: Vec<(i32, &'static str)>,
+    fiscal_year_start_month: i32,
+    saved: bool,
+    current_user: CurrentUser,
+}
+
+impl ReportingSettingsTemplate {
+    fn is_fiscal_start(&self, month: &i32) -> bool {
+        *month == self.fiscal_year_start_month
+    }
+}
+
+#[derive(Deserialize)]
+pub struct ReportingSettingsQuery {
+    saved: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct ReportingSettingsForm {
+    fiscal_year_start_month: i32,
+}
+
+// Organization-wide settings behind period presets such as "fiscal year to date"
+pub async fn reporting_settings_page(
+    query: Query<ReportingSettingsQuery>,
+    AuthUser(current_user): AuthUser,
+    State(db): State<Database>,
+) -> Result<Html<String>, StatusCode> {
+    if !current_user.has_manage_roles {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let fiscal_year_start_month = sqlx::query_scalar::<_, i32>("SELECT fiscal_year_start_month FROM reporting_settings")
+        .fetch_optional(&db)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error loading reporting settings: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .unwrap_or(1);
+
+    let template = ReportingSettingsTemplate {
+        months: (1..).zip(MONTH_NAMES).collect(),
+        fiscal_year_start_month,
+        saved: query.saved.is_some(),
+        current_user,
+    };
+
+    Ok(Html(template.render().unwrap()))
+}
+
+pub async fn update_reporting_settings(
+    AuthUser(current_user): AuthUser,
+    State(db): State<Database>,
+    Form(form): Form<ReportingSettingsForm>,
+) -> Result<Redirect, StatusCode> {
+    if !current_user.has_manage_roles {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    if !(1..=12).contains(&form.fiscal_year_start_month) {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+
+    sqlx::query("UPDATE reporting_settings SET fiscal_year_start_month = $1, updated_by = $2")
+        .bind(form.fiscal_year_start_month)
+        .bind(current_user.id)
+        .execute(&db)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error saving reporting settings: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    let _ = create_audit_log(
+        &db,
+        &current_user,
+        "update".to_string(),
+        "reporting_settings".to_string(),
+        None,
+        None,
+        Some(json!({ "fiscal_year_start_month": form.fiscal_year_start_month })),
+    )
+    .await;
+
+    Ok(Redirect::to("/team/reporting?saved=1"))
+}
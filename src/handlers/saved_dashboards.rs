@@ -0,0 +1,283 @@
+use axum::{
+    extract::{Form, Path, Query, State},
+    http::StatusCode,
+    response::{Html, Redirect},
+};
+use askama::Template;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    database::Database,
+    handlers::team::create_audit_log,
+    middleware::{AuthUser, CurrentUser},
+    services::{
+        dashboard::{self as widgets, Widget, WidgetDef},
+        mailer,
+        saved_dashboards::{self, SavedDashboard},
+    },
+};
+
+#[derive(Template)]
+#[template(path = "dashboards/list.html")]
+struct DashboardsTemplate {
+    dashboards: Vec<SavedDashboard>,
+    // Widgets the user can put on a dashboard
+    choices: Vec<&'static WidgetDef>,
+    error: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "dashboards/view.html")]
+struct DashboardTemplate {
+    dashboard: SavedDashboard,
+    widgets: Vec<Widget>,
+    // Widgets left out because the viewer lacks their permission
+    hidden: usize,
+    // The owner's own page has the edit and sharing controls; a share link
+    // only ever views it
+    is_owner: bool,
+    choices: Vec<&'static WidgetDef>,
+    share_url: Option<String>,
+    error: Option<String>,
+}
+
+impl DashboardTemplate {
+    fn is_chosen(&self, key: &str) -> bool {
+        self.dashboard.widgets.iter().any(|w| w == key)
+    }
+}
+
+#[derive(Deserialize)]
+pub struct DashboardQuery {
+    error: Option<String>,
+}
+
+// The name and every ticked widget; checkboxes share a name, so the form
+// comes through as pairs
+fn parse_form(fields: Vec<(String, String)>) -> (String, Vec<String>) {
+    let mut name = String::new();
+    let mut chosen = Vec::new();
+    for (key, value) in fields {
+        match key.as_str() {
+            "name" => name = value.trim().to_string(),
+            "widgets" if !chosen.contains(&value) => chosen.push(value),
+            _ => {}
+        }
+    }
+    (name, chosen)
+}
+
+fn share_url(token: &str) -> String {
+    format!("{}/dashboards/shared/{}", mailer::app_url(), token)
+}
+
+// The widgets the viewer may see load with their own data and access; the
+// rest are only counted
+async fn load_widgets(db: &Database, current_user: &CurrentUser, keys: &[String]) -> (Vec<Widget>, usize) {
+    let allowed = widgets::widgets_allowed(&current_user.permissions);
+    let mut loaded = Vec::new();
+    let mut hidden = 0;
+    for key in keys {
+        let Some(def) = widgets::widget(key) else {
+            continue;
+        };
+        if !allowed.iter().any(|a| a.key == def.key) {
+            hidden += 1;
+            continue;
+        }
+        match widgets::load_widget(db, def, current_user).await {
+            Ok(widget) => loaded.push(widget),
+            Err(e) => tracing::error!("Error loading dashboard widget {}: {}", def.key, e),
+        }
+    }
+    (loaded, hidden)
+}
+
+async fn owned(db: &Database, current_user: &CurrentUser, id: Uuid) -> Result<SavedDashboard, StatusCode> {
+    saved_dashboards::get_owned(db, id, current_user.id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error loading dashboard {}: {}", id, e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+pub async fn dashboards_page(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Query(query): Query<DashboardQuery>,
+) -> Result<Html<String>, StatusCode> {
+    let dashboards = saved_dashboards::list_for(&db, current_user.id).await.map_err(|e| {
+        tracing::error!("Error loading dashboards: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let template = DashboardsTemplate {
+        dashboards,
+        choices: widgets::widgets_allowed(&current_user.permissions),
+        error: query.error,
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+pub async fn create_dashboard(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Form(fields): Form<Vec<(String, String)>>,
+) -> Result<Redirect, StatusCode> {
+    let (name, chosen) = parse_form(fields);
+    if let Err(error) = saved_dashboards::validate(&name, &chosen, &current_user.permissions) {
+        return Ok(Redirect::to(&format!("/dashboards?error={}", urlencoding::encode(&error))));
+    }
+
+    let id = saved_dashboards::create(&db, &name, &chosen, current_user.id).await.map_err(|e| {
+        tracing::error!("Error creating dashboard: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    Ok(Redirect::to(&format!("/dashboards/{}", id)))
+}
+
+pub async fn dashboard_page(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+    Query(query): Query<DashboardQuery>,
+) -> Result<Html<String>, StatusCode> {
+    let dashboard = owned(&db, &current_user, id).await?;
+    let (widgets, hidden) = load_widgets(&db, &current_user, &dashboard.widgets).await;
+
+    let template = DashboardTemplate {
+        share_url: dashboard.share_token.as_deref().map(share_url),
+        dashboard,
+        widgets,
+        hidden,
+        is_owner: true,
+        choices: widgets::widgets_allowed(&current_user.permissions),
+        error: query.error,
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+pub async fn update_dashboard(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+    Form(fields): Form<Vec<(String, String)>>,
+) -> Result<Redirect, StatusCode> {
+    let (name, chosen) = parse_form(fields);
+    if let Err(error) = saved_dashboards::validate(&name, &chosen, &current_user.permissions) {
+        return Ok(Redirect::to(&format!("/dashboards/{}?error={}", id, urlencoding::encode(&error))));
+    }
+
+    let updated = saved_dashboards::update(&db, id, current_user.id, &name, &chosen).await.map_err(|e| {
+        tracing::error!("Error updating dashboard: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    if !updated {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    Ok(Redirect::to(&format!("/dashboards/{}", id)))
+}
+
+// Issues a new link, retiring any earlier one
+pub async fn share_dashboard(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<Redirect, StatusCode> {
+    saved_dashboards::share(&db, id, current_user.id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error sharing dashboard: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let _ = create_audit_log(
+        &db,
+        &current_user,
+        "share".to_string(),
+        "dashboard".to_string(),
+        Some(id),
+        None,
+        None,
+    )
+    .await;
+
+    Ok(Redirect::to(&format!("/dashboards/{}", id)))
+}
+
+pub async fn unshare_dashboard(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<Redirect, StatusCode> {
+    let unshared = saved_dashboards::unshare(&db, id, current_user.id).await.map_err(|e| {
+        tracing::error!("Error unsharing dashboard: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    if !unshared {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let _ = create_audit_log(
+        &db,
+        &current_user,
+        "unshare".to_string(),
+        "dashboard".to_string(),
+        Some(id),
+        None,
+        None,
+    )
+    .await;
+
+    Ok(Redirect::to(&format!("/dashboards/{}", id)))
+}
+
+pub async fn delete_dashboard(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<Redirect, StatusCode> {
+    let deleted = saved_dashboards::delete(&db, id, current_user.id).await.map_err(|e| {
+        tracing::error!("Error deleting dashboard: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    if !deleted {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    Ok(Redirect::to("/dashboards"))
+}
+
+// What a share link opens: the dashboard read-only, each widget showing the
+// viewer's own data
+pub async fn shared_dashboard(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(token): Path<String>,
+) -> Result<Html<String>, StatusCode> {
+    let dashboard = saved_dashboards::by_token(&db, &token)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error loading shared dashboard: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let (widgets, hidden) = load_widgets(&db, &current_user, &dashboard.widgets).await;
+
+    let template = DashboardTemplate {
+        is_owner: false,
+        share_url: None,
+        dashboard,
+        widgets,
+        hidden,
+        choices: Vec::new(),
+        error: None,
+    };
+    Ok(Html(template.render().unwrap()))
+}
@@ -0,0 +1,87 @@
+use askama::Template;
+use axum::{
+    extract::{Extension, Query, State},
+    http::StatusCode,
+    response::{Html, Json},
+};
+use serde::{Deserialize, Serialize};
+use tower_cookies::Cookies;
+
+use crate::{
+    database::Database,
+    middleware::{api_auth::ApiPrincipal, get_current_user, AuthUser, CurrentUser},
+    services::{
+        sandbox,
+        search::{self, SearchGroup},
+    },
+    utils::search_terms,
+};
+
+#[derive(Deserialize)]
+pub struct SearchQuery {
+    q: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "search.html")]
+struct SearchTemplate {
+    query: String,
+    groups: Vec<SearchGroup>,
+    searched: bool,
+}
+
+#[derive(Serialize)]
+pub struct SearchResponse {
+    query: String,
+    groups: Vec<SearchGroup>,
+}
+
+async fn run(db: &Database, user: &CurrentUser, sandbox: bool, tsquery: &str) -> Result<Vec<SearchGroup>, StatusCode> {
+    let mut tx = sandbox::begin(db, sandbox)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    search::search(&mut tx, user, !sandbox, tsquery).await.map_err(|e| {
+        tracing::error!("Error searching for {:?}: {}", tsquery, e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
+
+pub async fn search_page(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Query(params): Query<SearchQuery>,
+) -> Result<Html<String>, StatusCode> {
+    let query = params.q.unwrap_or_default().trim().to_string();
+    let mut groups = match search_terms::prefix_query(&query) {
+        Some(tsquery) => run(&db, &current_user, false, &tsquery).await?,
+        None => Vec::new(),
+    };
+    groups.retain(|group| !group.hits.is_empty());
+
+    let template = SearchTemplate {
+        searched: !query.is_empty(),
+        query,
+        groups,
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+// Every kind the caller can see is listed, even with no hits, so clients
+// can tell "nothing found" from "not allowed"
+pub async fn api_search(
+    State(db): State<Database>,
+    cookies: Cookies,
+    Query(params): Query<SearchQuery>,
+    principal: Option<Extension<ApiPrincipal>>,
+) -> Result<Json<SearchResponse>, StatusCode> {
+    let sandbox = principal.as_ref().is_some_and(|Extension(p)| p.sandbox);
+    let user = match principal {
+        Some(Extension(principal)) => principal.user,
+        None => get_current_user(cookies, &db).await.ok_or(StatusCode::UNAUTHORIZED)?,
+    };
+    let query = params.q.unwrap_or_default().trim().to_string();
+    let tsquery = search_terms::prefix_query(&query).ok_or(StatusCode::BAD_REQUEST)?;
+
+    let groups = run(&db, &user, sandbox, &tsquery).await?;
+    Ok(Json(SearchResponse { query, groups }))
+}
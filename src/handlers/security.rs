@@ -0,0 +1,464 @@
+use axum::{
+    extract::{ConnectInfo, Form, Query, State},
+    http::{HeaderMap, StatusCode},
+    response::{Html, IntoResponse, Redirect, Response},
+};
+use askama::Template;
+use serde::Deserialize;
+use std::net::SocketAddr;
+
+use crate::{
+    database::Database,
+    handlers::team::create_audit_log,
+    middleware::{AuthUser, CurrentUser},
+    models::{SecuritySettings, SECURITY_SETTINGS_SELECT},
+    services::signing_keys,
+    utils::{legacy_key_retires_at, request::{client_ip, parse_cidr_list}, signing_keys as current_signing_keys, SigningKey},
+};
+
+#[derive(Template)]
+#[template(path = "team/security.html")]
+struct SecuritySettingsTemplate {
+    settings: SecuritySettings,
+    ip_allowlist: String,
+    your_ip: String,
+    saved: bool,
+    error: Option<String>,
+    current_user: CurrentUser,
+    // Newest first; secrets aren't shown
+    signing_keys: Vec<SigningKey>,
+    // When JWT_SECRET stops being accepted, if it's been rotated out but not yet retired
+    legacy_key_until: Option<chrono::DateTime<chrono::Utc>>,
+}
+
+#[derive(Deserialize)]
+pub struct SecuritySettingsForm {
+    ip_allowlist_enabled: Option<String>,
+    ip_allowlist: String,
+    exempt_api_keys: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct SessionSettingsForm {
+    session_max_hours: String,
+    session_idle_minutes: String,
+    max_sessions_per_user: String,
+}
+
+#[derive(Deserialize)]
+pub struct LockoutSettingsForm {
+    lockout_threshold: String,
+    lockout_minutes: String,
+}
+
+#[derive(Deserialize)]
+pub struct PasswordSettingsForm {
+    password_min_length: String,
+    password_required_classes: String,
+    password_block_breached: Option<String>,
+    password_history_count: String,
+}
+
+#[derive(Deserialize)]
+pub struct SavedQuery {
+    saved: Option<String>,
+}
+
+async fn load_settings(db: &Database) -> Result<SecuritySettings, StatusCode> {
+    sqlx::query_as::<_, SecuritySettings>(SECURITY_SETTINGS_SELECT)
+        .fetch_one(db)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error loading security settings: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+}
+
+fn render(
+    settings: SecuritySettings,
+    ip_allowlist: String,
+    your_ip: String,
+    saved: bool,
+    error: Option<String>,
+    current_user: CurrentUser,
+) -> Html<String> {
+    let legacy_key_until = legacy_key_retires_at().filter(|at| *at > chrono::Utc::now());
+    let template = SecuritySettingsTemplate {
+        settings,
+        ip_allowlist,
+        your_ip,
+        saved,
+        error,
+        current_user,
+        signing_keys: current_signing_keys(),
+        legacy_key_until,
+    };
+    Html(template.render().unwrap())
+}
+
+// 90 days; matches the check on security_settings.session_max_hours
+const MAX_SESSION_HOURS: i32 = 2160;
+
+// Blank turns a limit off; anything else must be a positive whole number
+fn parse_limit(input: &str, label: &str) -> Result<Option<i32>, String> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Ok(None);
+    }
+    match input.parse::<i32>() {
+        Ok(value) if value > 0 => Ok(Some(value)),
+        _ => Err(format!("{} must be a whole number greater than zero, or blank for no limit.", label)),
+    }
+}
+
+// An enabled allowlist must be non-empty and must include the admin saving
+// it, so nobody locks themselves out by mistake.
+async fn validate_allowlist(
+    db: &Database,
+    input: &str,
+    enabled: bool,
+    your_ip: &str,
+) -> Result<Result<Vec<String>, String>, StatusCode> {
+    let list = match parse_cidr_list(input) {
+        Ok(list) => list,
+        Err(message) => return Ok(Err(message)),
+    };
+
+    if !enabled {
+        return Ok(Ok(list));
+    }
+    if list.is_empty() {
+        return Ok(Err("Add at least one address or range before turning on the allowlist.".to_string()));
+    }
+
+    let includes_you = sqlx::query_scalar::<_, Option<bool>>("SELECT $1::inet <<= ANY($2::cidr[])")
+        .bind(your_ip)
+        .bind(&list)
+        .fetch_one(db)
+        .await
+        .ok()
+        .flatten()
+        .unwrap_or(false);
+
+    if includes_you {
+        Ok(Ok(list))
+    } else {
+        Ok(Err(format!(
+            "Your current address ({}) is not in the list. Add it so you don't lock yourself out.",
+            your_ip
+        )))
+    }
+}
+
+pub async fn security_settings_page(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    headers: HeaderMap,
+    peer: Option<ConnectInfo<SocketAddr>>,
+    Query(query): Query<SavedQuery>,
+) -> Result<Html<String>, StatusCode> {
+    if !current_user.has_manage_roles {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let settings = load_settings(&db).await?;
+    let ip_allowlist = settings.ip_allowlist.join("\n");
+    let your_ip = client_ip(&headers, peer.map(|ConnectInfo(addr)| addr))
+        .map(|ip| ip.to_string())
+        .unwrap_or_default();
+
+    Ok(render(settings, ip_allowlist, your_ip, query.saved.is_some(), None, current_user))
+}
+
+pub async fn update_security_settings(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    headers: HeaderMap,
+    peer: Option<ConnectInfo<SocketAddr>>,
+    Form(form): Form<SecuritySettingsForm>,
+) -> Result<Response, StatusCode> {
+    if !current_user.has_manage_roles {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let your_ip = client_ip(&headers, peer.map(|ConnectInfo(addr)| addr))
+        .map(|ip| ip.to_string())
+        .unwrap_or_default();
+    let enabled = form.ip_allowlist_enabled.is_some();
+    let exempt_api_keys = form.exempt_api_keys.is_some();
+
+    let list = match validate_allowlist(&db, &form.ip_allowlist, enabled, &your_ip).await? {
+        Ok(list) => list,
+        Err(error) => {
+            // Re-show what was submitted alongside the error
+            let mut settings = load_settings(&db).await?;
+            settings.ip_allowlist_enabled = enabled;
+            settings.exempt_api_keys = exempt_api_keys;
+            return Ok(render(settings, form.ip_allowlist, your_ip, false, Some(error), current_user).into_response());
+        }
+    };
+
+    sqlx::query(
+        r#"
+        UPDATE security_settings
+        SET ip_allowlist_enabled = $1, ip_allowlist = $2::cidr[], exempt_api_keys = $3, updated_by = $4
+        "#,
+    )
+    .bind(enabled)
+    .bind(&list)
+    .bind(exempt_api_keys)
+    .bind(current_user.id)
+    .execute(&db)
+    .await
+    .map_err(|e| {
+        tracing::error!("Error saving security settings: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let _ = create_audit_log(
+        &db,
+        &current_user,
+        "update".to_string(),
+        "security_settings".to_string(),
+        None,
+        None,
+        Some(serde_json::json!({
+            "ip_allowlist_enabled": enabled,
+            "ip_allowlist": list,
+            "exempt_api_keys": exempt_api_keys,
+        })),
+    )
+    .await;
+
+    Ok(Redirect::to("/team/security?saved=1").into_response())
+}
+
+pub async fn update_session_settings(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    headers: HeaderMap,
+    peer: Option<ConnectInfo<SocketAddr>>,
+    Form(form): Form<SessionSettingsForm>,
+) -> Result<Response, StatusCode> {
+    if !current_user.has_manage_roles {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let max_hours = match form.session_max_hours.trim().parse::<i32>() {
+        Ok(hours) if (1..=MAX_SESSION_HOURS).contains(&hours) => Ok(hours),
+        _ => Err(format!("Maximum session length must be between 1 and {} hours.", MAX_SESSION_HOURS)),
+    };
+    let limits = max_hours.and_then(|max_hours| {
+        parse_limit(&form.session_idle_minutes, "Idle timeout").and_then(|idle| {
+            parse_limit(&form.max_sessions_per_user, "Concurrent sessions").map(|max| (max_hours, idle, max))
+        })
+    });
+    let (max_hours, idle_minutes, max_sessions) = match limits {
+        Ok(limits) => limits,
+        Err(error) => {
+            let settings = load_settings(&db).await?;
+            let ip_allowlist = settings.ip_allowlist.join("\n");
+            let your_ip = client_ip(&headers, peer.map(|ConnectInfo(addr)| addr))
+                .map(|ip| ip.to_string())
+                .unwrap_or_default();
+            return Ok(render(settings, ip_allowlist, your_ip, false, Some(error), current_user).into_response());
+        }
+    };
+
+    sqlx::query(
+        r#"
+        UPDATE security_settings
+        SET session_idle_minutes = $1, max_sessions_per_user = $2, updated_by = $3, session_max_hours = $4
+        "#,
+    )
+    .bind(idle_minutes)
+    .bind(max_sessions)
+    .bind(current_user.id)
+    .bind(max_hours)
+    .execute(&db)
+    .await
+    .map_err(|e| {
+        tracing::error!("Error saving session settings: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let _ = create_audit_log(
+        &db,
+        &current_user,
+        "update".to_string(),
+        "security_settings".to_string(),
+        None,
+        None,
+        Some(serde_json::json!({
+            "session_max_hours": max_hours,
+            "session_idle_minutes": idle_minutes,
+            "max_sessions_per_user": max_sessions,
+        })),
+    )
+    .await;
+
+    Ok(Redirect::to("/team/security?saved=1").into_response())
+}
+
+pub async fn update_lockout_settings(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    headers: HeaderMap,
+    peer: Option<ConnectInfo<SocketAddr>>,
+    Form(form): Form<LockoutSettingsForm>,
+) -> Result<Response, StatusCode> {
+    if !current_user.has_manage_roles {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let limits = parse_limit(&form.lockout_threshold, "Failed sign-ins before locking").and_then(|threshold| {
+        parse_limit(&form.lockout_minutes, "Lockout duration").map(|minutes| (threshold, minutes))
+    });
+    let (threshold, minutes) = match limits {
+        Ok(limits) => limits,
+        Err(error) => {
+            let settings = load_settings(&db).await?;
+            let ip_allowlist = settings.ip_allowlist.join("\n");
+            let your_ip = client_ip(&headers, peer.map(|ConnectInfo(addr)| addr))
+                .map(|ip| ip.to_string())
+                .unwrap_or_default();
+            return Ok(render(settings, ip_allowlist, your_ip, false, Some(error), current_user).into_response());
+        }
+    };
+
+    sqlx::query(
+        r#"
+        UPDATE security_settings
+        SET lockout_threshold = $1, lockout_minutes = $2, updated_by = $3
+        "#,
+    )
+    .bind(threshold)
+    .bind(minutes)
+    .bind(current_user.id)
+    .execute(&db)
+    .await
+    .map_err(|e| {
+        tracing::error!("Error saving lockout settings: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let _ = create_audit_log(
+        &db,
+        &current_user,
+        "update".to_string(),
+        "security_settings".to_string(),
+        None,
+        None,
+        Some(serde_json::json!({
+            "lockout_threshold": threshold,
+            "lockout_minutes": minutes,
+        })),
+    )
+    .await;
+
+    Ok(Redirect::to("/team/security?saved=1").into_response())
+}
+
+pub async fn update_password_settings(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    headers: HeaderMap,
+    peer: Option<ConnectInfo<SocketAddr>>,
+    Form(form): Form<PasswordSettingsForm>,
+) -> Result<Response, StatusCode> {
+    if !current_user.has_manage_roles {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let min_length = match form.password_min_length.trim().parse::<i32>() {
+        Ok(length) if (6..=72).contains(&length) => Ok(length),
+        _ => Err("Minimum length must be a whole number from 6 to 72.".to_string()),
+    };
+    let required_classes = match form.password_required_classes.trim().parse::<i32>() {
+        Ok(classes) if (0..=4).contains(&classes) => Ok(classes),
+        _ => Err("Character types must be a number from 0 to 4.".to_string()),
+    };
+    let checked = min_length.and_then(|length| {
+        required_classes.and_then(|classes| {
+            parse_limit(&form.password_history_count, "Passwords remembered").map(|history| (length, classes, history))
+        })
+    });
+    let (min_length, required_classes, history_count) = match checked {
+        Ok(values) => values,
+        Err(error) => {
+            let settings = load_settings(&db).await?;
+            let ip_allowlist = settings.ip_allowlist.join("\n");
+            let your_ip = client_ip(&headers, peer.map(|ConnectInfo(addr)| addr))
+                .map(|ip| ip.to_string())
+                .unwrap_or_default();
+            return Ok(render(settings, ip_allowlist, your_ip, false, Some(error), current_user).into_response());
+        }
+    };
+    let block_breached = form.password_block_breached.is_some();
+
+    sqlx::query(
+        r#"
+        UPDATE security_settings
+        SET password_min_length = $1, password_required_classes = $2,
+            password_block_breached = $3, password_history_count = $4, updated_by = $5
+        "#,
+    )
+    .bind(min_length)
+    .bind(required_classes)
+    .bind(block_breached)
+    .bind(history_count)
+    .bind(current_user.id)
+    .execute(&db)
+    .await
+    .map_err(|e| {
+        tracing::error!("Error saving password settings: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let _ = create_audit_log(
+        &db,
+        &current_user,
+        "update".to_string(),
+        "security_settings".to_string(),
+        None,
+        None,
+        Some(serde_json::json!({
+            "password_min_length": min_length,
+            "password_required_classes": required_classes,
+            "password_block_breached": block_breached,
+            "password_history_count": history_count,
+        })),
+    )
+    .await;
+
+    Ok(Redirect::to("/team/security?saved=1").into_response())
+}
+
+// Sign new session tokens with a fresh secret, e.g. after the old one may have
+// leaked. Existing sessions carry on until their tokens expire.
+pub async fn rotate_signing_key(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+) -> Result<Redirect, StatusCode> {
+    if !current_user.has_manage_roles {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let kid = signing_keys::rotate(&db, current_user.id).await.map_err(|e| {
+        tracing::error!("Error rotating signing key: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let _ = create_audit_log(
+        &db,
+        &current_user,
+        "rotate".to_string(),
+        "signing_key".to_string(),
+        None,
+        None,
+        Some(serde_json::json!({ "kid": kid })),
+    )
+    .await;
+
+    Ok(Redirect::to("/team/security?saved=1"))
+}
@@ -0,0 +1,391 @@
+use axum::{
+    extract::{Form, Path, Query, State},
+    http::StatusCode,
+    response::{Html, Redirect},
+};
+use askama::Template;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    database::Database,
+    handlers::{crm::require_access, team::create_audit_log},
+    middleware::{AuthUser, CurrentUser},
+    models::{ActivityType, Sequence, SequenceStep, MERGE_FIELDS, SEQUENCE_STEP_TYPES},
+    services::{
+        activity_types,
+        sequences::{self, NewStep},
+        sharing::{Access, RecordKind},
+    },
+};
+
+#[derive(Template)]
+#[template(path = "crm/sequences.html")]
+struct SequencesTemplate {
+    current_user: CurrentUser,
+    sequences: Vec<Sequence>,
+    can_edit: bool,
+    error: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "crm/sequence_detail.html")]
+struct SequenceDetailTemplate {
+    current_user: CurrentUser,
+    sequence: Sequence,
+    steps: Vec<SequenceStep>,
+    step_types: &'static [&'static str],
+    activity_types: Vec<ActivityType>,
+    merge_fields: &'static [&'static str],
+    can_edit: bool,
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct SequencesQuery {
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct SequenceForm {
+    name: String,
+    description: Option<String>,
+    // Checkbox on the edit form: only sent when ticked
+    is_active: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct StepForm {
+    day: i32,
+    step_type: String,
+    activity_type: Option<String>,
+    subject: String,
+    body: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct EnrollForm {
+    sequence_id: Uuid,
+}
+
+fn trimmed(value: &Option<String>) -> Option<&str> {
+    value.as_deref().map(str::trim).filter(|v| !v.is_empty())
+}
+
+pub async fn sequences_page(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Query(query): Query<SequencesQuery>,
+) -> Result<Html<String>, StatusCode> {
+    current_user.require("customers:read")?;
+    let sequences = sequences::list(&db).await.map_err(|e| {
+        tracing::error!("Error loading sequences: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let template = SequencesTemplate {
+        can_edit: current_user.can("customers:write"),
+        current_user,
+        sequences,
+        error: query.error,
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+pub async fn create_sequence(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Form(form): Form<SequenceForm>,
+) -> Result<Redirect, StatusCode> {
+    current_user.require("customers:write")?;
+    let name = form.name.trim();
+
+    let created = sequences::create(&db, name, trimmed(&form.description), current_user.id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error creating sequence: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    let id = match created {
+        Ok(id) => id,
+        Err(error) => return Ok(Redirect::to(&format!("/crm/sequences?error={}", urlencoding::encode(&error)))),
+    };
+
+    let _ = create_audit_log(
+        &db,
+        &current_user,
+        "create".to_string(),
+        "sequence".to_string(),
+        Some(id),
+        None,
+        Some(serde_json::json!({ "name": name, "description": trimmed(&form.description) })),
+    )
+    .await;
+
+    Ok(Redirect::to(&format!("/crm/sequences/{}", id)))
+}
+
+pub async fn sequence_page(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+    Query(query): Query<SequencesQuery>,
+) -> Result<Html<String>, StatusCode> {
+    current_user.require("customers:read")?;
+    let sequence = sequences::find(&db, id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error loading sequence: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let steps = sequences::steps(&db, id).await.map_err(|e| {
+        tracing::error!("Error loading sequence steps: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let template = SequenceDetailTemplate {
+        can_edit: current_user.can("customers:write"),
+        current_user,
+        sequence,
+        steps,
+        step_types: SEQUENCE_STEP_TYPES,
+        merge_fields: &MERGE_FIELDS,
+        activity_types: activity_types::list(&db, false)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?,
+        error: query.error,
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+pub async fn update_sequence(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+    Form(form): Form<SequenceForm>,
+) -> Result<Redirect, StatusCode> {
+    current_user.require("customers:write")?;
+    let (name, is_active) = (form.name.trim(), form.is_active.is_some());
+
+    let updated = sequences::update(&db, id, name, trimmed(&form.description), is_active)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error updating sequence: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    if let Err(error) = updated {
+        return Ok(Redirect::to(&format!("/crm/sequences/{}?error={}", id, urlencoding::encode(&error))));
+    }
+
+    let _ = create_audit_log(
+        &db,
+        &current_user,
+        "update".to_string(),
+        "sequence".to_string(),
+        Some(id),
+        None,
+        Some(serde_json::json!({ "name": name, "description": trimmed(&form.description), "is_active": is_active })),
+    )
+    .await;
+
+    Ok(Redirect::to(&format!("/crm/sequences/{}", id)))
+}
+
+pub async fn add_step(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+    Form(form): Form<StepForm>,
+) -> Result<Redirect, StatusCode> {
+    current_user.require("customers:write")?;
+    sequences::find(&db, id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error loading sequence: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let step = NewStep {
+        day: form.day,
+        step_type: &form.step_type,
+        activity_type: trimmed(&form.activity_type),
+        subject: form.subject.trim(),
+        body: trimmed(&form.body),
+    };
+    let added = sequences::add_step(&db, id, step).await.map_err(|e| {
+        tracing::error!("Error adding sequence step: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    let step_id = match added {
+        Ok(step_id) => step_id,
+        Err(error) => return Ok(Redirect::to(&format!("/crm/sequences/{}?error={}", id, urlencoding::encode(&error)))),
+    };
+
+    let _ = create_audit_log(
+        &db,
+        &current_user,
+        "add_step".to_string(),
+        "sequence".to_string(),
+        Some(id),
+        None,
+        Some(serde_json::json!({ "step_id": step_id, "day": form.day, "step_type": form.step_type, "subject": form.subject.trim() })),
+    )
+    .await;
+
+    Ok(Redirect::to(&format!("/crm/sequences/{}", id)))
+}
+
+pub async fn delete_step(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path((id, step_id)): Path<(Uuid, Uuid)>,
+) -> Result<Redirect, StatusCode> {
+    current_user.require("customers:write")?;
+
+    let deleted = sequences::delete_step(&db, id, step_id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error deleting sequence step: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    if deleted {
+        let _ = create_audit_log(
+            &db,
+            &current_user,
+            "delete_step".to_string(),
+            "sequence".to_string(),
+            Some(id),
+            Some(serde_json::json!({ "step_id": step_id })),
+            None,
+        )
+        .await;
+    }
+
+    Ok(Redirect::to(&format!("/crm/sequences/{}", id)))
+}
+
+// Sends the viewer back to the record, with the reason if it was refused
+async fn enroll(
+    db: &Database,
+    current_user: &CurrentUser,
+    form: EnrollForm,
+    (customer_id, contact_id, deal_id): (Uuid, Option<Uuid>, Option<Uuid>),
+    back_to: String,
+) -> Result<Redirect, StatusCode> {
+    let enrolled = sequences::enroll(db, form.sequence_id, customer_id, contact_id, deal_id, current_user.id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error enrolling in sequence: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    let id = match enrolled {
+        Ok(id) => id,
+        Err(error) => return Ok(Redirect::to(&format!("{}?sequence_error={}", back_to, urlencoding::encode(&error)))),
+    };
+
+    let _ = create_audit_log(
+        db,
+        current_user,
+        "enroll".to_string(),
+        "sequence".to_string(),
+        Some(form.sequence_id),
+        None,
+        Some(serde_json::json!({ "enrollment_id": id, "contact_id": contact_id, "deal_id": deal_id })),
+    )
+    .await;
+
+    Ok(Redirect::to(&back_to))
+}
+
+pub async fn enroll_contact(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path((customer_id, contact_id)): Path<(Uuid, Uuid)>,
+    Form(form): Form<EnrollForm>,
+) -> Result<Redirect, StatusCode> {
+    current_user.require("customers:write")?;
+    require_access(&db, &current_user, RecordKind::Customer, customer_id, Access::Write).await?;
+
+    let exists = sqlx::query_scalar::<_, bool>("SELECT EXISTS(SELECT 1 FROM contacts WHERE id = $1 AND customer_id = $2)")
+        .bind(contact_id)
+        .bind(customer_id)
+        .fetch_one(&db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if !exists {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    let back_to = format!("/crm/customers/{}/contacts/{}", customer_id, contact_id);
+    enroll(&db, &current_user, form, (customer_id, Some(contact_id), None), back_to).await
+}
+
+// The deal's contact gets the emails; a deal without one can only take
+// sequences made of tasks
+pub async fn enroll_deal(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+    Form(form): Form<EnrollForm>,
+) -> Result<Redirect, StatusCode> {
+    current_user.require("customers:write")?;
+    require_access(&db, &current_user, RecordKind::Deal, id, Access::Write).await?;
+
+    let (customer_id, contact_id) = sqlx::query_as::<_, (Uuid, Option<Uuid>)>("SELECT customer_id, contact_id FROM deals WHERE id = $1")
+        .bind(id)
+        .fetch_optional(&db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    enroll(&db, &current_user, form, (customer_id, contact_id, Some(id)), format!("/crm/deals/{}", id)).await
+}
+
+pub async fn stop_enrollment(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<Redirect, StatusCode> {
+    current_user.require("customers:write")?;
+    let enrollment = sequences::find_enrollment(&db, id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error loading sequence enrollment: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let back_to = match (enrollment.deal_id, enrollment.contact_id) {
+        (Some(deal_id), _) => {
+            require_access(&db, &current_user, RecordKind::Deal, deal_id, Access::Write).await?;
+            format!("/crm/deals/{}", deal_id)
+        }
+        (None, Some(contact_id)) => {
+            require_access(&db, &current_user, RecordKind::Customer, enrollment.customer_id, Access::Write).await?;
+            format!("/crm/customers/{}/contacts/{}", enrollment.customer_id, contact_id)
+        }
+        (None, None) => return Err(StatusCode::NOT_FOUND),
+    };
+
+    let reason = format!("Stopped by {} {}", current_user.first_name, current_user.last_name);
+    let stopped = sequences::stop(&db, id, &reason).await.map_err(|e| {
+        tracing::error!("Error stopping sequence enrollment: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    if stopped {
+        let _ = create_audit_log(
+            &db,
+            &current_user,
+            "stop".to_string(),
+            "sequence".to_string(),
+            Some(enrollment.sequence_id),
+            None,
+            Some(serde_json::json!({ "enrollment_id": id })),
+        )
+        .await;
+    }
+
+    Ok(Redirect::to(&back_to))
+}
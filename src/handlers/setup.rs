@@ -0,0 +1,134 @@
+use axum::{
+    extract::{Form, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Redirect, Response},
+};
+use askama::Template;
+use serde::Deserialize;
+
+use crate::{
+    database::Database,
+    middleware::ClientInfo,
+    services::{
+        password_policy::{self, PasswordPolicy},
+        setup::{self, NewOrganization},
+    },
+    utils::hash_password,
+};
+
+#[derive(Template)]
+#[template(path = "setup.html")]
+struct SetupTemplate {
+    form: SetupForm,
+    error: String,
+    password_policy: PasswordPolicy,
+}
+
+#[derive(Deserialize, Default)]
+pub struct SetupForm {
+    organization_name: String,
+    first_name: String,
+    last_name: String,
+    email: String,
+    #[serde(default)]
+    password: String,
+}
+
+async fn render_setup_page(db: &Database, mut form: SetupForm, error: String) -> Html<String> {
+    let password_policy = PasswordPolicy::load(db).await.unwrap_or_else(|e| {
+        tracing::error!("Error loading password policy: {}", e);
+        PasswordPolicy::default()
+    });
+    form.password.clear();
+    let template = SetupTemplate {
+        form,
+        error,
+        password_policy,
+    };
+    Html(template.render().unwrap())
+}
+
+async fn setup_complete(db: &Database) -> Result<bool, StatusCode> {
+    setup::is_complete(db).await.map_err(|e| {
+        tracing::error!("Error checking setup: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
+
+// First-run page, only reachable while the database has no organization
+pub async fn setup_page(State(db): State<Database>) -> Result<Response, StatusCode> {
+    if setup_complete(&db).await? {
+        return Ok(Redirect::to("/login").into_response());
+    }
+
+    Ok(render_setup_page(&db, SetupForm::default(), String::new()).await.into_response())
+}
+
+pub async fn complete_setup(
+    State(db): State<Database>,
+    client: ClientInfo,
+    Form(form): Form<SetupForm>,
+) -> Result<Response, StatusCode> {
+    if setup_complete(&db).await? {
+        return Ok(Redirect::to("/login").into_response());
+    }
+
+    let organization_name = form.organization_name.trim();
+    let first_name = form.first_name.trim();
+    let last_name = form.last_name.trim();
+    let email = form.email.trim();
+    if organization_name.is_empty() || first_name.is_empty() || last_name.is_empty() {
+        let error = "Enter the organization name and your first and last name.".to_string();
+        return Ok(render_setup_page(&db, form, error).await.into_response());
+    }
+    if !email.contains('@') || email.len() > 255 {
+        let error = "Enter a valid email address.".to_string();
+        return Ok(render_setup_page(&db, form, error).await.into_response());
+    }
+
+    let checked = password_policy::validate(&db, None, &form.password).await.map_err(|e| {
+        tracing::error!("Error checking password policy: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    if let Err(error) = checked {
+        return Ok(render_setup_page(&db, form, error).await.into_response());
+    }
+
+    let password_hash = hash_password(&form.password).map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let organization = NewOrganization {
+        name: organization_name,
+        email,
+        first_name,
+        last_name,
+        password_hash: &password_hash,
+    };
+    let admin_id = match setup::complete(&db, organization).await {
+        Ok(Some(admin_id)) => admin_id,
+        // Someone else finished setup while the form was open
+        Ok(None) => return Ok(Redirect::to("/login").into_response()),
+        Err(e) => {
+            tracing::error!("Error completing setup: {}", e);
+            return Err(StatusCode::INTERNAL_SERVER_ERROR);
+        }
+    };
+
+    if let Err(e) = password_policy::remember(&db, admin_id, &password_hash).await {
+        tracing::error!("Error recording password history: {}", e);
+    }
+
+    let _ = sqlx::query(
+        r#"
+        INSERT INTO audit_logs (user_id, action, resource_type, new_values, ip_address, user_agent)
+        VALUES ($1, 'setup', 'organization', $2, $3, $4)
+        "#,
+    )
+    .bind(admin_id)
+    .bind(serde_json::json!({ "name": organization_name, "admin_email": email }))
+    .bind(&client.ip_address)
+    .bind(&client.user_agent)
+    .execute(&db)
+    .await;
+
+    Ok(Redirect::to("/login").into_response())
+}
@@ -0,0 +1,419 @@
+use axum::{
+    extract::{Form, Path, Query, State},
+    http::StatusCode,
+    response::{Html, Json, Redirect},
+};
+use askama::Template;
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+use crate::{
+    database::Database,
+    handlers::team::create_audit_log,
+    middleware::{AuthUser, CurrentUser},
+    models::{StatusIncident, StatusMaintenance, INCIDENT_IMPACTS, STATUS_COMPONENTS},
+    services::status::{self, IncidentInput, MaintenanceInput, StatusSummary},
+    utils::timezone::parse_local_input,
+};
+
+#[derive(Template)]
+#[template(path = "public/status.html")]
+struct StatusTemplate {
+    summary: StatusSummary,
+}
+
+#[derive(Template)]
+#[template(path = "team/status.html")]
+struct StatusAdminTemplate {
+    incidents: Vec<StatusIncident>,
+    maintenance: Vec<StatusMaintenance>,
+    components: &'static [(&'static str, &'static str)],
+    impacts: &'static [(&'static str, &'static str)],
+    now: DateTime<Utc>,
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct StatusAdminQuery {
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct IncidentForm {
+    title: String,
+    message: Option<String>,
+    component: String,
+    impact: String,
+}
+
+#[derive(Deserialize)]
+pub struct MaintenanceForm {
+    title: String,
+    description: Option<String>,
+    component: String,
+    // datetime-local values, in UTC like the rest of the status page
+    starts_at: String,
+    ends_at: String,
+}
+
+// The JSON variant for monitoring tools. Field names are part of the
+// contract, so they're spelled out here rather than taken from the models.
+#[derive(Serialize)]
+pub struct StatusResponse {
+    status: &'static str,
+    generated_at: DateTime<Utc>,
+    components: Vec<ComponentResponse>,
+    incidents: Vec<IncidentResponse>,
+    maintenance: Vec<MaintenanceResponse>,
+}
+
+#[derive(Serialize)]
+pub struct ComponentResponse {
+    id: &'static str,
+    name: &'static str,
+    status: &'static str,
+    uptime_24h: Option<f64>,
+    uptime_7d: Option<f64>,
+    uptime_90d: Option<f64>,
+}
+
+#[derive(Serialize)]
+pub struct IncidentResponse {
+    id: Uuid,
+    title: String,
+    message: Option<String>,
+    component: String,
+    impact: String,
+    started_at: DateTime<Utc>,
+    resolved_at: Option<DateTime<Utc>>,
+    updated_at: DateTime<Utc>,
+}
+
+#[derive(Serialize)]
+pub struct MaintenanceResponse {
+    id: Uuid,
+    title: String,
+    description: Option<String>,
+    component: String,
+    starts_at: DateTime<Utc>,
+    ends_at: DateTime<Utc>,
+    in_progress: bool,
+}
+
+fn require_admin(current_user: &CurrentUser) -> Result<(), StatusCode> {
+    if current_user.has_manage_roles {
+        Ok(())
+    } else {
+        Err(StatusCode::FORBIDDEN)
+    }
+}
+
+fn trimmed(value: &Option<String>) -> Option<&str> {
+    value.as_deref().map(str::trim).filter(|v| !v.is_empty())
+}
+
+fn incident_input(form: &IncidentForm) -> IncidentInput<'_> {
+    IncidentInput {
+        title: form.title.trim(),
+        message: trimmed(&form.message),
+        component: &form.component,
+        impact: &form.impact,
+    }
+}
+
+fn error_redirect(error: &str) -> Redirect {
+    Redirect::to(&format!("/team/status?error={}", urlencoding::encode(error)))
+}
+
+async fn load_summary(db: &Database) -> Result<StatusSummary, StatusCode> {
+    status::summary(db).await.map_err(|e| {
+        tracing::error!("Error loading service status: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })
+}
+
+// Public: uptime, open and recent incidents, and planned maintenance
+pub async fn status_page(State(db): State<Database>) -> Result<Html<String>, StatusCode> {
+    let template = StatusTemplate { summary: load_summary(&db).await? };
+    Ok(Html(template.render().unwrap()))
+}
+
+pub async fn status_json(State(db): State<Database>) -> Result<Json<StatusResponse>, StatusCode> {
+    let summary = load_summary(&db).await?;
+    let now = summary.generated_at;
+
+    Ok(Json(StatusResponse {
+        status: summary.status,
+        generated_at: now,
+        components: summary
+            .components
+            .into_iter()
+            .map(|component| ComponentResponse {
+                id: component.id,
+                name: component.name,
+                status: component.status,
+                uptime_24h: component.uptime_day,
+                uptime_7d: component.uptime_week,
+                uptime_90d: component.uptime_quarter,
+            })
+            .collect(),
+        incidents: summary
+            .incidents
+            .into_iter()
+            .map(|incident| IncidentResponse {
+                id: incident.id,
+                title: incident.title,
+                message: incident.message,
+                component: incident.component,
+                impact: incident.impact,
+                started_at: incident.started_at,
+                resolved_at: incident.resolved_at,
+                updated_at: incident.updated_at,
+            })
+            .collect(),
+        maintenance: summary
+            .maintenance
+            .into_iter()
+            .map(|window| MaintenanceResponse {
+                in_progress: window.is_in_progress(now),
+                id: window.id,
+                title: window.title,
+                description: window.description,
+                component: window.component,
+                starts_at: window.starts_at,
+                ends_at: window.ends_at,
+            })
+            .collect(),
+    }))
+}
+
+pub async fn status_admin_page(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Query(query): Query<StatusAdminQuery>,
+) -> Result<Html<String>, StatusCode> {
+    require_admin(&current_user)?;
+
+    let incidents = status::list_incidents(&db).await.map_err(|e| {
+        tracing::error!("Error loading status incidents: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    let maintenance = status::list_maintenance(&db).await.map_err(|e| {
+        tracing::error!("Error loading maintenance windows: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let template = StatusAdminTemplate {
+        incidents,
+        maintenance,
+        components: STATUS_COMPONENTS,
+        impacts: INCIDENT_IMPACTS,
+        now: Utc::now(),
+        error: query.error,
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+pub async fn create_incident(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Form(form): Form<IncidentForm>,
+) -> Result<Redirect, StatusCode> {
+    require_admin(&current_user)?;
+
+    let created = status::create_incident(&db, &incident_input(&form), current_user.id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error creating status incident: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    let id = match created {
+        Ok(id) => id,
+        Err(error) => return Ok(error_redirect(&error)),
+    };
+
+    let _ = create_audit_log(
+        &db,
+        &current_user,
+        "create".to_string(),
+        "status_incident".to_string(),
+        Some(id),
+        None,
+        Some(serde_json::json!({ "title": form.title.trim(), "component": form.component, "impact": form.impact })),
+    )
+    .await;
+
+    Ok(Redirect::to("/team/status"))
+}
+
+pub async fn update_incident(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+    Form(form): Form<IncidentForm>,
+) -> Result<Redirect, StatusCode> {
+    require_admin(&current_user)?;
+
+    let updated = status::update_incident(&db, id, &incident_input(&form))
+        .await
+        .map_err(|e| {
+            tracing::error!("Error updating status incident: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    if let Err(error) = updated {
+        return Ok(error_redirect(&error));
+    }
+
+    let _ = create_audit_log(
+        &db,
+        &current_user,
+        "update".to_string(),
+        "status_incident".to_string(),
+        Some(id),
+        None,
+        Some(serde_json::json!({ "title": form.title.trim(), "component": form.component, "impact": form.impact })),
+    )
+    .await;
+
+    Ok(Redirect::to("/team/status"))
+}
+
+pub async fn resolve_incident(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<Redirect, StatusCode> {
+    require_admin(&current_user)?;
+
+    let resolved = status::resolve_incident(&db, id).await.map_err(|e| {
+        tracing::error!("Error resolving status incident: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    if resolved {
+        let _ = create_audit_log(
+            &db,
+            &current_user,
+            "resolve".to_string(),
+            "status_incident".to_string(),
+            Some(id),
+            None,
+            None,
+        )
+        .await;
+    }
+
+    Ok(Redirect::to("/team/status"))
+}
+
+pub async fn delete_incident(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<Redirect, StatusCode> {
+    require_admin(&current_user)?;
+
+    sqlx::query("DELETE FROM status_incidents WHERE id = $1")
+        .bind(id)
+        .execute(&db)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error deleting status incident: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    let _ = create_audit_log(
+        &db,
+        &current_user,
+        "delete".to_string(),
+        "status_incident".to_string(),
+        Some(id),
+        None,
+        None,
+    )
+    .await;
+
+    Ok(Redirect::to("/team/status"))
+}
+
+pub async fn create_maintenance(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Form(form): Form<MaintenanceForm>,
+) -> Result<Redirect, StatusCode> {
+    require_admin(&current_user)?;
+
+    let (Some(starts_at), Some(ends_at)) =
+        (parse_local_input(&form.starts_at, Tz::UTC), parse_local_input(&form.ends_at, Tz::UTC))
+    else {
+        return Ok(error_redirect("Enter when the window starts and ends"));
+    };
+    let input = MaintenanceInput {
+        title: form.title.trim(),
+        description: trimmed(&form.description),
+        component: &form.component,
+        starts_at,
+        ends_at,
+    };
+
+    let created = status::create_maintenance(&db, &input, current_user.id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error creating maintenance window: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    let id = match created {
+        Ok(id) => id,
+        Err(error) => return Ok(error_redirect(&error)),
+    };
+
+    let _ = create_audit_log(
+        &db,
+        &current_user,
+        "create".to_string(),
+        "status_maintenance".to_string(),
+        Some(id),
+        None,
+        Some(serde_json::json!({
+            "title": input.title,
+            "component": input.component,
+            "starts_at": starts_at,
+            "ends_at": ends_at,
+        })),
+    )
+    .await;
+
+    Ok(Redirect::to("/team/status"))
+}
+
+// Cancels a window that hasn't happened, or clears one from the list
+pub async fn delete_maintenance(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<Redirect, StatusCode> {
+    require_admin(&current_user)?;
+
+    sqlx::query("DELETE FROM status_maintenance WHERE id = $1")
+        .bind(id)
+        .execute(&db)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error deleting maintenance window: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+    let _ = create_audit_log(
+        &db,
+        &current_user,
+        "delete".to_string(),
+        "status_maintenance".to_string(),
+        Some(id),
+        None,
+        None,
+    )
+    .await;
+
+    Ok(Redirect::to("/team/status"))
+}
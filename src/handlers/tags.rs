@@ -0,0 +1,164 @@
+use axum::{
+    extract::{Form, Path, Query, State},
+    http::StatusCode,
+    response::{Html, Redirect},
+};
+use askama::Template;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    database::Database,
+    handlers::team::create_audit_log,
+    middleware::{AuthUser, CurrentUser},
+    models::{TagUsage, TAG_COLORS},
+    services::tags,
+};
+
+#[derive(Template)]
+#[template(path = "crm/tags.html")]
+struct TagsTemplate {
+    current_user: CurrentUser,
+    tags: Vec<TagUsage>,
+    colors: &'static [(&'static str, &'static str)],
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct TagsQuery {
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct TagForm {
+    name: String,
+    color: String,
+}
+
+fn require_admin(current_user: &CurrentUser) -> Result<(), StatusCode> {
+    if current_user.has_manage_roles {
+        Ok(())
+    } else {
+        Err(StatusCode::FORBIDDEN)
+    }
+}
+
+fn error_redirect(error: &str) -> Redirect {
+    Redirect::to(&format!("/crm/tags?error={}", urlencoding::encode(error)))
+}
+
+// Tags are made on the fly from the record forms; this is where they're
+// tidied up
+pub async fn tags_page(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Query(query): Query<TagsQuery>,
+) -> Result<Html<String>, StatusCode> {
+    require_admin(&current_user)?;
+
+    let tags = tags::list_with_usage(&db).await.map_err(|e| {
+        tracing::error!("Error loading tags: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let template = TagsTemplate { current_user, tags, colors: TAG_COLORS, error: query.error };
+    Ok(Html(template.render().unwrap()))
+}
+
+pub async fn create_tag(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Form(form): Form<TagForm>,
+) -> Result<Redirect, StatusCode> {
+    require_admin(&current_user)?;
+
+    let name = form.name.trim();
+    let created = tags::create(&db, name, &form.color, current_user.id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Error creating tag: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+    let id = match created {
+        Ok(id) => id,
+        Err(error) => return Ok(error_redirect(&error)),
+    };
+
+    let _ = create_audit_log(
+        &db,
+        &current_user,
+        "create".to_string(),
+        "tag".to_string(),
+        Some(id),
+        None,
+        Some(serde_json::json!({ "name": name, "color": form.color })),
+    )
+    .await;
+
+    Ok(Redirect::to("/crm/tags"))
+}
+
+pub async fn update_tag(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+    Form(form): Form<TagForm>,
+) -> Result<Redirect, StatusCode> {
+    require_admin(&current_user)?;
+
+    let before = tags::get(&db, id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let name = form.name.trim();
+    let updated = tags::update(&db, id, name, &form.color).await.map_err(|e| {
+        tracing::error!("Error updating tag: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    if let Err(error) = updated {
+        return Ok(error_redirect(&error));
+    }
+
+    let _ = create_audit_log(
+        &db,
+        &current_user,
+        "update".to_string(),
+        "tag".to_string(),
+        Some(id),
+        Some(serde_json::json!({ "name": before.name, "color": before.color })),
+        Some(serde_json::json!({ "name": name, "color": form.color })),
+    )
+    .await;
+
+    Ok(Redirect::to("/crm/tags"))
+}
+
+pub async fn delete_tag(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<Redirect, StatusCode> {
+    require_admin(&current_user)?;
+
+    let Some(tag) = tags::get(&db, id).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)? else {
+        return Ok(Redirect::to("/crm/tags"));
+    };
+    tags::delete(&db, id).await.map_err(|e| {
+        tracing::error!("Error deleting tag: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let _ = create_audit_log(
+        &db,
+        &current_user,
+        "delete".to_string(),
+        "tag".to_string(),
+        Some(id),
+        Some(serde_json::json!({ "name": tag.name, "color": tag.color })),
+        None,
+    )
+    .await;
+
+    Ok(Redirect::to("/crm/tags"))
+}
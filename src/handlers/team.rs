@@ -1,18 +1,26 @@
 use axum::{
     extract::{Path, State},
     http::StatusCode,
-    response::{Html, Redirect},
+    response::{Html, IntoResponse, Redirect, Response},
 };
 use askama::Template;
 use serde::Deserialize;
-use tower_cookies::Cookies;
 use uuid::Uuid;
 
 use crate::{
     database::Database,
-    models::{User, Role, RoleDisplay, UserWithRoles, get_all_permissions, Permission},
-    middleware::{get_current_user, CurrentUser},
-    utils::hash_password,
+    filters,
+    models::{AuditLogDisplay, User, LoginEvent, Role, RoleDisplay, UserWithRoles, get_all_permissions, Permission},
+    middleware::{
+        permission::{get_user_permissions, invalidate_all_permissions, invalidate_permissions},
+        AuthUser, CurrentUser,
+    },
+    services::{
+        audit_log::{self, AuditFilter}, dashboard::DASHBOARD_VARIANTS, hierarchy, login_events, offboarding,
+        password_policy::{self, PasswordPolicy},
+        roles, security,
+    },
+    utils::{hash_password, timezone::format_local},
 };
 
 #[derive(Template)]
@@ -25,6 +33,12 @@ struct TeamDashboardTemplate {
     current_user: CurrentUser,
 }
 
+impl TeamDashboardTemplate {
+    fn when(&self, entry: &AuditLogDisplay) -> String {
+        format_local(entry.created_at, self.current_user.timezone, "%b %d, %H:%M")
+    }
+}
+
 #[derive(Template)]
 #[template(path = "team/users.html")]
 struct UsersTemplate {
@@ -37,8 +51,12 @@ struct UsersTemplate {
 struct UserFormTemplate {
     user: Option<UserWithRoles>,
     roles: Vec<RoleDisplay>,
+    managers: Vec<User>,
+    password_policy: PasswordPolicy,
     error: String,
     current_user: CurrentUser,
+    // Recent sign-in attempts of the user being edited
+    login_events: Vec<LoginEvent>,
 }
 
 #[derive(Template)]
@@ -56,6 +74,23 @@ struct RoleFormTemplate {
     error: String,
     current_user: CurrentUser,
     role_permissions: Vec<String>,
+    dashboards: Vec<(String, String)>,
+    // Roles this one may extend: not itself or anything below it
+    parent_options: Vec<(Uuid, String)>,
+    // Granted through the saved parent, shown next to the checkboxes
+    inherited_permissions: Vec<String>,
+    // Warehouses the role's inventory access can be limited to
+    warehouse_options: Vec<(Uuid, String)>,
+}
+
+impl RoleFormTemplate {
+    fn is_parent(&self, id: &Uuid) -> bool {
+        self.role.as_ref().and_then(|role| role.parent_role_id.as_ref()) == Some(id)
+    }
+
+    fn is_warehouse(&self, id: &Uuid) -> bool {
+        self.role.as_ref().and_then(|role| role.warehouse_id.as_ref()) == Some(id)
+    }
 }
 
 // Fixed form structures to handle HTML form data properly
@@ -118,25 +153,11 @@ pub struct LoginForm {
     password: String,
 }
 
-use serde::Serialize;
-
-#[derive(Debug, Serialize)]
-pub struct AuditLogDisplay {
-    pub id: Uuid,
-    pub user_name: String,
-    pub action: String,
-    pub resource_type: String,
-    pub created_at: chrono::DateTime<chrono::Utc>,
-}
-
 // Team Dashboard
 pub async fn team_dashboard(
-    cookies: Cookies,
+    AuthUser(current_user): AuthUser,
     State(db): State<Database>,
 ) -> Result<Html<String>, StatusCode> {
-    let current_user = get_current_user(cookies, &db).await
-        .ok_or(StatusCode::UNAUTHORIZED)?;
-
     if !current_user.has_team_read {
         return Err(StatusCode::FORBIDDEN);
     }
@@ -156,8 +177,16 @@ pub async fn team_dashboard(
         .await
         .unwrap_or(0);
 
-    // Get recent audit activities - simplified query to avoid the IpAddr issue
-    let recent_activities = vec![]; // Simplified for now to avoid the IpAddr issue
+    let recent_activities = if current_user.can("audit:read") {
+        audit_log::search(&db, &AuditFilter::default(), 10, 0)
+            .await
+            .map_err(|e| {
+                tracing::error!("Error loading recent audit entries: {}", e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?
+    } else {
+        vec![]
+    };
 
     let template = TeamDashboardTemplate {
         user_count,
@@ -172,12 +201,9 @@ pub async fn team_dashboard(
 
 // Users Management
 pub async fn users_list(
-    cookies: Cookies,
+    AuthUser(current_user): AuthUser,
     State(db): State<Database>,
 ) -> Result<Html<String>, StatusCode> {
-    let current_user = get_current_user(cookies, &db).await
-        .ok_or(StatusCode::UNAUTHORIZED)?;
-
     if !current_user.has_team_read {
         return Err(StatusCode::FORBIDDEN);
     }
@@ -189,41 +215,21 @@ pub async fn users_list(
 }
 
 pub async fn user_form(
-    cookies: Cookies,
+    AuthUser(current_user): AuthUser,
     State(db): State<Database>,
 ) -> Result<Html<String>, StatusCode> {
-    let current_user = get_current_user(cookies, &db).await
-        .ok_or(StatusCode::UNAUTHORIZED)?;
-
     if !current_user.has_team_write {
         return Err(StatusCode::FORBIDDEN);
     }
 
-    let roles = sqlx::query_as::<_, Role>("SELECT * FROM roles WHERE is_active = true ORDER BY name")
-        .fetch_all(&db)
-        .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
-        .into_iter()
-        .map(RoleDisplay::from)
-        .collect();
-
-    let template = UserFormTemplate {
-        user: None,
-        roles,
-        error: String::new(),
-        current_user,
-    };
-    Ok(Html(template.render().unwrap()))
+    render_user_form(&db, None, String::new(), current_user).await
 }
 
 pub async fn user_edit_form(
-    cookies: Cookies,
+    AuthUser(current_user): AuthUser,
     State(db): State<Database>,
     Path(user_id): Path<Uuid>,
 ) -> Result<Html<String>, StatusCode> {
-    let current_user = get_current_user(cookies, &db).await
-        .ok_or(StatusCode::UNAUTHORIZED)?;
-
     if !current_user.has_team_write {
         return Err(StatusCode::FORBIDDEN);
     }
@@ -231,32 +237,56 @@ pub async fn user_edit_form(
     let user = get_user_with_roles(&db, user_id).await
         .map_err(|_| StatusCode::NOT_FOUND)?;
 
+    render_user_form(&db, Some(user), String::new(), current_user).await
+}
+
+async fn render_user_form(
+    db: &Database,
+    user: Option<UserWithRoles>,
+    error: String,
+    current_user: CurrentUser,
+) -> Result<Html<String>, StatusCode> {
     let roles = sqlx::query_as::<_, Role>("SELECT * FROM roles WHERE is_active = true ORDER BY name")
-        .fetch_all(&db)
+        .fetch_all(db)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
         .into_iter()
         .map(RoleDisplay::from)
         .collect();
 
+    let managers = manager_options(db, user.as_ref().map(|user| user.id)).await?;
+
+    let password_policy = PasswordPolicy::load(db).await.map_err(|e| {
+        tracing::error!("Error loading password policy: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let login_events = match &user {
+        Some(user) => login_events::recent(db, user.id, 20).await.map_err(|e| {
+            tracing::error!("Error loading login history: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?,
+        None => Vec::new(),
+    };
+
     let template = UserFormTemplate {
-        user: Some(user),
+        user,
         roles,
-        error: String::new(),
+        managers,
+        password_policy,
+        error,
         current_user,
+        login_events,
     };
     Ok(Html(template.render().unwrap()))
 }
 
 // Updated create_user function with better form handling
 pub async fn create_user(
-    cookies: Cookies,
+    AuthUser(current_user): AuthUser,
     State(db): State<Database>,
     body: String, // Use raw body to handle form parsing manually
-) -> Result<Redirect, StatusCode> {
-    let current_user = get_current_user(cookies, &db).await
-        .ok_or(StatusCode::UNAUTHORIZED)?;
-
+) -> Result<Response, StatusCode> {
     if !current_user.has_team_write {
         return Err(StatusCode::FORBIDDEN);
     }
@@ -269,14 +299,19 @@ pub async fn create_user(
     let last_name = form_data.get("last_name").ok_or(StatusCode::BAD_REQUEST)?.clone();
     let password = form_data.get("password").cloned();
     let is_active = form_data.contains_key("is_active");
+    let manager_id = parse_manager_id(&form_data)?;
     
     // Handle role_ids - get all values with this key
     let role_ids = get_form_values(&body, "role_ids");
 
     // Validate password is provided for new users
     let password = password.ok_or(StatusCode::BAD_REQUEST)?;
-    if password.len() < 6 {
-        return Err(StatusCode::BAD_REQUEST);
+    let checked = password_policy::validate(&db, None, &password).await.map_err(|e| {
+        tracing::error!("Error checking password policy: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    if let Err(message) = checked {
+        return Ok(render_user_form(&db, None, message, current_user).await?.into_response());
     }
 
     let password_hash = hash_password(&password)
@@ -285,8 +320,8 @@ pub async fn create_user(
     // Create user
     let user = sqlx::query_as::<_, User>(
         r#"
-        INSERT INTO users (email, password_hash, first_name, last_name, is_active)
-        VALUES ($1, $2, $3, $4, $5)
+        INSERT INTO users (email, password_hash, first_name, last_name, is_active, manager_id)
+        VALUES ($1, $2, $3, $4, $5, $6)
         RETURNING *
         "#,
     )
@@ -295,10 +330,15 @@ pub async fn create_user(
     .bind(&first_name)
     .bind(&last_name)
     .bind(is_active)
+    .bind(manager_id)
     .fetch_one(&db)
     .await
     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
 
+    if let Err(e) = password_policy::remember(&db, user.id, &password_hash).await {
+        tracing::error!("Error recording password history: {}", e);
+    }
+
     // Assign roles
     for role_id_str in role_ids {
         if let Ok(role_id) = Uuid::parse_str(&role_id_str) {
@@ -316,7 +356,7 @@ pub async fn create_user(
     // Create audit log
     let _ = create_audit_log(
         &db,
-        current_user.id,
+        &current_user,
         "create".to_string(),
         "user".to_string(),
         Some(user.id),
@@ -325,22 +365,20 @@ pub async fn create_user(
             "email": email,
             "first_name": first_name,
             "last_name": last_name,
-            "is_active": is_active
+            "is_active": is_active,
+            "manager_id": manager_id
         })),
     ).await;
 
-    Ok(Redirect::to("/team/users"))
+    Ok(Redirect::to("/team/users").into_response())
 }
 
 pub async fn update_user(
-    cookies: Cookies,
+    AuthUser(current_user): AuthUser,
     State(db): State<Database>,
     Path(user_id): Path<Uuid>,
     body: String, // Use raw body to handle form parsing manually
-) -> Result<Redirect, StatusCode> {
-    let current_user = get_current_user(cookies, &db).await
-        .ok_or(StatusCode::UNAUTHORIZED)?;
-
+) -> Result<Response, StatusCode> {
     if !current_user.has_team_write {
         return Err(StatusCode::FORBIDDEN);
     }
@@ -353,42 +391,76 @@ pub async fn update_user(
     let last_name = form_data.get("last_name").ok_or(StatusCode::BAD_REQUEST)?.clone();
     let password = form_data.get("password").cloned();
     let is_active = form_data.contains_key("is_active");
+    let manager_id = parse_manager_id(&form_data)?;
     
     // Handle role_ids - get all values with this key
     let role_ids = get_form_values(&body, "role_ids");
 
-    // Handle password update properly
-    if let Some(password) = &password {
-        if !password.is_empty() && password.len() >= 6 {
-            let password_hash = hash_password(password)
-                .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-            
-            // Update with password
-            sqlx::query(
-                "UPDATE users SET email = $1, first_name = $2, last_name = $3, is_active = $4, password_hash = $5, updated_at = NOW() WHERE id = $6"
-            )
-            .bind(&email)
-            .bind(&first_name)
-            .bind(&last_name)
-            .bind(is_active)
-            .bind(&password_hash)
-            .bind(user_id)
-            .execute(&db)
+    // A user can't report to themselves or to anyone in their own reporting line
+    if let Some(manager_id) = manager_id {
+        let cycle = hierarchy::would_create_cycle(&db, user_id, manager_id)
             .await
             .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-        } else {
-            // Update without password
-            sqlx::query(
-                "UPDATE users SET email = $1, first_name = $2, last_name = $3, is_active = $4, updated_at = NOW() WHERE id = $5"
-            )
-            .bind(&email)
-            .bind(&first_name)
-            .bind(&last_name)
-            .bind(is_active)
-            .bind(user_id)
-            .execute(&db)
+        if cycle {
+            return Err(StatusCode::BAD_REQUEST);
+        }
+    }
+
+    // Switching off someone who still owns records would strand them, so
+    // that goes through the deactivate page and its successor
+    if !is_active {
+        let user = get_user_with_roles(&db, user_id).await
+            .map_err(|_| StatusCode::NOT_FOUND)?;
+        let holdings = offboarding::holdings(&db, user_id)
             .await
             .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+        if user.is_active && !holdings.is_empty() {
+            let message = format!(
+                "{} still owns customers, deals, activities or direct reports. Use Deactivate on the users list to hand them to a successor.",
+                user.first_name
+            );
+            return Ok(render_user_form(&db, Some(user), message, current_user).await?.into_response());
+        }
+    }
+
+    // A blank password leaves the current one alone
+    let password = password.filter(|password| !password.is_empty());
+    if let Some(password) = &password {
+        let checked = password_policy::validate(&db, Some(user_id), password).await.map_err(|e| {
+            tracing::error!("Error checking password policy: {}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+        if let Err(message) = checked {
+            let user = get_user_with_roles(&db, user_id).await
+                .map_err(|_| StatusCode::NOT_FOUND)?;
+            return Ok(render_user_form(&db, Some(user), message, current_user).await?.into_response());
+        }
+    }
+
+    let roles_before = role_names(&db, user_id).await?;
+    let password_changed = password.is_some();
+
+    // Handle password update properly
+    if let Some(password) = &password {
+        let password_hash = hash_password(password)
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        // Update with password
+        sqlx::query(
+            "UPDATE users SET email = $1, first_name = $2, last_name = $3, is_active = $4, password_hash = $5, updated_at = NOW() WHERE id = $6"
+        )
+        .bind(&email)
+        .bind(&first_name)
+        .bind(&last_name)
+        .bind(is_active)
+        .bind(&password_hash)
+        .bind(user_id)
+        .execute(&db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+        if let Err(e) = password_policy::remember(&db, user_id, &password_hash).await {
+            tracing::error!("Error recording password history: {}", e);
         }
     } else {
         // Update without password
@@ -405,6 +477,13 @@ pub async fn update_user(
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
     }
 
+    sqlx::query("UPDATE users SET manager_id = $1 WHERE id = $2")
+        .bind(manager_id)
+        .bind(user_id)
+        .execute(&db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
     // Update roles - remove existing and add new ones
     sqlx::query("DELETE FROM user_roles WHERE user_id = $1")
         .bind(user_id)
@@ -424,11 +503,31 @@ pub async fn update_user(
             .await;
         }
     }
+    invalidate_permissions(user_id);
+
+    // Let the user know when their password or access changed
+    if password_changed {
+        if let Err(e) = security::record_change(&db, user_id, "password_changed", "Password reset by an administrator", current_user.id).await {
+            tracing::error!("Error recording password change: {}", e);
+        }
+    }
+
+    let roles_after = role_names(&db, user_id).await?;
+    if roles_after != roles_before {
+        let detail = if roles_after.is_empty() {
+            "all roles removed".to_string()
+        } else {
+            format!("now {}", roles_after.join(", "))
+        };
+        if let Err(e) = security::record_change(&db, user_id, "roles_changed", &detail, current_user.id).await {
+            tracing::error!("Error recording role change: {}", e);
+        }
+    }
 
     // Create audit log
     let _ = create_audit_log(
         &db,
-        current_user.id,
+        &current_user,
         "update".to_string(),
         "user".to_string(),
         Some(user_id),
@@ -437,21 +536,19 @@ pub async fn update_user(
             "email": email,
             "first_name": first_name,
             "last_name": last_name,
-            "is_active": is_active
+            "is_active": is_active,
+            "manager_id": manager_id
         })),
     ).await;
 
-    Ok(Redirect::to("/team/users"))
+    Ok(Redirect::to("/team/users").into_response())
 }
 
 pub async fn lock_user(
-    cookies: Cookies,
+    AuthUser(current_user): AuthUser,
     State(db): State<Database>,
     Path(user_id): Path<Uuid>,
 ) -> Result<Redirect, StatusCode> {
-    let current_user = get_current_user(cookies, &db).await
-        .ok_or(StatusCode::UNAUTHORIZED)?;
-
     if !current_user.has_team_write {
         return Err(StatusCode::FORBIDDEN);
     }
@@ -473,7 +570,7 @@ pub async fn lock_user(
     // Create audit log
     let _ = create_audit_log(
         &db,
-        current_user.id,
+        &current_user,
         "lock".to_string(),
         "user".to_string(),
         Some(user_id),
@@ -485,19 +582,16 @@ pub async fn lock_user(
 }
 
 pub async fn unlock_user(
-    cookies: Cookies,
+    AuthUser(current_user): AuthUser,
     State(db): State<Database>,
     Path(user_id): Path<Uuid>,
 ) -> Result<Redirect, StatusCode> {
-    let current_user = get_current_user(cookies, &db).await
-        .ok_or(StatusCode::UNAUTHORIZED)?;
-
     if !current_user.has_team_write {
         return Err(StatusCode::FORBIDDEN);
     }
 
     sqlx::query(
-        "UPDATE users SET is_locked = false, locked_at = NULL, locked_by = NULL WHERE id = $1"
+        "UPDATE users SET is_locked = false, locked_at = NULL, locked_by = NULL, locked_until = NULL, failed_login_count = 0 WHERE id = $1"
     )
     .bind(user_id)
     .execute(&db)
@@ -507,7 +601,7 @@ pub async fn unlock_user(
     // Create audit log
     let _ = create_audit_log(
         &db,
-        current_user.id,
+        &current_user,
         "unlock".to_string(),
         "user".to_string(),
         Some(user_id),
@@ -518,90 +612,11 @@ pub async fn unlock_user(
     Ok(Redirect::to("/team/users"))
 }
 
-pub async fn delete_user(
-    cookies: Cookies,
-    State(db): State<Database>,
-    Path(user_id): Path<Uuid>,
-) -> Result<Redirect, StatusCode> {
-    let current_user = get_current_user(cookies, &db).await.ok_or(StatusCode::UNAUTHORIZED)?;
-
-    if !current_user.has_team_delete {
-        return Err(StatusCode::FORBIDDEN);
-    }
-
-    if current_user.id == user_id {
-        return Err(StatusCode::BAD_REQUEST);
-    }
-
-    let user_to_delete = sqlx::query_as::<_, User>("SELECT * FROM users WHERE id = $1")
-        .bind(user_id)
-        .fetch_one(&db)
-        .await
-        .map_err(|_| StatusCode::NOT_FOUND)?;
-
-    let mut tx = db.begin().await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
-
-    let admin_id = current_user.id;
-
-    sqlx::query("UPDATE deals SET created_by = $1 WHERE created_by = $2")
-        .bind(admin_id).bind(user_id).execute(&mut *tx).await
-        .map_err(|e| { eprintln!("Error updating deals: {}", e); StatusCode::INTERNAL_SERVER_ERROR })?;
-
-    sqlx::query("UPDATE activities SET created_by = $1 WHERE created_by = $2")
-        .bind(admin_id).bind(user_id).execute(&mut *tx).await
-        .map_err(|e| { eprintln!("Error updating activities: {}", e); StatusCode::INTERNAL_SERVER_ERROR })?;
-
-    sqlx::query("UPDATE roles SET created_by = $1 WHERE created_by = $2")
-        .bind(admin_id).bind(user_id).execute(&mut *tx).await
-        .map_err(|e| { eprintln!("Error updating roles: {}", e); StatusCode::INTERNAL_SERVER_ERROR })?;
-        
-    sqlx::query("UPDATE user_roles SET assigned_by = $1 WHERE assigned_by = $2")
-        .bind(admin_id).bind(user_id).execute(&mut *tx).await
-        .map_err(|e| { eprintln!("Error updating user_roles: {}", e); StatusCode::INTERNAL_SERVER_ERROR })?;
-
-    sqlx::query("UPDATE users SET locked_by = NULL, locked_at = NULL WHERE locked_by = $1")
-        .bind(user_id).execute(&mut *tx).await
-        .map_err(|e| { eprintln!("Error updating users locked_by: {}", e); StatusCode::INTERNAL_SERVER_ERROR })?;
-    
-    sqlx::query("UPDATE audit_logs SET user_id = NULL WHERE user_id = $1")
-        .bind(user_id).execute(&mut *tx).await
-        .map_err(|e| { eprintln!("Error updating audit_logs: {}", e); StatusCode::INTERNAL_SERVER_ERROR })?;
-
-    sqlx::query("DELETE FROM user_roles WHERE user_id = $1")
-        .bind(user_id).execute(&mut *tx).await
-        .map_err(|e| { eprintln!("Error deleting from user_roles: {}", e); StatusCode::INTERNAL_SERVER_ERROR })?;
-
-    sqlx::query("DELETE FROM users WHERE id = $1")
-        .bind(user_id).execute(&mut *tx).await
-        .map_err(|e| { eprintln!("Error deleting user: {}", e); StatusCode::INTERNAL_SERVER_ERROR })?;
-
-    tx.commit().await.map_err(|e| { eprintln!("Error committing transaction: {}", e); StatusCode::INTERNAL_SERVER_ERROR })?;
-    
-    let _ = create_audit_log(
-        &db,
-        current_user.id,
-        "delete".to_string(),
-        "user".to_string(),
-        Some(user_id),
-        Some(serde_json::json!({
-            "email": user_to_delete.email,
-            "first_name": user_to_delete.first_name,
-            "last_name": user_to_delete.last_name
-        })),
-        None,
-    ).await;
-
-    Ok(Redirect::to("/team/users"))
-}
-
 // Roles Management
 pub async fn roles_list(
-    cookies: Cookies,
+    AuthUser(current_user): AuthUser,
     State(db): State<Database>,
 ) -> Result<Html<String>, StatusCode> {
-    let current_user = get_current_user(cookies, &db).await
-        .ok_or(StatusCode::UNAUTHORIZED)?;
-
     if !current_user.has_manage_roles {
         return Err(StatusCode::FORBIDDEN);
     }
@@ -609,22 +624,89 @@ pub async fn roles_list(
     let roles = sqlx::query_as::<_, Role>("SELECT * FROM roles ORDER BY name")
         .fetch_all(&db)
         .await
-        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    let names: HashMap<Uuid, String> = roles.iter().map(|role| (role.id, role.name.clone())).collect();
+    let warehouses: HashMap<Uuid, String> = warehouse_options(&db).await?.into_iter().collect();
+    let roles = roles
         .into_iter()
-        .map(RoleDisplay::from)
+        .map(|role| {
+            let parent_name = role.parent_role_id.and_then(|id| names.get(&id).cloned());
+            let warehouse_name = role.warehouse_id.and_then(|id| warehouses.get(&id).cloned());
+            RoleDisplay { parent_name, warehouse_name, ..RoleDisplay::from(role) }
+        })
         .collect();
 
     let template = RolesTemplate { roles, current_user };
     Ok(Html(template.render().unwrap()))
 }
 
+fn dashboard_options() -> Vec<(String, String)> {
+    DASHBOARD_VARIANTS
+        .iter()
+        .map(|(key, label, _)| (key.to_string(), label.to_string()))
+        .collect()
+}
+
+// Every role except `role_id` and the roles extending it, which would make a loop
+async fn parent_options(db: &Database, role_id: Option<Uuid>) -> Result<Vec<(Uuid, String)>, StatusCode> {
+    let excluded = match role_id {
+        Some(role_id) => roles::descendant_ids(db, role_id).await.map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?,
+        None => Vec::new(),
+    };
+    sqlx::query_as::<_, (Uuid, String)>("SELECT id, name FROM roles WHERE NOT (id = ANY($1)) ORDER BY name")
+        .bind(&excluded)
+        .fetch_all(db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+async fn warehouse_options(db: &Database) -> Result<Vec<(Uuid, String)>, StatusCode> {
+    sqlx::query_as::<_, (Uuid, String)>("SELECT id, name FROM warehouses ORDER BY name")
+        .fetch_all(db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+// The warehouse the role is limited to, if one was chosen
+async fn parse_role_warehouse(db: &Database, form_data: &HashMap<String, String>) -> Result<Option<Uuid>, StatusCode> {
+    let warehouse_id = match form_data.get("warehouse_id").map(|s| s.trim()) {
+        None | Some("") => return Ok(None),
+        Some(value) => Uuid::parse_str(value).map_err(|_| StatusCode::BAD_REQUEST)?,
+    };
+    let exists = sqlx::query_scalar::<_, bool>("SELECT EXISTS(SELECT 1 FROM warehouses WHERE id = $1)")
+        .bind(warehouse_id)
+        .fetch_one(db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if !exists {
+        return Err(StatusCode::BAD_REQUEST);
+    }
+    Ok(Some(warehouse_id))
+}
+
+// The chosen parent, refusing one that doesn't exist or would make a loop
+async fn parse_parent_role(
+    db: &Database,
+    form_data: &HashMap<String, String>,
+    role_id: Option<Uuid>,
+) -> Result<Option<Uuid>, StatusCode> {
+    let parent_id = match form_data.get("parent_role_id").map(|s| s.trim()) {
+        None | Some("") => return Ok(None),
+        Some(value) => Uuid::parse_str(value).map_err(|_| StatusCode::BAD_REQUEST)?,
+    };
+    let checked = roles::check_parent(db, role_id, parent_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    match checked {
+        Ok(()) => Ok(Some(parent_id)),
+        Err(_) => Err(StatusCode::BAD_REQUEST),
+    }
+}
+
 pub async fn role_form(
-    cookies: Cookies,
+    AuthUser(current_user): AuthUser,
     State(db): State<Database>,
 ) -> Result<Html<String>, StatusCode> {
-    let current_user = get_current_user(cookies, &db).await
-        .ok_or(StatusCode::UNAUTHORIZED)?;
-
     if !current_user.has_manage_roles {
         return Err(StatusCode::FORBIDDEN);
     }
@@ -637,18 +719,19 @@ pub async fn role_form(
         error: String::new(),
         current_user,
         role_permissions: vec![], // Empty for new role
+        dashboards: dashboard_options(),
+        parent_options: parent_options(&db, None).await?,
+        inherited_permissions: vec![],
+        warehouse_options: warehouse_options(&db).await?,
     };
     Ok(Html(template.render().unwrap()))
 }
 
 pub async fn role_edit_form(
-    cookies: Cookies,
+    AuthUser(current_user): AuthUser,
     State(db): State<Database>,
     Path(role_id): Path<Uuid>,
 ) -> Result<Html<String>, StatusCode> {
-    let current_user = get_current_user(cookies, &db).await
-        .ok_or(StatusCode::UNAUTHORIZED)?;
-
     if !current_user.has_manage_roles {
         return Err(StatusCode::FORBIDDEN);
     }
@@ -661,6 +744,12 @@ pub async fn role_edit_form(
 
     let permissions = get_all_permissions();
     let role_permissions = role.permissions.0.clone();
+    let inherited_permissions = match role.parent_role_id {
+        Some(parent_id) => roles::inherited_permissions(&db, parent_id)
+            .await
+            .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?,
+        None => Vec::new(),
+    };
 
     let template = RoleFormTemplate {
         role: Some(RoleDisplay::from(role)),
@@ -668,18 +757,19 @@ pub async fn role_edit_form(
         error: String::new(),
         current_user,
         role_permissions, // Pass the role's permissions for checking
+        dashboards: dashboard_options(),
+        parent_options: parent_options(&db, Some(role_id)).await?,
+        inherited_permissions,
+        warehouse_options: warehouse_options(&db).await?,
     };
     Ok(Html(template.render().unwrap()))
 }
 
 pub async fn create_role(
-    cookies: Cookies,
+    AuthUser(current_user): AuthUser,
     State(db): State<Database>,
     body: String, // Use raw body to handle form parsing manually
 ) -> Result<Redirect, StatusCode> {
-    let current_user = get_current_user(cookies, &db).await
-        .ok_or(StatusCode::UNAUTHORIZED)?;
-
     if !current_user.has_manage_roles {
         return Err(StatusCode::FORBIDDEN);
     }
@@ -690,6 +780,13 @@ pub async fn create_role(
     let name = form_data.get("name").ok_or(StatusCode::BAD_REQUEST)?.clone();
     let description = form_data.get("description").cloned().unwrap_or_default();
     let is_active = form_data.contains_key("is_active");
+    let is_read_only = form_data.contains_key("is_read_only");
+    let dashboard = form_data
+        .get("dashboard")
+        .filter(|d| DASHBOARD_VARIANTS.iter().any(|(key, _, _)| *key == d.as_str()))
+        .cloned();
+    let parent_role_id = parse_parent_role(&db, &form_data, None).await?;
+    let warehouse_id = parse_role_warehouse(&db, &form_data).await?;
     
     // Handle permissions - get all values with this key
     let permissions = get_form_values(&body, "permissions");
@@ -699,8 +796,8 @@ pub async fn create_role(
 
     let role = sqlx::query_as::<_, Role>(
         r#"
-        INSERT INTO roles (name, description, permissions, is_active, created_by)
-        VALUES ($1, $2, $3, $4, $5)
+        INSERT INTO roles (name, description, permissions, is_active, created_by, dashboard, is_read_only, parent_role_id, warehouse_id)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
         RETURNING *
         "#,
     )
@@ -709,6 +806,10 @@ pub async fn create_role(
     .bind(permissions_json)
     .bind(is_active)
     .bind(current_user.id)
+    .bind(&dashboard)
+    .bind(is_read_only)
+    .bind(parent_role_id)
+    .bind(warehouse_id)
     .fetch_one(&db)
     .await
     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
@@ -716,7 +817,7 @@ pub async fn create_role(
     // Create audit log
     let _ = create_audit_log(
         &db,
-        current_user.id,
+        &current_user,
         "create".to_string(),
         "role".to_string(),
         Some(role.id),
@@ -725,7 +826,11 @@ pub async fn create_role(
             "name": name,
             "description": description,
             "permissions": permissions,
-            "is_active": is_active
+            "is_active": is_active,
+            "is_read_only": is_read_only,
+            "dashboard": dashboard,
+            "parent_role_id": parent_role_id,
+            "warehouse_id": warehouse_id
         })),
     ).await;
 
@@ -733,14 +838,11 @@ pub async fn create_role(
 }
 
 pub async fn update_role(
-    cookies: Cookies,
+    AuthUser(current_user): AuthUser,
     State(db): State<Database>,
     Path(role_id): Path<Uuid>,
     body: String, // Use raw body to handle form parsing manually
 ) -> Result<Redirect, StatusCode> {
-    let current_user = get_current_user(cookies, &db).await
-        .ok_or(StatusCode::UNAUTHORIZED)?;
-
     if !current_user.has_manage_roles {
         return Err(StatusCode::FORBIDDEN);
     }
@@ -751,6 +853,13 @@ pub async fn update_role(
     let name = form_data.get("name").ok_or(StatusCode::BAD_REQUEST)?.clone();
     let description = form_data.get("description").cloned().unwrap_or_default();
     let is_active = form_data.contains_key("is_active");
+    let is_read_only = form_data.contains_key("is_read_only");
+    let dashboard = form_data
+        .get("dashboard")
+        .filter(|d| DASHBOARD_VARIANTS.iter().any(|(key, _, _)| *key == d.as_str()))
+        .cloned();
+    let parent_role_id = parse_parent_role(&db, &form_data, Some(role_id)).await?;
+    let warehouse_id = parse_role_warehouse(&db, &form_data).await?;
     
     // Handle permissions - get all values with this key
     let permissions = get_form_values(&body, "permissions");
@@ -765,6 +874,10 @@ pub async fn update_role(
             description = $2, 
             permissions = $3, 
             is_active = $4, 
+            dashboard = $6,
+            is_read_only = $7,
+            parent_role_id = $8,
+            warehouse_id = $9,
             updated_at = NOW()
         WHERE id = $5
         "#,
@@ -774,14 +887,19 @@ pub async fn update_role(
     .bind(permissions_json)
     .bind(is_active)
     .bind(role_id)
+    .bind(&dashboard)
+    .bind(is_read_only)
+    .bind(parent_role_id)
+    .bind(warehouse_id)
     .execute(&db)
     .await
     .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    invalidate_all_permissions();
 
     // Create audit log
     let _ = create_audit_log(
         &db,
-        current_user.id,
+        &current_user,
         "update".to_string(),
         "role".to_string(),
         Some(role_id),
@@ -790,7 +908,11 @@ pub async fn update_role(
             "name": name,
             "description": description,
             "permissions": permissions,
-            "is_active": is_active
+            "is_active": is_active,
+            "is_read_only": is_read_only,
+            "dashboard": dashboard,
+            "parent_role_id": parent_role_id,
+            "warehouse_id": warehouse_id
         })),
     ).await;
 
@@ -798,13 +920,10 @@ pub async fn update_role(
 }
 
 pub async fn delete_role(
-    cookies: Cookies,
+    AuthUser(current_user): AuthUser,
     State(db): State<Database>,
     Path(role_id): Path<Uuid>,
 ) -> Result<Redirect, StatusCode> {
-    let current_user = get_current_user(cookies, &db).await
-        .ok_or(StatusCode::UNAUTHORIZED)?;
-
     if !current_user.has_manage_roles {
         return Err(StatusCode::FORBIDDEN);
     }
@@ -822,6 +941,19 @@ pub async fn delete_role(
         return Err(StatusCode::CONFLICT); // Cannot delete role with assigned users
     }
 
+    // Roles extending it would quietly lose what they inherit
+    let child_count = sqlx::query_scalar::<_, i64>(
+        "SELECT COUNT(*) FROM roles WHERE parent_role_id = $1"
+    )
+    .bind(role_id)
+    .fetch_one(&db)
+    .await
+    .unwrap_or(0);
+
+    if child_count > 0 {
+        return Err(StatusCode::CONFLICT);
+    }
+
     // Get role info for audit log
     let role = sqlx::query_as::<_, Role>("SELECT * FROM roles WHERE id = $1")
         .bind(role_id)
@@ -835,11 +967,12 @@ pub async fn delete_role(
         .execute(&db)
         .await
         .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    invalidate_all_permissions();
 
     // Create audit log
     let _ = create_audit_log(
         &db,
-        current_user.id,
+        &current_user,
         "delete".to_string(),
         "role".to_string(),
         Some(role_id),
@@ -857,7 +990,25 @@ pub async fn delete_role(
 // Helper functions for form parsing
 use std::collections::HashMap;
 
-fn parse_form_data(body: &str) -> HashMap<String, String> {
+// Active users that can be picked as a manager, leaving out the user being edited
+pub(crate) async fn manager_options(db: &Database, exclude: Option<Uuid>) -> Result<Vec<User>, StatusCode> {
+    sqlx::query_as::<_, User>(
+        "SELECT * FROM users WHERE is_active = true AND ($1::uuid IS NULL OR id <> $1) ORDER BY first_name, last_name"
+    )
+    .bind(exclude)
+    .fetch_all(db)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+pub(crate) fn parse_manager_id(form_data: &HashMap<String, String>) -> Result<Option<Uuid>, StatusCode> {
+    match form_data.get("manager_id").map(|s| s.trim()) {
+        None | Some("") => Ok(None),
+        Some(value) => Uuid::parse_str(value).map(Some).map_err(|_| StatusCode::BAD_REQUEST),
+    }
+}
+
+pub(crate) fn parse_form_data(body: &str) -> HashMap<String, String> {
     let mut form_data = HashMap::new();
     
     for pair in body.split('&') {
@@ -871,7 +1022,7 @@ fn parse_form_data(body: &str) -> HashMap<String, String> {
     form_data
 }
 
-fn get_form_values(body: &str, key: &str) -> Vec<String> {
+pub(crate) fn get_form_values(body: &str, key: &str) -> Vec<String> {
     let mut values = Vec::new();
     
     for pair in body.split('&') {
@@ -913,7 +1064,7 @@ async fn get_users_with_roles(db: &Database) -> Result<Vec<UserWithRoles>, sqlx:
         .map(RoleDisplay::from)
         .collect::<Vec<_>>();
 
-        let permissions = get_user_permissions_from_roles(&roles);
+        let permissions = get_user_permissions(db, user.id).await;
 
         users_with_roles.push(UserWithRoles {
             id: user.id,
@@ -924,6 +1075,7 @@ async fn get_users_with_roles(db: &Database) -> Result<Vec<UserWithRoles>, sqlx:
             is_locked: user.is_locked,
             last_login: user.last_login,
             locked_at: user.locked_at,
+            manager_id: user.manager_id,
             created_at: user.created_at,
             updated_at: user.updated_at,
             roles,
@@ -955,7 +1107,7 @@ async fn get_user_with_roles(db: &Database, user_id: Uuid) -> Result<UserWithRol
     .map(RoleDisplay::from)
     .collect::<Vec<_>>();
 
-    let permissions = get_user_permissions_from_roles(&roles);
+    let permissions = get_user_permissions(db, user.id).await;
 
     Ok(UserWithRoles {
         id: user.id,
@@ -966,6 +1118,7 @@ async fn get_user_with_roles(db: &Database, user_id: Uuid) -> Result<UserWithRol
         is_locked: user.is_locked,
         last_login: user.last_login,
         locked_at: user.locked_at,
+        manager_id: user.manager_id,
         created_at: user.created_at,
         updated_at: user.updated_at,
         roles,
@@ -973,39 +1126,24 @@ async fn get_user_with_roles(db: &Database, user_id: Uuid) -> Result<UserWithRol
     })
 }
 
-fn get_user_permissions_from_roles(roles: &[RoleDisplay]) -> Vec<String> {
-    let mut permissions = Vec::new();
-    for role in roles {
-        permissions.extend(role.permissions.clone());
-    }
-    permissions.sort();
-    permissions.dedup();
-    permissions
+async fn role_names(db: &Database, user_id: Uuid) -> Result<Vec<String>, StatusCode> {
+    sqlx::query_scalar::<_, String>(
+        "SELECT r.name FROM roles r JOIN user_roles ur ON r.id = ur.role_id WHERE ur.user_id = $1 ORDER BY r.name"
+    )
+    .bind(user_id)
+    .fetch_all(db)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)
 }
 
-async fn create_audit_log(
+pub(crate) async fn create_audit_log(
     db: &Database,
-    user_id: Uuid,
+    actor: &CurrentUser,
     action: String,
     resource_type: String,
     resource_id: Option<Uuid>,
     old_values: Option<serde_json::Value>,
     new_values: Option<serde_json::Value>,
 ) -> Result<(), sqlx::Error> {
-    sqlx::query(
-        r#"
-        INSERT INTO audit_logs (user_id, action, resource_type, resource_id, old_values, new_values)
-        VALUES ($1, $2, $3, $4, $5, $6)
-        "#,
-    )
-    .bind(user_id)
-    .bind(action)
-    .bind(resource_type)
-    .bind(resource_id)
-    .bind(old_values)
-    .bind(new_values)
-    .execute(db)
-    .await?;
-
-    Ok(())
-}
\ No newline at end of file
+    audit_log::write(db, actor, &action, &resource_type, resource_id, old_values, new_values).await
+}
@@ -0,0 +1,248 @@
+use axum::{
+    extract::{Form, Path, Query, State},
+    http::StatusCode,
+    response::{Html, Redirect},
+};
+use askama::Template;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    database::Database,
+    handlers::team::create_audit_log,
+    middleware::{AuthUser, CurrentUser},
+    models::{Team, TeamMember, User},
+    services::teams,
+};
+
+#[derive(Template)]
+#[template(path = "team/teams.html")]
+struct TeamsTemplate {
+    current_user: CurrentUser,
+    teams: Vec<Team>,
+    error: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "team/team_detail.html")]
+struct TeamDetailTemplate {
+    current_user: CurrentUser,
+    team: Team,
+    members: Vec<TeamMember>,
+    // Active users not on the team yet
+    candidates: Vec<User>,
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct TeamsQuery {
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct TeamForm {
+    name: String,
+    description: Option<String>,
+    // Checkbox on the edit form: only sent when ticked
+    is_active: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct TeamMemberForm {
+    user_id: Uuid,
+    is_manager: Option<String>,
+}
+
+fn description(form: &TeamForm) -> Option<&str> {
+    form.description.as_deref().map(str::trim).filter(|d| !d.is_empty())
+}
+
+pub async fn teams_page(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Query(query): Query<TeamsQuery>,
+) -> Result<Html<String>, StatusCode> {
+    if !current_user.has_team_read {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let teams = teams::list(&db).await.map_err(|e| {
+        tracing::error!("Error loading teams: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let template = TeamsTemplate {
+        current_user,
+        teams,
+        error: query.error,
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+pub async fn create_team(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Form(form): Form<TeamForm>,
+) -> Result<Redirect, StatusCode> {
+    if !current_user.has_team_write {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let name = form.name.trim();
+
+    let created = teams::create(&db, name, description(&form), current_user.id).await.map_err(|e| {
+        tracing::error!("Error creating team: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    let id = match created {
+        Ok(id) => id,
+        Err(error) => return Ok(Redirect::to(&format!("/team/teams?error={}", urlencoding::encode(&error)))),
+    };
+
+    let _ = create_audit_log(
+        &db,
+        &current_user,
+        "create".to_string(),
+        "team".to_string(),
+        Some(id),
+        None,
+        Some(serde_json::json!({ "name": name, "description": description(&form) })),
+    )
+    .await;
+
+    Ok(Redirect::to(&format!("/team/teams/{}", id)))
+}
+
+pub async fn team_page(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+    Query(query): Query<TeamsQuery>,
+) -> Result<Html<String>, StatusCode> {
+    if !current_user.has_team_read {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let team = teams::get(&db, id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let members = teams::members(&db, id).await.map_err(|e| {
+        tracing::error!("Error loading team members: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    let candidates = sqlx::query_as::<_, User>(
+        r#"
+        SELECT * FROM users
+        WHERE is_active = true AND id NOT IN (SELECT user_id FROM team_members WHERE team_id = $1)
+        ORDER BY first_name, last_name
+        "#,
+    )
+    .bind(id)
+    .fetch_all(&db)
+    .await
+    .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    let template = TeamDetailTemplate {
+        current_user,
+        team,
+        members,
+        candidates,
+        error: query.error,
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+pub async fn update_team(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+    Form(form): Form<TeamForm>,
+) -> Result<Redirect, StatusCode> {
+    if !current_user.has_team_write {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    let (name, is_active) = (form.name.trim(), form.is_active.is_some());
+
+    let updated = teams::update(&db, id, name, description(&form), is_active).await.map_err(|e| {
+        tracing::error!("Error updating team: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    if let Err(error) = updated {
+        return Ok(Redirect::to(&format!("/team/teams/{}?error={}", id, urlencoding::encode(&error))));
+    }
+
+    let _ = create_audit_log(
+        &db,
+        &current_user,
+        "update".to_string(),
+        "team".to_string(),
+        Some(id),
+        None,
+        Some(serde_json::json!({ "name": name, "description": description(&form), "is_active": is_active })),
+    )
+    .await;
+
+    Ok(Redirect::to(&format!("/team/teams/{}", id)))
+}
+
+// Also how a member is made or unmade a manager
+pub async fn add_member(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+    Form(form): Form<TeamMemberForm>,
+) -> Result<Redirect, StatusCode> {
+    if !current_user.has_team_write {
+        return Err(StatusCode::FORBIDDEN);
+    }
+    teams::get(&db, id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+    let is_manager = form.is_manager.is_some();
+
+    teams::set_member(&db, id, form.user_id, is_manager).await.map_err(|e| {
+        tracing::error!("Error adding team member: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let _ = create_audit_log(
+        &db,
+        &current_user,
+        "set_member".to_string(),
+        "team".to_string(),
+        Some(id),
+        None,
+        Some(serde_json::json!({ "user_id": form.user_id, "is_manager": is_manager })),
+    )
+    .await;
+
+    Ok(Redirect::to(&format!("/team/teams/{}", id)))
+}
+
+pub async fn remove_member(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path((id, user_id)): Path<(Uuid, Uuid)>,
+) -> Result<Redirect, StatusCode> {
+    if !current_user.has_team_write {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    let removed = teams::remove_member(&db, id, user_id).await.map_err(|e| {
+        tracing::error!("Error removing team member: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    if removed {
+        let _ = create_audit_log(
+            &db,
+            &current_user,
+            "remove_member".to_string(),
+            "team".to_string(),
+            Some(id),
+            Some(serde_json::json!({ "user_id": user_id })),
+            None,
+        )
+        .await;
+    }
+
+    Ok(Redirect::to(&format!("/team/teams/{}", id)))
+}
@@ -0,0 +1,211 @@
+use axum::{
+    extract::{Form, Path, Query, State},
+    http::StatusCode,
+    response::{Html, Redirect},
+};
+use askama::Template;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    database::Database,
+    handlers::team::create_audit_log,
+    middleware::AuthUser,
+    models::{PickListLine, Warehouse, WarehouseLocation, WarehouseSummary},
+    services::locations::{self, NewLocation},
+};
+
+#[derive(Template)]
+#[template(path = "inventory/warehouses.html")]
+struct WarehousesTemplate {
+    warehouses: Vec<WarehouseSummary>,
+}
+
+#[derive(Template)]
+#[template(path = "inventory/warehouse_detail.html")]
+struct WarehouseDetailTemplate {
+    warehouse: Warehouse,
+    locations: Vec<WarehouseLocation>,
+    can_edit: bool,
+    error: Option<String>,
+}
+
+#[derive(Template)]
+#[template(path = "inventory/pick_list.html")]
+struct PickListTemplate {
+    warehouse: Warehouse,
+    lines: Vec<PickListLine>,
+    generated_at: String,
+}
+
+#[derive(Deserialize)]
+pub struct WarehouseDetailQuery {
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+pub struct LocationForm {
+    code: String,
+    aisle: Option<String>,
+    bin: Option<String>,
+    walk_order: Option<i32>,
+    // Blank when there's no sensible limit
+    capacity: Option<String>,
+}
+
+fn trimmed(value: &Option<String>) -> Option<String> {
+    value.as_deref().map(str::trim).filter(|v| !v.is_empty()).map(str::to_string)
+}
+
+async fn load_warehouse(db: &Database, id: Uuid) -> Result<Warehouse, StatusCode> {
+    locations::find_warehouse(db, id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)
+}
+
+fn back_to(id: Uuid, error: Option<&str>) -> Redirect {
+    match error {
+        Some(error) => Redirect::to(&format!("/inventory/warehouses/{}?error={}", id, urlencoding::encode(error))),
+        None => Redirect::to(&format!("/inventory/warehouses/{}", id)),
+    }
+}
+
+pub async fn warehouses_list(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+) -> Result<Html<String>, StatusCode> {
+    current_user.require("inventory:read")?;
+
+    let warehouses = locations::warehouses(&db, &current_user).await.map_err(|e| {
+        tracing::error!("Error loading warehouses: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let template = WarehousesTemplate { warehouses };
+    Ok(Html(template.render().unwrap()))
+}
+
+// A warehouse's bin locations in walk order, with what each holds
+pub async fn warehouse_detail(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+    Query(query): Query<WarehouseDetailQuery>,
+) -> Result<Html<String>, StatusCode> {
+    current_user.require("inventory:read")?;
+    current_user.require_warehouse(id)?;
+    let warehouse = load_warehouse(&db, id).await?;
+
+    let locations = locations::for_warehouse(&db, id).await.map_err(|e| {
+        tracing::error!("Error loading warehouse locations: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let template = WarehouseDetailTemplate {
+        warehouse,
+        locations,
+        can_edit: current_user.can("inventory:write"),
+        error: query.error,
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+pub async fn add_location(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+    Form(form): Form<LocationForm>,
+) -> Result<Redirect, StatusCode> {
+    current_user.require("inventory:write")?;
+    current_user.require_warehouse(id)?;
+    load_warehouse(&db, id).await?;
+
+    let capacity = match trimmed(&form.capacity) {
+        Some(capacity) => Some(capacity.parse::<i32>().map_err(|_| StatusCode::BAD_REQUEST)?),
+        None => None,
+    };
+    let location = NewLocation {
+        code: form.code,
+        aisle: trimmed(&form.aisle),
+        bin: trimmed(&form.bin),
+        walk_order: form.walk_order.unwrap_or(0),
+        capacity,
+    };
+    let added = locations::add(&db, id, &location).await.map_err(|e| {
+        tracing::error!("Error adding warehouse location: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    let location_id = match added {
+        Ok(location_id) => location_id,
+        Err(reason) => return Ok(back_to(id, Some(&reason))),
+    };
+
+    let _ = create_audit_log(
+        &db,
+        &current_user,
+        "add_location".to_string(),
+        "warehouse".to_string(),
+        Some(id),
+        None,
+        Some(serde_json::json!({
+            "location_id": location_id,
+            "code": location.code.trim(),
+            "walk_order": location.walk_order,
+            "capacity": location.capacity,
+        })),
+    )
+    .await;
+
+    Ok(back_to(id, None))
+}
+
+pub async fn delete_location(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path((id, location_id)): Path<(Uuid, Uuid)>,
+) -> Result<Redirect, StatusCode> {
+    current_user.require("inventory:write")?;
+    current_user.require_warehouse(id)?;
+
+    let removed = locations::remove(&db, id, location_id)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+    if removed {
+        let _ = create_audit_log(
+            &db,
+            &current_user,
+            "delete_location".to_string(),
+            "warehouse".to_string(),
+            Some(id),
+            Some(serde_json::json!({ "location_id": location_id })),
+            None,
+        )
+        .await;
+    }
+
+    Ok(back_to(id, None))
+}
+
+// Printable list of what's ready to ship from the warehouse, in walk order
+pub async fn pick_list(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<Html<String>, StatusCode> {
+    current_user.require("inventory:read")?;
+    current_user.require_warehouse(id)?;
+    let warehouse = load_warehouse(&db, id).await?;
+
+    let lines = locations::pick_list(&db, &current_user, id).await.map_err(|e| {
+        tracing::error!("Error loading pick list: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    let template = PickListTemplate {
+        warehouse,
+        lines,
+        generated_at: chrono::Utc::now().format("%Y-%m-%d %H:%M UTC").to_string(),
+    };
+    Ok(Html(template.render().unwrap()))
+}
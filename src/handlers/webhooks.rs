@@ -0,0 +1,287 @@
+use axum::{
+    extract::{Form, Path, State},
+    http::StatusCode,
+    response::{Html, IntoResponse, Redirect, Response},
+};
+use askama::Template;
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::{
+    database::Database,
+    middleware::{AuthUser, CurrentUser},
+    models::{Webhook, WebhookDelivery},
+    services::webhooks::{self, WEBHOOK_EVENTS},
+    utils::json_template,
+};
+
+pub struct DeliveryRow {
+    pub delivery: WebhookDelivery,
+    pub webhook_name: String,
+}
+
+#[derive(sqlx::FromRow)]
+struct DeliveryWithName {
+    #[sqlx(flatten)]
+    delivery: WebhookDelivery,
+    webhook_name: String,
+}
+
+pub struct WebhookRow {
+    pub webhook: Webhook,
+    pub event_label: String,
+}
+
+#[derive(Template)]
+#[template(path = "team/webhooks.html")]
+struct WebhooksTemplate {
+    webhooks: Vec<WebhookRow>,
+    deliveries: Vec<DeliveryRow>,
+}
+
+#[derive(Template)]
+#[template(path = "team/webhook_form.html")]
+struct WebhookFormTemplate {
+    webhook_id: Option<Uuid>,
+    form: WebhookForm,
+    events: Vec<(String, String)>,
+    fields: Vec<String>,
+    preview: Option<String>,
+    error: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+pub struct WebhookForm {
+    name: String,
+    url: String,
+    event: String,
+    payload_template: String,
+    is_active: Option<String>,
+    // "preview" re-renders the form with the sample payload instead of saving
+    action: Option<String>,
+}
+
+fn require_api_admin(current_user: &CurrentUser) -> Result<(), StatusCode> {
+    if current_user.permissions.contains(&"api:admin".to_string()) {
+        Ok(())
+    } else {
+        Err(StatusCode::FORBIDDEN)
+    }
+}
+
+fn render_form(
+    webhook_id: Option<Uuid>,
+    form: WebhookForm,
+    preview: Option<String>,
+    error: Option<String>,
+) -> Result<Html<String>, StatusCode> {
+    let event = if form.event.is_empty() { WEBHOOK_EVENTS[0].0 } else { form.event.as_str() };
+    let template = WebhookFormTemplate {
+        webhook_id,
+        fields: json_template::paths(&webhooks::sample_context(event)),
+        events: WEBHOOK_EVENTS.iter().map(|(key, label)| (key.to_string(), label.to_string())).collect(),
+        form,
+        preview,
+        error,
+    };
+    Ok(Html(template.render().unwrap()))
+}
+
+// Checks the form and returns the template to store (None for the default
+// envelope), or a message for the admin
+fn validate(form: &WebhookForm) -> Result<Option<String>, String> {
+    if form.name.trim().is_empty() || form.name.len() > 100 {
+        return Err("Give the webhook a name of at most 100 characters.".to_string());
+    }
+    if !WEBHOOK_EVENTS.iter().any(|(key, _)| *key == form.event) {
+        return Err("Choose an event.".to_string());
+    }
+    match reqwest::Url::parse(form.url.trim()) {
+        Ok(url) if url.scheme() == "http" || url.scheme() == "https" => {}
+        _ => return Err("The URL must be a full http:// or https:// address.".to_string()),
+    }
+
+    let template = form.payload_template.trim();
+    if template.is_empty() {
+        return Ok(None);
+    }
+    json_template::validate(template, &webhooks::sample_context(&form.event))?;
+    Ok(Some(template.to_string()))
+}
+
+// Renders the template (or the default envelope) against the sample event
+fn preview(form: &WebhookForm) -> Result<String, String> {
+    let context = webhooks::sample_context(&form.event);
+    let rendered = match form.payload_template.trim() {
+        "" => context.to_string(),
+        template => json_template::render(template, &context)?,
+    };
+    // Pretty-print when possible so the preview is readable
+    Ok(serde_json::from_str::<serde_json::Value>(&rendered)
+        .and_then(|value| serde_json::to_string_pretty(&value))
+        .unwrap_or(rendered))
+}
+
+pub async fn webhooks_list(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+) -> Result<Html<String>, StatusCode> {
+    require_api_admin(&current_user)?;
+
+    let webhooks = sqlx::query_as::<_, Webhook>("SELECT * FROM webhooks ORDER BY name")
+        .fetch_all(&db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .into_iter()
+        .map(|webhook| WebhookRow { event_label: webhooks::event_label(&webhook.event), webhook })
+        .collect();
+
+    let deliveries = sqlx::query_as::<_, DeliveryWithName>(
+        r#"
+        SELECT d.*, w.name as webhook_name
+        FROM webhook_deliveries d
+        JOIN webhooks w ON w.id = d.webhook_id
+        ORDER BY d.created_at DESC
+        LIMIT 25
+        "#,
+    )
+    .fetch_all(&db)
+    .await
+    .map_err(|e| {
+        tracing::error!("Error loading webhook deliveries: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?
+    .into_iter()
+    .map(|row| DeliveryRow { delivery: row.delivery, webhook_name: row.webhook_name })
+    .collect();
+
+    let template = WebhooksTemplate { webhooks, deliveries };
+    Ok(Html(template.render().unwrap()))
+}
+
+pub async fn webhook_form(
+    AuthUser(current_user): AuthUser,
+) -> Result<Html<String>, StatusCode> {
+    require_api_admin(&current_user)?;
+
+    let form = WebhookForm {
+        event: WEBHOOK_EVENTS[0].0.to_string(),
+        is_active: Some("true".to_string()),
+        ..Default::default()
+    };
+    render_form(None, form, None, None)
+}
+
+pub async fn webhook_edit_form(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<Html<String>, StatusCode> {
+    require_api_admin(&current_user)?;
+
+    let webhook = sqlx::query_as::<_, Webhook>("SELECT * FROM webhooks WHERE id = $1")
+        .bind(id)
+        .fetch_optional(&db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .ok_or(StatusCode::NOT_FOUND)?;
+
+    let form = WebhookForm {
+        name: webhook.name,
+        url: webhook.url,
+        event: webhook.event,
+        payload_template: webhook.payload_template.unwrap_or_default(),
+        is_active: webhook.is_active.then(|| "true".to_string()),
+        action: None,
+    };
+    render_form(Some(id), form, None, None)
+}
+
+async fn save(db: &Database, id: Option<Uuid>, form: WebhookForm, user_id: Uuid) -> Result<Response, StatusCode> {
+    if form.action.as_deref() == Some("preview") {
+        return match preview(&form) {
+            Ok(rendered) => render_form(id, form, Some(rendered), None),
+            Err(error) => render_form(id, form, None, Some(error)),
+        }
+        .map(IntoResponse::into_response);
+    }
+
+    let payload_template = match validate(&form) {
+        Ok(template) => template,
+        Err(error) => return render_form(id, form, None, Some(error)).map(IntoResponse::into_response),
+    };
+
+    let saved = match id {
+        Some(id) => {
+            sqlx::query(
+                "UPDATE webhooks SET name = $2, url = $3, event = $4, payload_template = $5, is_active = $6 WHERE id = $1",
+            )
+            .bind(id)
+            .bind(form.name.trim())
+            .bind(form.url.trim())
+            .bind(&form.event)
+            .bind(payload_template)
+            .bind(form.is_active.is_some())
+            .execute(db)
+            .await
+        }
+        None => {
+            sqlx::query(
+                "INSERT INTO webhooks (name, url, event, payload_template, is_active, created_by) VALUES ($1, $2, $3, $4, $5, $6)",
+            )
+            .bind(form.name.trim())
+            .bind(form.url.trim())
+            .bind(&form.event)
+            .bind(payload_template)
+            .bind(form.is_active.is_some())
+            .bind(user_id)
+            .execute(db)
+            .await
+        }
+    }
+    .map_err(|e| {
+        tracing::error!("Error saving webhook: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    if saved.rows_affected() == 0 {
+        return Err(StatusCode::NOT_FOUND);
+    }
+
+    Ok(Redirect::to("/team/webhooks").into_response())
+}
+
+pub async fn create_webhook(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Form(form): Form<WebhookForm>,
+) -> Result<Response, StatusCode> {
+    require_api_admin(&current_user)?;
+    save(&db, None, form, current_user.id).await
+}
+
+pub async fn update_webhook(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+    Form(form): Form<WebhookForm>,
+) -> Result<Response, StatusCode> {
+    require_api_admin(&current_user)?;
+    save(&db, Some(id), form, current_user.id).await
+}
+
+pub async fn delete_webhook(
+    State(db): State<Database>,
+    AuthUser(current_user): AuthUser,
+    Path(id): Path<Uuid>,
+) -> Result<Redirect, StatusCode> {
+    require_api_admin(&current_user)?;
+
+    sqlx::query("DELETE FROM webhooks WHERE id = $1")
+        .bind(id)
+        .execute(&db)
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?;
+
+    Ok(Redirect::to("/team/webhooks"))
+}
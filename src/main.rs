@@ -4,22 +4,20 @@ mod middleware;
 mod models;
 mod utils;
 mod filters;
+mod services;
+mod scheduler;
 
 use axum::{
     body::Bytes,
     extract::DefaultBodyLimit,
     response::Redirect,
-    routing::{get, post},
+    routing::{get, patch, post},
     Router,
 };
-use std::env;
+use std::{env, net::SocketAddr};
 use tower::ServiceBuilder;
 use tower_cookies::CookieManagerLayer;
-use tower_http::{
-    cors::CorsLayer,
-    services::ServeDir,
-    trace::TraceLayer,
-};
+use tower_http::services::ServeDir;
 use dotenvy::dotenv;
 
 use database::{Database, create_database_pool};
@@ -29,8 +27,13 @@ async fn main() {
     // Load environment variables
     dotenv().ok();
 
-    // Initialize logging
-    env_logger::init();
+    // Initialize logging; RUST_LOG picks the levels, info by default
+    tracing_subscriber::fmt()
+        .with_env_filter(
+            tracing_subscriber::EnvFilter::try_from_default_env()
+                .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info")),
+        )
+        .init();
 
     // Initialize database
     let database_url = env::var("DATABASE_URL")
@@ -39,63 +42,87 @@ async fn main() {
     let db = create_database_pool(&database_url).await
         .expect("Failed to connect to database");
 
-    println!("Database connection successful!");
+    tracing::info!("Database connection successful!");
 
-    // Build the application router
-    let app = create_router(db);
+    // Tokens can't be signed or checked until the keys are loaded
+    services::signing_keys::load(&db).await
+        .expect("Failed to load token signing keys");
 
     // Get port from environment or use default
     let port = env::var("PORT").unwrap_or_else(|_| "3000".to_string());
     let addr = format!("0.0.0.0:{}", port);
 
-    println!("🚀 Allo server starting on http://{}", addr);
+    // Bound before the background jobs start so the first status probe has
+    // something to answer it
+    let listener = tokio::net::TcpListener::bind(&addr).await.unwrap();
+
+    services::diagnostics::started_at();
+
+    // Start background jobs (email delivery, digests)
+    scheduler::start(db.clone());
+
+    // Build the application router
+    let app = create_router(db);
+
+    tracing::info!("🚀 Allo server starting on http://{}", addr);
 
     // Start the server
-    let listener = tokio::net::TcpListener::bind(&addr).await.unwrap();
-    axum::serve(listener, app).await.unwrap();
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
+        .await
+        .unwrap();
 }
 
 // Custom handler for form data that handles raw body
 async fn handle_create_user(
-    cookies: tower_cookies::Cookies,
+    user: middleware::AuthUser,
     axum::extract::State(db): axum::extract::State<Database>,
     body: Bytes,
-) -> Result<Redirect, axum::http::StatusCode> {
+) -> Result<axum::response::Response, axum::http::StatusCode> {
     let body_str = String::from_utf8(body.to_vec())
         .map_err(|_| axum::http::StatusCode::BAD_REQUEST)?;
-    handlers::team::create_user(cookies, axum::extract::State(db), body_str).await
+    handlers::team::create_user(user, axum::extract::State(db), body_str).await
 }
 
 async fn handle_update_user(
-    cookies: tower_cookies::Cookies,
+    user: middleware::AuthUser,
     axum::extract::Path(user_id): axum::extract::Path<uuid::Uuid>,
     axum::extract::State(db): axum::extract::State<Database>,
     body: Bytes,
-) -> Result<Redirect, axum::http::StatusCode> {
+) -> Result<axum::response::Response, axum::http::StatusCode> {
     let body_str = String::from_utf8(body.to_vec())
         .map_err(|_| axum::http::StatusCode::BAD_REQUEST)?;
-    handlers::team::update_user(cookies, axum::extract::State(db), axum::extract::Path(user_id), body_str).await
+    handlers::team::update_user(user, axum::extract::State(db), axum::extract::Path(user_id), body_str).await
 }
 
 async fn handle_create_role(
-    cookies: tower_cookies::Cookies,
+    user: middleware::AuthUser,
     axum::extract::State(db): axum::extract::State<Database>,
     body: Bytes,
 ) -> Result<Redirect, axum::http::StatusCode> {
     let body_str = String::from_utf8(body.to_vec())
         .map_err(|_| axum::http::StatusCode::BAD_REQUEST)?;
-    handlers::team::create_role(cookies, axum::extract::State(db), body_str).await
+    handlers::team::create_role(user, axum::extract::State(db), body_str).await
 }
 
 async fn handle_update_role(
-    cookies: tower_cookies::Cookies,
+    user: middleware::AuthUser,
     axum::extract::Path(role_id): axum::extract::Path<uuid::Uuid>,
     axum::extract::State(db): axum::extract::State<Database>,
     body: Bytes,
 ) -> Result<Redirect, axum::http::StatusCode> {
     let body_str = String::from_utf8(body.to_vec())
         .map_err(|_| axum::http::StatusCode::BAD_REQUEST)?;
-    handlers::team::update_role(cookies, axum::extract::State(db), axum::extract::Path(role_id), body_str).await
+    handlers::team::update_role(user, axum::extract::State(db), axum::extract::Path(role_id), body_str).await
+}
+
+fn api_router(db: Database) -> Router<Database> {
+    Router::new()
+        .route("/api/customers", post(handlers::crm::api_create_customer))
+        .route("/api/customers/:id/contacts", get(handlers::crm::get_customer_contacts))
+        .route("/api/customers/:id/items/:code", get(handlers::crm::api_customer_item))
+        .route("/api/lookups/:kind", get(handlers::lookups::api_lookups))
+        .route("/api/search", get(handlers::search::api_search))
+        .route_layer(axum::middleware::from_fn_with_state(db, middleware::api_auth::authenticate))
 }
 
 fn create_router(db: Database) -> Router {
@@ -104,70 +131,246 @@ fn create_router(db: Database) -> Router {
         .route("/", get(|| async { Redirect::permanent("/login") }))
         .route("/login", get(handlers::auth::login_page))
         .route("/login", post(handlers::auth::login))
-        .route("/register", get(handlers::auth::register_page))
-        .route("/register", post(handlers::auth::register))
+        .route("/setup", get(handlers::setup::setup_page))
+        .route("/setup", post(handlers::setup::complete_setup))
+        .route("/invite/:token", get(handlers::invitations::accept_page))
+        .route("/invite", post(handlers::invitations::accept_invite))
         .route("/logout", post(handlers::auth::logout))
+        .route("/public/lead", get(handlers::lead_capture::lead_form))
+        .route("/public/lead", post(handlers::lead_capture::submit_lead))
+        .route("/t/o/:id", get(handlers::email_tracking::track_open))
+        .route("/t/c/:id", get(handlers::email_tracking::track_click))
+        .route("/webhooks/email", post(handlers::email_webhooks::email_provider_webhook))
+        .route("/status", get(handlers::status::status_page))
+        .route("/status.json", get(handlers::status::status_json))
 
         // Protected routes (authentication required)
         // MODIFIED: Correct path to the dashboard handler function
         .route("/dashboard", get(handlers::dashboard::dashboard))
+        .route("/search", get(handlers::search::search_page))
+
+        // Profile routes
+        .route("/profile", get(handlers::profile::profile_page))
+        .route("/profile/digest", post(handlers::profile::update_digest_preference))
+        .route("/profile/timezone", post(handlers::profile::update_timezone))
+        .route("/profile/signature", post(handlers::profile::update_signature))
+        .route("/profile/signature/preview", post(handlers::profile::preview_signature))
+        .route("/profile/backup", post(handlers::profile::update_backup))
+        .route("/profile/out-of-office", post(handlers::profile::add_out_of_office))
+        .route("/profile/out-of-office/:id/delete", post(handlers::profile::remove_out_of_office))
+        .route("/profile/password", get(handlers::profile::password_page).post(handlers::profile::change_password))
+        .route("/profile/security", get(handlers::profile::security_page))
+        .route("/profile/sessions", get(handlers::profile::sessions_page))
+        .route("/profile/sessions/:id/revoke", post(handlers::profile::revoke_session))
+        .route("/profile/sessions/revoke-all", post(handlers::profile::revoke_all_sessions))
+        .route("/notifications", get(handlers::notifications::notifications_list))
+        .route("/notifications/read", post(handlers::notifications::mark_all_read))
+        .route("/approvals", get(handlers::approvals::approvals_page))
+        .route("/approvals/:kind/:id/approve", post(handlers::approvals::approve))
+        .route("/approvals/:kind/:id/deny", post(handlers::approvals::deny))
+        .route("/dashboards", get(handlers::saved_dashboards::dashboards_page).post(handlers::saved_dashboards::create_dashboard))
+        .route("/dashboards/shared/:token", get(handlers::saved_dashboards::shared_dashboard))
+        .route("/dashboards/:id", get(handlers::saved_dashboards::dashboard_page).post(handlers::saved_dashboards::update_dashboard))
+        .route("/dashboards/:id/share", post(handlers::saved_dashboards::share_dashboard))
+        .route("/dashboards/:id/unshare", post(handlers::saved_dashboards::unshare_dashboard))
+        .route("/dashboards/:id/delete", post(handlers::saved_dashboards::delete_dashboard))
 
         // CRM routes
         .route("/crm", get(handlers::crm::crm_dashboard))
         .route("/crm/customers", get(handlers::crm::customers_list))
+        .route("/crm/customers/export.xlsx", get(handlers::crm::customers_export))
         .route("/crm/customers/new", get(handlers::crm::customer_form))
         .route("/crm/customers", post(handlers::crm::create_customer))
         .route("/crm/customers/:id", get(handlers::crm::customer_detail))
         .route("/crm/customers/:id/edit", get(handlers::crm::customer_edit_form))
         .route("/crm/customers/:id", post(handlers::crm::update_customer))
+        .route("/crm/customers/:id/history", get(handlers::audit_log::customer_history))
         .route("/crm/customers/:id/delete", get(handlers::crm::delete_customer))
+        .route("/crm/customers/:id/status", patch(handlers::crm::patch_customer_status))
+        .route("/crm/customers/:id/shares", post(handlers::crm::share_customer))
+        .route("/crm/customers/:id/watch", post(handlers::crm::watch_customer))
+        .route("/crm/customers/:id/unwatch", post(handlers::crm::unwatch_customer))
+        .route("/crm/customers/:id/part-numbers", post(handlers::crm::add_part_number))
+        .route("/crm/customers/:id/part-numbers/:part_number_id/delete", post(handlers::crm::delete_part_number))
+        .route("/crm/customers/:id/pricing-agreements", post(handlers::crm::add_pricing_agreement))
+        .route("/crm/customers/:id/pricing-agreements/:agreement_id/delete", post(handlers::crm::delete_pricing_agreement))
+        .route("/crm/customers/:id/shares/:share_id/delete", post(handlers::crm::unshare_customer))
+        .route("/crm/customers/:id/notes", post(handlers::crm::add_customer_note))
+        .route("/crm/customers/:id/notes/:note_id", post(handlers::crm::update_customer_note))
+        .route("/crm/customers/:id/notes/:note_id/delete", post(handlers::crm::delete_customer_note))
 
         // Contacts
         .route("/crm/contacts", post(handlers::crm::create_contact))
         .route("/crm/customers/:customer_id/contacts/:contact_id/delete", get(handlers::crm::delete_contact))
         .route("/crm/customers/:customer_id/contacts/:contact_id/edit", get(handlers::crm::contact_edit_form))
-        .route("/crm/customers/:customer_id/contacts/:contact_id", post(handlers::crm::update_contact))
+        .route("/crm/customers/:customer_id/contacts/:contact_id", get(handlers::crm::contact_detail).post(handlers::crm::update_contact))
+        .route("/crm/customers/:customer_id/contacts/:contact_id/email", post(handlers::crm::send_contact_email))
+        .route("/crm/customers/:customer_id/contacts/:contact_id/photo", post(handlers::contact_photos::upload_contact_photo))
+        .route("/crm/customers/:customer_id/contacts/:contact_id/photo/delete", post(handlers::contact_photos::remove_contact_photo))
+        .route("/crm/contacts/scan-card", post(handlers::contact_photos::scan_business_card))
+        .route("/crm/customers/:customer_id/contacts/:contact_id/sequences", post(handlers::sequences::enroll_contact))
 
         // Deals routes
         .route("/crm/deals", get(handlers::crm::deals_list))
+        .route("/crm/deals/export.xlsx", get(handlers::crm::deals_export))
         .route("/crm/deals/new", get(handlers::crm::deal_form))
+        .route("/crm/deals/stages", get(handlers::crm::deal_stage_settings))
+        .route("/crm/deals/stages", post(handlers::crm::update_deal_stage_settings))
+        .route("/crm/deals/discounts", get(handlers::crm::discount_settings))
+        .route("/crm/deals/discounts", post(handlers::crm::add_discount_threshold))
+        .route("/crm/deals/discounts/:id/delete", post(handlers::crm::delete_discount_threshold))
         .route("/crm/deals", post(handlers::crm::create_deal))
         .route("/crm/deals/:id", get(handlers::crm::deal_detail))
         .route("/crm/deals/:id/edit", get(handlers::crm::deal_edit_form))
         .route("/crm/deals/:id", post(handlers::crm::update_deal))
+        .route("/crm/deals/:id/history", get(handlers::audit_log::deal_history))
         .route("/crm/deals/:id/delete", get(handlers::crm::delete_deal))
+        .route("/crm/deals/:id/stage", patch(handlers::crm::patch_deal_stage))
+        .route("/crm/deals/:id/line-items", post(handlers::crm::add_deal_line_item))
+        .route("/crm/deals/:id/line-items/:line_id/delete", post(handlers::crm::delete_deal_line_item))
+        .route("/crm/deals/:id/shares", post(handlers::crm::share_deal))
+        .route("/crm/deals/:id/watch", post(handlers::crm::watch_deal))
+        .route("/crm/deals/:id/unwatch", post(handlers::crm::unwatch_deal))
+        .route("/crm/deals/:id/shares/:share_id/delete", post(handlers::crm::unshare_deal))
+        .route("/crm/deals/:id/notes", post(handlers::crm::add_deal_note))
+        .route("/crm/deals/:id/notes/:note_id", post(handlers::crm::update_deal_note))
+        .route("/crm/deals/:id/notes/:note_id/delete", post(handlers::crm::delete_deal_note))
+        .route("/crm/deals/:id/sequences", post(handlers::sequences::enroll_deal))
+
+        // Lead routes
+        .route("/crm/leads", get(handlers::leads::leads_list).post(handlers::leads::create_lead))
+        .route("/crm/leads/new", get(handlers::leads::lead_form))
+        .route("/crm/leads/:id", get(handlers::leads::lead_detail).post(handlers::leads::update_lead))
+        .route("/crm/leads/:id/edit", get(handlers::leads::lead_edit_form))
+        .route("/crm/leads/:id/delete", post(handlers::leads::delete_lead))
+        .route("/crm/leads/:id/convert", post(handlers::leads::convert_lead))
+
+        // Tag routes
+        .route("/crm/tags", get(handlers::tags::tags_page).post(handlers::tags::create_tag))
+        .route("/crm/tags/:id", post(handlers::tags::update_tag))
+        .route("/crm/tags/:id/delete", post(handlers::tags::delete_tag))
+
+        // Custom field routes
+        .route("/crm/custom-fields", get(handlers::custom_fields::custom_fields_page).post(handlers::custom_fields::save_custom_field))
+        .route("/crm/custom-fields/:id/toggle", post(handlers::custom_fields::toggle_custom_field))
+
+        // Follow-up sequences
+        .route("/crm/sequences", get(handlers::sequences::sequences_page).post(handlers::sequences::create_sequence))
+        .route("/crm/sequences/:id", get(handlers::sequences::sequence_page).post(handlers::sequences::update_sequence))
+        .route("/crm/sequences/:id/steps", post(handlers::sequences::add_step))
+        .route("/crm/sequences/:id/steps/:step_id/delete", post(handlers::sequences::delete_step))
+        .route("/crm/sequence-enrollments/:id/stop", post(handlers::sequences::stop_enrollment))
+
+        // Blanket order routes
+        .route("/crm/blanket-orders", get(handlers::blanket_orders::blanket_orders_list))
+        .route("/crm/blanket-orders/new", get(handlers::blanket_orders::blanket_order_form))
+        .route("/crm/blanket-orders", post(handlers::blanket_orders::create_blanket_order))
+        .route("/crm/blanket-orders/:id", get(handlers::blanket_orders::blanket_order_detail))
+        .route("/crm/blanket-orders/:id/lines", post(handlers::blanket_orders::add_blanket_order_line))
+        .route("/crm/blanket-orders/:id/releases", post(handlers::blanket_orders::schedule_blanket_order_release))
+        .route("/crm/blanket-orders/:id/releases/:release_id/ship", post(handlers::blanket_orders::ship_blanket_order_release))
+        .route("/crm/blanket-orders/:id/releases/:release_id/cancel", post(handlers::blanket_orders::cancel_blanket_order_release))
+        .route("/crm/blanket-orders/:id/finish", post(handlers::blanket_orders::finish_blanket_order))
+        .route("/crm/blanket-orders/:id/document", get(handlers::blanket_orders::blanket_order_document))
+
+        // Campaign routes
+        .route("/crm/campaigns", get(handlers::campaigns::campaigns_list))
+        .route("/crm/campaigns/new", get(handlers::campaigns::campaign_form))
+        .route("/crm/campaigns", post(handlers::campaigns::create_campaign))
+        .route("/crm/campaigns/roi", get(handlers::campaigns::campaign_roi_report))
+        .route("/crm/campaigns/roi/export.xlsx", get(handlers::campaigns::campaign_roi_export))
+        .route("/crm/campaigns/:id/edit", get(handlers::campaigns::campaign_edit_form))
+        .route("/crm/campaigns/:id", post(handlers::campaigns::update_campaign))
+
+        // Segment and mass email routes
+        .route("/crm/segments", get(handlers::mass_email::segments_list))
+        .route("/crm/segments", post(handlers::mass_email::create_segment))
+        .route("/crm/segments/:id/delete", post(handlers::mass_email::delete_segment))
+        .route("/crm/mass-emails", get(handlers::mass_email::mass_emails_list))
+        .route("/crm/mass-emails/new", get(handlers::mass_email::mass_email_form))
+        .route("/crm/mass-emails", post(handlers::mass_email::create_mass_email))
+        .route("/crm/mass-emails/:id", get(handlers::mass_email::mass_email_report))
+        .route("/crm/mass-emails/:id/cancel", post(handlers::mass_email::cancel_mass_email))
+        .route("/crm/alerts", get(handlers::metric_alerts::alerts_page))
+        .route("/crm/alerts", post(handlers::metric_alerts::create_alert_rule))
+        .route("/crm/alerts/:id", post(handlers::metric_alerts::update_alert_rule))
+        .route("/crm/alerts/:id/delete", post(handlers::metric_alerts::delete_alert_rule))
 
         // Activities routes
         .route("/crm/activities", get(handlers::crm::activities_list))
         .route("/crm/activities/new", get(handlers::crm::activity_form))
+        .route("/crm/activities/outcomes", get(handlers::crm::activity_outcomes))
+        .route("/crm/activities/outcomes", post(handlers::crm::create_activity_outcome))
+        .route("/crm/activities/outcomes/:id/toggle", post(handlers::crm::toggle_activity_outcome))
+        .route("/crm/activities/types", get(handlers::crm::activity_types_page))
+        .route("/crm/activities/types", post(handlers::crm::save_activity_type))
+        .route("/crm/activities/types/:id/toggle", post(handlers::crm::toggle_activity_type))
         .route("/crm/activities", post(handlers::crm::create_activity))
         .route("/crm/activities/:id/delete", get(handlers::crm::delete_activity))
         .route("/crm/activities/:id/edit", get(handlers::crm::activity_edit_form))
         .route("/crm/activities/:id", post(handlers::crm::update_activity))
+        .route("/crm/activities/:id/completed", patch(handlers::crm::patch_activity_completed))
 
         // Reports routes
         .route("/crm/reports", get(handlers::reports::reports_list))
+        .route("/crm/reports/export.xlsx", get(handlers::reports::reports_export))
+        .route("/crm/reports/pivot", get(handlers::reports::pivot_report))
+        .route("/crm/reports/pivot/export.xlsx", get(handlers::reports::pivot_export))
+        .route("/crm/reports/teams", get(handlers::reports::team_rollup))
+        .route("/crm/reports/summary", get(handlers::reports::summary_report))
 
         // Expense Tracking Routes
         .route("/expenses", get(handlers::expenses::expenses_list))
         .route("/expenses/new", get(handlers::expenses::expense_form))
+        .route("/expenses/cost-centers", get(handlers::cost_centers::cost_centers_page))
+        .route("/expenses/cost-centers", post(handlers::cost_centers::create_cost_center))
+        .route("/expenses/cost-centers/export.xlsx", get(handlers::cost_centers::cost_centers_export))
+        .route("/expenses/cost-centers/:id", post(handlers::cost_centers::update_cost_center))
         .route("/expenses", post(handlers::expenses::create_expense))
         .route("/expenses/:id/edit", get(handlers::expenses::expense_edit_form))
+        .route("/expenses/:id/history", get(handlers::audit_log::expense_history))
         .route("/expenses/:id", post(handlers::expenses::update_expense))
         .route("/expenses/:id/delete", get(handlers::expenses::delete_expense))
         .route("/expenses/:id/approve", get(handlers::expenses::approve_expense))
         .route("/expenses/:id/deny", get(handlers::expenses::deny_expense))
 
+        // Project Routes
+        .route("/projects", get(handlers::projects::projects_list))
+        .route("/projects", post(handlers::projects::create_project))
+        .route("/projects/new", get(handlers::projects::project_form))
+        .route("/projects/cost-rates", get(handlers::projects::cost_rates_page))
+        .route("/projects/cost-rates/:user_id", post(handlers::projects::update_cost_rate))
+        .route("/projects/wip", get(handlers::projects::wip_report))
+        .route("/projects/wip/export.xlsx", get(handlers::projects::wip_export))
+        .route("/projects/:id", get(handlers::projects::project_detail))
+        .route("/projects/:id", post(handlers::projects::update_project))
+        .route("/projects/:id/edit", get(handlers::projects::project_edit_form))
+        .route("/projects/:id/time", post(handlers::projects::log_time))
+        .route("/projects/:id/time/:entry_id/delete", post(handlers::projects::delete_time_entry))
+        .route("/projects/:id/billings", post(handlers::projects::add_billing))
+        .route("/projects/:id/billings/:billing_id/delete", post(handlers::projects::delete_billing))
+
         // Team management routes
         .route("/team", get(handlers::team::team_dashboard))
         .route("/team/users", get(handlers::team::users_list))
         .route("/team/users/new", get(handlers::team::user_form))
         .route("/team/users", post(handle_create_user)) // Use custom handler
+        .route("/team/users/invite", get(handlers::invitations::invite_page))
+        .route("/team/users/invite", post(handlers::invitations::send_invite))
+        .route("/team/users/import", get(handlers::imports::user_import_page))
+        .route("/team/users/import", post(handlers::imports::create_user_import))
+        .route("/team/users/invites/:id/revoke", post(handlers::invitations::revoke_invite))
         .route("/team/users/:id/edit", get(handlers::team::user_edit_form))
         .route("/team/users/:id", post(handle_update_user)) // Use custom handler
         .route("/team/users/:id/lock", get(handlers::team::lock_user))
         .route("/team/users/:id/unlock", get(handlers::team::unlock_user))
-        .route("/team/users/:id/delete", get(handlers::team::delete_user))
+        .route("/team/users/:id/deactivate", get(handlers::offboarding::deactivate_page))
+        .route("/team/users/:id/deactivate", post(handlers::offboarding::deactivate_user))
+        .route("/team/users/:id/delete", get(handlers::offboarding::delete_page))
+        .route("/team/users/:id/delete", post(handlers::offboarding::delete_user))
+        .route("/team/users/:id/impersonate", post(handlers::impersonation::impersonate))
+        .route("/impersonation/end", post(handlers::impersonation::end_impersonation))
+        .route("/impersonation/banner", get(handlers::impersonation::banner))
 
         // Roles routes
         .route("/team/roles", get(handlers::team::roles_list))
@@ -177,24 +380,112 @@ fn create_router(db: Database) -> Router {
         .route("/team/roles/:id", post(handle_update_role)) // Use custom handler
         .route("/team/roles/:id/delete", get(handlers::team::delete_role))
 
+        // Teams (departments)
+        .route("/team/teams", get(handlers::teams::teams_page))
+        .route("/team/teams", post(handlers::teams::create_team))
+        .route("/team/teams/:id", get(handlers::teams::team_page))
+        .route("/team/teams/:id", post(handlers::teams::update_team))
+        .route("/team/teams/:id/members", post(handlers::teams::add_member))
+        .route("/team/teams/:id/members/:user_id/remove", post(handlers::teams::remove_member))
+
         // Inventory routes
         .route("/inventory", get(|| async { Redirect::permanent("/inventory/items") }))
         .route("/inventory/items", get(handlers::inventory::items_list))
         .route("/inventory/items/new", get(handlers::inventory::item_form))
         .route("/inventory/items", post(handlers::inventory::create_item))
+        .route("/inventory/items/:id", get(handlers::inventory::item_detail))
+        .route("/inventory/items/:id/variants", post(handlers::inventory::generate_variants))
+        .route("/inventory/items/:id/receive", post(handlers::inventory::receive_stock))
+        .route("/inventory/items/:id/transfer", post(handlers::inventory::transfer_stock))
+        .route("/inventory/items/:id/held-stock", post(handlers::inventory::receive_held_stock))
+        .route("/inventory/items/:id/held-stock/:held_id/release", post(handlers::inventory::release_held_stock))
+        .route("/inventory/backorders", get(handlers::inventory::backorders_page))
+        .route("/inventory/warehouses", get(handlers::warehouses::warehouses_list))
+        .route("/inventory/warehouses/:id", get(handlers::warehouses::warehouse_detail))
+        .route("/inventory/warehouses/:id/locations", post(handlers::warehouses::add_location))
+        .route("/inventory/warehouses/:id/locations/:location_id/delete", post(handlers::warehouses::delete_location))
+        .route("/inventory/warehouses/:id/pick-list", get(handlers::warehouses::pick_list))
 
-        // API routes
-        .route("/api/customers/:id/contacts", get(handlers::crm::get_customer_contacts))
+        // API key management
+        .route("/team/api-keys", get(handlers::api_keys::api_keys_list))
+        .route("/team/api-keys", post(handlers::api_keys::create_api_key))
+        .route("/team/api-keys/:id/edit", get(handlers::api_keys::api_key_edit_form))
+        .route("/team/api-keys/:id", post(handlers::api_keys::update_api_key))
+        .route("/team/api-keys/:id/revoke", post(handlers::api_keys::revoke_api_key))
+        .route("/team/api-keys/sandbox/reset", post(handlers::api_keys::reset_sandbox))
+        .route("/team/audit", get(handlers::audit_log::audit_log_list))
+        .route("/team/audit/:id", get(handlers::audit_log::audit_log_detail))
+        .route("/team/api-logs", get(handlers::api_logs::api_logs_list))
+        .route("/team/api-logs/:id", get(handlers::api_logs::api_log_detail))
+        .route("/team/api-logs/:id/replay", post(handlers::api_logs::replay_api_call))
+        .route("/team/webhooks", get(handlers::webhooks::webhooks_list))
+        .route("/team/webhooks/new", get(handlers::webhooks::webhook_form))
+        .route("/team/webhooks", post(handlers::webhooks::create_webhook))
+        .route("/team/webhooks/:id/edit", get(handlers::webhooks::webhook_edit_form))
+        .route("/team/webhooks/:id", post(handlers::webhooks::update_webhook))
+        .route("/team/webhooks/:id/delete", post(handlers::webhooks::delete_webhook))
+        .route("/team/security", get(handlers::security::security_settings_page))
+        .route("/team/security", post(handlers::security::update_security_settings))
+        .route("/team/security/sessions", post(handlers::security::update_session_settings))
+        .route("/team/security/lockout", post(handlers::security::update_lockout_settings))
+        .route("/team/security/passwords", post(handlers::security::update_password_settings))
+        .route("/team/security/signing-keys/rotate", post(handlers::security::rotate_signing_key))
+        .route("/team/reporting", get(handlers::reports::reporting_settings_page))
+        .route("/team/reporting", post(handlers::reports::update_reporting_settings))
+        .route("/imports", get(handlers::imports::imports_list))
+        .route("/crm/customers/import", get(handlers::imports::customer_import_page).post(handlers::imports::upload_customer_import))
+        .route("/crm/customers/import/:id", get(handlers::imports::customer_import_mapping).post(handlers::imports::save_customer_import))
+        .route("/imports", post(handlers::imports::create_import))
+        .route("/imports/:id", get(handlers::imports::import_detail))
+        .route("/imports/:id/errors.csv", get(handlers::imports::import_errors_csv))
+        .route("/team/lookups", get(handlers::lookups::lookups_page))
+        .route("/team/lookups", post(handlers::lookups::save_lookup_value))
+        .route("/team/lookups/:id/toggle", post(handlers::lookups::toggle_lookup_value))
+        .route("/team/numbering", get(handlers::numbering::numbering_page))
+        .route("/team/numbering/:document_type", post(handlers::numbering::update_number_sequence))
+        .route("/team/documents", get(handlers::documents::documents_page))
+        .route("/team/documents/:document_type", post(handlers::documents::update_document_template))
+        .route("/team/documents/:document_type/preview", get(handlers::documents::preview_document))
+        .route("/team/exchange-rates", get(handlers::exchange_rates::exchange_rates_page))
+        .route("/team/exchange-rates/:currency", post(handlers::exchange_rates::update_exchange_rate))
+        .route("/team/status", get(handlers::status::status_admin_page))
+        .route("/team/status/incidents", post(handlers::status::create_incident))
+        .route("/team/status/incidents/:id", post(handlers::status::update_incident))
+        .route("/team/status/incidents/:id/resolve", post(handlers::status::resolve_incident))
+        .route("/team/status/incidents/:id/delete", post(handlers::status::delete_incident))
+        .route("/team/status/maintenance", post(handlers::status::create_maintenance))
+        .route("/team/status/maintenance/:id/delete", post(handlers::status::delete_maintenance))
+        .route("/team/jobs", get(handlers::jobs::jobs_dashboard))
+        .route("/team/diagnostics", get(handlers::diagnostics::diagnostics_page))
+        .route("/team/jobs/:id", get(handlers::jobs::job_detail))
+        .route("/team/jobs/:id/retry", post(handlers::jobs::retry_job))
+        .route("/team/jobs/:id/cancel", post(handlers::jobs::cancel_job))
+
+        // API routes, reachable with a session cookie or a bearer API key
+        .merge(api_router(db.clone()))
 
         // Static files
         .nest_service("/static", ServeDir::new("static"))
 
+        .route_layer(axum::middleware::from_fn(middleware::request_id::record_route))
+
         // Middleware
+        .layer(axum::middleware::from_fn_with_state(db.clone(), middleware::permission::enforce_read_only))
+        .layer(axum::middleware::from_fn_with_state(db.clone(), middleware::ip_allowlist::enforce))
+        .layer(axum::middleware::from_fn_with_state(db.clone(), middleware::throttle::throttle_public_forms))
+        .layer(axum::middleware::from_fn_with_state(db.clone(), middleware::setup::require_setup))
+        // Outside everything that reads the database
+        .layer(axum::middleware::from_fn_with_state(db.clone(), middleware::tenant::resolve))
+        // Turns floods away before they cost a query; needs the client info
+        .layer(axum::middleware::from_fn(middleware::rate_limit::limit))
+        .layer(axum::middleware::from_fn(middleware::client_info::capture))
+        .layer(axum::middleware::from_fn(middleware::security_headers::set_headers))
+        // Around all of the app's own middleware, so the request's span covers it
+        .layer(axum::middleware::from_fn(middleware::request_id::trace))
         .layer(
             ServiceBuilder::new()
-                .layer(TraceLayer::new_for_http())
                 .layer(CookieManagerLayer::new())
-                .layer(CorsLayer::permissive())
+                .layer(middleware::security_headers::cors())
                 .layer(DefaultBodyLimit::max(10 * 1024 * 1024)) // 10MB
         )
         .with_state(db)
@@ -0,0 +1,261 @@
+use axum::{
+    body::{to_bytes, Body},
+    extract::{ConnectInfo, MatchedPath, Request, State},
+    http::{header::AUTHORIZATION, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::{
+    net::{IpAddr, SocketAddr},
+    time::Instant,
+};
+use uuid::Uuid;
+
+use super::{
+    client_info::ClientInfo,
+    permission::{get_user_by_id, is_mutating_request, CurrentUser},
+    request_id,
+};
+use crate::{
+    database::Database,
+    models::{ApiKey, API_KEY_SELECT},
+    services::api_log::{self, ApiCall},
+    utils::{api_key::hash_api_key, request::client_ip},
+};
+
+// Permission a key needs for each API route. Routes missing here are refused
+// to keys so a new endpoint is never reachable by a token by accident.
+const ROUTE_PERMISSIONS: &[(&str, &str, &str)] = &[
+    ("POST", "/api/customers", "customers:write"),
+    ("GET", "/api/customers/:id/contacts", "customers:read"),
+    ("GET", "/api/customers/:id/items/:code", "inventory:read"),
+    ("GET", "/api/lookups/:kind", "customers:read"),
+    ("GET", "/api/search", "customers:read"),
+];
+
+// Matches the global DefaultBodyLimit
+const MAX_BODY_BYTES: usize = 10 * 1024 * 1024;
+
+// The acting user for a request authenticated with a bearer token, with
+// permissions already narrowed to what the key allows. Sandbox keys work on
+// the shadow tables, see services::sandbox.
+#[derive(Debug, Clone)]
+pub struct ApiPrincipal {
+    pub user: CurrentUser,
+    pub sandbox: bool,
+}
+
+// Stands in for the bearer token when an admin replays a logged call. It is
+// only ever inserted server-side, so clients cannot forge it.
+#[derive(Debug, Clone, Copy)]
+pub struct ApiReplay {
+    pub key_id: Uuid,
+    pub log_id: Uuid,
+}
+
+// Added to the response of every logged call so callers can link to the entry
+#[derive(Debug, Clone, Copy)]
+pub struct ApiCallLogged(pub Uuid);
+
+pub fn required_permission(method: &Method, path: &str) -> Option<&'static str> {
+    ROUTE_PERMISSIONS
+        .iter()
+        .find(|(m, p, _)| *m == method.as_str() && *p == path)
+        .map(|(_, _, permission)| *permission)
+}
+
+// Whether a key narrowed to these permissions may call the route
+fn route_permitted(permissions: &[String], method: &Method, route: &str) -> bool {
+    required_permission(method, route).is_some_and(|permission| permissions.iter().any(|p| p == permission))
+}
+
+async fn find_key(db: &Database, condition: &str, value: String) -> Result<Option<ApiKey>, StatusCode> {
+    sqlx::query_as::<_, ApiKey>(&format!("{} WHERE {}", API_KEY_SELECT, condition))
+        .bind(value)
+        .fetch_optional(db)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to look up API key: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })
+}
+
+// Requests without an Authorization header fall through to the cookie session
+// used by the web UI; anything carrying a bearer token must be a valid key.
+// Every call made with a known key is written to the API call log.
+pub async fn authenticate(
+    State(db): State<Database>,
+    request: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    let replay = request.extensions().get::<ApiReplay>().copied();
+
+    let key = if let Some(replay) = replay {
+        find_key(&db, "k.id = $1::uuid", replay.key_id.to_string()).await?
+    } else {
+        let Some(header) = request.headers().get(AUTHORIZATION) else {
+            return Ok(next.run(request).await);
+        };
+
+        let token = header
+            .to_str()
+            .ok()
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .map(str::trim)
+            .filter(|token| !token.is_empty())
+            .ok_or(StatusCode::UNAUTHORIZED)?;
+
+        find_key(&db, "k.key_hash = $1", hash_api_key(token)).await?
+    };
+    let key = key.ok_or(StatusCode::UNAUTHORIZED)?;
+
+    let started = Instant::now();
+    let peer = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|info| info.0);
+    let ip = client_ip(request.headers(), peer);
+    let method = request.method().clone();
+    let route = request
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_default();
+    let path = request
+        .uri()
+        .path_and_query()
+        .map(|pq| pq.as_str().to_string())
+        .unwrap_or_default();
+
+    // Bodies are buffered so they can be logged; handlers get them back intact
+    let (parts, body) = request.into_parts();
+    let request_body = to_bytes(body, MAX_BODY_BYTES)
+        .await
+        .map_err(|_| StatusCode::PAYLOAD_TOO_LARGE)?;
+    let mut request = Request::from_parts(parts, Body::from(request_body.clone()));
+
+    let response = match authorize(&db, &key, ip, &method, &route, replay.is_some()).await {
+        Ok(user) => {
+            request_id::record_user(user.id);
+            let client = request.extensions().get::<ClientInfo>().cloned().unwrap_or_default();
+            let user = CurrentUser { client, ..user };
+            request.extensions_mut().insert(ApiPrincipal { user, sandbox: key.is_sandbox });
+            next.run(request).await
+        }
+        Err(status) => status.into_response(),
+    };
+
+    let (mut parts, body) = response.into_parts();
+    let response_body = to_bytes(body, usize::MAX).await.unwrap_or_default();
+
+    let logged = api_log::record(
+        &db,
+        ApiCall {
+            api_key_id: key.id,
+            method: method.as_str(),
+            route: &route,
+            path: &path,
+            status: parts.status.as_u16(),
+            latency_ms: started.elapsed().as_millis(),
+            request_body: &request_body,
+            response_body: &response_body,
+            ip: ip.map(|ip| ip.to_string()),
+            replay_of: replay.map(|replay| replay.log_id),
+        },
+    )
+    .await;
+
+    match logged {
+        Ok(log_id) => {
+            parts.extensions.insert(ApiCallLogged(log_id));
+        }
+        Err(e) => tracing::error!("Failed to write API call log: {:?}", e),
+    }
+
+    Ok(Response::from_parts(parts, Body::from(response_body)))
+}
+
+// Checks a known key against its state, its owner and the route being called,
+// returning the owner narrowed to the key's permissions
+async fn authorize(
+    db: &Database,
+    key: &ApiKey,
+    ip: Option<IpAddr>,
+    method: &Method,
+    route: &str,
+    is_replay: bool,
+) -> Result<CurrentUser, StatusCode> {
+    if key.revoked_at.is_some() || key.is_expired() {
+        return Err(StatusCode::UNAUTHORIZED);
+    }
+
+    // Replays come from the server on an admin's behalf, not from a client
+    // address, so the allowlist does not apply to them
+    if !key.allowed_ips.is_empty() && !is_replay {
+        let ip = ip.ok_or(StatusCode::UNAUTHORIZED)?;
+        let allowed = sqlx::query_scalar::<_, bool>(
+            "SELECT $1::inet <<= ANY(allowed_ips) FROM api_keys WHERE id = $2",
+        )
+        .bind(ip.to_string())
+        .bind(key.id)
+        .fetch_one(db)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to check API key allowlist: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR
+        })?;
+
+        if !allowed {
+            return Err(StatusCode::UNAUTHORIZED);
+        }
+    }
+
+    // Locked or deactivated owners take their keys down with them
+    let owner = get_user_by_id(db, key.user_id)
+        .await
+        .ok_or(StatusCode::UNAUTHORIZED)?;
+
+    if !owner.permissions.contains(&"api:access".to_string()) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    // A key never grants more than its owner currently holds
+    let user = owner.restricted_to(key.permissions.0.clone());
+
+    if !route_permitted(&user.permissions, method, route) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    // Keys of read-only owners are read-only too
+    if user.is_read_only && is_mutating_request(method, route) {
+        return Err(StatusCode::FORBIDDEN);
+    }
+
+    // Replays are not the integration using its key
+    if !is_replay {
+        sqlx::query("UPDATE api_keys SET last_used_at = NOW(), last_used_ip = $2 WHERE id = $1")
+            .bind(key.id)
+            .bind(ip.map(|ip| ip.to_string()))
+            .execute(db)
+            .await
+            .map_err(|e| {
+                tracing::error!("Failed to record API key use: {:?}", e);
+                StatusCode::INTERNAL_SERVER_ERROR
+            })?;
+    }
+
+    Ok(user)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inventory_key_reaches_customer_items() {
+        let route = "/api/customers/:id/items/:code";
+        assert!(route_permitted(&["inventory:read".to_string()], &Method::GET, route));
+        assert!(!route_permitted(&["customers:read".to_string()], &Method::GET, route));
+        assert!(!route_permitted(&["inventory:read".to_string()], &Method::POST, route));
+    }
+}
@@ -0,0 +1,51 @@
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts, Request},
+    http::{header::USER_AGENT, request::Parts},
+    middleware::Next,
+    response::Response,
+};
+use serde::{Deserialize, Serialize};
+use std::{convert::Infallible, net::SocketAddr};
+
+use crate::utils::request::client_ip;
+
+// Characters of the User-Agent header kept; some clients send very long ones
+const USER_AGENT_MAX: usize = 512;
+
+// Where a request came from, recorded with every audit entry it writes
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ClientInfo {
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+// Reads the caller's address and browser once per request and leaves them in
+// the request extensions, where AuthUser and the API principal pick them up
+pub async fn capture(mut request: Request, next: Next) -> Response {
+    let peer = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| *addr);
+    let info = ClientInfo {
+        ip_address: client_ip(request.headers(), peer).map(|ip| ip.to_string()),
+        user_agent: request
+            .headers()
+            .get(USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.chars().take(USER_AGENT_MAX).collect()),
+    };
+    request.extensions_mut().insert(info);
+    next.run(request).await
+}
+
+// For handlers that write audit entries without a signed-in user, like
+// sign-in itself
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for ClientInfo {
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        Ok(parts.extensions.get::<ClientInfo>().cloned().unwrap_or_default())
+    }
+}
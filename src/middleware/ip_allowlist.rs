@@ -0,0 +1,59 @@
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{header::AUTHORIZATION, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::net::SocketAddr;
+use tower_cookies::Cookies;
+
+use crate::{database::Database, utils::request::client_ip};
+
+// Paths that start an interactive session, checked even before anyone is signed in
+const SIGN_IN_PATHS: &[&str] = &["/login", "/setup"];
+
+// Enforces the organization IP allowlist for browser sessions and, unless they
+// are exempted, API keys. Unauthenticated public routes (lead forms, tracking
+// pixels, provider webhooks) are left alone.
+pub async fn enforce(
+    State(db): State<Database>,
+    cookies: Cookies,
+    request: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    let bearer = request.headers().contains_key(AUTHORIZATION);
+    let interactive = cookies.get("auth_token").is_some()
+        || SIGN_IN_PATHS.contains(&request.uri().path());
+
+    if !bearer && !interactive {
+        return Ok(next.run(request).await);
+    }
+
+    let peer = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| *addr);
+    let ip = client_ip(request.headers(), peer).map(|ip| ip.to_string());
+
+    let (enabled, exempt_api_keys, allowed) = sqlx::query_as::<_, (bool, bool, Option<bool>)>(
+        "SELECT ip_allowlist_enabled, exempt_api_keys, $1::inet <<= ANY(ip_allowlist) FROM security_settings",
+    )
+    .bind(&ip)
+    .fetch_optional(&db)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to load IP allowlist: {:?}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?
+    .unwrap_or((false, true, None));
+
+    if !enabled || (bearer && !interactive && exempt_api_keys) || allowed == Some(true) {
+        return Ok(next.run(request).await);
+    }
+
+    Ok((
+        StatusCode::FORBIDDEN,
+        "Access to Allo is restricted to approved networks. Connect from the office or VPN and try again.",
+    )
+        .into_response())
+}
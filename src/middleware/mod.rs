@@ -1,3 +1,13 @@
 pub mod permission;
+pub mod api_auth;
+pub mod ip_allowlist;
+pub mod throttle;
+pub mod rate_limit;
+pub mod security_headers;
+pub mod request_id;
+pub mod setup;
+pub mod client_info;
+pub mod tenant;
 
-pub use permission::{CurrentUser, get_current_user};
\ No newline at end of file
+pub use client_info::ClientInfo;
+pub use permission::{AuthUser, CurrentUser, get_current_user};
@@ -1,11 +1,30 @@
+use axum::{
+    async_trait,
+    extract::{FromRequestParts, Request, State},
+    http::{header, request::Parts, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Redirect, Response},
+};
+use chrono::Weekday;
+use chrono_tz::Tz;
 use serde::{Deserialize, Serialize};
-use tower_cookies::Cookies;
+use std::{
+    collections::HashMap,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Mutex, OnceLock,
+    },
+    time::{Duration, Instant},
+};
+use tower_cookies::{Cookie, Cookies};
 use uuid::Uuid;
 
+use super::{client_info::ClientInfo, request_id};
 use crate::{
     database::Database,
-    models::User,
-    utils::verify_token,
+    models::{FieldAccess, User},
+    services::sessions,
+    utils::{timezone::parse_timezone, verify_token},
 };
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -23,6 +42,27 @@ pub struct CurrentUser {
     pub has_team_delete: bool,
     pub has_manage_roles: bool,
     pub has_expense_approval: bool, // NEW: For approve/deny buttons
+    pub has_finance_read: bool,
+    pub has_data_export: bool,
+    // Holds a read-only role, so every mutating route is refused
+    pub is_read_only: bool,
+    // Set when the roles granting inventory access are limited to these
+    // warehouses; their stock is the only stock the user sees or moves
+    pub warehouse_ids: Option<Vec<Uuid>>,
+    // Preferred time zone; dates are entered and shown in it
+    pub timezone: Tz,
+    // First day of the week in date presets
+    pub week_start: Weekday,
+    // Set while an administrator is signed in as this user
+    pub impersonator: Option<Impersonator>,
+    // Where the current request came from, for audit entries
+    pub client: ClientInfo,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Impersonator {
+    pub id: Uuid,
+    pub name: String,
 }
 
 impl CurrentUser {
@@ -33,6 +73,8 @@ impl CurrentUser {
         let has_manage_roles = permissions.contains(&"team:manage_roles".to_string());
         // NEW: Check for the specific permission to approve expenses
         let has_expense_approval = permissions.contains(&"expenses:approve".to_string());
+        let has_finance_read = permissions.contains(&"finance:read".to_string());
+        let has_data_export = permissions.contains(&"data:export".to_string());
 
         Self {
             id: user.id,
@@ -47,6 +89,71 @@ impl CurrentUser {
             has_team_delete,
             has_manage_roles,
             has_expense_approval, // NEW
+            has_finance_read,
+            has_data_export,
+            is_read_only: false,
+            warehouse_ids: None,
+            timezone: Tz::UTC,
+            week_start: Weekday::Mon,
+            impersonator: None,
+            client: ClientInfo::default(),
+        }
+    }
+
+    // The administrator to record alongside this user in audit logs
+    pub fn impersonator_id(&self) -> Option<Uuid> {
+        self.impersonator.as_ref().map(|impersonator| impersonator.id)
+    }
+
+    pub fn field_access(&self) -> FieldAccess {
+        FieldAccess::new(self.id, &self.permissions)
+    }
+
+    // For handlers: `current_user.require("inventory:write")?;`
+    pub fn can(&self, permission: &str) -> bool {
+        self.permissions.iter().any(|p| p == permission)
+    }
+
+    pub fn require(&self, permission: &str) -> Result<(), StatusCode> {
+        if self.can(permission) {
+            Ok(())
+        } else {
+            Err(StatusCode::FORBIDDEN)
+        }
+    }
+
+    pub fn can_use_warehouse(&self, warehouse_id: Uuid) -> bool {
+        self.warehouse_ids.as_ref().is_none_or(|ids| ids.contains(&warehouse_id))
+    }
+
+    // For handlers: `current_user.require_warehouse(form.warehouse_id)?;`
+    pub fn require_warehouse(&self, warehouse_id: Uuid) -> Result<(), StatusCode> {
+        if self.can_use_warehouse(warehouse_id) {
+            Ok(())
+        } else {
+            Err(StatusCode::FORBIDDEN)
+        }
+    }
+
+    // The same user limited to a subset of permissions, e.g. what an API key allows
+    pub fn restricted_to(self, permissions: Vec<String>) -> Self {
+        let permissions = self
+            .permissions
+            .iter()
+            .filter(|p| permissions.contains(p))
+            .cloned()
+            .collect::<Vec<_>>();
+
+        Self {
+            has_team_read: permissions.contains(&"team:read".to_string()),
+            has_team_write: permissions.contains(&"team:write".to_string()),
+            has_team_delete: permissions.contains(&"team:delete".to_string()),
+            has_manage_roles: permissions.contains(&"team:manage_roles".to_string()),
+            has_expense_approval: permissions.contains(&"expenses:approve".to_string()),
+            has_finance_read: permissions.contains(&"finance:read".to_string()),
+            has_data_export: permissions.contains(&"data:export".to_string()),
+            permissions,
+            ..self
         }
     }
 }
@@ -54,47 +161,89 @@ impl CurrentUser {
 pub async fn get_current_user(cookies: Cookies, db: &Database) -> Option<CurrentUser> {
     // Try to get JWT token from auth_token cookie
     let token = cookies.get("auth_token")?.value().to_string();
-    
-    // Verify the JWT token
-    let claims = match verify_token(&token) {
-        Ok(claims) => claims,
-        Err(_) => {
-            // Token is invalid, try fallback to super admin for development
-            return get_super_admin_user(db).await;
-        }
+
+    // Expired, forged or signed with a retired key: the user has to sign in again
+    let verified = verify_token(&token).ok().and_then(|claims| {
+        let user_id = Uuid::parse_str(&claims.sub).ok()?;
+        let session_id = Uuid::parse_str(claims.sid.as_deref()?).ok()?;
+        Some((user_id, session_id))
+    });
+    let Some((user_id, session_id)) = verified else {
+        cookies.remove(Cookie::from("auth_token"));
+        return None;
     };
 
-    // Parse user ID from token claims
-    let user_id = match Uuid::parse_str(&claims.sub) {
-        Ok(id) => id,
-        Err(_) => return get_super_admin_user(db).await,
+    // Tokens only count while their session is live, which also slides the idle timeout
+    let owner = match sessions::touch(db, session_id).await {
+        Ok(Some(owner)) if owner.user_id == user_id => owner,
+        Ok(_) => {
+            // Expired, idle too long or revoked: the user has to sign in again
+            cookies.remove(Cookie::from("auth_token"));
+            return None;
+        }
+        Err(e) => {
+            tracing::error!("Error checking session {}: {}", session_id, e);
+            return None;
+        }
     };
 
+    sessions::set_cookie(&cookies, token, owner.expires_at, owner.idle_minutes);
+
     // Get user data from database
-    get_user_by_id(db, user_id).await
+    let user = get_user_by_id(db, user_id).await?;
+    let Some(impersonator_id) = owner.impersonator_id else {
+        return Some(user);
+    };
+
+    let name = sqlx::query_scalar::<_, String>("SELECT CONCAT(first_name, ' ', last_name) FROM users WHERE id = $1")
+        .bind(impersonator_id)
+        .fetch_one(db)
+        .await
+        .map_err(|e| tracing::error!("Error loading impersonator {}: {}", impersonator_id, e))
+        .ok()?;
+    Some(CurrentUser {
+        impersonator: Some(Impersonator { id: impersonator_id, name }),
+        ..user
+    })
 }
 
-async fn get_super_admin_user(db: &Database) -> Option<CurrentUser> {
-    // Fallback: get the super admin user directly (for development)
-    let user_result = sqlx::query_as::<_, User>(
-        "SELECT * FROM users WHERE email = 'andrew.davis@habb.tech' AND is_active = true AND is_locked = false"
-    )
-    .fetch_optional(db)
-    .await;
-    
-    match user_result {
-        Ok(Some(user)) => {
-            let permissions = get_user_permissions(db, user.id).await;
-            Some(CurrentUser::from_user_and_permissions(user, permissions))
-        },
-        _ => None
+// The signed-in user as a handler argument, written `AuthUser(current_user): AuthUser`.
+// Without a live session a page load is sent to the login page and anything
+// else gets a 401.
+pub struct AuthUser(pub CurrentUser);
+
+#[async_trait]
+impl FromRequestParts<Database> for AuthUser {
+    type Rejection = Response;
+
+    async fn from_request_parts(parts: &mut Parts, db: &Database) -> Result<Self, Self::Rejection> {
+        let cookies = Cookies::from_request_parts(parts, db)
+            .await
+            .map_err(IntoResponse::into_response)?;
+        if let Some(user) = get_current_user(cookies, db).await {
+            request_id::record_user(user.id);
+            let client = parts.extensions.get::<ClientInfo>().cloned().unwrap_or_default();
+            return Ok(Self(CurrentUser { client, ..user }));
+        }
+
+        let wants_page = parts.method == Method::GET
+            && parts
+                .headers
+                .get(header::ACCEPT)
+                .and_then(|accept| accept.to_str().ok())
+                .is_some_and(|accept| accept.contains("text/html"));
+        if wants_page {
+            Err(Redirect::to("/login").into_response())
+        } else {
+            Err(StatusCode::UNAUTHORIZED.into_response())
+        }
     }
 }
 
-async fn get_user_by_id(db: &Database, user_id: Uuid) -> Option<CurrentUser> {
+pub async fn get_user_by_id(db: &Database, user_id: Uuid) -> Option<CurrentUser> {
     // Get user data
     let user_row = sqlx::query!(
-        "SELECT id, email, password_hash, first_name, last_name, is_active, is_locked, last_login, locked_at, locked_by, created_at, updated_at FROM users WHERE id = $1 AND is_active = true AND is_locked = false",
+        "SELECT id, email, password_hash, first_name, last_name, is_active, is_locked, last_login, locked_at, locked_by, manager_id, created_at, updated_at, timezone, week_start FROM users WHERE id = $1 AND is_active = true AND is_locked = false",
         user_id
     )
     .fetch_optional(db)
@@ -113,31 +262,308 @@ async fn get_user_by_id(db: &Database, user_id: Uuid) -> Option<CurrentUser> {
         last_login: user_row.last_login,
         locked_at: user_row.locked_at,
         locked_by: user_row.locked_by,
-        created_at: user_row.created_at.unwrap_or_else(|| chrono::Utc::now()),
-        updated_at: user_row.updated_at.unwrap_or_else(|| chrono::Utc::now()),
+        manager_id: user_row.manager_id,
+        created_at: user_row.created_at.unwrap_or_else(chrono::Utc::now),
+        updated_at: user_row.updated_at.unwrap_or_else(chrono::Utc::now),
     };
 
-    let permissions = get_user_permissions(db, user.id).await;
-    
-    Some(CurrentUser::from_user_and_permissions(user, permissions))
+    let (permissions, is_read_only, warehouse_ids) = resolve_roles(db, user.id).await;
+
+    Some(CurrentUser {
+        is_read_only,
+        warehouse_ids,
+        timezone: parse_timezone(&user_row.timezone),
+        week_start: user_row.week_start.parse().unwrap_or(Weekday::Mon),
+        ..CurrentUser::from_user_and_permissions(user, permissions)
+    })
 }
 
-pub async fn get_user_permissions(db: &Database, user_id: Uuid) -> Vec<String> {
-    let permissions = sqlx::query!(
+// What a user's roles add up to, kept for a short while so page loads don't
+// re-run the role queries each time. Changes made here clear the affected
+// entries straight away; the TTL bounds how stale other app instances get.
+const ROLE_CACHE_TTL: Duration = Duration::from_secs(60);
+
+struct CachedRoles {
+    permissions: Vec<String>,
+    is_read_only: bool,
+    warehouse_ids: Option<Vec<Uuid>>,
+    loaded_at: Instant,
+}
+
+static ROLE_CACHE: OnceLock<Mutex<HashMap<Uuid, CachedRoles>>> = OnceLock::new();
+static ROLE_CACHE_HITS: AtomicU64 = AtomicU64::new(0);
+static ROLE_CACHE_MISSES: AtomicU64 = AtomicU64::new(0);
+
+fn role_cache() -> &'static Mutex<HashMap<Uuid, CachedRoles>> {
+    ROLE_CACHE.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+// How well the role cache has done since this server started
+pub struct RoleCacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub entries: usize,
+}
+
+pub fn role_cache_stats() -> RoleCacheStats {
+    RoleCacheStats {
+        hits: ROLE_CACHE_HITS.load(Ordering::Relaxed),
+        misses: ROLE_CACHE_MISSES.load(Ordering::Relaxed),
+        entries: role_cache().lock().unwrap().len(),
+    }
+}
+
+// Call after changing which roles a user holds
+pub fn invalidate_permissions(user_id: Uuid) {
+    role_cache().lock().unwrap().remove(&user_id);
+}
+
+// Call after changing a role itself, which can affect any number of users
+pub fn invalidate_all_permissions() {
+    role_cache().lock().unwrap().clear();
+}
+
+// (permissions, is_read_only, warehouse_ids) for the user
+async fn resolve_roles(db: &Database, user_id: Uuid) -> (Vec<String>, bool, Option<Vec<Uuid>>) {
+    if let Some(cached) = role_cache().lock().unwrap().get(&user_id) {
+        if cached.loaded_at.elapsed() < ROLE_CACHE_TTL {
+            ROLE_CACHE_HITS.fetch_add(1, Ordering::Relaxed);
+            return (cached.permissions.clone(), cached.is_read_only, cached.warehouse_ids.clone());
+        }
+    }
+    ROLE_CACHE_MISSES.fetch_add(1, Ordering::Relaxed);
+
+    let loaded = async {
+        let permissions = fetch_permissions(db, user_id).await?;
+        let is_read_only = has_read_only_role(db, user_id).await?;
+        let warehouse_ids = limited_warehouses(db, user_id).await?;
+        Ok::<_, sqlx::Error>((permissions, is_read_only, warehouse_ids))
+    };
+    match loaded.await {
+        Ok((permissions, is_read_only, warehouse_ids)) => {
+            let mut cache = role_cache().lock().unwrap();
+            cache.retain(|_, entry| entry.loaded_at.elapsed() < ROLE_CACHE_TTL);
+            cache.insert(
+                user_id,
+                CachedRoles {
+                    permissions: permissions.clone(),
+                    is_read_only,
+                    warehouse_ids: warehouse_ids.clone(),
+                    loaded_at: Instant::now(),
+                },
+            );
+            (permissions, is_read_only, warehouse_ids)
+        }
+        Err(e) => {
+            // Fail closed, and don't remember it
+            tracing::error!("Error loading roles for {}: {}", user_id, e);
+            (Vec::new(), true, Some(Vec::new()))
+        }
+    }
+}
+
+// `held_roles (user_id, role_id)`: the active roles each user holds plus
+// every active role those extend, however far up. UNION drops rows already
+// seen, so a loop in the parents ends the walk instead of recursing forever.
+// With `user_param`, only that user's roles are walked.
+pub(crate) fn held_roles_cte(user_param: Option<usize>) -> String {
+    let user_filter = user_param
+        .map(|param| format!("AND ur.user_id = ${}", param))
+        .unwrap_or_default();
+    format!(
         r#"
-        SELECT DISTINCT jsonb_array_elements_text(r.permissions) as permission
-        FROM roles r
-        JOIN user_roles ur ON r.id = ur.role_id
-        WHERE ur.user_id = $1 AND r.is_active = true
+        WITH RECURSIVE held_roles (user_id, role_id) AS (
+            SELECT ur.user_id, r.id FROM user_roles ur
+            JOIN roles r ON r.id = ur.role_id AND r.is_active = true
+            WHERE true {}
+            UNION
+            SELECT h.user_id, parent.id FROM held_roles h
+            JOIN roles child ON child.id = h.role_id
+            JOIN roles parent ON parent.id = child.parent_role_id AND parent.is_active = true
+        )
+        "#,
+        user_filter
+    )
+}
+
+// Extending a read-only role makes a role read-only too
+async fn has_read_only_role(db: &Database, user_id: Uuid) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar::<_, bool>(&format!(
+        r#"
+        {}
+        SELECT EXISTS (
+            SELECT 1 FROM held_roles h
+            JOIN roles r ON r.id = h.role_id
+            WHERE r.is_read_only = true
+        )
+        "#,
+        held_roles_cte(Some(1))
+    ))
+    .bind(user_id)
+    .fetch_one(db)
+    .await
+}
+
+// A role limited to a warehouse limits whatever it grants, including what
+// it inherits, so the limit is carried up the walk. The user is limited only
+// if every role giving them inventory access is: one unlimited role is enough
+// to see every warehouse. None means no limit.
+async fn limited_warehouses(db: &Database, user_id: Uuid) -> Result<Option<Vec<Uuid>>, sqlx::Error> {
+    let scopes = sqlx::query_scalar::<_, Option<Uuid>>(
+        r#"
+        WITH RECURSIVE held_roles (role_id, warehouse_id) AS (
+            SELECT r.id, r.warehouse_id FROM user_roles ur
+            JOIN roles r ON r.id = ur.role_id AND r.is_active = true
+            WHERE ur.user_id = $1
+            UNION
+            SELECT parent.id, COALESCE(h.warehouse_id, parent.warehouse_id) FROM held_roles h
+            JOIN roles child ON child.id = h.role_id
+            JOIN roles parent ON parent.id = child.parent_role_id AND parent.is_active = true
+        )
+        SELECT DISTINCT h.warehouse_id
+        FROM held_roles h
+        JOIN roles r ON r.id = h.role_id
+        WHERE EXISTS (
+            SELECT 1 FROM jsonb_array_elements_text(r.permissions) p WHERE p LIKE 'inventory:%'
+        )
         "#,
-        user_id
     )
+    .bind(user_id)
+    .fetch_all(db)
+    .await?;
+
+    if scopes.is_empty() || scopes.contains(&None) {
+        return Ok(None);
+    }
+    Ok(Some(scopes.into_iter().flatten().collect()))
+}
+
+// GET routes that change data, left over from link-driven actions
+const MUTATING_GET_SUFFIXES: &[&str] = &["/delete", "/lock", "/unlock", "/approve", "/deny"];
+
+// Routes a read-only user still needs: signing in and out, clearing their
+// notifications and an admin handing back a read-only account they were
+// impersonating
+const READ_ONLY_EXEMPT_PATHS: &[&str] = &["/login", "/logout", "/notifications/read", "/impersonation/end"];
+
+// A user's own account: password, sessions and preferences
+const READ_ONLY_EXEMPT_PREFIXES: &[&str] = &["/profile/"];
+
+// Watching a record only changes what the user is notified about
+const READ_ONLY_EXEMPT_SUFFIXES: &[&str] = &["/watch", "/unwatch"];
+
+pub fn is_mutating_request(method: &Method, path: &str) -> bool {
+    match *method {
+        Method::GET | Method::HEAD | Method::OPTIONS => {
+            MUTATING_GET_SUFFIXES.iter().any(|suffix| path.ends_with(suffix))
+        }
+        _ => true,
+    }
+}
+
+// Refuses any mutating request from a user holding a read-only role,
+// regardless of the other permissions their roles grant
+pub async fn enforce_read_only(
+    State(db): State<Database>,
+    cookies: Cookies,
+    request: Request,
+    next: Next,
+) -> Response {
+    let path = request.uri().path();
+    if !is_mutating_request(request.method(), path)
+        || READ_ONLY_EXEMPT_PATHS.contains(&path)
+        || READ_ONLY_EXEMPT_PREFIXES.iter().any(|prefix| path.starts_with(prefix))
+        || READ_ONLY_EXEMPT_SUFFIXES.iter().any(|suffix| path.ends_with(suffix))
+    {
+        return next.run(request).await;
+    }
+
+    match get_current_user(cookies, &db).await {
+        Some(user) if user.is_read_only => (
+            StatusCode::FORBIDDEN,
+            "Your account has read-only access and can't make changes.",
+        )
+            .into_response(),
+        _ => next.run(request).await,
+    }
+}
+
+pub async fn get_user_permissions(db: &Database, user_id: Uuid) -> Vec<String> {
+    fetch_permissions(db, user_id).await.unwrap_or_default()
+}
+
+// Includes what the user's roles inherit from their parents
+async fn fetch_permissions(db: &Database, user_id: Uuid) -> Result<Vec<String>, sqlx::Error> {
+    sqlx::query_scalar::<_, String>(&format!(
+        r#"
+        {}
+        SELECT DISTINCT jsonb_array_elements_text(r.permissions)
+        FROM held_roles h
+        JOIN roles r ON r.id = h.role_id
+        "#,
+        held_roles_cte(Some(1))
+    ))
+    .bind(user_id)
     .fetch_all(db)
     .await
-    .unwrap_or_default()
-    .into_iter()
-    .filter_map(|row| row.permission)
-    .collect();
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::Claims;
+    use chrono::{Duration as ChronoDuration, Utc};
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use sqlx::postgres::PgPoolOptions;
+
+    // Never connected to: a token that doesn't verify has to be turned away
+    // before the session is looked up
+    fn unreachable_db() -> Database {
+        PgPoolOptions::new().connect_lazy("postgres://allo@127.0.0.1:1/allo").unwrap()
+    }
+
+    fn signed_in_with(token: String) -> Cookies {
+        let cookies = Cookies::default();
+        cookies.add(Cookie::new("auth_token", token));
+        cookies
+    }
 
-    permissions
-}
\ No newline at end of file
+    // Retired keys are dropped from the ring, so a retired kid is one the
+    // ring doesn't have. The ring itself is left alone as utils::auth's test
+    // changes it.
+    #[tokio::test]
+    async fn retired_key_signs_out() {
+        let claims = Claims::new(
+            Uuid::new_v4(),
+            "ann@allo.test".to_string(),
+            Uuid::new_v4(),
+            Utc::now() + ChronoDuration::hours(1),
+        );
+        let token = encode(
+            &Header { kid: Some("retired".to_string()), ..Header::default() },
+            &claims,
+            &EncodingKey::from_secret(b"secret-retired"),
+        )
+        .unwrap();
+
+        let cookies = signed_in_with(token);
+        assert!(get_current_user(cookies.clone(), &unreachable_db()).await.is_none());
+        assert!(cookies.get("auth_token").is_none());
+    }
+
+    // Past session_max_hours the token itself has expired
+    #[tokio::test]
+    async fn expired_token_signs_out() {
+        std::env::set_var("JWT_SECRET", "legacy-secret");
+        let claims = Claims::new(
+            Uuid::new_v4(),
+            "ann@allo.test".to_string(),
+            Uuid::new_v4(),
+            Utc::now() - ChronoDuration::hours(1),
+        );
+        let token = encode(&Header::default(), &claims, &EncodingKey::from_secret(b"legacy-secret")).unwrap();
+
+        let cookies = signed_in_with(token);
+        assert!(get_current_user(cookies.clone(), &unreachable_db()).await.is_none());
+        assert!(cookies.get("auth_token").is_none());
+    }
+}
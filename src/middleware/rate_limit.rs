@@ -0,0 +1,109 @@
+use axum::{
+    extract::Request,
+    http::{
+        header::{AUTHORIZATION, RETRY_AFTER},
+        HeaderMap, Method, StatusCode,
+    },
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::{
+    env,
+    sync::{Mutex, OnceLock},
+    time::{Duration, Instant},
+};
+use tower_cookies::Cookies;
+
+use crate::{
+    middleware::ClientInfo,
+    utils::{api_key::hash_api_key, rate_limit::RateLimiter, verify_token},
+};
+
+// Where accounts are signed into or created, held to the stricter limit
+const SIGN_IN_PATHS: &[&str] = &["/login", "/invite", "/setup"];
+
+struct Limits {
+    per_ip: Mutex<RateLimiter>,
+    per_user: Mutex<RateLimiter>,
+    sign_in: Mutex<RateLimiter>,
+}
+
+static LIMITS: OnceLock<Limits> = OnceLock::new();
+
+// Requests a minute from the environment, with 0 for no limit
+fn per_minute(name: &str, default: u32) -> Mutex<RateLimiter> {
+    let per_minute = env::var(name)
+        .ok()
+        .and_then(|value| value.trim().parse().ok())
+        .unwrap_or(default);
+    Mutex::new(RateLimiter::new(per_minute))
+}
+
+fn limits() -> &'static Limits {
+    LIMITS.get_or_init(|| Limits {
+        per_ip: per_minute("RATE_LIMIT_PER_IP", 600),
+        per_user: per_minute("RATE_LIMIT_PER_USER", 300),
+        sign_in: per_minute("RATE_LIMIT_SIGN_IN", 10),
+    })
+}
+
+// Who is calling, when they say: the signed-in user, or the API key. Only
+// verified tokens count, so nobody can spend someone else's allowance.
+fn principal(headers: &HeaderMap, cookies: Option<&Cookies>) -> Option<String> {
+    if let Some(token) = headers
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::trim)
+        .filter(|token| !token.is_empty())
+    {
+        return Some(format!("key:{}", hash_api_key(token)));
+    }
+    let token = cookies?.get("auth_token")?.value().to_string();
+    verify_token(&token).ok().map(|claims| format!("user:{}", claims.sub))
+}
+
+fn take(limiter: &Mutex<RateLimiter>, key: &str, now: Instant) -> Result<(), Duration> {
+    limiter.lock().unwrap().take(key, now)
+}
+
+// Token-bucket limits per client IP and per user or API key, plus a much
+// tighter one per IP on the sign-in and sign-up forms. Kept in memory, so
+// each app instance counts on its own.
+pub async fn limit(request: Request, next: Next) -> Response {
+    let path = request.uri().path();
+    if path.starts_with("/static/") {
+        return next.run(request).await;
+    }
+
+    let limits = limits();
+    let now = Instant::now();
+    let ip = request
+        .extensions()
+        .get::<ClientInfo>()
+        .and_then(|client| client.ip_address.clone());
+    let sign_in = request.method() == Method::POST && SIGN_IN_PATHS.contains(&path);
+
+    let mut allowed = Ok(());
+    if let Some(ip) = &ip {
+        if sign_in {
+            allowed = take(&limits.sign_in, ip, now);
+        }
+        allowed = allowed.and_then(|_| take(&limits.per_ip, ip, now));
+    }
+    if allowed.is_ok() {
+        if let Some(principal) = principal(request.headers(), request.extensions().get::<Cookies>()) {
+            allowed = take(&limits.per_user, &principal, now);
+        }
+    }
+
+    match allowed {
+        Ok(()) => next.run(request).await,
+        Err(wait) => (
+            StatusCode::TOO_MANY_REQUESTS,
+            [(RETRY_AFTER, (wait.as_secs_f64().ceil() as u64).max(1).to_string())],
+            "Too many requests. Please wait a moment and try again.",
+        )
+            .into_response(),
+    }
+}
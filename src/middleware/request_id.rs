@@ -0,0 +1,124 @@
+use askama::Template;
+use axum::{
+    body::Body,
+    extract::{MatchedPath, Request},
+    http::{
+        header::{ACCEPT, CONTENT_LENGTH, CONTENT_TYPE},
+        HeaderName, HeaderValue,
+    },
+    middleware::Next,
+    response::Response,
+};
+use std::time::Instant;
+use tracing::{field, Instrument, Span};
+use uuid::Uuid;
+
+static REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+
+// Longest ID accepted from a proxy in front of the app
+const FORWARDED_ID_MAX: usize = 64;
+
+tokio::task_local! {
+    static CURRENT: String;
+}
+
+// The ID of the request being handled, for pages that show it
+pub fn current() -> Option<String> {
+    CURRENT.try_with(String::clone).ok()
+}
+
+#[derive(Template)]
+#[template(path = "error.html")]
+struct ErrorTemplate {
+    status: u16,
+    reason: &'static str,
+    request_id: String,
+}
+
+// A load balancer's X-Request-Id is kept, so its logs and ours line up;
+// otherwise a new one is made
+fn request_id(request: &Request) -> String {
+    request
+        .headers()
+        .get(&REQUEST_ID)
+        .and_then(|value| value.to_str().ok())
+        .filter(|id| {
+            !id.is_empty()
+                && id.len() <= FORWARDED_ID_MAX
+                && id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        })
+        .map(str::to_string)
+        .unwrap_or_else(|| Uuid::new_v4().simple().to_string())
+}
+
+// Runs each request in a span carrying its ID, with the route and user
+// filled in once they're known, and hands the ID back in X-Request-Id. Bare
+// error statuses shown to a browser become a page quoting the ID.
+pub async fn trace(request: Request, next: Next) -> Response {
+    let id = request_id(&request);
+    let wants_page = request
+        .headers()
+        .get(ACCEPT)
+        .and_then(|accept| accept.to_str().ok())
+        .is_some_and(|accept| accept.contains("text/html"));
+    let span = tracing::info_span!(
+        "request",
+        request_id = %id,
+        method = %request.method(),
+        path = %request.uri().path(),
+        route = field::Empty,
+        user_id = field::Empty,
+    );
+
+    let started = Instant::now();
+    let mut response = CURRENT
+        .scope(id.clone(), next.run(request))
+        .instrument(span.clone())
+        .await;
+
+    let status = response.status();
+    let latency_ms = started.elapsed().as_millis() as u64;
+    span.in_scope(|| {
+        if status.is_server_error() {
+            tracing::error!(status = status.as_u16(), latency_ms, "request failed");
+        } else {
+            tracing::info!(status = status.as_u16(), latency_ms, "request finished");
+        }
+    });
+
+    let bare_error = (status.is_client_error() || status.is_server_error())
+        && !response.headers().contains_key(CONTENT_TYPE);
+    if wants_page && bare_error {
+        response = error_page(response, id.clone());
+    }
+    if let Ok(value) = HeaderValue::from_str(&id) {
+        response.headers_mut().insert(REQUEST_ID.clone(), value);
+    }
+    response
+}
+
+// Only matched routes get here, so it runs as a route layer
+pub async fn record_route(request: Request, next: Next) -> Response {
+    if let Some(route) = request.extensions().get::<MatchedPath>() {
+        Span::current().record("route", route.as_str());
+    }
+    next.run(request).await
+}
+
+// Notes the signed-in user or API key owner on the request's span
+pub fn record_user(user_id: Uuid) {
+    Span::current().record("user_id", field::display(user_id));
+}
+
+// Keeps the response's status and headers, swapping in the page for its body
+fn error_page(response: Response, request_id: String) -> Response {
+    let (mut parts, _) = response.into_parts();
+    let template = ErrorTemplate {
+        status: parts.status.as_u16(),
+        reason: parts.status.canonical_reason().unwrap_or("Error"),
+        request_id,
+    };
+    parts.headers.remove(CONTENT_LENGTH);
+    parts.headers.insert(CONTENT_TYPE, HeaderValue::from_static("text/html; charset=utf-8"));
+    Response::from_parts(parts, Body::from(template.render().unwrap()))
+}
@@ -0,0 +1,124 @@
+use axum::{
+    extract::Request,
+    http::{
+        header::{
+            AUTHORIZATION, CONTENT_SECURITY_POLICY, CONTENT_TYPE, REFERRER_POLICY, STRICT_TRANSPORT_SECURITY,
+            X_CONTENT_TYPE_OPTIONS, X_FRAME_OPTIONS,
+        },
+        HeaderValue, Method,
+    },
+    middleware::Next,
+    response::Response,
+};
+use std::{env, sync::OnceLock};
+use tower_http::cors::CorsLayer;
+
+use crate::services::{captcha, mailer};
+
+// Where the templates load scripts and styles from: Tailwind and htmx in
+// base.html, Chart.js on the dashboard and reports. CSP_ASSET_ORIGINS
+// replaces the list for deployments serving them from elsewhere.
+const DEFAULT_ASSET_ORIGINS: &str = "https://cdn.tailwindcss.com https://unpkg.com https://cdn.jsdelivr.net";
+
+// Pages other sites may show in a frame, like the lead form on a marketing site
+const FRAMEABLE_PATHS: &[&str] = &["/public/lead"];
+
+const HSTS: &str = "max-age=31536000; includeSubDomains";
+
+struct Policies {
+    default: HeaderValue,
+    frameable: HeaderValue,
+    hsts: bool,
+}
+
+static POLICIES: OnceLock<Policies> = OnceLock::new();
+
+// Space or comma separated
+fn origins_from_env(name: &str, default: &str) -> Vec<String> {
+    env::var(name)
+        .unwrap_or_else(|_| default.to_string())
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|origin| !origin.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+// The templates use inline scripts, styles and onclick handlers, so those
+// stay allowed; everything else is limited to the app and the asset origins
+fn content_security_policy(frame_ancestors: &str) -> HeaderValue {
+    let asset_origins = origins_from_env("CSP_ASSET_ORIGINS", DEFAULT_ASSET_ORIGINS);
+    let assets: Vec<&str> = asset_origins.iter().map(String::as_str).collect();
+    let captcha = captcha::origins();
+    let sources = |base: &str, origins: &[&str]| {
+        std::iter::once(base).chain(origins.iter().copied()).collect::<Vec<_>>().join(" ")
+    };
+    let scripts: Vec<&str> = assets.iter().chain(captcha).copied().collect();
+    let frames = if captcha.is_empty() { "'none'".to_string() } else { captcha.join(" ") };
+    let policy = format!(
+        "default-src 'self'; \
+         script-src {}; \
+         style-src {}; \
+         font-src {}; \
+         img-src 'self' data: https:; \
+         connect-src {}; \
+         frame-src {}; \
+         object-src 'none'; \
+         base-uri 'self'; \
+         form-action 'self'; \
+         frame-ancestors {}",
+        sources("'self' 'unsafe-inline'", &scripts),
+        sources("'self' 'unsafe-inline'", &assets),
+        sources("'self' data:", &assets),
+        sources("'self'", captcha),
+        frames,
+        frame_ancestors,
+    );
+    HeaderValue::from_str(&policy).unwrap_or_else(|_| {
+        tracing::warn!("CSP_ASSET_ORIGINS holds characters not allowed in a header; ignoring it");
+        HeaderValue::from_static("default-src 'self'")
+    })
+}
+
+fn policies() -> &'static Policies {
+    POLICIES.get_or_init(|| Policies {
+        default: content_security_policy("'none'"),
+        frameable: content_security_policy("*"),
+        hsts: mailer::app_url().starts_with("https://"),
+    })
+}
+
+// Adds the security headers to every response. HSTS is only sent when the
+// app is served over HTTPS, going by APP_URL.
+pub async fn set_headers(request: Request, next: Next) -> Response {
+    let frameable = FRAMEABLE_PATHS.contains(&request.uri().path());
+    let mut response = next.run(request).await;
+
+    let policies = policies();
+    let headers = response.headers_mut();
+    headers.insert(
+        CONTENT_SECURITY_POLICY,
+        if frameable { policies.frameable.clone() } else { policies.default.clone() },
+    );
+    if !frameable {
+        headers.insert(X_FRAME_OPTIONS, HeaderValue::from_static("DENY"));
+    }
+    headers.insert(REFERRER_POLICY, HeaderValue::from_static("strict-origin-when-cross-origin"));
+    headers.insert(X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff"));
+    if policies.hsts {
+        headers.insert(STRICT_TRANSPORT_SECURITY, HeaderValue::from_static(HSTS));
+    }
+    response
+}
+
+// Cross-origin browser access, only for the origins in CORS_ALLOWED_ORIGINS.
+// API clients calling from servers aren't affected either way.
+pub fn cors() -> CorsLayer {
+    let origins: Vec<HeaderValue> = origins_from_env("CORS_ALLOWED_ORIGINS", "")
+        .iter()
+        .filter_map(|origin| HeaderValue::from_str(origin).ok())
+        .collect();
+    CorsLayer::new()
+        .allow_origin(origins)
+        .allow_methods([Method::GET, Method::POST, Method::PUT, Method::PATCH, Method::DELETE])
+        .allow_headers([AUTHORIZATION, CONTENT_TYPE])
+}
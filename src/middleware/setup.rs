@@ -0,0 +1,32 @@
+use axum::{
+    extract::{Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::{IntoResponse, Redirect, Response},
+};
+
+use crate::{database::Database, services::setup};
+
+// Until the first-run setup has created the organization and its first admin,
+// every page sends the visitor to /setup. The status page stays up for
+// monitoring tools.
+pub async fn require_setup(
+    State(db): State<Database>,
+    request: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    let path = request.uri().path();
+    if path == "/setup" || path == "/status" || path == "/status.json" || path.starts_with("/static/") {
+        return Ok(next.run(request).await);
+    }
+
+    let complete = setup::is_complete(&db).await.map_err(|e| {
+        tracing::error!("Error checking setup: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    if !complete {
+        return Ok(Redirect::to("/setup").into_response());
+    }
+
+    Ok(next.run(request).await)
+}
@@ -0,0 +1,68 @@
+use axum::{
+    extract::{Request, State},
+    http::{header::HOST, StatusCode},
+    middleware::Next,
+    response::Response,
+};
+use tower_cookies::Cookies;
+use uuid::Uuid;
+
+use crate::{
+    database::Database,
+    services::tenancy::{self, Tenant},
+    utils::verify_token,
+};
+
+// Works out which tenant the request is for and runs the rest of it in that
+// tenant's scope. The subdomain decides (acme.example.com is the tenant with
+// slug "acme"); without one the signed-in session does, and failing that the
+// primary tenant.
+pub async fn resolve(
+    State(db): State<Database>,
+    cookies: Cookies,
+    request: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    let host = request
+        .headers()
+        .get(HOST)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    let tenant = find(&db, host.as_deref(), &cookies).await.map_err(|e| {
+        tracing::error!("Error resolving tenant: {}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+    let Some(tenant) = tenant else {
+        return Err(StatusCode::NOT_FOUND);
+    };
+
+    Ok(tenancy::scope(tenant, next.run(request)).await)
+}
+
+async fn find(db: &Database, host: Option<&str>, cookies: &Cookies) -> Result<Option<Tenant>, sqlx::Error> {
+    if let Some(slug) = host.and_then(subdomain) {
+        if let Some(tenant) = tenancy::by_slug(db, slug).await? {
+            return Ok(Some(tenant));
+        }
+    }
+
+    let session_id = cookies
+        .get("auth_token")
+        .and_then(|cookie| verify_token(cookie.value()).ok())
+        .and_then(|claims| claims.sid)
+        .and_then(|sid| Uuid::parse_str(&sid).ok());
+    if let Some(session_id) = session_id {
+        if let Some(tenant) = tenancy::for_session(db, session_id).await? {
+            return Ok(Some(tenant));
+        }
+    }
+
+    tenancy::primary(db).await
+}
+
+// The first label of a dotted host name; IP addresses have none
+fn subdomain(host: &str) -> Option<&str> {
+    let host = host.rsplit_once(':').map_or(host, |(name, _port)| name);
+    let (label, _) = host.split_once('.')?;
+    (!label.is_empty() && !label.starts_with(|c: char| c.is_ascii_digit())).then_some(label)
+}
@@ -0,0 +1,81 @@
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::{header::RETRY_AFTER, Method, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use std::net::SocketAddr;
+
+use crate::{database::Database, utils::request::client_ip};
+
+// Public form submissions allowed per client IP: (path, attempts, window in minutes)
+const LIMITS: &[(&str, i64, i32)] = &[
+    ("/login", 20, 15),
+    ("/invite", 10, 60),
+    ("/setup", 10, 60),
+    ("/public/lead", 10, 60),
+];
+
+// Per-IP throttle on the unauthenticated forms. Every submission counts,
+// successful or not, so scripted sign-ups and lead spam slow down as well.
+pub async fn throttle_public_forms(
+    State(db): State<Database>,
+    request: Request,
+    next: Next,
+) -> Result<Response, StatusCode> {
+    if request.method() != Method::POST {
+        return Ok(next.run(request).await);
+    }
+    let Some(&(endpoint, max_attempts, window_minutes)) =
+        LIMITS.iter().find(|(path, _, _)| *path == request.uri().path())
+    else {
+        return Ok(next.run(request).await);
+    };
+
+    let peer = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| *addr);
+    let Some(ip) = client_ip(request.headers(), peer).map(|ip| ip.to_string()) else {
+        return Ok(next.run(request).await);
+    };
+
+    // Expired attempts are cleared as we go so the table stays small
+    let _ = sqlx::query(
+        "DELETE FROM public_form_attempts WHERE endpoint = $1 AND ip_address = $2 AND created_at < NOW() - make_interval(mins => $3)",
+    )
+    .bind(endpoint)
+    .bind(&ip)
+    .bind(window_minutes)
+    .execute(&db)
+    .await;
+
+    let attempts = sqlx::query_scalar::<_, i64>(
+        "SELECT COUNT(*) FROM public_form_attempts WHERE endpoint = $1 AND ip_address = $2",
+    )
+    .bind(endpoint)
+    .bind(&ip)
+    .fetch_one(&db)
+    .await
+    .map_err(|e| {
+        tracing::error!("Failed to check form throttle: {:?}", e);
+        StatusCode::INTERNAL_SERVER_ERROR
+    })?;
+
+    if attempts >= max_attempts {
+        return Ok((
+            StatusCode::TOO_MANY_REQUESTS,
+            [(RETRY_AFTER, (window_minutes * 60).to_string())],
+            "Too many attempts from your network. Please wait a while and try again.",
+        )
+            .into_response());
+    }
+
+    let _ = sqlx::query("INSERT INTO public_form_attempts (endpoint, ip_address) VALUES ($1, $2)")
+        .bind(endpoint)
+        .bind(&ip)
+        .execute(&db)
+        .await;
+
+    Ok(next.run(request).await)
+}
@@ -0,0 +1,101 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+// allowed_ips is CIDR[] in the database; load keys through API_KEY_SELECT so
+// it arrives as text
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ApiKey {
+    pub id: Uuid,
+    pub name: String,
+    pub key_prefix: String,
+    pub user_id: Uuid,
+    pub owner_name: String,
+    pub permissions: sqlx::types::Json<Vec<String>>,
+    pub allowed_ips: Vec<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub last_used_ip: Option<String>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub is_sandbox: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+pub const API_KEY_SELECT: &str = r#"
+    SELECT k.id, k.name, k.key_prefix, k.user_id,
+           CONCAT(u.first_name, ' ', u.last_name) as owner_name,
+           k.permissions, k.allowed_ips::text[] as allowed_ips,
+           k.expires_at, k.last_used_at, k.last_used_ip, k.revoked_at, k.is_sandbox, k.created_at
+    FROM api_keys k
+    JOIN users u ON u.id = k.user_id
+"#;
+
+impl ApiKey {
+    pub fn is_expired(&self) -> bool {
+        self.expires_at.is_some_and(|at| at <= Utc::now())
+    }
+
+    pub fn status(&self) -> &'static str {
+        if self.revoked_at.is_some() {
+            "revoked"
+        } else if self.is_expired() {
+            "expired"
+        } else {
+            "active"
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ApiCallLog {
+    pub id: Uuid,
+    pub api_key_id: Option<Uuid>,
+    pub key_name: Option<String>,
+    pub key_prefix: Option<String>,
+    pub method: String,
+    pub route: String,
+    pub path: String,
+    pub status: i32,
+    pub latency_ms: i32,
+    pub request_body: Option<String>,
+    pub response_body: Option<String>,
+    pub ip: Option<String>,
+    pub replay_of: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl ApiCallLog {
+    // Only GETs are replayed; anything else could change data twice
+    pub fn can_replay(&self) -> bool {
+        self.method == "GET" && self.api_key_id.is_some()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Webhook {
+    pub id: Uuid,
+    pub name: String,
+    pub url: String,
+    pub event: String,
+    pub payload_template: Option<String>,
+    pub is_active: bool,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct WebhookDelivery {
+    pub id: Uuid,
+    pub webhook_id: Uuid,
+    pub event: String,
+    pub payload: String,
+    pub status: String,
+    pub attempts: i32,
+    pub response_status: Option<i32>,
+    pub error: Option<String>,
+    pub next_attempt_at: DateTime<Utc>,
+    pub delivered_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
@@ -0,0 +1,158 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+pub const BLANKET_ORDER_STATUSES: &[&str] = &["open", "closed", "cancelled"];
+
+// Selects a BlanketOrder; alias the order `o` and join the customer as `c`
+pub const BLANKET_ORDER_SELECT: &str = r#"
+    SELECT o.*, c.company_name, c.preferred_language,
+           COALESCE((SELECT SUM(l.quantity) FROM blanket_order_lines l WHERE l.blanket_order_id = o.id), 0) as agreed,
+           COALESCE((SELECT SUM(r.quantity) FROM blanket_order_releases r
+                     JOIN blanket_order_lines l ON l.id = r.line_id
+                     WHERE l.blanket_order_id = o.id AND r.status = 'shipped'), 0) as shipped,
+           COALESCE((SELECT SUM(r.quantity) FROM blanket_order_releases r
+                     JOIN blanket_order_lines l ON l.id = r.line_id
+                     WHERE l.blanket_order_id = o.id AND r.status = 'reserved'), 0) as reserved
+    FROM blanket_orders o
+    JOIN customers c ON c.id = o.customer_id
+"#;
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct BlanketOrder {
+    pub id: Uuid,
+    pub customer_id: Uuid,
+    // Ours, from the sales order sequence; orders made before numbering have none
+    pub order_number: Option<String>,
+    // The customer's PO or contract number
+    pub reference: String,
+    pub status: String,
+    pub starts_on: NaiveDate,
+    pub ends_on: NaiveDate,
+    pub reserve_days_ahead: i32,
+    pub currency: String,
+    // Locked when the order was made; None when its currency had no rate then
+    pub exchange_rate: Option<Decimal>,
+    pub notes: Option<String>,
+    pub created_by: Option<Uuid>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+    pub company_name: String,
+    // The customer's, for printing
+    pub preferred_language: Option<String>,
+    // Units over all lines
+    pub agreed: i64,
+    pub shipped: i64,
+    pub reserved: i64,
+}
+
+impl BlanketOrder {
+    // Still owed to the customer, reserved or not
+    pub fn remaining(&self) -> i64 {
+        self.agreed - self.shipped
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.status == "open"
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct BlanketOrderLine {
+    pub id: Uuid,
+    pub blanket_order_id: Uuid,
+    pub item_id: Uuid,
+    pub item_name: String,
+    pub sku: String,
+    pub quantity: i32,
+    pub unit_price: Decimal,
+    pub customer_part_number: Option<String>,
+    // Units in releases by status
+    pub shipped: i64,
+    pub reserved: i64,
+    pub scheduled: i64,
+}
+
+impl BlanketOrderLine {
+    pub fn remaining(&self) -> i64 {
+        i64::from(self.quantity) - self.shipped
+    }
+
+    // Agreed but not in any release yet
+    pub fn unreleased(&self) -> i64 {
+        self.remaining() - self.reserved - self.scheduled
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct BlanketOrderRelease {
+    pub id: Uuid,
+    pub line_id: Uuid,
+    pub item_id: Uuid,
+    pub item_name: String,
+    pub warehouse_id: Uuid,
+    pub warehouse_name: String,
+    pub quantity: i32,
+    pub scheduled_for: NaiveDate,
+    // scheduled, reserved, shipped or cancelled
+    pub status: String,
+    pub reserved_at: Option<DateTime<Utc>>,
+    pub shipped_at: Option<DateTime<Utc>>,
+    // Still waiting on stock; it can't ship until this is zero
+    pub backordered: i32,
+}
+
+impl BlanketOrderRelease {
+    pub fn is_pending(&self) -> bool {
+        self.status == "scheduled" || self.status == "reserved"
+    }
+}
+
+// An open backorder as listed in the queue
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct Backorder {
+    pub id: Uuid,
+    pub release_id: Uuid,
+    pub item_id: Uuid,
+    pub item_name: String,
+    pub sku: String,
+    pub warehouse_id: Uuid,
+    pub warehouse_name: String,
+    pub blanket_order_id: Uuid,
+    // Our order number, or the customer's reference on older orders
+    pub reference: String,
+    pub customer_id: Uuid,
+    pub company_name: String,
+    pub scheduled_for: NaiveDate,
+    pub quantity: i32,
+    pub allocated: i32,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Backorder {
+    pub fn outstanding(&self) -> i32 {
+        self.quantity - self.allocated
+    }
+}
+
+// What open blanket orders still owe a customer of one item
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct ItemCommitment {
+    pub blanket_order_id: Uuid,
+    // Our order number, or the customer's reference on older orders
+    pub reference: String,
+    pub customer_id: Uuid,
+    pub company_name: String,
+    pub agreed: i64,
+    pub shipped: i64,
+    pub reserved: i64,
+    pub next_release: Option<NaiveDate>,
+}
+
+impl ItemCommitment {
+    pub fn remaining(&self) -> i64 {
+        self.agreed - self.shipped
+    }
+}
@@ -0,0 +1,93 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+use chrono::{DateTime, NaiveDate, Utc};
+
+pub const CAMPAIGN_CHANNELS: [&str; 7] = [
+    "email", "paid_search", "social", "event", "referral", "content", "other",
+];
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct Campaign {
+    pub id: Uuid,
+    pub name: String,
+    pub channel: String,
+    pub cost: rust_decimal::Decimal,
+    pub currency: Option<String>,
+    pub start_date: Option<NaiveDate>,
+    pub end_date: Option<NaiveDate>,
+    pub description: Option<String>,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub utm_campaign: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CampaignDisplay {
+    pub id: Uuid,
+    pub name: String,
+    pub channel: String,
+    pub cost: String,
+    pub currency: String,
+    pub start_date: String,
+    pub end_date: String,
+    pub description: String,
+    pub utm_campaign: String,
+}
+
+impl From<Campaign> for CampaignDisplay {
+    fn from(campaign: Campaign) -> Self {
+        Self {
+            id: campaign.id,
+            name: campaign.name,
+            channel: campaign.channel,
+            cost: format!("{:.2}", campaign.cost),
+            currency: campaign.currency.unwrap_or_else(|| "USD".to_string()),
+            start_date: campaign.start_date.map(|d| d.to_string()).unwrap_or_default(),
+            end_date: campaign.end_date.map(|d| d.to_string()).unwrap_or_default(),
+            description: campaign.description.unwrap_or_default(),
+            utm_campaign: campaign.utm_campaign.unwrap_or_default(),
+        }
+    }
+}
+
+// One row of the campaign ROI report
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct CampaignRoi {
+    pub id: Uuid,
+    pub name: String,
+    pub channel: String,
+    pub cost: rust_decimal::Decimal,
+    pub customers: i64,
+    pub deals: i64,
+    pub pipeline_value: rust_decimal::Decimal,
+    pub won_deals: i64,
+    pub won_revenue: rust_decimal::Decimal,
+}
+
+impl CampaignRoi {
+    // Return on spend as a percentage; None when the campaign had no cost
+    pub fn roi_percent(&self) -> Option<i64> {
+        use rust_decimal::prelude::ToPrimitive;
+
+        if self.cost.is_zero() {
+            return None;
+        }
+        ((self.won_revenue - self.cost) / self.cost * rust_decimal::Decimal::from(100))
+            .round()
+            .to_i64()
+    }
+}
+
+// Web leads grouped by their UTM parameters, for the attribution report
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct UtmLeadSummary {
+    pub utm_source: String,
+    pub utm_medium: String,
+    pub utm_campaign: String,
+    pub campaign_name: Option<String>,
+    pub leads: i64,
+    pub deals: i64,
+    pub won_revenue: rust_decimal::Decimal,
+}
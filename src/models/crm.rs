@@ -2,6 +2,14 @@ use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use uuid::Uuid;
 use chrono::{DateTime, NaiveDate, Utc};
+use chrono_tz::Tz;
+
+use super::{lead_source_label, FieldAccess};
+use crate::utils::{locale::LANGUAGES, timezone::format_local};
+
+pub const CUSTOMER_STATUSES: &[&str] = &["prospect", "active", "inactive"];
+
+pub const DEAL_STAGES: &[&str] = &["prospect", "negotiation", "closed_won", "closed_lost"];
 
 #[derive(Debug, Serialize, Deserialize, FromRow)]
 pub struct Customer {
@@ -22,6 +30,19 @@ pub struct Customer {
     pub created_by: Option<Uuid>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    pub campaign_id: Option<Uuid>,
+    pub lead_source: Option<String>,
+    pub utm_source: Option<String>,
+    pub utm_medium: Option<String>,
+    pub utm_campaign: Option<String>,
+    pub referrer: Option<String>,
+    // Code from LANGUAGES; documents default to English
+    pub preferred_language: Option<String>,
+    // Owner when it isn't the creator
+    pub assigned_to: Option<Uuid>,
+    // Values of the admin-defined fields; see services::custom_fields
+    #[sqlx(default)]
+    pub custom_fields: serde_json::Value,
 }
 
 // Template-friendly customer struct
@@ -41,6 +62,14 @@ pub struct CustomerTemplate {
     pub country: String,
     pub status: String,
     pub notes: String,
+    pub campaign_id: Option<Uuid>,
+    pub preferred_language: String,
+}
+
+impl CustomerTemplate {
+    pub fn prefers_language(&self, code: &str) -> bool {
+        self.preferred_language == code
+    }
 }
 
 impl From<Customer> for CustomerTemplate {
@@ -60,6 +89,8 @@ impl From<Customer> for CustomerTemplate {
             country: customer.country.unwrap_or_else(|| "United States".to_string()),
             status: customer.status,
             notes: customer.notes.unwrap_or_default(),
+            campaign_id: customer.campaign_id,
+            preferred_language: customer.preferred_language.unwrap_or_default(),
         }
     }
 }
@@ -83,6 +114,15 @@ pub struct CustomerDisplay {
     pub notes: String,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    pub lead_source: String,
+    pub utm_source: String,
+    pub utm_medium: String,
+    pub utm_campaign: String,
+    pub referrer: String,
+    // Name of the language documents are printed in; empty for the default
+    pub language: String,
+    // How the lead source reads on screen; empty when there is none
+    pub lead_source_label: String,
 }
 
 impl From<Customer> for CustomerDisplay {
@@ -104,6 +144,17 @@ impl From<Customer> for CustomerDisplay {
             notes: customer.notes.unwrap_or_default(),
             created_at: customer.created_at,
             updated_at: customer.updated_at,
+            lead_source_label: customer.lead_source.as_deref().map(lead_source_label).unwrap_or_default().to_string(),
+            lead_source: customer.lead_source.unwrap_or_default(),
+            utm_source: customer.utm_source.unwrap_or_default(),
+            utm_medium: customer.utm_medium.unwrap_or_default(),
+            utm_campaign: customer.utm_campaign.unwrap_or_default(),
+            referrer: customer.referrer.unwrap_or_default(),
+            language: LANGUAGES
+                .iter()
+                .find(|(code, _)| customer.preferred_language.as_deref() == Some(*code))
+                .map(|(_, name)| name.to_string())
+                .unwrap_or_default(),
         }
     }
 }
@@ -120,9 +171,19 @@ pub struct Contact {
     pub mobile: Option<String>,
     pub is_primary: bool,
     pub notes: Option<String>,
+    pub email_tracking_opt_out: bool,
+    pub do_not_contact: bool,
+    pub email_status: String,
+    pub email_status_reason: Option<String>,
+    pub email_status_at: Option<DateTime<Utc>>,
     pub created_by: Option<Uuid>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    // Storage URLs of uploaded images
+    pub photo_url: Option<String>,
+    pub business_card_url: Option<String>,
+    #[sqlx(default)]
+    pub custom_fields: serde_json::Value,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -137,6 +198,13 @@ pub struct ContactDisplay {
     pub mobile: String,
     pub is_primary: bool,
     pub notes: String,
+    pub email_tracking_opt_out: bool,
+    pub do_not_contact: bool,
+    pub email_status: String, // valid, invalid, complained
+    pub email_status_reason: String,
+    pub photo_url: String,
+    pub business_card_url: String,
+    pub custom_fields: serde_json::Value,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
@@ -154,6 +222,13 @@ impl From<Contact> for ContactDisplay {
             mobile: contact.mobile.unwrap_or_default(),
             is_primary: contact.is_primary,
             notes: contact.notes.unwrap_or_default(),
+            email_tracking_opt_out: contact.email_tracking_opt_out,
+            do_not_contact: contact.do_not_contact,
+            email_status: contact.email_status,
+            email_status_reason: contact.email_status_reason.unwrap_or_default(),
+            photo_url: contact.photo_url.unwrap_or_default(),
+            business_card_url: contact.business_card_url.unwrap_or_default(),
+            custom_fields: contact.custom_fields,
             created_at: contact.created_at,
             updated_at: contact.updated_at,
         }
@@ -169,6 +244,8 @@ pub struct Deal {
     pub description: Option<String>,
     pub value: Option<rust_decimal::Decimal>,
     pub currency: String,
+    // Locked when the deal was made or its currency changed; see services::exchange_rates
+    pub exchange_rate: Option<rust_decimal::Decimal>,
     pub stage: String,
     pub probability: i32,
     pub expected_close_date: Option<NaiveDate>,
@@ -177,6 +254,10 @@ pub struct Deal {
     pub created_by: Option<Uuid>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    pub stage_changed_at: Option<DateTime<Utc>>,
+    pub campaign_id: Option<Uuid>,
+    #[sqlx(default)]
+    pub custom_fields: serde_json::Value,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -187,33 +268,212 @@ pub struct DealDisplay {
     pub description: String,
     pub value: String,
     pub currency: String,
+    // Empty when no rate was locked
+    pub exchange_rate: String,
     pub stage: String,
     pub probability: i32,
     pub expected_close_date: String,
     pub actual_close_date: String,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    // Set by handlers from the stage settings; see services::deal_health
+    pub is_stalled: bool,
+    // The viewer lacks finance:read, so `value` is left empty
+    pub value_hidden: bool,
 }
 
-impl From<Deal> for DealDisplay {
-    fn from(deal: Deal) -> Self {
+impl DealDisplay {
+    pub fn new(deal: Deal, access: &FieldAccess) -> Self {
         Self {
             id: deal.id,
             customer_id: deal.customer_id,
             title: deal.title,
             description: deal.description.unwrap_or_default(),
-            value: deal.value.map(|v| format!("{}", v)).unwrap_or_default(),
+            value: deal.value
+                .filter(|_| access.finance)
+                .map(|v| format!("{}", v))
+                .unwrap_or_default(),
             currency: deal.currency,
+            exchange_rate: deal.exchange_rate.map(|rate| rate.normalize().to_string()).unwrap_or_default(),
             stage: deal.stage,
             probability: deal.probability,
             expected_close_date: deal.expected_close_date.map(|d| d.to_string()).unwrap_or_default(),
             actual_close_date: deal.actual_close_date.map(|d| d.to_string()).unwrap_or_default(),
             created_at: deal.created_at,
             updated_at: deal.updated_at,
+            is_stalled: false,
+            value_hidden: !access.finance,
         }
     }
 }
 
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct DealStageSetting {
+    pub stage: String,
+    pub stale_after_days: i32,
+    pub updated_at: DateTime<Utc>,
+}
+
+// A stage a deal moved into, and how long it stayed there
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct DealStageChange {
+    pub id: Uuid,
+    pub old_stage: Option<String>,
+    pub new_stage: String,
+    pub changed_by_name: Option<String>,
+    pub changed_at: DateTime<Utc>,
+    // Up to the next move, or until now for the stage the deal is in
+    pub days_in_stage: i32,
+    pub is_current: bool,
+    // When an open stage counts as stuck; None for closed stages
+    pub stale_after_days: Option<i32>,
+}
+
+impl DealStageChange {
+    // Still in an open stage for longer than it should take
+    pub fn is_stuck(&self) -> bool {
+        self.is_current && self.stale_after_days.is_some_and(|days| self.days_in_stage > days)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct DealLineItem {
+    pub id: Uuid,
+    pub deal_id: Uuid,
+    pub item_id: Option<Uuid>,
+    pub description: String,
+    pub quantity: rust_decimal::Decimal,
+    pub unit_price: rust_decimal::Decimal,
+    pub discount_percent: rust_decimal::Decimal,
+    // none, pending, approved or denied; see services::discounts
+    pub discount_status: String,
+    pub discount_decided_by: Option<Uuid>,
+    pub discount_decided_at: Option<DateTime<Utc>>,
+    pub created_by: Option<Uuid>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub customer_part_number: Option<String>,
+    // Set when the price came from the customer's pricing agreement
+    pub pricing_agreement_id: Option<Uuid>,
+}
+
+impl DealLineItem {
+    pub fn has_discount(&self) -> bool {
+        !self.discount_percent.is_zero()
+    }
+
+    pub fn total(&self) -> rust_decimal::Decimal {
+        let hundred = rust_decimal::Decimal::from(100);
+        (self.quantity * self.unit_price * (hundred - self.discount_percent) / hundred).round_dp(2)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct DiscountThreshold {
+    pub id: Uuid,
+    pub above_percent: rust_decimal::Decimal,
+    // 'manager' or 'approver' (holders of discounts:approve only)
+    pub approver: String,
+    pub created_at: Option<DateTime<Utc>>,
+}
+
+impl DiscountThreshold {
+    pub fn approver_label(&self) -> &'static str {
+        match self.approver.as_str() {
+            "manager" => "Their manager",
+            _ => "A discount approver",
+        }
+    }
+}
+
+// A customer's own number for one of our items, with the item it maps to
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct CustomerPartNumber {
+    pub id: Uuid,
+    pub customer_id: Uuid,
+    pub item_id: Uuid,
+    pub part_number: String,
+    pub description: Option<String>,
+    pub item_name: String,
+    pub sku: String,
+}
+
+// A negotiated price for one item: fixed, or a discount off its selling price
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct PricingAgreement {
+    pub id: Uuid,
+    pub customer_id: Uuid,
+    pub item_id: Uuid,
+    pub fixed_price: Option<rust_decimal::Decimal>,
+    pub discount_percent: Option<rust_decimal::Decimal>,
+    pub valid_from: NaiveDate,
+    pub valid_until: Option<NaiveDate>,
+    pub notes: Option<String>,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub item_name: String,
+    pub sku: String,
+    pub selling_price: Option<rust_decimal::Decimal>,
+    // upcoming, current, expiring or expired, as of today
+    pub status: String,
+}
+
+impl PricingAgreement {
+    // The unit price the customer pays under the agreement; None for a
+    // discount on an item without a selling price
+    pub fn unit_price(&self) -> Option<rust_decimal::Decimal> {
+        let hundred = rust_decimal::Decimal::from(100);
+        self.fixed_price.or_else(|| {
+            let percent = self.discount_percent?;
+            Some((self.selling_price? * (hundred - percent) / hundred).round_dp(2))
+        })
+    }
+
+    // "12.50 each" or "15% off"
+    pub fn terms_label(&self) -> String {
+        match (self.fixed_price, self.discount_percent) {
+            (Some(price), _) => format!("{} each", price),
+            (None, Some(percent)) => format!("{}% off", percent.normalize()),
+            (None, None) => String::new(),
+        }
+    }
+
+    pub fn is_expiring(&self) -> bool {
+        self.status == "expiring"
+    }
+
+    pub fn is_expired(&self) -> bool {
+        self.status == "expired"
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ActivityType {
+    pub id: Uuid,
+    pub code: String,
+    pub name: String,
+    pub icon: String,
+    pub default_duration_minutes: Option<i32>,
+    pub requires_outcome: bool,
+    pub sort_order: i32,
+    pub is_active: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct ActivityOutcome {
+    pub id: Uuid,
+    pub activity_type: String,
+    pub code: String,
+    pub label: String,
+    pub is_connect: bool,
+    pub is_held: bool,
+    pub is_active: bool,
+    pub sort_order: i32,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
 #[derive(Debug, Serialize, Deserialize, FromRow)]
 pub struct Activity {
     pub id: Uuid,
@@ -226,6 +486,7 @@ pub struct Activity {
     pub activity_date: DateTime<Utc>,
     pub duration_minutes: Option<i32>,
     pub completed: bool,
+    pub outcome_code: Option<String>,
     pub assigned_to: Option<Uuid>,
     pub created_by: Option<Uuid>,
     pub created_at: DateTime<Utc>,
@@ -237,30 +498,52 @@ pub struct ActivityDisplay {
     pub id: Uuid,
     pub customer_id: Uuid,
     pub activity_type: String,
+    // From the activity type; a type that's gone shows its code and a note icon
+    pub type_name: String,
+    pub icon: String,
     pub subject: String,
     pub description: String,
     pub activity_date: String,
     pub duration_minutes: String,
     pub completed: bool,
+    pub outcome: String, // Outcome code; activities_list swaps in the configured label
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
 impl From<Activity> for ActivityDisplay {
     fn from(activity: Activity) -> Self {
+        Self::in_timezone(activity, Tz::UTC)
+    }
+}
+
+impl ActivityDisplay {
+    // Shown in the viewer's own time zone
+    pub fn in_timezone(activity: Activity, tz: Tz) -> Self {
         Self {
             id: activity.id,
             customer_id: activity.customer_id,
+            type_name: activity.activity_type.clone(),
+            icon: "📝".to_string(),
             activity_type: activity.activity_type,
             subject: activity.subject,
             description: activity.description.unwrap_or_default(),
-            activity_date: activity.activity_date.format("%B %d, %Y at %I:%M %p").to_string(),
+            activity_date: format_local(activity.activity_date, tz, "%B %d, %Y at %I:%M %p"),
             duration_minutes: activity.duration_minutes.map(|d| d.to_string()).unwrap_or_default(),
             completed: activity.completed,
+            outcome: activity.outcome_code.unwrap_or_default(),
             created_at: activity.created_at,
             updated_at: activity.updated_at,
         }
     }
+
+    pub fn with_type(mut self, types: &[ActivityType]) -> Self {
+        if let Some(activity_type) = types.iter().find(|t| t.code == self.activity_type) {
+            self.type_name = activity_type.name.clone();
+            self.icon = activity_type.icon.clone();
+        }
+        self
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -278,4 +561,14 @@ pub struct CreateCustomer {
     pub country: Option<String>,
     pub status: String,
     pub notes: Option<String>,
-}
\ No newline at end of file
+}
+// A share row joined with the names of who it was shared with and by
+#[derive(Debug, FromRow)]
+pub struct RecordShare {
+    pub id: Uuid,
+    pub grantee_name: String,
+    pub is_team: bool,
+    pub access: String,
+    pub shared_by_name: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
@@ -0,0 +1,57 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+// Kinds of custom field: (stored value, label)
+pub const CUSTOM_FIELD_TYPES: &[(&str, &str)] = &[
+    ("text", "Text"),
+    ("number", "Number"),
+    ("date", "Date"),
+    ("select", "Dropdown"),
+];
+
+// Records custom fields can be added to: (stored value, label)
+pub const CUSTOM_FIELD_RECORDS: &[(&str, &str)] = &[
+    ("customer", "Customers"),
+    ("contact", "Contacts"),
+    ("deal", "Deals"),
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct CustomField {
+    pub id: Uuid,
+    pub record_type: String,
+    pub key: String,
+    pub label: String,
+    pub field_type: String,
+    pub options: Vec<String>,
+    pub is_required: bool,
+    pub sort_order: i32,
+    pub is_active: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl CustomField {
+    pub fn type_label(&self) -> &str {
+        CUSTOM_FIELD_TYPES
+            .iter()
+            .find(|(value, _)| *value == self.field_type)
+            .map(|(_, label)| *label)
+            .unwrap_or(&self.field_type)
+    }
+
+    pub fn record_label(&self) -> &str {
+        CUSTOM_FIELD_RECORDS
+            .iter()
+            .find(|(value, _)| *value == self.record_type)
+            .map(|(_, label)| *label)
+            .unwrap_or(&self.record_type)
+    }
+
+    // The form input's name; kept apart from the record's own fields
+    pub fn input_name(&self) -> String {
+        format!("cf_{}", self.key)
+    }
+}
@@ -0,0 +1,60 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+use super::number_sequence::NUMBERED_DOCUMENTS;
+
+pub const LOGO_POSITIONS: &[(&str, &str)] = &[
+    ("left", "Left"),
+    ("center", "Centered"),
+    ("right", "Right"),
+    ("none", "No logo"),
+];
+
+// Line columns a document can print, in the order they appear
+pub const DOCUMENT_COLUMNS: &[(&str, &str)] = &[
+    ("sku", "SKU"),
+    ("customer_part_number", "Customer part #"),
+    ("description", "Description"),
+    ("quantity", "Quantity"),
+    ("unit_price", "Unit price"),
+    ("line_total", "Amount"),
+];
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct DocumentTemplate {
+    pub document_type: String,
+    // The heading printed on the document, e.g. "Invoice"
+    pub title: String,
+    pub logo_url: Option<String>,
+    pub logo_position: String,
+    // Printed above the lines, terms and footer below them
+    pub header_text: Option<String>,
+    pub terms_text: Option<String>,
+    pub footer_text: Option<String>,
+    pub columns: Vec<String>,
+    pub updated_by: Option<Uuid>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+impl DocumentTemplate {
+    pub fn label(&self) -> &'static str {
+        NUMBERED_DOCUMENTS
+            .iter()
+            .find(|(key, _)| *key == self.document_type)
+            .map_or("Documents", |(_, label)| *label)
+    }
+
+    pub fn shows(&self, column: &str) -> bool {
+        self.columns.iter().any(|c| c == column)
+    }
+
+    pub fn logo_at(&self, position: &str) -> bool {
+        self.logo_position == position
+    }
+
+    pub fn has_logo(&self) -> bool {
+        self.logo_position != "none" && self.logo_url.is_some()
+    }
+}
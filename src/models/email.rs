@@ -0,0 +1,43 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct OutboxEmail {
+    pub id: Uuid,
+    pub to_address: String,
+    pub subject: String,
+    pub body_html: String,
+    pub status: String,
+    pub error: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub sent_at: Option<DateTime<Utc>>,
+    pub contact_id: Option<Uuid>,
+    pub sender_name: Option<String>,
+    pub reply_to: Option<String>,
+}
+
+// A contact email with its tracked opens and clicks, for the contact timeline
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct TrackedEmail {
+    pub id: Uuid,
+    pub subject: String,
+    pub status: String,
+    pub created_at: DateTime<Utc>,
+    pub open_count: i64,
+    pub click_count: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct EmailEvent {
+    pub id: Uuid,
+    pub email_id: Uuid,
+    pub subject: String,
+    pub event_type: String,
+    pub detail: Option<String>, // Clicked URL, or the provider's bounce/complaint reason
+    pub created_at: DateTime<Utc>,
+}
+
+// Allowed values for users.digest_frequency
+pub const DIGEST_FREQUENCIES: [&str; 3] = ["off", "daily", "weekly"];
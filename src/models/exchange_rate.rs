@@ -0,0 +1,21 @@
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+
+// A currency from the dropdown with today's rate against the base currency
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct ExchangeRate {
+    pub currency: String,
+    pub label: String,
+    // Base currency units for one unit of this one; None until it's set
+    pub rate: Option<Decimal>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+impl ExchangeRate {
+    // Without trailing zeros, for the form
+    pub fn rate_input(&self) -> String {
+        self.rate.map(|rate| rate.normalize().to_string()).unwrap_or_default()
+    }
+}
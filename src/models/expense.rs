@@ -3,6 +3,8 @@ use sqlx::FromRow;
 use uuid::Uuid;
 use chrono::{DateTime, NaiveDate, Utc};
 
+use super::FieldAccess;
+
 #[derive(Debug, Serialize, Deserialize, FromRow)]
 pub struct ExpenseCategory {
     pub id: Uuid,
@@ -14,12 +16,26 @@ pub struct ExpenseCategory {
     pub created_by: Option<Uuid>,
 }
 
+// A department spend is booked against; `code` is the accounting system's
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct CostCenter {
+    pub id: Uuid,
+    pub code: String,
+    pub name: String,
+    pub is_active: bool,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
 #[derive(Debug, Serialize, Deserialize, FromRow)]
 pub struct Expense {
     pub id: Uuid,
     pub user_id: Uuid,
     pub category_id: Uuid,
     pub customer_id: Option<Uuid>,
+    pub cost_center_id: Uuid,
+    pub project_id: Option<Uuid>,
     pub amount: rust_decimal::Decimal,
     pub description: Option<String>,
     pub receipt_url: Option<String>,
@@ -34,8 +50,10 @@ pub struct Expense {
 #[derive(Debug, Serialize, Deserialize, FromRow)] // MODIFIED: Added FromRow
 pub struct ExpenseDisplay {
     pub id: Uuid,
+    pub user_id: Uuid,
     pub user_name: String,
     pub category_name: String,
+    pub cost_center_name: String,
     pub customer_name: Option<String>,
     pub amount: String,
     pub description: String,
@@ -43,4 +61,21 @@ pub struct ExpenseDisplay {
     pub status: String,
     pub expense_date: String,
     pub created_at: DateTime<Utc>,
+    #[sqlx(default)]
+    pub amount_hidden: bool,
+    // Whether the viewer may approve or deny it; only the expense list selects it
+    #[sqlx(default)]
+    pub can_decide: bool,
+}
+
+impl ExpenseDisplay {
+    // Rows come straight from SQL, so the access rules are applied once loaded.
+    // People always see the amounts of their own expenses.
+    pub fn with_access(mut self, access: &FieldAccess) -> Self {
+        if !access.finance && self.user_id != access.viewer_id {
+            self.amount = String::new();
+            self.amount_hidden = true;
+        }
+        self
+    }
 }
\ No newline at end of file
@@ -0,0 +1,60 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+// Select list for imports with their job's progress; the uploaded file itself is left out
+pub const IMPORT_SELECT: &str = r#"
+    SELECT i.id, i.import_type, i.file_name, i.total_rows, i.imported_rows, i.rejected_rows,
+           i.merged_rows, i.skipped_rows,
+           i.job_id, COALESCE(j.status, CASE WHEN i.staged THEN 'staged' ELSE 'failed' END) as status, COALESCE(j.progress_done, 0) as progress_done,
+           j.last_error, i.created_by,
+           NULLIF(CONCAT(u.first_name, ' ', u.last_name), ' ') as created_by_name, i.created_at
+    FROM imports i
+    LEFT JOIN jobs j ON j.id = i.job_id
+    LEFT JOIN users u ON u.id = i.created_by
+"#;
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Import {
+    pub id: Uuid,
+    pub import_type: String,
+    pub file_name: String,
+    pub total_rows: i32,
+    pub imported_rows: i32,
+    pub rejected_rows: i32,
+    pub merged_rows: i32,
+    pub skipped_rows: i32,
+    pub job_id: Option<Uuid>,
+    pub status: String,
+    pub progress_done: i32,
+    pub last_error: Option<String>,
+    pub created_by: Option<Uuid>,
+    pub created_by_name: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Import {
+    // Uploaded, with its column mapping still to be confirmed
+    pub fn is_staged(&self) -> bool {
+        self.status == "staged"
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.status == "queued" || self.status == "running"
+    }
+
+    pub fn percent(&self) -> i32 {
+        if self.total_rows <= 0 {
+            return 0;
+        }
+        (i64::from(self.progress_done) * 100 / i64::from(self.total_rows)).min(100) as i32
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct ImportError {
+    pub row_number: i32,
+    pub fields: sqlx::types::Json<Vec<String>>,
+    pub reason: String,
+}
@@ -3,6 +3,8 @@ use sqlx::FromRow;
 use uuid::Uuid;
 use chrono::{DateTime, NaiveDate, Utc};
 
+use super::FieldAccess;
+
 #[derive(Debug, Serialize, Deserialize, FromRow)]
 pub struct Warehouse {
     pub id: Uuid,
@@ -47,6 +49,115 @@ pub struct InventoryItem {
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     pub created_by: Option<Uuid>,
+    pub parent_item_id: Option<Uuid>,
+    pub variant_options: Option<sqlx::types::Json<Vec<VariantOption>>>,
+}
+
+// One of a variant's option values, e.g. Size: M
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct VariantOption {
+    pub name: String,
+    pub value: String,
+}
+
+impl InventoryItem {
+    // "M / Red" for a variant, empty for other items
+    pub fn variant_label(&self) -> String {
+        self.variant_options
+            .as_ref()
+            .map(|options| options.iter().map(|o| o.value.as_str()).collect::<Vec<_>>().join(" / "))
+            .unwrap_or_default()
+    }
+
+    // Purchase and cost figures need finance:read; selling price stays visible
+    pub fn with_access(mut self, access: &FieldAccess) -> Self {
+        if !access.finance {
+            self.purchase_price = None;
+            self.cost_price = None;
+            self.landed_cost = None;
+            self.average_cost = None;
+            self.gross_margin = None;
+        }
+        self
+    }
+}
+
+// The options a parent item's variants are generated from
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct ItemOptionSet {
+    pub id: Uuid,
+    pub item_id: Uuid,
+    pub name: String,
+    pub option_values: Vec<String>,
+    pub position: i32,
+}
+
+// Stock of one item summed over every warehouse
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct ItemStock {
+    pub item_id: Uuid,
+    pub on_hand: i64,
+    pub committed: i64,
+    pub available: i64,
+}
+
+// Stock of one item in one warehouse
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct WarehouseStock {
+    pub warehouse_name: String,
+    pub quantity_on_hand: i32,
+    pub quantity_committed: i32,
+    pub quantity_available: i32,
+    // The bin location's code, when one is assigned
+    pub location: Option<String>,
+}
+
+// A warehouse as listed, with what it holds
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct WarehouseSummary {
+    pub id: Uuid,
+    pub name: String,
+    pub location: Option<String>,
+    pub is_active: bool,
+    pub location_count: i64,
+    pub units_on_hand: i64,
+}
+
+// A bin location within a warehouse
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct WarehouseLocation {
+    pub id: Uuid,
+    pub warehouse_id: Uuid,
+    pub code: String,
+    pub aisle: Option<String>,
+    pub bin: Option<String>,
+    pub walk_order: i32,
+    // Roughly how many units fit; None when nobody has said
+    pub capacity: Option<i32>,
+    pub created_at: DateTime<Utc>,
+    // On hand across the items assigned here
+    pub units_stored: i64,
+}
+
+impl WarehouseLocation {
+    pub fn free_capacity(&self) -> Option<i64> {
+        self.capacity.map(|capacity| (i64::from(capacity) - self.units_stored).max(0))
+    }
+}
+
+// One reserved release to pick, at the location its item is kept in
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct PickListLine {
+    pub release_id: Uuid,
+    pub location_code: Option<String>,
+    pub item_id: Uuid,
+    pub item_name: String,
+    pub sku: String,
+    pub quantity: i32,
+    pub scheduled_for: NaiveDate,
+    pub blanket_order_id: Uuid,
+    pub reference: String,
+    pub company_name: String,
 }
 
 #[derive(Debug, Serialize, Deserialize, FromRow)]
@@ -76,6 +187,34 @@ pub struct StockMovement {
     pub reference_id: Option<String>,
     pub moved_by: Option<Uuid>,
     pub moved_at: DateTime<Utc>,
+    // owned, consignment or customer_owned
+    pub ownership: String,
+}
+
+// Stock kept in a warehouse that isn't ours: a supplier's consignment or
+// goods a customer owns. Never available to sell and never valued.
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct HeldStock {
+    pub id: Uuid,
+    pub item_id: Uuid,
+    pub warehouse_id: Uuid,
+    pub warehouse_name: String,
+    pub ownership: String,
+    pub customer_id: Option<Uuid>,
+    // The customer's company name, or the consignor
+    pub owner_name: String,
+    pub quantity_on_hand: i32,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl HeldStock {
+    pub fn is_consignment(&self) -> bool {
+        self.ownership == "consignment"
+    }
+
+    pub fn ownership_label(&self) -> &'static str {
+        if self.is_consignment() { "Consignment" } else { "Customer-owned" }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize, FromRow)]
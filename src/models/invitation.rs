@@ -0,0 +1,51 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+// Select list for invitations with role and inviter names; the token hash is left out
+pub const INVITATION_SELECT: &str = r#"
+    SELECT i.id, i.email, i.first_name, i.last_name, i.role_ids, i.manager_id,
+           ARRAY(SELECT r.name FROM roles r WHERE r.id = ANY(i.role_ids) ORDER BY r.name) as role_names,
+           i.invited_by, NULLIF(CONCAT(u.first_name, ' ', u.last_name), ' ') as invited_by_name,
+           i.expires_at, i.accepted_at, i.revoked_at, i.user_id, i.created_at
+    FROM user_invitations i
+    LEFT JOIN users u ON u.id = i.invited_by
+"#;
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Invitation {
+    pub id: Uuid,
+    pub email: String,
+    pub first_name: String,
+    pub last_name: String,
+    pub role_ids: Vec<Uuid>,
+    pub manager_id: Option<Uuid>,
+    pub role_names: Vec<String>,
+    pub invited_by: Option<Uuid>,
+    pub invited_by_name: Option<String>,
+    pub expires_at: DateTime<Utc>,
+    pub accepted_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    // Set up front for accounts created before the invitation is accepted
+    pub user_id: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Invitation {
+    pub fn status(&self) -> &'static str {
+        if self.accepted_at.is_some() {
+            "accepted"
+        } else if self.revoked_at.is_some() {
+            "revoked"
+        } else if self.expires_at <= Utc::now() {
+            "expired"
+        } else {
+            "pending"
+        }
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.status() == "pending"
+    }
+}
@@ -0,0 +1,53 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+pub const JOB_STATUSES: &[&str] = &["queued", "running", "succeeded", "failed", "cancelled"];
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Job {
+    pub id: Uuid,
+    pub kind: String,
+    pub description: String,
+    pub payload: serde_json::Value,
+    pub status: String,
+    pub progress_done: i32,
+    pub progress_total: Option<i32>,
+    pub retries: i32,
+    pub last_error: Option<String>,
+    pub run_after: DateTime<Utc>,
+    pub created_by: Option<Uuid>,
+    #[sqlx(default)]
+    pub created_by_name: Option<String>,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Job {
+    pub fn can_retry(&self) -> bool {
+        self.status == "failed" || self.status == "cancelled"
+    }
+
+    pub fn can_cancel(&self) -> bool {
+        self.status == "queued" || self.status == "running"
+    }
+
+    // Whole percent complete, when the job knows how much work it has
+    pub fn percent(&self) -> Option<i32> {
+        self.progress_total
+            .filter(|total| *total > 0)
+            .map(|total| (i64::from(self.progress_done) * 100 / i64::from(total)).min(100) as i32)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct JobLog {
+    pub id: Uuid,
+    pub job_id: Uuid,
+    pub level: String,
+    pub message: String,
+    pub created_at: DateTime<Utc>,
+}
@@ -0,0 +1,85 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+// Statuses a lead can be set to; "converted" is only reached by converting it
+pub const LEAD_STATUSES: &[&str] = &["new", "contacted", "qualified", "disqualified"];
+
+// Where a lead came from: (stored value, label). Customers created by the
+// web form, the API and imports carry "web_form", "api" and "import".
+pub const LEAD_SOURCES: &[(&str, &str)] = &[
+    ("referral", "Referral"),
+    ("event", "Event"),
+    ("cold_outreach", "Cold outreach"),
+    ("partner", "Partner"),
+    ("web_form", "Web form"),
+    ("other", "Other"),
+];
+
+pub fn lead_source_label(source: &str) -> &str {
+    match source {
+        "api" => "API",
+        "import" => "Import",
+        _ => LEAD_SOURCES
+            .iter()
+            .find(|(value, _)| *value == source)
+            .map(|(_, label)| *label)
+            .unwrap_or(source),
+    }
+}
+
+// Selects a Lead; alias the lead `l`
+pub const LEAD_SELECT: &str = r#"
+    SELECT l.*, NULLIF(concat_ws(' ', u.first_name, u.last_name), '') as owner_name,
+           cp.name as campaign_name, c.company_name as converted_customer_name
+    FROM leads l
+    LEFT JOIN users u ON u.id = COALESCE(l.assigned_to, l.created_by)
+    LEFT JOIN campaigns cp ON cp.id = l.campaign_id
+    LEFT JOIN customers c ON c.id = l.converted_customer_id
+"#;
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Lead {
+    pub id: Uuid,
+    pub first_name: String,
+    pub last_name: String,
+    pub company_name: Option<String>,
+    pub title: Option<String>,
+    pub email: Option<String>,
+    pub phone: Option<String>,
+    pub lead_source: Option<String>,
+    pub campaign_id: Option<Uuid>,
+    pub status: String,
+    pub notes: Option<String>,
+    pub assigned_to: Option<Uuid>,
+    pub created_by: Option<Uuid>,
+    pub converted_customer_id: Option<Uuid>,
+    pub converted_deal_id: Option<Uuid>,
+    pub converted_at: Option<DateTime<Utc>>,
+    pub converted_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    pub owner_name: Option<String>,
+    pub campaign_name: Option<String>,
+    pub converted_customer_name: Option<String>,
+}
+
+impl Lead {
+    pub fn full_name(&self) -> String {
+        format!("{} {}", self.first_name, self.last_name)
+    }
+
+    pub fn is_converted(&self) -> bool {
+        self.status == "converted"
+    }
+
+    pub fn source_label(&self) -> &str {
+        self.lead_source.as_deref().map(lead_source_label).unwrap_or("")
+    }
+
+    // The company the converted customer is named after
+    pub fn suggested_company(&self) -> String {
+        self.company_name.clone().unwrap_or_else(|| self.full_name())
+    }
+}
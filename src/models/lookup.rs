@@ -0,0 +1,23 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+// Dropdowns whose options are managed at /team/lookups: (kind, label)
+pub const LOOKUP_KINDS: &[(&str, &str)] = &[
+    ("industry", "Industries"),
+    ("country", "Countries"),
+    ("currency", "Currencies"),
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct LookupValue {
+    pub id: Uuid,
+    pub kind: String,
+    pub value: String,
+    pub label: String,
+    pub sort_order: i32,
+    pub is_active: bool,
+    pub created_at: Option<DateTime<Utc>>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
@@ -0,0 +1,91 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+
+use super::lead_source_label;
+
+// Merge fields available in mass email subjects and bodies
+pub const MERGE_FIELDS: [&str; 3] = ["first_name", "last_name", "company_name"];
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct CustomerSegment {
+    pub id: Uuid,
+    pub name: String,
+    pub description: Option<String>,
+    pub status: Option<String>,
+    pub industry: Option<String>,
+    pub campaign_id: Option<Uuid>,
+    pub lead_source: Option<String>,
+    pub primary_contacts_only: bool,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SegmentDisplay {
+    pub id: Uuid,
+    pub name: String,
+    pub description: String,
+    pub criteria: String,
+    pub recipient_count: i64,
+}
+
+impl CustomerSegment {
+    // Human-readable summary of the filters, e.g. "status: active, industry: Retail"
+    pub fn criteria(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(status) = &self.status {
+            parts.push(format!("status: {}", status));
+        }
+        if let Some(industry) = &self.industry {
+            parts.push(format!("industry: {}", industry));
+        }
+        if self.campaign_id.is_some() {
+            parts.push("campaign".to_string());
+        }
+        if let Some(lead_source) = &self.lead_source {
+            parts.push(format!("lead source: {}", lead_source_label(lead_source)));
+        }
+        if self.primary_contacts_only {
+            parts.push("primary contacts only".to_string());
+        }
+        if parts.is_empty() {
+            "All customers".to_string()
+        } else {
+            parts.join(", ")
+        }
+    }
+}
+
+// A mass email send with its delivery counts
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct MassEmailReport {
+    pub id: Uuid,
+    pub subject: String,
+    pub status: String,
+    pub segment_name: Option<String>,
+    pub per_minute_limit: i32,
+    pub created_at: DateTime<Utc>,
+    pub completed_at: Option<DateTime<Utc>>,
+    pub total: i64,
+    pub suppressed: i64,
+    pub pending: i64,
+    pub queued: i64,
+    pub delivered: i64,
+    pub failed: i64,
+    pub bounced: i64,
+    pub opened: i64,
+    pub clicked: i64,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct MassEmailRecipient {
+    pub email_address: String,
+    pub contact_name: Option<String>,
+    pub company_name: Option<String>,
+    pub status: String,
+    pub delivery_status: Option<String>,
+    pub queued_at: Option<DateTime<Utc>>,
+}
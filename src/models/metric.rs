@@ -0,0 +1,51 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+use chrono::{DateTime, NaiveDate, Utc};
+use rust_decimal::Decimal;
+
+// Snapshot metrics an alert rule can watch, with their display names
+pub const ALERT_METRICS: [(&str, &str); 7] = [
+    ("activities_logged", "Activities logged per day"),
+    ("expense_amount", "Expenses submitted per day"),
+    ("pipeline_value", "Pipeline value (all stages)"),
+    ("pipeline_deals", "Deals (all stages)"),
+    ("customers", "Customers"),
+    ("headcount", "Active users"),
+    ("stock_value", "Stock value"),
+];
+
+pub const ALERT_DIRECTIONS: [&str; 3] = ["drop", "spike", "either"];
+
+pub fn metric_label(metric: &str) -> String {
+    ALERT_METRICS
+        .iter()
+        .find(|(key, _)| *key == metric)
+        .map(|(_, label)| label.to_string())
+        .unwrap_or_else(|| metric.to_string())
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct MetricAlertRule {
+    pub id: Uuid,
+    pub metric: String,
+    pub direction: String,
+    pub threshold_percent: Decimal,
+    pub trailing_days: i32,
+    pub min_baseline: Decimal,
+    pub is_active: bool,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+// A rule firing, joined with the rule it belongs to
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct MetricAlert {
+    pub id: Uuid,
+    pub metric: String,
+    pub snapshot_date: NaiveDate,
+    pub value: Decimal,
+    pub baseline: Decimal,
+    pub deviation_percent: Decimal,
+}
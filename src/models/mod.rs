@@ -3,20 +3,72 @@ pub mod crm;
 pub mod rbac;
 pub mod expense;
 pub mod inventory; // Add this line
+pub mod email;
+pub mod campaign;
+pub mod mass_email;
+pub mod metric;
+pub mod api;
+pub mod settings;
+pub mod job;
+pub mod import;
+pub mod lookup;
+pub mod invitation;
+pub mod blanket_order;
+pub mod number_sequence;
+pub mod document_template;
+pub mod exchange_rate;
+pub mod project;
+pub mod team;
+pub mod sequence;
+pub mod lead;
+pub mod status;
+pub mod tag;
+pub mod custom_field;
+pub mod note;
 
 // Re-export only the types we actually use
-pub use user::{User, CreateUser};
+pub use user::{User, LoginEvent, SecurityEvent, Session};
 pub use crm::{
     Customer, CustomerTemplate, CustomerDisplay,
     Contact, ContactDisplay,
-    Deal, DealDisplay,
-    Activity, ActivityDisplay
+    Deal, DealDisplay, DealStageChange, DealStageSetting, DealLineItem, DiscountThreshold, CustomerPartNumber, PricingAgreement,
+    Activity, ActivityDisplay, ActivityOutcome, ActivityType, RecordShare, CUSTOMER_STATUSES, DEAL_STAGES
 };
 pub use rbac::{
-    Role, RoleDisplay, UserWithRoles,
-    Permission, get_all_permissions
+    Role, RoleDisplay, UserWithRoles, AuditLogDisplay,
+    Permission, FieldAccess, get_all_permissions
 };
-pub use expense::{Expense, ExpenseCategory, ExpenseDisplay};
+pub use expense::{CostCenter, Expense, ExpenseCategory, ExpenseDisplay};
 pub use inventory::{ // Add these lines
-    Warehouse, InventoryItem, StockLevel, StockMovement, Notification
-};
\ No newline at end of file
+    Warehouse, InventoryItem, StockLevel, StockMovement, Notification,
+    HeldStock, ItemOptionSet, ItemStock, PickListLine, VariantOption, WarehouseLocation, WarehouseStock, WarehouseSummary,
+};
+pub use email::{EmailEvent, OutboxEmail, TrackedEmail, DIGEST_FREQUENCIES};
+pub use campaign::{Campaign, CampaignDisplay, CampaignRoi, UtmLeadSummary, CAMPAIGN_CHANNELS};
+pub use mass_email::{CustomerSegment, SegmentDisplay, MassEmailReport, MassEmailRecipient, MERGE_FIELDS};
+pub use metric::{metric_label, MetricAlert, MetricAlertRule, ALERT_DIRECTIONS, ALERT_METRICS};
+pub use api::{ApiCallLog, ApiKey, Webhook, WebhookDelivery, API_KEY_SELECT};
+pub use settings::{SecuritySettings, SECURITY_SETTINGS_SELECT};
+pub use job::{Job, JobLog, JOB_STATUSES};
+pub use import::{Import, ImportError, IMPORT_SELECT};
+pub use lookup::{LookupValue, LOOKUP_KINDS};
+pub use invitation::{Invitation, INVITATION_SELECT};
+pub use blanket_order::{
+    Backorder, BlanketOrder, BlanketOrderLine, BlanketOrderRelease, ItemCommitment,
+    BLANKET_ORDER_SELECT, BLANKET_ORDER_STATUSES,
+};
+pub use number_sequence::NumberSequence;
+pub use document_template::{DocumentTemplate, DOCUMENT_COLUMNS, LOGO_POSITIONS};
+pub use exchange_rate::ExchangeRate;
+pub use project::{
+    CostRate, Project, ProjectBilling, ProjectSummary, TimeEntryDisplay, WipLine, WipTotals, PROJECT_STATUSES,
+};
+pub use team::{Team, TeamMember};
+pub use sequence::{Sequence, SequenceEnrollment, SequenceStep, SEQUENCE_STEP_TYPES};
+pub use lead::{lead_source_label, Lead, LEAD_SELECT, LEAD_SOURCES, LEAD_STATUSES};
+pub use status::{
+    status_label, status_severity, StatusIncident, StatusMaintenance, INCIDENT_IMPACTS, STATUS_COMPONENTS,
+};
+pub use tag::{Tag, TagUsage, TAG_COLORS};
+pub use custom_field::{CustomField, CUSTOM_FIELD_RECORDS, CUSTOM_FIELD_TYPES};
+pub use note::Note;
@@ -0,0 +1,33 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+use crate::utils::markdown::render_markdown;
+
+// A note in a customer's or deal's thread
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Note {
+    pub id: Uuid,
+    pub record_type: String,
+    pub record_id: Uuid,
+    // Markdown as written
+    pub body: String,
+    pub author_id: Option<Uuid>,
+    pub edited_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    // Empty once the author's account is deleted
+    #[sqlx(default)]
+    pub author_name: Option<String>,
+}
+
+impl Note {
+    pub fn body_html(&self) -> String {
+        render_markdown(&self.body)
+    }
+
+    pub fn author(&self) -> &str {
+        self.author_name.as_deref().unwrap_or("Former user")
+    }
+}
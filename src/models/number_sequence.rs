@@ -0,0 +1,55 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+// Documents that take a number from a sequence: (document_type, label)
+pub const NUMBERED_DOCUMENTS: &[(&str, &str)] = &[
+    ("quote", "Quotes"),
+    ("invoice", "Invoices"),
+    ("sales_order", "Sales Orders"),
+    ("purchase_order", "Purchase Orders"),
+    ("rma", "Returns (RMAs)"),
+];
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct NumberSequence {
+    pub document_type: String,
+    pub prefix: String,
+    pub padding: i32,
+    pub reset_yearly: bool,
+    pub next_number: i64,
+    pub period_year: Option<i32>,
+    pub updated_by: Option<Uuid>,
+    pub updated_at: Option<DateTime<Utc>>,
+}
+
+impl NumberSequence {
+    pub fn label(&self) -> &'static str {
+        NUMBERED_DOCUMENTS
+            .iter()
+            .find(|(key, _)| *key == self.document_type)
+            .map_or("Documents", |(_, label)| *label)
+    }
+
+    // The number `n` as printed: prefix, the year when counting resets
+    // yearly, then the zero-padded count. INV- + 2026 + 42 -> INV-2026-00042
+    pub fn format(&self, year: i32, n: i64) -> String {
+        let width = self.padding.max(1) as usize;
+        if self.reset_yearly {
+            format!("{}{}-{:0width$}", self.prefix, year, n, width = width)
+        } else {
+            format!("{}{:0width$}", self.prefix, n, width = width)
+        }
+    }
+
+    // What the next document will get if it's issued in `year`
+    pub fn upcoming(&self, year: i32) -> String {
+        let n = if self.reset_yearly && self.period_year.is_some_and(|last| last != year) {
+            1
+        } else {
+            self.next_number
+        };
+        self.format(year, n)
+    }
+}
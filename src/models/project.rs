@@ -0,0 +1,215 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+pub const PROJECT_STATUSES: &[&str] = &["active", "closed"];
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct Project {
+    pub id: Uuid,
+    pub name: String,
+    pub customer_id: Uuid,
+    // The won deal the project delivers; its value is the project's revenue
+    pub deal_id: Option<Uuid>,
+    pub status: String,
+    pub description: Option<String>,
+    // What finishing it should cost, for how far along it is
+    pub estimated_cost: Option<Decimal>,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+// A project with what has gone into it and what it brought in
+#[derive(Debug, Serialize, FromRow)]
+pub struct ProjectSummary {
+    pub id: Uuid,
+    pub name: String,
+    pub customer_id: Uuid,
+    pub customer_name: String,
+    pub deal_id: Option<Uuid>,
+    pub deal_title: Option<String>,
+    pub deal_stage: Option<String>,
+    pub status: String,
+    pub description: Option<String>,
+    pub estimated_cost: Option<Decimal>,
+    pub hours: Decimal,
+    pub time_cost: Decimal,
+    // Entries logged by someone without a cost rate, so missing from time_cost
+    pub unpriced_entries: i64,
+    pub expense_cost: Decimal,
+    pub pending_expenses: Decimal,
+    // Base currency value of the deal once it's won
+    pub revenue: Decimal,
+}
+
+impl ProjectSummary {
+    pub fn total_cost(&self) -> Decimal {
+        self.time_cost + self.expense_cost
+    }
+
+    pub fn profit(&self) -> Decimal {
+        self.revenue - self.total_cost()
+    }
+
+    pub fn is_loss(&self) -> bool {
+        self.profit() < Decimal::ZERO
+    }
+
+    // Profit as a share of revenue, when there is revenue
+    pub fn margin(&self) -> Option<Decimal> {
+        if self.revenue.is_zero() {
+            return None;
+        }
+        Some((self.profit() * Decimal::from(100) / self.revenue).round_dp(1))
+    }
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct TimeEntryDisplay {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub user_name: String,
+    pub work_date: NaiveDate,
+    pub hours: Decimal,
+    pub description: Option<String>,
+    pub hourly_cost: Option<Decimal>,
+}
+
+impl TimeEntryDisplay {
+    pub fn cost(&self) -> Option<Decimal> {
+        self.hourly_cost.map(|rate| (rate * self.hours).round_dp(2))
+    }
+}
+
+// An invoice raised for the project outside the app
+#[derive(Debug, Serialize, FromRow)]
+pub struct ProjectBilling {
+    pub id: Uuid,
+    pub billed_on: NaiveDate,
+    // Time and expenses up to this day are covered by it
+    pub billed_through: NaiveDate,
+    pub amount: Decimal,
+    pub reference: Option<String>,
+    pub created_by_name: Option<String>,
+}
+
+// A project's work in progress as of a day. Costs and billings after that
+// day are left out.
+#[derive(Debug, Serialize, FromRow)]
+pub struct WipLine {
+    pub id: Uuid,
+    pub name: String,
+    pub customer_name: String,
+    pub status: String,
+    // The won deal's value in the base currency
+    pub contract_value: Decimal,
+    pub estimated_cost: Option<Decimal>,
+    pub hours: Decimal,
+    pub time_cost: Decimal,
+    pub expense_cost: Decimal,
+    // Costs dated after the last billed_through
+    pub unbilled_hours: Decimal,
+    pub unbilled_time_cost: Decimal,
+    pub unbilled_expenses: Decimal,
+    pub billed: Decimal,
+    pub billed_through: Option<NaiveDate>,
+}
+
+impl WipLine {
+    pub fn cost_to_date(&self) -> Decimal {
+        self.time_cost + self.expense_cost
+    }
+
+    pub fn unbilled_cost(&self) -> Decimal {
+        self.unbilled_time_cost + self.unbilled_expenses
+    }
+
+    // Share of the estimated cost spent so far, capped at all of it. Closed
+    // projects are done whatever the estimate said.
+    pub fn completion(&self) -> Option<Decimal> {
+        if self.status == "closed" {
+            return Some(Decimal::ONE);
+        }
+        match self.estimated_cost {
+            Some(estimate) if estimate > Decimal::ZERO => Some((self.cost_to_date() / estimate).min(Decimal::ONE)),
+            _ => None,
+        }
+    }
+
+    pub fn percent_complete(&self) -> Option<Decimal> {
+        self.completion().map(|share| (share * Decimal::from(100)).round_dp(1))
+    }
+
+    // Earned by percentage of completion. Without an estimate there's no way
+    // to tell, so what has been billed is taken as earned.
+    pub fn recognized_revenue(&self) -> Decimal {
+        match self.completion() {
+            Some(share) => (self.contract_value * share).round_dp(2),
+            None => self.billed,
+        }
+    }
+
+    // Earned but not billed yet
+    pub fn unbilled_revenue(&self) -> Decimal {
+        (self.recognized_revenue() - self.billed).max(Decimal::new(0, 2))
+    }
+
+    // Billed ahead of the work
+    pub fn deferred_revenue(&self) -> Decimal {
+        (self.billed - self.recognized_revenue()).max(Decimal::new(0, 2))
+    }
+
+    pub fn margin(&self) -> Decimal {
+        self.recognized_revenue() - self.cost_to_date()
+    }
+
+    pub fn is_loss(&self) -> bool {
+        self.margin() < Decimal::ZERO
+    }
+}
+
+// Column totals of the WIP report
+#[derive(Debug, Default)]
+pub struct WipTotals {
+    pub contract_value: Decimal,
+    pub cost_to_date: Decimal,
+    pub unbilled_cost: Decimal,
+    pub recognized_revenue: Decimal,
+    pub billed: Decimal,
+    pub unbilled_revenue: Decimal,
+    pub deferred_revenue: Decimal,
+    pub margin: Decimal,
+}
+
+impl WipTotals {
+    pub fn of(lines: &[WipLine]) -> Self {
+        lines.iter().fold(Self::default(), |totals, line| Self {
+            contract_value: totals.contract_value + line.contract_value,
+            cost_to_date: totals.cost_to_date + line.cost_to_date(),
+            unbilled_cost: totals.unbilled_cost + line.unbilled_cost(),
+            recognized_revenue: totals.recognized_revenue + line.recognized_revenue(),
+            billed: totals.billed + line.billed,
+            unbilled_revenue: totals.unbilled_revenue + line.unbilled_revenue(),
+            deferred_revenue: totals.deferred_revenue + line.deferred_revenue(),
+            margin: totals.margin + line.margin(),
+        })
+    }
+}
+
+// Someone's hourly cost, as set by an admin
+#[derive(Debug, Serialize, FromRow)]
+pub struct CostRate {
+    pub user_id: Uuid,
+    pub name: String,
+    pub email: String,
+    pub hourly_cost: Option<Decimal>,
+}
+
+impl CostRate {
+    pub fn rate_input(&self) -> String {
+        self.hourly_cost.map(|rate| rate.normalize().to_string()).unwrap_or_default()
+    }
+}
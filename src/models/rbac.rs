@@ -13,6 +13,12 @@ pub struct Role {
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     pub created_by: Option<Uuid>,
+    pub dashboard: Option<String>,
+    pub is_read_only: bool,
+    // Everything the parent grants, this role grants too
+    pub parent_role_id: Option<Uuid>,
+    // Limits the inventory access it grants to this warehouse's stock
+    pub warehouse_id: Option<Uuid>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -25,6 +31,13 @@ pub struct RoleDisplay {
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     pub permission_count: usize,
+    pub dashboard: String,
+    pub is_read_only: bool,
+    pub parent_role_id: Option<Uuid>,
+    pub warehouse_id: Option<Uuid>,
+    // Filled in by handlers that list roles
+    pub parent_name: Option<String>,
+    pub warehouse_name: Option<String>,
 }
 
 impl From<Role> for RoleDisplay {
@@ -39,6 +52,12 @@ impl From<Role> for RoleDisplay {
             is_active: role.is_active,
             created_at: role.created_at,
             updated_at: role.updated_at,
+            dashboard: role.dashboard.unwrap_or_default(),
+            is_read_only: role.is_read_only,
+            parent_role_id: role.parent_role_id,
+            warehouse_id: role.warehouse_id,
+            parent_name: None,
+            warehouse_name: None,
         }
     }
 }
@@ -61,6 +80,7 @@ pub struct UserWithRoles {
     pub is_locked: bool,
     pub last_login: Option<DateTime<Utc>>,
     pub locked_at: Option<DateTime<Utc>>,
+    pub manager_id: Option<Uuid>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
     pub roles: Vec<RoleDisplay>,
@@ -82,15 +102,21 @@ pub struct AuditLog {
     pub created_at: DateTime<Utc>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+// An audit entry with the names of who made the change
+#[derive(Debug, Serialize, FromRow)]
 pub struct AuditLogDisplay {
     pub id: Uuid,
-    pub user_name: String,
+    pub user_id: Option<Uuid>,
+    pub user_name: Option<String>,
+    // Set when an admin made the change while impersonating user_name
+    pub impersonator_name: Option<String>,
     pub action: String,
     pub resource_type: String,
     pub resource_id: Option<Uuid>,
-    pub changes: String,
-    pub ip_address: String,
+    pub old_values: Option<sqlx::types::Json<serde_json::Value>>,
+    pub new_values: Option<sqlx::types::Json<serde_json::Value>>,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
     pub created_at: DateTime<Utc>,
 }
 
@@ -101,6 +127,23 @@ pub struct CreateRole {
     pub permissions: Vec<String>,
 }
 
+// Which restricted fields a viewer may see. Display models take this when
+// they're built so pages, emails and API responses hide the same things.
+#[derive(Debug, Clone, Copy)]
+pub struct FieldAccess {
+    pub viewer_id: Uuid,
+    pub finance: bool,
+}
+
+impl FieldAccess {
+    pub fn new(viewer_id: Uuid, permissions: &[String]) -> Self {
+        Self {
+            viewer_id,
+            finance: permissions.iter().any(|p| p == "finance:read"),
+        }
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Permission {
     pub key: String,
@@ -130,6 +173,46 @@ pub fn get_all_permissions() -> Vec<Permission> {
             description: "Delete customer records".to_string(),
             category: "Customer Management".to_string(),
         },
+        Permission {
+            key: "crm:all_records".to_string(),
+            name: "Access All Records".to_string(),
+            description: "Open and edit any customer, deal or activity, not just those owned by the user, their reports, or shared with them".to_string(),
+            category: "Customer Management".to_string(),
+        },
+        Permission {
+            key: "crm:read_all".to_string(),
+            name: "View All Records".to_string(),
+            description: "See every customer, deal and activity, but only edit those owned by the user, their reports, or shared with them".to_string(),
+            category: "Customer Management".to_string(),
+        },
+        
+        // Marketing
+        Permission {
+            key: "campaigns:read".to_string(),
+            name: "View Campaigns".to_string(),
+            description: "View marketing campaigns and the ROI report".to_string(),
+            category: "Marketing".to_string(),
+        },
+        Permission {
+            key: "campaigns:write".to_string(),
+            name: "Manage Campaigns".to_string(),
+            description: "Create and edit marketing campaigns".to_string(),
+            category: "Marketing".to_string(),
+        },
+        
+        // Reporting
+        Permission {
+            key: "data:export".to_string(),
+            name: "Export Data".to_string(),
+            description: "Download spreadsheet exports of customers, deals and reports".to_string(),
+            category: "Reporting".to_string(),
+        },
+        Permission {
+            key: "alerts:manage".to_string(),
+            name: "Manage Metric Alerts".to_string(),
+            description: "Configure anomaly alert rules and receive their notifications".to_string(),
+            category: "Reporting".to_string(),
+        },
         
         // Inventory Management
         Permission {
@@ -176,6 +259,12 @@ pub fn get_all_permissions() -> Vec<Permission> {
             description: "Create, edit, and assign roles and permissions".to_string(),
             category: "Team Management".to_string(),
         },
+        Permission {
+            key: "audit:read".to_string(),
+            name: "View Audit Log".to_string(),
+            description: "Browse the audit log and the changes recorded in it".to_string(),
+            category: "Team Management".to_string(),
+        },
         
         // Expense Tracking
         Permission {
@@ -196,7 +285,47 @@ pub fn get_all_permissions() -> Vec<Permission> {
             description: "Delete expense records".to_string(),
             category: "Expense Tracking".to_string(),
         },
+        Permission {
+            key: "expenses:approve".to_string(),
+            name: "Approve Expenses".to_string(),
+            description: "Approve or deny anyone's expenses".to_string(),
+            category: "Expense Tracking".to_string(),
+        },
+        Permission {
+            key: "expenses:approve_team".to_string(),
+            name: "Approve Team Expenses".to_string(),
+            description: "Approve or deny expenses from members of the teams they manage".to_string(),
+            category: "Expense Tracking".to_string(),
+        },
+        
+        // Projects
+        Permission {
+            key: "projects:read".to_string(),
+            name: "View Projects".to_string(),
+            description: "View projects with their time and expenses".to_string(),
+            category: "Projects".to_string(),
+        },
+        Permission {
+            key: "projects:write".to_string(),
+            name: "Manage Projects".to_string(),
+            description: "Create and edit projects and log time against them".to_string(),
+            category: "Projects".to_string(),
+        },
         
+        // Finance
+        Permission {
+            key: "finance:read".to_string(),
+            name: "View Financial Fields".to_string(),
+            description: "See deal values, purchase and cost prices, and other people's expense amounts".to_string(),
+            category: "Finance".to_string(),
+        },
+        Permission {
+            key: "discounts:approve".to_string(),
+            name: "Approve Discounts".to_string(),
+            description: "Approve deal discounts above the configured thresholds, including other teams'".to_string(),
+            category: "Finance".to_string(),
+        },
+
         // Shipping Tracking
         Permission {
             key: "shipping:read".to_string(),
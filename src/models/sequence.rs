@@ -0,0 +1,66 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+pub const SEQUENCE_STEP_TYPES: &[&str] = &["email", "task"];
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Sequence {
+    pub id: Uuid,
+    pub name: String,
+    pub description: Option<String>,
+    pub is_active: bool,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    // Filled in by list queries only
+    #[sqlx(default)]
+    pub step_count: i64,
+    #[sqlx(default)]
+    pub active_enrollments: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct SequenceStep {
+    pub id: Uuid,
+    pub sequence_id: Uuid,
+    pub day: i32,
+    pub step_type: String,
+    pub activity_type: Option<String>,
+    pub subject: String,
+    pub body: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl SequenceStep {
+    pub fn is_email(&self) -> bool {
+        self.step_type == "email"
+    }
+}
+
+// An enrollment as listed on a contact or deal, with its progress
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct SequenceEnrollment {
+    pub id: Uuid,
+    pub sequence_id: Uuid,
+    pub sequence_name: String,
+    pub customer_id: Uuid,
+    pub contact_id: Option<Uuid>,
+    pub deal_id: Option<Uuid>,
+    pub status: String,
+    pub stop_reason: Option<String>,
+    pub enrolled_by_name: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: Option<DateTime<Utc>>,
+    pub steps_run: i64,
+    pub step_count: i64,
+    // Day of the next step still to run, counting the enrollment day as 1
+    pub next_day: Option<i32>,
+}
+
+impl SequenceEnrollment {
+    pub fn is_active(&self) -> bool {
+        self.status == "active"
+    }
+}
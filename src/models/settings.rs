@@ -0,0 +1,31 @@
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+use chrono::{DateTime, Utc};
+
+// Select list for security settings; CIDR values come back as text
+pub const SECURITY_SETTINGS_SELECT: &str = r#"
+    SELECT ip_allowlist_enabled, ip_allowlist::text[] as ip_allowlist, exempt_api_keys,
+           session_max_hours, session_idle_minutes, max_sessions_per_user, lockout_threshold, lockout_minutes,
+           password_min_length, password_required_classes, password_block_breached, password_history_count,
+           updated_by, updated_at
+    FROM security_settings
+"#;
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct SecuritySettings {
+    pub ip_allowlist_enabled: bool,
+    pub ip_allowlist: Vec<String>,
+    pub exempt_api_keys: bool,
+    pub session_max_hours: i32,
+    pub session_idle_minutes: Option<i32>,
+    pub max_sessions_per_user: Option<i32>,
+    pub lockout_threshold: Option<i32>,
+    pub lockout_minutes: Option<i32>,
+    pub password_min_length: i32,
+    pub password_required_classes: i32,
+    pub password_block_breached: bool,
+    pub password_history_count: Option<i32>,
+    pub updated_by: Option<Uuid>,
+    pub updated_at: DateTime<Utc>,
+}
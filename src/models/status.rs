@@ -0,0 +1,116 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+// What the status page reports on: (id, name). Incidents and maintenance
+// can also name "all".
+pub const STATUS_COMPONENTS: &[(&str, &str)] = &[("app", "Web app"), ("api", "API")];
+
+// Incident impacts, least to most severe: (stored value, label)
+pub const INCIDENT_IMPACTS: &[(&str, &str)] = &[
+    ("degraded", "Degraded performance"),
+    ("partial_outage", "Partial outage"),
+    ("major_outage", "Major outage"),
+];
+
+pub fn component_label(component: &str) -> &str {
+    match component {
+        "all" => "Web app and API",
+        _ => STATUS_COMPONENTS
+            .iter()
+            .find(|(id, _)| *id == component)
+            .map(|(_, name)| *name)
+            .unwrap_or(component),
+    }
+}
+
+pub fn impact_label(impact: &str) -> &str {
+    INCIDENT_IMPACTS
+        .iter()
+        .find(|(value, _)| *value == impact)
+        .map(|(_, label)| *label)
+        .unwrap_or(impact)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct StatusIncident {
+    pub id: Uuid,
+    pub title: String,
+    pub message: Option<String>,
+    pub component: String,
+    pub impact: String,
+    pub started_at: DateTime<Utc>,
+    pub resolved_at: Option<DateTime<Utc>>,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl StatusIncident {
+    pub fn is_resolved(&self) -> bool {
+        self.resolved_at.is_some()
+    }
+
+    pub fn affects(&self, component: &str) -> bool {
+        self.component == "all" || self.component == component
+    }
+
+    pub fn component_label(&self) -> &str {
+        component_label(&self.component)
+    }
+
+    pub fn impact_label(&self) -> &str {
+        impact_label(&self.impact)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct StatusMaintenance {
+    pub id: Uuid,
+    pub title: String,
+    pub description: Option<String>,
+    pub component: String,
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: DateTime<Utc>,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl StatusMaintenance {
+    pub fn affects(&self, component: &str) -> bool {
+        self.component == "all" || self.component == component
+    }
+
+    pub fn is_in_progress(&self, now: DateTime<Utc>) -> bool {
+        self.starts_at <= now && now < self.ends_at
+    }
+
+    pub fn component_label(&self) -> &str {
+        component_label(&self.component)
+    }
+}
+
+// Overall states, best to worst: (value, label). A component shows the worst
+// that applies to it and the page the worst of its components.
+pub const STATUS_LEVELS: &[(&str, &str)] = &[
+    ("operational", "Operational"),
+    ("maintenance", "Under maintenance"),
+    ("degraded", "Degraded performance"),
+    ("partial_outage", "Partial outage"),
+    ("major_outage", "Major outage"),
+];
+
+pub fn status_label(status: &str) -> &str {
+    STATUS_LEVELS
+        .iter()
+        .find(|(value, _)| *value == status)
+        .map(|(_, label)| *label)
+        .unwrap_or(status)
+}
+
+// Position in STATUS_LEVELS, for picking the worst
+pub fn status_severity(status: &str) -> usize {
+    STATUS_LEVELS.iter().position(|(value, _)| *value == status).unwrap_or(0)
+}
@@ -0,0 +1,62 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+// Colors a tag can be shown in: (stored value, label)
+pub const TAG_COLORS: &[(&str, &str)] = &[
+    ("gray", "Gray"),
+    ("red", "Red"),
+    ("yellow", "Yellow"),
+    ("green", "Green"),
+    ("blue", "Blue"),
+    ("indigo", "Indigo"),
+    ("purple", "Purple"),
+    ("pink", "Pink"),
+];
+
+// Chip classes for a color, spelled out so Tailwind keeps them
+pub fn tag_chip_class(color: &str) -> &'static str {
+    match color {
+        "red" => "bg-red-100 text-red-800",
+        "yellow" => "bg-yellow-100 text-yellow-800",
+        "green" => "bg-green-100 text-green-800",
+        "blue" => "bg-blue-100 text-blue-800",
+        "indigo" => "bg-indigo-100 text-indigo-800",
+        "purple" => "bg-purple-100 text-purple-800",
+        "pink" => "bg-pink-100 text-pink-800",
+        _ => "bg-gray-100 text-gray-800",
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Tag {
+    pub id: Uuid,
+    pub name: String,
+    pub color: String,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl Tag {
+    pub fn chip_class(&self) -> &'static str {
+        tag_chip_class(&self.color)
+    }
+}
+
+// A tag on the management page, with how much it's used
+#[derive(Debug, Clone, Serialize, FromRow)]
+pub struct TagUsage {
+    #[sqlx(flatten)]
+    pub tag: Tag,
+    pub customer_count: i64,
+    pub contact_count: i64,
+    pub deal_count: i64,
+}
+
+impl TagUsage {
+    pub fn total(&self) -> i64 {
+        self.customer_count + self.contact_count + self.deal_count
+    }
+}
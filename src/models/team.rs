@@ -0,0 +1,29 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct Team {
+    pub id: Uuid,
+    pub name: String,
+    pub description: Option<String>,
+    pub is_active: bool,
+    pub created_by: Option<Uuid>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    // Filled in by list queries only
+    #[sqlx(default)]
+    pub member_count: i64,
+    #[sqlx(default)]
+    pub managers: Option<String>,
+}
+
+#[derive(Debug, Clone, FromRow)]
+pub struct TeamMember {
+    pub user_id: Uuid,
+    pub name: String,
+    pub email: String,
+    pub is_active: bool,
+    pub is_manager: bool,
+}
@@ -15,18 +15,11 @@ pub struct User {
     pub last_login: Option<DateTime<Utc>>,
     pub locked_at: Option<DateTime<Utc>>,
     pub locked_by: Option<Uuid>,
+    pub manager_id: Option<Uuid>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-pub struct CreateUser {
-    pub email: String,
-    pub password: String,
-    pub first_name: String,
-    pub last_name: String,
-}
-
 #[derive(Debug, Serialize, Deserialize)]
 pub struct LoginRequest {
     pub email: String,
@@ -56,4 +49,69 @@ impl From<User> for UserResponse {
             last_login: user.last_login,
         }
     }
-}
\ No newline at end of file
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct SecurityEvent {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub event_type: String,
+    pub device_id: Option<String>,
+    pub new_device: bool,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub detail: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct Session {
+    pub id: Uuid,
+    pub user_id: Uuid,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub created_at: Option<DateTime<Utc>>,
+    pub last_seen_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+// One sign-in attempt from the login history
+#[derive(Debug, Serialize, Deserialize, FromRow)]
+pub struct LoginEvent {
+    pub id: Uuid,
+    pub user_id: Option<Uuid>,
+    pub email: String,
+    pub succeeded: bool,
+    pub failure_reason: Option<String>,
+    pub device_id: Option<String>,
+    pub new_device: bool,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl LoginEvent {
+    pub fn label(&self) -> &'static str {
+        match self.failure_reason.as_deref() {
+            None => "Signed in",
+            Some("invalid_credentials") => "Wrong password",
+            Some("locked") => "Refused: account locked",
+            Some("throttled") => "Refused: too many attempts",
+            Some(_) => "Failed sign-in",
+        }
+    }
+}
+
+impl SecurityEvent {
+    pub fn label(&self) -> &'static str {
+        match self.event_type.as_str() {
+            "login" if self.new_device => "Sign-in from a new device",
+            "login" => "Sign-in",
+            "password_changed" => "Password changed",
+            "roles_changed" => "Roles changed",
+            "account_locked" => "Locked after failed sign-ins",
+            "impersonated" => "An administrator signed in as you",
+            _ => "Account activity",
+        }
+    }
+}
@@ -0,0 +1,208 @@
+use std::future::Future;
+use std::sync::{Mutex, OnceLock};
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+
+use crate::{
+    database::Database,
+    services::{
+        api_log, archive, blanket_orders, deal_health, digest, jobs, mailer, metrics, reporting_views, sequences,
+        signing_keys, status, tenancy, webhooks,
+    },
+};
+
+// Start the background jobs. Each job runs on its own fixed interval, once
+// for every tenant.
+pub fn start(db: Database) {
+    spawn_job("email delivery", Duration::from_secs(60), db.clone(), |db| async move {
+        mailer::deliver_pending(&db).await.map(|_| ())
+    });
+
+    spawn_job("webhook delivery", Duration::from_secs(60), db.clone(), |db| async move {
+        webhooks::deliver_pending(&db).await.map(|_| ())
+    });
+
+    // Queued one-off work such as mass email sends; see services::jobs
+    spawn_job("job queue", Duration::from_secs(15), db.clone(), |db| async move {
+        jobs::run_due(&db).await.map(|_| ())
+    });
+
+    // Picks up token signing keys rotated by another server
+    spawn_server_job("signing keys", Duration::from_secs(60), db.clone(), |db| async move {
+        signing_keys::load(&db).await
+    });
+
+    // Uptime on the public status page; the checks belong to no tenant
+    spawn_server_job("status checks", Duration::from_secs(60), db.clone(), |db| async move {
+        status::probe(&db).await
+    });
+
+    // The views hold every tenant's totals, so one refresh covers them all
+    spawn_server_job(
+        "reporting views",
+        Duration::from_secs(reporting_views::REFRESH_MINUTES * 60),
+        db.clone(),
+        |db| async move { reporting_views::refresh(&db).await },
+    );
+
+    // Emails and tasks from follow-up sequences as their days come round
+    spawn_job("follow-up sequences", Duration::from_secs(15 * 60), db.clone(), |db| async move {
+        sequences::run_due(&db).await.map(|_| ())
+    });
+
+    spawn_job("digests", Duration::from_secs(60 * 60), db.clone(), |db| async move {
+        digest::send_due_digests(&db).await.map(|_| ())
+    });
+
+    spawn_job("stalled deals", Duration::from_secs(60 * 60), db.clone(), |db| async move {
+        deal_health::notify_stalled_deal_owners(&db).await.map(|_| ())
+    });
+
+    // Reserves stock for blanket order releases coming due
+    spawn_job("blanket order reservations", Duration::from_secs(60 * 60), db.clone(), |db| async move {
+        blanket_orders::reserve_due(&db).await.map(|_| ())
+    });
+
+    spawn_job("api call log retention", Duration::from_secs(24 * 60 * 60), db.clone(), |db| async move {
+        api_log::purge_expired(&db).await.map(|_| ())
+    });
+
+    spawn_job("archiving", Duration::from_secs(24 * 60 * 60), db.clone(), |db| async move {
+        archive::archive_old(&db).await.map(|_| ())
+    });
+
+    // Checked hourly; a snapshot is only taken on the first run of each day
+    spawn_job("metric snapshots", Duration::from_secs(60 * 60), db, |db| async move {
+        if metrics::capture_daily_snapshot(&db).await? {
+            metrics::check_alerts(&db).await?;
+        }
+        Ok(())
+    });
+}
+
+// The last run of a job on this server, shown on /team/diagnostics. Runs
+// aren't stored, so a restart starts the list afresh.
+#[derive(Clone)]
+pub struct JobRun {
+    pub name: &'static str,
+    pub period: Duration,
+    pub started_at: Option<DateTime<Utc>>,
+    pub finished_at: Option<DateTime<Utc>>,
+    // The last failure of the last run; for tenant jobs, any one tenant's
+    pub error: Option<String>,
+    pub failed_runs: u64,
+}
+
+impl JobRun {
+    // The interval as 15s, 15m, 1h or 1d
+    pub fn every(&self) -> String {
+        let seconds = self.period.as_secs();
+        match seconds {
+            s if s % 86_400 == 0 => format!("{}d", s / 86_400),
+            s if s % 3_600 == 0 => format!("{}h", s / 3_600),
+            s if s % 60 == 0 => format!("{}m", s / 60),
+            s => format!("{}s", s),
+        }
+    }
+
+    // Started and not finished since; a run that never finished counts too
+    pub fn is_running(&self) -> bool {
+        self.started_at.is_some() && self.finished_at < self.started_at
+    }
+}
+
+static RUNS: OnceLock<Mutex<Vec<JobRun>>> = OnceLock::new();
+
+fn runs() -> &'static Mutex<Vec<JobRun>> {
+    RUNS.get_or_init(|| Mutex::new(Vec::new()))
+}
+
+// Every job started on this server, in the order they were started
+pub fn last_runs() -> Vec<JobRun> {
+    runs().lock().unwrap().clone()
+}
+
+fn register(name: &'static str, period: Duration) {
+    runs().lock().unwrap().push(JobRun {
+        name,
+        period,
+        started_at: None,
+        finished_at: None,
+        error: None,
+        failed_runs: 0,
+    });
+}
+
+fn update(name: &'static str, change: impl FnOnce(&mut JobRun)) {
+    if let Some(run) = runs().lock().unwrap().iter_mut().find(|run| run.name == name) {
+        change(run);
+    }
+}
+
+fn run_started(name: &'static str) {
+    update(name, |run| run.started_at = Some(Utc::now()));
+}
+
+fn run_finished(name: &'static str, error: Option<String>) {
+    update(name, |run| {
+        run.finished_at = Some(Utc::now());
+        if error.is_some() {
+            run.failed_runs += 1;
+        }
+        run.error = error;
+    });
+}
+
+fn spawn_job<F, Fut>(name: &'static str, period: Duration, db: Database, job: F)
+where
+    F: Fn(Database) -> Fut + Send + 'static,
+    Fut: Future<Output = Result<(), sqlx::Error>> + Send,
+{
+    register(name, period);
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(period);
+        loop {
+            interval.tick().await;
+            run_started(name);
+            let tenants = match tenancy::all(&db).await {
+                Ok(tenants) => tenants,
+                Err(e) => {
+                    tracing::error!("Scheduled job '{}' couldn't list tenants: {}", name, e);
+                    run_finished(name, Some(format!("Couldn't list tenants: {}", e)));
+                    continue;
+                }
+            };
+            let mut error = None;
+            for tenant in tenants {
+                let slug = tenant.slug.clone();
+                if let Err(e) = tenancy::scope(tenant, job(db.clone())).await {
+                    tracing::error!("Scheduled job '{}' failed for tenant {}: {}", name, slug, e);
+                    error = Some(format!("Tenant {}: {}", slug, e));
+                }
+            }
+            run_finished(name, error);
+        }
+    });
+}
+
+// For work on the server's own tables, run once with no tenant
+fn spawn_server_job<F, Fut>(name: &'static str, period: Duration, db: Database, job: F)
+where
+    F: Fn(Database) -> Fut + Send + 'static,
+    Fut: Future<Output = Result<(), sqlx::Error>> + Send,
+{
+    register(name, period);
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(period);
+        loop {
+            interval.tick().await;
+            run_started(name);
+            let result = job(db.clone()).await;
+            if let Err(e) = &result {
+                tracing::error!("Scheduled job '{}' failed: {}", name, e);
+            }
+            run_finished(name, result.err().map(|e| e.to_string()));
+        }
+    });
+}
@@ -0,0 +1,87 @@
+use crate::{database::Database, models::ActivityType};
+
+// What an admin submits from /crm/activities/types
+pub struct TypeInput<'a> {
+    pub code: &'a str,
+    pub name: &'a str,
+    pub icon: &'a str,
+    pub default_duration_minutes: Option<i32>,
+    pub requires_outcome: bool,
+}
+
+pub async fn list(db: &Database, include_inactive: bool) -> Result<Vec<ActivityType>, sqlx::Error> {
+    sqlx::query_as::<_, ActivityType>(
+        "SELECT * FROM activity_types WHERE is_active OR $1 ORDER BY sort_order, name",
+    )
+    .bind(include_inactive)
+    .fetch_all(db)
+    .await
+}
+
+// The type an activity is stored with, retired or not
+pub async fn get(db: &Database, code: &str) -> Result<Option<ActivityType>, sqlx::Error> {
+    sqlx::query_as::<_, ActivityType>("SELECT * FROM activity_types WHERE code = $1")
+        .bind(code)
+        .fetch_optional(db)
+        .await
+}
+
+// Check a submitted type against the active ones
+pub async fn is_allowed(db: &Database, code: &str) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS(SELECT 1 FROM activity_types WHERE code = $1 AND is_active)",
+    )
+    .bind(code)
+    .fetch_one(db)
+    .await
+}
+
+// Adds a type, or updates the one with the same code and brings it back if
+// it was retired. The code can't change once activities store it.
+pub async fn save(db: &Database, input: &TypeInput<'_>) -> Result<Result<(), String>, sqlx::Error> {
+    let code = input.code.trim().to_lowercase().replace(' ', "_");
+    let name = input.name.trim();
+    if code.is_empty() || name.is_empty() {
+        return Ok(Err("Code and name are required".to_string()));
+    }
+    if code.len() > 50 {
+        return Ok(Err("Code can be at most 50 characters".to_string()));
+    }
+    if input.default_duration_minutes.is_some_and(|minutes| minutes <= 0) {
+        return Ok(Err("Default duration must be a positive number of minutes".to_string()));
+    }
+    let icon = match input.icon.trim() {
+        "" => "📝",
+        icon if icon.chars().count() > 4 => return Ok(Err("Icon must be a single emoji".to_string())),
+        icon => icon,
+    };
+
+    sqlx::query(
+        r#"
+        INSERT INTO activity_types (code, name, icon, default_duration_minutes, requires_outcome, sort_order)
+        VALUES ($1, $2, $3, $4, $5, (SELECT COALESCE(MAX(sort_order), 0) + 1 FROM activity_types))
+        ON CONFLICT (tenant_id, code) DO UPDATE SET
+            name = EXCLUDED.name, icon = EXCLUDED.icon,
+            default_duration_minutes = EXCLUDED.default_duration_minutes,
+            requires_outcome = EXCLUDED.requires_outcome, is_active = true
+        "#,
+    )
+    .bind(&code)
+    .bind(name)
+    .bind(icon)
+    .bind(input.default_duration_minutes)
+    .bind(input.requires_outcome)
+    .execute(db)
+    .await?;
+
+    Ok(Ok(()))
+}
+
+// Types are retired rather than deleted so past activities keep their name and icon
+pub async fn toggle(db: &Database, id: uuid::Uuid) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE activity_types SET is_active = NOT is_active WHERE id = $1")
+        .bind(id)
+        .execute(db)
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,86 @@
+use uuid::Uuid;
+
+use crate::{database::Database, models::ApiCallLog};
+
+// Bytes of each request and response body kept in the log
+const PAYLOAD_LIMIT: usize = 4096;
+
+// Calls older than this are purged by the scheduler
+const RETENTION_DAYS: i32 = 30;
+
+pub struct ApiCall<'a> {
+    pub api_key_id: Uuid,
+    pub method: &'a str,
+    pub route: &'a str,
+    pub path: &'a str,
+    pub status: u16,
+    pub latency_ms: u128,
+    pub request_body: &'a [u8],
+    pub response_body: &'a [u8],
+    pub ip: Option<String>,
+    pub replay_of: Option<Uuid>,
+}
+
+// Bodies are stored as text, cut at PAYLOAD_LIMIT bytes on a char boundary
+fn truncate_payload(body: &[u8]) -> Option<String> {
+    if body.is_empty() {
+        return None;
+    }
+
+    let text = String::from_utf8_lossy(body);
+    if text.len() <= PAYLOAD_LIMIT {
+        return Some(text.into_owned());
+    }
+
+    let mut end = PAYLOAD_LIMIT;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    Some(format!("{}… [truncated, {} bytes total]", &text[..end], body.len()))
+}
+
+pub async fn record(db: &Database, call: ApiCall<'_>) -> Result<Uuid, sqlx::Error> {
+    sqlx::query_scalar::<_, Uuid>(
+        r#"
+        INSERT INTO api_call_logs
+            (api_key_id, method, route, path, status, latency_ms, request_body, response_body, ip, replay_of)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+        RETURNING id
+        "#,
+    )
+    .bind(call.api_key_id)
+    .bind(call.method)
+    .bind(call.route)
+    .bind(call.path)
+    .bind(call.status as i32)
+    .bind(call.latency_ms.min(i32::MAX as u128) as i32)
+    .bind(truncate_payload(call.request_body))
+    .bind(truncate_payload(call.response_body))
+    .bind(call.ip)
+    .bind(call.replay_of)
+    .fetch_one(db)
+    .await
+}
+
+pub const API_CALL_LOG_SELECT: &str = r#"
+    SELECT l.id, l.api_key_id, k.name as key_name, k.key_prefix, l.method, l.route, l.path,
+           l.status, l.latency_ms, l.request_body, l.response_body, l.ip, l.replay_of, l.created_at
+    FROM api_call_logs l
+    LEFT JOIN api_keys k ON k.id = l.api_key_id
+"#;
+
+pub async fn find(db: &Database, log_id: Uuid) -> Result<Option<ApiCallLog>, sqlx::Error> {
+    sqlx::query_as::<_, ApiCallLog>(&format!("{} WHERE l.id = $1", API_CALL_LOG_SELECT))
+        .bind(log_id)
+        .fetch_optional(db)
+        .await
+}
+
+pub async fn purge_expired(db: &Database) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query("DELETE FROM api_call_logs WHERE created_at < NOW() - make_interval(days => $1)")
+        .bind(RETENTION_DAYS)
+        .execute(db)
+        .await?;
+
+    Ok(result.rows_affected())
+}
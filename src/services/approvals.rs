@@ -0,0 +1,164 @@
+use chrono::{DateTime, Utc};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+use crate::{
+    database::Database,
+    middleware::CurrentUser,
+    services::{discounts, teams},
+};
+
+// What can wait on someone's approval: (kind, label, permission needed to decide)
+pub const APPROVAL_KINDS: &[(&str, &str, &str)] = &[
+    ("expense", "Expense", "expenses:approve"),
+    ("discount", "Discount", discounts::APPROVE_PERMISSION),
+];
+
+// One request waiting on the current user, whatever it is
+#[derive(Debug, FromRow)]
+pub struct Approval {
+    pub kind: String,
+    pub id: Uuid,
+    pub requested_by: String,
+    pub summary: String,
+    pub detail: Option<String>,
+    // Preformatted; None when the viewer can't see amounts
+    pub amount: Option<String>,
+    pub requested_at: Option<DateTime<Utc>>,
+    pub link: String,
+}
+
+impl Approval {
+    pub fn kind_label(&self) -> &'static str {
+        APPROVAL_KINDS
+            .iter()
+            .find(|(kind, _, _)| *kind == self.kind)
+            .map(|(_, label, _)| *label)
+            .unwrap_or("Request")
+    }
+}
+
+// Discounts under a manager-level threshold also go to the requester's manager
+// without the permission, and team managers may approve just their teams'
+// expenses, so for those the queries decide what's theirs
+pub fn can_decide(user: &CurrentUser, kind: &str) -> bool {
+    kind == "discount"
+        || (kind == "expense" && teams::approves_expenses(&user.permissions))
+        || APPROVAL_KINDS
+            .iter()
+            .any(|(k, _, permission)| *k == kind && user.permissions.iter().any(|p| p == permission))
+}
+
+// Everything the user could approve, oldest first. People don't approve their
+// own requests.
+pub async fn pending(db: &Database, user: &CurrentUser) -> Result<Vec<Approval>, sqlx::Error> {
+    let mut approvals = Vec::new();
+
+    if can_decide(user, "expense") {
+        approvals.extend(
+            sqlx::query_as::<_, Approval>(&format!(
+                r#"
+                SELECT 'expense' as kind, e.id,
+                       CONCAT(u.first_name, ' ', u.last_name) as requested_by,
+                       ec.name || ' · ' || TO_CHAR(e.expense_date, 'YYYY-MM-DD') as summary,
+                       NULLIF(CONCAT_WS(' · ', c.company_name, NULLIF(e.description, '')), '') as detail,
+                       CASE WHEN $2 THEN '$' || TO_CHAR(e.amount, 'FM999,999,999,990.00') END as amount,
+                       e.created_at as requested_at,
+                       '/expenses?user_id=' || e.user_id as link
+                FROM expenses e
+                JOIN users u ON u.id = e.user_id
+                JOIN expense_categories ec ON ec.id = e.category_id
+                LEFT JOIN customers c ON c.id = e.customer_id
+                WHERE e.status = 'pending' AND e.user_id <> $1 AND {}
+                "#,
+                teams::expense_approval_condition(&user.permissions, "e", "$1")
+            ))
+            .bind(user.id)
+            .bind(user.has_finance_read)
+            .fetch_all(db)
+            .await?,
+        );
+    }
+
+    approvals.extend(
+        sqlx::query_as::<_, Approval>(&format!(
+            r#"
+            SELECT 'discount' as kind, li.id,
+                   CONCAT(u.first_name, ' ', u.last_name) as requested_by,
+                   li.discount_percent::float8 || '% off ' || li.description as summary,
+                   d.title || ' · ' || c.company_name as detail,
+                   CASE WHEN $2 THEN d.currency || ' ' || TO_CHAR(li.quantity * li.unit_price * (100 - li.discount_percent) / 100, 'FM999,999,999,990.00') END as amount,
+                   li.created_at as requested_at,
+                   '/crm/deals/' || d.id as link
+            FROM deal_line_items li
+            JOIN deals d ON d.id = li.deal_id
+            JOIN customers c ON c.id = d.customer_id
+            JOIN users u ON u.id = li.created_by
+            WHERE li.discount_status = 'pending' AND li.created_by <> $1
+              AND ($3 OR (u.manager_id = $1 AND {} = 'manager'))
+            "#,
+            discounts::REQUIRED_APPROVER_SQL
+        ))
+        .bind(user.id)
+        .bind(user.has_finance_read)
+        .bind(discounts::can_approve(user))
+        .fetch_all(db)
+        .await?,
+    );
+
+    approvals.sort_by_key(|approval| approval.requested_at);
+    Ok(approvals)
+}
+
+// Approve or deny one pending request. Returns false when it isn't waiting on
+// this user (already decided, their own, or gone).
+pub async fn decide(
+    db: &Database,
+    user: &CurrentUser,
+    kind: &str,
+    id: Uuid,
+    approve: bool,
+) -> Result<bool, sqlx::Error> {
+    if !can_decide(user, kind) {
+        return Ok(false);
+    }
+
+    let status = if approve { "approved" } else { "denied" };
+    let result = match kind {
+        "expense" => {
+            sqlx::query(&format!(
+                r#"
+                UPDATE expenses e SET status = $1, approved_by = $2, approved_at = NOW()
+                WHERE e.id = $3 AND e.status = 'pending' AND e.user_id <> $2 AND {}
+                "#,
+                teams::expense_approval_condition(&user.permissions, "e", "$2")
+            ))
+            .bind(status)
+            .bind(user.id)
+            .bind(id)
+            .execute(db)
+            .await?
+        }
+        "discount" => {
+            sqlx::query(&format!(
+                r#"
+                UPDATE deal_line_items li
+                SET discount_status = $1, discount_decided_by = $2, discount_decided_at = NOW()
+                FROM users u
+                WHERE li.id = $3 AND li.discount_status = 'pending' AND li.created_by <> $2
+                  AND u.id = li.created_by
+                  AND ($4 OR (u.manager_id = $2 AND {} = 'manager'))
+                "#,
+                discounts::REQUIRED_APPROVER_SQL
+            ))
+            .bind(status)
+            .bind(user.id)
+            .bind(id)
+            .bind(discounts::can_approve(user))
+            .execute(db)
+            .await?
+        }
+        _ => return Ok(false),
+    };
+    Ok(result.rows_affected() > 0)
+}
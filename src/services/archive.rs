@@ -0,0 +1,82 @@
+use chrono::{DateTime, Months, NaiveDate, Utc};
+
+use crate::database::Database;
+
+// Completed activities and audit entries older than this move to the archive
+// tables. Anything newer is always in the live tables.
+pub const ARCHIVE_AFTER_MONTHS: u32 = 24;
+
+// Rows moved per statement, so one run doesn't hold a huge transaction
+const BATCH_SIZE: i64 = 5000;
+
+fn cutoff() -> DateTime<Utc> {
+    Utc::now()
+        .checked_sub_months(Months::new(ARCHIVE_AFTER_MONTHS))
+        .unwrap_or(DateTime::<Utc>::MIN_UTC)
+}
+
+// What to select activities from for a range starting at `since`: the live
+// table alone when the range is recent enough, or with the archive added
+// back in. No start means all time.
+pub fn activities_from(since: Option<NaiveDate>) -> &'static str {
+    match since {
+        Some(since) if since >= cutoff().date_naive() => "activities",
+        _ => "activities_with_archive",
+    }
+}
+
+// As activities_from, for the audit log
+pub fn audit_logs_from(since: Option<DateTime<Utc>>) -> &'static str {
+    match since {
+        Some(since) if since >= cutoff() => "audit_logs",
+        _ => "audit_logs_with_archive",
+    }
+}
+
+// Open activities stay live however old they are, as they're still someone's
+// to do
+const MOVE_ACTIVITIES: &str = r#"
+    WITH moved AS (
+        DELETE FROM activities
+        WHERE id IN (
+            SELECT id FROM activities
+            WHERE completed = true AND activity_date < NOW() - make_interval(months => $1)
+            LIMIT $2
+        )
+        RETURNING *
+    )
+    INSERT INTO activities_archive SELECT * FROM moved
+"#;
+
+const MOVE_AUDIT_LOGS: &str = r#"
+    WITH moved AS (
+        DELETE FROM audit_logs
+        WHERE id IN (
+            SELECT id FROM audit_logs
+            WHERE created_at < NOW() - make_interval(months => $1)
+            LIMIT $2
+        )
+        RETURNING *
+    )
+    INSERT INTO audit_logs_archive SELECT * FROM moved
+"#;
+
+// Moves everything past ARCHIVE_AFTER_MONTHS into the archives, returning
+// how many rows moved
+pub async fn archive_old(db: &Database) -> Result<u64, sqlx::Error> {
+    let mut moved = 0;
+    for statement in [MOVE_ACTIVITIES, MOVE_AUDIT_LOGS] {
+        loop {
+            let result = sqlx::query(statement)
+                .bind(ARCHIVE_AFTER_MONTHS as i32)
+                .bind(BATCH_SIZE)
+                .execute(db)
+                .await?;
+            moved += result.rows_affected();
+            if result.rows_affected() < BATCH_SIZE as u64 {
+                break;
+            }
+        }
+    }
+    Ok(moved)
+}
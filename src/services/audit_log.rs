@@ -0,0 +1,264 @@
+use chrono::{DateTime, Utc};
+use serde_json::Value;
+use sqlx::{
+    postgres::{PgArguments, PgQueryResult},
+    query::Query,
+    Postgres,
+};
+use uuid::Uuid;
+
+use crate::{
+    database::Database,
+    middleware::CurrentUser,
+    models::{AuditLogDisplay, FieldAccess},
+    services::archive,
+    utils::json_diff::{self, FieldChange},
+};
+
+// Entries from `source`: audit_logs, or the view adding the archive to it
+fn audit_log_select(source: &str) -> String {
+    format!(
+        r#"
+        SELECT a.id, a.user_id, u.first_name || ' ' || u.last_name as user_name,
+               i.first_name || ' ' || i.last_name as impersonator_name,
+               a.action, a.resource_type, a.resource_id, a.old_values, a.new_values,
+               a.ip_address, a.user_agent, a.created_at
+        FROM {} a
+        LEFT JOIN users u ON u.id = a.user_id
+        LEFT JOIN users i ON i.id = a.impersonator_id
+        "#,
+        source
+    )
+}
+
+// Records whose changes are audited with the whole row before and after, so
+// the audit log can show field by field what changed
+#[derive(Clone, Copy)]
+pub enum Audited {
+    Customer,
+    Contact,
+    Deal,
+    Activity,
+    Expense,
+    InventoryItem,
+    Lead,
+}
+
+impl Audited {
+    fn table(self) -> &'static str {
+        match self {
+            Self::Customer => "customers",
+            Self::Contact => "contacts",
+            Self::Deal => "deals",
+            Self::Activity => "activities",
+            Self::Expense => "expenses",
+            Self::InventoryItem => "inventory_items",
+            Self::Lead => "leads",
+        }
+    }
+
+    fn resource_type(self) -> &'static str {
+        match self {
+            Self::Customer => "customer",
+            Self::Contact => "contact",
+            Self::Deal => "deal",
+            Self::Activity => "activity",
+            Self::Expense => "expense",
+            Self::InventoryItem => "inventory_item",
+            Self::Lead => "lead",
+        }
+    }
+}
+
+// Fields that need finance:read, by the resource type entries are recorded
+// against. The records' own pages hide the same ones.
+fn finance_fields(resource_type: &str) -> &'static [&'static str] {
+    match resource_type {
+        "deal" => &["value", "base_value"],
+        "expense" => &["amount"],
+        "inventory_item" => &["purchase_price", "cost_price", "landed_cost", "average_cost", "gross_margin"],
+        "project" => &["estimated_cost"],
+        "cost_rate" => &["hourly_cost"],
+        _ => &[],
+    }
+}
+
+// What an entry changed field by field, without the financial fields unless
+// the viewer has finance:read
+pub fn visible_changes(entry: &AuditLogDisplay, access: &FieldAccess) -> Vec<FieldChange> {
+    let hidden = if access.finance { &[] } else { finance_fields(&entry.resource_type) };
+    json_diff::diff(
+        entry.old_values.as_ref().map(|values| &values.0),
+        entry.new_values.as_ref().map(|values| &values.0),
+    )
+    .into_iter()
+    .filter(|change| !hidden.contains(&change.field.as_str()))
+    .collect()
+}
+
+pub async fn write(
+    db: &Database,
+    actor: &CurrentUser,
+    action: &str,
+    resource_type: &str,
+    resource_id: Option<Uuid>,
+    old_values: Option<Value>,
+    new_values: Option<Value>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO audit_logs
+            (user_id, impersonator_id, action, resource_type, resource_id, old_values, new_values, ip_address, user_agent)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+        "#,
+    )
+    .bind(actor.id)
+    .bind(actor.impersonator_id())
+    .bind(action)
+    .bind(resource_type)
+    .bind(resource_id)
+    .bind(old_values)
+    .bind(new_values)
+    .bind(&actor.client.ip_address)
+    .bind(&actor.client.user_agent)
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+// The record's row as JSON, or None once it's gone. updated_at is left out
+// since it changes on every save.
+pub async fn snapshot(db: &Database, record: Audited, id: Uuid) -> Result<Option<Value>, sqlx::Error> {
+    sqlx::query_scalar::<_, Value>(&format!(
+        "SELECT to_jsonb(t) - 'updated_at' FROM {} t WHERE t.id = $1",
+        record.table()
+    ))
+    .bind(id)
+    .fetch_optional(db)
+    .await
+}
+
+// Audit a change to a record, given its snapshot from before the change (None
+// for a new record). The row is read again for the after side, and nothing is
+// written when it didn't change. Failures are logged rather than failing the
+// change, which has already happened.
+pub async fn record(db: &Database, actor: &CurrentUser, action: &str, record: Audited, id: Uuid, before: Option<Value>) {
+    let after = match snapshot(db, record, id).await {
+        Ok(after) => after,
+        Err(e) => {
+            tracing::error!("Error reading {} {} for the audit log: {}", record.resource_type(), id, e);
+            return;
+        }
+    };
+    if before == after {
+        return;
+    }
+    if let Err(e) = write(db, actor, action, record.resource_type(), Some(id), before, after).await {
+        tracing::error!("Error auditing {} of {} {}: {}", action, record.resource_type(), id, e);
+    }
+}
+
+// Run an update or delete of one record and audit it. Nothing is recorded
+// when the statement didn't touch a row.
+pub async fn audited_execute(
+    db: &Database,
+    actor: &CurrentUser,
+    action: &str,
+    audited: Audited,
+    id: Uuid,
+    query: Query<'_, Postgres, PgArguments>,
+) -> Result<PgQueryResult, sqlx::Error> {
+    let before = snapshot(db, audited, id).await?;
+    let result = query.execute(db).await?;
+    if result.rows_affected() > 0 {
+        record(db, actor, action, audited, id, before).await;
+    }
+    Ok(result)
+}
+
+// Unset fields don't filter. The range includes `from` and stops before `to`.
+#[derive(Default)]
+pub struct AuditFilter {
+    pub user_id: Option<Uuid>,
+    pub action: Option<String>,
+    pub resource_type: Option<String>,
+    pub resource_id: Option<Uuid>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+// Newest first
+pub async fn search(db: &Database, filter: &AuditFilter, limit: i64, offset: i64) -> Result<Vec<AuditLogDisplay>, sqlx::Error> {
+    sqlx::query_as::<_, AuditLogDisplay>(&format!(
+        r#"{}
+        WHERE ($1::uuid IS NULL OR a.user_id = $1)
+          AND ($2::text IS NULL OR a.action = $2)
+          AND ($3::text IS NULL OR a.resource_type = $3)
+          AND ($4::uuid IS NULL OR a.resource_id = $4)
+          AND ($5::timestamptz IS NULL OR a.created_at >= $5)
+          AND ($6::timestamptz IS NULL OR a.created_at < $6)
+        ORDER BY a.created_at DESC, a.id
+        LIMIT $7 OFFSET $8
+        "#,
+        audit_log_select(archive::audit_logs_from(filter.from))
+    ))
+    .bind(filter.user_id)
+    .bind(filter.action.as_deref())
+    .bind(filter.resource_type.as_deref())
+    .bind(filter.resource_id)
+    .bind(filter.from)
+    .bind(filter.to)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(db)
+    .await
+}
+
+// Every entry about one record, oldest first, for exporting its history,
+// archived ones included. Entries outlive the record, so this works for
+// deleted ones too.
+pub async fn history(db: &Database, record: Audited, id: Uuid) -> Result<Vec<AuditLogDisplay>, sqlx::Error> {
+    sqlx::query_as::<_, AuditLogDisplay>(&format!(
+        "{} WHERE a.resource_type = $1 AND a.resource_id = $2 ORDER BY a.created_at, a.id",
+        audit_log_select(archive::audit_logs_from(None))
+    ))
+    .bind(record.resource_type())
+    .bind(id)
+    .fetch_all(db)
+    .await
+}
+
+// Archived entries too, since search lists them for older ranges
+pub async fn find(db: &Database, id: Uuid) -> Result<Option<AuditLogDisplay>, sqlx::Error> {
+    sqlx::query_as::<_, AuditLogDisplay>(&format!("{} WHERE a.id = $1", audit_log_select(archive::audit_logs_from(None))))
+        .bind(id)
+        .fetch_optional(db)
+        .await
+}
+
+// The actions and resource types that have been recorded, for the filters
+pub async fn actions(db: &Database) -> Result<Vec<String>, sqlx::Error> {
+    sqlx::query_scalar::<_, String>("SELECT DISTINCT action FROM audit_logs ORDER BY action")
+        .fetch_all(db)
+        .await
+}
+
+pub async fn resource_types(db: &Database) -> Result<Vec<String>, sqlx::Error> {
+    sqlx::query_scalar::<_, String>("SELECT DISTINCT resource_type FROM audit_logs ORDER BY resource_type")
+        .fetch_all(db)
+        .await
+}
+
+// Everyone with at least one entry, including deactivated users
+pub async fn actors(db: &Database) -> Result<Vec<(Uuid, String)>, sqlx::Error> {
+    sqlx::query_as::<_, (Uuid, String)>(
+        r#"
+        SELECT u.id, u.first_name || ' ' || u.last_name
+        FROM users u
+        WHERE EXISTS(SELECT 1 FROM audit_logs a WHERE a.user_id = u.id)
+        ORDER BY u.first_name, u.last_name
+        "#,
+    )
+    .fetch_all(db)
+    .await
+}
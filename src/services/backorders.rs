@@ -0,0 +1,144 @@
+use sqlx::{Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    database::Database,
+    middleware::CurrentUser,
+    models::Backorder,
+    services::{
+        locations,
+        stock::{adjust_stock, lock_available},
+    },
+};
+
+// The open backorders in the user's warehouses, oldest first, as they'll be filled
+pub async fn queue(db: &Database, user: &CurrentUser) -> Result<Vec<Backorder>, sqlx::Error> {
+    sqlx::query_as::<_, Backorder>(&format!(
+        r#"
+        SELECT b.id, b.release_id, b.item_id, i.item_name, i.sku, b.warehouse_id, w.name as warehouse_name,
+               o.id as blanket_order_id, COALESCE(o.order_number, o.reference) as reference,
+               o.customer_id, c.company_name, r.scheduled_for, b.quantity, b.allocated, b.created_at
+        FROM backorders b
+        JOIN blanket_order_releases r ON r.id = b.release_id
+        JOIN blanket_order_lines l ON l.id = r.line_id
+        JOIN blanket_orders o ON o.id = l.blanket_order_id
+        JOIN customers c ON c.id = o.customer_id
+        JOIN inventory_items i ON i.id = b.item_id
+        JOIN warehouses w ON w.id = b.warehouse_id
+        WHERE b.status = 'open' AND {}
+        ORDER BY b.created_at
+        "#,
+        locations::warehouse_condition("b.warehouse_id", 1)
+    ))
+    .bind(user.warehouse_ids.as_deref())
+    .fetch_all(db)
+    .await
+}
+
+// Commit stock for a release being reserved. For an item that allows
+// backorders, whatever the warehouse can't cover is backordered instead of
+// committed; other items are committed in full as before. Returns how much
+// was backordered.
+pub(crate) async fn reserve(
+    tx: &mut Transaction<'_, Postgres>,
+    release_id: Uuid,
+    item_id: Uuid,
+    warehouse_id: Uuid,
+    quantity: i32,
+) -> Result<i32, sqlx::Error> {
+    let allowed = sqlx::query_scalar::<_, Option<bool>>("SELECT backorder_allowed FROM inventory_items WHERE id = $1")
+        .bind(item_id)
+        .fetch_one(&mut **tx)
+        .await?
+        .unwrap_or(false);
+    let short = if allowed {
+        let available = lock_available(tx, item_id, warehouse_id).await?;
+        (quantity - available.max(0)).clamp(0, quantity)
+    } else {
+        0
+    };
+
+    adjust_stock(tx, item_id, warehouse_id, 0, quantity - short).await?;
+    if short > 0 {
+        sqlx::query("INSERT INTO backorders (release_id, item_id, warehouse_id, quantity) VALUES ($1, $2, $3, $4)")
+            .bind(release_id)
+            .bind(item_id)
+            .bind(warehouse_id)
+            .bind(short)
+            .execute(&mut **tx)
+            .await?;
+    }
+    Ok(short)
+}
+
+// Hand a warehouse's free stock of an item to its open backorders, oldest
+// first. Returns how much was allocated.
+pub(crate) async fn allocate(tx: &mut Transaction<'_, Postgres>, item_id: Uuid, warehouse_id: Uuid) -> Result<i32, sqlx::Error> {
+    let mut available = lock_available(tx, item_id, warehouse_id).await?;
+    if available <= 0 {
+        return Ok(0);
+    }
+
+    let open = sqlx::query_as::<_, (Uuid, i32)>(
+        r#"
+        SELECT id, quantity - allocated FROM backorders
+        WHERE item_id = $1 AND warehouse_id = $2 AND status = 'open'
+        ORDER BY created_at
+        FOR UPDATE
+        "#,
+    )
+    .bind(item_id)
+    .bind(warehouse_id)
+    .fetch_all(&mut **tx)
+    .await?;
+
+    let mut allocated = 0;
+    for (backorder_id, outstanding) in open {
+        let take = outstanding.min(available);
+        if take <= 0 {
+            break;
+        }
+        sqlx::query(
+            r#"
+            UPDATE backorders SET allocated = allocated + $2,
+                status = CASE WHEN allocated + $2 = quantity THEN 'filled' ELSE status END,
+                filled_at = CASE WHEN allocated + $2 = quantity THEN NOW() END
+            WHERE id = $1
+            "#,
+        )
+        .bind(backorder_id)
+        .bind(take)
+        .execute(&mut **tx)
+        .await?;
+        available -= take;
+        allocated += take;
+    }
+
+    if allocated > 0 {
+        adjust_stock(tx, item_id, warehouse_id, 0, allocated).await?;
+    }
+    Ok(allocated)
+}
+
+// What a release is still waiting for
+pub(crate) async fn outstanding(tx: &mut Transaction<'_, Postgres>, release_id: Uuid) -> Result<i32, sqlx::Error> {
+    let outstanding = sqlx::query_scalar::<_, i32>(
+        "SELECT quantity - allocated FROM backorders WHERE release_id = $1 AND status = 'open'",
+    )
+    .bind(release_id)
+    .fetch_optional(&mut **tx)
+    .await?;
+    Ok(outstanding.unwrap_or(0))
+}
+
+// Drop a release's open backorder when the release is cancelled. Returns what
+// it was still waiting for, which was never committed.
+pub(crate) async fn cancel(tx: &mut Transaction<'_, Postgres>, release_id: Uuid) -> Result<i32, sqlx::Error> {
+    let outstanding = sqlx::query_scalar::<_, i32>(
+        "UPDATE backorders SET status = 'cancelled' WHERE release_id = $1 AND status = 'open' RETURNING quantity - allocated",
+    )
+    .bind(release_id)
+    .fetch_optional(&mut **tx)
+    .await?;
+    Ok(outstanding.unwrap_or(0))
+}
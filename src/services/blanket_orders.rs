@@ -0,0 +1,336 @@
+use chrono::NaiveDate;
+use sqlx::{Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    database::Database,
+    models::{BlanketOrder, BlanketOrderLine, BlanketOrderRelease, ItemCommitment, BLANKET_ORDER_SELECT},
+    services::{
+        backorders,
+        sharing::{self, RecordKind},
+        stock::adjust_stock,
+    },
+};
+
+const LINE_SELECT: &str = r#"
+    SELECT l.id, l.blanket_order_id, l.item_id, i.item_name, i.sku, l.quantity, l.unit_price, l.customer_part_number,
+           COALESCE(SUM(r.quantity) FILTER (WHERE r.status = 'shipped'), 0) as shipped,
+           COALESCE(SUM(r.quantity) FILTER (WHERE r.status = 'reserved'), 0) as reserved,
+           COALESCE(SUM(r.quantity) FILTER (WHERE r.status = 'scheduled'), 0) as scheduled
+    FROM blanket_order_lines l
+    JOIN inventory_items i ON i.id = l.item_id
+    LEFT JOIN blanket_order_releases r ON r.line_id = l.id
+"#;
+
+const RELEASE_SELECT: &str = r#"
+    SELECT r.id, r.line_id, l.item_id, i.item_name, r.warehouse_id, w.name as warehouse_name,
+           r.quantity, r.scheduled_for, r.status, r.reserved_at, r.shipped_at,
+           COALESCE(b.quantity - b.allocated, 0) as backordered
+    FROM blanket_order_releases r
+    JOIN blanket_order_lines l ON l.id = r.line_id
+    JOIN inventory_items i ON i.id = l.item_id
+    JOIN warehouses w ON w.id = r.warehouse_id
+    LEFT JOIN backorders b ON b.release_id = r.id AND b.status = 'open'
+"#;
+
+pub async fn find(db: &Database, id: Uuid) -> Result<Option<BlanketOrder>, sqlx::Error> {
+    sqlx::query_as::<_, BlanketOrder>(&format!("{} WHERE o.id = $1", BLANKET_ORDER_SELECT))
+        .bind(id)
+        .fetch_optional(db)
+        .await
+}
+
+pub async fn for_customer(db: &Database, customer_id: Uuid) -> Result<Vec<BlanketOrder>, sqlx::Error> {
+    sqlx::query_as::<_, BlanketOrder>(&format!(
+        "{} WHERE o.customer_id = $1 ORDER BY o.status = 'open' DESC, o.ends_on DESC",
+        BLANKET_ORDER_SELECT
+    ))
+    .bind(customer_id)
+    .fetch_all(db)
+    .await
+}
+
+pub async fn lines(db: &Database, order_id: Uuid) -> Result<Vec<BlanketOrderLine>, sqlx::Error> {
+    sqlx::query_as::<_, BlanketOrderLine>(&format!(
+        "{} WHERE l.blanket_order_id = $1 GROUP BY l.id, i.item_name, i.sku ORDER BY l.created_at",
+        LINE_SELECT
+    ))
+    .bind(order_id)
+    .fetch_all(db)
+    .await
+}
+
+pub async fn releases(db: &Database, order_id: Uuid) -> Result<Vec<BlanketOrderRelease>, sqlx::Error> {
+    sqlx::query_as::<_, BlanketOrderRelease>(&format!(
+        "{} WHERE l.blanket_order_id = $1 ORDER BY r.scheduled_for, i.item_name",
+        RELEASE_SELECT
+    ))
+    .bind(order_id)
+    .fetch_all(db)
+    .await
+}
+
+// What open blanket orders still owe of an item. For a parent item this
+// covers its variants too. With `visible_to`, only customers that user can
+// see are included.
+pub async fn commitments(db: &Database, item_id: Uuid, visible_to: Option<Uuid>) -> Result<Vec<ItemCommitment>, sqlx::Error> {
+    let scope = match visible_to {
+        Some(_) => format!("AND {}", sharing::visibility_condition(RecordKind::Customer, "c", 2)),
+        None => String::new(),
+    };
+    sqlx::query_as::<_, ItemCommitment>(&format!(
+        r#"
+        SELECT o.id as blanket_order_id, COALESCE(o.order_number, o.reference) as reference, o.customer_id, c.company_name,
+               SUM(l.quantity)::BIGINT as agreed,
+               COALESCE(SUM(r.shipped), 0)::BIGINT as shipped,
+               COALESCE(SUM(r.reserved), 0)::BIGINT as reserved,
+               MIN(r.next_release) as next_release
+        FROM blanket_order_lines l
+        JOIN blanket_orders o ON o.id = l.blanket_order_id
+        JOIN customers c ON c.id = o.customer_id
+        JOIN inventory_items i ON i.id = l.item_id
+        LEFT JOIN LATERAL (
+            SELECT SUM(quantity) FILTER (WHERE status = 'shipped') as shipped,
+                   SUM(quantity) FILTER (WHERE status = 'reserved') as reserved,
+                   MIN(scheduled_for) FILTER (WHERE status IN ('scheduled', 'reserved')) as next_release
+            FROM blanket_order_releases
+            WHERE line_id = l.id
+        ) r ON TRUE
+        WHERE o.status = 'open' AND (i.id = $1 OR i.parent_item_id = $1) {}
+        GROUP BY o.id, c.company_name
+        ORDER BY MIN(r.next_release) NULLS LAST, 2
+        "#,
+        scope
+    ))
+    .bind(item_id)
+    .bind(visible_to)
+    .fetch_all(db)
+    .await
+}
+
+// Reserve stock for every release that has come within its order's
+// reservation window, backordering what the warehouse can't cover. Returns
+// how many were reserved.
+pub async fn reserve_due(db: &Database) -> Result<usize, sqlx::Error> {
+    let mut tx = db.begin().await?;
+    let due = sqlx::query_as::<_, (Uuid, Uuid, Uuid, i32)>(
+        r#"
+        SELECT r.id, l.item_id, r.warehouse_id, r.quantity
+        FROM blanket_order_releases r
+        JOIN blanket_order_lines l ON l.id = r.line_id
+        JOIN blanket_orders o ON o.id = l.blanket_order_id
+        WHERE r.status = 'scheduled'
+          AND o.status = 'open'
+          AND r.scheduled_for <= CURRENT_DATE + o.reserve_days_ahead
+        FOR UPDATE OF r SKIP LOCKED
+        "#,
+    )
+    .fetch_all(&mut *tx)
+    .await?;
+
+    for (release_id, item_id, warehouse_id, quantity) in &due {
+        backorders::reserve(&mut tx, *release_id, *item_id, *warehouse_id, *quantity).await?;
+        sqlx::query("UPDATE blanket_order_releases SET status = 'reserved', reserved_at = NOW() WHERE id = $1")
+            .bind(release_id)
+            .execute(&mut *tx)
+            .await?;
+    }
+    tx.commit().await?;
+
+    Ok(due.len())
+}
+
+pub struct NewRelease {
+    pub line_id: Uuid,
+    pub warehouse_id: Uuid,
+    pub quantity: i32,
+    pub scheduled_for: NaiveDate,
+}
+
+// Schedule a call-off against a line of an open order. Releases can't add up
+// to more than the line's agreed quantity. One that is already due is
+// reserved straight away.
+pub async fn schedule_release(
+    db: &Database,
+    order: &BlanketOrder,
+    release: NewRelease,
+    created_by: Uuid,
+) -> Result<Result<Uuid, String>, sqlx::Error> {
+    if !order.is_open() {
+        return Ok(Err("Releases can only be scheduled on an open order".to_string()));
+    }
+    if release.quantity <= 0 {
+        return Ok(Err("Enter a quantity above zero".to_string()));
+    }
+    if release.scheduled_for < order.starts_on || release.scheduled_for > order.ends_on {
+        return Ok(Err(format!(
+            "The release date has to fall within the order, {} to {}",
+            order.starts_on, order.ends_on
+        )));
+    }
+
+    let mut tx = db.begin().await?;
+    // Locking the line keeps two releases from both taking the last units
+    let line = sqlx::query_as::<_, (Uuid, i32)>(
+        "SELECT item_id, quantity FROM blanket_order_lines WHERE id = $1 AND blanket_order_id = $2 FOR UPDATE",
+    )
+    .bind(release.line_id)
+    .bind(order.id)
+    .fetch_optional(&mut *tx)
+    .await?;
+    let Some((item_id, agreed)) = line else {
+        return Ok(Err("That line isn't on this order".to_string()));
+    };
+    let released = sqlx::query_scalar::<_, i64>(
+        "SELECT COALESCE(SUM(quantity), 0) FROM blanket_order_releases WHERE line_id = $1 AND status <> 'cancelled'",
+    )
+    .bind(release.line_id)
+    .fetch_one(&mut *tx)
+    .await?;
+    let left = i64::from(agreed) - released;
+    if i64::from(release.quantity) > left {
+        return Ok(Err(format!("Only {} more can be released on that line", left)));
+    }
+
+    let reserve_now = release.scheduled_for
+        <= chrono::Utc::now().date_naive() + chrono::Duration::days(i64::from(order.reserve_days_ahead));
+    let id = sqlx::query_scalar::<_, Uuid>(
+        r#"
+        INSERT INTO blanket_order_releases (line_id, warehouse_id, quantity, scheduled_for, status, reserved_at, created_by)
+        VALUES ($1, $2, $3, $4, $5, CASE WHEN $6 THEN NOW() END, $7)
+        RETURNING id
+        "#,
+    )
+    .bind(release.line_id)
+    .bind(release.warehouse_id)
+    .bind(release.quantity)
+    .bind(release.scheduled_for)
+    .bind(if reserve_now { "reserved" } else { "scheduled" })
+    .bind(reserve_now)
+    .bind(created_by)
+    .fetch_one(&mut *tx)
+    .await?;
+    if reserve_now {
+        backorders::reserve(&mut tx, id, item_id, release.warehouse_id, release.quantity).await?;
+    }
+    tx.commit().await?;
+
+    Ok(Ok(id))
+}
+
+async fn pending_release(
+    tx: &mut Transaction<'_, Postgres>,
+    order_id: Uuid,
+    release_id: Uuid,
+) -> Result<Option<(Uuid, Uuid, i32, String)>, sqlx::Error> {
+    sqlx::query_as::<_, (Uuid, Uuid, i32, String)>(
+        r#"
+        SELECT l.item_id, r.warehouse_id, r.quantity, r.status
+        FROM blanket_order_releases r
+        JOIN blanket_order_lines l ON l.id = r.line_id
+        WHERE r.id = $1 AND l.blanket_order_id = $2 AND r.status IN ('scheduled', 'reserved')
+        FOR UPDATE OF r
+        "#,
+    )
+    .bind(release_id)
+    .bind(order_id)
+    .fetch_optional(&mut **tx)
+    .await
+}
+
+// Ship a release: its stock leaves the warehouse and any reservation is used
+// up. Returns why it can't if it isn't pending on this order or is still
+// waiting on a backorder.
+pub async fn ship_release(
+    db: &Database,
+    order: &BlanketOrder,
+    release_id: Uuid,
+    shipped_by: Uuid,
+) -> Result<Result<(), String>, sqlx::Error> {
+    let mut tx = db.begin().await?;
+    let Some((item_id, warehouse_id, quantity, status)) = pending_release(&mut tx, order.id, release_id).await? else {
+        return Ok(Err("That release has already been shipped or cancelled".to_string()));
+    };
+    let backordered = backorders::outstanding(&mut tx, release_id).await?;
+    if backordered > 0 {
+        return Ok(Err(format!("That release is still waiting on {} backordered", backordered)));
+    }
+
+    let committed = if status == "reserved" { -quantity } else { 0 };
+    adjust_stock(&mut tx, item_id, warehouse_id, -quantity, committed).await?;
+    sqlx::query("UPDATE blanket_order_releases SET status = 'shipped', shipped_at = NOW() WHERE id = $1")
+        .bind(release_id)
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query(
+        r#"
+        INSERT INTO stock_movements (item_id, from_warehouse_id, quantity, movement_type, reason, reference_id, moved_by)
+        VALUES ($1, $2, $3, 'sale', $4, $5, $6)
+        "#,
+    )
+    .bind(item_id)
+    .bind(warehouse_id)
+    .bind(quantity)
+    .bind(format!("Blanket order release for {}", order.company_name))
+    .bind(order.order_number.as_ref().unwrap_or(&order.reference))
+    .bind(shipped_by)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    Ok(Ok(()))
+}
+
+// Cancel a pending release, giving back any stock it had reserved
+pub async fn cancel_release(db: &Database, order_id: Uuid, release_id: Uuid) -> Result<bool, sqlx::Error> {
+    let mut tx = db.begin().await?;
+    let Some((item_id, warehouse_id, quantity, status)) = pending_release(&mut tx, order_id, release_id).await? else {
+        return Ok(false);
+    };
+
+    if status == "reserved" {
+        let backordered = backorders::cancel(&mut tx, release_id).await?;
+        adjust_stock(&mut tx, item_id, warehouse_id, 0, backordered - quantity).await?;
+    }
+    sqlx::query("UPDATE blanket_order_releases SET status = 'cancelled' WHERE id = $1")
+        .bind(release_id)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+
+    Ok(true)
+}
+
+// Close or cancel an open order. Releases not yet shipped are cancelled and
+// their reservations given back.
+pub async fn finish(db: &Database, order_id: Uuid, status: &str) -> Result<bool, sqlx::Error> {
+    let mut tx = db.begin().await?;
+    let finished = sqlx::query("UPDATE blanket_orders SET status = $2 WHERE id = $1 AND status = 'open'")
+        .bind(order_id)
+        .bind(status)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+    if finished == 0 {
+        return Ok(false);
+    }
+
+    let reserved = sqlx::query_as::<_, (Uuid, Uuid, Uuid, i32)>(
+        r#"
+        UPDATE blanket_order_releases r SET status = 'cancelled'
+        FROM blanket_order_lines l
+        WHERE l.id = r.line_id AND l.blanket_order_id = $1 AND r.status IN ('scheduled', 'reserved')
+        RETURNING r.id, l.item_id, r.warehouse_id, CASE WHEN r.reserved_at IS NULL THEN 0 ELSE r.quantity END
+        "#,
+    )
+    .bind(order_id)
+    .fetch_all(&mut *tx)
+    .await?;
+    for (release_id, item_id, warehouse_id, quantity) in reserved.into_iter().filter(|(_, _, _, quantity)| *quantity > 0) {
+        let backordered = backorders::cancel(&mut tx, release_id).await?;
+        adjust_stock(&mut tx, item_id, warehouse_id, 0, backordered - quantity).await?;
+    }
+    tx.commit().await?;
+
+    Ok(true)
+}
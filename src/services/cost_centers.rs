@@ -0,0 +1,182 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use rust_decimal::Decimal;
+use uuid::Uuid;
+
+use crate::{database::Database, models::CostCenter};
+
+// Spend booked against one cost center over a period
+#[derive(sqlx::FromRow)]
+pub struct CostCenterSpend {
+    pub id: Uuid,
+    pub code: String,
+    pub name: String,
+    pub is_active: bool,
+    pub expense_count: i64,
+    pub approved: Decimal,
+    pub pending: Decimal,
+}
+
+// One expense as accounting needs it
+#[derive(sqlx::FromRow)]
+pub struct ExportLine {
+    pub expense_date: NaiveDate,
+    pub code: String,
+    pub cost_center: String,
+    pub category: String,
+    pub user_name: String,
+    pub customer: Option<String>,
+    pub description: Option<String>,
+    pub amount: Decimal,
+    pub status: String,
+    pub approved_at: Option<DateTime<Utc>>,
+}
+
+pub async fn list(db: &Database, include_inactive: bool) -> Result<Vec<CostCenter>, sqlx::Error> {
+    sqlx::query_as::<_, CostCenter>(
+        "SELECT * FROM cost_centers WHERE is_active OR $1 ORDER BY code",
+    )
+    .bind(include_inactive)
+    .fetch_all(db)
+    .await
+}
+
+// Active ones, plus `keep` so editing an expense booked to a retired center
+// doesn't force a change
+pub async fn options(db: &Database, keep: Option<Uuid>) -> Result<Vec<CostCenter>, sqlx::Error> {
+    sqlx::query_as::<_, CostCenter>(
+        "SELECT * FROM cost_centers WHERE is_active OR id = $1 ORDER BY code",
+    )
+    .bind(keep)
+    .fetch_all(db)
+    .await
+}
+
+pub async fn is_usable(db: &Database, id: Uuid, keep: Option<Uuid>) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS(SELECT 1 FROM cost_centers WHERE id = $1 AND (is_active OR id = $2))",
+    )
+    .bind(id)
+    .bind(keep)
+    .fetch_one(db)
+    .await
+}
+
+// Matches the code or the name, ignoring case
+pub async fn find_active(db: &Database, code_or_name: &str) -> Result<Option<Uuid>, sqlx::Error> {
+    sqlx::query_scalar::<_, Uuid>(
+        r#"
+        SELECT id FROM cost_centers
+        WHERE is_active AND (LOWER(code) = LOWER($1) OR LOWER(name) = LOWER($1))
+        ORDER BY LOWER(code) = LOWER($1) DESC
+        LIMIT 1
+        "#,
+    )
+    .bind(code_or_name)
+    .fetch_optional(db)
+    .await
+}
+
+fn validate(code: &str, name: &str) -> Result<(), String> {
+    if code.is_empty()
+        || code.len() > 30
+        || !code.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.'))
+    {
+        return Err("The code can have up to 30 letters, digits, and - _ .".to_string());
+    }
+    if name.is_empty() || name.chars().count() > 100 {
+        return Err("The name is required and can be up to 100 characters".to_string());
+    }
+    Ok(())
+}
+
+// Returns why it was refused, if it was
+pub async fn create(db: &Database, code: &str, name: &str, created_by: Uuid) -> Result<Result<Uuid, String>, sqlx::Error> {
+    if let Err(reason) = validate(code, name) {
+        return Ok(Err(reason));
+    }
+    let id = sqlx::query_scalar::<_, Uuid>(
+        r#"
+        INSERT INTO cost_centers (code, name, created_by) VALUES ($1, $2, $3)
+        ON CONFLICT (tenant_id, code) DO NOTHING
+        RETURNING id
+        "#,
+    )
+    .bind(code)
+    .bind(name)
+    .bind(created_by)
+    .fetch_optional(db)
+    .await?;
+    Ok(id.ok_or_else(|| format!("There is already a cost center with code {}", code)))
+}
+
+// The code can change too; exports made before keep the old one
+pub async fn update(db: &Database, id: Uuid, code: &str, name: &str, is_active: bool) -> Result<Result<(), String>, sqlx::Error> {
+    if let Err(reason) = validate(code, name) {
+        return Ok(Err(reason));
+    }
+    let taken = sqlx::query_scalar::<_, bool>("SELECT EXISTS(SELECT 1 FROM cost_centers WHERE code = $1 AND id <> $2)")
+        .bind(code)
+        .bind(id)
+        .fetch_one(db)
+        .await?;
+    if taken {
+        return Ok(Err(format!("There is already a cost center with code {}", code)));
+    }
+    let updated = sqlx::query(
+        "UPDATE cost_centers SET code = $2, name = $3, is_active = $4, updated_at = NOW() WHERE id = $1",
+    )
+    .bind(id)
+    .bind(code)
+    .bind(name)
+    .bind(is_active)
+    .execute(db)
+    .await?
+    .rows_affected();
+    if updated == 0 {
+        return Ok(Err("Unknown cost center".to_string()));
+    }
+    Ok(Ok(()))
+}
+
+// Every cost center, including those with nothing booked; denied expenses aren't spend
+pub async fn spend(db: &Database, from: NaiveDate, to: NaiveDate) -> Result<Vec<CostCenterSpend>, sqlx::Error> {
+    sqlx::query_as::<_, CostCenterSpend>(
+        r#"
+        SELECT cc.id, cc.code, cc.name, cc.is_active,
+               COUNT(e.id) as expense_count,
+               COALESCE(SUM(e.amount) FILTER (WHERE e.status = 'approved'), 0) as approved,
+               COALESCE(SUM(e.amount) FILTER (WHERE e.status = 'pending'), 0) as pending
+        FROM cost_centers cc
+        LEFT JOIN expenses e ON e.cost_center_id = cc.id
+            AND e.status <> 'denied' AND e.expense_date BETWEEN $1 AND $2
+        GROUP BY cc.id
+        HAVING cc.is_active OR COUNT(e.id) > 0
+        ORDER BY cc.code
+        "#,
+    )
+    .bind(from)
+    .bind(to)
+    .fetch_all(db)
+    .await
+}
+
+pub async fn export_lines(db: &Database, from: NaiveDate, to: NaiveDate) -> Result<Vec<ExportLine>, sqlx::Error> {
+    sqlx::query_as::<_, ExportLine>(
+        r#"
+        SELECT e.expense_date, cc.code, cc.name as cost_center, ec.name as category,
+               CONCAT(u.first_name, ' ', u.last_name) as user_name, c.company_name as customer,
+               e.description, e.amount, e.status, e.approved_at
+        FROM expenses e
+        JOIN cost_centers cc ON cc.id = e.cost_center_id
+        JOIN expense_categories ec ON ec.id = e.category_id
+        JOIN users u ON u.id = e.user_id
+        LEFT JOIN customers c ON c.id = e.customer_id
+        WHERE e.status <> 'denied' AND e.expense_date BETWEEN $1 AND $2
+        ORDER BY cc.code, e.expense_date
+        "#,
+    )
+    .bind(from)
+    .bind(to)
+    .fetch_all(db)
+    .await
+}
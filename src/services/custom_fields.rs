@@ -0,0 +1,294 @@
+use std::collections::HashMap;
+
+use chrono::NaiveDate;
+use serde_json::{Map, Value};
+use uuid::Uuid;
+
+use crate::{
+    database::Database,
+    models::{CustomField, CUSTOM_FIELD_RECORDS, CUSTOM_FIELD_TYPES},
+    utils::xlsx::{Cell, ColumnType},
+};
+
+// Records that carry custom fields; the key is custom_fields.record_type
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Extended {
+    Customer,
+    Contact,
+    Deal,
+}
+
+impl Extended {
+    pub fn key(self) -> &'static str {
+        match self {
+            Extended::Customer => "customer",
+            Extended::Contact => "contact",
+            Extended::Deal => "deal",
+        }
+    }
+
+    fn table(self) -> &'static str {
+        match self {
+            Extended::Customer => "customers",
+            Extended::Contact => "contacts",
+            Extended::Deal => "deals",
+        }
+    }
+}
+
+// What an admin submits from /crm/custom-fields
+pub struct FieldDefinition<'a> {
+    pub record_type: &'a str,
+    pub key: &'a str,
+    pub label: &'a str,
+    pub field_type: &'a str,
+    // A select field's choices, one per line
+    pub options: &'a str,
+    pub is_required: bool,
+}
+
+// A field on a record's form, with the value to show in it
+pub struct FieldInput {
+    pub field: CustomField,
+    pub value: String,
+}
+
+impl FieldInput {
+    pub fn is_selected(&self, option: &str) -> bool {
+        self.value == option
+    }
+
+    // A dropdown value whose option has since been taken out of the list
+    pub fn is_removed_option(&self) -> bool {
+        !self.value.is_empty() && !self.field.options.contains(&self.value)
+    }
+}
+
+// A filled-in field on a record's page
+pub struct FieldValue {
+    pub label: String,
+    pub value: String,
+}
+
+const MAX_TEXT_LENGTH: usize = 1000;
+
+pub async fn list(db: &Database) -> Result<Vec<CustomField>, sqlx::Error> {
+    sqlx::query_as::<_, CustomField>(
+        "SELECT * FROM custom_fields ORDER BY record_type, sort_order, label",
+    )
+    .fetch_all(db)
+    .await
+}
+
+// The active fields of one kind of record, in form order
+pub async fn for_record_type(db: &Database, kind: Extended) -> Result<Vec<CustomField>, sqlx::Error> {
+    sqlx::query_as::<_, CustomField>(
+        "SELECT * FROM custom_fields WHERE record_type = $1 AND is_active ORDER BY sort_order, label",
+    )
+    .bind(kind.key())
+    .fetch_all(db)
+    .await
+}
+
+// A record's stored values; None when there's no such record
+pub async fn values(db: &Database, kind: Extended, record_id: Uuid) -> Result<Option<Value>, sqlx::Error> {
+    sqlx::query_scalar::<_, Value>(&format!("SELECT custom_fields FROM {} WHERE id = $1", kind.table()))
+        .bind(record_id)
+        .fetch_optional(db)
+        .await
+}
+
+// Stored values as form text: numbers as written, dates as YYYY-MM-DD
+fn value_text(value: Option<&Value>) -> String {
+    match value {
+        Some(Value::String(text)) => text.clone(),
+        Some(Value::Null) | None => String::new(),
+        Some(other) => other.to_string(),
+    }
+}
+
+pub fn inputs(fields: Vec<CustomField>, values: &Value) -> Vec<FieldInput> {
+    fields
+        .into_iter()
+        .map(|field| {
+            let value = value_text(values.get(&field.key));
+            FieldInput { field, value }
+        })
+        .collect()
+}
+
+pub fn display(fields: &[CustomField], values: &Value) -> Vec<FieldValue> {
+    fields
+        .iter()
+        .filter_map(|field| {
+            let value = value_text(values.get(&field.key));
+            (!value.is_empty()).then(|| FieldValue { label: field.label.clone(), value })
+        })
+        .collect()
+}
+
+// The custom inputs of a submitted form, keyed by field key
+pub fn form_values(form: &HashMap<String, String>) -> HashMap<String, String> {
+    form.iter()
+        .filter_map(|(name, value)| name.strip_prefix("cf_").map(|key| (key.to_string(), value.clone())))
+        .collect()
+}
+
+// An API body's custom_fields object as the text a form would have sent.
+// Keys that aren't fields of the record are refused rather than dropped.
+pub fn json_values(fields: &[CustomField], body: &Map<String, Value>) -> Result<HashMap<String, String>, String> {
+    body.iter()
+        .map(|(key, value)| {
+            if !fields.iter().any(|field| field.key == *key) {
+                return Err(format!("Unknown custom field \"{}\"", key));
+            }
+            Ok((key.clone(), value_text(Some(value))))
+        })
+        .collect()
+}
+
+// Checks the submitted values against the fields and merges them into the
+// record's existing ones. Values of retired fields are kept.
+pub fn collect(fields: &[CustomField], submitted: &HashMap<String, String>, existing: &Value) -> Result<Value, String> {
+    let mut values = existing.as_object().cloned().unwrap_or_default();
+
+    for field in fields {
+        let raw = submitted.get(&field.key).map(|value| value.trim()).unwrap_or("");
+        if raw.is_empty() {
+            if field.is_required {
+                return Err(format!("{} is required", field.label));
+            }
+            values.remove(&field.key);
+            continue;
+        }
+
+        let value = match field.field_type.as_str() {
+            "number" => raw
+                .parse::<f64>()
+                .ok()
+                .and_then(serde_json::Number::from_f64)
+                .map(Value::Number)
+                .ok_or_else(|| format!("{} must be a number", field.label))?,
+            "date" => NaiveDate::parse_from_str(raw, "%Y-%m-%d")
+                .map(|date| Value::String(date.to_string()))
+                .map_err(|_| format!("{} must be a date", field.label))?,
+            "select" => {
+                // A removed option can stay on the records that already have it
+                let unchanged = existing.get(&field.key).and_then(Value::as_str) == Some(raw);
+                if !unchanged && !field.options.iter().any(|option| option == raw) {
+                    return Err(format!("{} must be one of the listed options", field.label));
+                }
+                Value::String(raw.to_string())
+            }
+            _ => {
+                if raw.chars().count() > MAX_TEXT_LENGTH {
+                    return Err(format!("{} can be at most {} characters", field.label, MAX_TEXT_LENGTH));
+                }
+                Value::String(raw.to_string())
+            }
+        };
+        values.insert(field.key.clone(), value);
+    }
+
+    Ok(Value::Object(values))
+}
+
+pub fn export_columns(fields: &[CustomField]) -> Vec<(String, ColumnType)> {
+    fields
+        .iter()
+        .map(|field| {
+            let kind = match field.field_type.as_str() {
+                "number" => ColumnType::Number,
+                "date" => ColumnType::Date,
+                _ => ColumnType::Text,
+            };
+            (field.label.clone(), kind)
+        })
+        .collect()
+}
+
+pub fn export_cells(fields: &[CustomField], values: &Value) -> Vec<Cell> {
+    fields
+        .iter()
+        .map(|field| match values.get(&field.key) {
+            Some(Value::Number(number)) => number.as_f64().map(Cell::Number).unwrap_or(Cell::Empty),
+            Some(Value::String(text)) if field.field_type == "date" => NaiveDate::parse_from_str(text, "%Y-%m-%d")
+                .map(Cell::Date)
+                .unwrap_or_else(|_| Cell::Text(text.clone())),
+            Some(Value::String(text)) => Cell::Text(text.clone()),
+            _ => Cell::Empty,
+        })
+        .collect()
+}
+
+// Adds a field, or updates the one with the same key and brings it back if
+// it was retired. The key and type can't change once records store values
+// under them.
+pub async fn save(db: &Database, input: &FieldDefinition<'_>) -> Result<Result<(), String>, sqlx::Error> {
+    let key = input.key.trim().to_lowercase().replace([' ', '-'], "_");
+    let label = input.label.trim();
+    if !CUSTOM_FIELD_RECORDS.iter().any(|(value, _)| *value == input.record_type)
+        || !CUSTOM_FIELD_TYPES.iter().any(|(value, _)| *value == input.field_type)
+    {
+        return Ok(Err("Pick a record and a field type".to_string()));
+    }
+    if key.is_empty() || label.is_empty() {
+        return Ok(Err("Key and label are required".to_string()));
+    }
+    if key.len() > 50
+        || !key.starts_with(|c: char| c.is_ascii_lowercase())
+        || !key.chars().all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+    {
+        return Ok(Err("Key must start with a letter and use only letters, digits and underscores, up to 50 characters".to_string()));
+    }
+
+    let options: Vec<String> = if input.field_type == "select" {
+        input.options.lines().map(str::trim).filter(|option| !option.is_empty()).map(String::from).collect()
+    } else {
+        Vec::new()
+    };
+    if input.field_type == "select" && options.is_empty() {
+        return Ok(Err("A dropdown needs at least one option".to_string()));
+    }
+
+    let existing_type = sqlx::query_scalar::<_, String>(
+        "SELECT field_type FROM custom_fields WHERE record_type = $1 AND key = $2",
+    )
+    .bind(input.record_type)
+    .bind(&key)
+    .fetch_optional(db)
+    .await?;
+    if existing_type.as_deref().is_some_and(|field_type| field_type != input.field_type) {
+        return Ok(Err(format!("\"{}\" already exists with a different type", key)));
+    }
+
+    sqlx::query(
+        r#"
+        INSERT INTO custom_fields (record_type, key, label, field_type, options, is_required, sort_order)
+        VALUES ($1, $2, $3, $4, $5, $6,
+            (SELECT COALESCE(MAX(sort_order), 0) + 1 FROM custom_fields WHERE record_type = $1))
+        ON CONFLICT (tenant_id, record_type, key) DO UPDATE SET
+            label = EXCLUDED.label, options = EXCLUDED.options,
+            is_required = EXCLUDED.is_required, is_active = true
+        "#,
+    )
+    .bind(input.record_type)
+    .bind(&key)
+    .bind(label)
+    .bind(input.field_type)
+    .bind(&options)
+    .bind(input.is_required)
+    .execute(db)
+    .await?;
+
+    Ok(Ok(()))
+}
+
+// Fields are retired rather than deleted so records keep their values
+pub async fn toggle(db: &Database, id: Uuid) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE custom_fields SET is_active = NOT is_active WHERE id = $1")
+        .bind(id)
+        .execute(db)
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,303 @@
+use uuid::Uuid;
+
+use crate::{
+    database::Database,
+    middleware::CurrentUser,
+    services::{
+        locations::warehouse_condition,
+        sharing::{self, RecordKind},
+    },
+};
+
+// Dashboard variants in priority order, each with the widgets it shows. A user
+// whose roles map to several variants sees the widgets of all of them.
+pub const DASHBOARD_VARIANTS: [(&str, &str, &[&str]); 3] = [
+    ("manager", "Manager", &["team_pipeline", "approvals", "my_tasks"]),
+    ("sales", "Sales", &["my_pipeline", "my_tasks"]),
+    ("warehouse", "Warehouse", &["low_stock", "open_transfers"]),
+];
+
+// Rows a widget is limited to beyond its permission, written into its query
+// at `{scope}`
+#[derive(Clone, Copy)]
+enum Scope {
+    All,
+    // Deals the user can see under record ownership, `alias` being the deals table
+    Deals(&'static str),
+    // Stock in the user's warehouses, any of the columns holding one of them
+    Warehouses(&'static [&'static str]),
+}
+
+// A widget is a titled list of rows produced by one query. Each query returns
+// (label, detail, value, url) and takes the current user's id as $1 when
+// `per_user` is set, followed by whatever its scope binds. Values of
+// `financial` widgets need finance:read.
+pub struct WidgetDef {
+    pub key: &'static str,
+    pub title: &'static str,
+    pub permission: &'static str,
+    pub link_url: &'static str,
+    pub link_label: &'static str,
+    pub empty_message: &'static str,
+    per_user: bool,
+    financial: bool,
+    scope: Scope,
+    sql: &'static str,
+}
+
+pub static WIDGETS: [WidgetDef; 6] = [
+    WidgetDef {
+        key: "my_pipeline",
+        title: "My Pipeline",
+        permission: "customers:read",
+        link_url: "/crm/deals",
+        link_label: "All deals",
+        empty_message: "No open deals assigned to you.",
+        per_user: true,
+        financial: true,
+        scope: Scope::All,
+        sql: r#"
+            SELECT d.title,
+                   INITCAP(REPLACE(d.stage, '_', ' ')) || COALESCE(' · closes ' || TO_CHAR(d.expected_close_date, 'YYYY-MM-DD'), ''),
+                   '$' || TO_CHAR(COALESCE(d.base_value, 0), 'FM999,999,999,990.00'),
+                   '/crm/deals/' || d.id
+            FROM deals d
+            WHERE COALESCE(d.assigned_to, d.created_by) = $1
+              AND d.stage NOT IN ('closed_won', 'closed_lost')
+            ORDER BY d.expected_close_date NULLS LAST, d.created_at
+            LIMIT 10
+        "#,
+    },
+    WidgetDef {
+        key: "my_tasks",
+        title: "My Tasks",
+        permission: "customers:read",
+        link_url: "/crm/activities",
+        link_label: "All activities",
+        empty_message: "Nothing outstanding.",
+        per_user: true,
+        financial: false,
+        scope: Scope::All,
+        sql: r#"
+            SELECT a.subject,
+                   INITCAP(a.activity_type) || ' · ' || c.company_name,
+                   TO_CHAR(a.activity_date AT TIME ZONE me.timezone, 'YYYY-MM-DD HH24:MI'),
+                   '/crm/activities/' || a.id || '/edit'
+            FROM activities a
+            JOIN customers c ON c.id = a.customer_id
+            JOIN users me ON me.id = $1
+            WHERE a.completed = false
+              AND COALESCE(a.assigned_to, a.created_by) = $1
+            ORDER BY a.activity_date
+            LIMIT 10
+        "#,
+    },
+    WidgetDef {
+        key: "team_pipeline",
+        title: "Team Pipeline",
+        permission: "customers:read",
+        link_url: "/crm/reports/pivot",
+        link_label: "Owner report",
+        empty_message: "No open deals.",
+        per_user: false,
+        financial: true,
+        scope: Scope::Deals("d"),
+        sql: r#"
+            SELECT COALESCE(NULLIF(CONCAT(u.first_name, ' ', u.last_name), ' '), 'Unassigned'),
+                   COUNT(*) || CASE WHEN COUNT(*) = 1 THEN ' open deal' ELSE ' open deals' END,
+                   '$' || TO_CHAR(COALESCE(SUM(d.base_value), 0), 'FM999,999,999,990.00'),
+                   NULL::text
+            FROM deals d
+            LEFT JOIN users u ON u.id = COALESCE(d.assigned_to, d.created_by)
+            WHERE d.stage NOT IN ('closed_won', 'closed_lost') AND {scope}
+            GROUP BY 1
+            ORDER BY SUM(d.base_value) DESC NULLS LAST
+            LIMIT 10
+        "#,
+    },
+    WidgetDef {
+        key: "approvals",
+        title: "Awaiting Approval",
+        permission: "expenses:approve",
+        link_url: "/approvals",
+        link_label: "Approval inbox",
+        empty_message: "No expenses waiting for approval.",
+        per_user: false,
+        financial: true,
+        scope: Scope::All,
+        sql: r#"
+            SELECT u.first_name || ' ' || u.last_name,
+                   ec.name || ' · ' || TO_CHAR(e.expense_date, 'YYYY-MM-DD'),
+                   '$' || TO_CHAR(e.amount, 'FM999,999,999,990.00'),
+                   '/expenses'
+            FROM expenses e
+            JOIN users u ON u.id = e.user_id
+            JOIN expense_categories ec ON ec.id = e.category_id
+            WHERE e.status = 'pending'
+            ORDER BY e.created_at
+            LIMIT 10
+        "#,
+    },
+    WidgetDef {
+        key: "low_stock",
+        title: "Low Stock",
+        permission: "inventory:read",
+        link_url: "/inventory/items",
+        link_label: "All items",
+        empty_message: "Everything is above its reorder point.",
+        per_user: false,
+        financial: false,
+        scope: Scope::Warehouses(&["sl.warehouse_id"]),
+        sql: r#"
+            SELECT i.item_name,
+                   i.sku,
+                   COALESCE(SUM(sl.quantity_available), 0) || ' / ' || i.reorder_point,
+                   '/inventory/items/' || i.id
+            FROM inventory_items i
+            LEFT JOIN stock_levels sl ON sl.item_id = i.id AND {scope}
+            WHERE i.is_active = true AND i.reorder_point > 0
+              -- Stock of an item with variants is held, and reordered, per variant
+              AND NOT EXISTS (SELECT 1 FROM inventory_items v WHERE v.parent_item_id = i.id)
+            GROUP BY i.id
+            HAVING COALESCE(SUM(sl.quantity_available), 0) <= i.reorder_point
+            ORDER BY COALESCE(SUM(sl.quantity_available), 0) - i.reorder_point
+            LIMIT 10
+        "#,
+    },
+    // Movements carry no completion status, so transfers logged in the last
+    // week are shown as the ones still in flight
+    WidgetDef {
+        key: "open_transfers",
+        title: "Open Transfers",
+        permission: "inventory:read",
+        link_url: "/inventory/items",
+        link_label: "Inventory",
+        empty_message: "No transfers in the last 7 days.",
+        per_user: false,
+        financial: false,
+        scope: Scope::Warehouses(&["m.from_warehouse_id", "m.to_warehouse_id"]),
+        sql: r#"
+            SELECT i.item_name,
+                   COALESCE(wf.name, '?') || ' → ' || COALESCE(wt.name, '?') || ' · ' || TO_CHAR(m.moved_at, 'YYYY-MM-DD'),
+                   m.quantity::text,
+                   NULL::text
+            FROM stock_movements m
+            JOIN inventory_items i ON i.id = m.item_id
+            LEFT JOIN warehouses wf ON wf.id = m.from_warehouse_id
+            LEFT JOIN warehouses wt ON wt.id = m.to_warehouse_id
+            WHERE m.movement_type = 'transfer' AND m.moved_at > NOW() - INTERVAL '7 days'
+              AND {scope}
+            ORDER BY m.moved_at DESC
+            LIMIT 10
+        "#,
+    },
+];
+
+pub struct WidgetRow {
+    pub label: String,
+    pub detail: Option<String>,
+    pub value: Option<String>,
+    pub url: Option<String>,
+}
+
+pub struct Widget {
+    pub def: &'static WidgetDef,
+    pub rows: Vec<WidgetRow>,
+}
+
+// Dashboard variants assigned to the user through their active roles
+pub async fn user_variants(db: &Database, user_id: Uuid) -> Result<Vec<String>, sqlx::Error> {
+    sqlx::query_scalar::<_, String>(
+        r#"
+        SELECT DISTINCT r.dashboard FROM roles r
+        JOIN user_roles ur ON ur.role_id = r.id
+        WHERE ur.user_id = $1 AND r.is_active = true AND r.dashboard IS NOT NULL
+        "#,
+    )
+    .bind(user_id)
+    .fetch_all(db)
+    .await
+}
+
+// Widgets for the given variants, de-duplicated and limited to what the user may see
+pub fn widgets_for(variants: &[String], permissions: &[String]) -> Vec<&'static WidgetDef> {
+    let mut widgets: Vec<&'static WidgetDef> = Vec::new();
+
+    for (key, _, widget_keys) in DASHBOARD_VARIANTS.iter() {
+        if !variants.iter().any(|v| v == key) {
+            continue;
+        }
+        for widget_key in widget_keys.iter() {
+            let Some(def) = WIDGETS.iter().find(|w| w.key == *widget_key) else {
+                continue;
+            };
+            if permissions.iter().any(|p| p == def.permission)
+                && !widgets.iter().any(|w| w.key == def.key)
+            {
+                widgets.push(def);
+            }
+        }
+    }
+
+    widgets
+}
+
+pub fn widget(key: &str) -> Option<&'static WidgetDef> {
+    WIDGETS.iter().find(|w| w.key == key)
+}
+
+// Every widget the user may see, in definition order
+pub fn widgets_allowed(permissions: &[String]) -> Vec<&'static WidgetDef> {
+    WIDGETS
+        .iter()
+        .filter(|def| permissions.iter().any(|p| p == def.permission))
+        .collect()
+}
+
+pub async fn load_widget(
+    db: &Database,
+    def: &'static WidgetDef,
+    user: &CurrentUser,
+) -> Result<Widget, sqlx::Error> {
+    let next_param = usize::from(def.per_user) + 1;
+    let scoped_deals = matches!(def.scope, Scope::Deals(_)) && sharing::is_scoped(user);
+    let scope = match def.scope {
+        Scope::Deals(alias) if scoped_deals => sharing::visibility_condition(RecordKind::Deal, alias, next_param),
+        Scope::All | Scope::Deals(_) => "true".to_string(),
+        Scope::Warehouses(columns) => format!(
+            "({})",
+            columns
+                .iter()
+                .map(|column| warehouse_condition(column, next_param))
+                .collect::<Vec<_>>()
+                .join(" OR ")
+        ),
+    };
+    let sql = def.sql.replace("{scope}", &scope);
+
+    let mut query = sqlx::query_as::<_, (String, Option<String>, Option<String>, Option<String>)>(&sql);
+    if def.per_user {
+        query = query.bind(user.id);
+    }
+    if scoped_deals {
+        query = query.bind(user.id);
+    }
+    if let Scope::Warehouses(_) = def.scope {
+        query = query.bind(user.warehouse_ids.as_deref());
+    }
+
+    let hide_values = def.financial && !user.field_access().finance;
+    let rows = query
+        .fetch_all(db)
+        .await?
+        .into_iter()
+        .map(|(label, detail, value, url)| WidgetRow {
+            label,
+            detail,
+            value: value.filter(|_| !hide_values),
+            url,
+        })
+        .collect();
+
+    Ok(Widget { def, rows })
+}
@@ -0,0 +1,108 @@
+use std::collections::HashSet;
+use sqlx::FromRow;
+use uuid::Uuid;
+
+use crate::{
+    database::Database,
+    models::DealDisplay,
+};
+
+// An open deal is stalled when neither its stage nor its activities have moved
+// within the stale_after_days configured for its current stage.
+const STALLED_DEALS_SQL: &str = r#"
+    SELECT d.id, d.title, d.stage, COALESCE(d.assigned_to, d.created_by) as owner_id,
+           EXTRACT(DAY FROM NOW() - GREATEST(
+               COALESCE(d.stage_changed_at, d.created_at),
+               COALESCE(last_activity.activity_date, d.created_at)
+           ))::int as idle_days
+    FROM deals d
+    JOIN deal_stage_settings s ON s.stage = d.stage
+    LEFT JOIN LATERAL (
+        SELECT MAX(a.activity_date) as activity_date
+        FROM activities a
+        WHERE a.deal_id = d.id AND a.activity_date <= NOW()
+    ) last_activity ON true
+    WHERE GREATEST(
+        COALESCE(d.stage_changed_at, d.created_at),
+        COALESCE(last_activity.activity_date, d.created_at)
+    ) < NOW() - make_interval(days => s.stale_after_days)
+"#;
+
+#[derive(FromRow)]
+struct StalledDeal {
+    id: Uuid,
+    title: String,
+    stage: String,
+    owner_id: Option<Uuid>,
+    idle_days: i32,
+}
+
+// Which of the given deals are stalled, so a page only checks the deals it shows
+pub async fn stalled_deal_ids(db: &Database, deal_ids: &[Uuid]) -> Result<HashSet<Uuid>, sqlx::Error> {
+    if deal_ids.is_empty() {
+        return Ok(HashSet::new());
+    }
+
+    let deals = sqlx::query_as::<_, StalledDeal>(&format!("{} AND d.id = ANY($1)", STALLED_DEALS_SQL))
+        .bind(deal_ids)
+        .fetch_all(db)
+        .await?;
+
+    Ok(deals.into_iter().map(|deal| deal.id).collect())
+}
+
+// Flag stalled deals on already-converted display rows
+pub async fn mark_stalled(db: &Database, deals: &mut [DealDisplay]) {
+    let deal_ids: Vec<Uuid> = deals.iter().map(|deal| deal.id).collect();
+
+    match stalled_deal_ids(db, &deal_ids).await {
+        Ok(stalled) => {
+            for deal in deals.iter_mut() {
+                deal.is_stalled = stalled.contains(&deal.id);
+            }
+        }
+        Err(e) => tracing::error!("Failed to load stalled deals: {}", e),
+    }
+}
+
+// Weekly reminder to owners of stalled deals, recorded in notifications
+pub async fn notify_stalled_deal_owners(db: &Database) -> Result<usize, sqlx::Error> {
+    let query = format!(
+        r#"
+        SELECT stalled.* FROM ({}) stalled
+        JOIN deals d ON d.id = stalled.id
+        WHERE d.stall_notified_at IS NULL OR d.stall_notified_at < NOW() - INTERVAL '7 days'
+        "#,
+        STALLED_DEALS_SQL
+    );
+
+    let deals = sqlx::query_as::<_, StalledDeal>(&query)
+        .fetch_all(db)
+        .await?;
+
+    let mut notified = 0;
+
+    for deal in deals {
+        if let Some(owner_id) = deal.owner_id {
+            sqlx::query(
+                "INSERT INTO notifications (user_id, message, link_url) VALUES ($1, $2, $3)"
+            )
+            .bind(owner_id)
+            .bind(format!(
+                "Deal \"{}\" has stalled in {} with no activity for {} days",
+                deal.title, deal.stage, deal.idle_days
+            ))
+            .bind(format!("/crm/deals/{}", deal.id))
+            .execute(db)
+            .await?;
+            notified += 1;
+        }
+
+        sqlx::query("UPDATE deals SET stall_notified_at = NOW() WHERE id = $1")
+            .bind(deal.id)
+            .execute(db)
+            .await?;
+    }
+
+    Ok(notified)
+}
@@ -0,0 +1,208 @@
+use std::{collections::HashSet, env, fs, sync::OnceLock};
+
+use chrono::{DateTime, Utc};
+use sqlx::FromRow;
+
+use crate::{database::Database, middleware::permission};
+
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+// Set by the release build, e.g. ALLO_BUILD=$(git rev-parse --short HEAD)
+pub const BUILD: Option<&str> = option_env!("ALLO_BUILD");
+
+static STARTED_AT: OnceLock<DateTime<Utc>> = OnceLock::new();
+
+// When this server started; call once at startup so it isn't the first page view
+pub fn started_at() -> DateTime<Utc> {
+    *STARTED_AT.get_or_init(Utc::now)
+}
+
+#[derive(FromRow)]
+pub struct AppliedMigration {
+    pub version: String,
+    pub applied_at: DateTime<Utc>,
+}
+
+pub struct MigrationStatus {
+    // False until 088_add_schema_migrations has been run
+    pub tracked: bool,
+    pub applied: usize,
+    pub latest: Option<AppliedMigration>,
+    // In the migrations directory but not recorded as run
+    pub pending: Vec<String>,
+    // Recorded as run but not in the directory: the database is ahead of this build
+    pub unknown: Vec<String>,
+    // None when the directory couldn't be read
+    pub files: Option<usize>,
+}
+
+// The .sql files the server was deployed with, by name without the extension.
+// MIGRATIONS_DIR overrides where they're looked for.
+fn migration_files() -> Option<Vec<String>> {
+    let dir = env::var("MIGRATIONS_DIR").unwrap_or_else(|_| "migrations".to_string());
+    let entries = match fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            tracing::warn!("Couldn't read migrations from {}: {}", dir, e);
+            return None;
+        }
+    };
+    let mut files: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| entry.file_name().to_str()?.strip_suffix(".sql").map(String::from))
+        .collect();
+    files.sort();
+    Some(files)
+}
+
+pub async fn migration_status(db: &Database) -> Result<MigrationStatus, sqlx::Error> {
+    let applied = sqlx::query_as::<_, AppliedMigration>(
+        "SELECT version, applied_at FROM schema_migrations ORDER BY version",
+    )
+    .fetch_all(db)
+    .await;
+    let (tracked, applied) = match applied {
+        Ok(applied) => (true, applied),
+        // undefined_table
+        Err(sqlx::Error::Database(e)) if e.code().as_deref() == Some("42P01") => (false, Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    let files = migration_files();
+    let recorded: HashSet<&str> = applied.iter().map(|m| m.version.as_str()).collect();
+    let (pending, unknown) = match &files {
+        Some(files) if tracked => {
+            let on_disk: HashSet<&str> = files.iter().map(String::as_str).collect();
+            (
+                files.iter().filter(|file| !recorded.contains(file.as_str())).cloned().collect(),
+                applied
+                    .iter()
+                    .filter(|m| !on_disk.contains(m.version.as_str()))
+                    .map(|m| m.version.clone())
+                    .collect(),
+            )
+        }
+        _ => (Vec::new(), Vec::new()),
+    };
+
+    Ok(MigrationStatus {
+        tracked,
+        applied: applied.len(),
+        files: files.as_ref().map(Vec::len),
+        pending,
+        unknown,
+        latest: applied.into_iter().last(),
+    })
+}
+
+pub struct PoolStats {
+    pub size: u32,
+    pub idle: usize,
+    pub max: u32,
+    // Connections to the database from every server, this one included
+    pub server_connections: i64,
+    pub database_version: String,
+}
+
+impl PoolStats {
+    pub fn in_use(&self) -> usize {
+        (self.size as usize).saturating_sub(self.idle)
+    }
+}
+
+pub async fn pool_stats(db: &Database) -> Result<PoolStats, sqlx::Error> {
+    let server_connections = sqlx::query_scalar::<_, i64>(
+        "SELECT COUNT(*) FROM pg_stat_activity WHERE datname = current_database()",
+    )
+    .fetch_one(db)
+    .await?;
+    let database_version = sqlx::query_scalar::<_, String>("SHOW server_version")
+        .fetch_one(db)
+        .await?;
+
+    Ok(PoolStats {
+        size: db.size(),
+        idle: db.num_idle(),
+        max: db.options().get_max_connections(),
+        server_connections,
+        database_version,
+    })
+}
+
+// Work waiting on the scheduler, for the current organization
+#[derive(FromRow)]
+pub struct QueueDepth {
+    pub name: String,
+    pub waiting: i64,
+    pub failed_today: i64,
+    pub oldest_waiting: Option<DateTime<Utc>>,
+}
+
+pub async fn queue_depths(db: &Database) -> Result<Vec<QueueDepth>, sqlx::Error> {
+    sqlx::query_as::<_, QueueDepth>(
+        r#"
+        SELECT 'Email outbox' as name,
+               COUNT(*) FILTER (WHERE status = 'queued') as waiting,
+               COUNT(*) FILTER (WHERE status = 'failed' AND created_at > NOW() - INTERVAL '1 day') as failed_today,
+               MIN(created_at) FILTER (WHERE status = 'queued') as oldest_waiting
+        FROM email_outbox
+        UNION ALL
+        SELECT 'Webhook deliveries',
+               COUNT(*) FILTER (WHERE status = 'pending'),
+               COUNT(*) FILTER (WHERE status = 'failed' AND created_at > NOW() - INTERVAL '1 day'),
+               MIN(created_at) FILTER (WHERE status = 'pending')
+        FROM webhook_deliveries
+        UNION ALL
+        SELECT 'Background jobs',
+               COUNT(*) FILTER (WHERE status IN ('queued', 'running')),
+               COUNT(*) FILTER (WHERE status = 'failed' AND created_at > NOW() - INTERVAL '1 day'),
+               MIN(created_at) FILTER (WHERE status IN ('queued', 'running'))
+        FROM jobs
+        "#,
+    )
+    .fetch_all(db)
+    .await
+}
+
+pub struct CacheRate {
+    pub name: &'static str,
+    pub description: String,
+    pub hits: i64,
+    pub misses: i64,
+}
+
+impl CacheRate {
+    pub fn hit_rate(&self) -> String {
+        let total = self.hits + self.misses;
+        if total == 0 {
+            return "—".to_string();
+        }
+        format!("{:.1}%", self.hits as f64 * 100.0 / total as f64)
+    }
+}
+
+pub async fn cache_rates(db: &Database) -> Result<Vec<CacheRate>, sqlx::Error> {
+    let (blocks_hit, blocks_read) = sqlx::query_as::<_, (i64, i64)>(
+        "SELECT blks_hit, blks_read FROM pg_stat_database WHERE datname = current_database()",
+    )
+    .fetch_one(db)
+    .await?;
+    let roles = permission::role_cache_stats();
+
+    Ok(vec![
+        CacheRate {
+            name: "Role cache",
+            description: format!(
+                "Each request's permissions, on this server since it started; {} users cached now",
+                roles.entries
+            ),
+            hits: roles.hits as i64,
+            misses: roles.misses as i64,
+        },
+        CacheRate {
+            name: "Database buffer cache",
+            description: "Table and index reads served from Postgres memory, since its statistics were last reset".to_string(),
+            hits: blocks_hit,
+            misses: blocks_read,
+        },
+    ])
+}
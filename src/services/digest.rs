@@ -0,0 +1,156 @@
+use askama::Template;
+use sqlx::FromRow;
+use uuid::Uuid;
+
+use crate::{
+    database::Database,
+    middleware::permission::get_user_permissions,
+    models::{Activity, ActivityDisplay, Deal, DealDisplay, ExpenseDisplay, FieldAccess},
+    services::{mailer, teams},
+    utils::timezone::parse_timezone,
+};
+
+#[derive(Template)]
+#[template(path = "emails/digest.html")]
+struct DigestEmailTemplate {
+    first_name: String,
+    period: String,
+    app_url: String,
+    tasks: Vec<ActivityDisplay>,
+    deals: Vec<DealDisplay>,
+    expenses: Vec<ExpenseDisplay>,
+}
+
+#[derive(FromRow)]
+struct DigestRecipient {
+    id: Uuid,
+    email: String,
+    first_name: String,
+    digest_frequency: String,
+    timezone: String,
+}
+
+// Queue a digest for every user whose daily/weekly digest is due
+pub async fn send_due_digests(db: &Database) -> Result<usize, sqlx::Error> {
+    let recipients = sqlx::query_as::<_, DigestRecipient>(
+        r#"
+        SELECT id, email, first_name, digest_frequency, timezone FROM users
+        WHERE is_active = true AND is_locked = false
+          AND (
+            (digest_frequency = 'daily' AND (last_digest_sent_at IS NULL OR last_digest_sent_at < NOW() - INTERVAL '1 day'))
+            OR (digest_frequency = 'weekly' AND (last_digest_sent_at IS NULL OR last_digest_sent_at < NOW() - INTERVAL '7 days'))
+          )
+        "#,
+    )
+    .fetch_all(db)
+    .await?;
+
+    let mut queued = 0;
+
+    for recipient in recipients {
+        let tz = parse_timezone(&recipient.timezone);
+        let tasks: Vec<ActivityDisplay> = sqlx::query_as::<_, Activity>(
+            r#"
+            SELECT * FROM activities
+            WHERE completed = false
+              AND (assigned_to = $1 OR (assigned_to IS NULL AND created_by = $1))
+            ORDER BY activity_date
+            LIMIT 20
+            "#,
+        )
+        .bind(recipient.id)
+        .fetch_all(db)
+        .await?
+        .into_iter()
+        .map(|activity| ActivityDisplay::in_timezone(activity, tz))
+        .collect();
+
+        let permissions = get_user_permissions(db, recipient.id).await;
+        let fields = FieldAccess::new(recipient.id, &permissions);
+
+        // Open deals that are overdue or expected to close within the next week
+        let deals: Vec<DealDisplay> = sqlx::query_as::<_, Deal>(
+            r#"
+            SELECT * FROM deals
+            WHERE stage NOT IN ('closed_won', 'closed_lost')
+              AND (assigned_to = $1 OR (assigned_to IS NULL AND created_by = $1))
+              AND expected_close_date <= CURRENT_DATE + 7
+            ORDER BY expected_close_date
+            LIMIT 20
+            "#,
+        )
+        .bind(recipient.id)
+        .fetch_all(db)
+        .await?
+        .into_iter()
+        .map(|deal| DealDisplay::new(deal, &fields))
+        .collect();
+
+        let expenses = if teams::approves_expenses(&permissions) {
+            sqlx::query_as::<_, ExpenseDisplay>(&format!(
+                r#"
+                SELECT
+                    e.id,
+                    e.user_id,
+                    CONCAT(u.first_name, ' ', u.last_name) as user_name,
+                    ec.name as category_name,
+                    cc.name as cost_center_name,
+                    c.company_name as customer_name,
+                    e.amount::text,
+                    COALESCE(e.description, '') as description,
+                    e.receipt_url,
+                    e.status,
+                    e.expense_date::text,
+                    e.created_at
+                FROM expenses e
+                JOIN users u ON e.user_id = u.id
+                JOIN expense_categories ec ON e.category_id = ec.id
+                JOIN cost_centers cc ON e.cost_center_id = cc.id
+                LEFT JOIN customers c ON e.customer_id = c.id
+                WHERE e.status = 'pending' AND e.user_id <> $1 AND {}
+                ORDER BY e.expense_date
+                LIMIT 20
+                "#,
+                teams::expense_approval_condition(&permissions, "e", "$1")
+            ))
+            .bind(recipient.id)
+            .fetch_all(db)
+            .await?
+            .into_iter()
+            .map(|expense| expense.with_access(&fields))
+            .collect()
+        } else {
+            Vec::new()
+        };
+
+        if !tasks.is_empty() || !deals.is_empty() || !expenses.is_empty() {
+            let period = if recipient.digest_frequency == "weekly" { "weekly" } else { "daily" };
+            let template = DigestEmailTemplate {
+                first_name: recipient.first_name,
+                period: period.to_string(),
+                app_url: mailer::app_url(),
+                tasks,
+                deals,
+                expenses,
+            };
+
+            let body = match template.render() {
+                Ok(body) => body,
+                Err(e) => {
+                    tracing::error!("Failed to render digest for {}: {}", recipient.email, e);
+                    continue;
+                }
+            };
+
+            mailer::queue_email(db, &recipient.email, &format!("Your {} Allo digest", period), &body).await?;
+            queued += 1;
+        }
+
+        sqlx::query("UPDATE users SET last_digest_sent_at = NOW() WHERE id = $1")
+            .bind(recipient.id)
+            .execute(db)
+            .await?;
+    }
+
+    Ok(queued)
+}
@@ -0,0 +1,53 @@
+use rust_decimal::Decimal;
+use uuid::Uuid;
+
+use crate::{database::Database, middleware::CurrentUser, models::DiscountThreshold};
+
+pub const APPROVE_PERMISSION: &str = "discounts:approve";
+
+// Who a discount needs to approve it, for queries with the line item as `li`:
+// the approver of the highest threshold it's above, or NULL within all of them
+pub const REQUIRED_APPROVER_SQL: &str = r#"
+    (SELECT t.approver FROM discount_thresholds t
+     WHERE li.discount_percent > t.above_percent
+     ORDER BY t.above_percent DESC LIMIT 1)
+"#;
+
+pub async fn thresholds(db: &Database) -> Result<Vec<DiscountThreshold>, sqlx::Error> {
+    sqlx::query_as::<_, DiscountThreshold>("SELECT * FROM discount_thresholds ORDER BY above_percent")
+        .fetch_all(db)
+        .await
+}
+
+pub fn can_approve(user: &CurrentUser) -> bool {
+    user.permissions.iter().any(|p| p == APPROVE_PERMISSION)
+}
+
+// The status a discount starts in when `user` gives it: 'none' within every
+// threshold, 'approved' when they could approve it themselves, otherwise
+// 'pending' until someone in the approval inbox decides
+pub async fn initial_status(db: &Database, user: &CurrentUser, percent: Decimal) -> Result<&'static str, sqlx::Error> {
+    let approver = sqlx::query_scalar::<_, String>(
+        "SELECT approver FROM discount_thresholds WHERE $1 > above_percent ORDER BY above_percent DESC LIMIT 1",
+    )
+    .bind(percent)
+    .fetch_optional(db)
+    .await?;
+
+    Ok(match approver {
+        None => "none",
+        Some(_) if can_approve(user) => "approved",
+        Some(_) => "pending",
+    })
+}
+
+// Line items on a deal whose discount is still waiting or was turned down.
+// The deal can't be marked won while there are any.
+pub async fn unapproved(db: &Database, deal_id: Uuid) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar::<_, i64>(
+        "SELECT COUNT(*) FROM deal_line_items WHERE deal_id = $1 AND discount_status IN ('pending', 'denied')",
+    )
+    .bind(deal_id)
+    .fetch_one(db)
+    .await
+}
@@ -0,0 +1,245 @@
+use askama::Template;
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use uuid::Uuid;
+
+use crate::{
+    database::Database,
+    models::{DocumentTemplate, DOCUMENT_COLUMNS, LOGO_POSITIONS},
+    utils::locale::{self, Locale},
+};
+
+// Enough for a small embedded logo
+const MAX_LOGO_BYTES: usize = 64 * 1024;
+const MAX_TEXT_CHARS: usize = 5000;
+
+pub struct TemplateChanges<'a> {
+    pub title: &'a str,
+    pub logo_url: Option<&'a str>,
+    pub logo_position: &'a str,
+    pub header_text: Option<&'a str>,
+    pub terms_text: Option<&'a str>,
+    pub footer_text: Option<&'a str>,
+    pub columns: Vec<String>,
+}
+
+// What gets printed, whatever record it came from
+pub struct Document {
+    pub number: String,
+    pub issued_on: NaiveDate,
+    // Catalog key for who it's addressed to, e.g. "customer"
+    pub party_heading: &'static str,
+    pub party_name: String,
+    // The other side's own number for it, e.g. their PO
+    pub reference: Option<String>,
+    pub currency: String,
+    // Locked when the record was made: one unit of `currency` in `base_currency`
+    pub exchange_rate: Option<Decimal>,
+    pub base_currency: String,
+    pub lines: Vec<DocumentLine>,
+    pub notes: Option<String>,
+    // The reader's language; labels, numbers and dates follow it
+    pub language: Option<String>,
+}
+
+pub struct DocumentLine {
+    pub sku: String,
+    pub customer_part_number: Option<String>,
+    pub description: String,
+    pub quantity: i64,
+    // None when the reader isn't allowed to see prices
+    pub unit_price: Option<Decimal>,
+}
+
+impl DocumentLine {
+    fn line_total(&self) -> Option<Decimal> {
+        self.unit_price.map(|price| price * Decimal::from(self.quantity))
+    }
+}
+
+impl Document {
+    fn total(&self) -> Option<Decimal> {
+        self.lines.iter().map(DocumentLine::line_total).sum()
+    }
+}
+
+#[derive(Template)]
+#[template(path = "documents/document.html")]
+struct RenderedDocument<'a> {
+    layout: &'a DocumentTemplate,
+    locale: &'static Locale,
+    title: &'a str,
+    organization: &'a str,
+    document: &'a Document,
+    issued_on: String,
+    // e.g. "1 CAD = 0.7300 USD"; only for documents in another currency
+    exchange_rate: Option<String>,
+    // The layout's columns that have something to show, in print order
+    columns: Vec<&'static str>,
+    total: Option<String>,
+}
+
+impl RenderedDocument<'_> {
+    fn cell(&self, line: &DocumentLine, column: &str) -> String {
+        match column {
+            "sku" => line.sku.clone(),
+            "customer_part_number" => line.customer_part_number.clone().unwrap_or_default(),
+            "description" => line.description.clone(),
+            "quantity" => self.locale.integer(line.quantity),
+            "unit_price" => line.unit_price.map(|p| self.locale.amount(p)).unwrap_or_default(),
+            "line_total" => line.line_total().map(|t| self.locale.amount(t)).unwrap_or_default(),
+            _ => String::new(),
+        }
+    }
+
+    fn is_amount(&self, column: &str) -> bool {
+        matches!(column, "quantity" | "unit_price" | "line_total")
+    }
+}
+
+pub async fn list(db: &Database) -> Result<Vec<DocumentTemplate>, sqlx::Error> {
+    sqlx::query_as::<_, DocumentTemplate>("SELECT * FROM document_templates ORDER BY document_type")
+        .fetch_all(db)
+        .await
+}
+
+pub async fn find(db: &Database, document_type: &str) -> Result<Option<DocumentTemplate>, sqlx::Error> {
+    sqlx::query_as::<_, DocumentTemplate>("SELECT * FROM document_templates WHERE document_type = $1")
+        .bind(document_type)
+        .fetch_optional(db)
+        .await
+}
+
+// Returns why the change was refused, if it was
+pub async fn update(
+    db: &Database,
+    document_type: &str,
+    changes: TemplateChanges<'_>,
+    updated_by: Uuid,
+) -> Result<Result<(), String>, sqlx::Error> {
+    if changes.title.is_empty() || changes.title.len() > 100 {
+        return Ok(Err("The title is required and can be up to 100 characters".to_string()));
+    }
+    if !LOGO_POSITIONS.iter().any(|(key, _)| *key == changes.logo_position) {
+        return Ok(Err("Unknown logo position".to_string()));
+    }
+    if let Some(logo_url) = changes.logo_url {
+        if !(logo_url.starts_with("https://") || logo_url.starts_with("http://") || logo_url.starts_with("data:image/")) {
+            return Ok(Err("The logo has to be an http(s) link or an embedded image".to_string()));
+        }
+        if logo_url.len() > MAX_LOGO_BYTES {
+            return Ok(Err("The logo is too large; use a link or a smaller image".to_string()));
+        }
+    }
+    if [changes.header_text, changes.terms_text, changes.footer_text]
+        .iter()
+        .flatten()
+        .any(|text| text.chars().count() > MAX_TEXT_CHARS)
+    {
+        return Ok(Err(format!("Text blocks can be up to {} characters", MAX_TEXT_CHARS)));
+    }
+    if changes.columns.iter().any(|c| !DOCUMENT_COLUMNS.iter().any(|(key, _)| key == c)) {
+        return Ok(Err("Unknown column".to_string()));
+    }
+    // Without either, nobody can tell the lines apart
+    if !changes.columns.iter().any(|c| c == "sku" || c == "description") {
+        return Ok(Err("Show the SKU or the description".to_string()));
+    }
+
+    let updated = sqlx::query(
+        r#"
+        UPDATE document_templates
+        SET title = $2, logo_url = $3, logo_position = $4, header_text = $5, terms_text = $6,
+            footer_text = $7, columns = $8, updated_by = $9, updated_at = NOW()
+        WHERE document_type = $1
+        "#,
+    )
+    .bind(document_type)
+    .bind(changes.title)
+    .bind(changes.logo_url)
+    .bind(changes.logo_position)
+    .bind(changes.header_text)
+    .bind(changes.terms_text)
+    .bind(changes.footer_text)
+    .bind(&changes.columns)
+    .bind(updated_by)
+    .execute(db)
+    .await?
+    .rows_affected();
+    if updated == 0 {
+        return Ok(Err("Unknown document type".to_string()));
+    }
+
+    Ok(Ok(()))
+}
+
+pub async fn organization_name(db: &Database) -> Result<String, sqlx::Error> {
+    let name = sqlx::query_scalar::<_, String>("SELECT name FROM organization")
+        .fetch_optional(db)
+        .await?;
+    Ok(name.unwrap_or_else(|| "Allo".to_string()))
+}
+
+// The document as a standalone HTML page laid out by `layout`; printing it
+// from the browser gives the PDF. The title the admin entered is used for
+// English, other languages get the catalog's name for the document. Text
+// blocks are printed as entered.
+pub fn render(layout: &DocumentTemplate, organization: &str, document: &Document) -> Result<String, askama::Error> {
+    let locale = locale::locale(document.language.as_deref());
+    let prices_shown = document.lines.iter().all(|line| line.unit_price.is_some());
+    let columns = DOCUMENT_COLUMNS
+        .iter()
+        .map(|(key, _)| *key)
+        .filter(|key| layout.shows(key))
+        .filter(|key| prices_shown || !matches!(*key, "unit_price" | "line_total"))
+        .collect();
+    let title = if locale.code == "en" { layout.title.as_str() } else { locale.text(&layout.document_type) };
+
+    RenderedDocument {
+        layout,
+        locale,
+        title,
+        organization,
+        document,
+        issued_on: locale.date(document.issued_on),
+        exchange_rate: document
+            .exchange_rate
+            .filter(|_| document.currency != document.base_currency)
+            .map(|rate| format!("1 {} = {} {}", document.currency, locale.decimal(rate, 4), document.base_currency)),
+        columns,
+        total: document.total().filter(|_| prices_shown).map(|total| locale.amount(total)),
+    }
+    .render()
+}
+
+// Made-up contents for previewing a layout in `language`
+pub fn sample(layout: &DocumentTemplate, language: Option<String>, today: NaiveDate) -> Document {
+    Document {
+        number: format!("{}-SAMPLE", layout.title.to_uppercase().replace(' ', "-")),
+        issued_on: today,
+        party_heading: "customer",
+        party_name: "Example Customer Ltd".to_string(),
+        reference: Some("PO-12345".to_string()),
+        currency: "USD".to_string(),
+        exchange_rate: Some(Decimal::ONE),
+        base_currency: "USD".to_string(),
+        lines: vec![
+            DocumentLine {
+                sku: "WID-100".to_string(),
+                customer_part_number: Some("EX-0042".to_string()),
+                description: "Standard widget".to_string(),
+                quantity: 1200,
+                unit_price: Some(Decimal::new(1250, 2)),
+            },
+            DocumentLine {
+                sku: "WID-200".to_string(),
+                customer_part_number: None,
+                description: "Heavy-duty widget".to_string(),
+                quantity: 2,
+                unit_price: Some(Decimal::new(8900, 2)),
+            },
+        ],
+        notes: None,
+        language,
+    }
+}
@@ -0,0 +1,129 @@
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::{
+    database::Database,
+    middleware::CurrentUser,
+    models::{Activity, Customer, Deal, Note},
+    services::{sharing::RecordKind, watchers, webhooks},
+};
+
+// Something that happened to a customer or deal. Published events go to the
+// webhooks subscribed to them and, as notifications, to the people watching
+// the records involved.
+pub enum Event<'a> {
+    CustomerCreated(&'a Customer),
+    CustomerUpdated(&'a Customer),
+    DealCreated(&'a Deal),
+    DealUpdated(&'a Deal),
+    DealStageChanged { deal: &'a Deal, previous_stage: &'a str },
+    ActivityCreated(&'a Activity),
+    // record_name is the customer's or deal's, for the notification
+    NoteCreated { note: &'a Note, kind: RecordKind, record_name: &'a str },
+}
+
+impl Event<'_> {
+    // As webhooks subscribe to it; see webhooks::WEBHOOK_EVENTS
+    fn name(&self) -> &'static str {
+        match self {
+            Self::CustomerCreated(_) => "customer.created",
+            Self::CustomerUpdated(_) => "customer.updated",
+            Self::DealCreated(_) => "deal.created",
+            Self::DealUpdated(_) => "deal.updated",
+            Self::DealStageChanged { .. } => "deal.stage_changed",
+            Self::ActivityCreated(_) => "activity.created",
+            Self::NoteCreated { .. } => "note.created",
+        }
+    }
+
+    fn data(&self) -> Value {
+        match self {
+            Self::CustomerCreated(customer) | Self::CustomerUpdated(customer) => json!(customer),
+            Self::DealCreated(deal) | Self::DealUpdated(deal) => json!(deal),
+            Self::DealStageChanged { deal, previous_stage } => {
+                let mut data = json!(deal);
+                data["previous_stage"] = json!(previous_stage);
+                data
+            }
+            Self::ActivityCreated(activity) => json!(activity),
+            Self::NoteCreated { note, .. } => json!(note),
+        }
+    }
+
+    // Whose watchers hear about it. A customer's watchers also hear about its
+    // deals and activities.
+    fn watched(&self) -> Vec<(RecordKind, Uuid)> {
+        match self {
+            // Nobody can be watching a record that didn't exist yet
+            Self::CustomerCreated(_) => Vec::new(),
+            Self::CustomerUpdated(customer) => vec![(RecordKind::Customer, customer.id)],
+            Self::DealCreated(deal) => vec![(RecordKind::Customer, deal.customer_id)],
+            Self::DealUpdated(deal) | Self::DealStageChanged { deal, .. } => {
+                vec![(RecordKind::Deal, deal.id), (RecordKind::Customer, deal.customer_id)]
+            }
+            Self::ActivityCreated(activity) => {
+                let mut records = vec![(RecordKind::Customer, activity.customer_id)];
+                records.extend(activity.deal_id.map(|id| (RecordKind::Deal, id)));
+                records
+            }
+            Self::NoteCreated { note, kind, .. } => vec![(*kind, note.record_id)],
+        }
+    }
+
+    fn notification(&self, actor: &str) -> (String, String) {
+        match self {
+            Self::CustomerCreated(customer) | Self::CustomerUpdated(customer) => (
+                format!("{} updated {}", actor, customer.company_name),
+                RecordKind::Customer.url(customer.id),
+            ),
+            Self::DealCreated(deal) => (
+                format!("{} opened the deal \"{}\"", actor, deal.title),
+                RecordKind::Deal.url(deal.id),
+            ),
+            Self::DealUpdated(deal) => (
+                format!("{} updated the deal \"{}\"", actor, deal.title),
+                RecordKind::Deal.url(deal.id),
+            ),
+            Self::DealStageChanged { deal, previous_stage } => (
+                format!(
+                    "{} moved the deal \"{}\" from {} to {}",
+                    actor,
+                    deal.title,
+                    stage_label(previous_stage),
+                    stage_label(&deal.stage)
+                ),
+                RecordKind::Deal.url(deal.id),
+            ),
+            Self::ActivityCreated(activity) => (
+                format!("{} logged a {}: {}", actor, activity.activity_type, activity.subject),
+                match activity.deal_id {
+                    Some(deal_id) => RecordKind::Deal.url(deal_id),
+                    None => RecordKind::Customer.url(activity.customer_id),
+                },
+            ),
+            Self::NoteCreated { note, kind, record_name } => (
+                format!("{} added a note to {}", actor, record_name),
+                format!("{}#notes", kind.url(note.record_id)),
+            ),
+        }
+    }
+}
+
+fn stage_label(stage: &str) -> String {
+    stage.replace('_', " ")
+}
+
+// Failures are logged rather than returned, so that nothing listening can
+// block the change itself
+pub async fn publish(db: &Database, actor: &CurrentUser, event: Event<'_>) {
+    webhooks::dispatch(db, event.name(), event.data()).await;
+
+    let watched = event.watched();
+    if watched.is_empty() {
+        return;
+    }
+    let (message, link_url) = event.notification(&format!("{} {}", actor.first_name, actor.last_name));
+    if let Err(e) = watchers::notify(db, &watched, actor.id, &message, &link_url).await {
+        tracing::error!("Error notifying watchers of {}: {}", event.name(), e);
+    }
+}
@@ -0,0 +1,81 @@
+use rust_decimal::Decimal;
+use uuid::Uuid;
+
+use crate::{database::Database, models::ExchangeRate, services::lookups};
+
+pub async fn base_currency(db: &Database) -> Result<String, sqlx::Error> {
+    let currency = sqlx::query_scalar::<_, String>("SELECT base_currency FROM organization")
+        .fetch_optional(db)
+        .await?;
+    Ok(currency.unwrap_or_else(|| "USD".to_string()))
+}
+
+// Every active currency other than the base one, with its rate if set
+pub async fn list(db: &Database) -> Result<Vec<ExchangeRate>, sqlx::Error> {
+    sqlx::query_as::<_, ExchangeRate>(
+        r#"
+        SELECT l.value as currency, l.label, r.rate, r.updated_at
+        FROM lookup_values l
+        LEFT JOIN exchange_rates r ON r.currency = l.value
+        WHERE l.kind = 'currency' AND l.is_active
+          AND l.value <> COALESCE((SELECT base_currency FROM organization LIMIT 1), 'USD')
+        ORDER BY l.sort_order, l.label
+        "#,
+    )
+    .fetch_all(db)
+    .await
+}
+
+// The rate to lock onto a new deal or order in `currency`: 1 for the base
+// currency, None when nobody has set one
+pub async fn rate_for(db: &Database, currency: &str) -> Result<Option<Decimal>, sqlx::Error> {
+    if currency == base_currency(db).await? {
+        return Ok(Some(Decimal::ONE));
+    }
+    sqlx::query_scalar::<_, Decimal>("SELECT rate FROM exchange_rates WHERE currency = $1")
+        .bind(currency)
+        .fetch_optional(db)
+        .await
+}
+
+// Set or, with None, clear today's rate. Locked rates on existing records
+// stay as they are. Returns why the change was refused, if it was.
+pub async fn update(
+    db: &Database,
+    currency: &str,
+    rate: Option<Decimal>,
+    updated_by: Uuid,
+) -> Result<Result<(), String>, sqlx::Error> {
+    if currency == base_currency(db).await? {
+        return Ok(Err("The base currency is always 1".to_string()));
+    }
+    if !lookups::is_allowed(db, "currency", currency).await? {
+        return Ok(Err("Unknown currency".to_string()));
+    }
+
+    let Some(rate) = rate else {
+        sqlx::query("DELETE FROM exchange_rates WHERE currency = $1")
+            .bind(currency)
+            .execute(db)
+            .await?;
+        return Ok(Ok(()));
+    };
+    if rate <= Decimal::ZERO || rate.round_dp(8) != rate || rate >= Decimal::from(10_000_000_000i64) {
+        return Ok(Err("The rate has to be above zero with at most 8 decimal places".to_string()));
+    }
+
+    sqlx::query(
+        r#"
+        INSERT INTO exchange_rates (currency, rate, updated_by, updated_at)
+        VALUES ($1, $2, $3, NOW())
+        ON CONFLICT (tenant_id, currency) DO UPDATE SET rate = $2, updated_by = $3, updated_at = NOW()
+        "#,
+    )
+    .bind(currency)
+    .bind(rate)
+    .bind(updated_by)
+    .execute(db)
+    .await?;
+
+    Ok(Ok(()))
+}
@@ -0,0 +1,211 @@
+use uuid::Uuid;
+
+use crate::{
+    database::Database,
+    middleware::CurrentUser,
+    models::HeldStock,
+    services::{
+        backorders,
+        locations,
+        sharing::{self, RecordKind},
+        stock::adjust_stock,
+    },
+};
+
+// Whose stock is being received
+pub enum Owner {
+    // A supplier, by name, who's consigned it to us
+    Consignor(String),
+    Customer(Uuid),
+}
+
+impl Owner {
+    fn ownership(&self) -> &'static str {
+        match self {
+            Owner::Consignor(_) => "consignment",
+            Owner::Customer(_) => "customer_owned",
+        }
+    }
+}
+
+// An item's held stock, and its variants', in the user's warehouses. Goods
+// owned by customers the viewer can't see are left out.
+pub async fn for_item(db: &Database, user: &CurrentUser, item_id: Uuid) -> Result<Vec<HeldStock>, sqlx::Error> {
+    let visible_to = sharing::is_scoped(user).then_some(user.id);
+    let scope = match visible_to {
+        Some(_) => format!("AND (h.customer_id IS NULL OR {})", sharing::visibility_condition(RecordKind::Customer, "c", 2)),
+        None => String::new(),
+    };
+    sqlx::query_as::<_, HeldStock>(&format!(
+        r#"
+        SELECT h.id, h.item_id, h.warehouse_id, w.name as warehouse_name, h.ownership, h.customer_id,
+               COALESCE(c.company_name, h.consignor) as owner_name, h.quantity_on_hand, h.updated_at
+        FROM held_stock h
+        JOIN inventory_items i ON i.id = h.item_id
+        JOIN warehouses w ON w.id = h.warehouse_id
+        LEFT JOIN customers c ON c.id = h.customer_id
+        WHERE (i.id = $1 OR i.parent_item_id = $1) AND h.quantity_on_hand > 0 AND {} {}
+        ORDER BY w.name, h.ownership, 7
+        "#,
+        locations::warehouse_condition("h.warehouse_id", 3),
+        scope
+    ))
+    .bind(item_id)
+    .bind(visible_to)
+    .bind(user.warehouse_ids.as_deref())
+    .fetch_all(db)
+    .await
+}
+
+pub async fn warehouse_of(db: &Database, id: Uuid) -> Result<Option<Uuid>, sqlx::Error> {
+    sqlx::query_scalar::<_, Uuid>("SELECT warehouse_id FROM held_stock WHERE id = $1")
+        .bind(id)
+        .fetch_optional(db)
+        .await
+}
+
+// Book in stock someone else owns. It's kept apart from ours, so it fills no
+// backorders and nothing can be reserved against it.
+pub async fn receive(
+    db: &Database,
+    item_id: Uuid,
+    warehouse_id: Uuid,
+    owner: &Owner,
+    quantity: i32,
+    moved_by: Uuid,
+) -> Result<Result<(), String>, sqlx::Error> {
+    if quantity <= 0 {
+        return Ok(Err("Enter a quantity above zero".to_string()));
+    }
+    let (customer_id, consignor) = match owner {
+        Owner::Consignor(name) => {
+            let name = name.trim();
+            if name.is_empty() {
+                return Ok(Err("Enter the supplier who consigned the stock".to_string()));
+            }
+            (None, Some(name))
+        }
+        Owner::Customer(customer_id) => (Some(*customer_id), None),
+    };
+
+    let mut tx = db.begin().await?;
+    sqlx::query(
+        r#"
+        INSERT INTO held_stock (item_id, warehouse_id, ownership, customer_id, consignor, quantity_on_hand)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        ON CONFLICT (item_id, warehouse_id, ownership, customer_id, consignor) DO UPDATE SET
+            quantity_on_hand = held_stock.quantity_on_hand + $6
+        "#,
+    )
+    .bind(item_id)
+    .bind(warehouse_id)
+    .bind(owner.ownership())
+    .bind(customer_id)
+    .bind(consignor)
+    .bind(quantity)
+    .execute(&mut *tx)
+    .await?;
+    let reason = match owner {
+        Owner::Consignor(_) => "Consignment receipt",
+        Owner::Customer(_) => "Customer goods received",
+    };
+    sqlx::query(
+        r#"
+        INSERT INTO stock_movements (item_id, to_warehouse_id, quantity, movement_type, reason, moved_by, ownership)
+        VALUES ($1, $2, $3, 'receipt', $4, $5, $6)
+        "#,
+    )
+    .bind(item_id)
+    .bind(warehouse_id)
+    .bind(quantity)
+    .bind(reason)
+    .bind(moved_by)
+    .bind(owner.ownership())
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    Ok(Ok(()))
+}
+
+// Let some of an item's held stock go: back to its owner, or, for a
+// consignment, into our own stock once we've bought it, where it fills
+// backorders like any other receipt. Returns how much went to backorders.
+pub async fn release(
+    db: &Database,
+    id: Uuid,
+    item_id: Uuid,
+    quantity: i32,
+    into_stock: bool,
+    moved_by: Uuid,
+) -> Result<Result<i32, String>, sqlx::Error> {
+    if quantity <= 0 {
+        return Ok(Err("Enter a quantity above zero".to_string()));
+    }
+
+    let mut tx = db.begin().await?;
+    let held = sqlx::query_as::<_, (Uuid, String, i32)>(
+        "SELECT warehouse_id, ownership, quantity_on_hand FROM held_stock WHERE id = $1 AND item_id = $2 FOR UPDATE",
+    )
+    .bind(id)
+    .bind(item_id)
+    .fetch_optional(&mut *tx)
+    .await?;
+    let Some((warehouse_id, ownership, on_hand)) = held else {
+        return Ok(Err("That stock is no longer held here".to_string()));
+    };
+    if quantity > on_hand {
+        return Ok(Err(format!("Only {} of it is held here", on_hand)));
+    }
+    if into_stock && ownership != "consignment" {
+        return Ok(Err("Only consigned stock can be taken into our own stock".to_string()));
+    }
+
+    if quantity == on_hand {
+        sqlx::query("DELETE FROM held_stock WHERE id = $1")
+            .bind(id)
+            .execute(&mut *tx)
+            .await?;
+    } else {
+        sqlx::query("UPDATE held_stock SET quantity_on_hand = quantity_on_hand - $2 WHERE id = $1")
+            .bind(id)
+            .bind(quantity)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    let allocated = if into_stock {
+        adjust_stock(&mut tx, item_id, warehouse_id, quantity, 0).await?;
+        sqlx::query(
+            r#"
+            INSERT INTO stock_movements (item_id, to_warehouse_id, quantity, movement_type, reason, moved_by)
+            VALUES ($1, $2, $3, 'purchase', 'Taken into stock from consignment', $4)
+            "#,
+        )
+        .bind(item_id)
+        .bind(warehouse_id)
+        .bind(quantity)
+        .bind(moved_by)
+        .execute(&mut *tx)
+        .await?;
+        backorders::allocate(&mut tx, item_id, warehouse_id).await?
+    } else {
+        sqlx::query(
+            r#"
+            INSERT INTO stock_movements (item_id, from_warehouse_id, quantity, movement_type, reason, moved_by, ownership)
+            VALUES ($1, $2, $3, 'return', 'Returned to its owner', $4, $5)
+            "#,
+        )
+        .bind(item_id)
+        .bind(warehouse_id)
+        .bind(quantity)
+        .bind(moved_by)
+        .bind(&ownership)
+        .execute(&mut *tx)
+        .await?;
+        0
+    };
+    tx.commit().await?;
+
+    Ok(Ok(allocated))
+}
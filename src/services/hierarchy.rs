@@ -0,0 +1,109 @@
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use sqlx::{FromRow, PgConnection};
+use uuid::Uuid;
+
+use crate::{database::Database, services::archive};
+
+// A user and everyone who reports to them, directly or indirectly ($1 = lead)
+const TEAM_SQL: &str = r#"
+    WITH RECURSIVE team AS (
+        SELECT id FROM users WHERE id = $1
+        UNION
+        SELECT u.id FROM users u JOIN team t ON u.manager_id = t.id
+    )
+    SELECT id FROM team
+"#;
+
+// Totals for one team lead across their whole reporting tree
+#[derive(Debug, FromRow)]
+pub struct TeamRollup {
+    pub lead_name: String,
+    pub team_size: i64,
+    pub open_pipeline: Decimal,
+    pub won_revenue: Decimal,
+    pub activities: i64,
+    pub expenses: Decimal,
+}
+
+pub async fn team_member_ids(db: &Database, lead_id: Uuid) -> Result<Vec<Uuid>, sqlx::Error> {
+    sqlx::query_scalar::<_, Uuid>(TEAM_SQL)
+        .bind(lead_id)
+        .fetch_all(db)
+        .await
+}
+
+pub async fn has_direct_reports(db: &Database, user_id: Uuid) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar::<_, bool>("SELECT EXISTS (SELECT 1 FROM users WHERE manager_id = $1)")
+        .bind(user_id)
+        .fetch_one(db)
+        .await
+}
+
+// Making `manager_id` the manager of `user_id` must not put the user above themselves
+pub async fn would_create_cycle(
+    db: &Database,
+    user_id: Uuid,
+    manager_id: Uuid,
+) -> Result<bool, sqlx::Error> {
+    Ok(team_member_ids(db, user_id).await?.contains(&manager_id))
+}
+
+// One row per user with direct reports, optionally limited to leads inside
+// `within` (a team member list). Pipeline is current; revenue, activities and
+// expenses fall within the date range, archived activities included when it
+// reaches back that far.
+pub async fn team_rollups(
+    conn: &mut PgConnection,
+    within: Option<&[Uuid]>,
+    date_from: NaiveDate,
+    date_to: NaiveDate,
+) -> Result<Vec<TeamRollup>, sqlx::Error> {
+    sqlx::query_as::<_, TeamRollup>(&format!(
+        r#"
+        WITH RECURSIVE tree AS (
+            SELECT id as lead_id, id as member_id FROM users
+            UNION
+            SELECT t.lead_id, u.id FROM users u JOIN tree t ON u.manager_id = t.member_id
+        ),
+        leads AS (
+            SELECT DISTINCT manager_id as id FROM users WHERE manager_id IS NOT NULL
+        )
+        SELECT
+            CONCAT(lu.first_name, ' ', lu.last_name) as lead_name,
+            (SELECT COUNT(*) FROM tree t WHERE t.lead_id = l.id) as team_size,
+            COALESCE((
+                SELECT SUM(d.base_value) FROM deals d
+                JOIN tree t ON t.member_id = COALESCE(d.assigned_to, d.created_by)
+                WHERE t.lead_id = l.id AND d.stage NOT IN ('closed_won', 'closed_lost')
+            ), 0) as open_pipeline,
+            COALESCE((
+                SELECT SUM(d.base_value) FROM deals d
+                JOIN tree t ON t.member_id = COALESCE(d.assigned_to, d.created_by)
+                WHERE t.lead_id = l.id AND d.stage = 'closed_won'
+                  AND COALESCE(d.actual_close_date, d.updated_at::date) BETWEEN $2 AND $3
+            ), 0) as won_revenue,
+            (
+                SELECT COUNT(*) FROM {} a
+                JOIN tree t ON t.member_id = COALESCE(a.assigned_to, a.created_by)
+                WHERE t.lead_id = l.id AND DATE(a.activity_date) BETWEEN $2 AND $3
+            ) as activities,
+            COALESCE((
+                SELECT SUM(e.amount) FROM expenses e
+                JOIN tree t ON t.member_id = e.user_id
+                WHERE t.lead_id = l.id AND e.status <> 'denied'
+                  AND e.expense_date BETWEEN $2 AND $3
+            ), 0) as expenses
+        FROM leads l
+        JOIN users lu ON lu.id = l.id
+        WHERE $1::uuid[] IS NULL OR l.id = ANY($1)
+        ORDER BY lu.first_name, lu.last_name
+        "#,
+        archive::activities_from(Some(date_from))
+    ))
+    .bind(within)
+    .bind(date_from)
+    .bind(date_to)
+    .fetch_all(conn)
+    .await
+}
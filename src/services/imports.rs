@@ -0,0 +1,1129 @@
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use std::{str::FromStr, time::Duration};
+use uuid::Uuid;
+
+use crate::{
+    database::Database,
+    middleware::{permission::get_user_by_id, CurrentUser},
+    models::{ImportError, Job, CUSTOMER_STATUSES},
+    services::{
+        cost_centers,
+        invitations::{self, NewInvitation},
+        jobs::{self, Step},
+        sharing::{self, Access, RecordKind},
+    },
+    utils::barcode::{normalize_gtin, normalize_sku},
+};
+
+pub const JOB_KIND: &str = "import";
+
+// Rows handled per job step; the job is requeued until the file is done
+const ROWS_PER_STEP: usize = 250;
+
+// Rows shown on the mapping page, checked as they would be imported
+pub const PREVIEW_ROWS: usize = 10;
+
+// Other names spreadsheets and CRMs give customer columns, for the initial
+// mapping of a staged import (after normalize_header)
+const CUSTOMER_ALIASES: &[(&str, &str)] = &[
+    ("company", "company_name"),
+    ("name", "company_name"),
+    ("account", "company_name"),
+    ("account_name", "company_name"),
+    ("organization", "company_name"),
+    ("organisation", "company_name"),
+    ("e_mail", "email"),
+    ("email_address", "email"),
+    ("e_mail_address", "email"),
+    ("phone_number", "phone"),
+    ("telephone", "phone"),
+    ("tel", "phone"),
+    ("web", "website"),
+    ("url", "website"),
+    ("sector", "industry"),
+    ("address", "address_line1"),
+    ("street", "address_line1"),
+    ("address_1", "address_line1"),
+    ("address_2", "address_line2"),
+    ("town", "city"),
+    ("province", "state"),
+    ("region", "state"),
+    ("zip", "postal_code"),
+    ("zip_code", "postal_code"),
+    ("postcode", "postal_code"),
+    ("comments", "notes"),
+    ("description", "notes"),
+];
+
+const ITEM_TYPES: &[&str] = &[
+    "Raw Materials",
+    "Work-in-Progress (WIP)",
+    "Finished Goods",
+    "Components / Parts",
+    "Consumables",
+    "Non-Inventory Items",
+    "Digital Goods",
+    "MRO Items",
+];
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ImportType {
+    Customers,
+    Contacts,
+    Inventory,
+    CardTransactions,
+    UserInvitations,
+    Users,
+}
+
+impl ImportType {
+    pub const ALL: [ImportType; 6] = [
+        Self::Customers,
+        Self::Contacts,
+        Self::Inventory,
+        Self::CardTransactions,
+        Self::UserInvitations,
+        Self::Users,
+    ];
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|kind| kind.key() == value)
+    }
+
+    pub fn key(self) -> &'static str {
+        match self {
+            Self::Customers => "customers",
+            Self::Contacts => "contacts",
+            Self::Inventory => "inventory",
+            Self::CardTransactions => "card_transactions",
+            Self::UserInvitations => "user_invitations",
+            Self::Users => "users",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Customers => "Customers",
+            Self::Contacts => "Contacts",
+            Self::Inventory => "Inventory Items",
+            Self::CardTransactions => "Card Transactions",
+            Self::UserInvitations => "Users (send invitations)",
+            Self::Users => "Users (create accounts)",
+        }
+    }
+
+    pub fn permission(self) -> &'static str {
+        match self {
+            Self::Customers | Self::Contacts => "customers:write",
+            Self::Inventory => "inventory:write",
+            Self::CardTransactions => "expenses:write",
+            Self::UserInvitations | Self::Users => "team:write",
+        }
+    }
+
+    pub fn required_columns(self) -> &'static [&'static str] {
+        match self {
+            Self::Customers => &["company_name"],
+            Self::Contacts => &["company_name", "first_name", "last_name"],
+            Self::Inventory => &["sku", "item_name", "item_type"],
+            Self::CardTransactions => &["date", "amount", "category", "cost_center"],
+            Self::UserInvitations | Self::Users => &["email"],
+        }
+    }
+
+    // Every column this kind of import reads, required ones first
+    pub fn fields(self) -> impl Iterator<Item = &'static str> {
+        self.required_columns().iter().chain(self.optional_columns()).copied()
+    }
+
+    pub fn optional_columns(self) -> &'static [&'static str] {
+        match self {
+            Self::Customers => &[
+                "email", "phone", "website", "industry", "status", "address_line1", "address_line2",
+                "city", "state", "postal_code", "country", "notes",
+            ],
+            Self::Contacts => &["title", "email", "phone", "mobile", "is_primary", "notes"],
+            Self::Inventory => &[
+                "upc", "category", "brand", "model", "description", "reorder_point",
+                "preferred_stock_level", "purchase_price", "selling_price",
+            ],
+            Self::CardTransactions => &["description", "customer"],
+            Self::UserInvitations | Self::Users => &["first_name", "last_name", "name", "roles", "manager_email"],
+        }
+    }
+}
+
+// What a customer import does with a row matching a customer that's
+// already there
+#[derive(Clone, Copy, PartialEq)]
+pub enum Duplicates {
+    Create,
+    Skip,
+    Merge,
+}
+
+impl Duplicates {
+    pub const ALL: [Duplicates; 3] = [Self::Skip, Self::Merge, Self::Create];
+
+    pub fn parse(value: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|mode| mode.key() == value)
+    }
+
+    pub fn key(self) -> &'static str {
+        match self {
+            Self::Create => "create",
+            Self::Skip => "skip",
+            Self::Merge => "merge",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Self::Create => "Import them as new customers anyway",
+            Self::Skip => "Skip them, leaving the existing customer as it is",
+            Self::Merge => "Merge them into the existing customer, filling in the file's non-blank values",
+        }
+    }
+}
+
+// What became of a row that wasn't rejected
+enum Imported {
+    Created,
+    Merged,
+    Skipped,
+}
+
+// Header names are matched case-insensitively, with spaces treated as underscores
+fn normalize_header(header: &str) -> String {
+    header.trim().to_lowercase().replace([' ', '-'], "_")
+}
+
+// A first guess at where each column goes: the field it's named after, or
+// for customers one it's commonly called. A field only gets its first column.
+fn guess_mapping(kind: ImportType, headers: &[String]) -> Vec<String> {
+    let mut used = Vec::new();
+    headers
+        .iter()
+        .map(|header| {
+            let field = kind.fields().find(|field| field == header).or_else(|| {
+                CUSTOMER_ALIASES
+                    .iter()
+                    .filter(|_| kind == ImportType::Customers)
+                    .find(|(alias, _)| alias == header)
+                    .map(|(_, field)| *field)
+            });
+            match field {
+                Some(field) if !used.contains(&field) => {
+                    used.push(field);
+                    field.to_string()
+                }
+                _ => String::new(),
+            }
+        })
+        .collect()
+}
+
+struct Row<'a> {
+    headers: &'a [String],
+    record: &'a csv::StringRecord,
+}
+
+impl Row<'_> {
+    // Trimmed value of a column, None when missing or blank
+    fn get(&self, column: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .position(|header| header == column)
+            .and_then(|index| self.record.get(index))
+            .map(str::trim)
+            .filter(|value| !value.is_empty())
+    }
+
+    fn require(&self, column: &str) -> Result<&str, String> {
+        self.get(column).ok_or_else(|| format!("{} is required", column))
+    }
+
+    fn owned(&self, column: &str) -> Option<String> {
+        self.get(column).map(str::to_string)
+    }
+}
+
+fn parse_amount(value: &str, column: &str) -> Result<Decimal, String> {
+    let cleaned: String = value.chars().filter(|c| !matches!(c, '$' | ',' | ' ')).collect();
+    Decimal::from_str(&cleaned).map_err(|_| format!("{} '{}' is not a number", column, value))
+}
+
+fn parse_count(value: Option<&str>, column: &str) -> Result<i32, String> {
+    match value {
+        None => Ok(0),
+        Some(value) => value
+            .parse::<i32>()
+            .ok()
+            .filter(|count| *count >= 0)
+            .ok_or_else(|| format!("{} '{}' must be a whole number", column, value)),
+    }
+}
+
+fn parse_date(value: &str) -> Result<NaiveDate, String> {
+    ["%Y-%m-%d", "%m/%d/%Y", "%d.%m.%Y"]
+        .iter()
+        .find_map(|format| NaiveDate::parse_from_str(value, format).ok())
+        .ok_or_else(|| format!("date '{}' should look like 2025-01-31 or 01/31/2025", value))
+}
+
+fn parse_bool(value: Option<&str>) -> Result<bool, String> {
+    match value.map(str::to_lowercase).as_deref() {
+        None | Some("false") | Some("no") | Some("n") | Some("0") => Ok(false),
+        Some("true") | Some("yes") | Some("y") | Some("1") => Ok(true),
+        Some(other) => Err(format!("'{}' is not yes or no", other)),
+    }
+}
+
+fn check_email(value: Option<&str>) -> Result<(), String> {
+    match value {
+        Some(email) if !email.contains('@') => Err(format!("email '{}' is not valid", email)),
+        _ => Ok(()),
+    }
+}
+
+// Constraint violations reject the row; anything else (lost connection etc.) fails the job
+fn rejected<T>(e: sqlx::Error) -> Result<Result<T, String>, sqlx::Error> {
+    match &e {
+        sqlx::Error::Database(db_err) => Ok(Err(match db_err.constraint() {
+            Some("inventory_items_sku_key" | "inventory_items_sku_upper_key") => "an item with this SKU already exists".to_string(),
+            Some("inventory_items_upc_key") => "an item with this UPC already exists".to_string(),
+            Some("users_email_key") => "a user with this email already exists".to_string(),
+            _ => db_err.message().to_string(),
+        })),
+        _ => Err(e),
+    }
+}
+
+// Customers the importing user (bound at $2) can see and whose `column`
+// matches $1, ignoring case; at most two, which is enough to tell one from many
+async fn customers_matching(
+    db: &Database,
+    user: &CurrentUser,
+    column: &str,
+    value: &str,
+) -> Result<Vec<(Uuid, String)>, sqlx::Error> {
+    let visible = if sharing::is_scoped(user) {
+        sharing::visibility_condition(RecordKind::Customer, "c", 2)
+    } else {
+        "($2::uuid IS NOT NULL)".to_string()
+    };
+
+    sqlx::query_as::<_, (Uuid, String)>(&format!(
+        "SELECT c.id, c.company_name FROM customers c WHERE LOWER(c.{}) = LOWER($1) AND {} LIMIT 2",
+        column, visible
+    ))
+    .bind(value)
+    .bind(user.id)
+    .fetch_all(db)
+    .await
+}
+
+// Look up a customer the importing user can see by exact company name
+async fn find_customer(db: &Database, user: &CurrentUser, company_name: &str) -> Result<Result<Uuid, String>, sqlx::Error> {
+    let matches = customers_matching(db, user, "company_name", company_name).await?;
+
+    Ok(match matches.as_slice() {
+        [(id, _)] => Ok(*id),
+        [] => Err(format!("no customer named '{}'", company_name)),
+        _ => Err(format!("more than one customer is named '{}'", company_name)),
+    })
+}
+
+// A customer row that passed its checks
+struct CustomerRow<'r> {
+    company_name: &'r str,
+    // Only when the row gives one; new customers default to prospect
+    status: Option<String>,
+    // The customer it duplicates, looked up unless duplicates are imported anyway
+    existing: Option<(Uuid, String)>,
+}
+
+// Checks a customer row and looks for a customer it duplicates, matching by
+// email first and then by company name
+async fn check_customer<'r>(
+    db: &Database,
+    user: &CurrentUser,
+    row: &'r Row<'_>,
+    duplicates: Duplicates,
+) -> Result<Result<CustomerRow<'r>, String>, sqlx::Error> {
+    let company_name = match row.require("company_name") {
+        Ok(name) => name,
+        Err(reason) => return Ok(Err(reason)),
+    };
+    let status = row.get("status").map(str::to_lowercase);
+    if status.as_deref().is_some_and(|status| !CUSTOMER_STATUSES.contains(&status)) {
+        return Ok(Err(format!("status must be one of {}", CUSTOMER_STATUSES.join(", "))));
+    }
+    if let Err(reason) = check_email(row.get("email")) {
+        return Ok(Err(reason));
+    }
+
+    let mut existing = None;
+    if duplicates != Duplicates::Create {
+        let by_email = match row.get("email") {
+            Some(email) => customers_matching(db, user, "email", email).await?,
+            None => Vec::new(),
+        };
+        let matches = if by_email.is_empty() {
+            customers_matching(db, user, "company_name", company_name).await?
+        } else {
+            by_email
+        };
+        existing = match matches.as_slice() {
+            [] => None,
+            [found] => Some(found.clone()),
+            _ => return Ok(Err("matches more than one existing customer".to_string())),
+        };
+    }
+
+    if let (Some((id, name)), Duplicates::Merge) = (&existing, duplicates) {
+        if sharing::access_level(db, user, RecordKind::Customer, *id).await? < Access::Write {
+            return Ok(Err(format!("matches '{}', which you can't edit", name)));
+        }
+    }
+
+    Ok(Ok(CustomerRow { company_name, status, existing }))
+}
+
+// Fills in the existing customer from the row. Blank cells leave what's
+// there; the company name is kept, as the match may have been by email.
+async fn merge_customer(db: &Database, id: Uuid, row: &Row<'_>, status: Option<String>) -> Result<Result<Imported, String>, sqlx::Error> {
+    let result = sqlx::query(
+        r#"
+        UPDATE customers SET
+            email = COALESCE($2, email), phone = COALESCE($3, phone), website = COALESCE($4, website),
+            industry = COALESCE($5, industry), status = COALESCE($6, status),
+            address_line1 = COALESCE($7, address_line1), address_line2 = COALESCE($8, address_line2),
+            city = COALESCE($9, city), state = COALESCE($10, state), postal_code = COALESCE($11, postal_code),
+            country = COALESCE($12, country), notes = COALESCE($13, notes), updated_at = NOW()
+        WHERE id = $1
+        "#,
+    )
+    .bind(id)
+    .bind(row.owned("email"))
+    .bind(row.owned("phone"))
+    .bind(row.owned("website"))
+    .bind(row.owned("industry"))
+    .bind(status)
+    .bind(row.owned("address_line1"))
+    .bind(row.owned("address_line2"))
+    .bind(row.owned("city"))
+    .bind(row.owned("state"))
+    .bind(row.owned("postal_code"))
+    .bind(row.owned("country"))
+    .bind(row.owned("notes"))
+    .execute(db)
+    .await;
+
+    result.map(|_| Ok(Imported::Merged)).or_else(rejected)
+}
+
+async fn import_customer(
+    db: &Database,
+    user: &CurrentUser,
+    row: &Row<'_>,
+    duplicates: Duplicates,
+) -> Result<Result<Imported, String>, sqlx::Error> {
+    let CustomerRow { company_name, status, existing } = match check_customer(db, user, row, duplicates).await? {
+        Ok(checked) => checked,
+        Err(reason) => return Ok(Err(reason)),
+    };
+    match (existing, duplicates) {
+        (Some(_), Duplicates::Skip) => return Ok(Ok(Imported::Skipped)),
+        (Some((id, _)), Duplicates::Merge) => return merge_customer(db, id, row, status).await,
+        _ => {}
+    }
+    let status = status.unwrap_or_else(|| "prospect".to_string());
+
+    let result = sqlx::query(
+        r#"
+        INSERT INTO customers (
+            company_name, email, phone, website, industry, status, address_line1, address_line2,
+            city, state, postal_code, country, notes, lead_source, created_by
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, 'import', $14)
+        "#,
+    )
+    .bind(company_name)
+    .bind(row.owned("email"))
+    .bind(row.owned("phone"))
+    .bind(row.owned("website"))
+    .bind(row.owned("industry"))
+    .bind(&status)
+    .bind(row.owned("address_line1"))
+    .bind(row.owned("address_line2"))
+    .bind(row.owned("city"))
+    .bind(row.owned("state"))
+    .bind(row.owned("postal_code"))
+    .bind(row.owned("country"))
+    .bind(row.owned("notes"))
+    .bind(user.id)
+    .execute(db)
+    .await;
+
+    result.map(|_| Ok(Imported::Created)).or_else(rejected)
+}
+
+async fn import_contact(db: &Database, user: &CurrentUser, row: &Row<'_>) -> Result<Result<(), String>, sqlx::Error> {
+    let (company_name, first_name, last_name) =
+        match (row.require("company_name"), row.require("first_name"), row.require("last_name")) {
+            (Ok(company), Ok(first), Ok(last)) => (company, first, last),
+            (Err(reason), _, _) | (_, Err(reason), _) | (_, _, Err(reason)) => return Ok(Err(reason)),
+        };
+    if let Err(reason) = check_email(row.get("email")) {
+        return Ok(Err(reason));
+    }
+    let is_primary = match parse_bool(row.get("is_primary")) {
+        Ok(is_primary) => is_primary,
+        Err(reason) => return Ok(Err(format!("is_primary: {}", reason))),
+    };
+    let customer_id = match find_customer(db, user, company_name).await? {
+        Ok(id) => id,
+        Err(reason) => return Ok(Err(reason)),
+    };
+
+    let result = sqlx::query(
+        r#"
+        INSERT INTO contacts (customer_id, first_name, last_name, title, email, phone, mobile, is_primary, notes, created_by)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10)
+        "#,
+    )
+    .bind(customer_id)
+    .bind(first_name)
+    .bind(last_name)
+    .bind(row.owned("title"))
+    .bind(row.owned("email"))
+    .bind(row.owned("phone"))
+    .bind(row.owned("mobile"))
+    .bind(is_primary)
+    .bind(row.owned("notes"))
+    .bind(user.id)
+    .execute(db)
+    .await;
+
+    result.map(|_| Ok(())).or_else(rejected)
+}
+
+async fn import_inventory_item(db: &Database, user: &CurrentUser, row: &Row<'_>) -> Result<Result<(), String>, sqlx::Error> {
+    let (sku, item_name, item_type) = match (row.require("sku"), row.require("item_name"), row.require("item_type")) {
+        (Ok(sku), Ok(name), Ok(kind)) => (sku, name, kind),
+        (Err(reason), _, _) | (_, Err(reason), _) | (_, _, Err(reason)) => return Ok(Err(reason)),
+    };
+    let Some(item_type) = ITEM_TYPES.iter().find(|known| known.eq_ignore_ascii_case(item_type)) else {
+        return Ok(Err(format!("item_type must be one of {}", ITEM_TYPES.join(", "))));
+    };
+    let sku = normalize_sku(sku);
+    let upc = match row.get("upc").map(normalize_gtin).transpose() {
+        Ok(upc) => upc,
+        Err(reason) => return Ok(Err(reason)),
+    };
+
+    let parsed = (|| {
+        let reorder_point = parse_count(row.get("reorder_point"), "reorder_point")?;
+        let preferred_stock_level = parse_count(row.get("preferred_stock_level"), "preferred_stock_level")?;
+        let purchase_price = row.get("purchase_price").map(|v| parse_amount(v, "purchase_price")).transpose()?;
+        let selling_price = row.get("selling_price").map(|v| parse_amount(v, "selling_price")).transpose()?;
+        Ok::<_, String>((reorder_point, preferred_stock_level, purchase_price, selling_price))
+    })();
+    let (reorder_point, preferred_stock_level, purchase_price, selling_price) = match parsed {
+        Ok(values) => values,
+        Err(reason) => return Ok(Err(reason)),
+    };
+
+    let result = sqlx::query(
+        r#"
+        INSERT INTO inventory_items (
+            item_name, sku, upc, item_type, category, brand, model, description,
+            reorder_point, preferred_stock_level, purchase_price, selling_price, created_by
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
+        "#,
+    )
+    .bind(item_name)
+    .bind(sku)
+    .bind(upc)
+    .bind(item_type)
+    .bind(row.owned("category"))
+    .bind(row.owned("brand"))
+    .bind(row.owned("model"))
+    .bind(row.owned("description"))
+    .bind(reorder_point)
+    .bind(preferred_stock_level)
+    // Same rule as the item form: costs are only taken from users who can see them
+    .bind(purchase_price.filter(|_| user.has_finance_read))
+    .bind(selling_price)
+    .bind(user.id)
+    .execute(db)
+    .await;
+
+    result.map(|_| Ok(())).or_else(rejected)
+}
+
+// Card statement lines become pending expenses for the importing user
+async fn import_card_transaction(db: &Database, user: &CurrentUser, row: &Row<'_>) -> Result<Result<(), String>, sqlx::Error> {
+    let parsed = (|| {
+        let expense_date = parse_date(row.require("date")?)?;
+        // Statements show charges as either sign; the expense is the size of the charge
+        let amount = parse_amount(row.require("amount")?, "amount")?.abs();
+        if amount.is_zero() {
+            return Err("amount must not be zero".to_string());
+        }
+        Ok((expense_date, amount, row.require("category")?, row.require("cost_center")?))
+    })();
+    let (expense_date, amount, category, cost_center) = match parsed {
+        Ok(values) => values,
+        Err(reason) => return Ok(Err(reason)),
+    };
+
+    let category_id = sqlx::query_scalar::<_, Uuid>(
+        "SELECT id FROM expense_categories WHERE LOWER(name) = LOWER($1) AND is_active = true",
+    )
+    .bind(category)
+    .fetch_optional(db)
+    .await?;
+    let Some(category_id) = category_id else {
+        return Ok(Err(format!("no active expense category named '{}'", category)));
+    };
+    let Some(cost_center_id) = cost_centers::find_active(db, cost_center).await? else {
+        return Ok(Err(format!("no active cost center '{}'", cost_center)));
+    };
+
+    let customer_id = match row.get("customer") {
+        Some(company_name) => match find_customer(db, user, company_name).await? {
+            Ok(id) => Some(id),
+            Err(reason) => return Ok(Err(reason)),
+        },
+        None => None,
+    };
+
+    let result = sqlx::query(
+        "INSERT INTO expenses (user_id, category_id, cost_center_id, customer_id, amount, description, expense_date) VALUES ($1, $2, $3, $4, $5, $6, $7)",
+    )
+    .bind(user.id)
+    .bind(category_id)
+    .bind(cost_center_id)
+    .bind(customer_id)
+    .bind(amount)
+    .bind(row.owned("description"))
+    .bind(expense_date)
+    .execute(db)
+    .await;
+
+    result.map(|_| Ok(())).or_else(rejected)
+}
+
+// Role names are separated by semicolons, commas or pipes and must match an
+// active role exactly, apart from case
+async fn find_roles(db: &Database, names: Option<&str>) -> Result<Result<Vec<Uuid>, String>, sqlx::Error> {
+    let mut role_ids = Vec::new();
+    for name in names.unwrap_or_default().split([';', ',', '|']).map(str::trim).filter(|name| !name.is_empty()) {
+        let role_id = sqlx::query_scalar::<_, Uuid>("SELECT id FROM roles WHERE LOWER(name) = LOWER($1) AND is_active = true")
+            .bind(name)
+            .fetch_optional(db)
+            .await?;
+        match role_id {
+            Some(id) => role_ids.push(id),
+            None => return Ok(Err(format!("no active role named '{}'", name))),
+        }
+    }
+    Ok(Ok(role_ids))
+}
+
+// Invitations and accounts go through the same checks as the invite form.
+// A single name column is split at the first space.
+async fn import_user(db: &Database, user: &CurrentUser, row: &Row<'_>, create: bool) -> Result<Result<(), String>, sqlx::Error> {
+    let email = match row.require("email") {
+        Ok(email) if email.contains('@') && email.len() <= 255 => email,
+        Ok(email) => return Ok(Err(format!("email '{}' is not valid", email))),
+        Err(reason) => return Ok(Err(reason)),
+    };
+    let (first_name, last_name) = match (row.get("first_name"), row.get("last_name"), row.get("name")) {
+        (None, None, Some(name)) => name.split_once(' ').map(|(first, last)| (first, last.trim())).unwrap_or((name, "")),
+        (first, last, _) => (first.unwrap_or_default(), last.unwrap_or_default()),
+    };
+
+    let role_ids = match find_roles(db, row.get("roles")).await? {
+        Ok(role_ids) => role_ids,
+        Err(reason) => return Ok(Err(reason)),
+    };
+    let manager_id = match row.get("manager_email") {
+        Some(manager_email) => {
+            let manager_id = sqlx::query_scalar::<_, Uuid>(
+                "SELECT id FROM users WHERE LOWER(email) = LOWER($1) AND is_active = true",
+            )
+            .bind(manager_email)
+            .fetch_optional(db)
+            .await?;
+            match manager_id {
+                Some(id) => Some(id),
+                None => return Ok(Err(format!("no active user with email '{}' to be the manager", manager_email))),
+            }
+        }
+        None => None,
+    };
+
+    let existing = sqlx::query_scalar::<_, bool>("SELECT EXISTS(SELECT 1 FROM users WHERE LOWER(email) = LOWER($1))")
+        .bind(email)
+        .fetch_one(db)
+        .await?;
+    if existing {
+        return Ok(Err("a user with this email already exists".to_string()));
+    }
+
+    let invite = NewInvitation { email, first_name, last_name, role_ids: &role_ids, manager_id };
+    let inviter_name = format!("{} {}", user.first_name, user.last_name);
+    let result = if create {
+        invitations::create_account(db, invite, user.id, &inviter_name).await
+    } else {
+        invitations::send(db, invite, None, user.id, &inviter_name).await
+    };
+    let id = match result {
+        Ok(id) => id,
+        Err(e) => return rejected(e),
+    };
+
+    let (action, resource_type) = if create { ("create", "user") } else { ("invite", "user_invitation") };
+    sqlx::query(
+        r#"
+        INSERT INTO audit_logs (user_id, action, resource_type, resource_id, new_values)
+        VALUES ($1, $2, $3, $4, $5)
+        "#,
+    )
+    .bind(user.id)
+    .bind(action)
+    .bind(resource_type)
+    .bind(id)
+    .bind(serde_json::json!({ "email": email, "role_ids": role_ids, "manager_id": manager_id, "source": "import" }))
+    .execute(db)
+    .await?;
+
+    Ok(Ok(()))
+}
+
+async fn import_row(
+    db: &Database,
+    kind: ImportType,
+    user: &CurrentUser,
+    row: &Row<'_>,
+    duplicates: Duplicates,
+) -> Result<Result<Imported, String>, sqlx::Error> {
+    let created = |outcome: Result<(), String>| outcome.map(|()| Imported::Created);
+    match kind {
+        ImportType::Customers => import_customer(db, user, row, duplicates).await,
+        ImportType::Contacts => import_contact(db, user, row).await.map(created),
+        ImportType::Inventory => import_inventory_item(db, user, row).await.map(created),
+        ImportType::CardTransactions => import_card_transaction(db, user, row).await.map(created),
+        ImportType::UserInvitations => import_user(db, user, row, false).await.map(created),
+        ImportType::Users => import_user(db, user, row, true).await.map(created),
+    }
+}
+
+fn read_headers(reader: &mut csv::Reader<&[u8]>) -> Result<Vec<String>, String> {
+    reader
+        .headers()
+        .map(|headers| headers.iter().map(normalize_header).collect())
+        .map_err(|e| format!("The file could not be read as CSV: {}", e))
+}
+
+// The file's normalized headers and how many rows follow them
+fn read_file(content: &str) -> Result<(Vec<String>, i32), String> {
+    let mut reader = csv::ReaderBuilder::new().flexible(true).from_reader(content.as_bytes());
+    let headers = read_headers(&mut reader)?;
+    let total_rows = reader.records().count();
+    if total_rows == 0 {
+        return Err("The file has a header row but no data.".to_string());
+    }
+    Ok((headers, total_rows.min(i32::MAX as usize) as i32))
+}
+
+fn missing_columns(kind: ImportType, columns: &[String]) -> Option<String> {
+    let missing: Vec<&str> = kind
+        .required_columns()
+        .iter()
+        .copied()
+        .filter(|column| !columns.iter().any(|header| header == column))
+        .collect();
+    (!missing.is_empty()).then(|| missing.join(", "))
+}
+
+async fn insert_import(
+    db: &Database,
+    kind: ImportType,
+    file_name: &str,
+    content: &str,
+    total_rows: i32,
+    created_by: Uuid,
+    // The initial mapping and duplicates choice of a staged import
+    staged: Option<(Vec<String>, Duplicates)>,
+) -> Result<Uuid, sqlx::Error> {
+    let (mapping, duplicates) = match staged {
+        Some((mapping, duplicates)) => (Some(mapping), duplicates),
+        None => (None, Duplicates::Create),
+    };
+    sqlx::query_scalar::<_, Uuid>(
+        r#"
+        INSERT INTO imports (import_type, file_name, content, total_rows, created_by, mapping, staged, duplicates)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        RETURNING id
+        "#,
+    )
+    .bind(kind.key())
+    .bind(file_name)
+    .bind(content)
+    .bind(total_rows)
+    .bind(created_by)
+    .bind(mapping.as_ref().map(sqlx::types::Json))
+    .bind(mapping.is_some())
+    .bind(duplicates.key())
+    .fetch_one(db)
+    .await
+}
+
+// Check the file's columns and queue it. Problems with the file as a whole
+// come back as a message; problems with single rows are reported once it runs.
+pub async fn start(
+    db: &Database,
+    kind: ImportType,
+    file_name: &str,
+    content: String,
+    created_by: Uuid,
+) -> Result<Result<Uuid, String>, sqlx::Error> {
+    let (headers, total_rows) = match read_file(&content) {
+        Ok(file) => file,
+        Err(message) => return Ok(Err(message)),
+    };
+    if let Some(missing) = missing_columns(kind, &headers) {
+        return Ok(Err(format!("The file is missing required columns: {}", missing)));
+    }
+
+    let import_id = insert_import(db, kind, file_name, &content, total_rows, created_by, None).await?;
+    queue(db, import_id, kind, file_name, created_by).await?;
+    Ok(Ok(import_id))
+}
+
+// Keep the file for its columns to be mapped on the mapping page, starting
+// from a guess and with duplicates skipped. Nothing is imported until the
+// mapping is confirmed.
+pub async fn stage(
+    db: &Database,
+    kind: ImportType,
+    file_name: &str,
+    content: String,
+    created_by: Uuid,
+) -> Result<Result<Uuid, String>, sqlx::Error> {
+    let (headers, total_rows) = match read_file(&content) {
+        Ok(file) => file,
+        Err(message) => return Ok(Err(message)),
+    };
+    let mapping = guess_mapping(kind, &headers);
+    insert_import(db, kind, file_name, &content, total_rows, created_by, Some((mapping, Duplicates::Skip)))
+        .await
+        .map(Ok)
+}
+
+async fn queue(db: &Database, import_id: Uuid, kind: ImportType, file_name: &str, created_by: Uuid) -> Result<(), sqlx::Error> {
+    let job_id = jobs::enqueue(
+        db,
+        JOB_KIND,
+        &format!("Import {}: {}", kind.label().to_lowercase(), file_name),
+        serde_json::json!({ "import_id": import_id }),
+        Some(created_by),
+    )
+    .await?;
+
+    sqlx::query("UPDATE imports SET job_id = $1, staged = false WHERE id = $2")
+        .bind(job_id)
+        .bind(import_id)
+        .execute(db)
+        .await?;
+
+    Ok(())
+}
+
+// An uploaded file waiting on the mapping page
+pub struct StagedImport {
+    pub id: Uuid,
+    pub kind: ImportType,
+    pub file_name: String,
+    pub total_rows: i32,
+    // As they appear in the file
+    pub headers: Vec<String>,
+    pub mapping: Vec<String>,
+    pub duplicates: Duplicates,
+    content: String,
+}
+
+pub async fn load_staged(db: &Database, import_id: Uuid) -> Result<Option<StagedImport>, sqlx::Error> {
+    let row = sqlx::query_as::<_, (String, String, i32, String, sqlx::types::Json<Vec<String>>, String)>(
+        r#"
+        SELECT import_type, file_name, total_rows, content, mapping, duplicates
+        FROM imports WHERE id = $1 AND staged AND mapping IS NOT NULL
+        "#,
+    )
+    .bind(import_id)
+    .fetch_optional(db)
+    .await?;
+
+    Ok(row.and_then(|(import_type, file_name, total_rows, content, mapping, duplicates)| {
+        let mut reader = csv::ReaderBuilder::new().flexible(true).from_reader(content.as_bytes());
+        let headers = reader.headers().ok()?.iter().map(|header| header.trim().to_string()).collect();
+        Some(StagedImport {
+            id: import_id,
+            kind: ImportType::parse(&import_type)?,
+            file_name,
+            total_rows,
+            headers,
+            mapping: mapping.0,
+            duplicates: Duplicates::parse(&duplicates).unwrap_or(Duplicates::Create),
+            content,
+        })
+    }))
+}
+
+// Save a mapping from the mapping page: a field (or '' for none) per column
+pub async fn save_mapping(
+    db: &Database,
+    staged: &mut StagedImport,
+    mapping: Vec<String>,
+    duplicates: Duplicates,
+) -> Result<Result<(), String>, sqlx::Error> {
+    if mapping.len() != staged.headers.len() {
+        return Ok(Err("Every column needs a choice.".to_string()));
+    }
+    let mut used: Vec<&str> = Vec::new();
+    for field in mapping.iter().filter(|field| !field.is_empty()) {
+        if !staged.kind.fields().any(|known| known == field) {
+            return Ok(Err(format!("'{}' isn't a field that can be imported.", field)));
+        }
+        if used.contains(&field.as_str()) {
+            return Ok(Err(format!("More than one column goes to {}.", field)));
+        }
+        used.push(field);
+    }
+
+    sqlx::query("UPDATE imports SET mapping = $2, duplicates = $3 WHERE id = $1 AND staged")
+        .bind(staged.id)
+        .bind(sqlx::types::Json(&mapping))
+        .bind(duplicates.key())
+        .execute(db)
+        .await?;
+    staged.mapping = mapping;
+    staged.duplicates = duplicates;
+    Ok(Ok(()))
+}
+
+// Start importing a staged file with its saved mapping
+pub async fn confirm(db: &Database, staged: &StagedImport, created_by: Uuid) -> Result<Result<(), String>, sqlx::Error> {
+    if let Some(missing) = missing_columns(staged.kind, &staged.mapping) {
+        return Ok(Err(format!("Choose a column for {}.", missing)));
+    }
+    queue(db, staged.id, staged.kind, &staged.file_name, created_by).await?;
+    Ok(Ok(()))
+}
+
+// One row of the preview: its values for the mapped columns, in file order,
+// and what importing it would do
+pub struct PreviewRow {
+    pub row_number: i32,
+    pub values: Vec<String>,
+    pub outcome: String,
+    pub rejected: bool,
+}
+
+// The first rows of a staged customer import, checked against the data
+// without writing anything
+pub async fn preview(db: &Database, user: &CurrentUser, staged: &StagedImport) -> Result<Vec<PreviewRow>, sqlx::Error> {
+    let mut reader = csv::ReaderBuilder::new().flexible(true).from_reader(staged.content.as_bytes());
+    let mapped: Vec<usize> = (0..staged.mapping.len()).filter(|&index| !staged.mapping[index].is_empty()).collect();
+    let mut rows = Vec::new();
+
+    for result in reader.records().take(PREVIEW_ROWS) {
+        let Ok(record) = result else {
+            rows.push(PreviewRow {
+                row_number: 0,
+                values: vec![String::new(); mapped.len()],
+                outcome: "could not be read".to_string(),
+                rejected: true,
+            });
+            continue;
+        };
+        let row = Row { headers: &staged.mapping, record: &record };
+        let (outcome, rejected) = match check_customer(db, user, &row, staged.duplicates).await? {
+            Err(reason) => (reason, true),
+            Ok(CustomerRow { existing: Some((_, name)), .. }) if staged.duplicates == Duplicates::Skip => {
+                (format!("Skipped: matches '{}'", name), false)
+            }
+            Ok(CustomerRow { existing: Some((_, name)), .. }) => (format!("Merged into '{}'", name), false),
+            Ok(_) => ("New customer".to_string(), false),
+        };
+        rows.push(PreviewRow {
+            row_number: record.position().map(|p| p.line() as i32).unwrap_or(0),
+            values: mapped.iter().map(|&index| record.get(index).unwrap_or("").trim().to_string()).collect(),
+            outcome,
+            rejected,
+        });
+    }
+    Ok(rows)
+}
+
+// One step of an import: the next batch of rows after those already processed.
+// Each row stands alone, so a bad row is recorded and the rest carry on.
+pub async fn run_import_job(db: &Database, job: &Job) -> Result<Step, sqlx::Error> {
+    let import_id = job
+        .payload
+        .get("import_id")
+        .and_then(|id| id.as_str())
+        .and_then(|id| Uuid::parse_str(id).ok())
+        .ok_or_else(|| sqlx::Error::Protocol("Job payload has no import_id".to_string()))?;
+
+    let (import_type, content, created_by, mapping, duplicates) =
+        sqlx::query_as::<_, (String, String, Option<Uuid>, Option<sqlx::types::Json<Vec<String>>>, String)>(
+            "SELECT import_type, content, created_by, mapping, duplicates FROM imports WHERE id = $1",
+        )
+    .bind(import_id)
+    .fetch_one(db)
+    .await?;
+
+    let kind = ImportType::parse(&import_type)
+        .ok_or_else(|| sqlx::Error::Protocol(format!("Unknown import type '{}'", import_type)))?;
+
+    // Rows are imported as the person who uploaded the file, with their current access
+    let user = match created_by {
+        Some(user_id) => get_user_by_id(db, user_id).await,
+        None => None,
+    }
+    .ok_or_else(|| sqlx::Error::Protocol("The user who started this import is no longer active".to_string()))?;
+    if !user.permissions.iter().any(|p| p == kind.permission()) {
+        return Err(sqlx::Error::Protocol(format!(
+            "{} {} no longer has the {} permission",
+            user.first_name,
+            user.last_name,
+            kind.permission()
+        )));
+    }
+
+    let mut reader = csv::ReaderBuilder::new().flexible(true).from_reader(content.as_bytes());
+    let headers = read_headers(&mut reader).map_err(sqlx::Error::Protocol)?;
+    // A mapped file's columns go where the mapping says, not by their names
+    let headers = mapping.map(|mapping| mapping.0).unwrap_or(headers);
+    let duplicates = Duplicates::parse(&duplicates).unwrap_or(Duplicates::Create);
+
+    let offset = job.progress_done.max(0) as usize;
+    let mut processed = 0;
+    let mut imported = 0;
+    let mut merged = 0;
+    let mut skipped = 0;
+    let mut rejected = 0;
+
+    for result in reader.records().skip(offset).take(ROWS_PER_STEP) {
+        processed += 1;
+
+        let (row_number, fields, outcome) = match result {
+            Ok(record) => {
+                let row_number = record.position().map(|p| p.line() as i32).unwrap_or(0);
+                let fields: Vec<String> = record.iter().map(str::to_string).collect();
+                let outcome = import_row(db, kind, &user, &Row { headers: &headers, record: &record }, duplicates).await?;
+                (row_number, fields, outcome)
+            }
+            Err(e) => {
+                let row_number = e.position().map(|p| p.line() as i32).unwrap_or(0);
+                (row_number, Vec::new(), Err(format!("could not be read: {}", e)))
+            }
+        };
+
+        match outcome {
+            Ok(Imported::Created) => imported += 1,
+            Ok(Imported::Merged) => merged += 1,
+            Ok(Imported::Skipped) => skipped += 1,
+            Err(reason) => {
+                rejected += 1;
+                sqlx::query("INSERT INTO import_errors (import_id, row_number, fields, reason) VALUES ($1, $2, $3, $4)")
+                    .bind(import_id)
+                    .bind(row_number)
+                    .bind(sqlx::types::Json(&fields))
+                    .bind(&reason)
+                    .execute(db)
+                    .await?;
+            }
+        }
+    }
+
+    let (total_rows, imported_rows, rejected_rows, merged_rows, skipped_rows) = sqlx::query_as::<_, (i32, i32, i32, i32, i32)>(
+        r#"
+        UPDATE imports SET imported_rows = imported_rows + $2, rejected_rows = rejected_rows + $3,
+                           merged_rows = merged_rows + $4, skipped_rows = skipped_rows + $5
+        WHERE id = $1
+        RETURNING total_rows, imported_rows, rejected_rows, merged_rows, skipped_rows
+        "#,
+    )
+    .bind(import_id)
+    .bind(imported)
+    .bind(rejected)
+    .bind(merged)
+    .bind(skipped)
+    .fetch_one(db)
+    .await?;
+
+    let done = (offset + processed) as i64;
+    jobs::set_progress(db, job.id, done, Some(i64::from(total_rows))).await?;
+
+    if processed < ROWS_PER_STEP {
+        jobs::log(
+            db,
+            job.id,
+            if rejected_rows > 0 { "warning" } else { "info" },
+            &format!(
+                "Imported {} of {} rows; {} merged, {} skipped as duplicates, {} rejected",
+                imported_rows, total_rows, merged_rows, skipped_rows, rejected_rows
+            ),
+        )
+        .await?;
+        Ok(Step::Done)
+    } else {
+        Ok(Step::Continue(Duration::ZERO))
+    }
+}
+
+// Rejected rows as CSV: the original header and fields, then the reason
+pub async fn error_report(db: &Database, import_id: Uuid) -> Result<Vec<u8>, sqlx::Error> {
+    let content = sqlx::query_scalar::<_, String>("SELECT content FROM imports WHERE id = $1")
+        .bind(import_id)
+        .fetch_one(db)
+        .await?;
+
+    let errors = sqlx::query_as::<_, ImportError>(
+        "SELECT row_number, fields, reason FROM import_errors WHERE import_id = $1 ORDER BY row_number",
+    )
+    .bind(import_id)
+    .fetch_all(db)
+    .await?;
+
+    let mut reader = csv::ReaderBuilder::new().flexible(true).from_reader(content.as_bytes());
+    let headers: Vec<String> = reader
+        .headers()
+        .map(|headers| headers.iter().map(str::to_string).collect())
+        .unwrap_or_default();
+
+    let mut writer = csv::WriterBuilder::new().flexible(true).from_writer(Vec::new());
+    let io_error = |e: csv::Error| sqlx::Error::Protocol(e.to_string());
+
+    let mut header_row = vec!["row".to_string()];
+    header_row.extend(headers);
+    header_row.push("error".to_string());
+    writer.write_record(&header_row).map_err(io_error)?;
+
+    for error in errors {
+        let mut record = vec![error.row_number.to_string()];
+        record.extend(error.fields.0);
+        record.push(error.reason);
+        writer.write_record(&record).map_err(io_error)?;
+    }
+
+    writer.into_inner().map_err(|e| sqlx::Error::Protocol(e.to_string()))
+}
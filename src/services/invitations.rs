@@ -0,0 +1,270 @@
+use chrono::{Duration, Utc};
+use rand::{distributions::Alphanumeric, Rng};
+use sha2::{Digest, Sha256};
+use sqlx::{Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    database::Database,
+    models::{Invitation, User, INVITATION_SELECT},
+    services::mailer,
+};
+
+// How long an invitation link stays usable
+const VALID_DAYS: i64 = 7;
+
+pub struct NewInvitation<'a> {
+    pub email: &'a str,
+    pub first_name: &'a str,
+    pub last_name: &'a str,
+    pub role_ids: &'a [Uuid],
+    pub manager_id: Option<Uuid>,
+}
+
+fn hash_token(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
+
+// Invitation rows for an address; inviting it again replaces any invitation
+// still outstanding for it, so only the newest link works. Returns the id and
+// the token to email.
+async fn issue(
+    tx: &mut Transaction<'_, Postgres>,
+    invite: &NewInvitation<'_>,
+    user_id: Option<Uuid>,
+    invited_by: Uuid,
+) -> Result<(Uuid, String), sqlx::Error> {
+    let token: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(40)
+        .map(char::from)
+        .collect();
+
+    sqlx::query(
+        r#"
+        UPDATE user_invitations SET revoked_at = NOW()
+        WHERE LOWER(email) = LOWER($1) AND accepted_at IS NULL AND revoked_at IS NULL
+        "#,
+    )
+    .bind(invite.email)
+    .execute(&mut **tx)
+    .await?;
+
+    let id = sqlx::query_scalar::<_, Uuid>(
+        r#"
+        INSERT INTO user_invitations
+            (email, first_name, last_name, role_ids, manager_id, token_hash, invited_by, expires_at, user_id)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9)
+        RETURNING id
+        "#,
+    )
+    .bind(invite.email)
+    .bind(invite.first_name)
+    .bind(invite.last_name)
+    .bind(invite.role_ids)
+    .bind(invite.manager_id)
+    .bind(hash_token(&token))
+    .bind(invited_by)
+    .bind(Utc::now() + Duration::days(VALID_DAYS))
+    .bind(user_id)
+    .fetch_one(&mut **tx)
+    .await?;
+
+    Ok((id, token))
+}
+
+async fn email_link(
+    db: &Database,
+    invite: &NewInvitation<'_>,
+    token: &str,
+    invited_by: Uuid,
+    inviter_name: &str,
+) -> Result<(), sqlx::Error> {
+    let greeting = match invite.first_name {
+        "" => "Hello,".to_string(),
+        name => format!("Hello {},", name),
+    };
+    let body = format!(
+        "{}\n\n{} has invited you to join Allo. Set your password to activate your account:\n\n{}/invite/{}\n\nThis link can be used once and expires in {} days.",
+        greeting,
+        inviter_name,
+        mailer::app_url(),
+        token,
+        VALID_DAYS
+    );
+    mailer::queue_email_as(db, invited_by, invite.email, "You're invited to Allo", &mailer::text_to_html(&body)).await?;
+    Ok(())
+}
+
+// Create an invitation and email its link. `user_id` is an account already
+// made for the address that is still waiting for its password.
+pub async fn send(
+    db: &Database,
+    invite: NewInvitation<'_>,
+    user_id: Option<Uuid>,
+    invited_by: Uuid,
+    inviter_name: &str,
+) -> Result<Uuid, sqlx::Error> {
+    let mut tx = db.begin().await?;
+    let (id, token) = issue(&mut tx, &invite, user_id, invited_by).await?;
+    tx.commit().await?;
+
+    email_link(db, &invite, &token, invited_by, inviter_name).await?;
+    Ok(id)
+}
+
+// Create the account now, with its roles and manager, so it can be assigned
+// work straight away, and invite its owner to set a password. The account has
+// no password until the invitation is accepted, so nobody can sign in to it.
+pub async fn create_account(
+    db: &Database,
+    invite: NewInvitation<'_>,
+    invited_by: Uuid,
+    inviter_name: &str,
+) -> Result<Uuid, sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    let user_id = sqlx::query_scalar::<_, Uuid>(
+        r#"
+        INSERT INTO users (email, password_hash, first_name, last_name, manager_id)
+        VALUES ($1, '', $2, $3, $4)
+        RETURNING id
+        "#,
+    )
+    .bind(invite.email)
+    .bind(invite.first_name)
+    .bind(invite.last_name)
+    .bind(invite.manager_id)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO user_roles (user_id, role_id, assigned_by)
+        SELECT $1, r.id, $2 FROM roles r WHERE r.id = ANY($3) AND r.is_active = true
+        "#,
+    )
+    .bind(user_id)
+    .bind(invited_by)
+    .bind(invite.role_ids)
+    .execute(&mut *tx)
+    .await?;
+
+    let (_, token) = issue(&mut tx, &invite, Some(user_id), invited_by).await?;
+    tx.commit().await?;
+
+    email_link(db, &invite, &token, invited_by, inviter_name).await?;
+    Ok(user_id)
+}
+
+// The invitation a link belongs to, if it can still be accepted
+pub async fn find_open(db: &Database, token: &str) -> Result<Option<Invitation>, sqlx::Error> {
+    sqlx::query_as::<_, Invitation>(&format!(
+        r#"{} WHERE i.token_hash = $1 AND i.accepted_at IS NULL AND i.revoked_at IS NULL AND i.expires_at > NOW()"#,
+        INVITATION_SELECT
+    ))
+    .bind(hash_token(token))
+    .fetch_optional(db)
+    .await
+}
+
+pub async fn list_recent(db: &Database) -> Result<Vec<Invitation>, sqlx::Error> {
+    sqlx::query_as::<_, Invitation>(&format!("{} ORDER BY i.created_at DESC LIMIT 50", INVITATION_SELECT))
+        .fetch_all(db)
+        .await
+}
+
+// Create the invited account with the roles picked on the invitation. Returns
+// None if the invitation was used or withdrawn in the meantime.
+pub async fn accept(
+    db: &Database,
+    invitation: &Invitation,
+    first_name: &str,
+    last_name: &str,
+    password_hash: &str,
+) -> Result<Option<User>, sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    let claimed = sqlx::query(
+        r#"
+        UPDATE user_invitations SET accepted_at = NOW()
+        WHERE id = $1 AND accepted_at IS NULL AND revoked_at IS NULL AND expires_at > NOW()
+        "#,
+    )
+    .bind(invitation.id)
+    .execute(&mut *tx)
+    .await?;
+    if claimed.rows_affected() == 0 {
+        return Ok(None);
+    }
+
+    // Accounts made ahead of time already have their roles and manager
+    let existing = match invitation.user_id {
+        Some(user_id) => {
+            sqlx::query_as::<_, User>(
+                "UPDATE users SET password_hash = $2, first_name = $3, last_name = $4 WHERE id = $1 RETURNING *",
+            )
+            .bind(user_id)
+            .bind(password_hash)
+            .bind(first_name)
+            .bind(last_name)
+            .fetch_optional(&mut *tx)
+            .await?
+        }
+        None => None,
+    };
+
+    let user = match existing {
+        Some(user) => user,
+        None => {
+            let user = sqlx::query_as::<_, User>(
+                r#"
+                INSERT INTO users (email, password_hash, first_name, last_name, manager_id)
+                VALUES ($1, $2, $3, $4, $5)
+                RETURNING *
+                "#,
+            )
+            .bind(&invitation.email)
+            .bind(password_hash)
+            .bind(first_name)
+            .bind(last_name)
+            .bind(invitation.manager_id)
+            .fetch_one(&mut *tx)
+            .await?;
+
+            // Roles deactivated since the invite was sent are skipped
+            sqlx::query(
+                r#"
+                INSERT INTO user_roles (user_id, role_id, assigned_by)
+                SELECT $1, r.id, $2 FROM roles r WHERE r.id = ANY($3) AND r.is_active = true
+                "#,
+            )
+            .bind(user.id)
+            .bind(invitation.invited_by)
+            .bind(&invitation.role_ids)
+            .execute(&mut *tx)
+            .await?;
+
+            user
+        }
+    };
+
+    sqlx::query("UPDATE user_invitations SET user_id = $1 WHERE id = $2")
+        .bind(user.id)
+        .bind(invitation.id)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+    Ok(Some(user))
+}
+
+pub async fn revoke(db: &Database, id: Uuid) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query(
+        "UPDATE user_invitations SET revoked_at = NOW() WHERE id = $1 AND accepted_at IS NULL AND revoked_at IS NULL",
+    )
+    .bind(id)
+    .execute(db)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
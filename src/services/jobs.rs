@@ -0,0 +1,213 @@
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::{
+    database::Database,
+    models::Job,
+    services::{imports, mass_email},
+};
+
+// Jobs still marked running after this long without an update are assumed
+// to have been interrupted (e.g. by a restart) and are picked up again
+const STALLED_AFTER_MINUTES: i32 = 10;
+
+// Jobs handled per scheduler tick, so one busy queue can't starve the rest
+const JOBS_PER_RUN: usize = 20;
+
+// What a job wants after a step of work
+pub enum Step {
+    Done,
+    // Requeue for another step after the delay, e.g. to respect a send rate
+    Continue(Duration),
+}
+
+pub async fn enqueue(
+    db: &Database,
+    kind: &str,
+    description: &str,
+    payload: serde_json::Value,
+    created_by: Option<Uuid>,
+) -> Result<Uuid, sqlx::Error> {
+    sqlx::query_scalar::<_, Uuid>(
+        "INSERT INTO jobs (kind, description, payload, created_by) VALUES ($1, $2, $3, $4) RETURNING id",
+    )
+    .bind(kind)
+    .bind(description)
+    .bind(payload)
+    .bind(created_by)
+    .fetch_one(db)
+    .await
+}
+
+pub async fn log(db: &Database, job_id: Uuid, level: &str, message: &str) -> Result<(), sqlx::Error> {
+    sqlx::query("INSERT INTO job_logs (job_id, level, message) VALUES ($1, $2, $3)")
+        .bind(job_id)
+        .bind(level)
+        .bind(message)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+pub async fn set_progress(db: &Database, job_id: Uuid, done: i64, total: Option<i64>) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE jobs SET progress_done = $2, progress_total = COALESCE($3, progress_total) WHERE id = $1")
+        .bind(job_id)
+        .bind(done.min(i64::from(i32::MAX)) as i32)
+        .bind(total.map(|total| total.min(i64::from(i32::MAX)) as i32))
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+// Put a failed or cancelled job back on the queue
+pub async fn retry(db: &Database, job_id: Uuid, retried_by: Uuid) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query(
+        r#"
+        UPDATE jobs
+        SET status = 'queued', run_after = NOW(), retries = retries + 1, last_error = NULL, finished_at = NULL
+        WHERE id = $1 AND status IN ('failed', 'cancelled')
+        "#,
+    )
+    .bind(job_id)
+    .execute(db)
+    .await?;
+
+    if result.rows_affected() == 0 {
+        return Ok(false);
+    }
+    log(db, job_id, "info", &format!("Retried by {}", user_name(db, retried_by).await?)).await?;
+    Ok(true)
+}
+
+// Stop a queued or running job. A running job finishes its current step, and
+// the result of that step is discarded.
+pub async fn cancel(db: &Database, job_id: Uuid, cancelled_by: Uuid) -> Result<bool, sqlx::Error> {
+    let job = sqlx::query_as::<_, Job>(
+        r#"
+        UPDATE jobs SET status = 'cancelled', finished_at = NOW()
+        WHERE id = $1 AND status IN ('queued', 'running')
+        RETURNING *
+        "#,
+    )
+    .bind(job_id)
+    .fetch_optional(db)
+    .await?;
+
+    let Some(job) = job else {
+        return Ok(false);
+    };
+
+    if job.kind == mass_email::JOB_KIND {
+        mass_email::cancel_send_job(db, &job).await?;
+    }
+    log(db, job_id, "warning", &format!("Cancelled by {}", user_name(db, cancelled_by).await?)).await?;
+    Ok(true)
+}
+
+async fn user_name(db: &Database, user_id: Uuid) -> Result<String, sqlx::Error> {
+    sqlx::query_scalar::<_, String>("SELECT CONCAT(first_name, ' ', last_name) FROM users WHERE id = $1")
+        .bind(user_id)
+        .fetch_optional(db)
+        .await
+        .map(|name| name.unwrap_or_else(|| "an unknown user".to_string()))
+}
+
+async fn execute(db: &Database, job: &Job) -> Result<Step, sqlx::Error> {
+    match job.kind.as_str() {
+        mass_email::JOB_KIND => mass_email::run_send_job(db, job).await,
+        imports::JOB_KIND => imports::run_import_job(db, job).await,
+        other => Err(sqlx::Error::Protocol(format!("Unknown job kind '{}'", other))),
+    }
+}
+
+async fn claim_next(db: &Database) -> Result<Option<(Job, bool)>, sqlx::Error> {
+    let claimed = sqlx::query_as::<_, (Uuid, bool)>(
+        r#"
+        WITH next AS (
+            SELECT id, status = 'running' as stalled
+            FROM jobs
+            WHERE (status = 'queued' AND run_after <= NOW())
+               OR (status = 'running' AND updated_at < NOW() - make_interval(mins => $1))
+            ORDER BY run_after
+            LIMIT 1
+            FOR UPDATE SKIP LOCKED
+        )
+        UPDATE jobs j
+        SET status = 'running', started_at = COALESCE(j.started_at, NOW())
+        FROM next
+        WHERE j.id = next.id
+        RETURNING j.id, next.stalled
+        "#,
+    )
+    .bind(STALLED_AFTER_MINUTES)
+    .fetch_optional(db)
+    .await?;
+
+    let Some((job_id, stalled)) = claimed else {
+        return Ok(None);
+    };
+
+    let job = sqlx::query_as::<_, Job>("SELECT * FROM jobs WHERE id = $1")
+        .bind(job_id)
+        .fetch_one(db)
+        .await?;
+    Ok(Some((job, stalled)))
+}
+
+// Run a step of each job that is due. Called from the scheduler.
+pub async fn run_due(db: &Database) -> Result<usize, sqlx::Error> {
+    let mut ran = 0;
+
+    while ran < JOBS_PER_RUN {
+        let Some((job, stalled)) = claim_next(db).await? else {
+            break;
+        };
+        ran += 1;
+
+        if stalled {
+            log(db, job.id, "warning", "Picked up again after the previous run stopped responding").await?;
+        }
+
+        match execute(db, &job).await {
+            Ok(Step::Done) => {
+                let finished = sqlx::query(
+                    "UPDATE jobs SET status = 'succeeded', finished_at = NOW() WHERE id = $1 AND status = 'running'",
+                )
+                .bind(job.id)
+                .execute(db)
+                .await?;
+                if finished.rows_affected() > 0 {
+                    log(db, job.id, "info", "Finished").await?;
+                }
+            }
+            Ok(Step::Continue(delay)) => {
+                sqlx::query(
+                    r#"
+                    UPDATE jobs SET status = 'queued', run_after = NOW() + make_interval(secs => $2)
+                    WHERE id = $1 AND status = 'running'
+                    "#,
+                )
+                .bind(job.id)
+                .bind(delay.as_secs_f64())
+                .execute(db)
+                .await?;
+            }
+            Err(e) => {
+                tracing::error!("Job {} ({}) failed: {}", job.id, job.kind, e);
+                sqlx::query(
+                    r#"
+                    UPDATE jobs SET status = 'failed', last_error = $2, finished_at = NOW()
+                    WHERE id = $1 AND status = 'running'
+                    "#,
+                )
+                .bind(job.id)
+                .bind(e.to_string())
+                .execute(db)
+                .await?;
+                log(db, job.id, "error", &e.to_string()).await?;
+            }
+        }
+    }
+
+    Ok(ran)
+}
@@ -0,0 +1,273 @@
+use rust_decimal::Decimal;
+use uuid::Uuid;
+
+use crate::{
+    database::Database,
+    middleware::CurrentUser,
+    models::{Customer, Deal, Lead, LEAD_SELECT, LEAD_SOURCES, LEAD_STATUSES},
+    services::sharing::{self, RecordKind},
+};
+
+// A lead as entered on /crm/leads
+pub struct LeadInput<'a> {
+    pub first_name: &'a str,
+    pub last_name: &'a str,
+    pub company_name: Option<&'a str>,
+    pub title: Option<&'a str>,
+    pub email: Option<&'a str>,
+    pub phone: Option<&'a str>,
+    pub lead_source: Option<&'a str>,
+    pub campaign_id: Option<Uuid>,
+    pub status: &'a str,
+    pub notes: Option<&'a str>,
+    pub assigned_to: Option<Uuid>,
+}
+
+// The deal opened alongside the customer, when asked for
+pub struct NewDeal<'a> {
+    pub title: &'a str,
+    pub value: Option<Decimal>,
+    pub currency: &'a str,
+    pub stage: &'a str,
+    pub probability: i32,
+    pub exchange_rate: Option<Decimal>,
+}
+
+pub struct Converted {
+    pub customer: Customer,
+    pub contact_id: Uuid,
+    pub deal: Option<Deal>,
+}
+
+fn validate(input: &LeadInput) -> Result<(), String> {
+    if input.first_name.is_empty() || input.last_name.is_empty() {
+        return Err("First and last name are required".to_string());
+    }
+    if input.first_name.chars().count() > 100 || input.last_name.chars().count() > 100 {
+        return Err("Names can be up to 100 characters".to_string());
+    }
+    if input.email.is_some_and(|email| !email.contains('@')) {
+        return Err("Enter a valid email address".to_string());
+    }
+    if !LEAD_STATUSES.contains(&input.status) {
+        return Err("Unknown lead status".to_string());
+    }
+    if input.lead_source.is_some_and(|source| !LEAD_SOURCES.iter().any(|(value, _)| *value == source)) {
+        return Err("Unknown lead source".to_string());
+    }
+    Ok(())
+}
+
+// Open leads, or those with the given status, newest first. Scoped users
+// see the leads they or their reports own.
+pub async fn list(db: &Database, user: &CurrentUser, status: Option<&str>) -> Result<Vec<Lead>, sqlx::Error> {
+    let mut conditions = vec![match status {
+        Some(_) => "l.status = $2".to_string(),
+        None => "l.status NOT IN ('converted', 'disqualified')".to_string(),
+    }];
+    if sharing::is_scoped(user) {
+        conditions.push(sharing::visibility_condition(RecordKind::Lead, "l", 1));
+    }
+
+    sqlx::query_as::<_, Lead>(&format!(
+        "{} WHERE {} ORDER BY l.created_at DESC",
+        LEAD_SELECT,
+        conditions.join(" AND ")
+    ))
+    .bind(user.id)
+    .bind(status)
+    .fetch_all(db)
+    .await
+}
+
+pub async fn find(db: &Database, id: Uuid) -> Result<Option<Lead>, sqlx::Error> {
+    sqlx::query_as::<_, Lead>(&format!("{} WHERE l.id = $1", LEAD_SELECT))
+        .bind(id)
+        .fetch_optional(db)
+        .await
+}
+
+pub async fn create(db: &Database, input: &LeadInput<'_>, created_by: Uuid) -> Result<Result<Uuid, String>, sqlx::Error> {
+    if let Err(error) = validate(input) {
+        return Ok(Err(error));
+    }
+
+    let id = sqlx::query_scalar::<_, Uuid>(
+        r#"
+        INSERT INTO leads (
+            first_name, last_name, company_name, title, email, phone,
+            lead_source, campaign_id, status, notes, assigned_to, created_by
+        )
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12)
+        RETURNING id
+        "#,
+    )
+    .bind(input.first_name)
+    .bind(input.last_name)
+    .bind(input.company_name)
+    .bind(input.title)
+    .bind(input.email)
+    .bind(input.phone)
+    .bind(input.lead_source)
+    .bind(input.campaign_id)
+    .bind(input.status)
+    .bind(input.notes)
+    .bind(input.assigned_to)
+    .bind(created_by)
+    .fetch_one(db)
+    .await?;
+
+    Ok(Ok(id))
+}
+
+// Converted leads are kept as they were converted
+pub async fn update(db: &Database, id: Uuid, input: &LeadInput<'_>) -> Result<Result<(), String>, sqlx::Error> {
+    if let Err(error) = validate(input) {
+        return Ok(Err(error));
+    }
+
+    let updated = sqlx::query(
+        r#"
+        UPDATE leads SET
+            first_name = $2, last_name = $3, company_name = $4, title = $5, email = $6, phone = $7,
+            lead_source = $8, campaign_id = $9, status = $10, notes = $11, assigned_to = $12
+        WHERE id = $1 AND status <> 'converted'
+        "#,
+    )
+    .bind(id)
+    .bind(input.first_name)
+    .bind(input.last_name)
+    .bind(input.company_name)
+    .bind(input.title)
+    .bind(input.email)
+    .bind(input.phone)
+    .bind(input.lead_source)
+    .bind(input.campaign_id)
+    .bind(input.status)
+    .bind(input.notes)
+    .bind(input.assigned_to)
+    .execute(db)
+    .await?;
+
+    if updated.rows_affected() == 0 {
+        return Ok(Err("A converted lead can't be changed".to_string()));
+    }
+    Ok(Ok(()))
+}
+
+// Turns the lead into a customer named `company_name` with the lead as its
+// primary contact, plus the deal if one is given, all or nothing. The lead's
+// source, campaign and owner carry over so reports still credit them.
+pub async fn convert(
+    db: &Database,
+    id: Uuid,
+    company_name: &str,
+    deal: Option<NewDeal<'_>>,
+    actor: Uuid,
+) -> Result<Result<Converted, String>, sqlx::Error> {
+    if company_name.is_empty() || company_name.chars().count() > 255 {
+        return Ok(Err("The company name is required and can be up to 255 characters".to_string()));
+    }
+    if deal.as_ref().is_some_and(|deal| deal.title.is_empty()) {
+        return Ok(Err("The deal needs a title".to_string()));
+    }
+
+    let mut tx = db.begin().await?;
+
+    // Locked so two people converting at once can't make two customers
+    let lead = sqlx::query_as::<_, Lead>(&format!("{} WHERE l.id = $1 FOR UPDATE OF l", LEAD_SELECT))
+        .bind(id)
+        .fetch_optional(&mut *tx)
+        .await?;
+    let lead = match lead {
+        Some(lead) if lead.is_converted() => return Ok(Err("This lead has already been converted".to_string())),
+        Some(lead) if lead.status == "disqualified" => {
+            return Ok(Err("Requalify the lead before converting it".to_string()))
+        }
+        Some(lead) => lead,
+        None => return Ok(Err("Lead not found".to_string())),
+    };
+
+    let customer = sqlx::query_as::<_, Customer>(
+        r#"
+        INSERT INTO customers (company_name, email, phone, status, notes, campaign_id, lead_source, assigned_to, created_by)
+        VALUES ($1, $2, $3, 'prospect', $4, $5, $6, $7, $8)
+        RETURNING *
+        "#,
+    )
+    .bind(company_name)
+    .bind(&lead.email)
+    .bind(&lead.phone)
+    .bind(&lead.notes)
+    .bind(lead.campaign_id)
+    .bind(&lead.lead_source)
+    .bind(lead.assigned_to)
+    .bind(actor)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    let contact_id = sqlx::query_scalar::<_, Uuid>(
+        r#"
+        INSERT INTO contacts (customer_id, first_name, last_name, title, email, phone, is_primary, created_by)
+        VALUES ($1, $2, $3, $4, $5, $6, true, $7)
+        RETURNING id
+        "#,
+    )
+    .bind(customer.id)
+    .bind(&lead.first_name)
+    .bind(&lead.last_name)
+    .bind(&lead.title)
+    .bind(&lead.email)
+    .bind(&lead.phone)
+    .bind(actor)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    let deal = match deal {
+        Some(deal) => Some(
+            sqlx::query_as::<_, Deal>(
+                r#"
+                INSERT INTO deals (
+                    customer_id, contact_id, title, value, currency, stage, probability,
+                    created_by, campaign_id, assigned_to, exchange_rate
+                )
+                VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11)
+                RETURNING *
+                "#,
+            )
+            .bind(customer.id)
+            .bind(contact_id)
+            .bind(deal.title)
+            .bind(deal.value)
+            .bind(deal.currency)
+            .bind(deal.stage)
+            .bind(deal.probability)
+            .bind(actor)
+            .bind(lead.campaign_id)
+            .bind(lead.assigned_to)
+            .bind(deal.exchange_rate)
+            .fetch_one(&mut *tx)
+            .await?,
+        ),
+        None => None,
+    };
+
+    sqlx::query(
+        r#"
+        UPDATE leads SET
+            status = 'converted', converted_customer_id = $2, converted_deal_id = $3,
+            converted_at = NOW(), converted_by = $4
+        WHERE id = $1
+        "#,
+    )
+    .bind(id)
+    .bind(customer.id)
+    .bind(deal.as_ref().map(|deal| deal.id))
+    .bind(actor)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(Ok(Converted { customer, contact_id, deal }))
+}
@@ -0,0 +1,198 @@
+use sqlx::{Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    database::Database,
+    middleware::CurrentUser,
+    models::{PickListLine, Warehouse, WarehouseLocation, WarehouseSummary},
+    services::sharing::{self, RecordKind},
+};
+
+// Selects a WarehouseLocation; alias the location `l`
+const LOCATION_SELECT: &str = r#"
+    SELECT l.id, l.warehouse_id, l.code, l.aisle, l.bin, l.walk_order, l.capacity, l.created_at,
+           COALESCE((SELECT SUM(sl.quantity_on_hand) FROM stock_levels sl WHERE sl.location_id = l.id), 0)::BIGINT as units_stored
+    FROM warehouse_locations l
+"#;
+
+// SQL condition keeping rows whose warehouse, `column`, is one of the
+// CurrentUser::warehouse_ids bound at `param`; binding None keeps them all
+pub fn warehouse_condition(column: &str, param: usize) -> String {
+    format!("(${0}::uuid[] IS NULL OR {1} = ANY(${0}::uuid[]))", param, column)
+}
+
+pub async fn warehouses(db: &Database, user: &CurrentUser) -> Result<Vec<WarehouseSummary>, sqlx::Error> {
+    sqlx::query_as::<_, WarehouseSummary>(&format!(
+        r#"
+        SELECT w.id, w.name, w.location, w.is_active,
+               (SELECT COUNT(*) FROM warehouse_locations l WHERE l.warehouse_id = w.id) as location_count,
+               COALESCE((SELECT SUM(sl.quantity_on_hand) FROM stock_levels sl WHERE sl.warehouse_id = w.id), 0)::BIGINT as units_on_hand
+        FROM warehouses w
+        WHERE {}
+        ORDER BY w.is_active DESC, w.name
+        "#,
+        warehouse_condition("w.id", 1)
+    ))
+    .bind(user.warehouse_ids.as_deref())
+    .fetch_all(db)
+    .await
+}
+
+// Where the user can receive or move stock
+pub async fn active_warehouses(db: &Database, user: &CurrentUser) -> Result<Vec<Warehouse>, sqlx::Error> {
+    sqlx::query_as::<_, Warehouse>(&format!(
+        "SELECT * FROM warehouses w WHERE w.is_active = true AND {} ORDER BY w.name",
+        warehouse_condition("w.id", 1)
+    ))
+    .bind(user.warehouse_ids.as_deref())
+    .fetch_all(db)
+    .await
+}
+
+pub async fn find_warehouse(db: &Database, id: Uuid) -> Result<Option<Warehouse>, sqlx::Error> {
+    sqlx::query_as::<_, Warehouse>("SELECT * FROM warehouses WHERE id = $1")
+        .bind(id)
+        .fetch_optional(db)
+        .await
+}
+
+// In walk order
+pub async fn for_warehouse(db: &Database, warehouse_id: Uuid) -> Result<Vec<WarehouseLocation>, sqlx::Error> {
+    sqlx::query_as::<_, WarehouseLocation>(&format!(
+        "{} WHERE l.warehouse_id = $1 ORDER BY l.walk_order, l.code",
+        LOCATION_SELECT
+    ))
+    .bind(warehouse_id)
+    .fetch_all(db)
+    .await
+}
+
+pub struct NewLocation {
+    pub code: String,
+    pub aisle: Option<String>,
+    pub bin: Option<String>,
+    pub walk_order: i32,
+    pub capacity: Option<i32>,
+}
+
+// Returns why it was refused, if it was
+pub async fn add(db: &Database, warehouse_id: Uuid, location: &NewLocation) -> Result<Result<Uuid, String>, sqlx::Error> {
+    let code = location.code.trim();
+    if code.is_empty() {
+        return Ok(Err("Enter a code for the location".to_string()));
+    }
+    if location.capacity.is_some_and(|capacity| capacity <= 0) {
+        return Ok(Err("Capacity has to be above zero, or left blank".to_string()));
+    }
+
+    let result = sqlx::query_scalar::<_, Uuid>(
+        r#"
+        INSERT INTO warehouse_locations (warehouse_id, code, aisle, bin, walk_order, capacity)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING id
+        "#,
+    )
+    .bind(warehouse_id)
+    .bind(code)
+    .bind(&location.aisle)
+    .bind(&location.bin)
+    .bind(location.walk_order)
+    .bind(location.capacity)
+    .fetch_one(db)
+    .await;
+    match result {
+        Ok(id) => Ok(Ok(id)),
+        Err(sqlx::Error::Database(e)) if e.is_unique_violation() => {
+            Ok(Err(format!("{} is already a location in this warehouse", code)))
+        }
+        Err(e) => Err(e),
+    }
+}
+
+// Stock kept there is left without a location
+pub async fn remove(db: &Database, warehouse_id: Uuid, id: Uuid) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query("DELETE FROM warehouse_locations WHERE id = $1 AND warehouse_id = $2")
+        .bind(id)
+        .bind(warehouse_id)
+        .execute(db)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+// Where to put an item away in a warehouse, best first: where it's already
+// kept, then empty locations with room, then any others with room, each
+// nearest the start of the walk. Without a quantity, any room will do.
+pub async fn suggest_putaway(
+    db: &Database,
+    item_id: Uuid,
+    warehouse_id: Uuid,
+    quantity: Option<i32>,
+) -> Result<Vec<WarehouseLocation>, sqlx::Error> {
+    let current = sqlx::query_scalar::<_, Option<Uuid>>(
+        "SELECT location_id FROM stock_levels WHERE item_id = $1 AND warehouse_id = $2",
+    )
+    .bind(item_id)
+    .bind(warehouse_id)
+    .fetch_optional(db)
+    .await?
+    .flatten();
+
+    let needed = i64::from(quantity.unwrap_or(1).max(1));
+    let mut locations: Vec<WarehouseLocation> = for_warehouse(db, warehouse_id)
+        .await?
+        .into_iter()
+        .filter(|location| Some(location.id) == current || location.free_capacity().is_none_or(|free| free >= needed))
+        .collect();
+    // Stable, so walk order is kept within each group
+    locations.sort_by_key(|location| (Some(location.id) != current, location.units_stored > 0));
+    Ok(locations)
+}
+
+// Keep an item's stock in a warehouse at a location from now on
+pub(crate) async fn assign(
+    tx: &mut Transaction<'_, Postgres>,
+    item_id: Uuid,
+    warehouse_id: Uuid,
+    location_id: Uuid,
+) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE stock_levels SET location_id = $3 WHERE item_id = $1 AND warehouse_id = $2")
+        .bind(item_id)
+        .bind(warehouse_id)
+        .bind(location_id)
+        .execute(&mut **tx)
+        .await?;
+    Ok(())
+}
+
+// Reserved releases ready to go out of the warehouse, in the order a picker
+// walks past their locations. Those waiting on a backorder aren't ready.
+pub async fn pick_list(db: &Database, user: &CurrentUser, warehouse_id: Uuid) -> Result<Vec<PickListLine>, sqlx::Error> {
+    let scoped = sharing::is_scoped(user);
+    let scope = if scoped {
+        format!("AND {}", sharing::visibility_condition(RecordKind::Customer, "c", 2))
+    } else {
+        String::new()
+    };
+    sqlx::query_as::<_, PickListLine>(&format!(
+        r#"
+        SELECT r.id as release_id, loc.code as location_code, l.item_id, i.item_name, i.sku, r.quantity, r.scheduled_for,
+               o.id as blanket_order_id, COALESCE(o.order_number, o.reference) as reference, c.company_name
+        FROM blanket_order_releases r
+        JOIN blanket_order_lines l ON l.id = r.line_id
+        JOIN blanket_orders o ON o.id = l.blanket_order_id
+        JOIN customers c ON c.id = o.customer_id
+        JOIN inventory_items i ON i.id = l.item_id
+        LEFT JOIN stock_levels sl ON sl.item_id = l.item_id AND sl.warehouse_id = r.warehouse_id
+        LEFT JOIN warehouse_locations loc ON loc.id = sl.location_id
+        WHERE r.warehouse_id = $1 AND r.status = 'reserved' AND o.status = 'open'
+          AND NOT EXISTS (SELECT 1 FROM backorders b WHERE b.release_id = r.id AND b.status = 'open')
+          {}
+        ORDER BY loc.walk_order NULLS LAST, loc.code, i.item_name, r.scheduled_for
+        "#,
+        scope
+    ))
+    .bind(warehouse_id)
+    .bind(scoped.then_some(user.id))
+    .fetch_all(db)
+    .await
+}
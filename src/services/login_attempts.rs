@@ -0,0 +1,149 @@
+use chrono::{DateTime, Utc};
+use uuid::Uuid;
+
+use crate::database::Database;
+
+// Failures counted towards backoff are those within this window
+const WINDOW_MINUTES: i32 = 15;
+
+// Failures allowed before each retry has to wait, and the longest wait (seconds)
+const ACCOUNT_FREE_FAILURES: i64 = 3;
+const ACCOUNT_MAX_DELAY: i64 = 5 * 60;
+const IP_FREE_FAILURES: i64 = 10;
+const IP_MAX_DELAY: i64 = 15 * 60;
+
+// Attempts are kept this long for the record, then pruned
+const KEEP_DAYS: i32 = 30;
+
+// Exponential backoff: 1s, 2s, 4s... once the free failures are used up
+fn delay_after(failures: i64, free: i64, max_delay: i64) -> i64 {
+    if failures < free {
+        return 0;
+    }
+    1i64.checked_shl((failures - free).min(30) as u32)
+        .unwrap_or(max_delay)
+        .min(max_delay)
+}
+
+fn remaining(last_failure: Option<DateTime<Utc>>, delay: i64) -> i64 {
+    last_failure
+        .map(|at| (at + chrono::Duration::seconds(delay) - Utc::now()).num_seconds())
+        .unwrap_or(0)
+}
+
+// Seconds to wait before another attempt for this email or from this address
+// is looked at. Unknown emails back off the same way as real ones, so the
+// delay doesn't reveal which accounts exist.
+pub async fn retry_after(db: &Database, email: &str, ip_address: Option<&str>) -> Result<Option<i64>, sqlx::Error> {
+    let (account_failures, account_last, ip_failures, ip_last) =
+        sqlx::query_as::<_, (i64, Option<DateTime<Utc>>, i64, Option<DateTime<Utc>>)>(
+            r#"
+            WITH account AS (
+                SELECT created_at FROM login_attempts
+                WHERE LOWER(email) = LOWER($1) AND NOT succeeded
+                  AND created_at > NOW() - make_interval(mins => $3)
+                  AND created_at > COALESCE(
+                      (SELECT MAX(created_at) FROM login_attempts WHERE LOWER(email) = LOWER($1) AND succeeded),
+                      '-infinity'
+                  )
+            ),
+            address AS (
+                SELECT created_at FROM login_attempts
+                WHERE ip_address = $2 AND NOT succeeded
+                  AND created_at > NOW() - make_interval(mins => $3)
+            )
+            SELECT (SELECT COUNT(*) FROM account), (SELECT MAX(created_at) FROM account),
+                   (SELECT COUNT(*) FROM address), (SELECT MAX(created_at) FROM address)
+            "#,
+        )
+        .bind(email)
+        .bind(ip_address)
+        .bind(WINDOW_MINUTES)
+        .fetch_one(db)
+        .await?;
+
+    let wait = remaining(account_last, delay_after(account_failures, ACCOUNT_FREE_FAILURES, ACCOUNT_MAX_DELAY))
+        .max(remaining(ip_last, delay_after(ip_failures, IP_FREE_FAILURES, IP_MAX_DELAY)));
+
+    Ok((wait > 0).then_some(wait))
+}
+
+pub async fn record(
+    db: &Database,
+    email: &str,
+    user_id: Option<Uuid>,
+    ip_address: Option<&str>,
+    succeeded: bool,
+) -> Result<(), sqlx::Error> {
+    sqlx::query("INSERT INTO login_attempts (email, user_id, ip_address, succeeded) VALUES ($1, $2, $3, $4)")
+        .bind(email)
+        .bind(user_id)
+        .bind(ip_address)
+        .bind(succeeded)
+        .execute(db)
+        .await?;
+
+    sqlx::query("DELETE FROM login_attempts WHERE created_at < NOW() - make_interval(days => $1)")
+        .bind(KEEP_DAYS)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+// Count a wrong password against an account, locking it once the configured
+// threshold is reached. Returns the failure count when this attempt locked it.
+pub async fn count_failure(db: &Database, user_id: Uuid) -> Result<Option<i32>, sqlx::Error> {
+    let updated = sqlx::query_as::<_, (i32, bool)>(
+        r#"
+        WITH next AS (
+            SELECT u.id, u.failed_login_count + 1 AS failures,
+                   st.lockout_threshold IS NOT NULL AND u.failed_login_count + 1 >= st.lockout_threshold AS now_locked,
+                   st.lockout_minutes
+            FROM users u, security_settings st
+            WHERE u.id = $1 AND u.is_locked = false
+            FOR UPDATE OF u
+        )
+        UPDATE users u
+        SET failed_login_count = next.failures,
+            is_locked = next.now_locked,
+            locked_at = CASE WHEN next.now_locked THEN NOW() ELSE u.locked_at END,
+            -- No lockout duration leaves the expiry empty: locked until an admin steps in
+            locked_until = CASE WHEN next.now_locked
+                                THEN NOW() + make_interval(mins => next.lockout_minutes)
+                                ELSE u.locked_until END
+        FROM next
+        WHERE u.id = next.id
+        RETURNING next.failures, next.now_locked
+        "#,
+    )
+    .bind(user_id)
+    .fetch_optional(db)
+    .await?;
+
+    Ok(updated.and_then(|(failures, locked)| locked.then_some(failures)))
+}
+
+// A successful sign-in starts the count again
+pub async fn reset(db: &Database, user_id: Uuid) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE users SET failed_login_count = 0 WHERE id = $1 AND failed_login_count > 0")
+        .bind(user_id)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+// Lift an automatic lock whose time is up. Locks set by an admin, and
+// automatic locks without an expiry, stay until an admin unlocks the account.
+pub async fn release_expired_lock(db: &Database, user_id: Uuid) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query(
+        r#"
+        UPDATE users
+        SET is_locked = false, locked_at = NULL, locked_until = NULL, failed_login_count = 0
+        WHERE id = $1 AND is_locked = true AND locked_by IS NULL AND locked_until <= NOW()
+        "#,
+    )
+    .bind(user_id)
+    .execute(db)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
@@ -0,0 +1,65 @@
+use uuid::Uuid;
+
+use crate::{database::Database, models::LoginEvent, services::security::LoginContext};
+
+// Why a sign-in attempt was turned away
+#[derive(Debug, Clone, Copy)]
+pub enum Failure {
+    InvalidCredentials,
+    Locked,
+    Throttled,
+}
+
+impl Failure {
+    fn as_str(self) -> &'static str {
+        match self {
+            Failure::InvalidCredentials => "invalid_credentials",
+            Failure::Locked => "locked",
+            Failure::Throttled => "throttled",
+        }
+    }
+}
+
+// Add an attempt to the login history. Failures are filed under whichever
+// account the email belongs to, if any. A device is new when the user has
+// signed in before but never from it.
+pub async fn record(
+    db: &Database,
+    email: &str,
+    failure: Option<Failure>,
+    context: &LoginContext,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        WITH account AS (
+            SELECT id FROM users WHERE LOWER(email) = LOWER($1) LIMIT 1
+        )
+        INSERT INTO login_events (user_id, email, succeeded, failure_reason, device_id, new_device, ip_address, user_agent)
+        SELECT
+            (SELECT id FROM account), $1, $2 IS NULL, $2, $3,
+            EXISTS (SELECT 1 FROM login_events WHERE user_id = (SELECT id FROM account) AND succeeded)
+                AND NOT EXISTS (
+                    SELECT 1 FROM login_events WHERE user_id = (SELECT id FROM account) AND succeeded AND device_id = $3
+                ),
+            $4, $5
+        "#,
+    )
+    .bind(email)
+    .bind(failure.map(Failure::as_str))
+    .bind(&context.device_id)
+    .bind(&context.ip_address)
+    .bind(&context.user_agent)
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+pub async fn recent(db: &Database, user_id: Uuid, limit: i64) -> Result<Vec<LoginEvent>, sqlx::Error> {
+    sqlx::query_as::<_, LoginEvent>(
+        "SELECT * FROM login_events WHERE user_id = $1 ORDER BY created_at DESC LIMIT $2",
+    )
+    .bind(user_id)
+    .bind(limit)
+    .fetch_all(db)
+    .await
+}
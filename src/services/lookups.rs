@@ -0,0 +1,69 @@
+use crate::{database::Database, models::LookupValue};
+
+// The active options for one dropdown
+pub struct LookupOptions {
+    pub values: Vec<LookupValue>,
+}
+
+impl LookupOptions {
+    // Whether a stored value is still offered. Forms add retired values back
+    // as an extra option so editing a record doesn't silently change them.
+    pub fn contains(&self, value: &str) -> bool {
+        self.values.iter().any(|option| option.value == value)
+    }
+}
+
+// Every managed dropdown, loaded once for templates that show several
+pub struct Lookups {
+    pub industries: LookupOptions,
+    pub countries: LookupOptions,
+    pub currencies: LookupOptions,
+}
+
+pub async fn list(db: &Database, kind: &str, include_inactive: bool) -> Result<Vec<LookupValue>, sqlx::Error> {
+    sqlx::query_as::<_, LookupValue>(
+        r#"
+        SELECT * FROM lookup_values
+        WHERE kind = $1 AND (is_active OR $2)
+        ORDER BY sort_order, label
+        "#,
+    )
+    .bind(kind)
+    .bind(include_inactive)
+    .fetch_all(db)
+    .await
+}
+
+pub async fn options(db: &Database, kind: &str) -> Result<LookupOptions, sqlx::Error> {
+    Ok(LookupOptions {
+        values: list(db, kind, false).await?,
+    })
+}
+
+pub async fn load(db: &Database) -> Result<Lookups, sqlx::Error> {
+    let values = sqlx::query_as::<_, LookupValue>(
+        "SELECT * FROM lookup_values WHERE is_active ORDER BY sort_order, label",
+    )
+    .fetch_all(db)
+    .await?;
+
+    let take = |kind: &str| LookupOptions {
+        values: values.iter().filter(|value| value.kind == kind).cloned().collect(),
+    };
+    Ok(Lookups {
+        industries: take("industry"),
+        countries: take("country"),
+        currencies: take("currency"),
+    })
+}
+
+// Check a submitted value against the active options
+pub async fn is_allowed(db: &Database, kind: &str, value: &str) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS(SELECT 1 FROM lookup_values WHERE kind = $1 AND value = $2 AND is_active)",
+    )
+    .bind(kind)
+    .bind(value)
+    .fetch_one(db)
+    .await
+}
@@ -0,0 +1,306 @@
+use lettre::{
+    message::{header::ContentType, Mailbox},
+    AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor,
+};
+use std::env;
+use uuid::Uuid;
+
+use crate::{
+    database::Database,
+    models::OutboxEmail,
+    services::tenancy,
+};
+
+// Base URL used when building links inside outgoing emails. Tenants other
+// than the primary one are reached on their own subdomain of it.
+pub fn app_url() -> String {
+    let url = env::var("APP_URL").unwrap_or_else(|_| "http://localhost:3000".to_string());
+    match tenancy::current() {
+        Some(tenant) if !tenant.is_primary => match url.split_once("://") {
+            Some((scheme, host)) => format!("{}://{}.{}", scheme, tenant.slug, host),
+            None => format!("{}.{}", tenant.slug, url),
+        },
+        _ => url,
+    }
+}
+
+// The user an email goes out on behalf of: shown as the sender's name, with
+// replies going to their reply-to (or account) address and their signature
+// appended
+#[derive(Debug, sqlx::FromRow)]
+pub struct Sender {
+    pub name: String,
+    pub reply_to: String,
+    // Sanitized when saved
+    pub signature: Option<String>,
+}
+
+impl Sender {
+    pub async fn load(db: &Database, user_id: Uuid) -> Result<Self, sqlx::Error> {
+        sqlx::query_as::<_, Sender>(
+            r#"
+            SELECT CONCAT(first_name, ' ', last_name) as name,
+                   COALESCE(NULLIF(reply_to, ''), email) as reply_to,
+                   NULLIF(email_signature, '') as signature
+            FROM users WHERE id = $1
+            "#,
+        )
+        .bind(user_id)
+        .fetch_one(db)
+        .await
+    }
+
+    pub fn sign(&self, body_html: &str) -> String {
+        match &self.signature {
+            Some(signature) => format!(r#"{}<div class="signature"><p>--</p>{}</div>"#, body_html, signature),
+            None => body_html.to_string(),
+        }
+    }
+}
+
+// Queue an email for delivery. The scheduler picks it up on its next run.
+pub async fn queue_email(
+    db: &Database,
+    to_address: &str,
+    subject: &str,
+    body_html: &str,
+) -> Result<Uuid, sqlx::Error> {
+    sqlx::query_scalar::<_, Uuid>(
+        r#"
+        INSERT INTO email_outbox (to_address, subject, body_html)
+        VALUES ($1, $2, $3)
+        RETURNING id
+        "#,
+    )
+    .bind(to_address)
+    .bind(subject)
+    .bind(body_html)
+    .fetch_one(db)
+    .await
+}
+
+// Queue an email sent on a user's behalf, signed with their signature
+pub async fn queue_email_as(
+    db: &Database,
+    sent_by: Uuid,
+    to_address: &str,
+    subject: &str,
+    body_html: &str,
+) -> Result<Uuid, sqlx::Error> {
+    let sender = Sender::load(db, sent_by).await?;
+    sqlx::query_scalar::<_, Uuid>(
+        r#"
+        INSERT INTO email_outbox (to_address, subject, body_html, sender_name, reply_to)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING id
+        "#,
+    )
+    .bind(to_address)
+    .bind(subject)
+    .bind(sender.sign(body_html))
+    .bind(&sender.name)
+    .bind(&sender.reply_to)
+    .fetch_one(db)
+    .await
+}
+
+// Turn a plain-text message into escaped HTML paragraphs with http(s) URLs as links
+pub fn text_to_html(text: &str) -> String {
+    let escape = |value: &str| {
+        value
+            .replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+            .replace('"', "&quot;")
+    };
+
+    text.replace("\r\n", "\n")
+        .split("\n\n")
+        .filter(|paragraph| !paragraph.trim().is_empty())
+        .map(|paragraph| {
+            let lines: Vec<String> = paragraph
+                .lines()
+                .map(|line| {
+                    line.split(' ')
+                        .map(|word| {
+                            if word.starts_with("http://") || word.starts_with("https://") {
+                                format!(r#"<a href="{0}">{0}</a>"#, escape(word))
+                            } else {
+                                escape(word)
+                            }
+                        })
+                        .collect::<Vec<_>>()
+                        .join(" ")
+                })
+                .collect();
+            format!("<p>{}</p>", lines.join("<br>"))
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+// Queue an email to a CRM contact on a user's behalf. Unless the contact opted out of
+// tracking, links are wrapped through /t/c/ and an open pixel is appended so events
+// show on their timeline.
+pub async fn queue_contact_email(
+    db: &Database,
+    contact_id: Uuid,
+    sent_by: Option<Uuid>,
+    subject: &str,
+    body_html: &str,
+) -> Result<Uuid, sqlx::Error> {
+    let (to_address, opt_out) = sqlx::query_as::<_, (Option<String>, bool)>(
+        "SELECT email, email_tracking_opt_out FROM contacts WHERE id = $1"
+    )
+    .bind(contact_id)
+    .fetch_one(db)
+    .await?;
+
+    let to_address = to_address.unwrap_or_default();
+    let sender = match sent_by {
+        Some(sent_by) => Some(Sender::load(db, sent_by).await?),
+        None => None,
+    };
+    let signed = sender.as_ref().map(|sender| sender.sign(body_html));
+    let body_html = signed.as_deref().unwrap_or(body_html);
+
+    let email_id = sqlx::query_scalar::<_, Uuid>(
+        r#"
+        INSERT INTO email_outbox (to_address, subject, body_html, contact_id, sender_name, reply_to)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING id
+        "#,
+    )
+    .bind(&to_address)
+    .bind(subject)
+    .bind(body_html)
+    .bind(contact_id)
+    .bind(sender.as_ref().map(|sender| &sender.name))
+    .bind(sender.as_ref().map(|sender| &sender.reply_to))
+    .fetch_one(db)
+    .await?;
+
+    if opt_out {
+        return Ok(email_id);
+    }
+
+    let base_url = app_url();
+    let mut tracked = String::with_capacity(body_html.len());
+    let mut rest = body_html;
+
+    while let Some(start) = rest.find("href=\"http") {
+        let url_start = start + "href=\"".len();
+        let Some(url_len) = rest[url_start..].find('"') else {
+            break;
+        };
+        let url = &rest[url_start..url_start + url_len];
+
+        let link_id = sqlx::query_scalar::<_, Uuid>(
+            "INSERT INTO email_links (email_id, url) VALUES ($1, $2) RETURNING id"
+        )
+        .bind(email_id)
+        .bind(url.replace("&amp;", "&"))
+        .fetch_one(db)
+        .await?;
+
+        tracked.push_str(&rest[..url_start]);
+        tracked.push_str(&format!("{}/t/c/{}", base_url, link_id));
+        rest = &rest[url_start + url_len..];
+    }
+    tracked.push_str(rest);
+    tracked.push_str(&format!(
+        r#"<img src="{}/t/o/{}" width="1" height="1" alt="" style="display:none">"#,
+        base_url, email_id
+    ));
+
+    sqlx::query("UPDATE email_outbox SET body_html = $1 WHERE id = $2")
+        .bind(&tracked)
+        .bind(email_id)
+        .execute(db)
+        .await?;
+
+    Ok(email_id)
+}
+
+// Send queued emails through SMTP_URL. Without SMTP configured the queue is left untouched.
+pub async fn deliver_pending(db: &Database) -> Result<usize, sqlx::Error> {
+    let smtp_url = match env::var("SMTP_URL") {
+        Ok(url) => url,
+        Err(_) => return Ok(0),
+    };
+
+    let transport = match AsyncSmtpTransport::<Tokio1Executor>::from_url(&smtp_url) {
+        Ok(builder) => builder.build(),
+        Err(e) => {
+            tracing::error!("Invalid SMTP_URL: {}", e);
+            return Ok(0);
+        }
+    };
+
+    let from = env::var("MAIL_FROM").unwrap_or_else(|_| "Allo <no-reply@allo.local>".to_string());
+    let from: Mailbox = match from.parse() {
+        Ok(from) => from,
+        Err(e) => {
+            tracing::error!("Invalid MAIL_FROM: {}", e);
+            return Ok(0);
+        }
+    };
+
+    let pending = sqlx::query_as::<_, OutboxEmail>(
+        "SELECT * FROM email_outbox WHERE status = 'queued' ORDER BY created_at LIMIT 50"
+    )
+    .fetch_all(db)
+    .await?;
+
+    let mut sent = 0;
+
+    for email in pending {
+        // Mail sent on someone's behalf still comes from our address, so it
+        // passes SPF/DKIM, but carries their name and takes their replies
+        let sender = Mailbox::new(
+            email.sender_name.as_ref().map(|name| format!("{} via Allo", name)).or(from.name.clone()),
+            from.email.clone(),
+        );
+        let reply_to = email.reply_to.as_deref().map(str::parse::<Mailbox>).transpose();
+
+        let message = match (email.to_address.parse(), reply_to) {
+            (Ok(to), Ok(reply_to)) => {
+                let mut builder = Message::builder()
+                    .from(sender)
+                    .to(to)
+                    .subject(email.subject.clone())
+                    .header(ContentType::TEXT_HTML);
+                if let Some(reply_to) = reply_to {
+                    builder = builder.reply_to(reply_to);
+                }
+                builder.body(email.body_html.clone()).map_err(|e| e.to_string())
+            }
+            _ => Err(format!("Invalid address: {}", email.to_address)),
+        };
+
+        let result = match message {
+            Ok(message) => transport.send(message).await.map(|_| ()).map_err(|e| e.to_string()),
+            Err(e) => Err(e),
+        };
+
+        match result {
+            Ok(()) => {
+                sqlx::query("UPDATE email_outbox SET status = 'sent', sent_at = NOW() WHERE id = $1")
+                    .bind(email.id)
+                    .execute(db)
+                    .await?;
+                sent += 1;
+            }
+            Err(e) => {
+                tracing::error!("Failed to send email {}: {}", email.id, e);
+                sqlx::query("UPDATE email_outbox SET status = 'failed', error = $1 WHERE id = $2")
+                    .bind(e)
+                    .bind(email.id)
+                    .execute(db)
+                    .await?;
+            }
+        }
+    }
+
+    Ok(sent)
+}
@@ -0,0 +1,292 @@
+use sqlx::FromRow;
+use std::time::Duration;
+use uuid::Uuid;
+
+use crate::{
+    database::Database,
+    models::{CustomerSegment, Job},
+    services::{
+        jobs::{self, Step},
+        mailer,
+    },
+};
+
+// Each send runs as a background job that queues a batch every minute
+pub const JOB_KIND: &str = "mass_email";
+
+// Contacts with an email address whose customer matches the segment filters ($1..$5)
+const SEGMENT_CONTACTS_SQL: &str = r#"
+    SELECT ct.id as contact_id, ct.email as email_address
+    FROM contacts ct
+    JOIN customers c ON c.id = ct.customer_id
+    WHERE COALESCE(ct.email, '') <> ''
+      AND ($1::text IS NULL OR c.status = $1)
+      AND ($2::text IS NULL OR c.industry ILIKE $2)
+      AND ($3::uuid IS NULL OR c.campaign_id = $3)
+      AND ($4::text IS NULL OR c.lead_source = $4)
+      AND (NOT $5 OR ct.is_primary)
+"#;
+
+#[derive(FromRow)]
+struct PendingRecipient {
+    id: Uuid,
+    contact_id: Option<Uuid>,
+    first_name: Option<String>,
+    last_name: Option<String>,
+    company_name: Option<String>,
+}
+
+#[derive(FromRow)]
+struct SendingMassEmail {
+    id: Uuid,
+    subject: String,
+    body_template: String,
+    per_minute_limit: i32,
+    created_by: Option<Uuid>,
+}
+
+// Number of distinct addresses a segment currently resolves to
+pub async fn count_segment_recipients(
+    db: &Database,
+    segment: &CustomerSegment,
+) -> Result<i64, sqlx::Error> {
+    let query = format!(
+        "SELECT COUNT(DISTINCT LOWER(matched.email_address)) FROM ({}) matched",
+        SEGMENT_CONTACTS_SQL
+    );
+
+    sqlx::query_scalar::<_, i64>(&query)
+        .bind(&segment.status)
+        .bind(&segment.industry)
+        .bind(segment.campaign_id)
+        .bind(&segment.lead_source)
+        .bind(segment.primary_contacts_only)
+        .fetch_one(db)
+        .await
+}
+
+// Create a send and snapshot its recipients. Each address appears once, and any
+// do-not-contact or bounced address is recorded as suppressed.
+pub async fn start_send(
+    db: &Database,
+    segment: &CustomerSegment,
+    subject: &str,
+    body_template: &str,
+    per_minute_limit: i32,
+    created_by: Uuid,
+) -> Result<Uuid, sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    let mass_email_id = sqlx::query_scalar::<_, Uuid>(
+        r#"
+        INSERT INTO mass_emails (segment_id, subject, body_template, per_minute_limit, created_by)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING id
+        "#,
+    )
+    .bind(segment.id)
+    .bind(subject)
+    .bind(body_template)
+    .bind(per_minute_limit)
+    .bind(created_by)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    let query = format!(
+        r#"
+        INSERT INTO mass_email_recipients (mass_email_id, contact_id, email_address, status)
+        SELECT DISTINCT ON (LOWER(matched.email_address))
+            $6, matched.contact_id, matched.email_address,
+            CASE WHEN EXISTS (
+                SELECT 1 FROM contacts dnc
+                WHERE (dnc.do_not_contact OR dnc.email_status = 'invalid')
+                  AND LOWER(dnc.email) = LOWER(matched.email_address)
+            ) THEN 'suppressed' ELSE 'pending' END
+        FROM ({}) matched
+        ORDER BY LOWER(matched.email_address)
+        "#,
+        SEGMENT_CONTACTS_SQL
+    );
+
+    sqlx::query(&query)
+        .bind(&segment.status)
+        .bind(&segment.industry)
+        .bind(segment.campaign_id)
+        .bind(&segment.lead_source)
+        .bind(segment.primary_contacts_only)
+        .bind(mass_email_id)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+
+    jobs::enqueue(
+        db,
+        JOB_KIND,
+        &format!("Mass email: {}", subject.chars().take(200).collect::<String>()),
+        serde_json::json!({ "mass_email_id": mass_email_id }),
+        Some(created_by),
+    )
+    .await?;
+
+    Ok(mass_email_id)
+}
+
+// Fills in the {{field}} placeholders listed in MERGE_FIELDS
+pub fn merge(template: &str, first_name: Option<&str>, last_name: Option<&str>, company_name: Option<&str>) -> String {
+    let mut merged = template.to_string();
+    for (field, value) in [("first_name", first_name), ("last_name", last_name), ("company_name", company_name)] {
+        merged = merged.replace(&format!("{{{{{}}}}}", field), value.unwrap_or(""));
+    }
+    merged
+}
+
+fn merge_recipient(template: &str, recipient: &PendingRecipient) -> String {
+    merge(
+        template,
+        recipient.first_name.as_deref(),
+        recipient.last_name.as_deref(),
+        recipient.company_name.as_deref(),
+    )
+}
+
+fn job_mass_email_id(job: &Job) -> Result<Uuid, sqlx::Error> {
+    job.payload
+        .get("mass_email_id")
+        .and_then(|id| id.as_str())
+        .and_then(|id| Uuid::parse_str(id).ok())
+        .ok_or_else(|| sqlx::Error::Protocol("Job payload has no mass_email_id".to_string()))
+}
+
+// One step of a send: queue up to per_minute_limit recipients that haven't
+// been queued in the last minute, then wait a minute if any are left
+pub async fn run_send_job(db: &Database, job: &Job) -> Result<Step, sqlx::Error> {
+    let mass_email_id = job_mass_email_id(job)?;
+
+    let send = sqlx::query_as::<_, SendingMassEmail>(
+        "SELECT id, subject, body_template, per_minute_limit, created_by FROM mass_emails WHERE id = $1 AND status = 'sending'"
+    )
+    .bind(mass_email_id)
+    .fetch_optional(db)
+    .await?;
+
+    let Some(send) = send else {
+        jobs::log(db, job.id, "info", "The send is no longer in progress").await?;
+        return Ok(Step::Done);
+    };
+
+    let queued = process_send(db, &send).await?;
+
+    let (done, total, completed) = sqlx::query_as::<_, (i64, i64, bool)>(
+        r#"
+        SELECT COUNT(*) FILTER (WHERE r.status <> 'pending'), COUNT(*), m.status = 'completed'
+        FROM mass_emails m
+        LEFT JOIN mass_email_recipients r ON r.mass_email_id = m.id
+        WHERE m.id = $1
+        GROUP BY m.status
+        "#,
+    )
+    .bind(send.id)
+    .fetch_one(db)
+    .await?;
+
+    jobs::set_progress(db, job.id, done, Some(total)).await?;
+    if queued > 0 {
+        jobs::log(db, job.id, "info", &format!("Queued {} emails ({} of {} recipients done)", queued, done, total)).await?;
+    }
+
+    if completed {
+        Ok(Step::Done)
+    } else {
+        Ok(Step::Continue(Duration::from_secs(60)))
+    }
+}
+
+// Cancelling the job stops the send as well
+pub async fn cancel_send_job(db: &Database, job: &Job) -> Result<(), sqlx::Error> {
+    sqlx::query("UPDATE mass_emails SET status = 'cancelled', completed_at = NOW() WHERE id = $1 AND status = 'sending'")
+        .bind(job_mass_email_id(job)?)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+// Move pending recipients into the outbox, at most per_minute_limit each minute
+async fn process_send(db: &Database, send: &SendingMassEmail) -> Result<usize, sqlx::Error> {
+    let mut queued = 0;
+
+    let recent = sqlx::query_scalar::<_, i64>(
+        r#"
+        SELECT COUNT(*) FROM mass_email_recipients
+        WHERE mass_email_id = $1 AND queued_at > NOW() - INTERVAL '1 minute'
+        "#,
+    )
+    .bind(send.id)
+    .fetch_one(db)
+    .await?;
+
+    let batch_size = (i64::from(send.per_minute_limit) - recent).max(0);
+
+    let recipients = sqlx::query_as::<_, PendingRecipient>(
+        r#"
+        SELECT r.id, r.contact_id, ct.first_name, ct.last_name, c.company_name
+        FROM mass_email_recipients r
+        LEFT JOIN contacts ct ON ct.id = r.contact_id
+        LEFT JOIN customers c ON c.id = ct.customer_id
+        WHERE r.mass_email_id = $1 AND r.status = 'pending'
+        ORDER BY r.email_address
+        LIMIT $2
+        "#,
+    )
+    .bind(send.id)
+    .bind(batch_size)
+    .fetch_all(db)
+    .await?;
+
+    for recipient in &recipients {
+        // Contacts can be deleted or flagged between the snapshot and their turn
+        let contact_id = match recipient.contact_id {
+            Some(contact_id) => sqlx::query_scalar::<_, Uuid>(
+                "SELECT id FROM contacts WHERE id = $1 AND do_not_contact = false AND email_status <> 'invalid'"
+            )
+            .bind(contact_id)
+            .fetch_optional(db)
+            .await?,
+            None => None,
+        };
+
+        let Some(contact_id) = contact_id else {
+            sqlx::query("UPDATE mass_email_recipients SET status = 'suppressed' WHERE id = $1")
+                .bind(recipient.id)
+                .execute(db)
+                .await?;
+            continue;
+        };
+
+        let subject = merge_recipient(&send.subject, recipient);
+        let body = mailer::text_to_html(&merge_recipient(&send.body_template, recipient));
+        let email_id = mailer::queue_contact_email(db, contact_id, send.created_by, &subject, &body).await?;
+
+        sqlx::query(
+            "UPDATE mass_email_recipients SET status = 'queued', email_id = $1, queued_at = NOW() WHERE id = $2"
+        )
+        .bind(email_id)
+        .bind(recipient.id)
+        .execute(db)
+        .await?;
+        queued += 1;
+    }
+
+    sqlx::query(
+        r#"
+        UPDATE mass_emails SET status = 'completed', completed_at = NOW()
+        WHERE id = $1 AND NOT EXISTS (
+            SELECT 1 FROM mass_email_recipients WHERE mass_email_id = $1 AND status = 'pending'
+        )
+        "#,
+    )
+    .bind(send.id)
+    .execute(db)
+    .await?;
+
+    Ok(queued)
+}
@@ -0,0 +1,241 @@
+use chrono::NaiveDate;
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::Decimal;
+use sqlx::FromRow;
+use uuid::Uuid;
+
+use crate::{
+    database::Database,
+    middleware::permission::held_roles_cte,
+    models::{metric_label, MetricAlertRule},
+};
+
+#[derive(FromRow)]
+struct RuleReading {
+    value: Option<Decimal>,
+    baseline: Option<Decimal>,
+    trailing_points: i64,
+}
+
+// Each query yields (metric, dimension, value) rows for today's snapshot
+const SNAPSHOT_QUERIES: &[&str] = &[
+    // Pipeline value and deal count by stage
+    r#"
+    SELECT 'pipeline_value', stage, COALESCE(SUM(base_value), 0) FROM deals GROUP BY stage
+    UNION ALL
+    SELECT 'pipeline_deals', stage, COUNT(*) FROM deals GROUP BY stage
+    "#,
+    "SELECT 'customers', '', COUNT(*) FROM customers",
+    "SELECT 'headcount', '', COUNT(*) FROM users WHERE is_active = true",
+    // Daily volumes for the previous full day, which alert rules compare over time
+    "SELECT 'activities_logged', '', COUNT(*) FROM activities WHERE created_at::date = CURRENT_DATE - 1",
+    "SELECT 'expense_amount', '', COALESCE(SUM(amount), 0) FROM expenses WHERE created_at::date = CURRENT_DATE - 1",
+    // On-hand stock valued at average cost, falling back to the cost and purchase prices
+    r#"
+    SELECT 'stock_value', '', COALESCE(SUM(sl.quantity_on_hand * COALESCE(i.average_cost, i.cost_price, i.purchase_price, 0)), 0)
+    FROM stock_levels sl
+    JOIN inventory_items i ON i.id = sl.item_id
+    "#,
+];
+
+// Record today's snapshot unless it has already been taken
+pub async fn capture_daily_snapshot(db: &Database) -> Result<bool, sqlx::Error> {
+    let taken = sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS (SELECT 1 FROM metric_snapshots WHERE snapshot_date = CURRENT_DATE)"
+    )
+    .fetch_one(db)
+    .await?;
+
+    if taken {
+        return Ok(false);
+    }
+
+    let mut tx = db.begin().await?;
+
+    for query in SNAPSHOT_QUERIES {
+        sqlx::query(&format!(
+            r#"
+            INSERT INTO metric_snapshots (snapshot_date, metric, dimension, value)
+            SELECT CURRENT_DATE, metric, dimension, value
+            FROM ({}) AS snapshot(metric, dimension, value)
+            ON CONFLICT (tenant_id, snapshot_date, metric, dimension) DO UPDATE SET value = EXCLUDED.value
+            "#,
+            query
+        ))
+        .execute(&mut *tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+
+    Ok(true)
+}
+
+// Value of a metric on the latest snapshot taken before `before`, summed over
+// the given dimensions (or all of them when empty)
+pub async fn value_before(
+    db: &Database,
+    metric: &str,
+    dimensions: &[&str],
+    before: NaiveDate,
+) -> Result<Option<f64>, sqlx::Error> {
+    let dimensions: Vec<String> = dimensions.iter().map(|d| d.to_string()).collect();
+
+    let value = sqlx::query_scalar::<_, Option<Decimal>>(
+        r#"
+        SELECT SUM(value) FROM metric_snapshots
+        WHERE metric = $1
+          AND (cardinality($2::text[]) = 0 OR dimension = ANY($2))
+          AND snapshot_date = (
+              SELECT MAX(snapshot_date) FROM metric_snapshots
+              WHERE metric = $1 AND snapshot_date < $3
+          )
+        "#,
+    )
+    .bind(metric)
+    .bind(&dimensions)
+    .bind(before)
+    .fetch_one(db)
+    .await?;
+
+    Ok(value.and_then(|v| v.to_f64()))
+}
+
+// Daily totals of a metric since `since`, summed over the given dimensions
+pub async fn series(
+    db: &Database,
+    metric: &str,
+    dimensions: &[&str],
+    since: NaiveDate,
+) -> Result<Vec<(NaiveDate, f64)>, sqlx::Error> {
+    let dimensions: Vec<String> = dimensions.iter().map(|d| d.to_string()).collect();
+
+    let rows = sqlx::query_as::<_, (NaiveDate, Decimal)>(
+        r#"
+        SELECT snapshot_date, SUM(value) FROM metric_snapshots
+        WHERE metric = $1
+          AND (cardinality($2::text[]) = 0 OR dimension = ANY($2))
+          AND snapshot_date >= $3
+        GROUP BY snapshot_date
+        ORDER BY snapshot_date
+        "#,
+    )
+    .bind(metric)
+    .bind(&dimensions)
+    .bind(since)
+    .fetch_all(db)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|(date, value)| (date, value.to_f64().unwrap_or(0.0)))
+        .collect())
+}
+
+// Fraction of the trailing window that must have snapshots before a rule is judged
+const MIN_TRAILING_COVERAGE: f64 = 0.5;
+
+// Compare today's snapshot against each active rule's trailing average and
+// notify alert managers of any deviation past the rule's threshold
+pub async fn check_alerts(db: &Database) -> Result<usize, sqlx::Error> {
+    let rules = sqlx::query_as::<_, MetricAlertRule>(
+        "SELECT * FROM metric_alert_rules WHERE is_active = true ORDER BY created_at"
+    )
+    .fetch_all(db)
+    .await?;
+
+    let mut fired = 0;
+
+    for rule in rules {
+        // Dimensions (e.g. deal stages) are summed per day
+        let reading = sqlx::query_as::<_, RuleReading>(
+            r#"
+            WITH daily AS (
+                SELECT snapshot_date, SUM(value) as value
+                FROM metric_snapshots
+                WHERE metric = $1
+                  AND snapshot_date BETWEEN CURRENT_DATE - $2 AND CURRENT_DATE
+                GROUP BY snapshot_date
+            )
+            SELECT
+                (SELECT value FROM daily WHERE snapshot_date = CURRENT_DATE) as value,
+                (SELECT AVG(value) FROM daily WHERE snapshot_date < CURRENT_DATE) as baseline,
+                (SELECT COUNT(*) FROM daily WHERE snapshot_date < CURRENT_DATE) as trailing_points
+            "#,
+        )
+        .bind(&rule.metric)
+        .bind(rule.trailing_days)
+        .fetch_one(db)
+        .await?;
+
+        let (Some(value), Some(baseline)) = (reading.value, reading.baseline) else {
+            continue;
+        };
+        if (reading.trailing_points as f64) < f64::from(rule.trailing_days) * MIN_TRAILING_COVERAGE
+            || baseline < rule.min_baseline
+            || baseline.is_zero()
+        {
+            continue;
+        }
+
+        let deviation = ((value - baseline) / baseline * Decimal::from(100)).round_dp(2);
+        let breached = match rule.direction.as_str() {
+            "drop" => -deviation >= rule.threshold_percent,
+            "spike" => deviation >= rule.threshold_percent,
+            _ => deviation.abs() >= rule.threshold_percent,
+        };
+        if !breached {
+            continue;
+        }
+
+        // Only the first firing of a rule per day is recorded and notified
+        let alert_id = sqlx::query_scalar::<_, Uuid>(
+            r#"
+            INSERT INTO metric_alerts (rule_id, snapshot_date, value, baseline, deviation_percent)
+            VALUES ($1, CURRENT_DATE, $2, $3, $4)
+            ON CONFLICT (rule_id, snapshot_date) DO NOTHING
+            RETURNING id
+            "#,
+        )
+        .bind(rule.id)
+        .bind(value)
+        .bind(baseline.round_dp(2))
+        .bind(deviation)
+        .fetch_optional(db)
+        .await?;
+
+        if alert_id.is_none() {
+            continue;
+        }
+
+        let message = format!(
+            "{} is {} {}% against its {}-day average ({} vs {})",
+            metric_label(&rule.metric),
+            if deviation.is_sign_negative() { "down" } else { "up" },
+            deviation.abs(),
+            rule.trailing_days,
+            value.round_dp(2),
+            baseline.round_dp(2),
+        );
+
+        sqlx::query(&format!(
+            r#"
+            {}
+            INSERT INTO notifications (user_id, message, link_url)
+            SELECT DISTINCT u.id, $1, '/crm/alerts'
+            FROM users u
+            JOIN held_roles h ON h.user_id = u.id
+            JOIN roles r ON r.id = h.role_id
+            WHERE u.is_active = true AND r.permissions ? 'alerts:manage'
+            "#,
+            held_roles_cte(None)
+        ))
+        .bind(&message)
+        .execute(db)
+        .await?;
+
+        fired += 1;
+    }
+
+    Ok(fired)
+}
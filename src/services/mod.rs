@@ -0,0 +1,64 @@
+pub mod mailer;
+pub mod digest;
+pub mod deal_health;
+pub mod stage_history;
+pub mod mass_email;
+pub mod metrics;
+pub mod dashboard;
+pub mod hierarchy;
+pub mod sharing;
+pub mod api_log;
+pub mod audit_log;
+pub mod sandbox;
+pub mod webhooks;
+pub mod security;
+pub mod sessions;
+pub mod captcha;
+pub mod jobs;
+pub mod imports;
+pub mod login_attempts;
+pub mod login_events;
+pub mod lookups;
+pub mod activity_types;
+pub mod password_policy;
+pub mod invitations;
+pub mod periods;
+pub mod offboarding;
+pub mod out_of_office;
+pub mod approvals;
+pub mod discounts;
+pub mod signing_keys;
+pub mod price_history;
+pub mod pricing_agreements;
+pub mod setup;
+pub mod variants;
+pub mod part_numbers;
+pub mod blanket_orders;
+pub mod backorders;
+pub mod stock;
+pub mod locations;
+pub mod held_stock;
+pub mod numbering;
+pub mod documents;
+pub mod exchange_rates;
+pub mod cost_centers;
+pub mod projects;
+pub mod roles;
+pub mod tenancy;
+pub mod watchers;
+pub mod events;
+pub mod teams;
+pub mod saved_dashboards;
+pub mod report_limits;
+pub mod reporting_views;
+pub mod archive;
+pub mod storage;
+pub mod ocr;
+pub mod search;
+pub mod sequences;
+pub mod leads;
+pub mod status;
+pub mod tags;
+pub mod custom_fields;
+pub mod diagnostics;
+pub mod notes;
@@ -0,0 +1,107 @@
+use uuid::Uuid;
+
+use crate::{database::Database, models::Note, services::sharing::RecordKind};
+
+// Long enough for meeting minutes; anything longer belongs in a document
+const MAX_BODY_LENGTH: usize = 10_000;
+
+const NOTE_SELECT: &str = r#"
+    SELECT n.*, NULLIF(CONCAT(u.first_name, ' ', u.last_name), ' ') as author_name
+    FROM notes n
+    LEFT JOIN users u ON u.id = n.author_id
+"#;
+
+fn check_body(body: &str) -> Result<&str, String> {
+    let body = body.trim();
+    if body.is_empty() {
+        return Err("A note can't be empty".to_string());
+    }
+    if body.chars().count() > MAX_BODY_LENGTH {
+        return Err(format!("A note can be at most {} characters", MAX_BODY_LENGTH));
+    }
+    Ok(body)
+}
+
+// A customer's or deal's thread, oldest first
+pub async fn for_record(db: &Database, kind: RecordKind, record_id: Uuid) -> Result<Vec<Note>, sqlx::Error> {
+    sqlx::query_as::<_, Note>(&format!(
+        "{} WHERE n.record_type = $1 AND n.record_id = $2 ORDER BY n.created_at, n.id",
+        NOTE_SELECT
+    ))
+    .bind(kind.key())
+    .bind(record_id)
+    .fetch_all(db)
+    .await
+}
+
+// A note in the given record's thread, so a note id can't be used through
+// another record's URL
+pub async fn get(db: &Database, kind: RecordKind, record_id: Uuid, id: Uuid) -> Result<Option<Note>, sqlx::Error> {
+    sqlx::query_as::<_, Note>(&format!(
+        "{} WHERE n.id = $1 AND n.record_type = $2 AND n.record_id = $3",
+        NOTE_SELECT
+    ))
+    .bind(id)
+    .bind(kind.key())
+    .bind(record_id)
+    .fetch_optional(db)
+    .await
+}
+
+// What the record is called, for notifications
+pub async fn record_name(db: &Database, kind: RecordKind, record_id: Uuid) -> Result<Option<String>, sqlx::Error> {
+    let query = match kind {
+        RecordKind::Customer => "SELECT company_name FROM customers WHERE id = $1",
+        RecordKind::Deal => "SELECT title FROM deals WHERE id = $1",
+        _ => return Ok(None),
+    };
+    sqlx::query_scalar::<_, String>(query).bind(record_id).fetch_optional(db).await
+}
+
+pub async fn create(
+    db: &Database,
+    kind: RecordKind,
+    record_id: Uuid,
+    author_id: Uuid,
+    body: &str,
+) -> Result<Result<Note, String>, sqlx::Error> {
+    let body = match check_body(body) {
+        Ok(body) => body,
+        Err(e) => return Ok(Err(e)),
+    };
+
+    let note = sqlx::query_as::<_, Note>(
+        "INSERT INTO notes (record_type, record_id, body, author_id) VALUES ($1, $2, $3, $4) RETURNING *",
+    )
+    .bind(kind.key())
+    .bind(record_id)
+    .bind(body)
+    .bind(author_id)
+    .fetch_one(db)
+    .await?;
+
+    Ok(Ok(note))
+}
+
+pub async fn update(db: &Database, id: Uuid, body: &str) -> Result<Result<(), String>, sqlx::Error> {
+    let body = match check_body(body) {
+        Ok(body) => body,
+        Err(e) => return Ok(Err(e)),
+    };
+
+    sqlx::query("UPDATE notes SET body = $2, edited_at = NOW() WHERE id = $1 AND body <> $2")
+        .bind(id)
+        .bind(body)
+        .execute(db)
+        .await?;
+
+    Ok(Ok(()))
+}
+
+pub async fn delete(db: &Database, id: Uuid) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM notes WHERE id = $1")
+        .bind(id)
+        .execute(db)
+        .await?;
+    Ok(())
+}
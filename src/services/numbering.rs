@@ -0,0 +1,95 @@
+use sqlx::{Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{database::Database, models::NumberSequence};
+
+pub struct SequenceChanges<'a> {
+    pub prefix: &'a str,
+    pub padding: i32,
+    pub reset_yearly: bool,
+    // Moves the counter forward; None leaves it where it is
+    pub next_number: Option<i64>,
+}
+
+pub async fn list(db: &Database) -> Result<Vec<NumberSequence>, sqlx::Error> {
+    sqlx::query_as::<_, NumberSequence>("SELECT * FROM number_sequences ORDER BY document_type")
+        .fetch_all(db)
+        .await
+}
+
+// Take the next number for a document inside the transaction that creates it.
+// The counter row stays locked until that transaction ends, so numbers are
+// handed out one at a time, and a rolled-back document gives its number back.
+pub async fn next(tx: &mut Transaction<'_, Postgres>, document_type: &str) -> Result<String, sqlx::Error> {
+    let sequence = sqlx::query_as::<_, NumberSequence>(
+        r#"
+        UPDATE number_sequences SET
+            next_number = CASE
+                WHEN reset_yearly AND period_year IS DISTINCT FROM EXTRACT(YEAR FROM CURRENT_DATE)::int THEN 2
+                ELSE next_number + 1
+            END,
+            period_year = EXTRACT(YEAR FROM CURRENT_DATE)::int
+        WHERE document_type = $1
+        RETURNING *
+        "#,
+    )
+    .bind(document_type)
+    .fetch_one(&mut **tx)
+    .await?;
+
+    // next_number has already moved past the one being issued
+    let year = sequence.period_year.unwrap_or_default();
+    Ok(sequence.format(year, sequence.next_number - 1))
+}
+
+// Returns why the change was refused, if it was
+pub async fn update(
+    db: &Database,
+    document_type: &str,
+    changes: SequenceChanges<'_>,
+    updated_by: Uuid,
+) -> Result<Result<(), String>, sqlx::Error> {
+    if changes.prefix.len() > 20
+        || !changes.prefix.chars().all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '/' | '.'))
+    {
+        return Ok(Err("The prefix can have up to 20 letters, digits, and - _ / .".to_string()));
+    }
+    if !(1..=12).contains(&changes.padding) {
+        return Ok(Err("Padding has to be between 1 and 12 digits".to_string()));
+    }
+
+    let mut tx = db.begin().await?;
+    let current = sqlx::query_scalar::<_, i64>(
+        "SELECT next_number FROM number_sequences WHERE document_type = $1 FOR UPDATE",
+    )
+    .bind(document_type)
+    .fetch_optional(&mut *tx)
+    .await?;
+    let Some(current) = current else {
+        return Ok(Err("Unknown document type".to_string()));
+    };
+    // Going backwards would hand out numbers that are already printed on documents
+    if changes.next_number.is_some_and(|next| next < current) {
+        return Ok(Err(format!("The next number can't go below {}, which is next already", current)));
+    }
+
+    sqlx::query(
+        r#"
+        UPDATE number_sequences
+        SET prefix = $2, padding = $3, reset_yearly = $4, next_number = COALESCE($5, next_number),
+            updated_by = $6, updated_at = NOW()
+        WHERE document_type = $1
+        "#,
+    )
+    .bind(document_type)
+    .bind(changes.prefix)
+    .bind(changes.padding)
+    .bind(changes.reset_yearly)
+    .bind(changes.next_number)
+    .bind(updated_by)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    Ok(Ok(()))
+}
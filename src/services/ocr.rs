@@ -0,0 +1,52 @@
+use std::{env, io, process::Stdio, time::Duration};
+use tokio::{io::AsyncWriteExt, process::Command, time::timeout};
+
+// How long one image may take to read before giving up
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub enum OcrError {
+    // Tesseract isn't installed where TESSERACT_PATH (or the PATH) says
+    Unavailable,
+    Failed(String),
+}
+
+impl OcrError {
+    pub fn message(&self) -> &'static str {
+        match self {
+            Self::Unavailable => "Business card scanning isn't set up on this server.",
+            Self::Failed(_) => "The card couldn't be read. Try a sharper, straight-on photo, or fill in the details by hand.",
+        }
+    }
+}
+
+// The text in an image, read by the Tesseract command line tool. It runs on
+// this server, so card images never leave it.
+pub async fn read_text(image: &[u8]) -> Result<String, OcrError> {
+    let program = env::var("TESSERACT_PATH").unwrap_or_else(|_| "tesseract".to_string());
+    let mut child = Command::new(program)
+        .args(["stdin", "stdout", "--psm", "3"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => OcrError::Unavailable,
+            _ => OcrError::Failed(e.to_string()),
+        })?;
+
+    let mut stdin = child.stdin.take().ok_or_else(|| OcrError::Failed("no stdin".to_string()))?;
+    let image = image.to_vec();
+    tokio::spawn(async move {
+        let _ = stdin.write_all(&image).await;
+    });
+
+    let output = timeout(READ_TIMEOUT, child.wait_with_output())
+        .await
+        .map_err(|_| OcrError::Failed("timed out".to_string()))?
+        .map_err(|e| OcrError::Failed(e.to_string()))?;
+    if !output.status.success() {
+        return Err(OcrError::Failed(String::from_utf8_lossy(&output.stderr).trim().to_string()));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
@@ -0,0 +1,175 @@
+use serde::Serialize;
+use sqlx::{FromRow, Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{database::Database, services::hierarchy};
+
+// What a user is responsible for, handed to a successor when they leave
+#[derive(Debug, Default, Serialize, FromRow)]
+pub struct Holdings {
+    pub customers: i64,
+    pub deals: i64,
+    // Open activities only; completed ones stay as a record of who did them
+    pub activities: i64,
+    pub direct_reports: i64,
+    // Outstanding invitations that name them as the new user's manager
+    pub invitations: i64,
+}
+
+impl Holdings {
+    pub fn is_empty(&self) -> bool {
+        self.customers + self.deals + self.activities + self.direct_reports + self.invitations == 0
+    }
+}
+
+// Ownership follows sharing: customers, deals and activities belong to the
+// assignee or else the creator
+pub async fn holdings(db: &Database, user_id: Uuid) -> Result<Holdings, sqlx::Error> {
+    sqlx::query_as::<_, Holdings>(
+        r#"
+        SELECT
+            (SELECT COUNT(*) FROM customers WHERE COALESCE(assigned_to, created_by) = $1) as customers,
+            (SELECT COUNT(*) FROM deals WHERE COALESCE(assigned_to, created_by) = $1) as deals,
+            (SELECT COUNT(*) FROM activities WHERE COALESCE(assigned_to, created_by) = $1 AND completed IS NOT TRUE) as activities,
+            (SELECT COUNT(*) FROM users WHERE manager_id = $1) as direct_reports,
+            (SELECT COUNT(*) FROM user_invitations
+             WHERE manager_id = $1 AND accepted_at IS NULL AND revoked_at IS NULL) as invitations
+        "#,
+    )
+    .bind(user_id)
+    .fetch_one(db)
+    .await
+}
+
+// The successor must be someone else who can sign in. Someone further down
+// the leaver's reporting line can't take over their reports without creating
+// a loop; a direct report can, and moves up to the leaver's own manager.
+pub async fn check_successor(db: &Database, user_id: Uuid, successor_id: Uuid) -> Result<Result<(), String>, sqlx::Error> {
+    if successor_id == user_id {
+        return Ok(Err("Choose someone else to take over.".to_string()));
+    }
+
+    let successor = sqlx::query_as::<_, (bool, Option<Uuid>)>("SELECT is_active, manager_id FROM users WHERE id = $1")
+        .bind(successor_id)
+        .fetch_optional(db)
+        .await?;
+    let Some((true, successor_manager)) = successor else {
+        return Ok(Err("The successor must be an active user.".to_string()));
+    };
+
+    if successor_manager != Some(user_id) && hierarchy::team_member_ids(db, user_id).await?.contains(&successor_id) {
+        return Ok(Err(
+            "The successor reports to this user indirectly. Choose one of their direct reports or someone outside their team.".to_string(),
+        ));
+    }
+    Ok(Ok(()))
+}
+
+// Move everything in Holdings from one user to another, returning what moved
+async fn hand_over(tx: &mut Transaction<'_, Postgres>, user_id: Uuid, successor_id: Uuid) -> Result<Holdings, sqlx::Error> {
+    let customers = sqlx::query("UPDATE customers SET assigned_to = $2 WHERE COALESCE(assigned_to, created_by) = $1")
+        .bind(user_id)
+        .bind(successor_id)
+        .execute(&mut **tx)
+        .await?;
+
+    let deals = sqlx::query("UPDATE deals SET assigned_to = $2 WHERE COALESCE(assigned_to, created_by) = $1")
+        .bind(user_id)
+        .bind(successor_id)
+        .execute(&mut **tx)
+        .await?;
+
+    let activities = sqlx::query("UPDATE activities SET assigned_to = $2 WHERE COALESCE(assigned_to, created_by) = $1 AND completed IS NOT TRUE")
+        .bind(user_id)
+        .bind(successor_id)
+        .execute(&mut **tx)
+        .await?;
+
+    // A direct report taking over moves up to the leaver's manager
+    let direct_reports = sqlx::query(
+        r#"
+        UPDATE users SET manager_id = CASE WHEN id = $2 THEN (SELECT manager_id FROM users WHERE id = $1) ELSE $2 END
+        WHERE manager_id = $1
+        "#,
+    )
+    .bind(user_id)
+    .bind(successor_id)
+    .execute(&mut **tx)
+    .await?;
+
+    let invitations = sqlx::query(
+        "UPDATE user_invitations SET manager_id = $2 WHERE manager_id = $1 AND accepted_at IS NULL AND revoked_at IS NULL",
+    )
+    .bind(user_id)
+    .bind(successor_id)
+    .execute(&mut **tx)
+    .await?;
+
+    Ok(Holdings {
+        customers: customers.rows_affected() as i64,
+        deals: deals.rows_affected() as i64,
+        activities: activities.rows_affected() as i64,
+        direct_reports: direct_reports.rows_affected() as i64,
+        invitations: invitations.rows_affected() as i64,
+    })
+}
+
+// Hand a user's records to their successor and switch the account off, all or nothing
+pub async fn deactivate(db: &Database, user_id: Uuid, successor_id: Option<Uuid>) -> Result<Holdings, sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    let moved = match successor_id {
+        Some(successor_id) => hand_over(&mut tx, user_id, successor_id).await?,
+        None => Holdings::default(),
+    };
+
+    sqlx::query("UPDATE users SET is_active = false, updated_at = NOW() WHERE id = $1")
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+    Ok(moved)
+}
+
+// Hand a user's records to their successor and remove the account. Records
+// that only note who created them go to the successor too. Expenses and stock
+// movements keep the user, so the delete fails with a foreign key violation
+// for anyone who has them; deactivate those users instead.
+pub async fn delete(db: &Database, user_id: Uuid, successor_id: Uuid) -> Result<Holdings, sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    let moved = hand_over(&mut tx, user_id, successor_id).await?;
+
+    for statement in [
+        "UPDATE customers SET created_by = $2 WHERE created_by = $1",
+        "UPDATE deals SET created_by = $2 WHERE created_by = $1",
+        "UPDATE activities SET created_by = $2 WHERE created_by = $1",
+        "UPDATE activities SET assigned_to = $2 WHERE assigned_to = $1",
+        "UPDATE activities_archive SET created_by = $2 WHERE created_by = $1",
+        "UPDATE activities_archive SET assigned_to = $2 WHERE assigned_to = $1",
+        "UPDATE contacts SET created_by = $2 WHERE created_by = $1",
+        "UPDATE roles SET created_by = $2 WHERE created_by = $1",
+        "UPDATE user_roles SET assigned_by = $2 WHERE assigned_by = $1",
+    ] {
+        sqlx::query(statement)
+            .bind(user_id)
+            .bind(successor_id)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    for statement in [
+        "UPDATE users SET locked_by = NULL, locked_at = NULL WHERE locked_by = $1",
+        "UPDATE audit_logs SET user_id = NULL WHERE user_id = $1",
+        "UPDATE audit_logs_archive SET user_id = NULL WHERE user_id = $1",
+        "DELETE FROM notifications WHERE user_id = $1",
+        "DELETE FROM user_roles WHERE user_id = $1",
+        "DELETE FROM users WHERE id = $1",
+    ] {
+        sqlx::query(statement).bind(user_id).execute(&mut *tx).await?;
+    }
+
+    tx.commit().await?;
+    Ok(moved)
+}
@@ -0,0 +1,149 @@
+use chrono::NaiveDate;
+use sqlx::FromRow;
+use uuid::Uuid;
+
+use crate::database::Database;
+
+// How far routing follows backups of backups before giving up
+const MAX_HOPS: usize = 5;
+
+// Whether a user is away today, in their own time zone, and until when ($1 = user id)
+const AWAY_UNTIL_SQL: &str = r#"
+    SELECT MAX(o.ends_on) FROM out_of_office o JOIN users u ON u.id = o.user_id
+    WHERE o.user_id = $1 AND (NOW() AT TIME ZONE u.timezone)::date BETWEEN o.starts_on AND o.ends_on
+"#;
+
+#[derive(Debug, FromRow)]
+pub struct Absence {
+    pub id: Uuid,
+    pub starts_on: NaiveDate,
+    pub ends_on: NaiveDate,
+    pub note: Option<String>,
+}
+
+// An active user as offered by assignment pickers
+#[derive(Debug, FromRow)]
+pub struct Assignee {
+    pub id: Uuid,
+    pub name: String,
+    pub away_until: Option<NaiveDate>,
+    pub backup_id: Option<Uuid>,
+    pub backup_name: Option<String>,
+}
+
+impl Assignee {
+    pub fn away_until_label(&self) -> String {
+        self.away_until.map(|date| date.format("%b %-d").to_string()).unwrap_or_default()
+    }
+}
+
+pub async fn assignees(db: &Database) -> Result<Vec<Assignee>, sqlx::Error> {
+    sqlx::query_as::<_, Assignee>(
+        r#"
+        SELECT u.id, CONCAT(u.first_name, ' ', u.last_name) as name,
+               (SELECT MAX(o.ends_on) FROM out_of_office o
+                WHERE o.user_id = u.id AND (NOW() AT TIME ZONE u.timezone)::date BETWEEN o.starts_on AND o.ends_on) as away_until,
+               b.id as backup_id, NULLIF(CONCAT(b.first_name, ' ', b.last_name), ' ') as backup_name
+        FROM users u
+        LEFT JOIN users b ON b.id = u.backup_user_id AND b.is_active = true
+        WHERE u.is_active = true
+        ORDER BY u.first_name, u.last_name
+        "#,
+    )
+    .fetch_all(db)
+    .await
+}
+
+// Who a new assignment to `user_id` should go to: the user, or while they're
+// away their backup (or the backup's backup). If nobody along the way is in,
+// the assignment stays with the user.
+pub async fn route(db: &Database, user_id: Uuid) -> Result<Uuid, sqlx::Error> {
+    let mut visited = vec![user_id];
+    let mut candidate = user_id;
+
+    for _ in 0..MAX_HOPS {
+        let away = sqlx::query_scalar::<_, Option<NaiveDate>>(AWAY_UNTIL_SQL)
+            .bind(candidate)
+            .fetch_one(db)
+            .await?;
+        if away.is_none() {
+            return Ok(candidate);
+        }
+
+        let backup = sqlx::query_scalar::<_, Uuid>(
+            "SELECT b.id FROM users u JOIN users b ON b.id = u.backup_user_id WHERE u.id = $1 AND b.is_active = true",
+        )
+        .bind(candidate)
+        .fetch_optional(db)
+        .await?;
+        match backup {
+            Some(backup) if !visited.contains(&backup) => {
+                visited.push(backup);
+                candidate = backup;
+            }
+            _ => break,
+        }
+    }
+
+    Ok(user_id)
+}
+
+// Current and upcoming absences
+pub async fn list(db: &Database, user_id: Uuid) -> Result<Vec<Absence>, sqlx::Error> {
+    sqlx::query_as::<_, Absence>(
+        r#"
+        SELECT o.id, o.starts_on, o.ends_on, o.note FROM out_of_office o JOIN users u ON u.id = o.user_id
+        WHERE o.user_id = $1 AND o.ends_on >= (NOW() AT TIME ZONE u.timezone)::date
+        ORDER BY o.starts_on
+        "#,
+    )
+    .bind(user_id)
+    .fetch_all(db)
+    .await
+}
+
+pub async fn add(
+    db: &Database,
+    user_id: Uuid,
+    starts_on: NaiveDate,
+    ends_on: NaiveDate,
+    note: Option<&str>,
+) -> Result<Uuid, sqlx::Error> {
+    sqlx::query_scalar::<_, Uuid>(
+        "INSERT INTO out_of_office (user_id, starts_on, ends_on, note) VALUES ($1, $2, $3, $4) RETURNING id",
+    )
+    .bind(user_id)
+    .bind(starts_on)
+    .bind(ends_on)
+    .bind(note)
+    .fetch_one(db)
+    .await
+}
+
+pub async fn remove(db: &Database, user_id: Uuid, id: Uuid) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query("DELETE FROM out_of_office WHERE id = $1 AND user_id = $2")
+        .bind(id)
+        .bind(user_id)
+        .execute(db)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+// State of the shared assignee picker (templates/assignee_picker.html)
+pub struct AssigneePicker {
+    pub assignees: Vec<Assignee>,
+    pub selected: Option<Uuid>,
+}
+
+impl AssigneePicker {
+    pub async fn load(db: &Database, selected: Option<Uuid>) -> Result<Self, sqlx::Error> {
+        Ok(Self {
+            assignees: assignees(db).await?,
+            selected,
+        })
+    }
+
+    pub fn is_selected(&self, assignee: &Assignee) -> bool {
+        self.selected == Some(assignee.id)
+    }
+}
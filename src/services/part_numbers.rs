@@ -0,0 +1,121 @@
+use uuid::Uuid;
+
+use crate::{database::Database, models::CustomerPartNumber, utils::barcode::normalize_sku};
+
+// An item found from a code a customer gave, and their number for it if any
+pub struct ResolvedItem {
+    pub item_id: Uuid,
+    pub customer_part_number: Option<String>,
+    // "customer_part_number", "sku" or "upc"
+    pub matched_by: &'static str,
+}
+
+const PART_NUMBER_SELECT: &str = r#"
+    SELECT p.id, p.customer_id, p.item_id, p.part_number, p.description, i.item_name, i.sku
+    FROM customer_part_numbers p
+    JOIN inventory_items i ON i.id = p.item_id
+"#;
+
+pub async fn for_customer(db: &Database, customer_id: Uuid) -> Result<Vec<CustomerPartNumber>, sqlx::Error> {
+    sqlx::query_as::<_, CustomerPartNumber>(&format!(
+        "{} WHERE p.customer_id = $1 ORDER BY UPPER(p.part_number)",
+        PART_NUMBER_SELECT
+    ))
+    .bind(customer_id)
+    .fetch_all(db)
+    .await
+}
+
+// The customer's number for an item. With several, the first one they were
+// given is the one printed.
+pub async fn for_item(db: &Database, customer_id: Uuid, item_id: Uuid) -> Result<Option<String>, sqlx::Error> {
+    sqlx::query_scalar::<_, String>(
+        "SELECT part_number FROM customer_part_numbers WHERE customer_id = $1 AND item_id = $2 ORDER BY created_at LIMIT 1",
+    )
+    .bind(customer_id)
+    .bind(item_id)
+    .fetch_optional(db)
+    .await
+}
+
+// Find the item a customer means by `code`: their own part number first, then
+// our SKU, then a UPC/EAN
+pub async fn resolve(db: &Database, customer_id: Uuid, code: &str) -> Result<Option<ResolvedItem>, sqlx::Error> {
+    let code = code.trim();
+    if code.is_empty() {
+        return Ok(None);
+    }
+
+    let by_part_number = sqlx::query_as::<_, (Uuid, String)>(
+        "SELECT item_id, part_number FROM customer_part_numbers WHERE customer_id = $1 AND UPPER(part_number) = UPPER($2)",
+    )
+    .bind(customer_id)
+    .bind(code)
+    .fetch_optional(db)
+    .await?;
+    if let Some((item_id, part_number)) = by_part_number {
+        return Ok(Some(ResolvedItem {
+            item_id,
+            customer_part_number: Some(part_number),
+            matched_by: "customer_part_number",
+        }));
+    }
+
+    let ours = sqlx::query_as::<_, (Uuid, bool)>(
+        "SELECT id, UPPER(sku) = $1 FROM inventory_items WHERE UPPER(sku) = $1 OR upc = $2 ORDER BY UPPER(sku) = $1 DESC LIMIT 1",
+    )
+    .bind(normalize_sku(code))
+    .bind(code.replace([' ', '-'], ""))
+    .fetch_optional(db)
+    .await?;
+    let Some((item_id, by_sku)) = ours else {
+        return Ok(None);
+    };
+
+    Ok(Some(ResolvedItem {
+        item_id,
+        customer_part_number: for_item(db, customer_id, item_id).await?,
+        matched_by: if by_sku { "sku" } else { "upc" },
+    }))
+}
+
+// Returns None when the customer already uses that number for something
+pub async fn add(
+    db: &Database,
+    customer_id: Uuid,
+    item_id: Uuid,
+    part_number: &str,
+    description: Option<&str>,
+    created_by: Uuid,
+) -> Result<Option<Uuid>, sqlx::Error> {
+    let result = sqlx::query_scalar::<_, Uuid>(
+        r#"
+        INSERT INTO customer_part_numbers (customer_id, item_id, part_number, description, created_by)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING id
+        "#,
+    )
+    .bind(customer_id)
+    .bind(item_id)
+    .bind(part_number)
+    .bind(description)
+    .bind(created_by)
+    .fetch_one(db)
+    .await;
+
+    match result {
+        Ok(id) => Ok(Some(id)),
+        Err(sqlx::Error::Database(e)) if e.is_unique_violation() => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+pub async fn remove(db: &Database, customer_id: Uuid, id: Uuid) -> Result<bool, sqlx::Error> {
+    let removed = sqlx::query("DELETE FROM customer_part_numbers WHERE id = $1 AND customer_id = $2")
+        .bind(id)
+        .bind(customer_id)
+        .execute(db)
+        .await?
+        .rows_affected();
+    Ok(removed > 0)
+}
@@ -0,0 +1,178 @@
+use std::{collections::HashSet, env, fs, sync::OnceLock};
+
+use sqlx::FromRow;
+use uuid::Uuid;
+
+use crate::{database::Database, utils::verify_password};
+
+// bcrypt only looks at the first 72 bytes, so anything longer gives a false
+// sense of strength
+const MAX_BYTES: usize = 72;
+
+static COMMON_PASSWORDS: &str = include_str!("common_passwords.txt");
+static BLOCKLIST: OnceLock<HashSet<String>> = OnceLock::new();
+
+// The bundled list of breached passwords, plus any extra file named by
+// PASSWORD_BLOCKLIST_FILE (one password per line)
+fn blocklist() -> &'static HashSet<String> {
+    BLOCKLIST.get_or_init(|| {
+        let extra = env::var("PASSWORD_BLOCKLIST_FILE")
+            .ok()
+            .filter(|path| !path.is_empty())
+            .and_then(|path| match fs::read_to_string(&path) {
+                Ok(content) => Some(content),
+                Err(e) => {
+                    tracing::error!("Error reading password blocklist {}: {}", path, e);
+                    None
+                }
+            })
+            .unwrap_or_default();
+
+        COMMON_PASSWORDS
+            .lines()
+            .chain(extra.lines())
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(str::to_lowercase)
+            .collect()
+    })
+}
+
+// Password rules from security settings
+#[derive(Debug, Clone, FromRow)]
+pub struct PasswordPolicy {
+    pub min_length: i32,
+    pub required_classes: i32,
+    pub block_breached: bool,
+    pub history_count: Option<i32>,
+}
+
+// Matches the column defaults, for when settings can't be read
+impl Default for PasswordPolicy {
+    fn default() -> Self {
+        PasswordPolicy {
+            min_length: 8,
+            required_classes: 3,
+            block_breached: true,
+            history_count: Some(5),
+        }
+    }
+}
+
+impl PasswordPolicy {
+    pub async fn load(db: &Database) -> Result<Self, sqlx::Error> {
+        sqlx::query_as::<_, PasswordPolicy>(
+            r#"
+            SELECT password_min_length AS min_length, password_required_classes AS required_classes,
+                   password_block_breached AS block_breached, password_history_count AS history_count
+            FROM security_settings
+            "#,
+        )
+        .fetch_one(db)
+        .await
+    }
+
+    // Rules that can be checked without knowing whose password it is
+    pub fn check(&self, password: &str) -> Result<(), String> {
+        if password.chars().count() < self.min_length as usize {
+            return Err(format!("Password must be at least {} characters.", self.min_length));
+        }
+        if password.len() > MAX_BYTES {
+            return Err(format!("Password must be no longer than {} characters.", MAX_BYTES));
+        }
+
+        let classes = [
+            password.chars().any(|c| c.is_lowercase()),
+            password.chars().any(|c| c.is_uppercase()),
+            password.chars().any(|c| c.is_ascii_digit()),
+            password.chars().any(|c| !c.is_alphanumeric()),
+        ]
+        .iter()
+        .filter(|present| **present)
+        .count();
+        if classes < self.required_classes as usize {
+            return Err(format!(
+                "Password must use at least {} of: lowercase letters, uppercase letters, numbers and symbols.",
+                self.required_classes
+            ));
+        }
+
+        if self.block_breached && blocklist().contains(&password.to_lowercase()) {
+            return Err("This password appears in lists of breached passwords. Choose a different one.".to_string());
+        }
+        Ok(())
+    }
+
+    // Short summary for hints next to password fields
+    pub fn describe(&self) -> String {
+        let mut rules = format!("At least {} characters", self.min_length);
+        if self.required_classes > 1 {
+            rules.push_str(&format!(
+                ", using {} of: lowercase, uppercase, numbers, symbols",
+                self.required_classes
+            ));
+        }
+        if let Some(count) = self.history_count {
+            rules.push_str(&format!(". Can't match your last {} password{}", count, if count == 1 { "" } else { "s" }));
+        }
+        rules.push('.');
+        rules
+    }
+}
+
+// Check a new password against the policy and, for an existing user, their
+// recent passwords
+pub async fn validate(
+    db: &Database,
+    user_id: Option<Uuid>,
+    password: &str,
+) -> Result<Result<(), String>, sqlx::Error> {
+    let policy = PasswordPolicy::load(db).await?;
+    if let Err(message) = policy.check(password) {
+        return Ok(Err(message));
+    }
+
+    if let (Some(user_id), Some(count)) = (user_id, policy.history_count) {
+        let hashes = sqlx::query_scalar::<_, String>(
+            "SELECT password_hash FROM password_history WHERE user_id = $1 ORDER BY created_at DESC LIMIT $2",
+        )
+        .bind(user_id)
+        .bind(count as i64)
+        .fetch_all(db)
+        .await?;
+
+        if hashes.iter().any(|hash| verify_password(password, hash).unwrap_or(false)) {
+            return Ok(Err(format!(
+                "This password was used recently. Choose one that isn't among your last {} password{}.",
+                count,
+                if count == 1 { "" } else { "s" }
+            )));
+        }
+    }
+    Ok(Ok(()))
+}
+
+// Add a newly set password to the user's history, keeping only as many as
+// the policy looks back over
+pub async fn remember(db: &Database, user_id: Uuid, password_hash: &str) -> Result<(), sqlx::Error> {
+    sqlx::query("INSERT INTO password_history (user_id, password_hash) VALUES ($1, $2)")
+        .bind(user_id)
+        .bind(password_hash)
+        .execute(db)
+        .await?;
+
+    sqlx::query(
+        r#"
+        DELETE FROM password_history
+        WHERE user_id = $1 AND id NOT IN (
+            SELECT id FROM password_history WHERE user_id = $1
+            ORDER BY created_at DESC
+            LIMIT (SELECT COALESCE(password_history_count, 1) FROM security_settings)
+        )
+        "#,
+    )
+    .bind(user_id)
+    .execute(db)
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,167 @@
+use chrono::{Datelike, Duration, Months, NaiveDate, Utc, Weekday};
+
+use crate::{database::Database, middleware::CurrentUser};
+
+// Days a week can start on, as stored in users.week_start
+pub const WEEK_STARTS: [(&str, &str); 3] = [("monday", "Monday"), ("sunday", "Sunday"), ("saturday", "Saturday")];
+
+#[derive(Clone, Copy)]
+enum Span {
+    // Rolling days ending today
+    Days(i64),
+    Week,
+    // Whole months, counted from January or from the fiscal year start
+    Months { count: u32, fiscal: bool },
+}
+
+pub struct Preset {
+    pub key: &'static str,
+    pub label: &'static str,
+    span: Span,
+    // 0 for the period containing today, 1 for the one before it
+    back: u32,
+    // Ends today instead of at the end of the period
+    to_date: bool,
+}
+
+const fn preset(key: &'static str, label: &'static str, span: Span, back: u32, to_date: bool) -> Preset {
+    Preset { key, label, span, back, to_date }
+}
+
+pub static PRESETS: [Preset; 16] = [
+    preset("today", "Today", Span::Days(1), 0, false),
+    preset("yesterday", "Yesterday", Span::Days(1), 1, false),
+    preset("last_7_days", "Last 7 days", Span::Days(7), 0, false),
+    preset("last_30_days", "Last 30 days", Span::Days(30), 0, false),
+    preset("last_90_days", "Last 90 days", Span::Days(90), 0, false),
+    preset("this_week", "This week", Span::Week, 0, false),
+    preset("last_week", "Last week", Span::Week, 1, false),
+    preset("this_month", "This month", Span::Months { count: 1, fiscal: false }, 0, false),
+    preset("last_month", "Last month", Span::Months { count: 1, fiscal: false }, 1, false),
+    preset("this_quarter", "This quarter", Span::Months { count: 3, fiscal: true }, 0, false),
+    preset("last_quarter", "Last quarter", Span::Months { count: 3, fiscal: true }, 1, false),
+    preset("this_year", "This year", Span::Months { count: 12, fiscal: false }, 0, false),
+    preset("last_year", "Last year", Span::Months { count: 12, fiscal: false }, 1, false),
+    preset("fiscal_ytd", "Fiscal year to date", Span::Months { count: 12, fiscal: true }, 0, true),
+    preset("this_fiscal_year", "This fiscal year", Span::Months { count: 12, fiscal: true }, 0, false),
+    preset("last_fiscal_year", "Last fiscal year", Span::Months { count: 12, fiscal: true }, 1, false),
+];
+
+// What presets are resolved against: the user's today, their first day of
+// the week and the organization's fiscal year
+pub struct PeriodContext {
+    pub today: NaiveDate,
+    pub week_start: Weekday,
+    pub fiscal_year_start: u32,
+}
+
+impl PeriodContext {
+    pub async fn for_user(db: &Database, user: &CurrentUser) -> Result<Self, sqlx::Error> {
+        let fiscal_year_start = sqlx::query_scalar::<_, i32>("SELECT fiscal_year_start_month FROM reporting_settings")
+            .fetch_optional(db)
+            .await?
+            .unwrap_or(1);
+
+        Ok(Self {
+            today: Utc::now().with_timezone(&user.timezone).date_naive(),
+            week_start: user.week_start,
+            fiscal_year_start: fiscal_year_start as u32,
+        })
+    }
+}
+
+impl Preset {
+    pub fn find(key: &str) -> Option<&'static Preset> {
+        PRESETS.iter().find(|preset| preset.key == key)
+    }
+
+    pub fn range(&self, ctx: &PeriodContext) -> (NaiveDate, NaiveDate) {
+        self.range_back(ctx, self.back)
+    }
+
+    // The period just before, cut to the same length for to-date presets
+    pub fn previous(&self, ctx: &PeriodContext) -> (NaiveDate, NaiveDate) {
+        self.range_back(ctx, self.back + 1)
+    }
+
+    fn range_back(&self, ctx: &PeriodContext, back: u32) -> (NaiveDate, NaiveDate) {
+        let (start, end) = self.bounds(ctx, back);
+        if !self.to_date {
+            return (start, end);
+        }
+        let elapsed = ctx.today - self.bounds(ctx, 0).0;
+        (start, (start + elapsed).min(end))
+    }
+
+    // First and last day of the whole period `back` steps before the current one
+    fn bounds(&self, ctx: &PeriodContext, back: u32) -> (NaiveDate, NaiveDate) {
+        let today = ctx.today;
+        match self.span {
+            Span::Days(days) => {
+                let end = today - Duration::days(days * back as i64);
+                (end - Duration::days(days - 1), end)
+            }
+            Span::Week => {
+                let offset = (7 + today.weekday().num_days_from_monday() - ctx.week_start.num_days_from_monday()) % 7;
+                let start = today - Duration::days(offset as i64 + 7 * back as i64);
+                (start, start + Duration::days(6))
+            }
+            Span::Months { count, fiscal } => {
+                let anchor = if fiscal { ctx.fiscal_year_start as i32 - 1 } else { 0 };
+                let count = count as i32;
+                // Months since year 0, shifted so periods start on a multiple of `count`
+                let index = today.year() * 12 + today.month0() as i32 - anchor;
+                let first = index - index.rem_euclid(count) - count * back as i32 + anchor;
+                let start = NaiveDate::from_ymd_opt(first.div_euclid(12), first.rem_euclid(12) as u32 + 1, 1)
+                    .unwrap_or(today);
+                let end = start
+                    .checked_add_months(Months::new(count as u32))
+                    .and_then(|next| next.pred_opt())
+                    .unwrap_or(today);
+                (start, end)
+            }
+        }
+    }
+}
+
+// The dates a filter form's period select stands for. Blank means custom
+// dates; an unrecognized key is an error.
+pub fn resolve(period: Option<&str>, ctx: &PeriodContext) -> Result<Option<(NaiveDate, NaiveDate)>, String> {
+    match period.map(str::trim) {
+        None | Some("") => Ok(None),
+        Some(key) => Preset::find(key)
+            .map(|preset| Some(preset.range(ctx)))
+            .ok_or_else(|| format!("Unknown period: {}", key)),
+    }
+}
+
+// State of the shared period picker (templates/period_picker.html)
+pub struct PeriodPicker {
+    pub selected: String,
+    pub date_from: String,
+    pub date_to: String,
+}
+
+impl PeriodPicker {
+    pub fn new(period: Option<&str>, date_from: Option<NaiveDate>, date_to: Option<NaiveDate>) -> Self {
+        let selected = period.map(str::trim).filter(|key| Preset::find(key).is_some()).unwrap_or_default();
+        Self {
+            selected: selected.to_string(),
+            date_from: date_from.map(|d| d.to_string()).unwrap_or_default(),
+            date_to: date_to.map(|d| d.to_string()).unwrap_or_default(),
+        }
+    }
+
+    pub fn presets(&self) -> &'static [Preset] {
+        &PRESETS
+    }
+
+    pub fn is_selected(&self, preset: &Preset) -> bool {
+        self.selected == preset.key
+    }
+
+    // Label of the chosen preset, for export headers
+    pub fn label(&self) -> &'static str {
+        Preset::find(&self.selected).map(|preset| preset.label).unwrap_or("")
+    }
+}
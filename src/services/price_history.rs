@@ -0,0 +1,87 @@
+use rust_decimal::Decimal;
+use sqlx::FromRow;
+use uuid::Uuid;
+
+use crate::database::Database;
+
+// How one line on a deal compares with what the item costs and what this
+// customer paid for it before
+#[derive(Debug, FromRow)]
+pub struct PriceCheck {
+    pub line_id: Uuid,
+    // After the line's discount
+    pub net_price: Decimal,
+    // What stock actually cost where known, otherwise the standard cost. None
+    // when the item is costed in another currency than the deal.
+    pub unit_cost: Option<Decimal>,
+    // Net prices of the customer's last three purchases on other deals, newest first
+    pub recent_prices: Vec<Decimal>,
+}
+
+impl PriceCheck {
+    pub fn below_cost(&self) -> bool {
+        self.unit_cost.is_some_and(|cost| self.net_price < cost)
+    }
+
+    pub fn below_recent(&self) -> bool {
+        self.recent_prices.iter().min().is_some_and(|lowest| self.net_price < *lowest)
+    }
+
+    pub fn recent_label(&self) -> String {
+        self.recent_prices.iter().map(Decimal::to_string).collect::<Vec<_>>().join(", ")
+    }
+}
+
+// Take the deal's item lines into the history. Lines already there are left
+// alone, so this can run again when a line is added to a won deal.
+pub async fn record_sale(db: &Database, deal_id: Uuid) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO item_price_history (item_id, customer_id, deal_id, line_item_id, quantity, unit_price, currency)
+        SELECT li.item_id, d.customer_id, d.id, li.id, li.quantity,
+               ROUND(li.unit_price * (100 - li.discount_percent) / 100, 2), d.currency
+        FROM deal_line_items li
+        JOIN deals d ON d.id = li.deal_id
+        WHERE d.id = $1 AND li.item_id IS NOT NULL
+        ON CONFLICT (line_item_id) DO NOTHING
+        "#,
+    )
+    .bind(deal_id)
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+// A won deal was reopened, so its prices weren't sold after all
+pub async fn forget_sale(db: &Database, deal_id: Uuid) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM item_price_history WHERE deal_id = $1")
+        .bind(deal_id)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+// Checks for every line on the deal that is for an inventory item
+pub async fn check_deal(db: &Database, deal_id: Uuid) -> Result<Vec<PriceCheck>, sqlx::Error> {
+    sqlx::query_as::<_, PriceCheck>(
+        r#"
+        SELECT li.id as line_id,
+               ROUND(li.unit_price * (100 - li.discount_percent) / 100, 2) as net_price,
+               CASE WHEN i.currency = d.currency THEN COALESCE(i.average_cost, i.cost_price) END as unit_cost,
+               ARRAY(
+                   SELECT h.unit_price FROM item_price_history h
+                   WHERE h.item_id = li.item_id AND h.customer_id = d.customer_id
+                     AND h.deal_id <> d.id AND h.currency = d.currency
+                   ORDER BY h.sold_at DESC
+                   LIMIT 3
+               ) as recent_prices
+        FROM deal_line_items li
+        JOIN deals d ON d.id = li.deal_id
+        JOIN inventory_items i ON i.id = li.item_id
+        WHERE li.deal_id = $1
+        "#,
+    )
+    .bind(deal_id)
+    .fetch_all(db)
+    .await
+}
@@ -0,0 +1,150 @@
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use uuid::Uuid;
+
+use crate::{database::Database, models::PricingAgreement};
+
+// How close to its end an agreement starts being flagged on deals
+pub const EXPIRY_WARNING_DAYS: i32 = 30;
+
+// Selects a PricingAgreement; alias the agreement `a`. $1 is always the
+// warning window in days.
+const AGREEMENT_SELECT: &str = r#"
+    SELECT a.id, a.customer_id, a.item_id, a.fixed_price, a.discount_percent, a.valid_from, a.valid_until,
+           a.notes, a.created_by, a.created_at, i.item_name, i.sku, i.selling_price,
+           CASE
+               WHEN a.valid_until < CURRENT_DATE THEN 'expired'
+               WHEN a.valid_from > CURRENT_DATE THEN 'upcoming'
+               WHEN a.valid_until < CURRENT_DATE + $1::int THEN 'expiring'
+               ELSE 'current'
+           END as status
+    FROM pricing_agreements a
+    JOIN inventory_items i ON i.id = a.item_id
+"#;
+
+pub async fn for_customer(db: &Database, customer_id: Uuid) -> Result<Vec<PricingAgreement>, sqlx::Error> {
+    sqlx::query_as::<_, PricingAgreement>(&format!(
+        "{} WHERE a.customer_id = $2 ORDER BY i.item_name, a.valid_from DESC",
+        AGREEMENT_SELECT
+    ))
+    .bind(EXPIRY_WARNING_DAYS)
+    .bind(customer_id)
+    .fetch_all(db)
+    .await
+}
+
+// The agreement covering an item for the customer on a given day
+pub async fn in_force(db: &Database, customer_id: Uuid, item_id: Uuid, on: NaiveDate) -> Result<Option<PricingAgreement>, sqlx::Error> {
+    sqlx::query_as::<_, PricingAgreement>(&format!(
+        r#"
+        {}
+        WHERE a.customer_id = $2 AND a.item_id = $3
+          AND a.valid_from <= $4 AND (a.valid_until IS NULL OR a.valid_until >= $4)
+        ORDER BY a.valid_from DESC
+        LIMIT 1
+        "#,
+        AGREEMENT_SELECT
+    ))
+    .bind(EXPIRY_WARNING_DAYS)
+    .bind(customer_id)
+    .bind(item_id)
+    .bind(on)
+    .fetch_optional(db)
+    .await
+}
+
+// The customer's agreements about to run out on items the deal is quoting
+pub async fn expiring_for_deal(db: &Database, deal_id: Uuid) -> Result<Vec<PricingAgreement>, sqlx::Error> {
+    let agreements = sqlx::query_as::<_, PricingAgreement>(&format!(
+        r#"
+        {}
+        JOIN deals d ON d.customer_id = a.customer_id
+        WHERE d.id = $2
+          AND EXISTS (SELECT 1 FROM deal_line_items li WHERE li.deal_id = d.id AND li.item_id = a.item_id)
+        ORDER BY a.valid_until, i.item_name
+        "#,
+        AGREEMENT_SELECT
+    ))
+    .bind(EXPIRY_WARNING_DAYS)
+    .bind(deal_id)
+    .fetch_all(db)
+    .await?;
+    Ok(agreements.into_iter().filter(PricingAgreement::is_expiring).collect())
+}
+
+pub struct NewAgreement {
+    pub item_id: Uuid,
+    pub fixed_price: Option<Decimal>,
+    pub discount_percent: Option<Decimal>,
+    pub valid_from: NaiveDate,
+    pub valid_until: Option<NaiveDate>,
+    pub notes: Option<String>,
+}
+
+// Returns why it was refused, if it was. An item can only have one agreement
+// with the customer at a time.
+pub async fn add(db: &Database, customer_id: Uuid, agreement: &NewAgreement, created_by: Uuid) -> Result<Result<Uuid, String>, sqlx::Error> {
+    match (agreement.fixed_price, agreement.discount_percent) {
+        (Some(_), Some(_)) | (None, None) => {
+            return Ok(Err("Enter either a fixed price or a discount".to_string()));
+        }
+        (Some(price), None) if price < Decimal::ZERO => {
+            return Ok(Err("The price can't be negative".to_string()));
+        }
+        (None, Some(percent)) if percent <= Decimal::ZERO || percent > Decimal::from(100) => {
+            return Ok(Err("The discount has to be above 0% and at most 100%".to_string()));
+        }
+        _ => {}
+    }
+    if agreement.valid_until.is_some_and(|until| until < agreement.valid_from) {
+        return Ok(Err("The agreement can't end before it starts".to_string()));
+    }
+
+    let overlapping = sqlx::query_scalar::<_, bool>(
+        r#"
+        SELECT EXISTS(
+            SELECT 1 FROM pricing_agreements
+            WHERE customer_id = $1 AND item_id = $2
+              AND daterange(valid_from, valid_until, '[]') && daterange($3, $4, '[]')
+        )
+        "#,
+    )
+    .bind(customer_id)
+    .bind(agreement.item_id)
+    .bind(agreement.valid_from)
+    .bind(agreement.valid_until)
+    .fetch_one(db)
+    .await?;
+    if overlapping {
+        return Ok(Err("That item already has an agreement with this customer for part of that period".to_string()));
+    }
+
+    let id = sqlx::query_scalar::<_, Uuid>(
+        r#"
+        INSERT INTO pricing_agreements (customer_id, item_id, fixed_price, discount_percent, valid_from, valid_until, notes, created_by)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        RETURNING id
+        "#,
+    )
+    .bind(customer_id)
+    .bind(agreement.item_id)
+    .bind(agreement.fixed_price)
+    .bind(agreement.discount_percent)
+    .bind(agreement.valid_from)
+    .bind(agreement.valid_until)
+    .bind(&agreement.notes)
+    .bind(created_by)
+    .fetch_one(db)
+    .await?;
+    Ok(Ok(id))
+}
+
+// Lines already priced from the agreement keep their price
+pub async fn remove(db: &Database, customer_id: Uuid, id: Uuid) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query("DELETE FROM pricing_agreements WHERE id = $1 AND customer_id = $2")
+        .bind(id)
+        .bind(customer_id)
+        .execute(db)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
@@ -0,0 +1,398 @@
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+use uuid::Uuid;
+
+use crate::{
+    database::Database,
+    models::{CostRate, Project, ProjectBilling, ProjectSummary, TimeEntryDisplay, WipLine, PROJECT_STATUSES},
+};
+
+// An expense booked to a project
+#[derive(sqlx::FromRow)]
+pub struct ProjectExpense {
+    pub expense_date: NaiveDate,
+    pub user_name: String,
+    pub category_name: String,
+    pub description: Option<String>,
+    pub amount: Decimal,
+    pub status: String,
+}
+
+// Costs are time at the rate captured when it was logged plus approved
+// expenses. Revenue is the linked deal once it's won, in the base currency.
+const SUMMARY_SELECT: &str = r#"
+    SELECT p.id, p.name, p.customer_id, c.company_name as customer_name,
+           p.deal_id, d.title as deal_title, d.stage as deal_stage,
+           p.status, p.description, p.estimated_cost,
+           COALESCE(t.hours, 0) as hours,
+           COALESCE(t.time_cost, 0) as time_cost,
+           COALESCE(t.unpriced_entries, 0) as unpriced_entries,
+           COALESCE(e.approved, 0) as expense_cost,
+           COALESCE(e.pending, 0) as pending_expenses,
+           CASE WHEN d.stage = 'closed_won' THEN COALESCE(d.base_value, 0) ELSE 0 END as revenue
+    FROM projects p
+    JOIN customers c ON c.id = p.customer_id
+    LEFT JOIN deals d ON d.id = p.deal_id
+    LEFT JOIN (
+        SELECT project_id, SUM(hours) as hours,
+               SUM(ROUND(hours * hourly_cost, 2)) as time_cost,
+               COUNT(*) FILTER (WHERE hourly_cost IS NULL) as unpriced_entries
+        FROM time_entries GROUP BY project_id
+    ) t ON t.project_id = p.id
+    LEFT JOIN (
+        SELECT project_id,
+               SUM(amount) FILTER (WHERE status = 'approved') as approved,
+               SUM(amount) FILTER (WHERE status = 'pending') as pending
+        FROM expenses WHERE project_id IS NOT NULL GROUP BY project_id
+    ) e ON e.project_id = p.id
+"#;
+
+pub async fn list(db: &Database, status: Option<&str>) -> Result<Vec<ProjectSummary>, sqlx::Error> {
+    sqlx::query_as::<_, ProjectSummary>(&format!(
+        "{} WHERE $1::text IS NULL OR p.status = $1 ORDER BY p.status, p.name",
+        SUMMARY_SELECT
+    ))
+    .bind(status)
+    .fetch_all(db)
+    .await
+}
+
+pub async fn summary(db: &Database, id: Uuid) -> Result<Option<ProjectSummary>, sqlx::Error> {
+    sqlx::query_as::<_, ProjectSummary>(&format!("{} WHERE p.id = $1", SUMMARY_SELECT))
+        .bind(id)
+        .fetch_optional(db)
+        .await
+}
+
+pub async fn get(db: &Database, id: Uuid) -> Result<Option<Project>, sqlx::Error> {
+    sqlx::query_as::<_, Project>("SELECT * FROM projects WHERE id = $1")
+        .bind(id)
+        .fetch_optional(db)
+        .await
+}
+
+// Active projects for the expense form, plus `keep` so editing an expense
+// on a closed project doesn't drop it
+pub async fn options(db: &Database, keep: Option<Uuid>) -> Result<Vec<Project>, sqlx::Error> {
+    sqlx::query_as::<_, Project>(
+        "SELECT * FROM projects WHERE status = 'active' OR id = $1 ORDER BY name",
+    )
+    .bind(keep)
+    .fetch_all(db)
+    .await
+}
+
+pub async fn is_usable(db: &Database, id: Uuid, keep: Option<Uuid>) -> Result<bool, sqlx::Error> {
+    sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS(SELECT 1 FROM projects WHERE id = $1 AND (status = 'active' OR id = $2))",
+    )
+    .bind(id)
+    .bind(keep)
+    .fetch_one(db)
+    .await
+}
+
+// Amounts are in the base currency, with cents at most
+fn is_amount(amount: Decimal) -> bool {
+    amount >= Decimal::ZERO && amount.normalize().scale() <= 2 && amount < Decimal::from(1_000_000_000_000u64)
+}
+
+// What the project form sets
+pub struct ProjectFields<'a> {
+    pub name: &'a str,
+    pub customer_id: Uuid,
+    pub deal_id: Option<Uuid>,
+    pub description: Option<&'a str>,
+    pub estimated_cost: Option<Decimal>,
+}
+
+async fn validate(db: &Database, fields: &ProjectFields<'_>, status: &str) -> Result<Result<(), String>, sqlx::Error> {
+    if fields.name.is_empty() || fields.name.chars().count() > 200 {
+        return Ok(Err("The name is required and can be up to 200 characters".to_string()));
+    }
+    if fields.estimated_cost.is_some_and(|cost| !is_amount(cost)) {
+        return Ok(Err("The estimated cost has to be a positive amount with at most two decimals".to_string()));
+    }
+    if !PROJECT_STATUSES.contains(&status) {
+        return Ok(Err("Unknown status".to_string()));
+    }
+    if let Some(deal_id) = fields.deal_id {
+        let matches = sqlx::query_scalar::<_, bool>(
+            "SELECT EXISTS(SELECT 1 FROM deals WHERE id = $1 AND customer_id = $2)",
+        )
+        .bind(deal_id)
+        .bind(fields.customer_id)
+        .fetch_one(db)
+        .await?;
+        if !matches {
+            return Ok(Err("The deal has to be one of the customer's".to_string()));
+        }
+    }
+    Ok(Ok(()))
+}
+
+// Returns why it was refused, if it was
+pub async fn create(db: &Database, fields: &ProjectFields<'_>, created_by: Uuid) -> Result<Result<Uuid, String>, sqlx::Error> {
+    if let Err(reason) = validate(db, fields, "active").await? {
+        return Ok(Err(reason));
+    }
+    let id = sqlx::query_scalar::<_, Uuid>(
+        r#"
+        INSERT INTO projects (name, customer_id, deal_id, description, estimated_cost, created_by)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING id
+        "#,
+    )
+    .bind(fields.name)
+    .bind(fields.customer_id)
+    .bind(fields.deal_id)
+    .bind(fields.description)
+    .bind(fields.estimated_cost)
+    .bind(created_by)
+    .fetch_one(db)
+    .await?;
+    Ok(Ok(id))
+}
+
+pub async fn update(db: &Database, id: Uuid, fields: &ProjectFields<'_>, status: &str) -> Result<Result<(), String>, sqlx::Error> {
+    if let Err(reason) = validate(db, fields, status).await? {
+        return Ok(Err(reason));
+    }
+    let updated = sqlx::query(
+        r#"
+        UPDATE projects
+        SET name = $2, customer_id = $3, deal_id = $4, status = $5, description = $6,
+            estimated_cost = $7, updated_at = NOW()
+        WHERE id = $1
+        "#,
+    )
+    .bind(id)
+    .bind(fields.name)
+    .bind(fields.customer_id)
+    .bind(fields.deal_id)
+    .bind(status)
+    .bind(fields.description)
+    .bind(fields.estimated_cost)
+    .execute(db)
+    .await?
+    .rows_affected();
+    if updated == 0 {
+        return Ok(Err("Unknown project".to_string()));
+    }
+    Ok(Ok(()))
+}
+
+pub async fn time_entries(db: &Database, project_id: Uuid) -> Result<Vec<TimeEntryDisplay>, sqlx::Error> {
+    sqlx::query_as::<_, TimeEntryDisplay>(
+        r#"
+        SELECT t.id, t.user_id, CONCAT(u.first_name, ' ', u.last_name) as user_name,
+               t.work_date, t.hours, t.description, t.hourly_cost
+        FROM time_entries t
+        JOIN users u ON u.id = t.user_id
+        WHERE t.project_id = $1
+        ORDER BY t.work_date DESC, t.created_at DESC
+        "#,
+    )
+    .bind(project_id)
+    .fetch_all(db)
+    .await
+}
+
+// Denied ones are left out since they cost nothing
+pub async fn expenses(db: &Database, project_id: Uuid) -> Result<Vec<ProjectExpense>, sqlx::Error> {
+    sqlx::query_as::<_, ProjectExpense>(
+        r#"
+        SELECT e.expense_date, CONCAT(u.first_name, ' ', u.last_name) as user_name,
+               ec.name as category_name, e.description, e.amount, e.status
+        FROM expenses e
+        JOIN users u ON u.id = e.user_id
+        JOIN expense_categories ec ON ec.id = e.category_id
+        WHERE e.project_id = $1 AND e.status <> 'denied'
+        ORDER BY e.expense_date DESC
+        "#,
+    )
+    .bind(project_id)
+    .fetch_all(db)
+    .await
+}
+
+// Time can only go on active projects. The user's current rate is copied
+// onto the entry.
+pub async fn log_time(
+    db: &Database,
+    project_id: Uuid,
+    user_id: Uuid,
+    work_date: NaiveDate,
+    hours: Decimal,
+    description: Option<&str>,
+) -> Result<Result<Uuid, String>, sqlx::Error> {
+    if hours <= Decimal::ZERO || hours > Decimal::from(24) {
+        return Ok(Err("Hours have to be more than 0 and at most 24".to_string()));
+    }
+    if hours.normalize().scale() > 2 {
+        return Ok(Err("Hours can have at most two decimals".to_string()));
+    }
+    let id = sqlx::query_scalar::<_, Uuid>(
+        r#"
+        INSERT INTO time_entries (project_id, user_id, work_date, hours, description, hourly_cost)
+        SELECT p.id, u.id, $3, $4, $5, u.hourly_cost
+        FROM projects p, users u
+        WHERE p.id = $1 AND u.id = $2 AND p.status = 'active'
+        RETURNING id
+        "#,
+    )
+    .bind(project_id)
+    .bind(user_id)
+    .bind(work_date)
+    .bind(hours)
+    .bind(description)
+    .fetch_optional(db)
+    .await?;
+    Ok(id.ok_or_else(|| "Time can only be logged on active projects".to_string()))
+}
+
+// Who logged it, for deciding who may remove it
+pub async fn time_entry_owner(db: &Database, project_id: Uuid, entry_id: Uuid) -> Result<Option<Uuid>, sqlx::Error> {
+    sqlx::query_scalar::<_, Uuid>("SELECT user_id FROM time_entries WHERE id = $1 AND project_id = $2")
+        .bind(entry_id)
+        .bind(project_id)
+        .fetch_optional(db)
+        .await
+}
+
+pub async fn delete_time(db: &Database, entry_id: Uuid) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM time_entries WHERE id = $1")
+        .bind(entry_id)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+pub async fn cost_rates(db: &Database) -> Result<Vec<CostRate>, sqlx::Error> {
+    sqlx::query_as::<_, CostRate>(
+        r#"
+        SELECT id as user_id, CONCAT(first_name, ' ', last_name) as name, email, hourly_cost
+        FROM users
+        WHERE is_active = true
+        ORDER BY first_name, last_name
+        "#,
+    )
+    .fetch_all(db)
+    .await
+}
+
+// Only applies to time logged from now on
+pub async fn set_cost_rate(db: &Database, user_id: Uuid, hourly_cost: Option<Decimal>) -> Result<bool, sqlx::Error> {
+    let updated = sqlx::query("UPDATE users SET hourly_cost = $2 WHERE id = $1")
+        .bind(user_id)
+        .bind(hourly_cost)
+        .execute(db)
+        .await?
+        .rows_affected();
+    Ok(updated > 0)
+}
+
+pub async fn billings(db: &Database, project_id: Uuid) -> Result<Vec<ProjectBilling>, sqlx::Error> {
+    sqlx::query_as::<_, ProjectBilling>(
+        r#"
+        SELECT b.id, b.billed_on, b.billed_through, b.amount, b.reference,
+               u.first_name || ' ' || u.last_name as created_by_name
+        FROM project_billings b
+        LEFT JOIN users u ON u.id = b.created_by
+        WHERE b.project_id = $1
+        ORDER BY b.billed_on DESC, b.created_at DESC
+        "#,
+    )
+    .bind(project_id)
+    .fetch_all(db)
+    .await
+}
+
+pub async fn add_billing(
+    db: &Database,
+    project_id: Uuid,
+    billed_on: NaiveDate,
+    billed_through: NaiveDate,
+    amount: Decimal,
+    reference: Option<&str>,
+    created_by: Uuid,
+) -> Result<Result<Uuid, String>, sqlx::Error> {
+    if !is_amount(amount) {
+        return Ok(Err("The amount has to be a positive amount with at most two decimals".to_string()));
+    }
+    if reference.is_some_and(|reference| reference.chars().count() > 100) {
+        return Ok(Err("The reference can be up to 100 characters".to_string()));
+    }
+    let id = sqlx::query_scalar::<_, Uuid>(
+        r#"
+        INSERT INTO project_billings (project_id, billed_on, billed_through, amount, reference, created_by)
+        SELECT id, $2, $3, $4, $5, $6 FROM projects WHERE id = $1
+        RETURNING id
+        "#,
+    )
+    .bind(project_id)
+    .bind(billed_on)
+    .bind(billed_through)
+    .bind(amount)
+    .bind(reference)
+    .bind(created_by)
+    .fetch_optional(db)
+    .await?;
+    Ok(id.ok_or_else(|| "Unknown project".to_string()))
+}
+
+pub async fn delete_billing(db: &Database, project_id: Uuid, billing_id: Uuid) -> Result<bool, sqlx::Error> {
+    let deleted = sqlx::query("DELETE FROM project_billings WHERE id = $1 AND project_id = $2")
+        .bind(billing_id)
+        .bind(project_id)
+        .execute(db)
+        .await?
+        .rows_affected();
+    Ok(deleted > 0)
+}
+
+// Work in progress as of a day: costs and billings dated up to it. Closed
+// projects are left out unless asked for, going by their status today.
+pub async fn wip(db: &Database, as_of: NaiveDate, include_closed: bool) -> Result<Vec<WipLine>, sqlx::Error> {
+    sqlx::query_as::<_, WipLine>(
+        r#"
+        SELECT p.id, p.name, c.company_name as customer_name, p.status, p.estimated_cost,
+               CASE WHEN d.stage = 'closed_won' THEN COALESCE(d.base_value, 0) ELSE 0 END as contract_value,
+               COALESCE(t.hours, 0) as hours,
+               COALESCE(t.time_cost, 0) as time_cost,
+               COALESCE(e.expense_cost, 0) as expense_cost,
+               COALESCE(t.unbilled_hours, 0) as unbilled_hours,
+               COALESCE(t.unbilled_time_cost, 0) as unbilled_time_cost,
+               COALESCE(e.unbilled_expenses, 0) as unbilled_expenses,
+               COALESCE(b.billed, 0) as billed,
+               b.billed_through
+        FROM projects p
+        JOIN customers c ON c.id = p.customer_id
+        LEFT JOIN deals d ON d.id = p.deal_id
+        LEFT JOIN (
+            SELECT project_id, SUM(amount) as billed, MAX(billed_through) as billed_through
+            FROM project_billings WHERE billed_on <= $1 GROUP BY project_id
+        ) b ON b.project_id = p.id
+        LEFT JOIN LATERAL (
+            SELECT SUM(te.hours) as hours,
+                   SUM(ROUND(te.hours * te.hourly_cost, 2)) as time_cost,
+                   SUM(te.hours) FILTER (WHERE b.billed_through IS NULL OR te.work_date > b.billed_through) as unbilled_hours,
+                   SUM(ROUND(te.hours * te.hourly_cost, 2)) FILTER (WHERE b.billed_through IS NULL OR te.work_date > b.billed_through) as unbilled_time_cost
+            FROM time_entries te
+            WHERE te.project_id = p.id AND te.work_date <= $1
+        ) t ON true
+        LEFT JOIN LATERAL (
+            SELECT SUM(ex.amount) as expense_cost,
+                   SUM(ex.amount) FILTER (WHERE b.billed_through IS NULL OR ex.expense_date > b.billed_through) as unbilled_expenses
+            FROM expenses ex
+            WHERE ex.project_id = p.id AND ex.status = 'approved' AND ex.expense_date <= $1
+        ) e ON true
+        WHERE $2 OR p.status = 'active'
+        ORDER BY c.company_name, p.name
+        "#,
+    )
+    .bind(as_of)
+    .bind(include_closed)
+    .fetch_all(db)
+    .await
+}
@@ -0,0 +1,89 @@
+use sqlx::{Executor, Postgres, Transaction};
+use std::{
+    collections::HashMap,
+    sync::{Mutex, OnceLock},
+};
+use uuid::Uuid;
+
+use crate::database::Database;
+
+// Reports one user can have running at once
+const MAX_RUNNING_PER_USER: usize = 2;
+
+// How long a single report query may run before Postgres cancels it
+const STATEMENT_TIMEOUT: &str = "20s";
+
+// Postgres' SQLSTATE for a statement cancelled by statement_timeout
+const QUERY_CANCELED: &str = "57014";
+
+static RUNNING: OnceLock<Mutex<HashMap<Uuid, usize>>> = OnceLock::new();
+
+fn running() -> &'static Mutex<HashMap<Uuid, usize>> {
+    RUNNING.get_or_init(|| Mutex::new(HashMap::new()))
+}
+
+// Why a report wasn't run
+#[derive(Clone, Copy)]
+pub enum Refusal {
+    // The user already has their share of reports running
+    Busy,
+    // It ran past STATEMENT_TIMEOUT
+    TooSlow,
+}
+
+impl Refusal {
+    pub fn message(self) -> &'static str {
+        match self {
+            Self::Busy => "You already have reports running. Please wait for them to finish, then try again.",
+            Self::TooSlow => {
+                "This report covers too much to run in one go. Please narrow your filters, such as a shorter period, one customer or one user, and try again."
+            }
+        }
+    }
+}
+
+// One of a user's running reports; the slot frees up when dropped
+pub struct Slot {
+    user_id: Uuid,
+}
+
+impl Drop for Slot {
+    fn drop(&mut self) {
+        let mut running = running().lock().unwrap();
+        if let Some(count) = running.get_mut(&self.user_id) {
+            *count -= 1;
+            if *count == 0 {
+                running.remove(&self.user_id);
+            }
+        }
+    }
+}
+
+// Counted per app instance, as the guard is only there to stop one user
+// tying up the pool
+pub fn claim(user_id: Uuid) -> Result<Slot, Refusal> {
+    let mut running = running().lock().unwrap();
+    let count = running.entry(user_id).or_insert(0);
+    if *count >= MAX_RUNNING_PER_USER {
+        return Err(Refusal::Busy);
+    }
+    *count += 1;
+    Ok(Slot { user_id })
+}
+
+// A read-only transaction for a report's queries, each cancelled once it
+// runs past STATEMENT_TIMEOUT. Dropping it ends the transaction and with it
+// the timeout, before the connection goes back to the pool.
+pub async fn begin(db: &Database) -> Result<Transaction<'static, Postgres>, sqlx::Error> {
+    let mut tx = db.begin().await?;
+    let settings = format!("SET TRANSACTION READ ONLY; SET LOCAL statement_timeout = '{}'", STATEMENT_TIMEOUT);
+    (&mut *tx).execute(settings.as_str()).await?;
+    Ok(tx)
+}
+
+pub fn timed_out(error: &sqlx::Error) -> bool {
+    error
+        .as_database_error()
+        .and_then(|e| e.code())
+        .is_some_and(|code| code == QUERY_CANCELED)
+}
@@ -0,0 +1,107 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use rust_decimal::Decimal;
+use sqlx::{FromRow, PgConnection};
+use uuid::Uuid;
+
+use crate::{database::Database, models::DEAL_STAGES, services::locations::warehouse_condition};
+
+// How often the scheduler refreshes the materialized views behind the summary
+// report, and so how far behind its totals can be
+pub const REFRESH_MINUTES: u64 = 15;
+
+#[derive(FromRow)]
+pub struct StageTotal {
+    pub stage: String,
+    pub deal_count: i64,
+    pub total_value: Decimal,
+}
+
+impl StageTotal {
+    pub fn label(&self) -> String {
+        self.stage.replace('_', " ")
+    }
+}
+
+#[derive(FromRow)]
+pub struct WarehouseValuation {
+    pub warehouse_name: String,
+    pub item_count: i64,
+    pub quantity_on_hand: i64,
+    pub total_value: Decimal,
+    // Held for suppliers and customers; counted, never valued
+    pub consigned_on_hand: i64,
+    pub customer_owned_on_hand: i64,
+}
+
+// Recomputes the totals for every tenant at once
+pub async fn refresh(db: &Database) -> Result<(), sqlx::Error> {
+    sqlx::query("SELECT refresh_reporting_views()").execute(db).await?;
+    Ok(())
+}
+
+// When the totals were last refreshed; None if the organization had nothing
+// to total at the time
+pub async fn refreshed_at(conn: &mut PgConnection) -> Result<Option<DateTime<Utc>>, sqlx::Error> {
+    sqlx::query_scalar::<_, Option<DateTime<Utc>>>(
+        r#"
+        SELECT GREATEST(
+            (SELECT MAX(refreshed_at) FROM reporting_pipeline_by_stage),
+            (SELECT MAX(refreshed_at) FROM reporting_expenses_by_category_month),
+            (SELECT MAX(refreshed_at) FROM reporting_stock_valuation)
+        )
+        "#,
+    )
+    .fetch_one(conn)
+    .await
+}
+
+// In pipeline order
+pub async fn pipeline_by_stage(conn: &mut PgConnection) -> Result<Vec<StageTotal>, sqlx::Error> {
+    sqlx::query_as::<_, StageTotal>(
+        r#"
+        SELECT stage, deal_count, total_value FROM reporting_pipeline_by_stage
+        ORDER BY array_position($1::text[], stage::text) NULLS LAST, stage
+        "#,
+    )
+    .bind(DEAL_STAGES)
+    .fetch_all(conn)
+    .await
+}
+
+// (category, "YYYY-MM", amount) for the months from `from` through `to`
+pub async fn expenses_by_category_month(
+    conn: &mut PgConnection,
+    from: NaiveDate,
+    to: NaiveDate,
+) -> Result<Vec<(String, String, Decimal)>, sqlx::Error> {
+    sqlx::query_as::<_, (String, String, Decimal)>(
+        r#"
+        SELECT category_name, TO_CHAR(month, 'YYYY-MM'), total_amount::numeric
+        FROM reporting_expenses_by_category_month
+        WHERE month BETWEEN $1 AND $2
+        "#,
+    )
+    .bind(from)
+    .bind(to)
+    .fetch_all(conn)
+    .await
+}
+
+// Limited to the given warehouses, CurrentUser::warehouse_ids; None for all
+pub async fn stock_valuation(
+    conn: &mut PgConnection,
+    warehouse_ids: Option<&[Uuid]>,
+) -> Result<Vec<WarehouseValuation>, sqlx::Error> {
+    sqlx::query_as::<_, WarehouseValuation>(&format!(
+        r#"
+        SELECT warehouse_name, item_count, quantity_on_hand, total_value, consigned_on_hand, customer_owned_on_hand
+        FROM reporting_stock_valuation
+        WHERE {}
+        ORDER BY total_value DESC, warehouse_name
+        "#,
+        warehouse_condition("warehouse_id", 1)
+    ))
+    .bind(warehouse_ids)
+    .fetch_all(conn)
+    .await
+}
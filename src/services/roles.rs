@@ -0,0 +1,71 @@
+use uuid::Uuid;
+
+use crate::database::Database;
+
+// A role and every role above it ($1 = role). UNION stops at a loop.
+const ANCESTORS_SQL: &str = r#"
+    WITH RECURSIVE ancestors AS (
+        SELECT id, parent_role_id FROM roles WHERE id = $1
+        UNION
+        SELECT r.id, r.parent_role_id FROM roles r JOIN ancestors a ON r.id = a.parent_role_id
+    )
+    SELECT id FROM ancestors
+"#;
+
+pub async fn ancestor_ids(db: &Database, role_id: Uuid) -> Result<Vec<Uuid>, sqlx::Error> {
+    sqlx::query_scalar::<_, Uuid>(ANCESTORS_SQL)
+        .bind(role_id)
+        .fetch_all(db)
+        .await
+}
+
+// Why `parent_id` can't be the parent of `role_id` (None when creating), if it can't
+pub async fn check_parent(db: &Database, role_id: Option<Uuid>, parent_id: Uuid) -> Result<Result<(), String>, sqlx::Error> {
+    let chain = ancestor_ids(db, parent_id).await?;
+    if chain.is_empty() {
+        return Ok(Err("The parent role doesn't exist".to_string()));
+    }
+    if role_id.is_some_and(|id| chain.contains(&id)) {
+        return Ok(Err("A role can't inherit from itself or from a role that inherits from it".to_string()));
+    }
+    Ok(Ok(()))
+}
+
+// What a role picks up from `parent_id` and above. Like sign-in, the walk
+// stops at an inactive role.
+pub async fn inherited_permissions(db: &Database, parent_id: Uuid) -> Result<Vec<String>, sqlx::Error> {
+    sqlx::query_scalar::<_, String>(
+        r#"
+        WITH RECURSIVE inherited AS (
+            SELECT id, parent_role_id, permissions FROM roles WHERE id = $1 AND is_active = true
+            UNION
+            SELECT r.id, r.parent_role_id, r.permissions FROM roles r
+            JOIN inherited i ON r.id = i.parent_role_id
+            WHERE r.is_active = true
+        )
+        SELECT DISTINCT jsonb_array_elements_text(permissions) as permission
+        FROM inherited
+        ORDER BY permission
+        "#,
+    )
+    .bind(parent_id)
+    .fetch_all(db)
+    .await
+}
+
+// A role and every role that extends it, directly or not
+pub async fn descendant_ids(db: &Database, role_id: Uuid) -> Result<Vec<Uuid>, sqlx::Error> {
+    sqlx::query_scalar::<_, Uuid>(
+        r#"
+        WITH RECURSIVE descendants AS (
+            SELECT id FROM roles WHERE id = $1
+            UNION
+            SELECT r.id FROM roles r JOIN descendants d ON r.parent_role_id = d.id
+        )
+        SELECT id FROM descendants
+        "#,
+    )
+    .bind(role_id)
+    .fetch_all(db)
+    .await
+}
@@ -0,0 +1,28 @@
+use sqlx::{Postgres, Transaction};
+
+use crate::database::Database;
+
+// Start a transaction for an API request. For sandbox keys the sandbox schema
+// is searched first, so unqualified queries on customers and contacts hit the
+// shadow tables while everything else still resolves to public.
+pub async fn begin(db: &Database, sandbox: bool) -> Result<Transaction<'static, Postgres>, sqlx::Error> {
+    let mut tx = db.begin().await?;
+    if sandbox {
+        sqlx::query("SET LOCAL search_path TO sandbox, public")
+            .execute(&mut *tx)
+            .await?;
+    }
+    Ok(tx)
+}
+
+// Throw away everything the tenant's sandbox keys have written. Not a
+// TRUNCATE, which would ignore row level security and empty every tenant's.
+pub async fn reset(db: &Database) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM sandbox.contacts")
+        .execute(db)
+        .await?;
+    sqlx::query("DELETE FROM sandbox.customers")
+        .execute(db)
+        .await?;
+    Ok(())
+}
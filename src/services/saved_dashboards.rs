@@ -0,0 +1,143 @@
+use chrono::{DateTime, Utc};
+use rand::{distributions::Alphanumeric, Rng};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+use crate::{database::Database, services::dashboard};
+
+// A set of widgets someone put together. Only its owner changes it; with a
+// share token anyone signed in who has the link can view it.
+#[derive(Debug, FromRow)]
+pub struct SavedDashboard {
+    pub id: Uuid,
+    pub name: String,
+    // Keys of services::dashboard::WIDGETS, in the order shown
+    pub widgets: Vec<String>,
+    pub share_token: Option<String>,
+    pub shared_at: Option<DateTime<Utc>>,
+    pub owner_name: String,
+}
+
+const SAVED_DASHBOARD_SELECT: &str = r#"
+    SELECT sd.id, sd.name, sd.widgets, sd.share_token, sd.shared_at,
+           CONCAT(u.first_name, ' ', u.last_name) as owner_name
+    FROM saved_dashboards sd
+    JOIN users u ON u.id = sd.created_by
+"#;
+
+// Widgets are picked from those the owner may see themselves
+pub fn validate(name: &str, widgets: &[String], permissions: &[String]) -> Result<(), String> {
+    if name.is_empty() || name.chars().count() > 100 {
+        return Err("The name is required and can be up to 100 characters".to_string());
+    }
+    if widgets.is_empty() {
+        return Err("Pick at least one widget".to_string());
+    }
+    let allowed = dashboard::widgets_allowed(permissions);
+    if let Some(key) = widgets.iter().find(|key| !allowed.iter().any(|def| def.key == key.as_str())) {
+        return Err(format!("Unknown widget {}", key));
+    }
+    Ok(())
+}
+
+pub async fn list_for(db: &Database, user_id: Uuid) -> Result<Vec<SavedDashboard>, sqlx::Error> {
+    sqlx::query_as::<_, SavedDashboard>(&format!(
+        "{} WHERE sd.created_by = $1 ORDER BY sd.name",
+        SAVED_DASHBOARD_SELECT
+    ))
+    .bind(user_id)
+    .fetch_all(db)
+    .await
+}
+
+pub async fn get_owned(db: &Database, id: Uuid, user_id: Uuid) -> Result<Option<SavedDashboard>, sqlx::Error> {
+    sqlx::query_as::<_, SavedDashboard>(&format!(
+        "{} WHERE sd.id = $1 AND sd.created_by = $2",
+        SAVED_DASHBOARD_SELECT
+    ))
+    .bind(id)
+    .bind(user_id)
+    .fetch_optional(db)
+    .await
+}
+
+// Dashboards of deactivated owners stop being shared
+pub async fn by_token(db: &Database, token: &str) -> Result<Option<SavedDashboard>, sqlx::Error> {
+    sqlx::query_as::<_, SavedDashboard>(&format!(
+        "{} WHERE sd.share_token = $1 AND u.is_active = true",
+        SAVED_DASHBOARD_SELECT
+    ))
+    .bind(token)
+    .fetch_optional(db)
+    .await
+}
+
+pub async fn create(db: &Database, name: &str, widgets: &[String], created_by: Uuid) -> Result<Uuid, sqlx::Error> {
+    sqlx::query_scalar::<_, Uuid>(
+        "INSERT INTO saved_dashboards (name, widgets, created_by) VALUES ($1, $2, $3) RETURNING id",
+    )
+    .bind(name)
+    .bind(widgets)
+    .bind(created_by)
+    .fetch_one(db)
+    .await
+}
+
+pub async fn update(db: &Database, id: Uuid, owner: Uuid, name: &str, widgets: &[String]) -> Result<bool, sqlx::Error> {
+    let updated = sqlx::query(
+        "UPDATE saved_dashboards SET name = $3, widgets = $4, updated_at = NOW() WHERE id = $1 AND created_by = $2",
+    )
+    .bind(id)
+    .bind(owner)
+    .bind(name)
+    .bind(widgets)
+    .execute(db)
+    .await?
+    .rows_affected();
+    Ok(updated > 0)
+}
+
+// A new link each time, so sharing again revokes the old one. None when the
+// dashboard isn't the owner's.
+pub async fn share(db: &Database, id: Uuid, owner: Uuid) -> Result<Option<String>, sqlx::Error> {
+    let token: String = rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(40)
+        .map(char::from)
+        .collect();
+
+    sqlx::query_scalar::<_, String>(
+        r#"
+        UPDATE saved_dashboards SET share_token = $3, shared_at = NOW()
+        WHERE id = $1 AND created_by = $2
+        RETURNING share_token
+        "#,
+    )
+    .bind(id)
+    .bind(owner)
+    .bind(token)
+    .fetch_optional(db)
+    .await
+}
+
+pub async fn unshare(db: &Database, id: Uuid, owner: Uuid) -> Result<bool, sqlx::Error> {
+    let updated = sqlx::query(
+        "UPDATE saved_dashboards SET share_token = NULL, shared_at = NULL WHERE id = $1 AND created_by = $2",
+    )
+    .bind(id)
+    .bind(owner)
+    .execute(db)
+    .await?
+    .rows_affected();
+    Ok(updated > 0)
+}
+
+pub async fn delete(db: &Database, id: Uuid, owner: Uuid) -> Result<bool, sqlx::Error> {
+    let deleted = sqlx::query("DELETE FROM saved_dashboards WHERE id = $1 AND created_by = $2")
+        .bind(id)
+        .bind(owner)
+        .execute(db)
+        .await?
+        .rows_affected();
+    Ok(deleted > 0)
+}
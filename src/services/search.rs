@@ -0,0 +1,207 @@
+use serde::Serialize;
+use sqlx::PgConnection;
+use uuid::Uuid;
+
+use crate::{
+    middleware::CurrentUser,
+    services::sharing::{self, RecordKind},
+};
+
+// Most matches shown for each kind of record
+pub const GROUP_LIMIT: i64 = 10;
+
+// The documents below must match the expressions indexed in
+// migrations/074_add_search_indexes.sql, or searches fall back to scanning
+const CUSTOMER_DOCUMENT: &str = "search_document(c.company_name, c.industry, c.email, c.phone, c.website, c.city, c.country, c.notes)";
+const CONTACT_DOCUMENT: &str = "search_document(ct.first_name, ct.last_name, ct.title, ct.email, ct.phone, ct.mobile, ct.notes)";
+const DEAL_DOCUMENT: &str = "search_document(d.title, d.description)";
+const ACTIVITY_DOCUMENT: &str = "search_document(a.subject, a.description)";
+const ITEM_DOCUMENT: &str =
+    "search_document(i.item_name, i.sku, i.upc, i.brand, i.model, i.category, i.short_description, i.description)";
+
+#[derive(Serialize)]
+pub struct SearchHit {
+    pub id: Uuid,
+    pub title: String,
+    pub subtitle: String,
+    pub url: String,
+}
+
+#[derive(Serialize)]
+pub struct SearchGroup {
+    pub kind: &'static str,
+    pub label: &'static str,
+    pub hits: Vec<SearchHit>,
+}
+
+// One row per match: the record, the page to open it on, and what to show
+#[derive(sqlx::FromRow)]
+struct HitRow {
+    id: Uuid,
+    url: String,
+    title: Option<String>,
+    subtitle: Option<String>,
+}
+
+// Runs `select` for one kind of record. It binds $1 to the user, $2 to the
+// query and $3 to the limit; `{scope}` becomes the visibility condition.
+async fn hits(
+    conn: &mut PgConnection,
+    user: &CurrentUser,
+    query: &str,
+    select: &str,
+    scope: Option<String>,
+) -> Result<Vec<SearchHit>, sqlx::Error> {
+    let scope = scope.map(|condition| format!("AND {}", condition)).unwrap_or_default();
+    let rows = sqlx::query_as::<_, HitRow>(&select.replace("{scope}", &scope))
+        .bind(user.id)
+        .bind(query)
+        .bind(GROUP_LIMIT)
+        .fetch_all(conn)
+        .await?;
+    Ok(rows
+        .into_iter()
+        .map(|row| SearchHit {
+            id: row.id,
+            url: row.url,
+            title: row.title.filter(|title| !title.trim().is_empty()).unwrap_or_else(|| "(untitled)".to_string()),
+            subtitle: row.subtitle.unwrap_or_default(),
+        })
+        .collect())
+}
+
+// Matches for a to_tsquery() string (see utils::search_terms), best first and
+// grouped by kind. Only kinds the user may see are searched, and only records
+// they could open. `scoped` is false for sandbox API keys, whose shadow
+// customers and contacts are shared by every sandbox key.
+pub async fn search(
+    conn: &mut PgConnection,
+    user: &CurrentUser,
+    scoped: bool,
+    query: &str,
+) -> Result<Vec<SearchGroup>, sqlx::Error> {
+    let scoped = scoped && sharing::is_scoped(user);
+    let scope = |kind: RecordKind, alias: &str| scoped.then(|| sharing::visibility_condition(kind, alias, 1));
+    let mut groups = Vec::new();
+
+    if user.can("customers:read") {
+        let customers = hits(
+            conn,
+            user,
+            query,
+            &format!(
+                r#"
+                SELECT c.id, '/crm/customers/' || c.id AS url, c.company_name AS title,
+                       NULLIF(concat_ws(' · ', NULLIF(c.industry, ''), NULLIF(c.city, '')), '') AS subtitle
+                FROM customers c, to_tsquery('english', $2) query
+                WHERE {doc} @@ query {{scope}}
+                ORDER BY ts_rank({doc}, query) DESC, c.company_name
+                LIMIT $3
+                "#,
+                doc = CUSTOMER_DOCUMENT
+            ),
+            scope(RecordKind::Customer, "c"),
+        )
+        .await?;
+        groups.push(SearchGroup { kind: "customers", label: "Customers", hits: customers });
+
+        let contacts = hits(
+            conn,
+            user,
+            query,
+            &format!(
+                r#"
+                SELECT ct.id, '/crm/customers/' || ct.customer_id || '/contacts/' || ct.id AS url,
+                       concat_ws(' ', ct.first_name, ct.last_name) AS title,
+                       NULLIF(concat_ws(' · ', NULLIF(ct.title, ''), c.company_name), '') AS subtitle
+                FROM contacts ct
+                JOIN customers c ON c.id = ct.customer_id,
+                     to_tsquery('english', $2) query
+                WHERE {doc} @@ query {{scope}}
+                ORDER BY ts_rank({doc}, query) DESC, ct.last_name, ct.first_name
+                LIMIT $3
+                "#,
+                doc = CONTACT_DOCUMENT
+            ),
+            scope(RecordKind::Customer, "c"),
+        )
+        .await?;
+        groups.push(SearchGroup { kind: "contacts", label: "Contacts", hits: contacts });
+    }
+
+    let deals = hits(
+        conn,
+        user,
+        query,
+        &format!(
+            r#"
+            SELECT d.id, '/crm/deals/' || d.id AS url, d.title,
+                   NULLIF(concat_ws(' · ', c.company_name, INITCAP(REPLACE(d.stage, '_', ' '))), '') AS subtitle
+            FROM deals d
+            LEFT JOIN customers c ON c.id = d.customer_id,
+                 to_tsquery('english', $2) query
+            WHERE {doc} @@ query {{scope}}
+            ORDER BY ts_rank({doc}, query) DESC, d.created_at DESC
+            LIMIT $3
+            "#,
+            doc = DEAL_DOCUMENT
+        ),
+        scope(RecordKind::Deal, "d"),
+    )
+    .await?;
+    groups.push(SearchGroup { kind: "deals", label: "Deals", hits: deals });
+
+    // Activities have no page of their own, so they open on their deal or
+    // customer when they have one
+    let activities = hits(
+        conn,
+        user,
+        query,
+        &format!(
+            r#"
+            SELECT a.id,
+                   CASE
+                       WHEN a.deal_id IS NOT NULL THEN '/crm/deals/' || a.deal_id
+                       WHEN a.customer_id IS NOT NULL THEN '/crm/customers/' || a.customer_id
+                       ELSE '/crm/activities/' || a.id || '/edit'
+                   END AS url,
+                   a.subject AS title,
+                   concat_ws(' · ', INITCAP(a.activity_type), TO_CHAR(a.activity_date, 'YYYY-MM-DD'), c.company_name) AS subtitle
+            FROM activities a
+            LEFT JOIN customers c ON c.id = a.customer_id,
+                 to_tsquery('english', $2) query
+            WHERE {doc} @@ query {{scope}}
+            ORDER BY ts_rank({doc}, query) DESC, a.activity_date DESC
+            LIMIT $3
+            "#,
+            doc = ACTIVITY_DOCUMENT
+        ),
+        scope(RecordKind::Activity, "a"),
+    )
+    .await?;
+    groups.push(SearchGroup { kind: "activities", label: "Activities", hits: activities });
+
+    if user.can("inventory:read") {
+        let items = hits(
+            conn,
+            user,
+            query,
+            &format!(
+                r#"
+                SELECT i.id, '/inventory/items/' || i.id AS url, i.item_name AS title,
+                       NULLIF(concat_ws(' · ', NULLIF(i.sku, ''), NULLIF(i.brand, '')), '') AS subtitle
+                FROM inventory_items i, to_tsquery('english', $2) query
+                WHERE {doc} @@ query {{scope}}
+                ORDER BY ts_rank({doc}, query) DESC, i.item_name
+                LIMIT $3
+                "#,
+                doc = ITEM_DOCUMENT
+            ),
+            None,
+        )
+        .await?;
+        groups.push(SearchGroup { kind: "inventory_items", label: "Inventory Items", hits: items });
+    }
+
+    Ok(groups)
+}
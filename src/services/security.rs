@@ -0,0 +1,200 @@
+use uuid::Uuid;
+
+use crate::{database::Database, middleware::ClientInfo, models::SecurityEvent, services::mailer};
+
+// Long-lived cookie identifying the browser a user signs in from
+pub const DEVICE_COOKIE: &str = "device_id";
+
+pub struct LoginContext {
+    pub device_id: String,
+    pub ip_address: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+// Record a successful sign-in. The first sign-in ever isn't flagged; after that
+// a device cookie we haven't seen for this user earns a notification and email.
+pub async fn record_login(db: &Database, user_id: Uuid, context: &LoginContext) -> Result<(), sqlx::Error> {
+    let (has_logins, known_device) = sqlx::query_as::<_, (bool, bool)>(
+        r#"
+        SELECT
+            EXISTS (SELECT 1 FROM security_events WHERE user_id = $1 AND event_type = 'login'),
+            EXISTS (SELECT 1 FROM security_events WHERE user_id = $1 AND event_type = 'login' AND device_id = $2)
+        "#,
+    )
+    .bind(user_id)
+    .bind(&context.device_id)
+    .fetch_one(db)
+    .await?;
+
+    let new_device = has_logins && !known_device;
+
+    sqlx::query(
+        r#"
+        INSERT INTO security_events (user_id, event_type, device_id, new_device, ip_address, user_agent)
+        VALUES ($1, 'login', $2, $3, $4, $5)
+        "#,
+    )
+    .bind(user_id)
+    .bind(&context.device_id)
+    .bind(new_device)
+    .bind(&context.ip_address)
+    .bind(&context.user_agent)
+    .execute(db)
+    .await?;
+
+    if new_device {
+        let from = match (&context.user_agent, &context.ip_address) {
+            (Some(agent), Some(ip)) => format!("{} at {}", agent, ip),
+            (Some(agent), None) => agent.clone(),
+            (None, Some(ip)) => ip.clone(),
+            (None, None) => "an unknown device".to_string(),
+        };
+        alert(
+            db,
+            user_id,
+            "New sign-in to your Allo account",
+            &format!("Your account was signed in to from a new device: {}.", from),
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+// Record a change made to the user's account and let them know about it
+pub async fn record_change(
+    db: &Database,
+    user_id: Uuid,
+    event_type: &str,
+    detail: &str,
+    changed_by: Uuid,
+) -> Result<(), sqlx::Error> {
+    sqlx::query("INSERT INTO security_events (user_id, event_type, detail) VALUES ($1, $2, $3)")
+        .bind(user_id)
+        .bind(event_type)
+        .bind(detail)
+        .execute(db)
+        .await?;
+
+    let by = sqlx::query_scalar::<_, String>("SELECT CONCAT(first_name, ' ', last_name) FROM users WHERE id = $1")
+        .bind(changed_by)
+        .fetch_optional(db)
+        .await?
+        .unwrap_or_else(|| "an administrator".to_string());
+
+    let (subject, message) = match event_type {
+        "password_changed" => (
+            "Your Allo password was changed",
+            format!("Your password was changed by {}.", by),
+        ),
+        "impersonated" => (
+            "An administrator signed in as you",
+            format!("{} signed in to Allo as you to help troubleshoot your account.", by),
+        ),
+        _ => (
+            "Your Allo roles were changed",
+            format!("Your roles were changed by {}: {}.", by, detail),
+        ),
+    };
+
+    alert(db, user_id, subject, &message).await
+}
+
+// Record that repeated failed sign-ins locked the account, and warn the owner
+pub async fn record_lockout(
+    db: &Database,
+    user_id: Uuid,
+    failures: i32,
+    ip_address: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    let detail = format!("{} failed sign-ins in a row", failures);
+    sqlx::query("INSERT INTO security_events (user_id, event_type, ip_address, detail) VALUES ($1, 'account_locked', $2, $3)")
+        .bind(user_id)
+        .bind(ip_address)
+        .bind(&detail)
+        .execute(db)
+        .await?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO audit_logs (action, resource_type, resource_id, new_values, ip_address)
+        VALUES ('lock', 'user', $1, $2, $3)
+        "#,
+    )
+    .bind(user_id)
+    .bind(serde_json::json!({ "locked": true, "reason": "failed_logins", "failures": failures }))
+    .bind(ip_address)
+    .execute(db)
+    .await?;
+
+    alert(
+        db,
+        user_id,
+        "Your Allo account has been locked",
+        &format!(
+            "Your account was locked after {}{}. Try again later, or ask an administrator to unlock it.",
+            detail,
+            ip_address.map(|ip| format!(", the last from {}", ip)).unwrap_or_default()
+        ),
+    )
+    .await
+}
+
+// Audit a sign-in or sign-out. The session is the resource, so the two
+// entries of one visit pair up.
+pub async fn audit_session(
+    db: &Database,
+    action: &str,
+    user_id: Uuid,
+    impersonator_id: Option<Uuid>,
+    session_id: Uuid,
+    client: &ClientInfo,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO audit_logs (user_id, impersonator_id, action, resource_type, resource_id, ip_address, user_agent)
+        VALUES ($1, $2, $3, 'session', $4, $5, $6)
+        "#,
+    )
+    .bind(user_id)
+    .bind(impersonator_id)
+    .bind(action)
+    .bind(session_id)
+    .bind(&client.ip_address)
+    .bind(&client.user_agent)
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+async fn alert(db: &Database, user_id: Uuid, subject: &str, message: &str) -> Result<(), sqlx::Error> {
+    sqlx::query("INSERT INTO notifications (user_id, message, link_url) VALUES ($1, $2, $3)")
+        .bind(user_id)
+        .bind(message)
+        .bind("/profile#security")
+        .execute(db)
+        .await?;
+
+    let email = sqlx::query_scalar::<_, String>("SELECT email FROM users WHERE id = $1")
+        .bind(user_id)
+        .fetch_one(db)
+        .await?;
+
+    let body = format!(
+        "{}\n\nIf this wasn't you, contact your administrator right away. Review recent activity at {}/profile#security",
+        message,
+        mailer::app_url()
+    );
+    mailer::queue_email(db, &email, subject, &mailer::text_to_html(&body)).await?;
+
+    Ok(())
+}
+
+pub async fn recent_events(db: &Database, user_id: Uuid) -> Result<Vec<SecurityEvent>, sqlx::Error> {
+    sqlx::query_as::<_, SecurityEvent>(
+        "SELECT * FROM security_events WHERE user_id = $1 ORDER BY created_at DESC LIMIT 25",
+    )
+    .bind(user_id)
+    .fetch_all(db)
+    .await
+}
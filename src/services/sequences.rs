@@ -0,0 +1,464 @@
+use std::collections::HashSet;
+
+use sqlx::FromRow;
+use uuid::Uuid;
+
+use crate::{
+    database::Database,
+    models::{Sequence, SequenceEnrollment, SequenceStep},
+    services::{activity_types, mailer, mass_email},
+};
+
+// Selects a SequenceEnrollment; alias the enrollment `e`
+const ENROLLMENT_SELECT: &str = r#"
+    SELECT e.id, e.sequence_id, q.name as sequence_name, e.customer_id, e.contact_id, e.deal_id,
+           e.status, e.stop_reason, NULLIF(concat_ws(' ', u.first_name, u.last_name), '') as enrolled_by_name,
+           e.started_at, e.finished_at,
+           (SELECT COUNT(*) FROM sequence_step_runs r WHERE r.enrollment_id = e.id) as steps_run,
+           (SELECT COUNT(*) FROM sequence_steps s WHERE s.sequence_id = e.sequence_id) as step_count,
+           (SELECT MIN(s.day) FROM sequence_steps s
+            WHERE s.sequence_id = e.sequence_id
+              AND NOT EXISTS (SELECT 1 FROM sequence_step_runs r WHERE r.enrollment_id = e.id AND r.step_id = s.id)) as next_day
+    FROM sequence_enrollments e
+    JOIN sequences q ON q.id = e.sequence_id
+    LEFT JOIN users u ON u.id = e.enrolled_by
+"#;
+
+pub async fn list(db: &Database) -> Result<Vec<Sequence>, sqlx::Error> {
+    sqlx::query_as::<_, Sequence>(
+        r#"
+        SELECT q.*,
+               (SELECT COUNT(*) FROM sequence_steps s WHERE s.sequence_id = q.id) as step_count,
+               (SELECT COUNT(*) FROM sequence_enrollments e WHERE e.sequence_id = q.id AND e.status = 'active') as active_enrollments
+        FROM sequences q
+        ORDER BY q.name
+        "#,
+    )
+    .fetch_all(db)
+    .await
+}
+
+// Sequences that can be applied: switched on and with at least one step
+pub async fn available(db: &Database) -> Result<Vec<Sequence>, sqlx::Error> {
+    sqlx::query_as::<_, Sequence>(
+        r#"
+        SELECT q.* FROM sequences q
+        WHERE q.is_active AND EXISTS (SELECT 1 FROM sequence_steps s WHERE s.sequence_id = q.id)
+        ORDER BY q.name
+        "#,
+    )
+    .fetch_all(db)
+    .await
+}
+
+pub async fn find(db: &Database, id: Uuid) -> Result<Option<Sequence>, sqlx::Error> {
+    sqlx::query_as::<_, Sequence>("SELECT * FROM sequences WHERE id = $1")
+        .bind(id)
+        .fetch_optional(db)
+        .await
+}
+
+pub async fn steps(db: &Database, sequence_id: Uuid) -> Result<Vec<SequenceStep>, sqlx::Error> {
+    sqlx::query_as::<_, SequenceStep>(
+        "SELECT * FROM sequence_steps WHERE sequence_id = $1 ORDER BY day, created_at",
+    )
+    .bind(sequence_id)
+    .fetch_all(db)
+    .await
+}
+
+fn validate(name: &str) -> Result<(), String> {
+    if name.is_empty() || name.chars().count() > 100 {
+        return Err("The name is required and can be up to 100 characters".to_string());
+    }
+    Ok(())
+}
+
+// Returns why it was refused, if it was
+pub async fn create(db: &Database, name: &str, description: Option<&str>, created_by: Uuid) -> Result<Result<Uuid, String>, sqlx::Error> {
+    if let Err(reason) = validate(name) {
+        return Ok(Err(reason));
+    }
+    let id = sqlx::query_scalar::<_, Uuid>(
+        r#"
+        INSERT INTO sequences (name, description, created_by) VALUES ($1, $2, $3)
+        ON CONFLICT (tenant_id, name) DO NOTHING
+        RETURNING id
+        "#,
+    )
+    .bind(name)
+    .bind(description)
+    .bind(created_by)
+    .fetch_optional(db)
+    .await?;
+    Ok(id.ok_or_else(|| format!("There is already a sequence called {}", name)))
+}
+
+// Switching a sequence off pauses its enrollments; steps that came due in
+// the meantime run when it's switched back on
+pub async fn update(db: &Database, id: Uuid, name: &str, description: Option<&str>, is_active: bool) -> Result<Result<(), String>, sqlx::Error> {
+    if let Err(reason) = validate(name) {
+        return Ok(Err(reason));
+    }
+    let taken = sqlx::query_scalar::<_, bool>("SELECT EXISTS(SELECT 1 FROM sequences WHERE name = $1 AND id <> $2)")
+        .bind(name)
+        .bind(id)
+        .fetch_one(db)
+        .await?;
+    if taken {
+        return Ok(Err(format!("There is already a sequence called {}", name)));
+    }
+    let updated = sqlx::query(
+        "UPDATE sequences SET name = $2, description = $3, is_active = $4, updated_at = NOW() WHERE id = $1",
+    )
+    .bind(id)
+    .bind(name)
+    .bind(description)
+    .bind(is_active)
+    .execute(db)
+    .await?
+    .rows_affected();
+    if updated == 0 {
+        return Ok(Err("Unknown sequence".to_string()));
+    }
+    Ok(Ok(()))
+}
+
+pub struct NewStep<'a> {
+    pub day: i32,
+    pub step_type: &'a str,
+    pub activity_type: Option<&'a str>,
+    pub subject: &'a str,
+    pub body: Option<&'a str>,
+}
+
+// A step added to a sequence already in use also runs for its current
+// enrollments, straight away if its day has passed
+pub async fn add_step(db: &Database, sequence_id: Uuid, step: NewStep<'_>) -> Result<Result<Uuid, String>, sqlx::Error> {
+    if !(1..=365).contains(&step.day) {
+        return Ok(Err("The day has to be between 1 and 365".to_string()));
+    }
+    if step.subject.is_empty() || step.subject.chars().count() > 255 {
+        return Ok(Err("The subject is required and can be up to 255 characters".to_string()));
+    }
+    let activity_type = match step.step_type {
+        "email" => {
+            if step.body.is_none() {
+                return Ok(Err("An email step needs a message".to_string()));
+            }
+            None
+        }
+        "task" => match step.activity_type {
+            Some(activity_type) if activity_types::is_allowed(db, activity_type).await? => Some(activity_type),
+            _ => return Ok(Err("Pick the kind of task".to_string())),
+        },
+        _ => return Ok(Err("Unknown kind of step".to_string())),
+    };
+
+    let id = sqlx::query_scalar::<_, Uuid>(
+        r#"
+        INSERT INTO sequence_steps (sequence_id, day, step_type, activity_type, subject, body)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING id
+        "#,
+    )
+    .bind(sequence_id)
+    .bind(step.day)
+    .bind(step.step_type)
+    .bind(activity_type)
+    .bind(step.subject)
+    .bind(step.body)
+    .fetch_one(db)
+    .await?;
+    Ok(Ok(id))
+}
+
+// Returns false if the step isn't on the sequence
+pub async fn delete_step(db: &Database, sequence_id: Uuid, step_id: Uuid) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query("DELETE FROM sequence_steps WHERE id = $1 AND sequence_id = $2")
+        .bind(step_id)
+        .bind(sequence_id)
+        .execute(db)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn find_enrollment(db: &Database, id: Uuid) -> Result<Option<SequenceEnrollment>, sqlx::Error> {
+    sqlx::query_as::<_, SequenceEnrollment>(&format!("{} WHERE e.id = $1", ENROLLMENT_SELECT))
+        .bind(id)
+        .fetch_optional(db)
+        .await
+}
+
+// Enrollments on a contact, whether made on the contact or on one of its deals
+pub async fn for_contact(db: &Database, contact_id: Uuid) -> Result<Vec<SequenceEnrollment>, sqlx::Error> {
+    sqlx::query_as::<_, SequenceEnrollment>(&format!(
+        "{} WHERE e.contact_id = $1 ORDER BY e.status = 'active' DESC, e.started_at DESC",
+        ENROLLMENT_SELECT
+    ))
+    .bind(contact_id)
+    .fetch_all(db)
+    .await
+}
+
+pub async fn for_deal(db: &Database, deal_id: Uuid) -> Result<Vec<SequenceEnrollment>, sqlx::Error> {
+    sqlx::query_as::<_, SequenceEnrollment>(&format!(
+        "{} WHERE e.deal_id = $1 ORDER BY e.status = 'active' DESC, e.started_at DESC",
+        ENROLLMENT_SELECT
+    ))
+    .bind(deal_id)
+    .fetch_all(db)
+    .await
+}
+
+// Start a contact, or a deal and its contact, on a sequence. Its day 1 steps
+// run on the scheduler's next pass.
+pub async fn enroll(
+    db: &Database,
+    sequence_id: Uuid,
+    customer_id: Uuid,
+    contact_id: Option<Uuid>,
+    deal_id: Option<Uuid>,
+    enrolled_by: Uuid,
+) -> Result<Result<Uuid, String>, sqlx::Error> {
+    let Some(sequence) = find(db, sequence_id).await? else {
+        return Ok(Err("That sequence doesn't exist".to_string()));
+    };
+    if !sequence.is_active {
+        return Ok(Err(format!("{} is switched off", sequence.name)));
+    }
+    let steps = steps(db, sequence_id).await?;
+    if steps.is_empty() {
+        return Ok(Err(format!("{} has no steps yet", sequence.name)));
+    }
+
+    if steps.iter().any(SequenceStep::is_email) {
+        let contact = match contact_id {
+            Some(contact_id) => sqlx::query_as::<_, (Option<String>, bool, String)>(
+                "SELECT email, do_not_contact, email_status FROM contacts WHERE id = $1",
+            )
+            .bind(contact_id)
+            .fetch_optional(db)
+            .await?,
+            None => None,
+        };
+        match contact {
+            None => return Ok(Err(format!("{} sends emails, so it needs a contact", sequence.name))),
+            Some((email, _, _)) if email.as_deref().unwrap_or("").trim().is_empty() => {
+                return Ok(Err("The contact has no email address".to_string()));
+            }
+            Some((_, true, _)) => return Ok(Err("The contact is marked do not contact".to_string())),
+            Some((_, _, status)) if status == "invalid" => {
+                return Ok(Err("The contact's email address is known to bounce".to_string()));
+            }
+            Some(_) => {}
+        }
+    }
+
+    let already = sqlx::query_scalar::<_, bool>(
+        r#"
+        SELECT EXISTS(
+            SELECT 1 FROM sequence_enrollments
+            WHERE sequence_id = $1 AND status = 'active' AND COALESCE(deal_id, contact_id) = COALESCE($2, $3)
+        )
+        "#,
+    )
+    .bind(sequence_id)
+    .bind(deal_id)
+    .bind(contact_id)
+    .fetch_one(db)
+    .await?;
+    if already {
+        return Ok(Err(format!("Already in {}", sequence.name)));
+    }
+
+    let id = sqlx::query_scalar::<_, Uuid>(
+        r#"
+        INSERT INTO sequence_enrollments (sequence_id, customer_id, contact_id, deal_id, enrolled_by)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING id
+        "#,
+    )
+    .bind(sequence_id)
+    .bind(customer_id)
+    .bind(contact_id)
+    .bind(deal_id)
+    .bind(enrolled_by)
+    .fetch_one(db)
+    .await?;
+
+    Ok(Ok(id))
+}
+
+// Stop an active enrollment; returns false if it had already finished
+pub async fn stop(db: &Database, enrollment_id: Uuid, reason: &str) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query(
+        r#"
+        UPDATE sequence_enrollments SET status = 'stopped', stop_reason = $2, finished_at = NOW()
+        WHERE id = $1 AND status = 'active'
+        "#,
+    )
+    .bind(enrollment_id)
+    .bind(reason)
+    .execute(db)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+// A deal's sequences only fit the stage it was in when they started
+pub async fn stop_for_deal(db: &Database, deal_id: Uuid, reason: &str) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query(
+        r#"
+        UPDATE sequence_enrollments SET status = 'stopped', stop_reason = $2, finished_at = NOW()
+        WHERE deal_id = $1 AND status = 'active'
+        "#,
+    )
+    .bind(deal_id)
+    .bind(reason)
+    .execute(db)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+// Once a contact writes back, the rest of the follow-up is up to a person
+pub async fn stop_for_reply(db: &Database, address: &str) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query(
+        r#"
+        UPDATE sequence_enrollments e SET status = 'stopped', stop_reason = 'The contact replied', finished_at = NOW()
+        FROM contacts ct
+        WHERE ct.id = e.contact_id AND LOWER(ct.email) = LOWER($1) AND e.status = 'active'
+        "#,
+    )
+    .bind(address)
+    .execute(db)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+#[derive(FromRow)]
+struct DueStep {
+    enrollment_id: Uuid,
+    customer_id: Uuid,
+    contact_id: Option<Uuid>,
+    deal_id: Option<Uuid>,
+    enrolled_by: Option<Uuid>,
+    step_id: Uuid,
+    step_type: String,
+    activity_type: Option<String>,
+    subject: String,
+    body: Option<String>,
+    first_name: Option<String>,
+    last_name: Option<String>,
+    company_name: String,
+    // Whether the contact can still be emailed
+    emailable: bool,
+}
+
+// Run every step that has come due on an active enrollment: tasks become
+// open activities for whoever enrolled, emails are queued to the contact and
+// logged. Enrollments with nothing left to run are completed. Returns how
+// many steps ran.
+pub async fn run_due(db: &Database) -> Result<usize, sqlx::Error> {
+    let due = sqlx::query_as::<_, DueStep>(
+        r#"
+        SELECT e.id as enrollment_id, e.customer_id, e.contact_id, e.deal_id, e.enrolled_by,
+               s.id as step_id, s.step_type, s.activity_type, s.subject, s.body,
+               ct.first_name, ct.last_name, c.company_name,
+               COALESCE(ct.email <> '' AND NOT ct.do_not_contact AND ct.email_status <> 'invalid', false) as emailable
+        FROM sequence_enrollments e
+        JOIN sequences q ON q.id = e.sequence_id
+        JOIN sequence_steps s ON s.sequence_id = e.sequence_id
+        JOIN customers c ON c.id = e.customer_id
+        LEFT JOIN contacts ct ON ct.id = e.contact_id
+        WHERE e.status = 'active'
+          AND q.is_active
+          AND e.started_at + (s.day - 1) * INTERVAL '1 day' <= NOW()
+          AND NOT EXISTS (SELECT 1 FROM sequence_step_runs r WHERE r.enrollment_id = e.id AND r.step_id = s.id)
+        ORDER BY e.started_at, s.day, s.created_at
+        "#,
+    )
+    .fetch_all(db)
+    .await?;
+
+    let mut stopped = HashSet::new();
+    let mut ran = 0;
+    for step in &due {
+        if stopped.contains(&step.enrollment_id) {
+            continue;
+        }
+
+        // Claiming the step first keeps a second server from running it too
+        let claimed = sqlx::query(
+            "INSERT INTO sequence_step_runs (enrollment_id, step_id) VALUES ($1, $2) ON CONFLICT DO NOTHING",
+        )
+        .bind(step.enrollment_id)
+        .bind(step.step_id)
+        .execute(db)
+        .await?;
+        if claimed.rows_affected() == 0 {
+            continue;
+        }
+
+        let merge = |text: &str| {
+            mass_email::merge(text, step.first_name.as_deref(), step.last_name.as_deref(), Some(&step.company_name))
+        };
+        let subject = merge(&step.subject);
+        let body = merge(step.body.as_deref().unwrap_or(""));
+
+        let email_id = if step.step_type == "email" {
+            let Some(contact_id) = step.contact_id.filter(|_| step.emailable) else {
+                stop(db, step.enrollment_id, "The contact can no longer be emailed").await?;
+                stopped.insert(step.enrollment_id);
+                continue;
+            };
+            Some(mailer::queue_contact_email(db, contact_id, step.enrolled_by, &subject, &mailer::text_to_html(&body)).await?)
+        } else {
+            None
+        };
+
+        let activity_id = sqlx::query_scalar::<_, Uuid>(
+            r#"
+            INSERT INTO activities (
+                customer_id, contact_id, deal_id, activity_type, subject, description,
+                activity_date, completed, created_by, assigned_to
+            )
+            VALUES ($1, $2, $3, $4, $5, NULLIF($6, ''), NOW(), $7, $8, $8)
+            RETURNING id
+            "#,
+        )
+        .bind(step.customer_id)
+        .bind(step.contact_id)
+        .bind(step.deal_id)
+        .bind(if email_id.is_some() { "email" } else { step.activity_type.as_deref().unwrap_or("task") })
+        .bind(&subject)
+        .bind(&body)
+        .bind(email_id.is_some())
+        .bind(step.enrolled_by)
+        .fetch_one(db)
+        .await?;
+
+        sqlx::query("UPDATE sequence_step_runs SET activity_id = $3, email_id = $4 WHERE enrollment_id = $1 AND step_id = $2")
+            .bind(step.enrollment_id)
+            .bind(step.step_id)
+            .bind(activity_id)
+            .bind(email_id)
+            .execute(db)
+            .await?;
+        ran += 1;
+    }
+
+    sqlx::query(
+        r#"
+        UPDATE sequence_enrollments e SET status = 'completed', finished_at = NOW()
+        WHERE e.status = 'active' AND NOT EXISTS (
+            SELECT 1 FROM sequence_steps s
+            WHERE s.sequence_id = e.sequence_id
+              AND NOT EXISTS (SELECT 1 FROM sequence_step_runs r WHERE r.enrollment_id = e.id AND r.step_id = s.id)
+        )
+        "#,
+    )
+    .execute(db)
+    .await?;
+
+    Ok(ran)
+}
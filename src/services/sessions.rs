@@ -0,0 +1,198 @@
+use chrono::{DateTime, Duration, Utc};
+use tower_cookies::{Cookie, Cookies};
+use uuid::Uuid;
+
+use crate::{database::Database, models::Session, utils::verify_token};
+
+// Open a session for a sign-in. When the organization caps concurrent
+// sessions, the user's oldest active sessions are revoked to make room.
+pub async fn start(
+    db: &Database,
+    user_id: Uuid,
+    expires_at: DateTime<Utc>,
+    ip_address: Option<&str>,
+    user_agent: Option<&str>,
+) -> Result<Uuid, sqlx::Error> {
+    let session_id = Uuid::new_v4();
+
+    sqlx::query(
+        "INSERT INTO sessions (id, user_id, expires_at, ip_address, user_agent) VALUES ($1, $2, $3, $4, $5)",
+    )
+    .bind(session_id)
+    .bind(user_id)
+    .bind(expires_at)
+    .bind(ip_address)
+    .bind(user_agent)
+    .execute(db)
+    .await?;
+
+    sqlx::query(
+        r#"
+        UPDATE sessions SET revoked_at = NOW()
+        WHERE id IN (
+            SELECT s.id
+            FROM sessions s, security_settings st
+            WHERE s.user_id = $1 AND s.revoked_at IS NULL AND s.expires_at > NOW()
+              AND s.impersonator_id IS NULL
+              AND st.max_sessions_per_user IS NOT NULL
+            ORDER BY s.created_at DESC, s.id = $2 DESC
+            OFFSET (SELECT COALESCE(max_sessions_per_user, 0) FROM security_settings)
+        )
+        "#,
+    )
+    .bind(user_id)
+    .bind(session_id)
+    .execute(db)
+    .await?;
+
+    Ok(session_id)
+}
+
+// Open a session in which an administrator acts as another user. It doesn't
+// count towards that user's session limit and ends on its own after `expires_at`.
+pub async fn start_impersonation(
+    db: &Database,
+    user_id: Uuid,
+    impersonator_id: Uuid,
+    return_session_id: Uuid,
+    expires_at: DateTime<Utc>,
+    ip_address: Option<&str>,
+    user_agent: Option<&str>,
+) -> Result<Uuid, sqlx::Error> {
+    sqlx::query_scalar::<_, Uuid>(
+        r#"
+        INSERT INTO sessions (user_id, impersonator_id, return_session_id, expires_at, ip_address, user_agent)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING id
+        "#,
+    )
+    .bind(user_id)
+    .bind(impersonator_id)
+    .bind(return_session_id)
+    .bind(expires_at)
+    .bind(ip_address)
+    .bind(user_agent)
+    .fetch_one(db)
+    .await
+}
+
+// Who a live session signs in, and the administrator behind it when it's an
+// impersonation session
+#[derive(sqlx::FromRow)]
+pub struct SessionOwner {
+    pub user_id: Uuid,
+    pub impersonator_id: Option<Uuid>,
+    pub return_session_id: Option<Uuid>,
+    pub expires_at: DateTime<Utc>,
+    pub idle_minutes: Option<i32>,
+}
+
+// Check a session is still live and slide its idle window forward. Returns its
+// owner, or None once it has expired, gone idle too long or been revoked.
+pub async fn touch(db: &Database, session_id: Uuid) -> Result<Option<SessionOwner>, sqlx::Error> {
+    sqlx::query_as::<_, SessionOwner>(
+        r#"
+        UPDATE sessions s SET last_seen_at = NOW()
+        FROM security_settings st
+        WHERE s.id = $1 AND s.revoked_at IS NULL AND s.expires_at > NOW()
+          AND (st.session_idle_minutes IS NULL
+               OR s.last_seen_at > NOW() - make_interval(mins => st.session_idle_minutes))
+        RETURNING s.user_id, s.impersonator_id, s.return_session_id, s.expires_at, st.session_idle_minutes as idle_minutes
+        "#,
+    )
+    .bind(session_id)
+    .fetch_optional(db)
+    .await
+}
+
+// When a session started now must end, however active it is
+pub async fn expiry(db: &Database) -> Result<DateTime<Utc>, sqlx::Error> {
+    let hours = sqlx::query_scalar::<_, i32>("SELECT session_max_hours FROM security_settings")
+        .fetch_one(db)
+        .await?;
+    Ok(Utc::now() + Duration::hours(hours.into()))
+}
+
+// Keep the auth cookie only as long as its session could still be used: until
+// it would go idle or reaches its absolute expiry, whichever is sooner. Set
+// again on every request so the idle window slides with activity.
+pub fn set_cookie(cookies: &Cookies, token: String, expires_at: DateTime<Utc>, idle_minutes: Option<i32>) {
+    let mut remaining = (expires_at - Utc::now()).num_seconds().max(0);
+    if let Some(minutes) = idle_minutes {
+        remaining = remaining.min(i64::from(minutes) * 60);
+    }
+
+    cookies.add(
+        Cookie::build(("auth_token", token))
+            .path("/")
+            .http_only(true)
+            .max_age(time::Duration::seconds(remaining))
+            .build(),
+    );
+}
+
+// The session behind the request's auth cookie, if it carries one
+pub fn from_cookies(cookies: &Cookies) -> Option<Uuid> {
+    cookies
+        .get("auth_token")
+        .and_then(|cookie| verify_token(cookie.value()).ok())
+        .and_then(|claims| claims.sid)
+        .and_then(|sid| Uuid::parse_str(&sid).ok())
+}
+
+// A user's own sessions that would still let them in, most recently used first
+pub async fn list_active(db: &Database, user_id: Uuid) -> Result<Vec<Session>, sqlx::Error> {
+    sqlx::query_as::<_, Session>(
+        r#"
+        SELECT s.id, s.user_id, s.ip_address, s.user_agent, s.created_at, s.last_seen_at, s.expires_at
+        FROM sessions s, security_settings st
+        WHERE s.user_id = $1 AND s.revoked_at IS NULL AND s.expires_at > NOW()
+          AND s.impersonator_id IS NULL
+          AND (st.session_idle_minutes IS NULL
+               OR s.last_seen_at > NOW() - make_interval(mins => st.session_idle_minutes))
+        ORDER BY s.last_seen_at DESC
+        "#,
+    )
+    .bind(user_id)
+    .fetch_all(db)
+    .await
+}
+
+// Remove a session outright, e.g. on sign-out
+// Returns who the session belonged to, and who was impersonating them, when
+// it still existed
+pub async fn remove(db: &Database, session_id: Uuid) -> Result<Option<(Uuid, Option<Uuid>)>, sqlx::Error> {
+    sqlx::query_as::<_, (Uuid, Option<Uuid>)>("DELETE FROM sessions WHERE id = $1 RETURNING user_id, impersonator_id")
+        .bind(session_id)
+        .fetch_optional(db)
+        .await
+}
+
+// End one of a user's sessions. Returns false when it wasn't theirs.
+pub async fn end(db: &Database, user_id: Uuid, session_id: Uuid) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query("DELETE FROM sessions WHERE id = $1 AND user_id = $2")
+        .bind(session_id)
+        .bind(user_id)
+        .execute(db)
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+// End every session a user has, signing them out on all devices
+pub async fn end_all(db: &Database, user_id: Uuid) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query("DELETE FROM sessions WHERE user_id = $1")
+        .bind(user_id)
+        .execute(db)
+        .await?;
+    Ok(result.rows_affected())
+}
+
+// End every session a user has except one, e.g. after they change their password
+pub async fn end_others(db: &Database, user_id: Uuid, keep: Option<Uuid>) -> Result<u64, sqlx::Error> {
+    let result = sqlx::query("DELETE FROM sessions WHERE user_id = $1 AND id IS DISTINCT FROM $2")
+        .bind(user_id)
+        .bind(keep)
+        .execute(db)
+        .await?;
+    Ok(result.rows_affected())
+}
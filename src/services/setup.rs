@@ -0,0 +1,210 @@
+use std::{
+    collections::HashSet,
+    sync::{Mutex, OnceLock},
+};
+use uuid::Uuid;
+
+use crate::{database::Database, models::get_all_permissions, services::tenancy};
+
+// Tenants known to have been set up; it can't be undone, so the table only
+// needs checking until then
+static COMPLETE: OnceLock<Mutex<HashSet<Uuid>>> = OnceLock::new();
+
+fn completed() -> &'static Mutex<HashSet<Uuid>> {
+    COMPLETE.get_or_init(|| Mutex::new(HashSet::new()))
+}
+
+fn mark_complete() {
+    if let Some(tenant) = tenancy::current_id() {
+        completed().lock().unwrap().insert(tenant);
+    }
+}
+
+struct DefaultRole {
+    name: &'static str,
+    description: &'static str,
+    dashboard: Option<&'static str>,
+    is_read_only: bool,
+    permissions: &'static [&'static str],
+}
+
+// Checked by the code but not offered in the role editor
+const SUPER_ADMIN_EXTRAS: &[&str] = &["items:read", "stock_movements:read"];
+
+// Gets every permission there is
+const SUPER_ADMIN: DefaultRole = DefaultRole {
+    name: "Super Admin",
+    description: "Full system access with all permissions",
+    dashboard: Some("manager"),
+    is_read_only: false,
+    permissions: &[],
+};
+
+// The other roles a new organization starts with
+const DEFAULT_ROLES: &[DefaultRole] = &[
+    DefaultRole {
+        name: "Manager",
+        description: "Runs the sales team: customers, campaigns, inventory, expenses and discount approvals",
+        dashboard: Some("manager"),
+        is_read_only: false,
+        permissions: &[
+            "customers:read", "customers:write", "crm:all_records", "campaigns:read", "campaigns:write",
+            "data:export", "alerts:manage", "inventory:read", "inventory:write", "team:read",
+            "expenses:read", "expenses:write", "expenses:approve", "finance:read", "discounts:approve",
+            "shipping:read", "shipping:write", "api:access",
+        ],
+    },
+    DefaultRole {
+        name: "Sales Rep",
+        description: "Works their own customers and deals and files expenses",
+        dashboard: Some("sales"),
+        is_read_only: false,
+        permissions: &[
+            "customers:read", "customers:write", "campaigns:read", "inventory:read",
+            "expenses:read", "expenses:write", "api:access",
+        ],
+    },
+    DefaultRole {
+        name: "Accountant",
+        description: "Handles expense approvals and financial reporting",
+        dashboard: Some("manager"),
+        is_read_only: false,
+        permissions: &[
+            "expenses:read", "expenses:write", "expenses:delete", "expenses:approve",
+            "finance:read", "data:export",
+        ],
+    },
+    DefaultRole {
+        name: "Inventory Manager",
+        description: "Full control over inventory, stock movements and shipments",
+        dashboard: Some("warehouse"),
+        is_read_only: false,
+        permissions: &[
+            "inventory:read", "inventory:write", "inventory:delete", "items:read", "stock_movements:read",
+            "shipping:read", "shipping:write", "finance:read",
+        ],
+    },
+    DefaultRole {
+        name: "Viewer",
+        description: "Read-only access to most resources",
+        dashboard: None,
+        is_read_only: false,
+        permissions: &["customers:read", "inventory:read", "expenses:read", "shipping:read"],
+    },
+    DefaultRole {
+        name: "Auditor",
+        description: "Read-only access for external auditors",
+        dashboard: None,
+        is_read_only: true,
+        permissions: &[
+            "customers:read", "crm:read_all", "campaigns:read", "inventory:read", "team:read",
+            "expenses:read", "shipping:read", "finance:read", "data:export", "audit:read",
+        ],
+    },
+];
+
+async fn save_role(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    role: &DefaultRole,
+    permissions: Vec<String>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO roles (name, description, permissions, dashboard, is_read_only)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT (tenant_id, name) DO UPDATE SET
+            description = EXCLUDED.description,
+            permissions = EXCLUDED.permissions,
+            dashboard = EXCLUDED.dashboard,
+            is_read_only = EXCLUDED.is_read_only,
+            is_active = true
+        "#,
+    )
+    .bind(role.name)
+    .bind(role.description)
+    .bind(sqlx::types::Json(permissions))
+    .bind(role.dashboard)
+    .bind(role.is_read_only)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+pub struct NewOrganization<'a> {
+    pub name: &'a str,
+    pub email: &'a str,
+    pub first_name: &'a str,
+    pub last_name: &'a str,
+    pub password_hash: &'a str,
+}
+
+pub async fn is_complete(db: &Database) -> Result<bool, sqlx::Error> {
+    if tenancy::current_id().is_some_and(|tenant| completed().lock().unwrap().contains(&tenant)) {
+        return Ok(true);
+    }
+    let complete = sqlx::query_scalar::<_, bool>("SELECT EXISTS(SELECT 1 FROM organization)")
+        .fetch_one(db)
+        .await?;
+    if complete {
+        mark_complete();
+    }
+    Ok(complete)
+}
+
+// Create the organization, its default roles and the first admin in one go.
+// Returns the admin's id, or None when setup was already done (e.g. by a
+// second browser submitting at the same time).
+pub async fn complete(db: &Database, org: NewOrganization<'_>) -> Result<Option<Uuid>, sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    let created = sqlx::query("INSERT INTO organization (name) VALUES ($1) ON CONFLICT DO NOTHING")
+        .bind(org.name)
+        .execute(&mut *tx)
+        .await?
+        .rows_affected();
+    if created == 0 {
+        return Ok(None);
+    }
+
+    // Whatever the migrations left behind, the default roles start out as
+    // defined above. Roles with other names are left alone.
+    let mut everything: Vec<String> = get_all_permissions().into_iter().map(|p| p.key).collect();
+    everything.extend(SUPER_ADMIN_EXTRAS.iter().map(|p| p.to_string()));
+    save_role(&mut tx, &SUPER_ADMIN, everything).await?;
+    for role in DEFAULT_ROLES {
+        save_role(&mut tx, role, role.permissions.iter().map(|p| p.to_string()).collect()).await?;
+    }
+
+    let admin_id = sqlx::query_scalar::<_, Uuid>(
+        r#"
+        INSERT INTO users (email, password_hash, first_name, last_name)
+        VALUES ($1, $2, $3, $4)
+        RETURNING id
+        "#,
+    )
+    .bind(org.email)
+    .bind(org.password_hash)
+    .bind(org.first_name)
+    .bind(org.last_name)
+    .fetch_one(&mut *tx)
+    .await?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO user_roles (user_id, role_id, assigned_by)
+        SELECT $1, id, $1 FROM roles WHERE name = 'Super Admin'
+        "#,
+    )
+    .bind(admin_id)
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query("UPDATE organization SET created_by = $1")
+        .bind(admin_id)
+        .execute(&mut *tx)
+        .await?;
+
+    tx.commit().await?;
+    mark_complete();
+    Ok(Some(admin_id))
+}
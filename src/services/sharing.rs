@@ -0,0 +1,242 @@
+use uuid::Uuid;
+
+use crate::{database::Database, middleware::CurrentUser, models::RecordShare};
+
+// Lets a role open and edit every customer, deal and activity regardless of owner
+pub const ALL_RECORDS_PERMISSION: &str = "crm:all_records";
+
+// Lets a role see every customer, deal and activity but only change its own
+pub const READ_ALL_PERMISSION: &str = "crm:read_all";
+
+// Records scoped to their owner, keyed as stored in record_shares.record_type.
+// Activities and leads aren't shared on their own.
+#[derive(Clone, Copy, PartialEq)]
+pub enum RecordKind {
+    Customer,
+    Deal,
+    Activity,
+    Lead,
+}
+
+impl RecordKind {
+    pub fn key(self) -> &'static str {
+        match self {
+            Self::Customer => "customer",
+            Self::Deal => "deal",
+            Self::Activity => "activity",
+            Self::Lead => "lead",
+        }
+    }
+
+    pub fn url(self, id: Uuid) -> String {
+        match self {
+            Self::Customer => format!("/crm/customers/{}", id),
+            Self::Deal => format!("/crm/deals/{}", id),
+            Self::Activity => format!("/crm/activities/{}/edit", id),
+            Self::Lead => format!("/crm/leads/{}", id),
+        }
+    }
+
+    fn table(self) -> &'static str {
+        match self {
+            Self::Customer => "customers",
+            Self::Deal => "deals",
+            Self::Activity => "activities",
+            Self::Lead => "leads",
+        }
+    }
+
+    // Records are owned by the assignee when set, otherwise by their creator
+    fn owner_expr(self, alias: &str) -> String {
+        format!("COALESCE({0}.assigned_to, {0}.created_by)", alias)
+    }
+}
+
+// What a user may do with a record, from least to most
+#[derive(Clone, Copy, PartialEq, PartialOrd)]
+pub enum Access {
+    None,
+    Read,
+    Write,
+    // Owner, a manager above the owner, or an admin: may also share the record
+    Manage,
+}
+
+// The user bound at $n and everyone reporting up to them
+fn team_of(param: usize) -> String {
+    format!(
+        "WITH RECURSIVE team AS (SELECT ${0}::uuid AS id UNION SELECT u.id FROM users u JOIN team t ON u.manager_id = t.id) SELECT id FROM team",
+        param
+    )
+}
+
+// The user bound at $n and every manager above them, i.e. the team leads whose teams include them
+fn leads_of(param: usize) -> String {
+    format!(
+        "WITH RECURSIVE chain AS (SELECT ${0}::uuid AS id UNION SELECT u.manager_id FROM users u JOIN chain c ON u.id = c.id WHERE u.manager_id IS NOT NULL) SELECT id FROM chain",
+        param
+    )
+}
+
+fn shares_for_user(kind: RecordKind, param: usize) -> String {
+    format!(
+        "SELECT s.record_id, s.access FROM record_shares s WHERE s.record_type = '{}' AND (s.user_id = ${} OR s.team_lead_id IN ({}))",
+        kind.key(),
+        param,
+        leads_of(param)
+    )
+}
+
+// SQL condition limiting rows of `kind` aliased as `alias` to those the user
+// bound at $param owns, manages through their reports, or has been shared
+pub fn visibility_condition(kind: RecordKind, alias: &str, param: usize) -> String {
+    format!(
+        "({} IN ({}) OR {}.id IN (SELECT record_id FROM ({}) shared))",
+        kind.owner_expr(alias),
+        team_of(param),
+        alias,
+        shares_for_user(kind, param)
+    )
+}
+
+fn holds(user: &CurrentUser, permission: &str) -> bool {
+    user.permissions.iter().any(|p| p == permission)
+}
+
+// Whether list queries need the visibility condition for this user
+pub fn is_scoped(user: &CurrentUser) -> bool {
+    !holds(user, ALL_RECORDS_PERMISSION) && !holds(user, READ_ALL_PERMISSION)
+}
+
+pub async fn access_level(
+    db: &Database,
+    user: &CurrentUser,
+    kind: RecordKind,
+    record_id: Uuid,
+) -> Result<Access, sqlx::Error> {
+    if user.has_manage_roles {
+        return Ok(Access::Manage);
+    }
+
+    let (owns, shared) = sqlx::query_as::<_, (bool, Option<String>)>(&format!(
+        r#"
+        SELECT
+            EXISTS (SELECT 1 FROM {table} r WHERE r.id = $2 AND {owner} IN ({team})),
+            (SELECT MAX(shared.access) FROM ({shares}) shared WHERE shared.record_id = $2)
+        "#,
+        table = kind.table(),
+        owner = kind.owner_expr("r"),
+        team = team_of(1),
+        shares = shares_for_user(kind, 1),
+    ))
+    .bind(user.id)
+    .bind(record_id)
+    .fetch_one(db)
+    .await?;
+
+    if owns {
+        return Ok(Access::Manage);
+    }
+
+    // 'write' sorts after 'read', so MAX picks the strongest share
+    let shared = match shared.as_deref() {
+        Some("write") => Access::Write,
+        Some(_) => Access::Read,
+        None => Access::None,
+    };
+
+    if holds(user, ALL_RECORDS_PERMISSION) {
+        Ok(Access::Write)
+    } else if holds(user, READ_ALL_PERMISSION) && shared < Access::Read {
+        Ok(Access::Read)
+    } else {
+        Ok(shared)
+    }
+}
+
+pub async fn list_shares(
+    db: &Database,
+    kind: RecordKind,
+    record_id: Uuid,
+) -> Result<Vec<RecordShare>, sqlx::Error> {
+    sqlx::query_as::<_, RecordShare>(
+        r#"
+        SELECT s.id,
+               CONCAT(g.first_name, ' ', g.last_name) as grantee_name,
+               s.team_lead_id IS NOT NULL as is_team,
+               s.access,
+               NULLIF(CONCAT(b.first_name, ' ', b.last_name), ' ') as shared_by_name,
+               s.created_at
+        FROM record_shares s
+        JOIN users g ON g.id = COALESCE(s.user_id, s.team_lead_id)
+        LEFT JOIN users b ON b.id = s.shared_by
+        WHERE s.record_type = $1 AND s.record_id = $2
+        ORDER BY g.first_name, g.last_name
+        "#,
+    )
+    .bind(kind.key())
+    .bind(record_id)
+    .fetch_all(db)
+    .await
+}
+
+// Share with a single user, or with a team lead and everyone under them.
+// Sharing again with the same grantee replaces the access level.
+pub async fn share(
+    db: &Database,
+    kind: RecordKind,
+    record_id: Uuid,
+    user_id: Option<Uuid>,
+    team_lead_id: Option<Uuid>,
+    access: &str,
+    shared_by: Uuid,
+) -> Result<(), sqlx::Error> {
+    let mut tx = db.begin().await?;
+
+    sqlx::query(
+        r#"
+        DELETE FROM record_shares
+        WHERE record_type = $1 AND record_id = $2
+          AND user_id IS NOT DISTINCT FROM $3 AND team_lead_id IS NOT DISTINCT FROM $4
+        "#,
+    )
+    .bind(kind.key())
+    .bind(record_id)
+    .bind(user_id)
+    .bind(team_lead_id)
+    .execute(&mut *tx)
+    .await?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO record_shares (record_type, record_id, user_id, team_lead_id, access, shared_by)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        "#,
+    )
+    .bind(kind.key())
+    .bind(record_id)
+    .bind(user_id)
+    .bind(team_lead_id)
+    .bind(access)
+    .bind(shared_by)
+    .execute(&mut *tx)
+    .await?;
+
+    tx.commit().await
+}
+
+pub async fn unshare(
+    db: &Database,
+    kind: RecordKind,
+    record_id: Uuid,
+    share_id: Uuid,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query("DELETE FROM record_shares WHERE id = $1 AND record_type = $2 AND record_id = $3")
+        .bind(share_id)
+        .bind(kind.key())
+        .bind(record_id)
+        .execute(db)
+        .await?;
+
+    Ok(result.rows_affected() > 0)
+}
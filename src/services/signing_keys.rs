@@ -0,0 +1,88 @@
+use chrono::{DateTime, Utc};
+use rand::{distributions::Alphanumeric, Rng};
+use sqlx::FromRow;
+use uuid::Uuid;
+
+use crate::{
+    database::Database,
+    utils::{set_signing_keys, SigningKey},
+};
+
+#[derive(FromRow)]
+struct KeyRow {
+    kid: String,
+    secret: String,
+    created_at: DateTime<Utc>,
+    retires_at: Option<DateTime<Utc>>,
+}
+
+fn random_string(length: usize) -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(length)
+        .map(char::from)
+        .collect()
+}
+
+// Load the keys still accepted into the process's key ring. Run at startup and
+// every minute, so a rotation made on another server is picked up shortly.
+pub async fn load(db: &Database) -> Result<(), sqlx::Error> {
+    let rows = sqlx::query_as::<_, KeyRow>(
+        "SELECT kid, secret, created_at, retires_at FROM jwt_signing_keys WHERE retires_at IS NULL OR retires_at > NOW()",
+    )
+    .fetch_all(db)
+    .await?;
+
+    // Sessions from before the first rotation have tokens signed with
+    // JWT_SECRET, which is accepted until the last of them expires
+    let legacy_retires_at = sqlx::query_scalar::<_, Option<DateTime<Utc>>>(
+        r#"
+        SELECT GREATEST(k.first_rotation, last_session_expiry(k.first_rotation))
+        FROM (SELECT MIN(created_at) as first_rotation FROM jwt_signing_keys) k
+        "#,
+    )
+    .fetch_one(db)
+    .await?;
+
+    let keys = rows
+        .into_iter()
+        .map(|row| SigningKey {
+            kid: row.kid,
+            secret: row.secret,
+            created_at: row.created_at,
+            retires_at: row.retires_at,
+        })
+        .collect();
+    set_signing_keys(keys, legacy_retires_at);
+    Ok(())
+}
+
+// Start signing with a new key. The current one keeps verifying until every
+// token it signed has expired. Returns the new key's id.
+pub async fn rotate(db: &Database, rotated_by: Uuid) -> Result<String, sqlx::Error> {
+    let kid = random_string(12);
+
+    let mut tx = db.begin().await?;
+    // Tokens expire with their session, so the last live session is the last
+    // token the old key signed. The keys serve every tenant, so their
+    // sessions all count.
+    sqlx::query(
+        r#"
+        UPDATE jwt_signing_keys
+        SET retires_at = GREATEST(NOW(), last_session_expiry(NULL))
+        WHERE retires_at IS NULL
+        "#,
+    )
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query("INSERT INTO jwt_signing_keys (kid, secret, created_by) VALUES ($1, $2, $3)")
+        .bind(&kid)
+        .bind(random_string(64))
+        .bind(rotated_by)
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+
+    load(db).await?;
+    Ok(kid)
+}
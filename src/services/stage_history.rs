@@ -0,0 +1,44 @@
+use uuid::Uuid;
+
+use crate::{database::Database, models::DealStageChange};
+
+// Note a deal entering a stage; `old_stage` is None when the deal is created
+pub async fn record(
+    db: &Database,
+    deal_id: Uuid,
+    old_stage: Option<&str>,
+    new_stage: &str,
+    changed_by: Uuid,
+) -> Result<(), sqlx::Error> {
+    sqlx::query("INSERT INTO deal_stage_history (deal_id, old_stage, new_stage, changed_by) VALUES ($1, $2, $3, $4)")
+        .bind(deal_id)
+        .bind(old_stage)
+        .bind(new_stage)
+        .bind(changed_by)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+// The deal's moves, newest first, each with the days spent in that stage
+pub async fn for_deal(db: &Database, deal_id: Uuid) -> Result<Vec<DealStageChange>, sqlx::Error> {
+    sqlx::query_as::<_, DealStageChange>(
+        r#"
+        SELECT h.id, h.old_stage, h.new_stage, NULLIF(concat_ws(' ', u.first_name, u.last_name), '') as changed_by_name, h.changed_at,
+               EXTRACT(DAY FROM COALESCE(h.left_at, NOW()) - h.changed_at)::int as days_in_stage,
+               h.left_at IS NULL as is_current,
+               s.stale_after_days
+        FROM (
+            SELECT *, LEAD(changed_at) OVER (ORDER BY changed_at, id) as left_at
+            FROM deal_stage_history
+            WHERE deal_id = $1
+        ) h
+        LEFT JOIN users u ON u.id = h.changed_by
+        LEFT JOIN deal_stage_settings s ON s.stage = h.new_stage
+        ORDER BY h.changed_at DESC
+        "#,
+    )
+    .bind(deal_id)
+    .fetch_all(db)
+    .await
+}
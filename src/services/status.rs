@@ -0,0 +1,409 @@
+use chrono::{DateTime, NaiveDate, Utc};
+use std::{env, time::Instant};
+use uuid::Uuid;
+
+use crate::{
+    database::Database,
+    models::{status_label, status_severity, StatusIncident, StatusMaintenance, INCIDENT_IMPACTS, STATUS_COMPONENTS},
+};
+
+// The page each component is probed on. Anything short of a server error
+// counts as up: the API answers 401 without a key, which still means it is
+// serving requests.
+const PROBES: &[(&str, &str)] = &[("app", "/login"), ("api", "/api/lookups/currency")];
+
+// How far back checks are kept, and so the longest uptime shown
+const RETENTION_DAYS: i32 = 90;
+
+// Days of daily uptime shown under each component
+const HISTORY_DAYS: i32 = 30;
+
+// Resolved incidents stay on the page this long
+const RECENT_INCIDENT_DAYS: i32 = 14;
+
+pub struct DayUptime {
+    pub day: NaiveDate,
+    // None before the first check
+    pub percent: Option<f64>,
+}
+
+impl DayUptime {
+    // How the day's bar is coloured: "good" from 99.9%, "fair" from 99%
+    pub fn level(&self) -> &'static str {
+        match self.percent {
+            Some(percent) if percent >= 99.9 => "good",
+            Some(percent) if percent >= 99.0 => "fair",
+            Some(_) => "poor",
+            None => "none",
+        }
+    }
+
+    pub fn label(&self) -> String {
+        match self.percent {
+            Some(percent) => format!("{}: {:.2}% up", self.day.format("%b %-d"), percent),
+            None => format!("{}: no data", self.day.format("%b %-d")),
+        }
+    }
+}
+
+pub struct ComponentStatus {
+    pub id: &'static str,
+    pub name: &'static str,
+    pub status: &'static str,
+    // Over the last 24 hours, 7 days and 90 days
+    pub uptime_day: Option<f64>,
+    pub uptime_week: Option<f64>,
+    pub uptime_quarter: Option<f64>,
+    pub history: Vec<DayUptime>,
+}
+
+impl ComponentStatus {
+    pub fn status_label(&self) -> &str {
+        status_label(self.status)
+    }
+}
+
+pub struct StatusSummary {
+    pub status: &'static str,
+    pub components: Vec<ComponentStatus>,
+    // Open incidents, then those resolved in the last two weeks
+    pub incidents: Vec<StatusIncident>,
+    // In progress or still to come
+    pub maintenance: Vec<StatusMaintenance>,
+    pub generated_at: DateTime<Utc>,
+}
+
+impl StatusSummary {
+    pub fn status_label(&self) -> &str {
+        status_label(self.status)
+    }
+}
+
+pub struct IncidentInput<'a> {
+    pub title: &'a str,
+    pub message: Option<&'a str>,
+    pub component: &'a str,
+    pub impact: &'a str,
+}
+
+pub struct MaintenanceInput<'a> {
+    pub title: &'a str,
+    pub description: Option<&'a str>,
+    pub component: &'a str,
+    pub starts_at: DateTime<Utc>,
+    pub ends_at: DateTime<Utc>,
+}
+
+fn is_component(component: &str) -> bool {
+    component == "all" || STATUS_COMPONENTS.iter().any(|(id, _)| *id == component)
+}
+
+fn validate_title(title: &str) -> Result<(), String> {
+    if title.is_empty() || title.chars().count() > 255 {
+        return Err("The title is required and can be up to 255 characters".to_string());
+    }
+    Ok(())
+}
+
+// Probes every component through this server's own listener and records the
+// minute. Run once a minute by each server; a minute is up if any of them
+// got an answer.
+pub async fn probe(db: &Database) -> Result<(), sqlx::Error> {
+    let port = env::var("PORT").unwrap_or_else(|_| "3000".to_string());
+    let client = reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(10))
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .map_err(|e| sqlx::Error::Protocol(e.to_string()))?;
+
+    for (component, path) in PROBES {
+        let started = Instant::now();
+        let ok = match client.get(format!("http://127.0.0.1:{}{}", port, path)).send().await {
+            Ok(response) => !response.status().is_server_error(),
+            Err(e) => {
+                tracing::warn!("Status probe of {} failed: {}", path, e);
+                false
+            }
+        };
+        let response_ms = i32::try_from(started.elapsed().as_millis()).unwrap_or(i32::MAX);
+
+        sqlx::query(
+            r#"
+            INSERT INTO status_checks (component, minute, ok, response_ms)
+            VALUES ($1, date_trunc('minute', NOW()), $2, $3)
+            ON CONFLICT (component, minute) DO UPDATE SET
+                ok = status_checks.ok OR EXCLUDED.ok,
+                response_ms = CASE WHEN status_checks.ok THEN status_checks.response_ms ELSE EXCLUDED.response_ms END
+            "#,
+        )
+        .bind(component)
+        .bind(ok)
+        .bind(response_ms)
+        .execute(db)
+        .await?;
+    }
+
+    sqlx::query("DELETE FROM status_checks WHERE minute < NOW() - make_interval(days => $1)")
+        .bind(RETENTION_DAYS)
+        .execute(db)
+        .await?;
+
+    Ok(())
+}
+
+// Share of the minutes in the last `days` with a successful check, counted
+// from the first check ever made so a new server doesn't start at 0%
+async fn uptime(db: &Database, component: &str, days: i32) -> Result<Option<f64>, sqlx::Error> {
+    let (up, expected) = sqlx::query_as::<_, (i64, Option<f64>)>(
+        r#"
+        SELECT COUNT(*) FILTER (WHERE ok AND minute >= NOW() - make_interval(days => $2)),
+               FLOOR(EXTRACT(EPOCH FROM NOW() - GREATEST(MIN(minute), NOW() - make_interval(days => $2))) / 60)::float8
+        FROM status_checks
+        WHERE component = $1
+        "#,
+    )
+    .bind(component)
+    .bind(days)
+    .fetch_one(db)
+    .await?;
+
+    Ok(expected.map(|expected| (up as f64 / expected.max(1.0) * 100.0).min(100.0)))
+}
+
+async fn daily_uptime(db: &Database, component: &str) -> Result<Vec<DayUptime>, sqlx::Error> {
+    let rows = sqlx::query_as::<_, (NaiveDate, i64, Option<f64>)>(
+        r#"
+        SELECT d::date,
+               (SELECT COUNT(*) FROM status_checks s
+                WHERE s.component = $1 AND s.ok AND s.minute >= d AND s.minute < d + INTERVAL '1 day'),
+               FLOOR(EXTRACT(EPOCH FROM LEAST(d + INTERVAL '1 day', NOW()) - GREATEST(d, first.minute)) / 60)::float8
+        FROM generate_series(
+                 date_trunc('day', NOW()) - make_interval(days => $2 - 1),
+                 date_trunc('day', NOW()),
+                 INTERVAL '1 day'
+             ) d,
+             (SELECT MIN(minute) as minute FROM status_checks WHERE component = $1) first
+        ORDER BY d
+        "#,
+    )
+    .bind(component)
+    .bind(HISTORY_DAYS)
+    .fetch_all(db)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|(day, up, expected)| DayUptime {
+            day,
+            percent: expected
+                .filter(|expected| *expected >= 0.0)
+                .map(|expected| (up as f64 / expected.max(1.0) * 100.0).min(100.0)),
+        })
+        .collect())
+}
+
+// Whether the latest check, if it's recent, failed
+async fn last_check_failed(db: &Database, component: &str) -> Result<bool, sqlx::Error> {
+    let ok = sqlx::query_scalar::<_, bool>(
+        r#"
+        SELECT ok FROM status_checks
+        WHERE component = $1 AND minute >= NOW() - INTERVAL '5 minutes'
+        ORDER BY minute DESC
+        LIMIT 1
+        "#,
+    )
+    .bind(component)
+    .fetch_optional(db)
+    .await?;
+    Ok(ok == Some(false))
+}
+
+pub async fn summary(db: &Database) -> Result<StatusSummary, sqlx::Error> {
+    let now = Utc::now();
+    let incidents = sqlx::query_as::<_, StatusIncident>(
+        r#"
+        SELECT * FROM status_incidents
+        WHERE resolved_at IS NULL OR resolved_at >= NOW() - make_interval(days => $1)
+        ORDER BY resolved_at IS NOT NULL, started_at DESC
+        "#,
+    )
+    .bind(RECENT_INCIDENT_DAYS)
+    .fetch_all(db)
+    .await?;
+    let maintenance = sqlx::query_as::<_, StatusMaintenance>(
+        "SELECT * FROM status_maintenance WHERE ends_at > NOW() ORDER BY starts_at",
+    )
+    .fetch_all(db)
+    .await?;
+
+    let mut components = Vec::new();
+    for (id, name) in STATUS_COMPONENTS {
+        let mut status = "operational";
+        let mut worsen = |to: &'static str| {
+            if status_severity(to) > status_severity(status) {
+                status = to;
+            }
+        };
+        if maintenance.iter().any(|window| window.affects(id) && window.is_in_progress(now)) {
+            worsen("maintenance");
+        }
+        // The page itself answered, so a failed probe is never a full outage
+        if last_check_failed(db, id).await? {
+            worsen("partial_outage");
+        }
+        for incident in incidents.iter().filter(|incident| !incident.is_resolved() && incident.affects(id)) {
+            if let Some((impact, _)) = INCIDENT_IMPACTS.iter().find(|(impact, _)| *impact == incident.impact) {
+                worsen(impact);
+            }
+        }
+
+        components.push(ComponentStatus {
+            id,
+            name,
+            status,
+            uptime_day: uptime(db, id, 1).await?,
+            uptime_week: uptime(db, id, 7).await?,
+            uptime_quarter: uptime(db, id, RETENTION_DAYS).await?,
+            history: daily_uptime(db, id).await?,
+        });
+    }
+
+    let status = components
+        .iter()
+        .map(|component| component.status)
+        .max_by_key(|status| status_severity(status))
+        .unwrap_or("operational");
+
+    Ok(StatusSummary { status, components, incidents, maintenance, generated_at: now })
+}
+
+// Every incident for the admin page, newest first
+pub async fn list_incidents(db: &Database) -> Result<Vec<StatusIncident>, sqlx::Error> {
+    sqlx::query_as::<_, StatusIncident>(
+        "SELECT * FROM status_incidents ORDER BY resolved_at IS NOT NULL, started_at DESC LIMIT 100",
+    )
+    .fetch_all(db)
+    .await
+}
+
+// Windows still to come and those ended in the last 30 days
+pub async fn list_maintenance(db: &Database) -> Result<Vec<StatusMaintenance>, sqlx::Error> {
+    sqlx::query_as::<_, StatusMaintenance>(
+        "SELECT * FROM status_maintenance WHERE ends_at > NOW() - INTERVAL '30 days' ORDER BY starts_at DESC",
+    )
+    .fetch_all(db)
+    .await
+}
+
+fn validate_incident(input: &IncidentInput) -> Result<(), String> {
+    validate_title(input.title)?;
+    if !is_component(input.component) {
+        return Err("Unknown component".to_string());
+    }
+    if !INCIDENT_IMPACTS.iter().any(|(impact, _)| *impact == input.impact) {
+        return Err("Unknown impact".to_string());
+    }
+    Ok(())
+}
+
+pub async fn create_incident(
+    db: &Database,
+    input: &IncidentInput<'_>,
+    created_by: Uuid,
+) -> Result<Result<Uuid, String>, sqlx::Error> {
+    if let Err(error) = validate_incident(input) {
+        return Ok(Err(error));
+    }
+
+    let id = sqlx::query_scalar::<_, Uuid>(
+        r#"
+        INSERT INTO status_incidents (title, message, component, impact, created_by)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING id
+        "#,
+    )
+    .bind(input.title)
+    .bind(input.message)
+    .bind(input.component)
+    .bind(input.impact)
+    .bind(created_by)
+    .fetch_one(db)
+    .await?;
+
+    Ok(Ok(id))
+}
+
+// Posts an update to an open incident; resolved ones are left as they closed
+pub async fn update_incident(
+    db: &Database,
+    id: Uuid,
+    input: &IncidentInput<'_>,
+) -> Result<Result<(), String>, sqlx::Error> {
+    if let Err(error) = validate_incident(input) {
+        return Ok(Err(error));
+    }
+
+    let updated = sqlx::query(
+        r#"
+        UPDATE status_incidents SET title = $2, message = $3, component = $4, impact = $5
+        WHERE id = $1 AND resolved_at IS NULL
+        "#,
+    )
+    .bind(id)
+    .bind(input.title)
+    .bind(input.message)
+    .bind(input.component)
+    .bind(input.impact)
+    .execute(db)
+    .await?;
+
+    if updated.rows_affected() == 0 {
+        return Ok(Err("Only open incidents can be updated".to_string()));
+    }
+    Ok(Ok(()))
+}
+
+pub async fn resolve_incident(db: &Database, id: Uuid) -> Result<bool, sqlx::Error> {
+    let resolved = sqlx::query("UPDATE status_incidents SET resolved_at = NOW() WHERE id = $1 AND resolved_at IS NULL")
+        .bind(id)
+        .execute(db)
+        .await?;
+    Ok(resolved.rows_affected() > 0)
+}
+
+pub async fn create_maintenance(
+    db: &Database,
+    input: &MaintenanceInput<'_>,
+    created_by: Uuid,
+) -> Result<Result<Uuid, String>, sqlx::Error> {
+    if let Err(error) = validate_title(input.title) {
+        return Ok(Err(error));
+    }
+    if !is_component(input.component) {
+        return Ok(Err("Unknown component".to_string()));
+    }
+    if input.ends_at <= input.starts_at {
+        return Ok(Err("The window has to end after it starts".to_string()));
+    }
+    if input.ends_at <= Utc::now() {
+        return Ok(Err("The window has already ended".to_string()));
+    }
+
+    let id = sqlx::query_scalar::<_, Uuid>(
+        r#"
+        INSERT INTO status_maintenance (title, description, component, starts_at, ends_at, created_by)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING id
+        "#,
+    )
+    .bind(input.title)
+    .bind(input.description)
+    .bind(input.component)
+    .bind(input.starts_at)
+    .bind(input.ends_at)
+    .bind(created_by)
+    .fetch_one(db)
+    .await?;
+
+    Ok(Ok(id))
+}
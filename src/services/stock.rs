@@ -0,0 +1,131 @@
+use sqlx::{Postgres, Transaction};
+use uuid::Uuid;
+
+use crate::{
+    database::Database,
+    services::{backorders, locations},
+};
+
+// Apply a change to a warehouse's stock, creating the row the first time.
+// Available is kept as on hand less committed.
+pub(crate) async fn adjust_stock(
+    tx: &mut Transaction<'_, Postgres>,
+    item_id: Uuid,
+    warehouse_id: Uuid,
+    on_hand: i32,
+    committed: i32,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO stock_levels (item_id, warehouse_id, quantity_on_hand, quantity_committed, quantity_available)
+        VALUES ($1, $2, $3, $4, $3 - $4)
+        ON CONFLICT (item_id, warehouse_id) DO UPDATE SET
+            quantity_on_hand = stock_levels.quantity_on_hand + $3,
+            quantity_committed = stock_levels.quantity_committed + $4,
+            quantity_available = stock_levels.quantity_available + $3 - $4
+        "#,
+    )
+    .bind(item_id)
+    .bind(warehouse_id)
+    .bind(on_hand)
+    .bind(committed)
+    .execute(&mut **tx)
+    .await?;
+    Ok(())
+}
+
+// What a warehouse has free of an item, locking its row so the caller's
+// commitment can't race another's
+pub(crate) async fn lock_available(tx: &mut Transaction<'_, Postgres>, item_id: Uuid, warehouse_id: Uuid) -> Result<i32, sqlx::Error> {
+    let available = sqlx::query_scalar::<_, i32>(
+        "SELECT quantity_available FROM stock_levels WHERE item_id = $1 AND warehouse_id = $2 FOR UPDATE",
+    )
+    .bind(item_id)
+    .bind(warehouse_id)
+    .fetch_optional(&mut **tx)
+    .await?;
+    Ok(available.unwrap_or(0))
+}
+
+// Book stock in against a purchase order, putting it away at `location_id`
+// if given. Returns how much of it went straight to backorders.
+pub async fn receive(
+    db: &Database,
+    item_id: Uuid,
+    warehouse_id: Uuid,
+    quantity: i32,
+    reference: Option<&str>,
+    location_id: Option<Uuid>,
+    moved_by: Uuid,
+) -> Result<Result<i32, String>, sqlx::Error> {
+    if quantity <= 0 {
+        return Ok(Err("Enter a quantity above zero".to_string()));
+    }
+
+    let mut tx = db.begin().await?;
+    adjust_stock(&mut tx, item_id, warehouse_id, quantity, 0).await?;
+    if let Some(location_id) = location_id {
+        locations::assign(&mut tx, item_id, warehouse_id, location_id).await?;
+    }
+    sqlx::query(
+        r#"
+        INSERT INTO stock_movements (item_id, to_warehouse_id, quantity, movement_type, reason, reference_id, moved_by)
+        VALUES ($1, $2, $3, 'purchase', 'Purchase order receipt', $4, $5)
+        "#,
+    )
+    .bind(item_id)
+    .bind(warehouse_id)
+    .bind(quantity)
+    .bind(reference)
+    .bind(moved_by)
+    .execute(&mut *tx)
+    .await?;
+    let allocated = backorders::allocate(&mut tx, item_id, warehouse_id).await?;
+    tx.commit().await?;
+
+    Ok(Ok(allocated))
+}
+
+// Move free stock between warehouses. Returns how much of it went straight
+// to backorders at the destination.
+pub async fn transfer(
+    db: &Database,
+    item_id: Uuid,
+    from_warehouse_id: Uuid,
+    to_warehouse_id: Uuid,
+    quantity: i32,
+    moved_by: Uuid,
+) -> Result<Result<i32, String>, sqlx::Error> {
+    if quantity <= 0 {
+        return Ok(Err("Enter a quantity above zero".to_string()));
+    }
+    if from_warehouse_id == to_warehouse_id {
+        return Ok(Err("Pick two different warehouses".to_string()));
+    }
+
+    let mut tx = db.begin().await?;
+    // Committed stock stays put for the orders holding it
+    let available = lock_available(&mut tx, item_id, from_warehouse_id).await?;
+    if quantity > available {
+        return Ok(Err(format!("Only {} available to transfer from that warehouse", available.max(0))));
+    }
+    adjust_stock(&mut tx, item_id, from_warehouse_id, -quantity, 0).await?;
+    adjust_stock(&mut tx, item_id, to_warehouse_id, quantity, 0).await?;
+    sqlx::query(
+        r#"
+        INSERT INTO stock_movements (item_id, from_warehouse_id, to_warehouse_id, quantity, movement_type, moved_by)
+        VALUES ($1, $2, $3, $4, 'transfer', $5)
+        "#,
+    )
+    .bind(item_id)
+    .bind(from_warehouse_id)
+    .bind(to_warehouse_id)
+    .bind(quantity)
+    .bind(moved_by)
+    .execute(&mut *tx)
+    .await?;
+    let allocated = backorders::allocate(&mut tx, item_id, to_warehouse_id).await?;
+    tx.commit().await?;
+
+    Ok(Ok(allocated))
+}
@@ -0,0 +1,59 @@
+use std::{io, path::Path};
+use tokio::fs;
+use uuid::Uuid;
+
+// Uploads are kept under static/, one folder per kind, and served from
+// /static/<folder>/<file>
+const ROOT: &str = "static";
+
+// What receipts, photos and scanned cards may be
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "jpeg"];
+
+fn extension(filename: &str) -> String {
+    Path::new(filename)
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or("")
+        .to_lowercase()
+}
+
+pub fn is_image(filename: &str) -> bool {
+    IMAGE_EXTENSIONS.contains(&extension(filename).as_str())
+}
+
+// Stores an uploaded image under a new name and returns its URL, or None
+// when it isn't one of the accepted image types
+pub async fn save_image(folder: &str, filename: &str, data: &[u8]) -> io::Result<Option<String>> {
+    if !is_image(filename) {
+        return Ok(None);
+    }
+    let dir = Path::new(ROOT).join(folder);
+    fs::create_dir_all(&dir).await?;
+    let name = format!("{}.{}", Uuid::new_v4(), extension(filename));
+    fs::write(dir.join(&name), data).await?;
+    Ok(Some(format!("/{}/{}/{}", ROOT, folder, name)))
+}
+
+// Whether `url` is a file save_image put in `folder`, for URLs that come
+// back through a form
+pub fn is_stored(folder: &str, url: &str) -> bool {
+    url.strip_prefix(&format!("/{}/{}/", ROOT, folder)).is_some_and(|name| {
+        !name.is_empty()
+            && !name.contains("..")
+            && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.')
+    })
+}
+
+// Deletes a stored file once nothing points at it. Failures are only logged,
+// as the record has already moved on.
+pub async fn remove(folder: &str, url: &str) {
+    if !is_stored(folder, url) {
+        return;
+    }
+    let path = Path::new(url.trim_start_matches('/'));
+    if let Err(e) = fs::remove_file(path).await {
+        if e.kind() != io::ErrorKind::NotFound {
+            tracing::error!("Error removing stored file {}: {}", url, e);
+        }
+    }
+}
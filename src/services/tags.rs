@@ -0,0 +1,263 @@
+use std::collections::HashMap;
+
+use uuid::Uuid;
+
+use crate::{
+    database::Database,
+    models::{Tag, TagUsage, TAG_COLORS},
+};
+
+// What can be tagged; the key is what taggings.record_type stores
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tagged {
+    Customer,
+    Contact,
+    Deal,
+}
+
+impl Tagged {
+    pub fn key(self) -> &'static str {
+        match self {
+            Tagged::Customer => "customer",
+            Tagged::Contact => "contact",
+            Tagged::Deal => "deal",
+        }
+    }
+}
+
+// The tag picker on a record's form: the record's tags and the ones it
+// can suggest
+pub struct TagPicker {
+    pub selected: Vec<Tag>,
+    pub available: Vec<Tag>,
+}
+
+impl TagPicker {
+    // What the hidden field submits when nothing is changed
+    pub fn value(&self) -> String {
+        self.selected.iter().map(|tag| tag.name.as_str()).collect::<Vec<_>>().join(",")
+    }
+}
+
+const MAX_NAME_LENGTH: usize = 50;
+
+// Tag names from the picker's comma-separated field, trimmed and without
+// repeats ("VIP" and "vip" are the same tag)
+pub fn parse_names(value: &str) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for name in value.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+        let name: String = name.chars().take(MAX_NAME_LENGTH).collect();
+        if !names.iter().any(|existing| existing.to_lowercase() == name.to_lowercase()) {
+            names.push(name);
+        }
+    }
+    names
+}
+
+pub async fn list(db: &Database) -> Result<Vec<Tag>, sqlx::Error> {
+    sqlx::query_as::<_, Tag>("SELECT * FROM tags ORDER BY LOWER(name)")
+        .fetch_all(db)
+        .await
+}
+
+pub async fn list_with_usage(db: &Database) -> Result<Vec<TagUsage>, sqlx::Error> {
+    sqlx::query_as::<_, TagUsage>(
+        r#"
+        SELECT t.*,
+               COUNT(tg.record_id) FILTER (WHERE tg.record_type = 'customer') as customer_count,
+               COUNT(tg.record_id) FILTER (WHERE tg.record_type = 'contact') as contact_count,
+               COUNT(tg.record_id) FILTER (WHERE tg.record_type = 'deal') as deal_count
+        FROM tags t
+        LEFT JOIN taggings tg ON tg.tag_id = t.id
+        GROUP BY t.id
+        ORDER BY LOWER(t.name)
+        "#,
+    )
+    .fetch_all(db)
+    .await
+}
+
+pub async fn get(db: &Database, id: Uuid) -> Result<Option<Tag>, sqlx::Error> {
+    sqlx::query_as::<_, Tag>("SELECT * FROM tags WHERE id = $1")
+        .bind(id)
+        .fetch_optional(db)
+        .await
+}
+
+pub async fn for_record(db: &Database, kind: Tagged, record_id: Uuid) -> Result<Vec<Tag>, sqlx::Error> {
+    sqlx::query_as::<_, Tag>(
+        r#"
+        SELECT t.* FROM tags t
+        JOIN taggings tg ON tg.tag_id = t.id
+        WHERE tg.record_type = $1 AND tg.record_id = $2
+        ORDER BY LOWER(t.name)
+        "#,
+    )
+    .bind(kind.key())
+    .bind(record_id)
+    .fetch_all(db)
+    .await
+}
+
+#[derive(sqlx::FromRow)]
+struct RecordTag {
+    record_id: Uuid,
+    #[sqlx(flatten)]
+    tag: Tag,
+}
+
+// Tags for a page of records at once, keyed by record
+pub async fn for_records(db: &Database, kind: Tagged, record_ids: &[Uuid]) -> Result<HashMap<Uuid, Vec<Tag>>, sqlx::Error> {
+    let rows = sqlx::query_as::<_, RecordTag>(
+        r#"
+        SELECT tg.record_id, t.* FROM tags t
+        JOIN taggings tg ON tg.tag_id = t.id
+        WHERE tg.record_type = $1 AND tg.record_id = ANY($2)
+        ORDER BY LOWER(t.name)
+        "#,
+    )
+    .bind(kind.key())
+    .bind(record_ids)
+    .fetch_all(db)
+    .await?;
+
+    let mut tags: HashMap<Uuid, Vec<Tag>> = HashMap::new();
+    for row in rows {
+        tags.entry(row.record_id).or_default().push(row.tag);
+    }
+    Ok(tags)
+}
+
+pub async fn picker(db: &Database, kind: Tagged, record_id: Option<Uuid>) -> Result<TagPicker, sqlx::Error> {
+    let selected = match record_id {
+        Some(record_id) => for_record(db, kind, record_id).await?,
+        None => Vec::new(),
+    };
+    Ok(TagPicker { selected, available: list(db).await? })
+}
+
+// Replaces the record's tags with `names`, creating any tag that doesn't
+// exist yet. Returns the names before and after, for the audit log.
+pub async fn set_for_record(
+    db: &Database,
+    kind: Tagged,
+    record_id: Uuid,
+    value: &str,
+    user_id: Uuid,
+) -> Result<(Vec<String>, Vec<String>), sqlx::Error> {
+    let names = parse_names(value);
+    let before: Vec<String> = for_record(db, kind, record_id).await?.into_iter().map(|tag| tag.name).collect();
+
+    let mut tx = db.begin().await?;
+    sqlx::query(
+        r#"
+        INSERT INTO tags (name, created_by)
+        SELECT name, $2 FROM UNNEST($1::text[]) AS n(name)
+        ON CONFLICT (tenant_id, LOWER(name)) DO NOTHING
+        "#,
+    )
+    .bind(&names)
+    .bind(user_id)
+    .execute(&mut *tx)
+    .await?;
+
+    let lowered: Vec<String> = names.iter().map(|name| name.to_lowercase()).collect();
+    sqlx::query(
+        r#"
+        DELETE FROM taggings tg USING tags t
+        WHERE t.id = tg.tag_id AND tg.record_type = $1 AND tg.record_id = $2
+          AND NOT LOWER(t.name) = ANY($3)
+        "#,
+    )
+    .bind(kind.key())
+    .bind(record_id)
+    .bind(&lowered)
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query(
+        r#"
+        INSERT INTO taggings (tag_id, record_type, record_id, created_by)
+        SELECT id, $1, $2, $4 FROM tags WHERE LOWER(name) = ANY($3)
+        ON CONFLICT DO NOTHING
+        "#,
+    )
+    .bind(kind.key())
+    .bind(record_id)
+    .bind(&lowered)
+    .bind(user_id)
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+
+    let after = for_record(db, kind, record_id).await?.into_iter().map(|tag| tag.name).collect();
+    Ok((before, after))
+}
+
+// SQL condition that the record aliased as `alias` has the tag bound at $param
+pub fn filter_condition(kind: Tagged, alias: &str, param: usize) -> String {
+    format!(
+        "EXISTS (SELECT 1 FROM taggings tg WHERE tg.record_type = '{}' AND tg.record_id = {}.id AND tg.tag_id = ${})",
+        kind.key(),
+        alias,
+        param
+    )
+}
+
+fn check(name: &str, color: &str) -> Result<(), String> {
+    if name.is_empty() {
+        return Err("Name is required".to_string());
+    }
+    if name.contains(',') {
+        return Err("Tag names can't contain commas".to_string());
+    }
+    if name.chars().count() > MAX_NAME_LENGTH {
+        return Err(format!("Name can be at most {} characters", MAX_NAME_LENGTH));
+    }
+    if !TAG_COLORS.iter().any(|(value, _)| *value == color) {
+        return Err("Pick one of the listed colors".to_string());
+    }
+    Ok(())
+}
+
+pub async fn create(db: &Database, name: &str, color: &str, user_id: Uuid) -> Result<Result<Uuid, String>, sqlx::Error> {
+    if let Err(error) = check(name, color) {
+        return Ok(Err(error));
+    }
+
+    let created = sqlx::query_scalar::<_, Uuid>("INSERT INTO tags (name, color, created_by) VALUES ($1, $2, $3) RETURNING id")
+        .bind(name)
+        .bind(color)
+        .bind(user_id)
+        .fetch_one(db)
+        .await;
+    match created {
+        Ok(id) => Ok(Ok(id)),
+        Err(sqlx::Error::Database(e)) if e.is_unique_violation() => Ok(Err(format!("There's already a tag called \"{}\"", name))),
+        Err(e) => Err(e),
+    }
+}
+
+// Renaming changes the tag everywhere it's used
+pub async fn update(db: &Database, id: Uuid, name: &str, color: &str) -> Result<Result<(), String>, sqlx::Error> {
+    if let Err(error) = check(name, color) {
+        return Ok(Err(error));
+    }
+
+    let updated = sqlx::query("UPDATE tags SET name = $2, color = $3 WHERE id = $1")
+        .bind(id)
+        .bind(name)
+        .bind(color)
+        .execute(db)
+        .await;
+    match updated {
+        Ok(_) => Ok(Ok(())),
+        Err(sqlx::Error::Database(e)) if e.is_unique_violation() => Ok(Err(format!("There's already a tag called \"{}\"", name))),
+        Err(e) => Err(e),
+    }
+}
+
+// Takes the tag off everything it's on
+pub async fn delete(db: &Database, id: Uuid) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM tags WHERE id = $1").bind(id).execute(db).await?;
+    Ok(())
+}
@@ -0,0 +1,212 @@
+use uuid::Uuid;
+
+use crate::{
+    database::Database,
+    models::{Team, TeamMember},
+};
+
+const APPROVE_ALL_PERMISSION: &str = "expenses:approve";
+
+// Lets a role approve and deny the expenses of people on teams it manages,
+// rather than everyone's
+pub const APPROVE_TEAM_PERMISSION: &str = "expenses:approve_team";
+
+// The user bound at `user` and everyone sharing an active team with them
+pub fn teammates_of(user: &str) -> String {
+    format!(
+        r#"SELECT m.user_id FROM team_members m JOIN teams t ON t.id = m.team_id AND t.is_active
+           WHERE m.team_id IN (SELECT team_id FROM team_members WHERE user_id = {0})
+           UNION SELECT {0}::uuid"#,
+        user
+    )
+}
+
+// The other members of the active teams the user bound at `user` manages
+pub fn managed_by(user: &str) -> String {
+    format!(
+        r#"SELECT m.user_id FROM team_members m JOIN teams t ON t.id = m.team_id AND t.is_active
+           WHERE m.team_id IN (SELECT team_id FROM team_members WHERE user_id = {0} AND is_manager)
+             AND m.user_id <> {0}"#,
+        user
+    )
+}
+
+fn holds(permissions: &[String], permission: &str) -> bool {
+    permissions.iter().any(|p| p == permission)
+}
+
+pub fn approves_expenses(permissions: &[String]) -> bool {
+    holds(permissions, APPROVE_ALL_PERMISSION) || holds(permissions, APPROVE_TEAM_PERMISSION)
+}
+
+// SQL condition limiting expenses aliased as `alias` to those a user with
+// `permissions`, whose id is bound at `param`, may approve or deny
+pub fn expense_approval_condition(permissions: &[String], alias: &str, param: &str) -> String {
+    if holds(permissions, APPROVE_ALL_PERMISSION) {
+        "true".to_string()
+    } else if holds(permissions, APPROVE_TEAM_PERMISSION) {
+        format!("{}.user_id IN ({})", alias, managed_by(param))
+    } else {
+        "false".to_string()
+    }
+}
+
+// The "my team" / "everyone" switch on the deal, activity and expense lists
+pub struct TeamFilter {
+    pub path: &'static str,
+    pub team_only: bool,
+    // Whether the viewer is on a team at all; without one there's nothing to switch
+    pub available: bool,
+}
+
+impl TeamFilter {
+    // `show` is the list's query parameter; "team" narrows it
+    pub async fn load(db: &Database, user_id: Uuid, path: &'static str, show: Option<&str>) -> Result<Self, sqlx::Error> {
+        let available = sqlx::query_scalar::<_, bool>(
+            r#"
+            SELECT EXISTS(
+                SELECT 1 FROM team_members m JOIN teams t ON t.id = m.team_id
+                WHERE m.user_id = $1 AND t.is_active
+            )
+            "#,
+        )
+        .bind(user_id)
+        .fetch_one(db)
+        .await?;
+        Ok(Self {
+            path,
+            team_only: available && show == Some("team"),
+            available,
+        })
+    }
+
+    // SQL condition keeping rows whose owner, `owner_expr`, is a teammate of
+    // the user bound at `user`; None when showing everyone
+    pub fn condition(&self, owner_expr: &str, user: &str) -> Option<String> {
+        self.team_only
+            .then(|| format!("{} IN ({})", owner_expr, teammates_of(user)))
+    }
+}
+
+pub async fn list(db: &Database) -> Result<Vec<Team>, sqlx::Error> {
+    sqlx::query_as::<_, Team>(
+        r#"
+        SELECT t.*,
+               COUNT(m.user_id) as member_count,
+               STRING_AGG(CONCAT(u.first_name, ' ', u.last_name), ', ' ORDER BY u.first_name, u.last_name)
+                   FILTER (WHERE m.is_manager) as managers
+        FROM teams t
+        LEFT JOIN team_members m ON m.team_id = t.id
+        LEFT JOIN users u ON u.id = m.user_id
+        GROUP BY t.id
+        ORDER BY t.is_active DESC, t.name
+        "#,
+    )
+    .fetch_all(db)
+    .await
+}
+
+pub async fn get(db: &Database, id: Uuid) -> Result<Option<Team>, sqlx::Error> {
+    sqlx::query_as::<_, Team>("SELECT * FROM teams WHERE id = $1")
+        .bind(id)
+        .fetch_optional(db)
+        .await
+}
+
+// Managers first
+pub async fn members(db: &Database, team_id: Uuid) -> Result<Vec<TeamMember>, sqlx::Error> {
+    sqlx::query_as::<_, TeamMember>(
+        r#"
+        SELECT u.id as user_id, CONCAT(u.first_name, ' ', u.last_name) as name, u.email, u.is_active, m.is_manager
+        FROM team_members m
+        JOIN users u ON u.id = m.user_id
+        WHERE m.team_id = $1
+        ORDER BY m.is_manager DESC, u.first_name, u.last_name
+        "#,
+    )
+    .bind(team_id)
+    .fetch_all(db)
+    .await
+}
+
+fn validate(name: &str) -> Result<(), String> {
+    if name.is_empty() || name.chars().count() > 100 {
+        return Err("The name is required and can be up to 100 characters".to_string());
+    }
+    Ok(())
+}
+
+// Returns why it was refused, if it was
+pub async fn create(db: &Database, name: &str, description: Option<&str>, created_by: Uuid) -> Result<Result<Uuid, String>, sqlx::Error> {
+    if let Err(reason) = validate(name) {
+        return Ok(Err(reason));
+    }
+    let id = sqlx::query_scalar::<_, Uuid>(
+        r#"
+        INSERT INTO teams (name, description, created_by) VALUES ($1, $2, $3)
+        ON CONFLICT (tenant_id, name) DO NOTHING
+        RETURNING id
+        "#,
+    )
+    .bind(name)
+    .bind(description)
+    .bind(created_by)
+    .fetch_optional(db)
+    .await?;
+    Ok(id.ok_or_else(|| format!("There is already a team called {}", name)))
+}
+
+// Deactivated teams keep their members but stop counting for filters and approvals
+pub async fn update(db: &Database, id: Uuid, name: &str, description: Option<&str>, is_active: bool) -> Result<Result<(), String>, sqlx::Error> {
+    if let Err(reason) = validate(name) {
+        return Ok(Err(reason));
+    }
+    let taken = sqlx::query_scalar::<_, bool>("SELECT EXISTS(SELECT 1 FROM teams WHERE name = $1 AND id <> $2)")
+        .bind(name)
+        .bind(id)
+        .fetch_one(db)
+        .await?;
+    if taken {
+        return Ok(Err(format!("There is already a team called {}", name)));
+    }
+    let updated = sqlx::query(
+        "UPDATE teams SET name = $2, description = $3, is_active = $4, updated_at = NOW() WHERE id = $1",
+    )
+    .bind(id)
+    .bind(name)
+    .bind(description)
+    .bind(is_active)
+    .execute(db)
+    .await?
+    .rows_affected();
+    if updated == 0 {
+        return Ok(Err("Unknown team".to_string()));
+    }
+    Ok(Ok(()))
+}
+
+// Adds the user, or changes whether they manage the team if already on it
+pub async fn set_member(db: &Database, team_id: Uuid, user_id: Uuid, is_manager: bool) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        r#"
+        INSERT INTO team_members (team_id, user_id, is_manager) VALUES ($1, $2, $3)
+        ON CONFLICT (team_id, user_id) DO UPDATE SET is_manager = EXCLUDED.is_manager
+        "#,
+    )
+    .bind(team_id)
+    .bind(user_id)
+    .bind(is_manager)
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+pub async fn remove_member(db: &Database, team_id: Uuid, user_id: Uuid) -> Result<bool, sqlx::Error> {
+    let removed = sqlx::query("DELETE FROM team_members WHERE team_id = $1 AND user_id = $2")
+        .bind(team_id)
+        .bind(user_id)
+        .execute(db)
+        .await?
+        .rows_affected();
+    Ok(removed > 0)
+}
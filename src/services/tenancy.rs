@@ -0,0 +1,67 @@
+use std::future::Future;
+
+use sqlx::FromRow;
+use uuid::Uuid;
+
+use crate::database::Database;
+
+// An organization sharing the database. Business tables only show a
+// connection the rows of the tenant it was set to; see database.rs and
+// migration 067.
+#[derive(Debug, Clone, FromRow)]
+pub struct Tenant {
+    pub id: Uuid,
+    pub slug: String,
+    pub is_primary: bool,
+}
+
+tokio::task_local! {
+    static CURRENT: Tenant;
+}
+
+// The tenant the running request or job works for. Work handed to
+// tokio::spawn leaves the scope and has to enter it again.
+pub fn current() -> Option<Tenant> {
+    CURRENT.try_with(Tenant::clone).ok()
+}
+
+pub fn current_id() -> Option<Uuid> {
+    CURRENT.try_with(|tenant| tenant.id).ok()
+}
+
+// Run `work` for `tenant`: every connection it takes from the pool is set
+// to that tenant
+pub async fn scope<F: Future>(tenant: Tenant, work: F) -> F::Output {
+    CURRENT.scope(tenant, work).await
+}
+
+// The tenants table has no row level security, so these work outside any scope
+
+pub async fn all(db: &Database) -> Result<Vec<Tenant>, sqlx::Error> {
+    sqlx::query_as::<_, Tenant>("SELECT id, slug, is_primary FROM tenants ORDER BY created_at")
+        .fetch_all(db)
+        .await
+}
+
+pub async fn primary(db: &Database) -> Result<Option<Tenant>, sqlx::Error> {
+    sqlx::query_as::<_, Tenant>("SELECT id, slug, is_primary FROM tenants WHERE is_primary")
+        .fetch_optional(db)
+        .await
+}
+
+pub async fn by_slug(db: &Database, slug: &str) -> Result<Option<Tenant>, sqlx::Error> {
+    sqlx::query_as::<_, Tenant>("SELECT id, slug, is_primary FROM tenants WHERE slug = LOWER($1)")
+        .bind(slug)
+        .fetch_optional(db)
+        .await
+}
+
+// The tenant whose user signed in to the session
+pub async fn for_session(db: &Database, session_id: Uuid) -> Result<Option<Tenant>, sqlx::Error> {
+    sqlx::query_as::<_, Tenant>(
+        "SELECT id, slug, is_primary FROM tenants WHERE id = session_tenant($1)",
+    )
+    .bind(session_id)
+    .fetch_optional(db)
+    .await
+}
@@ -0,0 +1,255 @@
+use uuid::Uuid;
+
+use crate::{
+    database::Database,
+    middleware::CurrentUser,
+    models::{InventoryItem, ItemOptionSet, ItemStock, VariantOption},
+    services::locations,
+    utils::barcode::normalize_sku,
+};
+
+// More than this many variants from one generate is almost certainly a typo
+const MAX_VARIANTS: usize = 200;
+
+pub struct OptionSet {
+    pub name: String,
+    pub values: Vec<String>,
+}
+
+// Available stock laid out with the first option set down the side and the
+// second across the top. Only built for items with exactly two option sets.
+pub struct StockMatrix {
+    pub row_name: String,
+    pub columns: Vec<String>,
+    // None where that combination has no variant
+    pub rows: Vec<(String, Vec<Option<i64>>)>,
+}
+
+// One option set per line, as "Size: S, M, L"
+pub fn parse_option_sets(text: &str) -> Result<Vec<OptionSet>, String> {
+    let mut sets: Vec<OptionSet> = Vec::new();
+    for line in text.lines().map(str::trim).filter(|line| !line.is_empty()) {
+        let Some((name, values)) = line.split_once(':') else {
+            return Err(format!("\"{}\" should look like \"Size: S, M, L\"", line));
+        };
+        let name = name.trim();
+        if name.is_empty() || name.len() > 50 {
+            return Err(format!("\"{}\" needs an option name of up to 50 characters", line));
+        }
+        if sets.iter().any(|set| set.name.eq_ignore_ascii_case(name)) {
+            return Err(format!("{} is listed twice", name));
+        }
+        let mut unique: Vec<String> = Vec::new();
+        for value in values.split(',').map(str::trim).filter(|v| !v.is_empty()) {
+            if !unique.iter().any(|seen| seen.eq_ignore_ascii_case(value)) {
+                unique.push(value.to_string());
+            }
+        }
+        if unique.is_empty() {
+            return Err(format!("{} has no values", name));
+        }
+        sets.push(OptionSet { name: name.to_string(), values: unique });
+    }
+
+    if sets.is_empty() {
+        return Err("Enter at least one option set".to_string());
+    }
+    let count = sets.iter().map(|set| set.values.len()).product::<usize>();
+    if count > MAX_VARIANTS {
+        return Err(format!("Those options make {} variants; the limit is {}", count, MAX_VARIANTS));
+    }
+    Ok(sets)
+}
+
+// Every combination of one value from each set, in the sets' order
+fn combinations(sets: &[OptionSet]) -> Vec<Vec<VariantOption>> {
+    sets.iter().fold(vec![Vec::new()], |combos, set| {
+        combos
+            .iter()
+            .flat_map(|combo| {
+                set.values.iter().map(move |value| {
+                    let mut next = combo.clone();
+                    next.push(VariantOption { name: set.name.clone(), value: value.clone() });
+                    next
+                })
+            })
+            .collect()
+    })
+}
+
+// The parent's SKU with each value appended, keeping only letters and digits:
+// TSHIRT + M, Navy Blue -> TSHIRT-M-NAVYBLUE
+fn variant_sku(parent_sku: &str, options: &[VariantOption]) -> String {
+    let mut sku = parent_sku.to_string();
+    for option in options {
+        sku.push('-');
+        sku.extend(option.value.chars().filter(|c| c.is_alphanumeric()));
+    }
+    normalize_sku(&sku)
+}
+
+// Same options regardless of the order the sets were listed in
+fn same_options(a: &[VariantOption], b: &[VariantOption]) -> bool {
+    a.len() == b.len() && a.iter().all(|option| b.contains(option))
+}
+
+pub async fn option_sets(db: &Database, item_id: Uuid) -> Result<Vec<ItemOptionSet>, sqlx::Error> {
+    sqlx::query_as::<_, ItemOptionSet>("SELECT * FROM item_option_sets WHERE item_id = $1 ORDER BY position")
+        .bind(item_id)
+        .fetch_all(db)
+        .await
+}
+
+pub async fn variants(db: &Database, parent_id: Uuid) -> Result<Vec<InventoryItem>, sqlx::Error> {
+    sqlx::query_as::<_, InventoryItem>(
+        "SELECT * FROM inventory_items WHERE parent_item_id = $1 ORDER BY created_at, sku",
+    )
+    .bind(parent_id)
+    .fetch_all(db)
+    .await
+}
+
+// Stock per item over the warehouses the user can see. A parent's figures are the sum of its
+// variants', since the stock is held against the variants.
+pub async fn stock(db: &Database, user: &CurrentUser, item_ids: &[Uuid]) -> Result<Vec<ItemStock>, sqlx::Error> {
+    sqlx::query_as::<_, ItemStock>(&format!(
+        r#"
+        SELECT i.id as item_id,
+               COALESCE(SUM(sl.quantity_on_hand), 0) as on_hand,
+               COALESCE(SUM(sl.quantity_committed), 0) as committed,
+               COALESCE(SUM(sl.quantity_available), 0) as available
+        FROM inventory_items i
+        LEFT JOIN inventory_items v ON v.parent_item_id = i.id
+        LEFT JOIN stock_levels sl ON sl.item_id = COALESCE(v.id, i.id) AND {}
+        WHERE i.id = ANY($1)
+        GROUP BY i.id
+        "#,
+        locations::warehouse_condition("sl.warehouse_id", 2)
+    ))
+    .bind(item_ids)
+    .bind(user.warehouse_ids.as_deref())
+    .fetch_all(db)
+    .await
+}
+
+// Save the option sets on `parent` and create the variants that don't exist
+// yet. Variants start with a copy of the parent's attributes and prices and
+// get their own SKU. Returns how many were created, or why none could be.
+pub async fn generate(
+    db: &Database,
+    parent: &InventoryItem,
+    sets: &[OptionSet],
+    created_by: Uuid,
+) -> Result<Result<usize, String>, sqlx::Error> {
+    if parent.parent_item_id.is_some() {
+        return Ok(Err("A variant can't have variants of its own".to_string()));
+    }
+    let has_stock = sqlx::query_scalar::<_, bool>(
+        "SELECT EXISTS(SELECT 1 FROM stock_levels WHERE item_id = $1 AND quantity_on_hand <> 0)",
+    )
+    .bind(parent.id)
+    .fetch_one(db)
+    .await?;
+    if has_stock {
+        return Ok(Err("This item holds stock itself. Stock is kept per variant, so it has to be moved off first.".to_string()));
+    }
+
+    let existing = variants(db, parent.id).await?;
+    let new: Vec<(String, Vec<VariantOption>)> = combinations(sets)
+        .into_iter()
+        .filter(|options| {
+            !existing.iter().any(|variant| {
+                variant.variant_options.as_ref().is_some_and(|theirs| same_options(theirs, options))
+            })
+        })
+        .map(|options| (variant_sku(&parent.sku, &options), options))
+        .collect();
+
+    let skus: Vec<String> = new.iter().map(|(sku, _)| sku.clone()).collect();
+    if let Some(clash) = skus.iter().enumerate().find(|(i, sku)| skus[..*i].contains(sku)) {
+        return Ok(Err(format!("Two variants would both get SKU {}; make their values differ by more than spaces or punctuation", clash.1)));
+    }
+    let taken = sqlx::query_scalar::<_, String>(
+        "SELECT sku FROM inventory_items WHERE UPPER(sku) = ANY($1) ORDER BY sku LIMIT 5",
+    )
+    .bind(&skus)
+    .fetch_all(db)
+    .await?;
+    if !taken.is_empty() {
+        return Ok(Err(format!("These SKUs are already used by other items: {}", taken.join(", "))));
+    }
+
+    let mut tx = db.begin().await?;
+    sqlx::query("DELETE FROM item_option_sets WHERE item_id = $1")
+        .bind(parent.id)
+        .execute(&mut *tx)
+        .await?;
+    for (position, set) in sets.iter().enumerate() {
+        sqlx::query("INSERT INTO item_option_sets (item_id, name, option_values, position) VALUES ($1, $2, $3, $4)")
+            .bind(parent.id)
+            .bind(&set.name)
+            .bind(&set.values)
+            .bind(position as i32)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    for (sku, options) in &new {
+        let label = options.iter().map(|o| o.value.as_str()).collect::<Vec<_>>().join(" / ");
+        sqlx::query(
+            r#"
+            INSERT INTO inventory_items (
+                item_name, sku, item_type, category, brand, model, description, short_description,
+                image_url, reorder_point, preferred_stock_level, lead_time, backorder_allowed,
+                preferred_supplier_id, purchase_price, selling_price, tax_category, cost_price,
+                currency, country_of_origin, hs_code, lifecycle_stage, created_by,
+                parent_item_id, variant_options
+            )
+            SELECT item_name || ' - ' || $2, $3, item_type, category, brand, model, description, short_description,
+                   image_url, reorder_point, preferred_stock_level, lead_time, backorder_allowed,
+                   preferred_supplier_id, purchase_price, selling_price, tax_category, cost_price,
+                   currency, country_of_origin, hs_code, lifecycle_stage, $4,
+                   id, $5
+            FROM inventory_items
+            WHERE id = $1
+            "#,
+        )
+        .bind(parent.id)
+        .bind(&label)
+        .bind(sku)
+        .bind(created_by)
+        .bind(sqlx::types::Json(options))
+        .execute(&mut *tx)
+        .await?;
+    }
+    tx.commit().await?;
+
+    Ok(Ok(new.len()))
+}
+
+pub fn stock_matrix(sets: &[ItemOptionSet], variants: &[InventoryItem], stock: &[ItemStock]) -> Option<StockMatrix> {
+    let [down, across] = sets else {
+        return None;
+    };
+
+    let available = |row: &str, column: &str| {
+        let wanted = [
+            VariantOption { name: down.name.clone(), value: row.to_string() },
+            VariantOption { name: across.name.clone(), value: column.to_string() },
+        ];
+        let variant = variants.iter().find(|variant| {
+            variant.variant_options.as_ref().is_some_and(|options| same_options(options, &wanted))
+        })?;
+        Some(stock.iter().find(|s| s.item_id == variant.id).map_or(0, |s| s.available))
+    };
+
+    Some(StockMatrix {
+        row_name: down.name.clone(),
+        columns: across.option_values.clone(),
+        rows: down
+            .option_values
+            .iter()
+            .map(|row| (row.clone(), across.option_values.iter().map(|column| available(row, column)).collect()))
+            .collect(),
+    })
+}
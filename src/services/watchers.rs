@@ -0,0 +1,74 @@
+use uuid::Uuid;
+
+use crate::{database::Database, services::sharing::RecordKind};
+
+pub async fn watch(db: &Database, kind: RecordKind, record_id: Uuid, user_id: Uuid) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO watchers (record_type, record_id, user_id) VALUES ($1, $2, $3) ON CONFLICT DO NOTHING",
+    )
+    .bind(kind.key())
+    .bind(record_id)
+    .bind(user_id)
+    .execute(db)
+    .await?;
+    Ok(())
+}
+
+pub async fn unwatch(db: &Database, kind: RecordKind, record_id: Uuid, user_id: Uuid) -> Result<(), sqlx::Error> {
+    sqlx::query("DELETE FROM watchers WHERE record_type = $1 AND record_id = $2 AND user_id = $3")
+        .bind(kind.key())
+        .bind(record_id)
+        .bind(user_id)
+        .execute(db)
+        .await?;
+    Ok(())
+}
+
+// Whether the user watches the record, and how many people do
+pub async fn status(db: &Database, kind: RecordKind, record_id: Uuid, user_id: Uuid) -> Result<(bool, i64), sqlx::Error> {
+    sqlx::query_as::<_, (bool, i64)>(
+        r#"
+        SELECT COALESCE(BOOL_OR(user_id = $3), false), COUNT(*)
+        FROM watchers
+        WHERE record_type = $1 AND record_id = $2
+        "#,
+    )
+    .bind(kind.key())
+    .bind(record_id)
+    .bind(user_id)
+    .fetch_one(db)
+    .await
+}
+
+// Notify everyone watching any of `records`, once each, except whoever made
+// the change
+pub async fn notify(
+    db: &Database,
+    records: &[(RecordKind, Uuid)],
+    actor_id: Uuid,
+    message: &str,
+    link_url: &str,
+) -> Result<u64, sqlx::Error> {
+    let (types, ids): (Vec<&str>, Vec<Uuid>) = records.iter().map(|(kind, id)| (kind.key(), *id)).unzip();
+
+    let result = sqlx::query(
+        r#"
+        INSERT INTO notifications (user_id, message, link_url)
+        SELECT DISTINCT w.user_id, $3, $4
+        FROM watchers w
+        JOIN UNNEST($1::text[], $2::uuid[]) AS r(record_type, record_id)
+          ON r.record_type = w.record_type AND r.record_id = w.record_id
+        JOIN users u ON u.id = w.user_id
+        WHERE w.user_id <> $5 AND u.is_active = true
+        "#,
+    )
+    .bind(types)
+    .bind(ids)
+    .bind(message)
+    .bind(link_url)
+    .bind(actor_id)
+    .execute(db)
+    .await?;
+
+    Ok(result.rows_affected())
+}
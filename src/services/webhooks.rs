@@ -0,0 +1,236 @@
+use chrono::{Duration, Utc};
+use serde_json::{json, Value};
+use uuid::Uuid;
+
+use crate::{
+    database::Database,
+    models::{Customer, Deal, Webhook},
+    utils::json_template,
+};
+
+// Events a webhook can subscribe to, with their labels
+pub const WEBHOOK_EVENTS: &[(&str, &str)] = &[
+    ("customer.created", "Customer created"),
+    ("customer.updated", "Customer updated"),
+    ("deal.created", "Deal created"),
+    ("deal.stage_changed", "Deal stage changed"),
+];
+
+// Deliveries are given up after this many failed attempts
+const MAX_ATTEMPTS: i32 = 5;
+
+pub fn event_label(event: &str) -> String {
+    WEBHOOK_EVENTS
+        .iter()
+        .find(|(key, _)| *key == event)
+        .map(|(_, label)| label.to_string())
+        .unwrap_or_else(|| event.to_string())
+}
+
+// What templates render against, and the body sent when a webhook has none
+fn envelope(event: &str, data: Value) -> Value {
+    json!({
+        "event": event,
+        "occurred_at": Utc::now().to_rfc3339(),
+        "data": data,
+    })
+}
+
+// A representative context for an event, used to validate templates and to
+// list the fields they can use
+pub fn sample_context(event: &str) -> Value {
+    let now = Utc::now();
+    let data = match event {
+        "customer.created" | "customer.updated" => json!(Customer {
+            id: Uuid::nil(),
+            company_name: "Acme Corp".to_string(),
+            industry: Some("Technology".to_string()),
+            website: Some("https://acme.example".to_string()),
+            phone: Some("+1 555 0100".to_string()),
+            email: Some("hello@acme.example".to_string()),
+            address_line1: None,
+            address_line2: None,
+            city: Some("Springfield".to_string()),
+            state: None,
+            postal_code: None,
+            country: Some("US".to_string()),
+            status: "prospect".to_string(),
+            notes: None,
+            created_by: Some(Uuid::nil()),
+            created_at: now,
+            updated_at: now,
+            campaign_id: None,
+            lead_source: Some("web_form".to_string()),
+            utm_source: None,
+            utm_medium: None,
+            utm_campaign: None,
+            referrer: None,
+            preferred_language: None,
+            assigned_to: None,
+            custom_fields: json!({}),
+        }),
+        _ => {
+            let mut deal = json!(Deal {
+                id: Uuid::nil(),
+                customer_id: Uuid::nil(),
+                contact_id: None,
+                title: "Annual licence".to_string(),
+                description: None,
+                value: Some(rust_decimal::Decimal::new(1250000, 2)),
+                currency: "USD".to_string(),
+                exchange_rate: Some(rust_decimal::Decimal::ONE),
+                stage: "negotiation".to_string(),
+                probability: 75,
+                expected_close_date: Some(now.date_naive()),
+                actual_close_date: None,
+                assigned_to: None,
+                created_by: Some(Uuid::nil()),
+                created_at: now,
+                updated_at: now,
+                stage_changed_at: Some(now),
+                campaign_id: None,
+                custom_fields: json!({}),
+            });
+            if event == "deal.stage_changed" {
+                deal["previous_stage"] = json!("prospect");
+            }
+            deal
+        }
+    };
+    envelope(event, data)
+}
+
+// Queue a delivery of `data` to every active webhook subscribed to `event`.
+// Failures are logged rather than returned so a broken webhook never blocks
+// the change that triggered it.
+pub async fn dispatch(db: &Database, event: &str, data: Value) {
+    let context = envelope(event, data);
+
+    let webhooks = match sqlx::query_as::<_, Webhook>("SELECT * FROM webhooks WHERE event = $1 AND is_active = true")
+        .bind(event)
+        .fetch_all(db)
+        .await
+    {
+        Ok(webhooks) => webhooks,
+        Err(e) => {
+            tracing::error!("Failed to load webhooks for {}: {}", event, e);
+            return;
+        }
+    };
+
+    for webhook in webhooks {
+        let payload = match webhook.payload_template.as_deref() {
+            Some(template) => json_template::render(template, &context),
+            None => Ok(context.to_string()),
+        };
+
+        // A template that renders badly for this record is recorded as a
+        // failed delivery so the admin can see it
+        let (payload, status, error) = match payload {
+            Ok(payload) => (payload, "pending", None),
+            Err(e) => (String::new(), "failed", Some(e)),
+        };
+
+        let result = sqlx::query(
+            "INSERT INTO webhook_deliveries (webhook_id, event, payload, status, error) VALUES ($1, $2, $3, $4, $5)",
+        )
+        .bind(webhook.id)
+        .bind(event)
+        .bind(payload)
+        .bind(status)
+        .bind(error)
+        .execute(db)
+        .await;
+
+        if let Err(e) = result {
+            tracing::error!("Failed to queue webhook {}: {}", webhook.id, e);
+        }
+    }
+}
+
+#[derive(sqlx::FromRow)]
+struct PendingDelivery {
+    id: Uuid,
+    url: String,
+    payload: String,
+    attempts: i32,
+}
+
+// POST due deliveries. Non-2xx responses and network errors are retried with
+// exponential backoff until MAX_ATTEMPTS.
+pub async fn deliver_pending(db: &Database) -> Result<usize, sqlx::Error> {
+    let pending = sqlx::query_as::<_, PendingDelivery>(
+        r#"
+        SELECT d.id, w.url, d.payload, d.attempts
+        FROM webhook_deliveries d
+        JOIN webhooks w ON w.id = d.webhook_id
+        WHERE d.status = 'pending' AND d.next_attempt_at <= NOW()
+        ORDER BY d.next_attempt_at
+        LIMIT 50
+        "#,
+    )
+    .fetch_all(db)
+    .await?;
+
+    if pending.is_empty() {
+        return Ok(0);
+    }
+
+    let client = reqwest::Client::builder()
+        .timeout(std::time::Duration::from_secs(10))
+        .build()
+        .map_err(|e| sqlx::Error::Protocol(e.to_string()))?;
+
+    let mut delivered = 0;
+
+    for delivery in pending {
+        let result = client
+            .post(&delivery.url)
+            .header("Content-Type", "application/json")
+            .header("User-Agent", "Allo-Webhooks/1.0")
+            .body(delivery.payload.clone())
+            .send()
+            .await;
+
+        let (response_status, error) = match result {
+            Ok(response) if response.status().is_success() => (Some(response.status().as_u16() as i32), None),
+            Ok(response) => (
+                Some(response.status().as_u16() as i32),
+                Some(format!("Receiver responded with {}", response.status())),
+            ),
+            Err(e) => (None, Some(e.to_string())),
+        };
+
+        let attempts = delivery.attempts + 1;
+        match error {
+            None => {
+                sqlx::query(
+                    "UPDATE webhook_deliveries SET status = 'delivered', attempts = $2, response_status = $3, error = NULL, delivered_at = NOW() WHERE id = $1",
+                )
+                .bind(delivery.id)
+                .bind(attempts)
+                .bind(response_status)
+                .execute(db)
+                .await?;
+                delivered += 1;
+            }
+            Some(error) => {
+                let status = if attempts >= MAX_ATTEMPTS { "failed" } else { "pending" };
+                let next_attempt_at = Utc::now() + Duration::minutes(2i64.pow(attempts as u32));
+                sqlx::query(
+                    "UPDATE webhook_deliveries SET status = $2, attempts = $3, response_status = $4, error = $5, next_attempt_at = $6 WHERE id = $1",
+                )
+                .bind(delivery.id)
+                .bind(status)
+                .bind(attempts)
+                .bind(response_status)
+                .bind(error)
+                .bind(next_attempt_at)
+                .execute(db)
+                .await?;
+            }
+        }
+    }
+
+    Ok(delivered)
+}
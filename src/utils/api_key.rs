@@ -0,0 +1,28 @@
+use rand::{distributions::Alphanumeric, Rng};
+use sha2::{Digest, Sha256};
+
+pub struct GeneratedApiKey {
+    // Shown to the user once and never stored
+    pub token: String,
+    pub prefix: String,
+    pub hash: String,
+}
+
+// Keys look like allo_<8 char id>_<32 char secret>. The id is kept in clear
+// so keys can be told apart in the UI.
+pub fn generate_api_key() -> GeneratedApiKey {
+    let mut rng = rand::thread_rng();
+    let mut random = |len: usize| -> String {
+        (&mut rng).sample_iter(&Alphanumeric).take(len).map(char::from).collect()
+    };
+
+    let prefix = format!("allo_{}", random(8));
+    let token = format!("{}_{}", prefix, random(32));
+    let hash = hash_api_key(&token);
+
+    GeneratedApiKey { token, prefix, hash }
+}
+
+pub fn hash_api_key(token: &str) -> String {
+    hex::encode(Sha256::digest(token.as_bytes()))
+}
@@ -1,8 +1,56 @@
-use jsonwebtoken::{decode, encode, DecodingKey, EncodingKey, Header, Validation};
+use jsonwebtoken::{decode, decode_header, encode, errors::ErrorKind, DecodingKey, EncodingKey, Header, Validation};
 use serde::{Deserialize, Serialize};
-use std::env;
+use std::{env, sync::RwLock};
 use uuid::Uuid;
-use chrono::{Duration, Utc};
+use chrono::{DateTime, Utc};
+
+// A secret from jwt_signing_keys; see services::signing_keys
+#[derive(Debug, Clone)]
+pub struct SigningKey {
+    pub kid: String,
+    pub secret: String,
+    pub created_at: DateTime<Utc>,
+    // None for the key currently signing
+    pub retires_at: Option<DateTime<Utc>>,
+}
+
+struct KeyRing {
+    keys: Vec<SigningKey>,
+    // When tokens without a kid, signed with JWT_SECRET, stop verifying; None
+    // until the first rotation
+    legacy_retires_at: Option<DateTime<Utc>>,
+}
+
+static KEY_RING: RwLock<KeyRing> = RwLock::new(KeyRing { keys: Vec::new(), legacy_retires_at: None });
+
+// Replace the keys tokens are signed and verified with
+pub fn set_signing_keys(keys: Vec<SigningKey>, legacy_retires_at: Option<DateTime<Utc>>) {
+    let mut ring = KEY_RING.write().unwrap();
+    ring.keys = keys;
+    ring.legacy_retires_at = legacy_retires_at;
+}
+
+// Keys still accepted, newest first
+pub fn signing_keys() -> Vec<SigningKey> {
+    let now = Utc::now();
+    let ring = KEY_RING.read().unwrap();
+    let mut keys: Vec<SigningKey> = ring
+        .keys
+        .iter()
+        .filter(|key| key.retires_at.is_none_or(|at| at > now))
+        .cloned()
+        .collect();
+    keys.sort_by_key(|key| std::cmp::Reverse(key.created_at));
+    keys
+}
+
+pub fn legacy_key_retires_at() -> Option<DateTime<Utc>> {
+    KEY_RING.read().unwrap().legacy_retires_at
+}
+
+fn legacy_secret() -> String {
+    env::var("JWT_SECRET").expect("JWT_SECRET must be set")
+}
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct Claims {
@@ -10,36 +58,57 @@ pub struct Claims {
     pub email: String,
     pub exp: i64,
     pub iat: i64,
+    // Row in the sessions table this token belongs to
+    #[serde(default)]
+    pub sid: Option<String>,
 }
 
 impl Claims {
-    pub fn new(user_id: Uuid, email: String) -> Self {
+    // Tokens last as long as their session could, however active it is
+    pub fn new(user_id: Uuid, email: String, session_id: Uuid, expires_at: DateTime<Utc>) -> Self {
         let now = Utc::now();
-        let exp = now + Duration::hours(24); // Token expires in 24 hours
-        
+
         Self {
             sub: user_id.to_string(),
             email,
-            exp: exp.timestamp(),
+            exp: expires_at.timestamp(),
             iat: now.timestamp(),
+            sid: Some(session_id.to_string()),
         }
     }
 }
 
-pub fn create_token(user_id: Uuid, email: String) -> Result<String, jsonwebtoken::errors::Error> {
-    let claims = Claims::new(user_id, email);
-    let secret = env::var("JWT_SECRET").expect("JWT_SECRET must be set");
-    
+pub fn create_token(
+    user_id: Uuid,
+    email: String,
+    session_id: Uuid,
+    expires_at: DateTime<Utc>,
+) -> Result<String, jsonwebtoken::errors::Error> {
+    let claims = Claims::new(user_id, email, session_id, expires_at);
+    let (kid, secret) = match signing_keys().into_iter().find(|key| key.retires_at.is_none()) {
+        Some(key) => (Some(key.kid), key.secret),
+        None => (None, legacy_secret()),
+    };
+
     encode(
-        &Header::default(),
+        &Header { kid, ..Header::default() },
         &claims,
         &EncodingKey::from_secret(secret.as_ref()),
     )
 }
 
 pub fn verify_token(token: &str) -> Result<Claims, jsonwebtoken::errors::Error> {
-    let secret = env::var("JWT_SECRET").expect("JWT_SECRET must be set");
-    
+    let secret = match decode_header(token)?.kid {
+        Some(kid) => signing_keys()
+            .into_iter()
+            .find(|key| key.kid == kid)
+            .map(|key| key.secret),
+        None => legacy_key_retires_at()
+            .is_none_or(|at| at > Utc::now())
+            .then(legacy_secret),
+    }
+    .ok_or(ErrorKind::InvalidSignature)?;
+
     let token_data = decode::<Claims>(
         token,
         &DecodingKey::from_secret(secret.as_ref()),
@@ -47,4 +116,49 @@ pub fn verify_token(token: &str) -> Result<Claims, jsonwebtoken::errors::Error>
     )?;
     
     Ok(token_data.claims)
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Duration;
+
+    fn key(kid: &str, retires_at: Option<DateTime<Utc>>) -> SigningKey {
+        SigningKey {
+            kid: kid.to_string(),
+            secret: format!("secret-{}", kid),
+            created_at: Utc::now(),
+            retires_at,
+        }
+    }
+
+    // One test, since the key ring is shared by the whole process
+    #[test]
+    fn rotation_keeps_old_tokens_until_they_retire() {
+        env::set_var("JWT_SECRET", "legacy-secret");
+        let user = Uuid::new_v4();
+        let expires_at = Utc::now() + Duration::hours(1);
+        let token = |email: &str| create_token(user, email.to_string(), Uuid::new_v4(), expires_at).unwrap();
+
+        let legacy = token("legacy@allo.test");
+        assert_eq!(decode_header(&legacy).unwrap().kid, None);
+        assert!(verify_token(&legacy).is_ok());
+
+        let soon = Utc::now() + Duration::hours(1);
+        set_signing_keys(vec![key("a", None)], Some(soon));
+        let first = token("first@allo.test");
+        assert_eq!(decode_header(&first).unwrap().kid.as_deref(), Some("a"));
+        assert!(verify_token(&legacy).is_ok());
+
+        set_signing_keys(vec![key("a", Some(soon)), key("b", None)], Some(soon));
+        assert_eq!(decode_header(&token("second@allo.test")).unwrap().kid.as_deref(), Some("b"));
+        assert_eq!(verify_token(&first).unwrap().email, "first@allo.test");
+
+        let past = Utc::now() - Duration::seconds(1);
+        set_signing_keys(vec![key("a", Some(past)), key("b", None)], Some(past));
+        assert!(verify_token(&first).is_err());
+        assert!(verify_token(&legacy).is_err());
+
+        set_signing_keys(Vec::new(), None);
+    }
+}
@@ -0,0 +1,47 @@
+// SKUs are matched without regard to case or surrounding spaces, so they're
+// stored trimmed and upper-cased
+pub fn normalize_sku(sku: &str) -> String {
+    sku.trim().to_uppercase()
+}
+
+// A UPC or EAN as digits only, after checking its length and check digit.
+// Spaces and hyphens are allowed in the input. EAN-8, UPC-A (12), EAN-13 and
+// GTIN-14 all share the same mod-10 check digit.
+pub fn normalize_gtin(input: &str) -> Result<String, String> {
+    let digits: String = input.chars().filter(|c| !matches!(c, ' ' | '-')).collect();
+    if !digits.chars().all(|c| c.is_ascii_digit()) {
+        return Err("UPC/EAN may only contain digits".to_string());
+    }
+    if ![8, 12, 13, 14].contains(&digits.len()) {
+        return Err("UPC/EAN must be 8, 12, 13 or 14 digits long".to_string());
+    }
+
+    let values: Vec<u32> = digits.chars().filter_map(|c| c.to_digit(10)).collect();
+    let (body, check) = values.split_at(values.len() - 1);
+    // Weights alternate 3, 1, ... starting from the digit next to the check digit
+    let sum: u32 = body.iter().rev().enumerate().map(|(i, d)| if i % 2 == 0 { d * 3 } else { *d }).sum();
+    if (10 - sum % 10) % 10 != check[0] {
+        return Err("UPC/EAN check digit is wrong; check for a typo".to_string());
+    }
+    Ok(digits)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_valid_codes() {
+        assert_eq!(normalize_gtin("036000291452"), Ok("036000291452".to_string()));
+        assert_eq!(normalize_gtin("4006381-333931"), Ok("4006381333931".to_string()));
+        assert_eq!(normalize_gtin("9638 5074"), Ok("96385074".to_string()));
+        assert_eq!(normalize_sku("  ab-12c "), "AB-12C");
+    }
+
+    #[test]
+    fn rejects_bad_codes() {
+        assert!(normalize_gtin("036000291453").is_err());
+        assert!(normalize_gtin("03600029145").is_err());
+        assert!(normalize_gtin("03600029145X").is_err());
+    }
+}
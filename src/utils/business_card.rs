@@ -0,0 +1,169 @@
+use serde::Serialize;
+
+// Contact details picked out of the text read off a business card. Anything
+// not found is left empty for the user to fill in.
+#[derive(Debug, Default, PartialEq, Serialize)]
+pub struct CardDetails {
+    pub first_name: String,
+    pub last_name: String,
+    pub title: String,
+    pub company: String,
+    pub email: String,
+    pub phone: String,
+    pub mobile: String,
+}
+
+const TITLE_WORDS: &[&str] = &[
+    "account", "administrator", "advisor", "agent", "analyst", "architect", "assistant", "associate", "ceo", "cfo",
+    "consultant", "coordinator", "coo", "cto", "designer", "developer", "director", "engineer", "executive", "founder",
+    "head", "lead", "manager", "officer", "owner", "partner", "president", "principal", "representative", "sales",
+    "specialist", "supervisor", "vp",
+];
+
+const COMPANY_WORDS: &[&str] = &[
+    "ag", "bv", "co", "company", "corp", "corporation", "gmbh", "group", "inc", "industries", "limited", "llc", "ltd",
+    "plc", "pty", "solutions", "technologies",
+];
+
+// Lower-case parts of surnames, as in "van Dyke"
+const NAME_PARTICLES: &[&str] = &["da", "de", "del", "der", "di", "du", "la", "le", "van", "von"];
+
+const MOBILE_LABELS: &[&str] = &["c", "cell", "m", "mob", "mobile"];
+const FAX_LABELS: &[&str] = &["f", "fax"];
+
+// Fewest digits for something to count as a phone number
+const PHONE_MIN_DIGITS: usize = 7;
+
+fn words(line: &str) -> impl Iterator<Item = String> + '_ {
+    line.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+}
+
+fn has_word(line: &str, list: &[&str]) -> bool {
+    words(line).any(|word| list.contains(&word.as_str()))
+}
+
+fn find_email(line: &str) -> Option<String> {
+    line.split(|c: char| c.is_whitespace() || c == ',' || c == ';')
+        .map(|token| token.rsplit(':').next().unwrap_or(token))
+        .map(|token| token.trim_matches(|c: char| !c.is_alphanumeric()))
+        .find(|token| {
+            token
+                .split_once('@')
+                .is_some_and(|(user, domain)| !user.is_empty() && domain.contains('.') && !domain.contains('@'))
+        })
+        .map(str::to_lowercase)
+}
+
+// The label in front of a number, like "Tel:" or "M", and the number
+fn phone_number(line: &str) -> Option<(String, String)> {
+    let (label, number) = match line.split_once(':') {
+        Some((label, number)) => (label.trim(), number.trim()),
+        None => {
+            let start = line.find(|c: char| c.is_ascii_digit() || c == '+' || c == '(')?;
+            (line[..start].trim(), &line[start..])
+        }
+    };
+    let is_number = number
+        .chars()
+        .all(|c| c.is_ascii_digit() || c.is_whitespace() || "+-().".contains(c));
+    let digits = number.chars().filter(char::is_ascii_digit).count();
+    if !is_number || digits < PHONE_MIN_DIGITS || label.chars().any(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    Some((label.trim_end_matches('.').to_lowercase(), number.to_string()))
+}
+
+fn is_web(line: &str) -> bool {
+    let line = line.to_lowercase();
+    line.contains("www.") || line.contains("http://") || line.contains("https://")
+}
+
+// Two to four capitalized words of letters, like "Mary-Jane O'Neil"
+fn looks_like_name(line: &str) -> bool {
+    let parts: Vec<&str> = line.split_whitespace().collect();
+    (2..=4).contains(&parts.len())
+        && parts.iter().enumerate().all(|(i, part)| {
+            (part.chars().next().is_some_and(char::is_uppercase) || (i > 0 && NAME_PARTICLES.contains(part)))
+                && part.chars().all(|c| c.is_alphabetic() || "'-.".contains(c))
+        })
+        && !has_word(line, TITLE_WORDS)
+        && !has_word(line, COMPANY_WORDS)
+}
+
+pub fn parse(text: &str) -> CardDetails {
+    let lines: Vec<&str> = text.lines().map(str::trim).filter(|line| !line.is_empty()).collect();
+    let mut card = CardDetails::default();
+    let mut unused = Vec::new();
+
+    for line in &lines {
+        if let Some(email) = find_email(line) {
+            if card.email.is_empty() {
+                card.email = email;
+            }
+        } else if let Some((label, number)) = phone_number(line) {
+            if MOBILE_LABELS.contains(&label.as_str()) {
+                if card.mobile.is_empty() {
+                    card.mobile = number;
+                }
+            } else if !FAX_LABELS.contains(&label.as_str()) && card.phone.is_empty() {
+                card.phone = number;
+            }
+        } else if !is_web(line) {
+            unused.push(*line);
+        }
+    }
+
+    if let Some(index) = unused.iter().position(|line| looks_like_name(line)) {
+        let name = unused.remove(index);
+        let (first, last) = name.split_once(' ').unwrap_or((name, ""));
+        card.first_name = first.to_string();
+        card.last_name = last.trim().to_string();
+    }
+    if let Some(index) = unused.iter().position(|line| has_word(line, TITLE_WORDS)) {
+        card.title = unused.remove(index).to_string();
+    }
+    // Failing a telltale word, the first line without digits, which an
+    // address would have
+    let company = unused
+        .iter()
+        .position(|line| has_word(line, COMPANY_WORDS))
+        .or_else(|| unused.iter().position(|line| !line.chars().any(|c| c.is_ascii_digit())));
+    if let Some(index) = company {
+        card.company = unused.remove(index).to_string();
+    }
+    card
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_a_typical_card() {
+        let text = "Acme Widgets Ltd\n\nJane van Dyke\nSenior Sales Manager\n\
+                    12 High Street, Leeds LS1 4AB\nTel: +44 (0)113 496 0000\nMob: 07700 900123\n\
+                    Fax: 0113 496 0001\njane.vandyke@acme.example\nwww.acme.example\n";
+        assert_eq!(
+            parse(text),
+            CardDetails {
+                first_name: "Jane".to_string(),
+                last_name: "van Dyke".to_string(),
+                title: "Senior Sales Manager".to_string(),
+                company: "Acme Widgets Ltd".to_string(),
+                email: "jane.vandyke@acme.example".to_string(),
+                phone: "+44 (0)113 496 0000".to_string(),
+                mobile: "07700 900123".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn leaves_out_what_it_cannot_find() {
+        let card = parse("E: Bob@Example.com\n555-0100 ext\nBob Stone\n");
+        assert_eq!(card.email, "bob@example.com");
+        assert_eq!((card.first_name.as_str(), card.last_name.as_str()), ("Bob", "Stone"));
+        assert!(card.phone.is_empty() && card.title.is_empty());
+    }
+}
@@ -0,0 +1,43 @@
+// What a list shows in place of an empty table (templates/empty_state.html).
+// The handler words it, since only it knows why nothing came back: nothing
+// exists yet, the viewer can't see any of it, or the filters left nothing.
+pub struct EmptyState {
+    pub icon: &'static str,
+    pub title: String,
+    pub message: String,
+    // The first is shown as the main button
+    pub actions: Vec<EmptyStateAction>,
+}
+
+pub struct EmptyStateAction {
+    pub label: String,
+    pub href: String,
+}
+
+impl EmptyState {
+    pub fn new(icon: &'static str, title: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            icon,
+            title: title.into(),
+            message: message.into(),
+            actions: Vec::new(),
+        }
+    }
+
+    pub fn action(mut self, label: impl Into<String>, href: impl Into<String>) -> Self {
+        self.actions.push(EmptyStateAction {
+            label: label.into(),
+            href: href.into(),
+        });
+        self
+    }
+
+    // Offer the action only to those allowed to take it
+    pub fn action_if(self, allowed: bool, label: impl Into<String>, href: impl Into<String>) -> Self {
+        if allowed {
+            self.action(label, href)
+        } else {
+            self
+        }
+    }
+}
@@ -0,0 +1,269 @@
+// Tags kept when cleaning user-supplied HTML, e.g. email signatures. Anything
+// else is dropped but its text is kept.
+const ALLOWED_TAGS: &[&str] = &[
+    "a", "b", "br", "div", "em", "font", "hr", "i", "img", "p", "small", "span", "strong", "table", "tbody", "td",
+    "tr", "u",
+];
+
+// Tags dropped together with everything inside them
+const DROPPED_WITH_CONTENT: &[&str] = &[
+    "script", "style", "iframe", "object", "embed", "head", "title", "template", "noscript", "svg", "math", "form",
+    "textarea", "select",
+];
+
+const VOID_TAGS: &[&str] = &["br", "hr", "img"];
+
+// (tag, attribute) pairs kept; "*" applies to every allowed tag
+const ALLOWED_ATTRIBUTES: &[(&str, &str)] = &[
+    ("a", "href"),
+    ("a", "title"),
+    ("img", "src"),
+    ("img", "alt"),
+    ("img", "width"),
+    ("img", "height"),
+    ("font", "color"),
+    ("td", "colspan"),
+    ("*", "style"),
+];
+
+fn escape_text(text: &str) -> String {
+    text.replace('<', "&lt;").replace('>', "&gt;")
+}
+
+fn escape_attribute(value: &str) -> String {
+    value.replace('"', "&quot;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+// Links may only point at web pages or mail addresses, and images may also be
+// embedded. Entity-encoded or obfuscated schemes never start with these.
+fn safe_url(tag: &str, url: &str) -> bool {
+    let url = url.trim().to_ascii_lowercase();
+    let allowed: &[&str] = match tag {
+        "img" => &["https://", "http://", "data:image/png;", "data:image/jpeg;", "data:image/gif;"],
+        _ => &["https://", "http://", "mailto:"],
+    };
+    allowed.iter().any(|prefix| url.starts_with(prefix))
+}
+
+// CSS properties kept in inline styles; enough for signature formatting
+const ALLOWED_STYLE_PROPERTIES: &[&str] = &[
+    "color", "background-color", "font-family", "font-size", "font-style", "font-weight", "text-align",
+    "text-decoration", "vertical-align", "line-height", "width", "height", "max-width", "margin", "margin-top",
+    "margin-right", "margin-bottom", "margin-left", "padding", "padding-top", "padding-right", "padding-bottom",
+    "padding-left", "border", "border-top", "border-right", "border-bottom", "border-left", "border-collapse",
+];
+
+// Functions a style value may call, for colors
+const ALLOWED_STYLE_FUNCTIONS: &[&str] = &["rgb", "rgba"];
+
+// A declaration value made of plain words, numbers, units and colors. CSS
+// escapes, comments and url() and friends all need characters or functions
+// outside this set, so they can't sneak through in disguise.
+fn safe_style_value(value: &str) -> bool {
+    let allowed_char =
+        |c: char| c.is_ascii_alphanumeric() || matches!(c, ' ' | '#' | '.' | ',' | '%' | '-' | '\'' | '(' | ')');
+    if value.is_empty() || !value.chars().all(allowed_char) {
+        return false;
+    }
+    value.match_indices('(').all(|(index, _)| {
+        let function = value[..index].rsplit(|c: char| !c.is_ascii_alphabetic()).next().unwrap_or("");
+        ALLOWED_STYLE_FUNCTIONS.contains(&function.to_ascii_lowercase().as_str())
+    })
+}
+
+// Inline styles reduced to allowed property:value declarations, or None when
+// nothing is left. Anything that could load a resource or, in old browsers,
+// evaluate an expression is dropped.
+fn safe_style(style: &str) -> Option<String> {
+    let declarations: Vec<String> = style
+        .split(';')
+        .filter_map(|declaration| {
+            let (property, value) = declaration.split_once(':')?;
+            let property = property.trim().to_ascii_lowercase();
+            let value = value.trim();
+            (ALLOWED_STYLE_PROPERTIES.contains(&property.as_str()) && safe_style_value(value))
+                .then(|| format!("{}: {}", property, value))
+        })
+        .collect();
+    (!declarations.is_empty()).then(|| declarations.join("; "))
+}
+
+// Attribute name/value pairs of a tag body such as `a href="x" title=y`
+fn parse_attributes(mut rest: &str) -> Vec<(String, String)> {
+    let mut attributes = Vec::new();
+    loop {
+        rest = rest.trim_start_matches(|c: char| c.is_whitespace() || c == '/');
+        if rest.is_empty() {
+            return attributes;
+        }
+
+        let name_end = rest.find(|c: char| c.is_whitespace() || c == '=' || c == '/').unwrap_or(rest.len());
+        let name = rest[..name_end].to_ascii_lowercase();
+        rest = rest[name_end..].trim_start();
+
+        let mut value = String::new();
+        if let Some(after) = rest.strip_prefix('=') {
+            let after = after.trim_start();
+            let (parsed, remaining) = match after.chars().next() {
+                Some(quote @ ('"' | '\'')) => {
+                    let body = &after[1..];
+                    let end = body.find(quote).unwrap_or(body.len());
+                    (&body[..end], body.get(end + 1..).unwrap_or(""))
+                }
+                _ => {
+                    let end = after.find(char::is_whitespace).unwrap_or(after.len());
+                    (&after[..end], &after[end..])
+                }
+            };
+            value = parsed.to_string();
+            rest = remaining;
+        }
+        attributes.push((name, value));
+    }
+}
+
+// Index just past the `>` closing a tag that starts at `start`, skipping any
+// `>` inside quoted attribute values
+fn tag_end(input: &str, start: usize) -> Option<usize> {
+    let mut quote = None;
+    for (offset, c) in input[start..].char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), _) if c == q => quote = None,
+            (None, '>') => return Some(start + offset + 1),
+            _ => {}
+        }
+    }
+    None
+}
+
+// Reduce untrusted HTML to a small set of formatting tags and safe attributes.
+// Unclosed tags are closed at the end so the result can't swallow whatever
+// it's embedded in.
+pub fn sanitize_html(input: &str) -> String {
+    let mut output = String::with_capacity(input.len());
+    let mut open: Vec<String> = Vec::new();
+    let mut position = 0;
+
+    while let Some(found) = input[position..].find('<') {
+        let start = position + found;
+        output.push_str(&escape_text(&input[position..start]));
+
+        if input[start..].starts_with("<!--") {
+            position = input[start..].find("-->").map(|end| start + end + 3).unwrap_or(input.len());
+            continue;
+        }
+
+        let Some(end) = tag_end(input, start) else {
+            output.push_str(&escape_text(&input[start..]));
+            position = input.len();
+            break;
+        };
+        position = end;
+
+        let body = input[start + 1..end - 1].trim();
+        let (closing, body) = match body.strip_prefix('/') {
+            Some(body) => (true, body.trim_start()),
+            None => (false, body),
+        };
+        let name_end = body.find(|c: char| c.is_whitespace() || c == '/').unwrap_or(body.len());
+        let name = body[..name_end].to_ascii_lowercase();
+
+        if !closing && DROPPED_WITH_CONTENT.contains(&name.as_str()) {
+            let close = format!("</{}", name);
+            position = input[position..]
+                .to_ascii_lowercase()
+                .find(&close)
+                .and_then(|offset| tag_end(input, position + offset))
+                .unwrap_or(input.len());
+            continue;
+        }
+        if !ALLOWED_TAGS.contains(&name.as_str()) {
+            continue;
+        }
+
+        if closing {
+            if let Some(index) = open.iter().rposition(|tag| *tag == name) {
+                for tag in open.drain(index..).rev() {
+                    output.push_str(&format!("</{}>", tag));
+                }
+            }
+            continue;
+        }
+
+        output.push('<');
+        output.push_str(&name);
+        for (attribute, value) in parse_attributes(&body[name_end..]) {
+            let allowed = ALLOWED_ATTRIBUTES
+                .iter()
+                .any(|(tag, allowed)| (*tag == name || *tag == "*") && *allowed == attribute);
+            if !allowed {
+                continue;
+            }
+            let value = match attribute.as_str() {
+                "href" | "src" => safe_url(&name, &value).then_some(value),
+                "style" => safe_style(&value),
+                _ => Some(value),
+            };
+            if let Some(value) = value {
+                output.push_str(&format!(r#" {}="{}""#, attribute, escape_attribute(&value)));
+            }
+        }
+        if name == "a" {
+            output.push_str(r#" rel="noopener noreferrer""#);
+        }
+        output.push('>');
+
+        if !VOID_TAGS.contains(&name.as_str()) {
+            open.push(name);
+        }
+    }
+
+    output.push_str(&escape_text(&input[position..]));
+    for tag in open.iter().rev() {
+        output.push_str(&format!("</{}>", tag));
+    }
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_formatting_and_links() {
+        assert_eq!(
+            sanitize_html(r#"<p><b>Ann</b><br>Sales<br/><a href="https://allo.test" target="_blank">allo.test</a></p>"#),
+            r#"<p><b>Ann</b><br>Sales<br><a href="https://allo.test" rel="noopener noreferrer">allo.test</a></p>"#
+        );
+    }
+
+    #[test]
+    fn drops_scripts_and_handlers() {
+        assert_eq!(sanitize_html("Hi<script>alert(1)</script> there"), "Hi there");
+        assert_eq!(sanitize_html(r#"<img src="x" onerror="alert(1)">"#), "<img>");
+        assert_eq!(
+            sanitize_html(r#"<a href=" JavaScript:alert(1)">x</a>"#),
+            r#"<a rel="noopener noreferrer">x</a>"#
+        );
+        assert_eq!(sanitize_html(r#"<span style="background:url(https://t.test)">x</span>"#), "<span>x</span>");
+    }
+
+    #[test]
+    fn keeps_only_allowed_styles() {
+        assert_eq!(
+            sanitize_html(r#"<span style="Color: rgb(0, 0, 255); position: fixed; font-size:12px">x</span>"#),
+            r#"<span style="color: rgb(0, 0, 255); font-size: 12px">x</span>"#
+        );
+        assert_eq!(sanitize_html(r#"<span style="background-color: u\72 l(https://t.test)">x</span>"#), "<span>x</span>");
+        assert_eq!(sanitize_html(r#"<span style="color: e/**/xpression(alert(1))">x</span>"#), "<span>x</span>");
+        assert_eq!(sanitize_html(r#"<span style="b\61 ckground: red">x</span>"#), "<span>x</span>");
+    }
+
+    #[test]
+    fn closes_what_it_opens() {
+        assert_eq!(sanitize_html("<div><b>bold"), "<div><b>bold</b></div>");
+        assert_eq!(sanitize_html("<b>a</div>b</b>"), "<b>ab</b>");
+        assert_eq!(sanitize_html("1 < 2 <"), "1 &lt; 2 &lt;");
+    }
+}
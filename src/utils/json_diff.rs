@@ -0,0 +1,84 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde_json::Value;
+
+// One field of a recorded change. Either side is None when the field wasn't
+// in that snapshot, like every old value of a create.
+#[derive(Debug, PartialEq)]
+pub struct FieldChange {
+    pub field: String,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+impl FieldChange {
+    pub fn changed(&self) -> bool {
+        self.old != self.new
+    }
+}
+
+// Lines up the top-level fields of two snapshots by name. A snapshot that
+// isn't an object is shown as a single "value" field.
+pub fn diff(old: Option<&Value>, new: Option<&Value>) -> Vec<FieldChange> {
+    let old = fields(old);
+    let new = fields(new);
+    let names: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+
+    names
+        .into_iter()
+        .map(|name| FieldChange {
+            field: name.clone(),
+            old: old.get(name).map(|value| render(value)),
+            new: new.get(name).map(|value| render(value)),
+        })
+        .collect()
+}
+
+fn fields(snapshot: Option<&Value>) -> BTreeMap<String, &Value> {
+    match snapshot {
+        None | Some(Value::Null) => BTreeMap::new(),
+        Some(Value::Object(map)) => map.iter().map(|(name, value)| (name.clone(), value)).collect(),
+        Some(other) => BTreeMap::from([("value".to_string(), other)]),
+    }
+}
+
+// Strings without their quotes, anything else as JSON
+fn render(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn lines_up_fields_from_both_sides() {
+        let old = json!({"name": "Acme", "status": "active", "tags": ["a"]});
+        let new = json!({"name": "Acme", "status": "inactive", "owner": null});
+        let changes = diff(Some(&old), Some(&new));
+
+        let names: Vec<&str> = changes.iter().map(|change| change.field.as_str()).collect();
+        assert_eq!(names, ["name", "owner", "status", "tags"]);
+        assert!(!changes[0].changed());
+        assert_eq!(changes[1].old, None);
+        assert_eq!(changes[1].new.as_deref(), Some("null"));
+        assert_eq!(changes[2].old.as_deref(), Some("active"));
+        assert_eq!(changes[2].new.as_deref(), Some("inactive"));
+        assert_eq!(changes[3].old.as_deref(), Some("[\"a\"]"));
+        assert_eq!(changes[3].new, None);
+    }
+
+    #[test]
+    fn handles_missing_and_scalar_snapshots() {
+        let changes = diff(None, Some(&json!(42)));
+        assert_eq!(
+            changes,
+            [FieldChange { field: "value".to_string(), old: None, new: Some("42".to_string()) }]
+        );
+        assert!(diff(Some(&Value::Null), None).is_empty());
+    }
+}
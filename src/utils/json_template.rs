@@ -0,0 +1,142 @@
+use serde_json::Value;
+
+// Small templating language for webhook payloads. The template is JSON text
+// with placeholders resolved against a context object:
+//
+//   {{ data.company_name }}         the value as a JSON literal (strings are
+//                                    quoted, missing fields become null)
+//   "Won {{ data.title | text }}"   the value escaped for use inside a string
+//
+// Paths are dot-separated; numeric segments index into arrays.
+pub fn render(template: &str, context: &Value) -> Result<String, String> {
+    let mut output = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find("{{") {
+        output.push_str(&rest[..start]);
+        let after = &rest[start + 2..];
+        let end = after
+            .find("}}")
+            .ok_or_else(|| "A '{{' placeholder is never closed with '}}'.".to_string())?;
+
+        let expression = after[..end].trim();
+        let (path, filter) = match expression.split_once('|') {
+            Some((path, filter)) => (path.trim(), Some(filter.trim())),
+            None => (expression, None),
+        };
+        if path.is_empty() {
+            return Err("Empty '{{ }}' placeholder.".to_string());
+        }
+
+        let value = lookup(context, path);
+        match filter {
+            None | Some("json") => output.push_str(&value.to_string()),
+            Some("text") => output.push_str(&text(&value)),
+            Some(other) => return Err(format!("Unknown filter '{}' in '{{{{ {} }}}}'.", other, expression)),
+        }
+
+        rest = &after[end + 2..];
+    }
+    output.push_str(rest);
+
+    Ok(output)
+}
+
+// Renders against `context` and checks the result is valid JSON
+pub fn validate(template: &str, context: &Value) -> Result<(), String> {
+    let rendered = render(template, context)?;
+    serde_json::from_str::<Value>(&rendered)
+        .map(|_| ())
+        .map_err(|e| format!("The rendered payload is not valid JSON: {}", e))
+}
+
+// Every dotted path in `context` that a template can refer to, for the editor
+pub fn paths(context: &Value) -> Vec<String> {
+    let mut paths = Vec::new();
+    collect_paths(context, String::new(), &mut paths);
+    paths
+}
+
+fn collect_paths(value: &Value, prefix: String, paths: &mut Vec<String>) {
+    match value {
+        Value::Object(map) => {
+            for (key, child) in map {
+                let path = if prefix.is_empty() { key.clone() } else { format!("{}.{}", prefix, key) };
+                collect_paths(child, path, paths);
+            }
+        }
+        _ if !prefix.is_empty() => paths.push(prefix),
+        _ => {}
+    }
+}
+
+fn lookup(context: &Value, path: &str) -> Value {
+    let mut current = context;
+    for segment in path.split('.') {
+        let next = match current {
+            Value::Object(map) => map.get(segment),
+            Value::Array(items) => segment.parse::<usize>().ok().and_then(|i| items.get(i)),
+            _ => None,
+        };
+        match next {
+            Some(value) => current = value,
+            None => return Value::Null,
+        }
+    }
+    current.clone()
+}
+
+// The value's text with JSON string escaping, without surrounding quotes
+fn text(value: &Value) -> String {
+    let raw = match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        other => other.to_string(),
+    };
+    let quoted = Value::String(raw).to_string();
+    quoted[1..quoted.len() - 1].to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn context() -> Value {
+        json!({
+            "event": "deal.won",
+            "data": { "title": "Q3 \"renewal\"\nphase 2", "value": 1200.5, "tags": ["vip", "eu"] }
+        })
+    }
+
+    #[test]
+    fn substitutes_placeholders() {
+        assert_eq!(
+            render(r#"{"e": {{ event }}, "v": {{data.value}}, "t": {{ data.tags.1 }}}"#, &context()).unwrap(),
+            r#"{"e": "deal.won", "v": 1200.5, "t": "eu"}"#
+        );
+        assert_eq!(render(r#""{{ event | text }} at {{ data.value | text }}""#, &context()).unwrap(), r#""deal.won at 1200.5""#);
+    }
+
+    #[test]
+    fn missing_keys_are_null_or_empty() {
+        assert_eq!(render("{{ data.owner.name }}", &context()).unwrap(), "null");
+        assert_eq!(render("{{ data.tags.9 }}", &context()).unwrap(), "null");
+        assert_eq!(render(r#""by {{ data.owner | text }}""#, &context()).unwrap(), r#""by ""#);
+    }
+
+    #[test]
+    fn escapes_interpolated_values() {
+        let rendered = render(r#"{"a": {{ data.title }}, "b": "Won {{ data.title | text }}"}"#, &context()).unwrap();
+        assert_eq!(rendered, r#"{"a": "Q3 \"renewal\"\nphase 2", "b": "Won Q3 \"renewal\"\nphase 2"}"#);
+        assert!(validate(r#"{"b": "Won {{ data.title | text }}"}"#, &context()).is_ok());
+    }
+
+    #[test]
+    fn rejects_bad_placeholders() {
+        assert!(render("{{ event", &context()).is_err());
+        assert!(render("{{ }}", &context()).is_err());
+        assert!(render("{{ event | upper }}", &context()).is_err());
+        assert!(validate("{{ event | text }}", &context()).is_err());
+    }
+}
@@ -0,0 +1,206 @@
+use chrono::NaiveDate;
+use rust_decimal::Decimal;
+
+// Languages documents can be printed in: (code, name in that language)
+pub const LANGUAGES: &[(&str, &str)] = &[
+    ("en", "English"),
+    ("es", "Español"),
+    ("fr", "Français"),
+    ("de", "Deutsch"),
+];
+
+pub struct Locale {
+    pub code: &'static str,
+    decimal_separator: char,
+    thousands_separator: char,
+    date_format: &'static str,
+    catalog: &'static [(&'static str, &'static str)],
+}
+
+// Every key is in English; the other catalogs fall back to it for anything missing
+const ENGLISH: &[(&str, &str)] = &[
+    ("quote", "Quotation"),
+    ("invoice", "Invoice"),
+    ("sales_order", "Sales Order"),
+    ("purchase_order", "Purchase Order"),
+    ("rma", "Return Authorization"),
+    ("customer", "Customer"),
+    ("your_reference", "Your reference"),
+    ("number", "No."),
+    ("date", "Date"),
+    ("currency", "Currency"),
+    ("exchange_rate", "Exchange rate"),
+    ("total", "Total"),
+    ("terms", "Terms"),
+    ("print", "Print / Save as PDF"),
+    ("sku", "SKU"),
+    ("customer_part_number", "Your part #"),
+    ("description", "Description"),
+    ("quantity", "Quantity"),
+    ("unit_price", "Unit price"),
+    ("line_total", "Amount"),
+    ("delivery_window", "Blanket order for delivery between {from} and {to}."),
+];
+
+const SPANISH: &[(&str, &str)] = &[
+    ("quote", "Presupuesto"),
+    ("invoice", "Factura"),
+    ("sales_order", "Pedido de venta"),
+    ("purchase_order", "Orden de compra"),
+    ("rma", "Autorización de devolución"),
+    ("customer", "Cliente"),
+    ("your_reference", "Su referencia"),
+    ("number", "N.º"),
+    ("date", "Fecha"),
+    ("currency", "Moneda"),
+    ("exchange_rate", "Tipo de cambio"),
+    ("total", "Total"),
+    ("terms", "Condiciones"),
+    ("print", "Imprimir / Guardar como PDF"),
+    ("sku", "Código"),
+    ("customer_part_number", "Su referencia de pieza"),
+    ("description", "Descripción"),
+    ("quantity", "Cantidad"),
+    ("unit_price", "Precio unitario"),
+    ("line_total", "Importe"),
+    ("delivery_window", "Pedido abierto con entregas entre el {from} y el {to}."),
+];
+
+const FRENCH: &[(&str, &str)] = &[
+    ("quote", "Devis"),
+    ("invoice", "Facture"),
+    ("sales_order", "Commande client"),
+    ("purchase_order", "Bon de commande"),
+    ("rma", "Autorisation de retour"),
+    ("customer", "Client"),
+    ("your_reference", "Votre référence"),
+    ("number", "N°"),
+    ("date", "Date"),
+    ("currency", "Devise"),
+    ("exchange_rate", "Taux de change"),
+    ("total", "Total"),
+    ("terms", "Conditions"),
+    ("print", "Imprimer / Enregistrer en PDF"),
+    ("sku", "Référence"),
+    ("customer_part_number", "Votre référence article"),
+    ("description", "Désignation"),
+    ("quantity", "Quantité"),
+    ("unit_price", "Prix unitaire"),
+    ("line_total", "Montant"),
+    ("delivery_window", "Commande cadre livrable entre le {from} et le {to}."),
+];
+
+const GERMAN: &[(&str, &str)] = &[
+    ("quote", "Angebot"),
+    ("invoice", "Rechnung"),
+    ("sales_order", "Auftragsbestätigung"),
+    ("purchase_order", "Bestellung"),
+    ("rma", "Rücksendegenehmigung"),
+    ("customer", "Kunde"),
+    ("your_reference", "Ihre Referenz"),
+    ("number", "Nr."),
+    ("date", "Datum"),
+    ("currency", "Währung"),
+    ("exchange_rate", "Wechselkurs"),
+    ("total", "Gesamt"),
+    ("terms", "Bedingungen"),
+    ("print", "Drucken / Als PDF speichern"),
+    ("sku", "Artikelnr."),
+    ("customer_part_number", "Ihre Artikelnr."),
+    ("description", "Bezeichnung"),
+    ("quantity", "Menge"),
+    ("unit_price", "Einzelpreis"),
+    ("line_total", "Betrag"),
+    ("delivery_window", "Rahmenauftrag mit Lieferung zwischen {from} und {to}."),
+];
+
+static LOCALES: &[Locale] = &[
+    Locale { code: "en", decimal_separator: '.', thousands_separator: ',', date_format: "%m/%d/%Y", catalog: ENGLISH },
+    Locale { code: "es", decimal_separator: ',', thousands_separator: '.', date_format: "%d/%m/%Y", catalog: SPANISH },
+    Locale { code: "fr", decimal_separator: ',', thousands_separator: '\u{a0}', date_format: "%d/%m/%Y", catalog: FRENCH },
+    Locale { code: "de", decimal_separator: ',', thousands_separator: '.', date_format: "%d.%m.%Y", catalog: GERMAN },
+];
+
+// The locale for a language code, English when there's none or it's unknown
+pub fn locale(code: Option<&str>) -> &'static Locale {
+    code.and_then(|code| LOCALES.iter().find(|locale| locale.code == code))
+        .unwrap_or(&LOCALES[0])
+}
+
+pub fn is_supported(code: &str) -> bool {
+    LANGUAGES.iter().any(|(key, _)| *key == code)
+}
+
+impl Locale {
+    // Untranslated keys come back as the key itself so they're easy to spot
+    pub fn text<'a>(&self, key: &'a str) -> &'a str {
+        let lookup = |catalog: &'static [(&'static str, &'static str)]| {
+            catalog.iter().find(|(k, _)| *k == key).map(|(_, text)| *text)
+        };
+        lookup(self.catalog).or_else(|| lookup(ENGLISH)).unwrap_or(key)
+    }
+
+    pub fn date(&self, date: NaiveDate) -> String {
+        date.format(self.date_format).to_string()
+    }
+
+    // To two places with the locale's separators: 1234.5 -> "1.234,50" in German
+    pub fn amount(&self, value: Decimal) -> String {
+        self.decimal(value, 2)
+    }
+
+    pub fn decimal(&self, value: Decimal, places: u32) -> String {
+        let plain = format!("{:.*}", places as usize, value.round_dp(places));
+        match plain.split_once('.') {
+            Some((whole, fraction)) => format!("{}{}{}", self.group(whole), self.decimal_separator, fraction),
+            None => self.group(&plain),
+        }
+    }
+
+    pub fn integer(&self, value: i64) -> String {
+        self.group(&value.to_string())
+    }
+
+    fn group(&self, digits: &str) -> String {
+        let (sign, digits) = match digits.strip_prefix('-') {
+            Some(rest) => ("-", rest),
+            None => ("", digits),
+        };
+        let mut grouped = String::new();
+        for (i, c) in digits.chars().enumerate() {
+            if i > 0 && (digits.len() - i) % 3 == 0 {
+                grouped.push(self.thousands_separator);
+            }
+            grouped.push(c);
+        }
+        format!("{}{}", sign, grouped)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn formats_numbers_and_dates_per_locale() {
+        let value = Decimal::new(123456789, 2);
+        assert_eq!(locale(Some("en")).amount(value), "1,234,567.89");
+        assert_eq!(locale(Some("de")).amount(value), "1.234.567,89");
+        assert_eq!(locale(Some("fr")).amount(Decimal::new(-5, 1)), "-0,50");
+        assert_eq!(locale(Some("es")).integer(1500), "1.500");
+        assert_eq!(locale(Some("en")).integer(999), "999");
+        assert_eq!(locale(Some("fr")).decimal(Decimal::new(7305, 4), 4), "0,7305");
+
+        let date = NaiveDate::from_ymd_opt(2026, 3, 9).unwrap();
+        assert_eq!(locale(Some("en")).date(date), "03/09/2026");
+        assert_eq!(locale(Some("de")).date(date), "09.03.2026");
+    }
+
+    #[test]
+    fn falls_back_to_english() {
+        assert_eq!(locale(None).code, "en");
+        assert_eq!(locale(Some("xx")).code, "en");
+        assert_eq!(locale(Some("de")).text("invoice"), "Rechnung");
+        assert_eq!(locale(Some("de")).text("no_such_key"), "no_such_key");
+    }
+}
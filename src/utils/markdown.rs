@@ -0,0 +1,67 @@
+use pulldown_cmark::{html, Event, Options, Parser, Tag, TagEnd};
+
+// Same schemes sanitize_html allows in links
+fn safe_url(url: &str) -> bool {
+    let url = url.trim().to_ascii_lowercase();
+    ["https://", "http://", "mailto:"].iter().any(|prefix| url.starts_with(prefix))
+}
+
+// Render user-written markdown to HTML that's safe to embed. Raw HTML is shown
+// as typed, links with other schemes keep only their text, and images become
+// links so viewing a note doesn't load anything. Line breaks are kept as
+// typed, the way people expect from a text box.
+pub fn render_markdown(input: &str) -> String {
+    let options = Options::ENABLE_STRIKETHROUGH | Options::ENABLE_TABLES | Options::ENABLE_TASKLISTS;
+    // Whether each open link or image was kept, to match up its end
+    let mut kept: Vec<bool> = Vec::new();
+
+    let events = Parser::new_ext(input, options).filter_map(|event| match event {
+        Event::Html(text) | Event::InlineHtml(text) => Some(Event::Text(text)),
+        Event::SoftBreak => Some(Event::HardBreak),
+        Event::Start(Tag::Link { link_type, dest_url, title, id })
+        | Event::Start(Tag::Image { link_type, dest_url, title, id }) => {
+            let safe = safe_url(&dest_url);
+            kept.push(safe);
+            safe.then_some(Event::Start(Tag::Link { link_type, dest_url, title, id }))
+        }
+        Event::End(TagEnd::Link) | Event::End(TagEnd::Image) => {
+            kept.pop().unwrap_or(false).then_some(Event::End(TagEnd::Link))
+        }
+        other => Some(other),
+    });
+
+    let mut output = String::with_capacity(input.len() * 3 / 2);
+    html::push_html(&mut output, events);
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn renders_formatting() {
+        assert_eq!(
+            render_markdown("**Call** back\nnext _week_\n\n- [docs](https://allo.test)"),
+            "<p><strong>Call</strong> back<br />\nnext <em>week</em></p>\n<ul>\n<li><a href=\"https://allo.test\">docs</a></li>\n</ul>\n"
+        );
+    }
+
+    #[test]
+    fn escapes_raw_html() {
+        assert_eq!(
+            render_markdown("Hi <script>alert(1)</script>"),
+            "<p>Hi &lt;script&gt;alert(1)&lt;/script&gt;</p>\n"
+        );
+        assert_eq!(render_markdown("<img src=x onerror=alert(1)>"), "&lt;img src=x onerror=alert(1)&gt;");
+    }
+
+    #[test]
+    fn drops_unsafe_links_and_images() {
+        assert_eq!(render_markdown("[x](javascript:alert(1))"), "<p>x</p>\n");
+        assert_eq!(
+            render_markdown("![chart](https://allo.test/c.png)"),
+            "<p><a href=\"https://allo.test/c.png\">chart</a></p>\n"
+        );
+    }
+}
@@ -1,5 +1,21 @@
+pub mod api_key;
+pub mod empty_state;
+pub mod html;
+pub mod json_diff;
+pub mod json_template;
+pub mod locale;
+pub mod markdown;
 pub mod auth;
+pub mod barcode;
+pub mod business_card;
+pub mod paging;
 pub mod password;
+pub mod pivot;
+pub mod rate_limit;
+pub mod request;
+pub mod search_terms;
+pub mod timezone;
+pub mod xlsx;
 
 pub use auth::*;
 pub use password::*;
\ No newline at end of file
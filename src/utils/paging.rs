@@ -0,0 +1,184 @@
+// Paging and sorting for list pages. Each list names the columns it can be
+// sorted by; anything else in the query string falls back to the first.
+
+pub const DEFAULT_PER_PAGE: i64 = 25;
+pub const PER_PAGE_OPTIONS: &[i64] = &[25, 50, 100];
+
+pub struct SortColumn {
+    // What the query string calls it
+    pub key: &'static str,
+    // The ORDER BY expression, never taken from the request
+    pub column: &'static str,
+    // Which way it sorts when first picked, e.g. newest first for dates
+    pub descending: bool,
+}
+
+pub struct PageRequest {
+    pub page: i64,
+    pub per_page: i64,
+    sort: &'static SortColumn,
+    descending: bool,
+}
+
+impl PageRequest {
+    pub fn new(
+        page: Option<i64>,
+        per_page: Option<i64>,
+        sort: Option<&str>,
+        dir: Option<&str>,
+        columns: &'static [SortColumn],
+    ) -> Self {
+        let sort = columns
+            .iter()
+            .find(|column| Some(column.key) == sort)
+            .unwrap_or(&columns[0]);
+        let descending = match dir {
+            Some("asc") => false,
+            Some("desc") => true,
+            _ => sort.descending,
+        };
+        let per_page = per_page
+            .filter(|per_page| PER_PAGE_OPTIONS.contains(per_page))
+            .unwrap_or(DEFAULT_PER_PAGE);
+        Self { page: page.unwrap_or(1).max(1), per_page, sort, descending }
+    }
+
+    // Pulls a page past the end back to the last one, once the total is known
+    pub fn within(mut self, total: i64) -> Self {
+        self.page = self.page.min(page_count(total, self.per_page));
+        self
+    }
+
+    // Ends with `tiebreak` so rows with equal sort values keep their place
+    // from one page to the next
+    pub fn order_by(&self, tiebreak: &str) -> String {
+        let dir = if self.descending { "DESC" } else { "ASC" };
+        format!("ORDER BY {} {}, {} {}", self.sort.column, dir, tiebreak, dir)
+    }
+
+    pub fn limit(&self) -> i64 {
+        self.per_page
+    }
+
+    pub fn offset(&self) -> i64 {
+        (self.page - 1) * self.per_page
+    }
+
+    // `filter_query` is the list's filters as `name=value&` pairs, kept on
+    // every page and sort link
+    pub fn pagination(&self, total: i64, filter_query: String) -> Pagination {
+        Pagination {
+            page: self.page,
+            per_page: self.per_page,
+            pages: page_count(total, self.per_page),
+            total,
+            sort: self.sort.key,
+            descending: self.descending,
+            filter_query,
+        }
+    }
+}
+
+fn page_count(total: i64, per_page: i64) -> i64 {
+    ((total + per_page - 1) / per_page).max(1)
+}
+
+// What a list template needs for its sort headers and page links
+pub struct Pagination {
+    pub page: i64,
+    pub per_page: i64,
+    pub pages: i64,
+    pub total: i64,
+    pub sort: &'static str,
+    pub descending: bool,
+    pub filter_query: String,
+}
+
+impl Pagination {
+    pub fn has_previous(&self) -> bool {
+        self.page > 1
+    }
+
+    pub fn has_next(&self) -> bool {
+        self.page < self.pages
+    }
+
+    pub fn first_row(&self) -> i64 {
+        if self.total == 0 { 0 } else { (self.page - 1) * self.per_page + 1 }
+    }
+
+    pub fn last_row(&self) -> i64 {
+        (self.page * self.per_page).min(self.total)
+    }
+
+    pub fn is_per_page(&self, option: &i64) -> bool {
+        self.per_page == *option
+    }
+
+    pub fn previous_query(&self) -> String {
+        self.page_query(self.page - 1)
+    }
+
+    pub fn next_query(&self) -> String {
+        self.page_query(self.page + 1)
+    }
+
+    // The query string for another page of the same list
+    fn page_query(&self, page: i64) -> String {
+        format!(
+            "{}sort={}&dir={}&per_page={}&page={}",
+            self.filter_query,
+            self.sort,
+            if self.descending { "desc" } else { "asc" },
+            self.per_page,
+            page
+        )
+    }
+
+    // The query string a column header links to. Picking the current column
+    // again flips it; other columns start from the first page.
+    pub fn sort_query(&self, key: &str) -> String {
+        let dir = match (key == self.sort, self.descending) {
+            (true, true) => "asc",
+            (true, false) => "desc",
+            // Left to the column's own default
+            (false, _) => "",
+        };
+        format!("{}sort={}&dir={}&per_page={}", self.filter_query, key, dir, self.per_page)
+    }
+
+    // The arrow beside a header, if the list is sorted by it
+    pub fn sort_marker(&self, key: &str) -> &'static str {
+        match (key == self.sort, self.descending) {
+            (true, true) => "↓",
+            (true, false) => "↑",
+            (false, _) => "",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    static COLUMNS: &[SortColumn] = &[
+        SortColumn { key: "created_at", column: "c.created_at", descending: true },
+        SortColumn { key: "name", column: "c.company_name", descending: false },
+    ];
+
+    #[test]
+    fn falls_back_on_unknown_input() {
+        let list = PageRequest::new(Some(0), Some(1000), Some("password; --"), Some("sideways"), COLUMNS);
+        assert_eq!((list.page, list.limit(), list.offset()), (1, DEFAULT_PER_PAGE, 0));
+        assert_eq!(list.order_by("c.id"), "ORDER BY c.created_at DESC, c.id DESC");
+
+        let list = PageRequest::new(Some(9), Some(50), Some("name"), None, COLUMNS).within(120);
+        assert_eq!((list.page, list.offset()), (3, 100));
+        assert_eq!(list.order_by("c.id"), "ORDER BY c.company_name ASC, c.id ASC");
+
+        let pagination = list.pagination(120, "status=active&".to_string());
+        assert_eq!((pagination.first_row(), pagination.last_row()), (101, 120));
+        assert!(!pagination.has_next());
+        assert_eq!(pagination.sort_query("name"), "status=active&sort=name&dir=desc&per_page=50");
+    }
+}
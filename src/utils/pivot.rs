@@ -0,0 +1,84 @@
+use rust_decimal::Decimal;
+use std::{collections::HashMap, hash::Hash};
+
+// A cross-tab of (row key, row label, column, value) records. Rows are kept
+// apart by key, so two owners sharing a name stay two rows, and sorted by
+// label. Columns keep the order they are given in so empty periods still show.
+pub struct Pivot {
+    pub row_labels: Vec<String>,
+    pub column_labels: Vec<String>,
+    pub cells: Vec<Vec<Decimal>>,
+    pub row_totals: Vec<Decimal>,
+    pub column_totals: Vec<Decimal>,
+    pub grand_total: Decimal,
+}
+
+impl Pivot {
+    // Records whose column is not in `columns` are ignored
+    pub fn build<K, I>(records: I, columns: Vec<String>) -> Self
+    where
+        K: Eq + Hash + Ord,
+        I: IntoIterator<Item = (K, String, String, Decimal)>,
+    {
+        let column_index: HashMap<&str, usize> = columns
+            .iter()
+            .enumerate()
+            .map(|(index, label)| (label.as_str(), index))
+            .collect();
+
+        let mut rows: HashMap<K, (String, Vec<Decimal>)> = HashMap::new();
+        for (key, label, column, value) in records {
+            if let Some(&col) = column_index.get(column.as_str()) {
+                rows.entry(key).or_insert_with(|| (label, vec![Decimal::ZERO; columns.len()])).1[col] += value;
+            }
+        }
+
+        let mut rows: Vec<(K, (String, Vec<Decimal>))> = rows.into_iter().collect();
+        rows.sort_by(|(a_key, (a_label, _)), (b_key, (b_label, _))| a_label.cmp(b_label).then(a_key.cmp(b_key)));
+        let (row_labels, cells): (Vec<String>, Vec<Vec<Decimal>>) = rows.into_iter().map(|(_, row)| row).unzip();
+
+        let row_totals: Vec<Decimal> = cells.iter().map(|row| row.iter().sum()).collect();
+        let column_totals: Vec<Decimal> = (0..columns.len())
+            .map(|col| cells.iter().map(|row| row[col]).sum())
+            .collect();
+        let grand_total = row_totals.iter().sum();
+
+        Self {
+            row_labels,
+            column_labels: columns,
+            cells,
+            row_totals,
+            column_totals,
+            grand_total,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_namesakes_apart_and_sums_exactly() {
+        let months = vec!["2026-01".to_string(), "2026-02".to_string()];
+        let record = |key: u8, label: &str, month: &str, value: &str| {
+            (key, label.to_string(), month.to_string(), value.parse::<Decimal>().unwrap())
+        };
+        let pivot = Pivot::build(
+            vec![
+                record(2, "Sam Lee", "2026-01", "0.10"),
+                record(1, "Sam Lee", "2026-01", "0.20"),
+                record(2, "Sam Lee", "2026-02", "0.20"),
+                record(3, "Ann Roe", "2026-02", "5.00"),
+                record(3, "Ann Roe", "2026-03", "9.00"),
+            ],
+            months,
+        );
+
+        assert_eq!(pivot.row_labels, ["Ann Roe", "Sam Lee", "Sam Lee"]);
+        assert_eq!(pivot.row_totals[1], "0.20".parse().unwrap());
+        assert_eq!(pivot.row_totals[2], "0.30".parse().unwrap());
+        assert_eq!(pivot.column_totals[0], "0.30".parse().unwrap());
+        assert_eq!(pivot.grand_total, "5.50".parse().unwrap());
+    }
+}
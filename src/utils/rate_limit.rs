@@ -0,0 +1,136 @@
+use std::{
+    collections::HashMap,
+    time::{Duration, Instant},
+};
+
+// Buckets kept before idle ones are dropped
+const MAX_BUCKETS: usize = 10_000;
+
+// Left after the least recently used buckets are evicted, so a flood of new
+// callers doesn't scan the map on every request
+const BUCKETS_AFTER_EVICTION: usize = MAX_BUCKETS * 9 / 10;
+
+// Requests a caller has left, as of `updated`
+#[derive(Clone, Copy)]
+struct TokenBucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn is_full(&self, per_minute: u32, now: Instant) -> bool {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens + elapsed * per_minute as f64 / 60.0 >= per_minute as f64
+    }
+
+    fn refill(&mut self, per_minute: u32, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * per_minute as f64 / 60.0).min(per_minute as f64);
+        self.updated = now;
+    }
+}
+
+// A limit of `per_minute` requests, refilled continuously and allowing up to
+// a minute's worth at once, with a bucket per caller (an IP address, a user,
+// an API key)
+pub struct RateLimiter {
+    per_minute: u32,
+    buckets: HashMap<String, TokenBucket>,
+}
+
+impl RateLimiter {
+    // 0 turns the limit off
+    pub fn new(per_minute: u32) -> Self {
+        Self {
+            per_minute,
+            buckets: HashMap::new(),
+        }
+    }
+
+    // Takes a request from the caller's allowance, or says how long until
+    // the next one is allowed
+    pub fn take(&mut self, key: &str, now: Instant) -> Result<(), Duration> {
+        if self.per_minute == 0 {
+            return Ok(());
+        }
+        if self.buckets.len() >= MAX_BUCKETS && !self.buckets.contains_key(key) {
+            self.prune(now);
+        }
+
+        let per_minute = self.per_minute;
+        let bucket = self.buckets.entry(key.to_string()).or_insert(TokenBucket {
+            tokens: per_minute as f64,
+            updated: now,
+        });
+        bucket.refill(per_minute, now);
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            Err(Duration::from_secs_f64((1.0 - bucket.tokens) * 60.0 / per_minute as f64))
+        }
+    }
+
+    // A full bucket is the same as no bucket, so those go first. If callers
+    // are still using more than the cap, the ones idle longest go too.
+    fn prune(&mut self, now: Instant) {
+        let per_minute = self.per_minute;
+        self.buckets.retain(|_, bucket| !bucket.is_full(per_minute, now));
+
+        if self.buckets.len() >= MAX_BUCKETS {
+            let mut last_used: Vec<(Instant, String)> =
+                self.buckets.iter().map(|(key, bucket)| (bucket.updated, key.clone())).collect();
+            let evicted = self.buckets.len() - BUCKETS_AFTER_EVICTION;
+            last_used.select_nth_unstable_by_key(evicted, |(updated, _)| *updated);
+            for (_, key) in &last_used[..evicted] {
+                self.buckets.remove(key);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn allows_a_burst_then_refills_over_time() {
+        let mut limiter = RateLimiter::new(3);
+        let start = Instant::now();
+        for _ in 0..3 {
+            assert!(limiter.take("1.2.3.4", start).is_ok());
+        }
+        let wait = limiter.take("1.2.3.4", start).unwrap_err();
+        assert_eq!(wait.as_secs(), 20);
+
+        // Other callers have their own allowance
+        assert!(limiter.take("5.6.7.8", start).is_ok());
+
+        assert!(limiter.take("1.2.3.4", start + Duration::from_secs(20)).is_ok());
+        assert!(limiter.take("1.2.3.4", start + Duration::from_secs(20)).is_err());
+    }
+
+    #[test]
+    fn stays_bounded_when_callers_rotate() {
+        let mut limiter = RateLimiter::new(3);
+        let start = Instant::now();
+        for n in 0..MAX_BUCKETS * 3 {
+            // Half of them at the same moment, as in a burst
+            let now = start + Duration::from_millis((n / 2) as u64);
+            assert!(limiter.take(&n.to_string(), now).is_ok());
+            assert!(limiter.buckets.len() <= MAX_BUCKETS);
+        }
+
+        // The most recent callers keep what they've used
+        let last = (MAX_BUCKETS * 3 - 1).to_string();
+        assert!(limiter.buckets.contains_key(&last));
+    }
+
+    #[test]
+    fn zero_is_unlimited() {
+        let mut limiter = RateLimiter::new(0);
+        let now = Instant::now();
+        assert!((0..1000).all(|_| limiter.take("1.2.3.4", now).is_ok()));
+    }
+}
@@ -0,0 +1,89 @@
+use axum::http::HeaderMap;
+use std::{env, net::{IpAddr, SocketAddr}};
+
+// How many proxies in front of the app append to X-Forwarded-For:
+// TRUST_FORWARDED_FOR=true (or 1) for one, a larger number for a chain.
+// Unset means the header is ignored, as any client could claim an address.
+fn trusted_hops() -> usize {
+    match env::var("TRUST_FORWARDED_FOR").as_deref() {
+        Ok("true") => 1,
+        Ok(value) => value.parse().unwrap_or(0),
+        Err(_) => 0,
+    }
+}
+
+// The address the outermost trusted proxy saw. Each proxy appends the peer
+// it got the request from, so entries left of that are whatever the client
+// sent and can't be believed.
+fn forwarded_client(header: &str, hops: usize) -> Option<IpAddr> {
+    let entries: Vec<&str> = header.split(',').map(str::trim).collect();
+    let index = entries.len().checked_sub(hops)?;
+    entries[index].parse().ok()
+}
+
+// The caller's address, from X-Forwarded-For only when the app sits behind
+// trusted proxies
+pub fn client_ip(headers: &HeaderMap, peer: Option<SocketAddr>) -> Option<IpAddr> {
+    let hops = trusted_hops();
+
+    if hops > 0 {
+        let forwarded = headers
+            .get("x-forwarded-for")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| forwarded_client(value, hops));
+        if forwarded.is_some() {
+            return forwarded;
+        }
+    }
+
+    peer.map(|addr| addr.ip())
+}
+
+// Comma or whitespace separated addresses and ranges, normalized for a CIDR[] column
+pub fn parse_cidr_list(input: &str) -> Result<Vec<String>, String> {
+    input
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|entry| !entry.is_empty())
+        .map(|entry| {
+            normalize_cidr(entry).ok_or_else(|| format!("\"{}\" is not an IP address or CIDR range.", entry))
+        })
+        .collect()
+}
+
+// Accepts "10.0.0.5", "10.0.0.0/24" or IPv6 equivalents. Host bits set under
+// the mask are rejected, as Postgres refuses them for CIDR values.
+fn normalize_cidr(entry: &str) -> Option<String> {
+    let (addr, prefix) = match entry.split_once('/') {
+        Some((addr, prefix)) => (addr.parse::<IpAddr>().ok()?, Some(prefix.parse::<u8>().ok()?)),
+        None => (entry.parse::<IpAddr>().ok()?, None),
+    };
+
+    let (bits, max) = match addr {
+        IpAddr::V4(v4) => (u32::from(v4) as u128, 32),
+        IpAddr::V6(v6) => (u128::from(v6), 128),
+    };
+    let prefix = prefix.unwrap_or(max);
+    if prefix > max {
+        return None;
+    }
+    let host_bits = u32::from(max - prefix);
+    if host_bits > 0 && bits & ((1u128 << host_bits) - 1) != 0 {
+        return None;
+    }
+
+    Some(format!("{}/{}", addr, prefix))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ignores_forged_forwarded_entries() {
+        let header = "6.6.6.6, 203.0.113.7";
+        assert_eq!(forwarded_client(header, 1), "203.0.113.7".parse().ok());
+        assert_eq!(forwarded_client("6.6.6.6, 203.0.113.7, 10.0.0.2", 2), "203.0.113.7".parse().ok());
+        // Fewer entries than proxies means the header didn't come through them
+        assert_eq!(forwarded_client("203.0.113.7", 2), None);
+    }
+}
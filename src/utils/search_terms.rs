@@ -0,0 +1,36 @@
+// Most words taken from one search, so a pasted paragraph can't build a
+// huge query
+const MAX_TERMS: usize = 8;
+
+// Turns what someone typed into a to_tsquery() argument matching records with
+// every word, each as a prefix so results show up while a word is still being
+// typed. Characters that mean something to to_tsquery are dropped; None when
+// nothing searchable is left.
+pub fn prefix_query(input: &str) -> Option<String> {
+    let terms: Vec<String> = input
+        .split_whitespace()
+        .map(|word| {
+            word.chars()
+                .filter(|c| c.is_alphanumeric() || "@.-_".contains(*c))
+                .collect::<String>()
+        })
+        .map(|word| word.trim_matches(|c: char| !c.is_alphanumeric()).to_string())
+        .filter(|word| !word.is_empty())
+        .take(MAX_TERMS)
+        .map(|word| format!("{}:*", word))
+        .collect();
+    (!terms.is_empty()).then(|| terms.join(" & "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn builds_a_prefix_query() {
+        assert_eq!(prefix_query("  acme   wid"), Some("acme:* & wid:*".to_string()));
+        assert_eq!(prefix_query("o'brien & (smith)!"), Some("obrien:* & smith:*".to_string()));
+        assert_eq!(prefix_query("bob@example.com."), Some("bob@example.com:*".to_string()));
+        assert_eq!(prefix_query(" ! & ' "), None);
+    }
+}
@@ -0,0 +1,121 @@
+use chrono::{DateTime, Duration, LocalResult, NaiveDateTime, Offset, TimeZone, Utc};
+use chrono_tz::Tz;
+
+// Value format of <input type="datetime-local">
+pub const DATETIME_LOCAL_FORMAT: &str = "%Y-%m-%dT%H:%M";
+
+// A stored preference, falling back to UTC for anything unrecognized
+pub fn parse_timezone(name: &str) -> Tz {
+    name.parse().unwrap_or(Tz::UTC)
+}
+
+// Wall-clock time in the user's zone to the instant it names. Times skipped
+// when clocks go forward are moved on by the gap (02:30 becomes 03:30), and
+// times that happen twice when clocks go back take the first occurrence.
+pub fn from_local(naive: NaiveDateTime, tz: Tz) -> DateTime<Utc> {
+    match tz.from_local_datetime(&naive) {
+        LocalResult::Single(local) => local.with_timezone(&Utc),
+        LocalResult::Ambiguous(earliest, _) => earliest.with_timezone(&Utc),
+        LocalResult::None => {
+            // Read it with the offset in force before the transition
+            let before = tz
+                .from_local_datetime(&(naive - Duration::days(1)))
+                .earliest()
+                .map(|local| local.offset().fix().local_minus_utc())
+                .unwrap_or(0);
+            Utc.from_utc_datetime(&(naive - Duration::seconds(before as i64)))
+        }
+    }
+}
+
+// Parse a datetime-local form value entered in the user's zone
+pub fn parse_local_input(input: &str, tz: Tz) -> Option<DateTime<Utc>> {
+    NaiveDateTime::parse_from_str(input.trim(), DATETIME_LOCAL_FORMAT)
+        .ok()
+        .map(|naive| from_local(naive, tz))
+}
+
+// A stored instant as a datetime-local form value in the user's zone
+pub fn to_local_input(at: DateTime<Utc>, tz: Tz) -> String {
+    at.with_timezone(&tz).format(DATETIME_LOCAL_FORMAT).to_string()
+}
+
+pub fn format_local(at: DateTime<Utc>, tz: Tz, format: &str) -> String {
+    at.with_timezone(&tz).format(format).to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(input: &str) -> DateTime<Utc> {
+        NaiveDateTime::parse_from_str(input, DATETIME_LOCAL_FORMAT).unwrap().and_utc()
+    }
+
+    #[test]
+    fn converts_standard_and_daylight_time() {
+        let tz: Tz = "America/New_York".parse().unwrap();
+        assert_eq!(parse_local_input("2025-01-15T09:00", tz), Some(utc("2025-01-15T14:00")));
+        assert_eq!(parse_local_input("2025-07-01T09:00", tz), Some(utc("2025-07-01T13:00")));
+    }
+
+    #[test]
+    fn either_side_of_spring_forward() {
+        // New York skips 02:00-03:00 on 9 March 2025
+        let tz: Tz = "America/New_York".parse().unwrap();
+        assert_eq!(parse_local_input("2025-03-09T01:59", tz), Some(utc("2025-03-09T06:59")));
+        assert_eq!(parse_local_input("2025-03-09T03:00", tz), Some(utc("2025-03-09T07:00")));
+    }
+
+    #[test]
+    fn skipped_time_moves_forward_by_the_gap() {
+        let tz: Tz = "America/New_York".parse().unwrap();
+        let at = parse_local_input("2025-03-09T02:30", tz).unwrap();
+        assert_eq!(at, utc("2025-03-09T07:30"));
+        assert_eq!(to_local_input(at, tz), "2025-03-09T03:30");
+
+        let london: Tz = "Europe/London".parse().unwrap();
+        let at = parse_local_input("2025-03-30T01:30", london).unwrap();
+        assert_eq!(to_local_input(at, london), "2025-03-30T02:30");
+
+        // Southern hemisphere clocks go forward in October
+        let sydney: Tz = "Australia/Sydney".parse().unwrap();
+        let at = parse_local_input("2025-10-05T02:30", sydney).unwrap();
+        assert_eq!(at, utc("2025-10-04T16:30"));
+        assert_eq!(to_local_input(at, sydney), "2025-10-05T03:30");
+    }
+
+    #[test]
+    fn repeated_time_takes_the_first_occurrence() {
+        // New York repeats 01:00-02:00 on 2 November 2025
+        let tz: Tz = "America/New_York".parse().unwrap();
+        assert_eq!(parse_local_input("2025-11-02T01:30", tz), Some(utc("2025-11-02T05:30")));
+        assert_eq!(parse_local_input("2025-11-02T02:30", tz), Some(utc("2025-11-02T07:30")));
+
+        let berlin: Tz = "Europe/Berlin".parse().unwrap();
+        assert_eq!(parse_local_input("2025-10-26T02:30", berlin), Some(utc("2025-10-26T00:30")));
+    }
+
+    #[test]
+    fn round_trips_through_form_values() {
+        let tz: Tz = "Europe/Berlin".parse().unwrap();
+        for input in ["2025-03-29T23:15", "2025-03-30T03:00", "2025-06-21T12:00", "2025-10-26T03:00", "2025-12-31T23:59"] {
+            let at = parse_local_input(input, tz).unwrap();
+            assert_eq!(to_local_input(at, tz), input);
+        }
+    }
+
+    #[test]
+    fn formats_in_the_users_zone() {
+        let tz: Tz = "Asia/Kolkata".parse().unwrap();
+        assert_eq!(format_local(utc("2025-03-09T20:00"), tz, "%Y-%m-%d %H:%M"), "2025-03-10 01:30");
+    }
+
+    #[test]
+    fn unknown_or_malformed_input() {
+        assert_eq!(parse_timezone("Mars/Olympus_Mons"), Tz::UTC);
+        assert_eq!(parse_timezone("Europe/Paris"), chrono_tz::Europe::Paris);
+        assert_eq!(parse_local_input("2025-13-01T09:00", Tz::UTC), None);
+        assert_eq!(parse_local_input("next tuesday", Tz::UTC), None);
+    }
+}
@@ -0,0 +1,233 @@
+use axum::{
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, NaiveDate, Utc};
+use rust_decimal::{prelude::ToPrimitive, Decimal};
+use rust_xlsxwriter::{DocProperties, Format, FormatAlign, Workbook, XlsxError};
+
+// How a column's cells are typed and formatted in the spreadsheet
+#[derive(Clone, Copy)]
+pub enum ColumnType {
+    Text,
+    Integer,
+    // Left as entered, decimals and all
+    Number,
+    Currency,
+    Percent,
+    Date,
+    DateTime,
+}
+
+pub enum Cell {
+    Text(String),
+    Number(f64),
+    Date(NaiveDate),
+    DateTime(DateTime<Utc>),
+    Empty,
+}
+
+impl From<String> for Cell {
+    fn from(value: String) -> Self {
+        Cell::Text(value)
+    }
+}
+
+impl From<&str> for Cell {
+    fn from(value: &str) -> Self {
+        Cell::Text(value.to_string())
+    }
+}
+
+impl From<i64> for Cell {
+    fn from(value: i64) -> Self {
+        Cell::Number(value as f64)
+    }
+}
+
+impl From<f64> for Cell {
+    fn from(value: f64) -> Self {
+        Cell::Number(value)
+    }
+}
+
+impl From<Decimal> for Cell {
+    fn from(value: Decimal) -> Self {
+        value.to_f64().map(Cell::Number).unwrap_or(Cell::Empty)
+    }
+}
+
+impl From<NaiveDate> for Cell {
+    fn from(value: NaiveDate) -> Self {
+        Cell::Date(value)
+    }
+}
+
+impl From<DateTime<Utc>> for Cell {
+    fn from(value: DateTime<Utc>) -> Self {
+        Cell::DateTime(value)
+    }
+}
+
+impl<T: Into<Cell>> From<Option<T>> for Cell {
+    fn from(value: Option<T>) -> Self {
+        value.map(Into::into).unwrap_or(Cell::Empty)
+    }
+}
+
+// Excel caps each page header and footer at 255 characters
+const HEADER_FOOTER_MAX: usize = 200;
+
+// Literal text in a header or footer; '&' starts a control code
+fn header_text(value: &str) -> String {
+    value.chars().take(HEADER_FOOTER_MAX).collect::<String>().replace('&', "&&")
+}
+
+// A single data sheet plus the filters it was produced with. The filters are
+// written to a second "Filters" sheet so the export documents itself, and the
+// exporting user, time and filters are stamped on every printed page and in
+// the document properties so a copy can be traced back.
+pub struct XlsxExport {
+    title: String,
+    columns: Vec<(String, ColumnType)>,
+    rows: Vec<Vec<Cell>>,
+    filters: Vec<(String, String)>,
+}
+
+impl XlsxExport {
+    pub fn new(title: &str, columns: &[(&str, ColumnType)]) -> Self {
+        Self {
+            title: title.to_string(),
+            columns: columns.iter().map(|(name, kind)| (name.to_string(), *kind)).collect(),
+            rows: Vec::new(),
+            filters: Vec::new(),
+        }
+    }
+
+    pub fn filter(&mut self, name: &str, value: impl Into<String>) -> &mut Self {
+        let value = value.into();
+        self.filters.push((name.to_string(), if value.is_empty() { "All".to_string() } else { value }));
+        self
+    }
+
+    pub fn row(&mut self, cells: Vec<Cell>) -> &mut Self {
+        self.rows.push(cells);
+        self
+    }
+
+    pub fn to_bytes(&self, generated_by: &str) -> Result<Vec<u8>, XlsxError> {
+        let mut workbook = Workbook::new();
+        let generated_at = Utc::now();
+        let stamp = format!("Exported by {} on {} UTC", generated_by, generated_at.format("%Y-%m-%d %H:%M"));
+        let criteria = if self.filters.is_empty() {
+            "Filters: none".to_string()
+        } else {
+            format!(
+                "Filters: {}",
+                self.filters.iter().map(|(name, value)| format!("{} = {}", name, value)).collect::<Vec<_>>().join("; ")
+            )
+        };
+
+        workbook.set_properties(
+            &DocProperties::new()
+                .set_title(&self.title)
+                .set_author(generated_by)
+                .set_comment(format!("{}. {}", stamp, criteria)),
+        );
+
+        let header = Format::new().set_bold().set_background_color("#E0E7FF");
+        let integer = Format::new().set_num_format("#,##0");
+        let number = Format::new().set_num_format("General");
+        let currency = Format::new().set_num_format("#,##0.00");
+        let percent = Format::new().set_num_format("0%");
+        let date = Format::new().set_num_format("yyyy-mm-dd").set_align(FormatAlign::Left);
+        let datetime = Format::new().set_num_format("yyyy-mm-dd hh:mm").set_align(FormatAlign::Left);
+
+        let sheet = workbook.add_worksheet();
+        // Excel limits sheet names to 31 characters
+        sheet.set_name(self.title.chars().take(31).collect::<String>())?;
+        sheet.set_header(format!("&L{}&R{}", header_text(&self.title), header_text(&stamp)));
+        sheet.set_footer(format!("&L{}&RPage &P of &N", header_text(&criteria)));
+
+        for (col, (name, _)) in self.columns.iter().enumerate() {
+            sheet.write_string_with_format(0, col as u16, name, &header)?;
+        }
+
+        for (index, cells) in self.rows.iter().enumerate() {
+            let row = index as u32 + 1;
+            for (col, cell) in cells.iter().enumerate() {
+                let col_num = col as u16;
+                let kind = self.columns.get(col).map(|(_, kind)| *kind).unwrap_or(ColumnType::Text);
+                match cell {
+                    Cell::Text(value) => {
+                        sheet.write_string(row, col_num, value)?;
+                    }
+                    Cell::Number(value) => {
+                        let format = match kind {
+                            ColumnType::Currency => &currency,
+                            ColumnType::Percent => &percent,
+                            ColumnType::Number => &number,
+                            _ => &integer,
+                        };
+                        sheet.write_number_with_format(row, col_num, *value, format)?;
+                    }
+                    Cell::Date(value) => {
+                        sheet.write_date_with_format(row, col_num, value, &date)?;
+                    }
+                    Cell::DateTime(value) => {
+                        sheet.write_datetime_with_format(row, col_num, value.naive_utc(), &datetime)?;
+                    }
+                    Cell::Empty => {}
+                }
+            }
+        }
+
+        sheet.set_freeze_panes(1, 0)?;
+        if !self.columns.is_empty() {
+            sheet.autofilter(0, 0, self.rows.len() as u32, self.columns.len() as u16 - 1)?;
+        }
+        sheet.autofit();
+
+        let summary = workbook.add_worksheet();
+        summary.set_name("Filters")?;
+        summary.write_string_with_format(0, 0, "Report", &header)?;
+        summary.write_string(0, 1, &self.title)?;
+        summary.write_string_with_format(1, 0, "Generated at", &header)?;
+        summary.write_datetime_with_format(1, 1, generated_at.naive_utc(), &datetime)?;
+        summary.write_string_with_format(2, 0, "Generated by", &header)?;
+        summary.write_string(2, 1, generated_by)?;
+        summary.write_string_with_format(3, 0, "Rows", &header)?;
+        summary.write_number(3, 1, self.rows.len() as f64)?;
+
+        if self.filters.is_empty() {
+            summary.write_string_with_format(5, 0, "Filters", &header)?;
+            summary.write_string(5, 1, "None")?;
+        }
+        for (index, (name, value)) in self.filters.iter().enumerate() {
+            let row = index as u32 + 5;
+            summary.write_string_with_format(row, 0, name, &header)?;
+            summary.write_string(row, 1, value)?;
+        }
+        summary.autofit();
+
+        workbook.save_to_buffer()
+    }
+
+    // Render the workbook as a download response
+    pub fn into_response(self, filename: &str, generated_by: &str) -> Response {
+        match self.to_bytes(generated_by) {
+            Ok(bytes) => (
+                [
+                    (header::CONTENT_TYPE, "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet".to_string()),
+                    (header::CONTENT_DISPOSITION, format!("attachment; filename=\"{}\"", filename)),
+                ],
+                bytes,
+            )
+                .into_response(),
+            Err(e) => {
+                tracing::error!("Failed to build {}: {}", filename, e);
+                StatusCode::INTERNAL_SERVER_ERROR.into_response()
+            }
+        }
+    }
+}